@@ -0,0 +1,89 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Result;
+use tracing::{info, warn};
+
+use crate::{
+    parse_error::{self, AccountViolation},
+    web::{SystemFrame, WS_SYSTEM_CHANNEL, WsHub},
+};
+
+/// Alert when an account is missing this many times a minute, unless configured
+pub const DEFAULT_ACCOUNT_VIOLATION_ALERT_PER_MIN: u64 = 10;
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The violations counted at least `threshold` times more in `now` than in `last`
+fn spikes(
+    last: &HashMap<AccountViolation, u64>,
+    now: &HashMap<AccountViolation, u64>,
+    threshold: u64,
+) -> Vec<(AccountViolation, u64)> {
+    let mut spikes: Vec<_> = now
+        .iter()
+        .map(|(violation, count)| {
+            let last = last.get(violation).copied().unwrap_or_default();
+            (*violation, count.saturating_sub(last))
+        })
+        .filter(|(_, count)| *count >= threshold)
+        .collect();
+    spikes.sort_by_key(|it| Reverse(it.1));
+    spikes
+}
+
+/// Warn and alert the `system` channel when the instruction accounts expected by an event
+/// go missing often, e.g. after a program changed its account layout.
+pub async fn start(hub: Arc<WsHub>, threshold: u64) -> Result<()> {
+    info!("start account violation watcher, alert at {threshold} per minute........");
+    let mut last = parse_error::account_violations();
+    let mut alerting = HashSet::new();
+    loop {
+        tokio::time::sleep(CHECK_INTERVAL).await;
+        let now = parse_error::account_violations();
+        let spikes = spikes(&last, &now, threshold);
+        for (violation, count) in &spikes {
+            let AccountViolation { dex, event, idx } = *violation;
+            warn!("{dex} {event} missed the instruction account #{idx} {count} times in a minute");
+            if !alerting.contains(violation) {
+                hub.publish(
+                    WS_SYSTEM_CHANNEL,
+                    &SystemFrame::AccountViolationAlert {
+                        dex,
+                        event,
+                        idx,
+                        count: *count,
+                    },
+                )?;
+            }
+        }
+        alerting = spikes.into_iter().map(|(it, _)| it).collect();
+        last = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::Dex;
+
+    use super::*;
+
+    #[test]
+    fn test_spikes() {
+        let violation = |idx| AccountViolation {
+            dex: Dex::RaydiumAmm,
+            event: "swap_base_in",
+            idx,
+        };
+        let last = HashMap::from([(violation(1), 100), (violation(4), 3)]);
+        let now = HashMap::from([(violation(1), 105), (violation(4), 20), (violation(17), 12)]);
+        assert_eq!(
+            spikes(&last, &now, 10),
+            vec![(violation(4), 17), (violation(17), 12)]
+        );
+        assert!(spikes(&now, &now, 1).is_empty());
+    }
+}
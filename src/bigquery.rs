@@ -0,0 +1,529 @@
+//! Export of the trades and created pools to bigquery, from the queues of the `bigquery` consumer.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, anyhow};
+use chrono::Utc;
+use reqwest::{StatusCode, header};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tracing::{info, warn};
+
+use crate::{
+    cache::{
+        self, BigQueryCursor, ConsumerCounter, DexEvent, DexEventKind, DexPoolCreatedRecord,
+        TradeRecord,
+    },
+    config::BigQueryConfig,
+};
+
+/// Name of the consumer whose queues are exported
+pub const BIGQUERY_CONSUMER: &str = "bigquery";
+
+/// Kinds of the events exported, a table each
+pub const BIGQUERY_KINDS: [DexEventKind; 2] = [DexEventKind::Trade, DexEventKind::PoolCreated];
+
+const BIGQUERY_API: &str = "https://bigquery.googleapis.com/bigquery/v2";
+/// access token of the service account of the GCE or GKE instance
+const METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+/// `(name, type)` of the columns of the trades table, all nullable so columns can be added
+const TRADE_FIELDS: &[(&str, &str)] = &[
+    ("blk_ts", "TIMESTAMP"),
+    ("slot", "INTEGER"),
+    ("txid", "STRING"),
+    ("idx", "INTEGER"),
+    ("dex", "STRING"),
+    ("pool", "STRING"),
+    ("mint", "STRING"),
+    ("decimals", "INTEGER"),
+    ("trader", "STRING"),
+    ("is_buy", "BOOLEAN"),
+    ("sol_amt", "INTEGER"),
+    ("token_amt", "INTEGER"),
+    ("pool_sol_amt", "INTEGER"),
+    ("pool_token_amt", "INTEGER"),
+    ("pool_age_secs", "INTEGER"),
+    ("pool_trade_seq", "INTEGER"),
+    ("trader_sol_delta", "INTEGER"),
+    ("trader_token_delta", "INTEGER"),
+    ("price_sol", "FLOAT"),
+    ("price_sol_decimal", "STRING"),
+    ("quote_mint", "STRING"),
+    ("price_quote", "FLOAT"),
+    ("quote_amt", "INTEGER"),
+    ("pool_quote_amt", "INTEGER"),
+    ("price_usd", "FLOAT"),
+    ("sol_price_usd_at_trade", "FLOAT"),
+    ("price_outlier", "BOOLEAN"),
+    ("stream_id", "STRING"),
+];
+
+const POOL_FIELDS: &[(&str, &str)] = &[
+    ("blk_ts", "TIMESTAMP"),
+    ("slot", "INTEGER"),
+    ("txid", "STRING"),
+    ("idx", "INTEGER"),
+    ("dex", "STRING"),
+    ("addr", "STRING"),
+    ("creator", "STRING"),
+    ("mint_a", "STRING"),
+    ("mint_b", "STRING"),
+    ("decimals_a", "INTEGER"),
+    ("decimals_b", "INTEGER"),
+    ("init_sol_amt", "INTEGER"),
+    ("init_token_amt", "INTEGER"),
+    ("init_price_sol", "FLOAT"),
+    ("creator_funded_by", "STRING"),
+    ("stream_id", "STRING"),
+];
+
+fn fields(kind: DexEventKind) -> &'static [(&'static str, &'static str)] {
+    match kind {
+        DexEventKind::PoolCreated => POOL_FIELDS,
+        _ => TRADE_FIELDS,
+    }
+}
+
+fn trade_row(trade: &TradeRecord) -> Value {
+    json!({
+        "blk_ts": trade.blk_ts.to_rfc3339(),
+        "slot": trade.slot,
+        "txid": trade.txid,
+        "idx": trade.idx,
+        "dex": trade.dex.to_string(),
+        "pool": trade.pool.to_string(),
+        "mint": trade.mint.to_string(),
+        "decimals": trade.decimals,
+        "trader": trade.trader.to_string(),
+        "is_buy": trade.is_buy,
+        "sol_amt": trade.sol_amt,
+        "token_amt": trade.token_amt,
+        "pool_sol_amt": trade.pool_sol_amt,
+        "pool_token_amt": trade.pool_token_amt,
+        "pool_age_secs": trade.pool_age_secs,
+        "pool_trade_seq": trade.pool_trade_seq,
+        "trader_sol_delta": trade.trader_sol_delta,
+        "trader_token_delta": trade.trader_token_delta,
+        "price_sol": trade.price_sol,
+        "price_sol_decimal": trade.price_sol_decimal,
+        "quote_mint": trade.quote_mint.to_string(),
+        "price_quote": trade.price_quote,
+        "quote_amt": trade.quote_amt,
+        "pool_quote_amt": trade.pool_quote_amt,
+        "price_usd": trade.price_usd,
+        "sol_price_usd_at_trade": trade.sol_price_usd_at_trade,
+        "price_outlier": trade.price_outlier,
+        "stream_id": trade.source.as_ref().map(|it| &it.stream_id),
+    })
+}
+
+fn pool_row(pool: &DexPoolCreatedRecord) -> Value {
+    let liquidity = pool.init_liquidity.as_ref();
+    json!({
+        "blk_ts": pool.blk_ts.to_rfc3339(),
+        "slot": pool.slot,
+        "txid": pool.txid,
+        "idx": pool.idx,
+        "dex": pool.dex.to_string(),
+        "addr": pool.addr.to_string(),
+        "creator": pool.creator.to_string(),
+        "mint_a": pool.mint_a.to_string(),
+        "mint_b": pool.mint_b.to_string(),
+        "decimals_a": pool.decimals_a,
+        "decimals_b": pool.decimals_b,
+        "init_sol_amt": liquidity.map(|it| it.sol_amt),
+        "init_token_amt": liquidity.map(|it| it.token_amt),
+        "init_price_sol": liquidity.map(|it| it.price_sol),
+        "creator_funded_by": pool.creator_funded_by.map(|it| it.to_string()),
+        "stream_id": pool.source.as_ref().map(|it| &it.stream_id),
+    })
+}
+
+/// `insertAll` row of the event, with `{txid}:{idx}` as the insert id bigquery dedups on.
+/// None for the kinds which aren't exported.
+fn insert_row(evt: &DexEvent) -> Option<Value> {
+    let row = match evt {
+        DexEvent::Trade(trade) => trade_row(trade),
+        DexEvent::PoolCreated(pool) => pool_row(pool),
+        _ => return None,
+    };
+    Some(json!({
+        "insertId": format!("{}:{}", evt.txid(), evt.idx()),
+        "json": row,
+    }))
+}
+
+/// Rows rejected as invalid by `insertAll`, an error when others failed, as the batch is to be
+/// retried then
+fn rejected_rows(body: &Value) -> Result<usize> {
+    let Some(errors) = body["insertErrors"].as_array() else {
+        return Ok(0);
+    };
+    let retried = errors.iter().find(|row| {
+        !row["errors"]
+            .as_array()
+            .is_some_and(|it| it.iter().all(|err| err["reason"] == "invalid"))
+    });
+    if let Some(row) = retried {
+        return Err(anyhow!(
+            "bigquery failed to insert {} rows, first: {row}",
+            errors.len()
+        ));
+    }
+    Ok(errors.len())
+}
+
+fn field_schema(name: &str, field_type: &str) -> Value {
+    json!({"name": name, "type": field_type, "mode": "NULLABLE"})
+}
+
+/// The expected fields absent from the table, added to its schema on start
+fn missing_fields(table_fields: &[Value], expected: &[(&str, &str)]) -> Vec<Value> {
+    expected
+        .iter()
+        .filter(|(name, _)| {
+            !table_fields
+                .iter()
+                .any(|it| it["name"].as_str() == Some(*name))
+        })
+        .map(|(name, field_type)| field_schema(name, field_type))
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataToken {
+    access_token: String,
+    expires_in: u64,
+}
+
+/// Tables api of the configured dataset
+struct BigQueryClient {
+    http_client: reqwest::Client,
+    config: BigQueryConfig,
+    /// token from the metadata server and when it expires
+    token: Option<(String, Instant)>,
+}
+
+impl BigQueryClient {
+    fn new(config: BigQueryConfig) -> Result<Self> {
+        let http_client = reqwest::ClientBuilder::new()
+            .connect_timeout(Duration::from_secs(1))
+            .timeout(Duration::from_secs(30))
+            .build()?;
+        Ok(Self {
+            http_client,
+            config,
+            token: None,
+        })
+    }
+
+    fn tables_url(&self) -> String {
+        format!(
+            "{BIGQUERY_API}/projects/{}/datasets/{}/tables",
+            self.config.project_id, self.config.dataset
+        )
+    }
+
+    async fn access_token(&mut self) -> Result<String> {
+        if let Some(token) = &self.config.access_token {
+            return Ok(token.clone());
+        }
+        if let Some((token, expires_at)) = &self.token
+            && Instant::now() < *expires_at
+        {
+            return Ok(token.clone());
+        }
+
+        let token: MetadataToken = self
+            .http_client
+            .get(METADATA_TOKEN_URL)
+            .header("Metadata-Flavor", "Google")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        // renewed a minute early
+        let expires_at = Instant::now() + Duration::from_secs(token.expires_in.saturating_sub(60));
+        self.token = Some((token.access_token.clone(), expires_at));
+        Ok(token.access_token)
+    }
+
+    /// The response of the request, failing on an unsuccessful status
+    async fn request(
+        &mut self,
+        method: reqwest::Method,
+        url: &str,
+        body: Option<Value>,
+    ) -> Result<Value> {
+        let (status, body) = self.send(method, url, body).await?;
+        if !status.is_success() {
+            return Err(anyhow!("bigquery {url} responded {status}: {body}"));
+        }
+        Ok(body)
+    }
+
+    async fn send(
+        &mut self,
+        method: reqwest::Method,
+        url: &str,
+        body: Option<Value>,
+    ) -> Result<(StatusCode, Value)> {
+        let token = self.access_token().await?;
+        let mut req = self
+            .http_client
+            .request(method, url)
+            .header(header::AUTHORIZATION, format!("Bearer {token}"));
+        if let Some(body) = body {
+            req = req.json(&body);
+        }
+        let resp = req.send().await?;
+        let status = resp.status();
+        let body = resp.json().await.unwrap_or(Value::Null);
+        Ok((status, body))
+    }
+
+    /// Create the table day partitioned by `blk_ts`, or add the fields missing from its schema.
+    async fn ensure_table(&mut self, table: &str, expected: &[(&str, &str)]) -> Result<()> {
+        let url = format!("{}/{table}", self.tables_url());
+        let (status, body) = self.send(reqwest::Method::GET, &url, None).await?;
+        if !status.is_success() && status != StatusCode::NOT_FOUND {
+            return Err(anyhow!("bigquery {url} responded {status}: {body}"));
+        }
+        if status == StatusCode::NOT_FOUND {
+            let fields: Vec<_> = expected
+                .iter()
+                .map(|(name, field_type)| field_schema(name, field_type))
+                .collect();
+            let table_def = json!({
+                "tableReference": {
+                    "projectId": self.config.project_id,
+                    "datasetId": self.config.dataset,
+                    "tableId": table,
+                },
+                "schema": {"fields": fields},
+                "timePartitioning": {"type": "DAY", "field": "blk_ts"},
+            });
+            let url = self.tables_url();
+            self.request(reqwest::Method::POST, &url, Some(table_def))
+                .await?;
+            info!("bigquery table {table} created");
+            return Ok(());
+        }
+
+        let mut fields = body["schema"]["fields"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        let missing = missing_fields(&fields, expected);
+        if missing.is_empty() {
+            return Ok(());
+        }
+        info!("add fields {missing:?} to bigquery table {table}");
+        fields.extend(missing);
+        self.request(
+            reqwest::Method::PATCH,
+            &url,
+            Some(json!({"schema": {"fields": fields}})),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Insert the rows, skipping the invalid ones, which are logged and counted
+    async fn insert_rows(&mut self, table: &str, rows: Vec<Value>) -> Result<usize> {
+        let url = format!("{}/{table}/insertAll", self.tables_url());
+        let body = self
+            .request(
+                reqwest::Method::POST,
+                &url,
+                Some(json!({"rows": rows, "skipInvalidRows": true})),
+            )
+            .await?;
+        let rejected = rejected_rows(&body).map_err(|err| anyhow!("{table}: {err}"))?;
+        if rejected > 0 {
+            warn!(
+                "bigquery rejected {rejected} invalid rows of {table}, first: {}",
+                body["insertErrors"][0]
+            );
+        }
+        Ok(rejected)
+    }
+}
+
+/// Inserts the events of one kind queued for the `bigquery` consumer into its table.
+pub struct BigQuerySink {
+    pub redis_client: Arc<redis::Client>,
+    pub config: BigQueryConfig,
+    pub kind: DexEventKind,
+}
+
+impl BigQuerySink {
+    pub fn table(&self) -> &str {
+        match self.kind {
+            DexEventKind::PoolCreated => &self.config.pools_table,
+            _ => &self.config.trades_table,
+        }
+    }
+
+    pub async fn start(&self) -> Result<()> {
+        let table = self.table();
+        let mut client = BigQueryClient::new(self.config.clone())?;
+        client.ensure_table(table, fields(self.kind)).await?;
+        info!(
+            "start {} bigquery export to table {table}........",
+            self.kind
+        );
+
+        loop {
+            let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+            let batch = cache::claim_dex_evts(&mut conn, self.kind, Some(BIGQUERY_CONSUMER))
+                .await
+                .map_err(|err| anyhow!("claim dex events error: {err}"))?;
+            if batch.events.is_empty() {
+                drop(conn);
+                tokio::time::sleep(self.kind.poll_interval()).await;
+                continue;
+            }
+
+            let cursor = cache::get_bigquery_cursor(&mut conn, table).await?;
+            if cursor.as_ref().is_some_and(|it| it.batch_id == batch.id) {
+                warn!("batch {} already inserted into {table}, ack it", batch.id);
+                cache::ack_dex_evts(&mut conn, self.kind, Some(BIGQUERY_CONSUMER)).await?;
+                continue;
+            }
+
+            let rows: Vec<_> = batch.events.iter().filter_map(insert_row).collect();
+            let rows_len = rows.len() as u64;
+            let rejected = match client.insert_rows(table, rows).await {
+                Ok(rejected) => rejected as u64,
+                Err(err) => {
+                    cache::incr_consumer_counter(
+                        &mut conn,
+                        BIGQUERY_CONSUMER,
+                        ConsumerCounter::Failed,
+                        1,
+                    )
+                    .await?;
+                    return Err(err);
+                }
+            };
+            let rows_len = rows_len - rejected;
+
+            let max_slot = batch.events.iter().filter_map(DexEvent::slot).max();
+            let cursor = BigQueryCursor {
+                batch_id: batch.id,
+                max_slot: max_slot
+                    .max(cursor.as_ref().map(|it| it.max_slot))
+                    .unwrap_or_default(),
+                rows: cursor.map(|it| it.rows).unwrap_or_default() + rows_len,
+                updated_at: Utc::now(),
+            };
+            cache::set_bigquery_cursor(&mut conn, table, &cursor).await?;
+            cache::ack_dex_evts(&mut conn, self.kind, Some(BIGQUERY_CONSUMER)).await?;
+            cache::incr_consumer_counter(
+                &mut conn,
+                BIGQUERY_CONSUMER,
+                ConsumerCounter::Sent,
+                rows_len,
+            )
+            .await?;
+            if rejected > 0 {
+                cache::incr_consumer_counter(
+                    &mut conn,
+                    BIGQUERY_CONSUMER,
+                    ConsumerCounter::Dropped,
+                    rejected,
+                )
+                .await?;
+            }
+            info!(
+                "{rows_len} {} events inserted into {table}, up to slot {}",
+                self.kind, cursor.max_slot
+            );
+            tokio::time::sleep(self.kind.send_interval()).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use solana_sdk::pubkey::Pubkey;
+
+    use crate::{cache::PoolLiquidity, common::Dex};
+
+    use super::*;
+
+    #[test]
+    fn test_rows_match_schema() {
+        let pool = DexPoolCreatedRecord {
+            blk_ts: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            slot: 250_000_000,
+            txid: "txid".to_string(),
+            idx: 3,
+            creator: Pubkey::new_unique(),
+            addr: Pubkey::new_unique(),
+            dex: Dex::PumpAmm,
+            mint_a: Pubkey::new_unique(),
+            mint_b: Pubkey::new_unique(),
+            decimals_a: 6,
+            decimals_b: 9,
+            init_liquidity: Some(PoolLiquidity {
+                sol_amt: 100,
+                token_amt: 2000,
+                price_sol: 0.05,
+            }),
+            creator_funded_by: None,
+            source: None,
+        };
+        let row = insert_row(&DexEvent::PoolCreated(pool)).unwrap();
+        assert_eq!(row["insertId"], "txid:3");
+        assert_eq!(row["json"]["blk_ts"], "2023-11-14T22:13:20+00:00");
+        assert_eq!(row["json"]["init_sol_amt"], 100);
+        let columns = row["json"].as_object().unwrap();
+        assert_eq!(columns.len(), POOL_FIELDS.len());
+        assert!(
+            POOL_FIELDS
+                .iter()
+                .all(|(name, _)| columns.contains_key(*name))
+        );
+    }
+
+    #[test]
+    fn test_missing_fields() {
+        let table_fields = vec![
+            field_schema("blk_ts", "TIMESTAMP"),
+            json!({"name": "slot", "type": "INTEGER", "mode": "REQUIRED"}),
+        ];
+        let expected = [
+            ("blk_ts", "TIMESTAMP"),
+            ("slot", "INTEGER"),
+            ("txid", "STRING"),
+        ];
+        assert_eq!(
+            missing_fields(&table_fields, &expected),
+            vec![field_schema("txid", "STRING")]
+        );
+        assert!(missing_fields(&table_fields, &expected[..2]).is_empty());
+    }
+
+    #[test]
+    fn test_rejected_rows() {
+        assert_eq!(
+            rejected_rows(&json!({"kind": "bigquery#tableDataInsertAllResponse"})).unwrap(),
+            0
+        );
+        let invalid =
+            json!({"index": 1, "errors": [{"reason": "invalid", "message": "no such field"}]});
+        let body = json!({"insertErrors": [invalid.clone(), invalid.clone()]});
+        assert_eq!(rejected_rows(&body).unwrap(), 2);
+        let body = json!({"insertErrors": [invalid, {"index": 2, "errors": [{"reason": "backendError"}]}]});
+        assert!(rejected_rows(&body).is_err());
+    }
+}
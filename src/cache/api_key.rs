@@ -0,0 +1,113 @@
+use anyhow::Result;
+use redis::{AsyncCommands, Script, aio::MultiplexedConnection};
+use serde::{Deserialize, Serialize};
+
+use crate::web::{ApiQuota, ApiScope};
+
+/// hash of api key hash -> key record json
+const API_KEYS_KEY: &str = "hash:api_keys";
+/// hash of key name -> api key hash
+const API_KEY_NAMES_KEY: &str = "hash:api_key_names";
+
+/// Save record ARGV[2] under key hash ARGV[1] and name ARGV[3], unless the name is taken.
+const CREATE_KEY_SCRIPT: &str = r#"
+if redis.call('HSETNX', KEYS[2], ARGV[3], ARGV[1]) == 0 then
+    return 0
+end
+redis.call('HSET', KEYS[1], ARGV[1], ARGV[2])
+return 1
+"#;
+
+/// Move the record of name ARGV[1] to key hash ARGV[2], returns the record.
+const ROTATE_KEY_SCRIPT: &str = r#"
+local old = redis.call('HGET', KEYS[2], ARGV[1])
+if not old then
+    return false
+end
+local record = redis.call('HGET', KEYS[1], old)
+redis.call('HDEL', KEYS[1], old)
+redis.call('HSET', KEYS[1], ARGV[2], record)
+redis.call('HSET', KEYS[2], ARGV[1], ARGV[2])
+return record
+"#;
+
+/// Remove the key of name ARGV[1]
+const DELETE_KEY_SCRIPT: &str = r#"
+local old = redis.call('HGET', KEYS[2], ARGV[1])
+if not old then
+    return 0
+end
+redis.call('HDEL', KEYS[1], old)
+redis.call('HDEL', KEYS[2], ARGV[1])
+return 1
+"#;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyRecord {
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    #[serde(default)]
+    pub quota: ApiQuota,
+}
+
+pub async fn get_api_key(
+    conn: &mut MultiplexedConnection,
+    key_hash: &str,
+) -> Result<Option<ApiKeyRecord>> {
+    let record: Option<String> = conn.hget(API_KEYS_KEY, key_hash).await?;
+    let record = record.map(|it| serde_json::from_str(&it)).transpose()?;
+    Ok(record)
+}
+
+pub async fn list_api_keys(conn: &mut MultiplexedConnection) -> Result<Vec<ApiKeyRecord>> {
+    let records: Vec<String> = conn.hvals(API_KEYS_KEY).await?;
+    let records = records
+        .iter()
+        .map(|it| serde_json::from_str(it))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(records)
+}
+
+/// false if a key of the name exists
+pub async fn create_api_key(
+    conn: &mut MultiplexedConnection,
+    key_hash: &str,
+    record: &ApiKeyRecord,
+) -> Result<bool> {
+    let created: bool = Script::new(CREATE_KEY_SCRIPT)
+        .key(API_KEYS_KEY)
+        .key(API_KEY_NAMES_KEY)
+        .arg(key_hash)
+        .arg(serde_json::to_string(record)?)
+        .arg(&record.name)
+        .invoke_async(conn)
+        .await?;
+    Ok(created)
+}
+
+/// Replace the key of the name, the old key stops working. None if there is no key of the name.
+pub async fn rotate_api_key(
+    conn: &mut MultiplexedConnection,
+    name: &str,
+    new_key_hash: &str,
+) -> Result<Option<ApiKeyRecord>> {
+    let record: Option<String> = Script::new(ROTATE_KEY_SCRIPT)
+        .key(API_KEYS_KEY)
+        .key(API_KEY_NAMES_KEY)
+        .arg(name)
+        .arg(new_key_hash)
+        .invoke_async(conn)
+        .await?;
+    let record = record.map(|it| serde_json::from_str(&it)).transpose()?;
+    Ok(record)
+}
+
+pub async fn delete_api_key(conn: &mut MultiplexedConnection, name: &str) -> Result<bool> {
+    let deleted: bool = Script::new(DELETE_KEY_SCRIPT)
+        .key(API_KEYS_KEY)
+        .key(API_KEY_NAMES_KEY)
+        .arg(name)
+        .invoke_async(conn)
+        .await?;
+    Ok(deleted)
+}
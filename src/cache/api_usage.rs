@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::NaiveDate;
+use redis::{AsyncCommands, aio::MultiplexedConnection};
+use serde::{Deserialize, Serialize};
+
+/// Usage of the past days is kept this long
+const API_USAGE_TTL_SECS: i64 = 35 * 24 * 3600;
+
+/// hash of the usage of a UTC day, `{key name}:{counter}` fields
+fn api_usage_key(day: NaiveDate) -> String {
+    format!("hash:api_usage:{}", day.format("%Y-%m-%d"))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiUsage {
+    pub requests: u64,
+    pub ws_messages: u64,
+    /// response bodies and websocket messages
+    pub bytes: u64,
+}
+
+impl ApiUsage {
+    pub fn add(&mut self, other: &ApiUsage) {
+        self.requests += other.requests;
+        self.ws_messages += other.ws_messages;
+        self.bytes += other.bytes;
+    }
+}
+
+/// Add the usage of the keys to the day, returns the totals of the day
+pub async fn incr_api_usage(
+    conn: &mut MultiplexedConnection,
+    day: NaiveDate,
+    usage: &[(String, ApiUsage)],
+) -> Result<Vec<ApiUsage>> {
+    if usage.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let key = api_usage_key(day);
+    let mut pipe = redis::pipe();
+    for (name, usage) in usage {
+        pipe.hincr(&key, format!("{name}:requests"), usage.requests)
+            .hincr(&key, format!("{name}:ws_messages"), usage.ws_messages)
+            .hincr(&key, format!("{name}:bytes"), usage.bytes);
+    }
+    pipe.expire(&key, API_USAGE_TTL_SECS).ignore();
+    let totals: Vec<u64> = pipe.query_async(conn).await?;
+
+    let totals = totals
+        .chunks(3)
+        .map(|it| ApiUsage {
+            requests: it[0],
+            ws_messages: it[1],
+            bytes: it[2],
+        })
+        .collect();
+    Ok(totals)
+}
+
+pub async fn api_usage_on(
+    conn: &mut MultiplexedConnection,
+    day: NaiveDate,
+) -> Result<HashMap<String, ApiUsage>> {
+    let fields: HashMap<String, u64> = conn.hgetall(api_usage_key(day)).await?;
+    let mut usage: HashMap<String, ApiUsage> = HashMap::new();
+    for (field, value) in fields {
+        let Some((name, counter)) = field.rsplit_once(':') else {
+            continue;
+        };
+        let entry = usage.entry(name.to_string()).or_default();
+        match counter {
+            "requests" => entry.requests = value,
+            "ws_messages" => entry.ws_messages = value,
+            "bytes" => entry.bytes = value,
+            _ => {}
+        }
+    }
+    Ok(usage)
+}
@@ -0,0 +1,532 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redis::aio::MultiplexedConnection;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{common::utils, config::AvgPriceConfig};
+
+use super::{DexEvent, TradeRecord, queue};
+
+/// Trades are summed in slots of this many seconds, by block timestamp, the close of a slot
+/// is one twap sample
+pub const AVG_PRICE_SLOT_SECS: i64 = 60;
+
+/// hash of one slot of the trades of a mint, `s` lamports, `t` raw token amount, `n` trades,
+/// `c` price of the latest trade and `d` token decimals
+fn slot_key(mint: &Pubkey, slot_start: i64) -> String {
+    format!("hash:avg_price:{mint}:{slot_start}")
+}
+
+/// hash of the trades of a mint over a window ending at its latest traded slot, `s`, `t` and
+/// `n` summed over the slots of the window and `e` the start of the latest slot
+fn window_key(mint: &Pubkey, window_secs: i64) -> String {
+    format!("hash:avg_price_window:{mint}:{window_secs}")
+}
+
+/// sorted set of the traded slots of a mint as `{slot_start}:{close}`, scored by the slot start
+fn closes_key(mint: &Pubkey) -> String {
+    format!("zset:avg_price_closes:{mint}")
+}
+
+fn slot_start(ts: i64) -> i64 {
+    ts - ts.rem_euclid(AVG_PRICE_SLOT_SECS)
+}
+
+/// Slot starts of the `window_secs` before `now`, the current slot included
+fn window_slots(window_secs: i64, now: i64) -> impl Iterator<Item = i64> {
+    let last = slot_start(now);
+    let first = slot_start(now - window_secs) + AVG_PRICE_SLOT_SECS;
+    (first..=last).step_by(AVG_PRICE_SLOT_SECS as usize)
+}
+
+/// Volume and time weighted average price of a mint over the last `window_secs`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AvgPrice {
+    pub window_secs: i64,
+    pub trades: u64,
+    /// none without a trade in the window
+    pub vwap_sol: Option<f64>,
+    /// `vwap_sol` as an exact decimal string
+    pub vwap_sol_decimal: Option<String>,
+    /// mean of the closes of the minutes since the first traded one of the window, a minute
+    /// without trade keeps the close of the minute before
+    pub twap_sol: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct AvgPriceSlot {
+    sol_amt: u64,
+    token_amt: u64,
+    trades: u64,
+    close: Option<f64>,
+    decimals: Option<u8>,
+}
+
+impl AvgPriceSlot {
+    fn from_fields(fields: &HashMap<String, String>) -> Self {
+        let get = |field| fields.get(field).and_then(|it| it.parse().ok());
+        Self {
+            sol_amt: get("s").unwrap_or_default(),
+            token_amt: get("t").unwrap_or_default(),
+            trades: get("n").unwrap_or_default(),
+            close: fields.get("c").and_then(|it| it.parse().ok()),
+            decimals: fields.get("d").and_then(|it| it.parse().ok()),
+        }
+    }
+}
+
+/// Running sums of the slots of a window, slid forward as the mint trades
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct WindowSums {
+    sol_amt: u64,
+    token_amt: u64,
+    trades: u64,
+    /// start of the latest slot of the window, none before the first trade
+    end: Option<i64>,
+}
+
+impl WindowSums {
+    fn from_fields(fields: &HashMap<String, String>) -> Self {
+        let get = |field| fields.get(field).and_then(|it| it.parse().ok());
+        Self {
+            sol_amt: get("s").unwrap_or_default(),
+            token_amt: get("t").unwrap_or_default(),
+            trades: get("n").unwrap_or_default(),
+            end: fields.get("e").and_then(|it| it.parse().ok()),
+        }
+    }
+
+    /// Move the end of the window to the slot `end`, subtracting the traded `slots` which
+    /// leave the window
+    fn slide(&mut self, window_secs: i64, end: i64, slots: &HashMap<i64, AvgPriceSlot>) {
+        let Some(old_end) = self.end else {
+            self.end = Some(end);
+            return;
+        };
+        if end <= old_end {
+            return;
+        }
+        let start = end - window_secs + AVG_PRICE_SLOT_SECS;
+        if start > old_end {
+            *self = Self {
+                end: Some(end),
+                ..Default::default()
+            };
+            return;
+        }
+        let old_start = old_end - window_secs + AVG_PRICE_SLOT_SECS;
+        for (_, slot) in slots
+            .iter()
+            .filter(|(slot, _)| (old_start..start).contains(*slot))
+        {
+            self.sol_amt = self.sol_amt.saturating_sub(slot.sol_amt);
+            self.token_amt = self.token_amt.saturating_sub(slot.token_amt);
+            self.trades = self.trades.saturating_sub(slot.trades);
+        }
+        self.end = Some(end);
+    }
+
+    /// Add a trade of the slot, unless it's older than the window
+    fn add(&mut self, window_secs: i64, slot: i64, trade: &TradeRecord) {
+        let Some(end) = self.end else {
+            return;
+        };
+        if slot <= end - window_secs {
+            return;
+        }
+        self.sol_amt += trade.sol_amt;
+        self.token_amt += trade.token_amt;
+        self.trades += 1;
+    }
+
+    /// The slots which leave the window when it ends at `end`
+    fn leaving_slots(&self, window_secs: i64, end: i64) -> std::ops::Range<i64> {
+        match self.end {
+            Some(old_end) if end > old_end => {
+                (old_end - window_secs + AVG_PRICE_SLOT_SECS)
+                    ..(end - window_secs + AVG_PRICE_SLOT_SECS)
+            }
+            _ => 0..0,
+        }
+    }
+}
+
+impl AvgPrice {
+    /// The averages of a window by its running sums and the closes of the traded slots of
+    /// the mint
+    fn from_window(
+        window_secs: i64,
+        sums: &WindowSums,
+        decimals: u8,
+        closes: &HashMap<i64, f64>,
+    ) -> Self {
+        let slots: Vec<_> = sums
+            .end
+            .into_iter()
+            .flat_map(|end| window_slots(window_secs, end))
+            .map(|slot| AvgPriceSlot {
+                close: closes.get(&slot).copied(),
+                ..Default::default()
+            })
+            .collect();
+        let vwap_decimals = Some(decimals).filter(|_| sums.token_amt > 0);
+        Self {
+            window_secs,
+            trades: sums.trades,
+            vwap_sol: vwap_decimals
+                .map(|decimals| utils::calc_price_sol(sums.sol_amt, sums.token_amt, decimals)),
+            vwap_sol_decimal: vwap_decimals
+                .and_then(|decimals| vwap_decimal(sums.sol_amt, sums.token_amt, decimals)),
+            twap_sol: Self::from_slots(window_secs, &slots).twap_sol,
+        }
+    }
+
+    /// `slots` are the slots of the window, the oldest first
+    fn from_slots(window_secs: i64, slots: &[AvgPriceSlot]) -> Self {
+        let sol_amt: u64 = slots.iter().map(|it| it.sol_amt).sum();
+        let token_amt: u64 = slots.iter().map(|it| it.token_amt).sum();
+        let vwap_decimals = slots
+            .iter()
+            .find_map(|it| it.decimals)
+            .filter(|_| token_amt > 0);
+        let vwap_sol =
+            vwap_decimals.map(|decimals| utils::calc_price_sol(sol_amt, token_amt, decimals));
+        let vwap_sol_decimal =
+            vwap_decimals.and_then(|decimals| vwap_decimal(sol_amt, token_amt, decimals));
+
+        let mut close = None;
+        let mut closes = vec![];
+        for slot in slots {
+            close = slot.close.or(close);
+            closes.extend(close);
+        }
+        let twap_sol =
+            (!closes.is_empty()).then(|| closes.iter().sum::<f64>() / closes.len() as f64);
+
+        Self {
+            window_secs,
+            trades: slots.iter().map(|it| it.trades).sum(),
+            vwap_sol,
+            vwap_sol_decimal,
+            twap_sol,
+        }
+    }
+}
+
+fn vwap_decimal(sol_amt: u64, token_amt: u64, decimals: u8) -> Option<String> {
+    Some(utils::calc_price_sol_decimal(sol_amt, token_amt, decimals)).filter(|it| !it.is_empty())
+}
+
+/// Sum the trades into the slots of their mints and the running sums of the windows, the
+/// outliers flagged by the price guard are left out. A retry of the claimed batch `batch_id`
+/// doesn't sum them again. With `on_trades`, the averages over the windows ending at the latest
+/// trade of each mint are attached to the trades.
+pub async fn record_avg_prices(
+    conn: &mut MultiplexedConnection,
+    batch_id: &str,
+    events: &mut [DexEvent],
+    config: &AvgPriceConfig,
+) -> Result<()> {
+    let trades: Vec<&TradeRecord> = events
+        .iter()
+        .filter_map(|it| match it {
+            DexEvent::Trade(trade) if !trade.price_outlier && trade.is_sol_quoted() => Some(trade),
+            _ => None,
+        })
+        .collect();
+    if trades.is_empty() {
+        return Ok(());
+    }
+
+    // the close of a slot is the price of its latest trade in the batch
+    let mut closes: HashMap<(Pubkey, i64), &TradeRecord> = HashMap::new();
+    for trade in &trades {
+        let slot = slot_start(trade.blk_ts.timestamp());
+        let close = closes.entry((trade.mint, slot)).or_insert(trade);
+        if (trade.slot, trade.idx) > (close.slot, close.idx) {
+            *close = trade;
+        }
+    }
+    let windows = &config.windows_secs;
+    let longest = windows.iter().max().copied().unwrap_or_default();
+    let mut mints: Vec<_> = trades.iter().map(|it| it.mint).collect();
+    mints.sort();
+    mints.dedup();
+
+    let mut pipe = redis::pipe();
+    for mint in &mints {
+        for window in windows {
+            pipe.hgetall(window_key(mint, *window));
+        }
+    }
+    let fields: Vec<HashMap<String, String>> = pipe.query_async(conn).await?;
+    let mut sums: HashMap<Pubkey, Vec<WindowSums>> = mints
+        .iter()
+        .zip(fields.chunks(windows.len().max(1)))
+        .map(|(mint, fields)| (*mint, fields.iter().map(WindowSums::from_fields).collect()))
+        .collect();
+    let mut pipe = redis::pipe();
+    for mint in &mints {
+        pipe.zrange(closes_key(mint), 0, -1);
+    }
+    let members: Vec<Vec<String>> = pipe.query_async(conn).await?;
+    let mut mint_closes: HashMap<Pubkey, HashMap<i64, f64>> = mints
+        .iter()
+        .zip(members)
+        .map(|(mint, members)| {
+            let closes = members
+                .iter()
+                .filter_map(|it| {
+                    let (slot, close) = it.split_once(':')?;
+                    Some((slot.parse().ok()?, close.parse().ok()?))
+                })
+                .collect();
+            (*mint, closes)
+        })
+        .collect();
+
+    if !queue::batch_step_applied(conn, batch_id, "avg_price").await? {
+        // the traded slots leaving the windows, to subtract them
+        let mut latest: HashMap<Pubkey, i64> = HashMap::new();
+        for trade in &trades {
+            let slot = slot_start(trade.blk_ts.timestamp());
+            let end = latest.entry(trade.mint).or_insert(slot);
+            *end = (*end).max(slot);
+        }
+        let mut leaving = HashSet::new();
+        for mint in &mints {
+            for (window, sums) in windows.iter().zip(&sums[mint]) {
+                let range = sums.leaving_slots(*window, latest[mint]);
+                leaving.extend(
+                    mint_closes[mint]
+                        .keys()
+                        .filter(|it| range.contains(*it))
+                        .map(|it| (*mint, *it)),
+                );
+            }
+        }
+        let leaving: Vec<_> = leaving.into_iter().collect();
+        let mut pipe = redis::pipe();
+        for (mint, slot) in &leaving {
+            pipe.hgetall(slot_key(mint, *slot));
+        }
+        let fields: Vec<HashMap<String, String>> = pipe.query_async(conn).await?;
+        let mut leaving_slots: HashMap<Pubkey, HashMap<i64, AvgPriceSlot>> = HashMap::new();
+        for ((mint, slot), fields) in leaving.iter().zip(&fields) {
+            leaving_slots
+                .entry(*mint)
+                .or_default()
+                .insert(*slot, AvgPriceSlot::from_fields(fields));
+        }
+
+        let no_slots = HashMap::new();
+        for mint in &mints {
+            let slots = leaving_slots.get(mint).unwrap_or(&no_slots);
+            let mint_sums = sums.get_mut(mint).expect("sums of every mint");
+            for (window, window_sums) in windows.iter().zip(mint_sums.iter_mut()) {
+                window_sums.slide(*window, latest[mint], slots);
+            }
+        }
+        let mut pipe = redis::pipe();
+        for trade in &trades {
+            let slot = slot_start(trade.blk_ts.timestamp());
+            let key = slot_key(&trade.mint, slot);
+            pipe.hincr(&key, "s", trade.sol_amt).ignore();
+            pipe.hincr(&key, "t", trade.token_amt).ignore();
+            pipe.hincr(&key, "n", 1).ignore();
+            let mint_sums = sums.get_mut(&trade.mint).expect("sums of every mint");
+            for (window, window_sums) in windows.iter().zip(mint_sums.iter_mut()) {
+                window_sums.add(*window, slot, trade);
+            }
+        }
+        let ttl = longest + AVG_PRICE_SLOT_SECS;
+        for ((mint, slot), trade) in &closes {
+            let key = slot_key(mint, *slot);
+            pipe.hset_multiple(
+                &key,
+                &[
+                    ("c", trade.price_sol.to_string()),
+                    ("d", trade.decimals.to_string()),
+                ],
+            )
+            .ignore();
+            pipe.expire(&key, ttl).ignore();
+            let key = closes_key(mint);
+            pipe.zrembyscore(&key, *slot, *slot).ignore();
+            pipe.zadd(&key, format!("{slot}:{}", trade.price_sol), *slot)
+                .ignore();
+            mint_closes
+                .entry(*mint)
+                .or_default()
+                .insert(*slot, trade.price_sol);
+        }
+        for mint in &mints {
+            let key = closes_key(mint);
+            pipe.zrembyscore(
+                &key,
+                "-inf",
+                format!("({}", latest[mint] - longest + AVG_PRICE_SLOT_SECS),
+            )
+            .ignore();
+            pipe.expire(&key, ttl).ignore();
+            for (window, window_sums) in windows.iter().zip(&sums[mint]) {
+                let key = window_key(mint, *window);
+                pipe.hset_multiple(
+                    &key,
+                    &[
+                        ("s", window_sums.sol_amt.to_string()),
+                        ("t", window_sums.token_amt.to_string()),
+                        ("n", window_sums.trades.to_string()),
+                        ("e", window_sums.end.unwrap_or_default().to_string()),
+                    ],
+                )
+                .ignore();
+                pipe.expire(&key, ttl).ignore();
+            }
+        }
+        queue::query_once(conn, batch_id, "avg_price", &mut pipe).await?;
+    }
+
+    if !config.on_trades {
+        return Ok(());
+    }
+    let decimals: HashMap<_, _> = trades.iter().map(|it| (it.mint, it.decimals)).collect();
+    let avg_prices: HashMap<_, Vec<_>> = mints
+        .iter()
+        .map(|mint| {
+            let avg_prices = windows
+                .iter()
+                .zip(&sums[mint])
+                .map(|(window, sums)| {
+                    AvgPrice::from_window(*window, sums, decimals[mint], &mint_closes[mint])
+                })
+                .collect();
+            (*mint, avg_prices)
+        })
+        .collect();
+    for evt in events {
+        if let DexEvent::Trade(trade) = evt
+            && !trade.price_outlier
+            && let Some(it) = avg_prices.get(&trade.mint)
+        {
+            trade.avg_prices = it.clone();
+        }
+    }
+    Ok(())
+}
+
+/// Averages of each mint over each of the `windows_secs` before `now`
+pub async fn mint_avg_prices(
+    conn: &mut MultiplexedConnection,
+    mints: &[Pubkey],
+    windows_secs: &[i64],
+    now: DateTime<Utc>,
+) -> Result<HashMap<Pubkey, Vec<AvgPrice>>> {
+    let Some(longest) = windows_secs.iter().max().copied() else {
+        return Ok(HashMap::new());
+    };
+    let slots: Vec<_> = window_slots(longest, now.timestamp()).collect();
+    let mut pipe = redis::pipe();
+    for mint in mints {
+        for slot in &slots {
+            pipe.hgetall(slot_key(mint, *slot));
+        }
+    }
+    let fields: Vec<HashMap<String, String>> = pipe.query_async(conn).await?;
+    Ok(mints
+        .iter()
+        .zip(fields.chunks(slots.len().max(1)))
+        .map(|(mint, fields)| {
+            let mint_slots: Vec<_> = fields.iter().map(AvgPriceSlot::from_fields).collect();
+            let avg_prices = windows_secs
+                .iter()
+                .map(|window| {
+                    let len = window_slots(*window, now.timestamp()).count();
+                    let window_slots = &mint_slots[mint_slots.len().saturating_sub(len)..];
+                    AvgPrice::from_slots(*window, window_slots)
+                })
+                .collect();
+            (*mint, avg_prices)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_avg_price_from_slots() {
+        let slot = |sol_amt, token_amt, close| AvgPriceSlot {
+            sol_amt,
+            token_amt,
+            trades: 1,
+            close: Some(close),
+            decimals: Some(6),
+        };
+        let slots = [
+            AvgPriceSlot::default(),
+            slot(1_000_000_000, 1_000_000, 1.0),
+            AvgPriceSlot::default(),
+            slot(3_000_000_000, 1_000_000, 3.0),
+        ];
+        let avg = AvgPrice::from_slots(240, &slots);
+        assert_eq!(avg.trades, 2);
+        assert_eq!(avg.vwap_sol, Some(2.0));
+        assert_eq!(avg.vwap_sol_decimal.as_deref(), Some("2"));
+        // 1.0 carried into the minute without trade
+        assert_eq!(avg.twap_sol, Some(5.0 / 3.0));
+
+        let avg = AvgPrice::from_slots(60, &slots[..1]);
+        assert_eq!((avg.trades, avg.vwap_sol, avg.twap_sol), (0, None, None));
+        assert_eq!(avg.vwap_sol_decimal, None);
+
+        let trade: TradeRecord = serde_json::from_value(serde_json::json!({
+            "blk_ts": 0, "slot": 1, "txid": "tx", "idx": 0,
+            "mint": Pubkey::new_unique().to_string(), "decimals": 6,
+            "trader": Pubkey::new_unique().to_string(), "dex": "Pumpfun",
+            "pool": Pubkey::new_unique().to_string(), "pool_sol_amt": 0, "pool_token_amt": 0,
+            "is_buy": true, "sol_amt": 1_000_000_000u64, "token_amt": 1_000_000, "price_sol": 1.0,
+        }))
+        .unwrap();
+        let mut sums = WindowSums::default();
+        sums.slide(180, 60, &HashMap::new());
+        sums.add(180, 60, &trade);
+        sums.slide(180, 120, &HashMap::new());
+        sums.add(180, 120, &trade);
+        // older than the window
+        sums.add(180, -60, &trade);
+        assert_eq!(
+            (sums.trades, sums.sol_amt, sums.end),
+            (2, 2_000_000_000, Some(120))
+        );
+        assert_eq!(sums.leaving_slots(180, 240), 0..120);
+        let leaving = HashMap::from([(60, slot(1_000_000_000, 1_000_000, 1.0))]);
+        sums.slide(180, 240, &leaving);
+        assert_eq!((sums.trades, sums.sol_amt), (1, 1_000_000_000));
+        let closes = HashMap::from([(120, 2.0)]);
+        let avg = AvgPrice::from_window(180, &sums, 6, &closes);
+        assert_eq!((avg.vwap_sol, avg.twap_sol), (Some(1.0), Some(2.0)));
+        assert_eq!(avg.vwap_sol_decimal.as_deref(), Some("1"));
+        // every slot left
+        sums.slide(180, 600, &HashMap::new());
+        assert_eq!(
+            sums,
+            WindowSums {
+                end: Some(600),
+                ..Default::default()
+            }
+        );
+
+        let fields = HashMap::from([
+            ("s".to_string(), "5".to_string()),
+            ("c".to_string(), "0.25".to_string()),
+        ]);
+        let parsed = AvgPriceSlot::from_fields(&fields);
+        assert_eq!((parsed.sol_amt, parsed.close), (5, Some(0.25)));
+        assert_eq!(window_slots(120, 150).collect::<Vec<_>>(), vec![60, 120]);
+    }
+}
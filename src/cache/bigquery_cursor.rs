@@ -0,0 +1,56 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc, serde::ts_milliseconds};
+use redis::{AsyncCommands, aio::MultiplexedConnection};
+use serde::{Deserialize, Serialize};
+
+/// hash of the export cursors, by bigquery table
+const BIGQUERY_CURSORS_KEY: &str = "hash:bigquery_cursors";
+
+/// Position of the export to a bigquery table, moved after each inserted batch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BigQueryCursor {
+    /// the last inserted batch, it isn't inserted again when claimed again before the ack
+    pub batch_id: String,
+    /// highest slot exported so far
+    pub max_slot: u64,
+    /// rows exported since the table's first batch
+    pub rows: u64,
+    #[serde(with = "ts_milliseconds")]
+    pub updated_at: DateTime<Utc>,
+}
+
+pub async fn get_bigquery_cursor(
+    conn: &mut MultiplexedConnection,
+    table: &str,
+) -> Result<Option<BigQueryCursor>> {
+    let cursor: Option<String> = conn.hget(BIGQUERY_CURSORS_KEY, table).await?;
+    cursor
+        .map(|it| serde_json::from_str(&it))
+        .transpose()
+        .map_err(Into::into)
+}
+
+pub async fn set_bigquery_cursor(
+    conn: &mut MultiplexedConnection,
+    table: &str,
+    cursor: &BigQueryCursor,
+) -> Result<()> {
+    let _: () = conn
+        .hset(BIGQUERY_CURSORS_KEY, table, serde_json::to_string(cursor)?)
+        .await?;
+    Ok(())
+}
+
+/// Cursors of every table exported to, by table
+pub async fn bigquery_cursors(
+    conn: &mut MultiplexedConnection,
+) -> Result<BTreeMap<String, BigQueryCursor>> {
+    let cursors: BTreeMap<String, String> = conn.hgetall(BIGQUERY_CURSORS_KEY).await?;
+    let mut parsed = BTreeMap::new();
+    for (table, cursor) in cursors {
+        parsed.insert(table, serde_json::from_str(&cursor)?);
+    }
+    Ok(parsed)
+}
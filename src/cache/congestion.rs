@@ -0,0 +1,182 @@
+use anyhow::Result;
+use redis::aio::MultiplexedConnection;
+use serde::Serialize;
+
+use crate::compute_budget::ComputeBudget;
+
+/// Slots kept for the congestion metrics, about 10 minutes
+pub const MAX_CONGESTION_SLOTS: usize = 1500;
+/// The slots of a replayed batch are kept a while too, then expire
+const CONGESTION_SLOT_TTL_SECS: i64 = 900;
+
+/// sorted set of the slots with compute budgets, scored by slot
+const CONGESTION_SLOTS_KEY: &str = "zset:congestion_slots";
+
+/// sorted set of the transactions of a slot, scored by their compute unit price
+fn cu_price_key(slot: u64) -> String {
+    format!("zset:cu_price:{slot}")
+}
+
+/// sorted set of the transactions of a slot which set a limit, scored by it
+fn cu_limit_key(slot: u64) -> String {
+    format!("zset:cu_limit:{slot}")
+}
+
+/// Medians of the compute budgets requested by the dex transactions of a slot
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SlotCongestion {
+    pub slot: u64,
+    pub txs: u64,
+    /// micro lamports per compute unit, 0 for the transactions which set no price
+    pub median_cu_price: u64,
+    /// none when no transaction of the slot set a limit
+    pub median_cu_limit: Option<u64>,
+}
+
+/// The congestion of the latest slots, by the medians of their medians
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Congestion {
+    pub txs: u64,
+    pub median_cu_price: Option<u64>,
+    pub median_cu_limit: Option<u64>,
+    /// the latest first
+    pub slots: Vec<SlotCongestion>,
+}
+
+impl Congestion {
+    fn new(slots: Vec<SlotCongestion>) -> Self {
+        let mut prices: Vec<_> = slots.iter().map(|it| it.median_cu_price).collect();
+        let mut limits: Vec<_> = slots.iter().filter_map(|it| it.median_cu_limit).collect();
+        Self {
+            txs: slots.iter().map(|it| it.txs).sum(),
+            median_cu_price: median(&mut prices),
+            median_cu_limit: median(&mut limits),
+            slots,
+        }
+    }
+}
+
+/// the lower median for an even number of values
+fn median(values: &mut [u64]) -> Option<u64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable();
+    Some(values[(values.len() - 1) / 2])
+}
+
+/// Add the compute budgets of the transactions, by slot and txid, to the sets of their slots
+pub async fn record_compute_budgets(
+    conn: &mut MultiplexedConnection,
+    budgets: &[(u64, &str, ComputeBudget)],
+) -> Result<()> {
+    if budgets.is_empty() {
+        return Ok(());
+    }
+
+    let mut pipe = redis::pipe();
+    let mut slots: Vec<_> = budgets.iter().map(|(slot, ..)| *slot).collect();
+    slots.sort();
+    slots.dedup();
+    for (slot, txid, budget) in budgets {
+        pipe.zadd(
+            cu_price_key(*slot),
+            *txid,
+            budget.cu_price.unwrap_or_default(),
+        )
+        .ignore();
+        if let Some(cu_limit) = budget.cu_limit {
+            pipe.zadd(cu_limit_key(*slot), *txid, cu_limit).ignore();
+        }
+    }
+    for slot in slots {
+        pipe.expire(cu_price_key(slot), CONGESTION_SLOT_TTL_SECS)
+            .ignore()
+            .expire(cu_limit_key(slot), CONGESTION_SLOT_TTL_SECS)
+            .ignore()
+            .zadd(CONGESTION_SLOTS_KEY, slot, slot)
+            .ignore();
+    }
+    pipe.zremrangebyrank(
+        CONGESTION_SLOTS_KEY,
+        0,
+        -(MAX_CONGESTION_SLOTS as isize) - 1,
+    )
+    .ignore();
+    let _: () = pipe.query_async(conn).await?;
+    Ok(())
+}
+
+/// The congestion of the `slots` latest slots with compute budgets
+pub async fn congestion(conn: &mut MultiplexedConnection, slots: usize) -> Result<Congestion> {
+    if slots == 0 {
+        return Ok(Congestion::default());
+    }
+    let latest: Vec<u64> = redis::cmd("ZREVRANGE")
+        .arg(CONGESTION_SLOTS_KEY)
+        .arg(0)
+        .arg(slots as isize - 1)
+        .query_async(conn)
+        .await?;
+    if latest.is_empty() {
+        return Ok(Congestion::default());
+    }
+
+    let mut pipe = redis::pipe();
+    for slot in &latest {
+        pipe.zcard(cu_price_key(*slot)).zcard(cu_limit_key(*slot));
+    }
+    let cards: Vec<u64> = pipe.query_async(conn).await?;
+    // the middle member of each set, by rank
+    let mut pipe = redis::pipe();
+    for (slot, cards) in latest.iter().zip(cards.chunks(2)) {
+        let (prices, limits) = (cards[0] as isize, cards[1] as isize);
+        pipe.zrange_withscores(cu_price_key(*slot), (prices - 1) / 2, (prices - 1) / 2)
+            .zrange_withscores(cu_limit_key(*slot), (limits - 1) / 2, (limits - 1) / 2);
+    }
+    let middles: Vec<Vec<(String, f64)>> = pipe.query_async(conn).await?;
+    let slots = latest
+        .into_iter()
+        .zip(cards.chunks(2))
+        .zip(middles.chunks(2))
+        .filter_map(|((slot, cards), middles)| {
+            // expired meanwhile
+            let median_cu_price = middles[0].first()?.1 as u64;
+            Some(SlotCongestion {
+                slot,
+                txs: cards[0],
+                median_cu_price,
+                median_cu_limit: middles[1].first().map(|it| it.1 as u64),
+            })
+        })
+        .collect();
+    Ok(Congestion::new(slots))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_congestion() {
+        assert_eq!(median(&mut []), None);
+        assert_eq!(median(&mut [5, 1, 3]), Some(3));
+        assert_eq!(median(&mut [4, 1, 3, 2]), Some(2));
+
+        let slot = |slot, txs, median_cu_price, median_cu_limit| SlotCongestion {
+            slot,
+            txs,
+            median_cu_price,
+            median_cu_limit,
+        };
+        let congestion = Congestion::new(vec![
+            slot(12, 3, 10_000, Some(200_000)),
+            slot(11, 1, 0, None),
+            slot(10, 4, 50_000, Some(100_000)),
+        ]);
+        assert_eq!(congestion.txs, 8);
+        assert_eq!(congestion.median_cu_price, Some(10_000));
+        assert_eq!(congestion.median_cu_limit, Some(100_000));
+        assert_eq!(congestion.slots.len(), 3);
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use redis::{AsyncCommands, aio::MultiplexedConnection};
+use serde::Serialize;
+
+use super::consumer_queue_len;
+
+/// hash of the consumer counters, `{consumer}:{counter}` fields
+const CONSUMER_METRICS_KEY: &str = "hash:consumer_metrics";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsumerCounter {
+    /// events delivered to the consumer's endpoint
+    Sent,
+    /// events dropped by the consumer's rules or not supported by its format
+    Dropped,
+    /// batches the endpoint failed to accept, they're retried
+    Failed,
+    /// events missed since the consumer's queue was full
+    Overflowed,
+}
+
+impl ConsumerCounter {
+    fn field(&self, consumer: &str) -> String {
+        let counter = match self {
+            ConsumerCounter::Sent => "sent",
+            ConsumerCounter::Dropped => "dropped",
+            ConsumerCounter::Failed => "failed",
+            ConsumerCounter::Overflowed => "overflowed",
+        };
+        format!("{consumer}:{counter}")
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConsumerMetrics {
+    pub name: String,
+    /// events waiting in the consumer's queues
+    pub queued: u64,
+    pub sent: u64,
+    pub dropped: u64,
+    pub failed: u64,
+    pub overflowed: u64,
+}
+
+pub async fn incr_consumer_counter(
+    conn: &mut MultiplexedConnection,
+    consumer: &str,
+    counter: ConsumerCounter,
+    n: u64,
+) -> Result<()> {
+    if n == 0 {
+        return Ok(());
+    }
+    let _: () = conn
+        .hincr(CONSUMER_METRICS_KEY, counter.field(consumer), n)
+        .await?;
+    Ok(())
+}
+
+/// Counters of every consumer which counted anything, by name
+pub async fn consumer_metrics(conn: &mut MultiplexedConnection) -> Result<Vec<ConsumerMetrics>> {
+    let fields: BTreeMap<String, u64> = conn.hgetall(CONSUMER_METRICS_KEY).await?;
+    let mut consumers: BTreeMap<String, ConsumerMetrics> = BTreeMap::new();
+    for (field, value) in fields {
+        let Some((name, counter)) = field.rsplit_once(':') else {
+            continue;
+        };
+        let metrics = consumers
+            .entry(name.to_string())
+            .or_insert_with(|| ConsumerMetrics {
+                name: name.to_string(),
+                ..Default::default()
+            });
+        match counter {
+            "sent" => metrics.sent = value,
+            "dropped" => metrics.dropped = value,
+            "failed" => metrics.failed = value,
+            "overflowed" => metrics.overflowed = value,
+            _ => {}
+        }
+    }
+
+    let mut metrics = vec![];
+    for (name, mut consumer) in consumers {
+        consumer.queued = consumer_queue_len(conn, &name).await?;
+        metrics.push(consumer);
+    }
+    Ok(metrics)
+}
@@ -0,0 +1,180 @@
+use anyhow::Result;
+use redis::{AsyncCommands, aio::MultiplexedConnection};
+use serde::Serialize;
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+
+use super::DexPoolCreatedRecord;
+
+/// first funder of each looked up wallet
+const FIRST_FUNDER_KEY: &str = "hash:first_funder";
+/// wallets waiting for a first funder lookup
+const FUNDER_LOOKUPS_KEY: &str = "set:funder_lookups";
+
+/// the wallet was looked up and has no first funder, until it expires
+fn no_first_funder_key(wallet: &Pubkey) -> String {
+    format!("str:no_first_funder:{wallet}")
+}
+
+fn creator_mints_key(creator: &Pubkey) -> String {
+    format!("set:creator_mints:{creator}")
+}
+
+fn mint_pools_key(mint: &Pubkey) -> String {
+    format!("set:mint_pools:{mint}")
+}
+
+/// A token of a creator with the pools of the token
+#[serde_as]
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatorToken {
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    pub pools: Vec<Pubkey>,
+}
+
+/// Link the creators to the tokens and the tokens to the pools.
+/// Pumpamm pools created by the migration of a bonding curve are only linked to their token.
+pub async fn record_created_pools(
+    conn: &mut MultiplexedConnection,
+    records: &[&DexPoolCreatedRecord],
+) -> Result<()> {
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    let mut pipe = redis::pipe();
+    for record in records {
+        let mint = record.as_pool_record().token_mint();
+        pipe.sadd(mint_pools_key(&mint), record.addr.to_string())
+            .ignore();
+        if !record.is_curve_migration() {
+            pipe.sadd(creator_mints_key(&record.creator), mint.to_string())
+                .ignore();
+        }
+    }
+    let _: () = pipe.query_async(conn).await?;
+    Ok(())
+}
+
+pub async fn creator_tokens(
+    conn: &mut MultiplexedConnection,
+    creator: &Pubkey,
+) -> Result<Vec<CreatorToken>> {
+    let mints: Vec<String> = conn.smembers(creator_mints_key(creator)).await?;
+    let mints = mints
+        .iter()
+        .map(|it| it.parse())
+        .collect::<Result<Vec<Pubkey>, _>>()?;
+    if mints.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut pipe = redis::pipe();
+    for mint in &mints {
+        pipe.smembers(mint_pools_key(mint));
+    }
+    let pools: Vec<Vec<String>> = pipe.query_async(conn).await?;
+
+    let mut tokens = vec![];
+    for (mint, pools) in mints.into_iter().zip(pools) {
+        let pools = pools
+            .iter()
+            .map(|it| it.parse())
+            .collect::<Result<Vec<_>, _>>()?;
+        tokens.push(CreatorToken { mint, pools });
+    }
+    Ok(tokens)
+}
+
+pub async fn get_first_funder(
+    conn: &mut MultiplexedConnection,
+    wallet: &Pubkey,
+) -> Result<Option<Pubkey>> {
+    let funder: Option<String> = conn.hget(FIRST_FUNDER_KEY, wallet.to_string()).await?;
+    let funder = funder.map(|it| it.parse()).transpose()?;
+    Ok(funder)
+}
+
+/// In the order of `wallets`, none for the wallets not looked up
+pub async fn get_first_funders(
+    conn: &mut MultiplexedConnection,
+    wallets: &[Pubkey],
+) -> Result<Vec<Option<Pubkey>>> {
+    if wallets.is_empty() {
+        return Ok(vec![]);
+    }
+    let wallets: Vec<_> = wallets.iter().map(|it| it.to_string()).collect();
+    let funders: Vec<Option<String>> = redis::cmd("HMGET")
+        .arg(FIRST_FUNDER_KEY)
+        .arg(wallets)
+        .query_async(conn)
+        .await?;
+    let funders = funders
+        .into_iter()
+        .map(|it| it.and_then(|it| it.parse().ok()))
+        .collect();
+    Ok(funders)
+}
+
+/// The first funder of a wallet never changes, it's kept without expiry
+pub async fn save_first_funder(
+    conn: &mut MultiplexedConnection,
+    wallet: &Pubkey,
+    funder: &Pubkey,
+) -> Result<()> {
+    let _: () = conn
+        .hset(FIRST_FUNDER_KEY, wallet.to_string(), funder.to_string())
+        .await?;
+    Ok(())
+}
+
+/// The wallet was looked up lately without finding its first funder
+pub async fn has_no_first_funder(
+    conn: &mut MultiplexedConnection,
+    wallet: &Pubkey,
+) -> Result<bool> {
+    let exists: bool = conn.exists(no_first_funder_key(wallet)).await?;
+    Ok(exists)
+}
+
+pub async fn save_no_first_funder(
+    conn: &mut MultiplexedConnection,
+    wallet: &Pubkey,
+    ttl_secs: u64,
+) -> Result<()> {
+    let _: () = conn
+        .set_ex(no_first_funder_key(wallet), 1, ttl_secs)
+        .await?;
+    Ok(())
+}
+
+/// Queue wallets for a background first funder lookup
+pub async fn queue_funder_lookups(
+    conn: &mut MultiplexedConnection,
+    wallets: &[Pubkey],
+) -> Result<()> {
+    if wallets.is_empty() {
+        return Ok(());
+    }
+    let wallets: Vec<_> = wallets.iter().map(|it| it.to_string()).collect();
+    let _: () = conn.sadd(FUNDER_LOOKUPS_KEY, wallets).await?;
+    Ok(())
+}
+
+pub async fn pop_funder_lookups(
+    conn: &mut MultiplexedConnection,
+    max: usize,
+) -> Result<Vec<Pubkey>> {
+    let wallets: Vec<String> = redis::cmd("SPOP")
+        .arg(FUNDER_LOOKUPS_KEY)
+        .arg(max)
+        .query_async(conn)
+        .await?;
+    let wallets = wallets
+        .iter()
+        .map(|it| it.parse())
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(wallets)
+}
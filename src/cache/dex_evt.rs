@@ -1,9 +1,22 @@
+use std::time::Duration;
+
 use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
 use redis::aio::MultiplexedConnection;
 use serde::{Deserialize, Serialize};
+use solana_sdk::{hash::hashv, pubkey::Pubkey};
+use strum::{Display, EnumIter, IntoEnumIterator};
 use tracing::warn;
 
-use super::{DexPoolCreatedRecord, PumpfunCompleteRecord, TradeRecord};
+use crate::{common::Dex, qn_req_processor::EventSource};
+
+use super::{
+    BinPriceUpdateRecord, DexPoolCreatedRecord, FailedSwapAttemptRecord, FollowedWalletTradeRecord,
+    LiquidityChangeRecord, LiquidityLockedRecord, MeteoraDbcCompleteRecord,
+    MigrationCompletedRecord, MintInactiveRecord, PoolDrainedRecord, PoolStateUpdatedRecord,
+    PressureUpdateRecord, PumpfunCompleteRecord, PumpfunSetParamsRecord, TradeRecord,
+    event_pubsub_enabled, publish_dex_evts, queue,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "kind")]
@@ -11,75 +24,426 @@ pub enum DexEvent {
     Trade(TradeRecord),
     PoolCreated(DexPoolCreatedRecord),
     PumpfunComplete(PumpfunCompleteRecord),
+    MigrationCompleted(MigrationCompletedRecord),
+    MintInactive(MintInactiveRecord),
+    PoolStateUpdated(PoolStateUpdatedRecord),
+    BinPriceUpdate(BinPriceUpdateRecord),
+    PressureUpdate(PressureUpdateRecord),
+    FollowedWalletTrade(FollowedWalletTradeRecord),
+    MeteoraDbcComplete(MeteoraDbcCompleteRecord),
+    LiquidityChange(LiquidityChangeRecord),
+    PumpfunSetParams(PumpfunSetParamsRecord),
+    LiquidityLocked(LiquidityLockedRecord),
+    PoolDrained(PoolDrainedRecord),
+    FailedSwapAttempt(FailedSwapAttemptRecord),
+}
+
+/// Each kind of event is queued separately, so trades can't delay the rare kinds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumIter, Serialize, Deserialize)]
+pub enum DexEventKind {
+    Trade,
+    PoolCreated,
+    PumpfunComplete,
+    MigrationCompleted,
+    MintInactive,
+    PoolStateUpdated,
+    BinPriceUpdate,
+    PressureUpdate,
+    FollowedWalletTrade,
+    MeteoraDbcComplete,
+    LiquidityChange,
+    PumpfunSetParams,
+    LiquidityLocked,
+    PoolDrained,
+    FailedSwapAttempt,
+}
+
+impl DexEventKind {
+    fn list_key(&self) -> &'static str {
+        match self {
+            // events of every kind were queued here before the queues were split
+            DexEventKind::Trade => "list:dex_events",
+            DexEventKind::PoolCreated => "list:dex_events:pool_created",
+            DexEventKind::PumpfunComplete => "list:dex_events:pumpfun_complete",
+            DexEventKind::MigrationCompleted => "list:dex_events:migration_completed",
+            DexEventKind::MintInactive => "list:dex_events:mint_inactive",
+            DexEventKind::PoolStateUpdated => "list:dex_events:pool_state_updated",
+            DexEventKind::BinPriceUpdate => "list:dex_events:bin_price_update",
+            DexEventKind::PressureUpdate => "list:dex_events:pressure_update",
+            DexEventKind::FollowedWalletTrade => "list:dex_events:followed_wallet_trade",
+            DexEventKind::MeteoraDbcComplete => "list:dex_events:meteora_dbc_complete",
+            DexEventKind::LiquidityChange => "list:dex_events:liquidity_change",
+            DexEventKind::PumpfunSetParams => "list:dex_events:pumpfun_set_params",
+            DexEventKind::LiquidityLocked => "list:dex_events:liquidity_locked",
+            DexEventKind::PoolDrained => "list:dex_events:pool_drained",
+            DexEventKind::FailedSwapAttempt => "list:dex_events:failed_swap_attempt",
+        }
+    }
+
+    /// Queue of the events of this kind routed to a consumer, the shared queue without consumer
+    fn queue_key(&self, consumer: Option<&str>) -> String {
+        match consumer {
+            Some(consumer) => format!("list:consumer:{consumer}:dex_events:{self}"),
+            None => self.list_key().to_string(),
+        }
+    }
+
+    /// How often the webhook checks the queue when it's empty
+    pub fn poll_interval(&self) -> Duration {
+        match self {
+            DexEventKind::Trade
+            | DexEventKind::MintInactive
+            | DexEventKind::PoolStateUpdated
+            | DexEventKind::BinPriceUpdate
+            | DexEventKind::PressureUpdate => Duration::from_millis(200),
+            DexEventKind::PoolCreated
+            | DexEventKind::PumpfunComplete
+            | DexEventKind::MigrationCompleted
+            | DexEventKind::FollowedWalletTrade
+            | DexEventKind::MeteoraDbcComplete
+            | DexEventKind::LiquidityChange
+            | DexEventKind::PumpfunSetParams
+            | DexEventKind::LiquidityLocked
+            | DexEventKind::PoolDrained
+            | DexEventKind::FailedSwapAttempt => Duration::from_millis(50),
+        }
+    }
+
+    /// Pause of the webhook after sending a batch
+    pub fn send_interval(&self) -> Duration {
+        match self {
+            DexEventKind::Trade
+            | DexEventKind::MintInactive
+            | DexEventKind::PoolStateUpdated
+            | DexEventKind::BinPriceUpdate
+            | DexEventKind::PressureUpdate => Duration::from_millis(500),
+            DexEventKind::PoolCreated
+            | DexEventKind::PumpfunComplete
+            | DexEventKind::MigrationCompleted
+            | DexEventKind::FollowedWalletTrade
+            | DexEventKind::MeteoraDbcComplete
+            | DexEventKind::LiquidityChange
+            | DexEventKind::PumpfunSetParams
+            | DexEventKind::LiquidityLocked
+            | DexEventKind::PoolDrained
+            | DexEventKind::FailedSwapAttempt => Duration::from_millis(100),
+        }
+    }
+}
+
+impl DexEvent {
+    pub fn kind(&self) -> DexEventKind {
+        match self {
+            DexEvent::Trade(_) => DexEventKind::Trade,
+            DexEvent::PoolCreated(_) => DexEventKind::PoolCreated,
+            DexEvent::PumpfunComplete(_) => DexEventKind::PumpfunComplete,
+            DexEvent::MigrationCompleted(_) => DexEventKind::MigrationCompleted,
+            DexEvent::MintInactive(_) => DexEventKind::MintInactive,
+            DexEvent::PoolStateUpdated(_) => DexEventKind::PoolStateUpdated,
+            DexEvent::BinPriceUpdate(_) => DexEventKind::BinPriceUpdate,
+            DexEvent::PressureUpdate(_) => DexEventKind::PressureUpdate,
+            DexEvent::FollowedWalletTrade(_) => DexEventKind::FollowedWalletTrade,
+            DexEvent::MeteoraDbcComplete(_) => DexEventKind::MeteoraDbcComplete,
+            DexEvent::LiquidityChange(_) => DexEventKind::LiquidityChange,
+            DexEvent::PumpfunSetParams(_) => DexEventKind::PumpfunSetParams,
+            DexEvent::LiquidityLocked(_) => DexEventKind::LiquidityLocked,
+            DexEvent::PoolDrained(_) => DexEventKind::PoolDrained,
+            DexEvent::FailedSwapAttempt(_) => DexEventKind::FailedSwapAttempt,
+        }
+    }
+
+    /// Dex of the event, none for the events of a mint
+    pub fn dex(&self) -> Option<Dex> {
+        match self {
+            DexEvent::Trade(it) => Some(it.dex),
+            DexEvent::FollowedWalletTrade(it) => Some(it.trade.dex),
+            DexEvent::PoolCreated(it) => Some(it.dex),
+            DexEvent::LiquidityChange(it) => Some(it.dex),
+            DexEvent::LiquidityLocked(it) => Some(it.dex),
+            DexEvent::PoolDrained(it) => Some(it.dex),
+            DexEvent::FailedSwapAttempt(it) => Some(it.dex),
+            DexEvent::PumpfunComplete(_) | DexEvent::PumpfunSetParams(_) => Some(Dex::Pumpfun),
+            DexEvent::MeteoraDbcComplete(_) => Some(Dex::MeteoraDbc),
+            DexEvent::MigrationCompleted(_) => Some(Dex::PumpAmm),
+            DexEvent::PoolStateUpdated(it) => Some(it.dex),
+            DexEvent::BinPriceUpdate(_) => Some(Dex::MeteoraDlmm),
+            DexEvent::MintInactive(_) | DexEvent::PressureUpdate(_) => None,
+        }
+    }
+
+    /// Token mint of the event, none for the events of no single mint
+    pub fn mint(&self) -> Option<Pubkey> {
+        match self {
+            DexEvent::Trade(it) => Some(it.mint),
+            DexEvent::FollowedWalletTrade(it) => Some(it.trade.mint),
+            DexEvent::PoolCreated(it) => Some(it.as_pool_record().token_mint()),
+            DexEvent::LiquidityChange(it) => Some(it.mint),
+            DexEvent::LiquidityLocked(it) => Some(it.mint),
+            DexEvent::PoolDrained(it) => Some(it.mint),
+            DexEvent::FailedSwapAttempt(it) => Some(it.mint),
+            DexEvent::PumpfunComplete(it) => Some(it.mint),
+            DexEvent::MeteoraDbcComplete(it) => Some(it.mint),
+            DexEvent::MigrationCompleted(it) => Some(it.mint),
+            DexEvent::MintInactive(it) => Some(it.mint),
+            DexEvent::PoolStateUpdated(it) => Some(it.mint),
+            DexEvent::BinPriceUpdate(it) => Some(it.mint),
+            DexEvent::PressureUpdate(it) => Some(it.mint),
+            DexEvent::PumpfunSetParams(_) => None,
+        }
+    }
+
+    /// Attach the stream batch to the events parsed from transactions
+    pub fn set_source(&mut self, source: &EventSource) {
+        let field = match self {
+            DexEvent::Trade(it) => &mut it.source,
+            DexEvent::FollowedWalletTrade(it) => &mut it.trade.source,
+            DexEvent::PoolCreated(it) => &mut it.source,
+            DexEvent::LiquidityChange(it) => &mut it.source,
+            DexEvent::PumpfunComplete(it) => &mut it.source,
+            DexEvent::PumpfunSetParams(it) => &mut it.source,
+            DexEvent::MeteoraDbcComplete(it) => &mut it.source,
+            DexEvent::MigrationCompleted(it) => &mut it.source,
+            DexEvent::PoolDrained(it) => &mut it.source,
+            DexEvent::FailedSwapAttempt(it) => &mut it.source,
+            DexEvent::MintInactive(_)
+            | DexEvent::PoolStateUpdated(_)
+            | DexEvent::BinPriceUpdate(_)
+            | DexEvent::PressureUpdate(_)
+            | DexEvent::LiquidityLocked(_) => return,
+        };
+        *field = Some(source.clone());
+    }
+
+    pub fn txid(&self) -> &str {
+        match self {
+            DexEvent::Trade(it) => &it.txid,
+            DexEvent::FollowedWalletTrade(it) => &it.trade.txid,
+            DexEvent::PoolCreated(it) => &it.txid,
+            DexEvent::LiquidityChange(it) => &it.txid,
+            DexEvent::PumpfunComplete(it) => &it.txid,
+            DexEvent::PumpfunSetParams(it) => &it.txid,
+            DexEvent::MeteoraDbcComplete(it) => &it.txid,
+            DexEvent::MigrationCompleted(it) => &it.txid,
+            DexEvent::PoolDrained(it) => &it.txid,
+            DexEvent::FailedSwapAttempt(it) => &it.txid,
+            // not emitted by a transaction
+            DexEvent::MintInactive(_)
+            | DexEvent::PoolStateUpdated(_)
+            | DexEvent::BinPriceUpdate(_)
+            | DexEvent::PressureUpdate(_)
+            | DexEvent::LiquidityLocked(_) => "",
+        }
+    }
+
+    /// Slot of the transaction or the pool update, none for the events of a mint
+    pub fn slot(&self) -> Option<u64> {
+        match self {
+            DexEvent::Trade(it) => Some(it.slot),
+            DexEvent::FollowedWalletTrade(it) => Some(it.trade.slot),
+            DexEvent::PoolCreated(it) => Some(it.slot),
+            DexEvent::LiquidityChange(it) => Some(it.slot),
+            DexEvent::PumpfunComplete(it) => Some(it.slot),
+            DexEvent::PumpfunSetParams(it) => Some(it.slot),
+            DexEvent::MeteoraDbcComplete(it) => Some(it.slot),
+            DexEvent::MigrationCompleted(it) => Some(it.slot),
+            DexEvent::PoolStateUpdated(it) => Some(it.slot),
+            DexEvent::BinPriceUpdate(it) => Some(it.slot),
+            DexEvent::LiquidityLocked(it) => Some(it.slot),
+            DexEvent::PoolDrained(it) => Some(it.slot),
+            DexEvent::FailedSwapAttempt(it) => Some(it.slot),
+            DexEvent::MintInactive(_) | DexEvent::PressureUpdate(_) => None,
+        }
+    }
+
+    /// Block time of the transaction, none for the events not parsed from one
+    pub fn blk_ts(&self) -> Option<DateTime<Utc>> {
+        match self {
+            DexEvent::Trade(it) => Some(it.blk_ts),
+            DexEvent::FollowedWalletTrade(it) => Some(it.trade.blk_ts),
+            DexEvent::PoolCreated(it) => Some(it.blk_ts),
+            DexEvent::LiquidityChange(it) => Some(it.blk_ts),
+            DexEvent::PumpfunComplete(it) => Some(it.blk_ts),
+            DexEvent::PumpfunSetParams(it) => Some(it.blk_ts),
+            DexEvent::MeteoraDbcComplete(it) => Some(it.blk_ts),
+            DexEvent::MigrationCompleted(it) => Some(it.blk_ts),
+            DexEvent::PoolDrained(it) => Some(it.blk_ts),
+            DexEvent::FailedSwapAttempt(it) => Some(it.blk_ts),
+            DexEvent::MintInactive(_)
+            | DexEvent::PoolStateUpdated(_)
+            | DexEvent::BinPriceUpdate(_)
+            | DexEvent::PressureUpdate(_)
+            | DexEvent::LiquidityLocked(_) => None,
+        }
+    }
+
+    pub fn idx(&self) -> u64 {
+        match self {
+            DexEvent::Trade(it) => it.idx,
+            DexEvent::FollowedWalletTrade(it) => it.trade.idx,
+            DexEvent::PoolCreated(it) => it.idx,
+            DexEvent::LiquidityChange(it) => it.idx,
+            DexEvent::PumpfunComplete(it) => it.idx,
+            DexEvent::PumpfunSetParams(it) => it.idx,
+            DexEvent::MeteoraDbcComplete(it) => it.idx,
+            DexEvent::MigrationCompleted(it) => it.idx,
+            DexEvent::PoolDrained(it) => it.idx,
+            DexEvent::FailedSwapAttempt(it) => it.idx,
+            DexEvent::MintInactive(_)
+            | DexEvent::PoolStateUpdated(_)
+            | DexEvent::BinPriceUpdate(_)
+            | DexEvent::PressureUpdate(_)
+            | DexEvent::LiquidityLocked(_) => 0,
+        }
+    }
 }
 
-const DEX_EVENT_LIST_KEY: &str = "list:dex_events";
 const MAX_EVENT_LEN: u64 = 50_000;
-pub async fn rpush_dex_evts(conn: &mut MultiplexedConnection, events: &[DexEvent]) -> Result<()> {
-    let q_len: u64 = redis::cmd("llen")
-        .arg(DEX_EVENT_LIST_KEY)
-        .query_async(conn)
-        .await?;
-    if q_len >= MAX_EVENT_LEN {
-        warn!("trade queue larger than {MAX_EVENT_LEN}");
-        return Err(anyhow!("trade queue larger than {MAX_EVENT_LEN}"));
+const DEX_EVENT_BATCH_LEN: usize = 5_000;
+
+/// Events are queued as messagepack, json is only used when sending them out.
+pub fn encode_dex_evt(evt: &DexEvent) -> Result<Vec<u8>> {
+    let bytes = rmp_serde::to_vec_named(evt)?;
+    Ok(bytes)
+}
+
+pub fn decode_dex_evt(record: &[u8]) -> Result<DexEvent> {
+    // events queued before messagepack was introduced are json objects
+    if record.first() == Some(&b'{') {
+        let evt = serde_json::from_slice(record)?;
+        return Ok(evt);
     }
+    let evt = rmp_serde::from_slice(record)?;
+    Ok(evt)
+}
 
-    // redis rpush
-    let mut cmd = redis::cmd("rpush");
-    cmd.arg(DEX_EVENT_LIST_KEY);
-    for evt in events {
-        let json = serde_json::to_string(evt)?;
-        cmd.arg(json);
+/// Queue the events to the queues of their kinds, all or none, so a retry after an error
+/// doesn't queue some of them twice. A full queue of one kind holds back the others.
+/// `batch_id` is the claimed batch of the events, whose retry doesn't publish them again.
+pub async fn rpush_dex_evts(
+    conn: &mut MultiplexedConnection,
+    batch_id: Option<&str>,
+    events: &[DexEvent],
+) -> Result<()> {
+    let mut lists = vec![];
+    for kind in DexEventKind::iter() {
+        let records = events
+            .iter()
+            .filter(|it| it.kind() == kind)
+            .map(encode_dex_evt)
+            .collect::<Result<Vec<_>>>()?;
+        lists.push((kind.list_key(), records));
+    }
+    if let Some(key) = queue::bounded_rpush_all(conn, &lists, MAX_EVENT_LEN).await? {
+        warn!("event queue {key} larger than {MAX_EVENT_LEN}");
+        return Err(anyhow!("event queue {key} larger than {MAX_EVENT_LEN}"));
+    }
+    // best effort, failing it would queue the events again on retry
+    if event_pubsub_enabled()
+        && let Err(err) = publish_dex_evts(conn, batch_id, events).await
+    {
+        warn!("publish dex events error: {err}");
     }
 
-    let _: () = cmd.query_async(conn).await?;
     Ok(())
 }
 
-pub async fn lrange_dex_evts(conn: &mut MultiplexedConnection) -> Result<Vec<DexEvent>> {
-    let llen: u64 = redis::cmd("llen")
-        .arg(DEX_EVENT_LIST_KEY)
-        .query_async(conn)
-        .await?;
-    if llen == 0 {
-        return Ok(vec![]);
-    }
+/// Events claimed from a queue, until acked
+pub struct DexEvtBatch {
+    /// hash of the queued records, the same when an unacked batch is claimed again
+    pub id: String,
+    pub events: Vec<DexEvent>,
+}
 
-    let records: Vec<String> = redis::cmd("lrange")
-        .arg(DEX_EVENT_LIST_KEY)
-        .arg(0)
-        .arg(llen - 1)
-        .query_async(conn)
-        .await?;
+/// Claim a batch of queued events, it's claimed again until acked with `ack_dex_evts`.
+/// The events of a consumer are claimed from its own queue, see `route_dex_evts`.
+pub async fn claim_dex_evts(
+    conn: &mut MultiplexedConnection,
+    kind: DexEventKind,
+    consumer: Option<&str>,
+) -> Result<DexEvtBatch> {
+    let key = kind.queue_key(consumer);
+    let records = queue::claim_batch(conn, &key, DEX_EVENT_BATCH_LEN).await?;
 
     let mut evts = vec![];
     for record in &records {
-        let evt = serde_json::from_str(record).map_err(|err| {
-            anyhow!("error parse event record from redis: {err}, record: {record}")
+        let evt = decode_dex_evt(record).map_err(|err| {
+            anyhow!(
+                "error parse event record from redis: {err}, record: {}",
+                String::from_utf8_lossy(record)
+            )
         })?;
         evts.push(evt);
     }
 
-    Ok(evts)
+    let records: Vec<&[u8]> = records.iter().map(Vec::as_slice).collect();
+    Ok(DexEvtBatch {
+        id: hashv(&records).to_string(),
+        events: evts,
+    })
 }
 
-pub async fn ltrim_dex_evts(conn: &mut MultiplexedConnection, len: usize) -> Result<()> {
-    let _: () = redis::cmd("ltrim")
-        .arg(DEX_EVENT_LIST_KEY)
-        .arg(len)
-        .arg(-1)
-        .query_async(conn)
-        .await?;
-    Ok(())
+pub async fn ack_dex_evts(
+    conn: &mut MultiplexedConnection,
+    kind: DexEventKind,
+    consumer: Option<&str>,
+) -> Result<()> {
+    queue::ack_batch(conn, &kind.queue_key(consumer)).await
+}
+
+/// A batch of events copied from the shared queue of a kind to the consumer queues
+#[derive(Debug, Default)]
+pub struct RoutedBatch {
+    pub events: usize,
+    /// consumers which missed the batch since their queue is full
+    pub overflowed: Vec<String>,
+}
+
+/// Copy a batch of the shared queue of the kind to the queue of each consumer.
+/// A consumer with a full queue misses the batch, so a stalled consumer can't hold back the others.
+pub async fn route_dex_evts(
+    conn: &mut MultiplexedConnection,
+    kind: DexEventKind,
+    consumers: &[String],
+) -> Result<RoutedBatch> {
+    let records = queue::claim_batch(conn, kind.list_key(), DEX_EVENT_BATCH_LEN).await?;
+    let mut batch = RoutedBatch {
+        events: records.len(),
+        ..Default::default()
+    };
+    if records.is_empty() {
+        return Ok(batch);
+    }
+
+    // with the ack, so a failure copies the batch again to none of the consumers or all of them
+    let keys: Vec<_> = consumers
+        .iter()
+        .map(|it| kind.queue_key(Some(it)))
+        .collect();
+    let full = queue::copy_claimed_batch(conn, kind.list_key(), &keys, MAX_EVENT_LEN).await?;
+    for consumer in full.into_iter().map(|it| &consumers[it]) {
+        warn!("{kind} event queue of consumer {consumer} larger than {MAX_EVENT_LEN}");
+        batch.overflowed.push(consumer.clone());
+    }
+    Ok(batch)
+}
+
+/// Events waiting in the queues of the consumer, of every kind
+pub async fn consumer_queue_len(conn: &mut MultiplexedConnection, consumer: &str) -> Result<u64> {
+    let mut pipe = redis::pipe();
+    for kind in DexEventKind::iter() {
+        pipe.llen(kind.queue_key(Some(consumer)));
+    }
+    let lens: Vec<u64> = pipe.query_async(conn).await?;
+    Ok(lens.into_iter().sum())
 }
 
 #[cfg(test)]
 mod test {
     use crate::{
-        cache::DexPoolCreatedRecord,
+        cache::{DexPoolCreatedRecord, MintInactiveRecord},
         common::{Dex, WSOL_MINT},
         pumpfun::PUMPFUN_PROGRAM_ID,
+        qn_req_processor::QnStreamMetadata,
         raydium::RAYDIUM_AMM_PROGRAM_ID,
     };
     use chrono::Utc;
@@ -87,7 +451,7 @@ mod test {
     use std::any::type_name_of_val;
     use std::collections::HashMap;
 
-    use super::{DexEvent, TradeRecord};
+    use super::{DexEvent, TradeRecord, decode_dex_evt, encode_dex_evt};
 
     #[test]
     fn serialize_dex_evt() {
@@ -106,7 +470,23 @@ mod test {
             is_buy: false,
             sol_amt: 123123,
             token_amt: 456456,
+            trader_sol_delta: -123123,
+            trader_token_delta: 456456,
             price_sol: 0.22222,
+            price_sol_decimal: "0.22222".to_string(),
+            quote_mint: WSOL_MINT,
+            price_quote: 0.22222,
+            quote_amt: 123123,
+            pool_quote_amt: 100,
+            price_usd: None,
+            sol_price_usd_at_trade: None,
+            price_outlier: false,
+            source: None,
+            avg_prices: vec![],
+            cluster_id: None,
+            pool_drained: false,
+            pool_age_secs: None,
+            pool_trade_seq: 0,
         });
         println!("trade evt: {}", serde_json::to_string(&evt).unwrap());
         let v = serde_json::to_value(&evt).unwrap();
@@ -124,8 +504,21 @@ mod test {
             mint_b: RAYDIUM_AMM_PROGRAM_ID,
             decimals_a: 9,
             decimals_b: 6,
+            init_liquidity: None,
+            creator_funded_by: None,
+            source: None,
         });
         println!("pool created evt: {}", serde_json::to_string(&evt).unwrap());
+        let bytes = encode_dex_evt(&evt).unwrap();
+        assert!(matches!(
+            decode_dex_evt(&bytes).unwrap(),
+            DexEvent::PoolCreated(DexPoolCreatedRecord { idx: 6, .. })
+        ));
+        let json = serde_json::to_vec(&evt).unwrap();
+        assert!(matches!(
+            decode_dex_evt(&json).unwrap(),
+            DexEvent::PoolCreated(DexPoolCreatedRecord { slot: 1, .. })
+        ));
         let v = serde_json::to_value(&evt).unwrap();
         assert_eq!(
             v.get("kind").and_then(|it| it.as_str()),
@@ -133,8 +526,8 @@ mod test {
         );
 
         let (a, b) = ("a", 4);
-        let (mut to_be, max_int, z) = (false, 1 << 30, "a");
-        to_be = true;
+        let (_max_int, _z) = (1 << 30, "a");
+        let to_be = true;
         let name_of_val = type_name_of_val(&to_be);
         println!("{}", name_of_val);
         println!("{}", a);
@@ -145,11 +538,67 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_set_source() {
+        let meta = QnStreamMetadata {
+            batch_end_range: 20,
+            batch_start_range: 10,
+            dataset: "block".to_string(),
+            end_range: -1,
+            keep_distance_from_tip: 0,
+            network: "solana-mainnet".to_string(),
+            start_range: 0,
+            stream_id: "stream1".to_string(),
+            stream_name: "dex".to_string(),
+            stream_region: "usa_east".to_string(),
+        };
+        let source = meta.event_source("batch1");
+        assert_eq!(
+            (source.stream_id.as_str(), source.batch_id.as_str()),
+            ("stream1", "batch1")
+        );
+        assert_eq!((source.batch_start_range, source.batch_end_range), (10, 20));
+
+        let mut evt = DexEvent::PoolCreated(DexPoolCreatedRecord {
+            blk_ts: Utc::now(),
+            slot: 12,
+            txid: "txid".to_string(),
+            idx: 0,
+            creator: RAYDIUM_AMM_PROGRAM_ID,
+            addr: WSOL_MINT,
+            dex: Dex::Pumpfun,
+            mint_a: WSOL_MINT,
+            mint_b: RAYDIUM_AMM_PROGRAM_ID,
+            decimals_a: 9,
+            decimals_b: 6,
+            init_liquidity: None,
+            creator_funded_by: None,
+            source: None,
+        });
+        let v = serde_json::to_value(&evt).unwrap();
+        assert!(v.get("source").is_none());
+        evt.set_source(&source);
+        let DexEvent::PoolCreated(record) = &evt else {
+            panic!("not a pool created event: {evt:?}");
+        };
+        assert_eq!(record.source.as_ref(), Some(&source));
+        let v = serde_json::to_value(&evt).unwrap();
+        assert_eq!(v["source"]["batch_id"], "batch1");
+
+        let mut evt = DexEvent::MintInactive(MintInactiveRecord {
+            mint: WSOL_MINT,
+            last_trade_ts: Utc::now(),
+        });
+        evt.set_source(&source);
+        let v = serde_json::to_value(&evt).unwrap();
+        assert!(v.get("source").is_none());
+    }
+
     ///牛顿法求平方根
     #[test]
     pub fn find_sqr_of_42() {
         let x = 42f64;
-        let mut z = x as f64 / 2.0;
+        let mut z = x / 2.0;
         let mut counter = 0;
 
         let now = std::time::Instant::now();
@@ -168,7 +617,7 @@ mod test {
 
     #[test]
     fn test_slice() {
-        let v = vec![2, 3, 5, 7, 11, 13];
+        let v = [2, 3, 5, 7, 11, 13];
         let mut s = &v[..];
         println!("slice1: {:?}", s);
         s = &s[..4];
@@ -188,24 +637,18 @@ mod test {
     fn test_wc() {
         let s = "hello, this is major tom. hello, major tom, this is your captain speaking.";
         let mut map = HashMap::new();
-        let _ = s
-            .split(' ')
-            .into_iter()
+        s.split(' ')
             .for_each(|word| *map.entry(word).or_insert(0) += 1);
 
         println!("map:{:?}", map);
     }
 
-    pub fn test1(f1: fn(u8, u8)->u8) -> u8 {
+    pub fn test1(f1: fn(u8, u8) -> u8) -> u8 {
         f1(1, 2)
     }
 
     #[test]
     fn test_fn() {
-
+        assert_eq!(test1(|a, b| a + b), 3);
     }
 }
-
-
-
-
@@ -0,0 +1,181 @@
+use std::{collections::HashMap, str::FromStr};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc, serde::ts_seconds};
+use redis::aio::MultiplexedConnection;
+use serde::Serialize;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use strum::IntoEnumIterator;
+
+use crate::common::Dex;
+
+use super::{TradeRecord, queue};
+
+const HOUR_SECS: i64 = 3600;
+/// Hours of the past days are kept this long
+const DEX_VOLUME_TTL_SECS: i64 = 8 * 24 * HOUR_SECS;
+
+/// hash of an hour by block timestamp, `v:{dex}` lamports traded and `c:{dex}` trade counts
+fn dex_volume_key(hour_start: i64) -> String {
+    format!("hash:dex_volume:{hour_start}")
+}
+
+fn hour_start(ts: i64) -> i64 {
+    ts - ts.rem_euclid(HOUR_SECS)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DexVolume {
+    pub dex: Dex,
+    pub trades: u64,
+    pub sol_volume: f64,
+    /// of the SOL volume of all dexes, 0 without trades
+    pub share: f64,
+}
+
+/// SOL volume of the dexes since `start`
+#[derive(Debug, Clone, Serialize)]
+pub struct DexShare {
+    #[serde(with = "ts_seconds")]
+    pub start: DateTime<Utc>,
+    pub trades: u64,
+    pub sol_volume: f64,
+    pub dexes: Vec<DexVolume>,
+}
+
+impl DexShare {
+    fn from_hours(start: DateTime<Utc>, hours: &[HashMap<String, u64>]) -> Self {
+        let mut volumes: HashMap<Dex, (u64, u64)> = HashMap::new();
+        for hour in hours {
+            for (field, value) in hour {
+                let Some((counter, dex)) = field.split_once(':') else {
+                    continue;
+                };
+                let Ok(dex) = Dex::from_str(dex) else {
+                    continue;
+                };
+                let (trades, lamports) = volumes.entry(dex).or_default();
+                match counter {
+                    "c" => *trades += value,
+                    "v" => *lamports += value,
+                    _ => {}
+                }
+            }
+        }
+
+        let total_lamports: u64 = volumes.values().map(|(_, it)| it).sum();
+        let dexes: Vec<_> = Dex::iter()
+            .map(|dex| {
+                let (trades, lamports) = volumes.get(&dex).copied().unwrap_or_default();
+                let share = if total_lamports == 0 {
+                    0.0
+                } else {
+                    lamports as f64 / total_lamports as f64
+                };
+                DexVolume {
+                    dex,
+                    trades,
+                    sol_volume: lamports as f64 / LAMPORTS_PER_SOL as f64,
+                    share,
+                }
+            })
+            .collect();
+        Self {
+            start,
+            trades: dexes.iter().map(|it| it.trades).sum(),
+            sol_volume: total_lamports as f64 / LAMPORTS_PER_SOL as f64,
+            dexes,
+        }
+    }
+}
+
+/// Add the trades to the SOL volume of their dex in the hour of their block timestamp
+pub async fn record_dex_volumes(
+    conn: &mut MultiplexedConnection,
+    batch_id: &str,
+    trades: &[&TradeRecord],
+) -> Result<()> {
+    if trades.is_empty() {
+        return Ok(());
+    }
+
+    let mut hours: HashMap<(i64, Dex), (u64, u64)> = HashMap::new();
+    for trade in trades {
+        let hour = hour_start(trade.blk_ts.timestamp());
+        let (count, lamports) = hours.entry((hour, trade.dex)).or_default();
+        *count += 1;
+        *lamports += trade.sol_amt;
+    }
+
+    let mut pipe = redis::pipe();
+    for ((hour, dex), (count, lamports)) in hours {
+        let key = dex_volume_key(hour);
+        pipe.hincr(&key, format!("c:{dex}"), count).ignore();
+        pipe.hincr(&key, format!("v:{dex}"), lamports).ignore();
+        pipe.expire(&key, DEX_VOLUME_TTL_SECS).ignore();
+    }
+    queue::query_once(conn, batch_id, "dex_volume", &mut pipe).await
+}
+
+/// Market share of the dexes from the start of the hour of `from` until now
+pub async fn dex_share_since(
+    conn: &mut MultiplexedConnection,
+    from: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<DexShare> {
+    let first = hour_start(from.timestamp());
+    let last = hour_start(now.timestamp());
+    let mut pipe = redis::pipe();
+    for hour in (first..=last).step_by(HOUR_SECS as usize) {
+        pipe.hgetall(dex_volume_key(hour));
+    }
+    let hours: Vec<HashMap<String, u64>> = pipe.query_async(conn).await?;
+    let start = DateTime::from_timestamp(first, 0).unwrap_or(from);
+    Ok(DexShare::from_hours(start, &hours))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dex_share() {
+        let hours = vec![
+            HashMap::from([
+                ("c:Pumpfun".to_string(), 3),
+                ("v:Pumpfun".to_string(), 3 * LAMPORTS_PER_SOL),
+                ("c:PumpAmm".to_string(), 1),
+                ("v:PumpAmm".to_string(), LAMPORTS_PER_SOL),
+            ]),
+            HashMap::from([
+                ("c:Pumpfun".to_string(), 1),
+                ("v:Pumpfun".to_string(), 4 * LAMPORTS_PER_SOL),
+                ("v:Unknown".to_string(), LAMPORTS_PER_SOL),
+            ]),
+            HashMap::new(),
+        ];
+        let start = DateTime::from_timestamp(0, 0).unwrap();
+        let share = DexShare::from_hours(start, &hours);
+        assert_eq!(share.trades, 5);
+        assert_eq!(share.sol_volume, 8.0);
+        assert_eq!(share.dexes.len(), Dex::iter().count());
+
+        let pumpfun = share
+            .dexes
+            .iter()
+            .find(|it| it.dex == Dex::Pumpfun)
+            .unwrap();
+        assert_eq!(pumpfun.trades, 4);
+        assert_eq!(pumpfun.share, 7.0 / 8.0);
+        let raydium = share
+            .dexes
+            .iter()
+            .find(|it| it.dex == Dex::RaydiumAmm)
+            .unwrap();
+        assert_eq!(raydium.share, 0.0);
+
+        assert_eq!(hour_start(7_250), 7_200);
+        let empty = DexShare::from_hours(start, &[]);
+        assert!(empty.dexes.iter().all(|it| it.share == 0.0));
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use redis::aio::MultiplexedConnection;
+
+use super::DexEvent;
+
+/// Key of a queued event by the log of the transaction it was parsed from, the hops of a route
+/// share the instruction. None for the events not emitted by a transaction.
+fn event_seen_key(evt: &DexEvent, log_idx: usize) -> Option<String> {
+    let txid = evt.txid();
+    if txid.is_empty() {
+        return None;
+    }
+    Some(format!("str:evt_seen:{txid}:{log_idx}:{}", evt.kind()))
+}
+
+/// Drop the events queued already, e.g. of a stream batch delivered again or of requests claimed
+/// again after a restart, by the logs they were parsed from, in step with the events. The events
+/// not emitted by a transaction are kept. Returns the keys of the kept events, to mark them with
+/// `mark_dex_evts_seen` once queued.
+pub async fn drop_seen_dex_evts(
+    conn: &mut MultiplexedConnection,
+    events: &mut Vec<DexEvent>,
+    event_logs: &[usize],
+) -> Result<Vec<String>> {
+    let keys: Vec<_> = events
+        .iter()
+        .zip(event_logs)
+        .map(|(evt, log_idx)| event_seen_key(evt, *log_idx))
+        .collect();
+    if keys.iter().all(Option::is_none) {
+        return Ok(vec![]);
+    }
+
+    let mut pipe = redis::pipe();
+    for key in keys.iter().flatten() {
+        pipe.exists(key);
+    }
+    let queued: Vec<bool> = pipe.query_async(conn).await?;
+    let mut queued = queued.into_iter();
+    let mut keys = keys.into_iter();
+    let mut kept = HashSet::new();
+    // an event repeated within the batch is kept once
+    events.retain(|_| match keys.next().flatten() {
+        Some(key) => !queued.next().unwrap_or_default() && kept.insert(key),
+        None => true,
+    });
+    Ok(kept.into_iter().collect())
+}
+
+/// Remember the queued events for `ttl_secs`, after they're queued so an error in between
+/// doesn't lose them on the retry
+pub async fn mark_dex_evts_seen(
+    conn: &mut MultiplexedConnection,
+    keys: &[String],
+    ttl_secs: u64,
+) -> Result<()> {
+    if keys.is_empty() {
+        return Ok(());
+    }
+
+    let mut pipe = redis::pipe();
+    for key in keys {
+        pipe.set_ex(key, 1, ttl_secs).ignore();
+    }
+    let _: () = pipe.query_async(conn).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+    use crate::cache::{MintInactiveRecord, PumpfunCompleteRecord};
+
+    #[test]
+    fn test_event_seen_key() {
+        let ts = DateTime::from_timestamp(1, 0).unwrap();
+        let complete = DexEvent::PumpfunComplete(PumpfunCompleteRecord {
+            blk_ts: ts,
+            slot: 1,
+            txid: "tx".to_string(),
+            idx: 3,
+            user: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            bonding_curve: Pubkey::new_unique(),
+            source: None,
+        });
+        assert_eq!(
+            event_seen_key(&complete, 5).as_deref(),
+            Some("str:evt_seen:tx:5:PumpfunComplete")
+        );
+        let inactive = DexEvent::MintInactive(MintInactiveRecord {
+            mint: Pubkey::new_unique(),
+            last_trade_ts: ts,
+        });
+        assert_eq!(event_seen_key(&inactive, 0), None);
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::OnceLock;
+
+use anyhow::Result;
+use redis::aio::MultiplexedConnection;
+
+use crate::naming::NameTemplate;
+
+use super::{DexEvent, queue};
+
+/// channel of the published events, unset if they aren't published
+static EVENT_CHANNEL: OnceLock<NameTemplate> = OnceLock::new();
+
+/// Publish the queued events to their pub/sub channel too, set once on startup
+pub fn enable_event_pubsub(channel: NameTemplate) {
+    let _ = EVENT_CHANNEL.set(channel);
+}
+
+pub fn event_pubsub_enabled() -> bool {
+    EVENT_CHANNEL.get().is_some()
+}
+
+/// `PUBLISH` each event json to its channel, in one round trip, once for the claimed batch
+/// `batch_id` if it's retried
+pub async fn publish_dex_evts(
+    conn: &mut MultiplexedConnection,
+    batch_id: Option<&str>,
+    events: &[DexEvent],
+) -> Result<()> {
+    let Some(channel) = EVENT_CHANNEL.get() else {
+        return Ok(());
+    };
+    if events.is_empty() {
+        return Ok(());
+    }
+    let mut pipe = redis::pipe();
+    for evt in events {
+        pipe.publish(channel.render(evt), serde_json::to_string(evt)?)
+            .ignore();
+    }
+    match batch_id {
+        Some(batch_id) => queue::query_once(conn, batch_id, "publish", &mut pipe).await,
+        None => {
+            let _: () = pipe.query_async(conn).await?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::DateTime;
+    use futures::StreamExt;
+    use solana_sdk::pubkey::Pubkey;
+
+    use crate::cache::MintInactiveRecord;
+
+    use super::*;
+
+    #[tokio::test]
+    #[ignore = "needs redis, set TEST_REDIS_URL"]
+    async fn test_publish_dex_evts_once_per_batch() {
+        let redis_url = std::env::var("TEST_REDIS_URL").expect("TEST_REDIS_URL of a redis");
+        let client = redis::Client::open(redis_url).unwrap();
+        let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+        let mut pubsub = client.get_async_pubsub().await.unwrap();
+        pubsub.psubscribe("test_pubsub:*").await.unwrap();
+        enable_event_pubsub(NameTemplate::new("test_pubsub:{kind}"));
+
+        let events = [DexEvent::MintInactive(MintInactiveRecord {
+            mint: Pubkey::new_unique(),
+            last_trade_ts: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        })];
+        let batch_id = Pubkey::new_unique().to_string();
+        // the batch retried after an error, then events of no batch
+        for batch_id in [Some(batch_id.as_str()), Some(&batch_id), None] {
+            publish_dex_evts(&mut conn, batch_id, &events)
+                .await
+                .unwrap();
+        }
+
+        let mut messages = pubsub.on_message();
+        for _ in 0..2 {
+            let message = tokio::time::timeout(Duration::from_secs(2), messages.next())
+                .await
+                .expect("no published event")
+                .unwrap();
+            assert_eq!(message.get_channel_name(), "test_pubsub:mint_inactive");
+        }
+        let next = tokio::time::timeout(Duration::from_millis(500), messages.next()).await;
+        assert!(next.is_err(), "event published twice");
+    }
+}
@@ -0,0 +1,183 @@
+use chrono::{DateTime, Utc, serde::ts_seconds};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    common::{Dex, TxBaseMetaInfo, WSOL_MINT},
+    parse_error::{DexParseError, LogAccounts},
+    pumpfun::instruction::PumpfunInstructions,
+    qn_req_processor::{EventSource, ProgramInvocation, Tx},
+};
+
+/// A swap of a failed transaction, decoded from its instruction as the failed transactions log
+/// no event. The amounts are the ones asked, not traded.
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedSwapAttemptRecord {
+    #[serde(with = "ts_seconds")]
+    pub blk_ts: DateTime<Utc>,
+    pub slot: u64,
+    pub txid: String,
+    pub idx: u64,
+    pub dex: Dex,
+    #[serde_as(as = "DisplayFromStr")]
+    pub pool: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub trader: Pubkey,
+    pub is_buy: bool,
+    /// one of the amounts is exact, the other the slippage limit. The tokens are exact but for
+    /// the pump amm pools with SOL as the base mint.
+    pub token_amt: u64,
+    /// lamports
+    pub sol_amt: u64,
+    /// the transaction error as reported by the node, e.g. `{"InstructionError":[2,{"Custom":6002}]}`
+    pub err: String,
+    /// the stream batch the event was parsed from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<EventSource>,
+}
+
+/// The swaps attempted by a failed transaction, none for a successful one. The instructions of
+/// the other dexes and of the pump amm pools without SOL are left out.
+pub fn parse_failed_swaps(tx: &Tx) -> Result<Vec<FailedSwapAttemptRecord>, DexParseError> {
+    let Some(err) = &tx.err else {
+        return Ok(vec![]);
+    };
+    let blk_ts = DateTime::from_timestamp(tx.blk_ts, 0).ok_or_else(|| {
+        DexParseError::BadTx(format!("block timestamp {} out of range", tx.blk_ts))
+    })?;
+    let mut attempts = vec![];
+    for invocation in &tx.ixs {
+        let tx_meta = TxBaseMetaInfo {
+            blk_ts,
+            slot: tx.slot,
+            txid: tx.signature.clone(),
+            idx: invocation.instruction.index,
+        };
+        if let Some(attempt) = parse_failed_swap(invocation, tx_meta, err.to_string())? {
+            attempts.push(attempt);
+        }
+    }
+    Ok(attempts)
+}
+
+fn parse_failed_swap(
+    invocation: &ProgramInvocation,
+    tx_meta: TxBaseMetaInfo,
+    err: String,
+) -> Result<Option<FailedSwapAttemptRecord>, DexParseError> {
+    let dex = match Dex::from_program_id(&invocation.program_id) {
+        Some(dex @ (Dex::Pumpfun | Dex::PumpAmm)) => dex,
+        _ => return Ok(None),
+    };
+    let (is_buy, args) = match PumpfunInstructions::from_ix_data(&invocation.instruction.data, dex)?
+    {
+        PumpfunInstructions::Buy(args) => (true, args),
+        PumpfunInstructions::Sell(args) => (false, args),
+        PumpfunInstructions::Other(_) => return Ok(None),
+    };
+    let accounts = LogAccounts::new(&invocation.instruction.accounts, dex, "FailedSwapAttempt");
+    let (pool, mint, trader) = if dex == Dex::Pumpfun {
+        // global, fee recipient, mint, bonding curve, curve token account, user token account, user
+        (
+            accounts.pubkey(3)?,
+            accounts.pubkey(2)?,
+            accounts.pubkey(6)?,
+        )
+    } else {
+        // pool, user, global config, base mint, quote mint
+        (
+            accounts.pubkey(0)?,
+            accounts.pubkey(3)?,
+            accounts.pubkey(1)?,
+        )
+    };
+    let (mint, is_buy, token_amt, sol_amt) =
+        if dex == Dex::Pumpfun || accounts.pubkey(4)? == WSOL_MINT {
+            (mint, is_buy, args.token_amount, args.sol_limit)
+        } else if mint == WSOL_MINT {
+            // a buy of the SOL base sells the token of the quote
+            (
+                accounts.pubkey(4)?,
+                !is_buy,
+                args.sol_limit,
+                args.token_amount,
+            )
+        } else {
+            return Ok(None);
+        };
+
+    Ok(Some(FailedSwapAttemptRecord {
+        blk_ts: tx_meta.blk_ts,
+        slot: tx_meta.slot,
+        txid: tx_meta.txid,
+        idx: tx_meta.idx,
+        dex,
+        pool,
+        mint,
+        trader,
+        is_buy,
+        token_amt,
+        sol_amt,
+        err,
+        source: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use borsh::BorshSerialize;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{pumpamm::PUMPAMM_PROGRAM_ID, pumpfun::instruction::SELL_IX_ID};
+
+    #[test]
+    fn test_parse_failed_swaps() {
+        let mut data = SELL_IX_ID.to_vec();
+        BorshSerialize::serialize(&(5_000_000u64, 42u64), &mut data).unwrap();
+        let (pool, user, token) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let accounts: Vec<_> = [pool, user, Pubkey::new_unique(), WSOL_MINT, token]
+            .iter()
+            .map(
+                |it| json!({"pubkey": it.to_string(), "preAmt": {"sol": 0}, "postAmt": {"sol": 0}}),
+            )
+            .collect();
+        let tx = json!({
+            "blkTs": 100,
+            "slot": 10,
+            "signature": "sig",
+            "logs": [],
+            "ixs": [{
+                "programId": PUMPAMM_PROGRAM_ID.to_string(),
+                "instruction": {"accounts": accounts, "data": bs58::encode(&data).into_string(), "index": 2},
+            }],
+            "err": {"InstructionError": [2, {"Custom": 6004}]},
+        });
+        let mut tx: Tx = serde_json::from_value(tx).unwrap();
+        let attempts = parse_failed_swaps(&tx).unwrap();
+        assert_eq!(attempts.len(), 1);
+        let attempt = &attempts[0];
+        assert_eq!(
+            (attempt.pool, attempt.mint, attempt.trader),
+            (pool, token, user)
+        );
+        // selling the SOL base buys the token
+        assert!(attempt.is_buy);
+        assert_eq!(
+            (attempt.token_amt, attempt.sol_amt, attempt.idx),
+            (42, 5_000_000, 2)
+        );
+        assert_eq!(attempt.err, r#"{"InstructionError":[2,{"Custom":6004}]}"#);
+
+        tx.err = None;
+        assert!(parse_failed_swaps(&tx).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redis::{AsyncCommands, aio::MultiplexedConnection};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+
+use super::{TradeRecord, queue};
+
+/// set of the followed trader wallets
+const FOLLOWED_WALLETS_KEY: &str = "set:followed_wallets";
+/// trades of the followed wallets are counted per hour of block time
+const TRADER_STATS_SLOT_SECS: i64 = 3600;
+/// recent stats of a followed wallet cover this many seconds
+pub const TRADER_STATS_WINDOW_SECS: i64 = 24 * 3600;
+
+/// hash of one hour of trades of a wallet, `t` trades, `b` buys, `vb` bought and `vs` sold lamports
+fn slot_key(wallet: &Pubkey, slot_start: i64) -> String {
+    format!("hash:trader_stats:{wallet}:{slot_start}")
+}
+
+fn slot_start(ts: i64) -> i64 {
+    ts - ts.rem_euclid(TRADER_STATS_SLOT_SECS)
+}
+
+/// Trades of a wallet over the last `window_secs`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TraderStats {
+    pub window_secs: i64,
+    pub trades: u64,
+    pub buys: u64,
+    pub sells: u64,
+    pub sol_bought: f64,
+    pub sol_sold: f64,
+    /// sol_sold - sol_bought
+    pub net_sol: f64,
+}
+
+impl TraderStats {
+    fn from_slots(window_secs: i64, slots: &[HashMap<String, u64>]) -> Self {
+        let sum = |field| -> u64 { slots.iter().filter_map(|it| it.get(field)).copied().sum() };
+        let (trades, buys) = (sum("t"), sum("b"));
+        let sol_bought = sum("vb") as f64 / LAMPORTS_PER_SOL as f64;
+        let sol_sold = sum("vs") as f64 / LAMPORTS_PER_SOL as f64;
+        Self {
+            window_secs,
+            trades,
+            buys,
+            sells: trades.saturating_sub(buys),
+            sol_bought,
+            sol_sold,
+            net_sol: sol_sold - sol_bought,
+        }
+    }
+}
+
+/// A trade of a followed wallet, with its recent stats, this trade included
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FollowedWalletTradeRecord {
+    pub trade: TradeRecord,
+    pub stats: TraderStats,
+}
+
+/// Returns the wallets not followed before
+pub async fn follow_wallets(conn: &mut MultiplexedConnection, wallets: &[Pubkey]) -> Result<u64> {
+    if wallets.is_empty() {
+        return Ok(0);
+    }
+    let wallets: Vec<_> = wallets.iter().map(Pubkey::to_string).collect();
+    let added: u64 = conn.sadd(FOLLOWED_WALLETS_KEY, wallets).await?;
+    Ok(added)
+}
+
+pub async fn unfollow_wallet(conn: &mut MultiplexedConnection, wallet: &Pubkey) -> Result<bool> {
+    let removed: u64 = conn.srem(FOLLOWED_WALLETS_KEY, wallet.to_string()).await?;
+    Ok(removed > 0)
+}
+
+pub async fn followed_wallets(conn: &mut MultiplexedConnection) -> Result<Vec<Pubkey>> {
+    let wallets: Vec<String> = conn.smembers(FOLLOWED_WALLETS_KEY).await?;
+    let mut wallets: Vec<Pubkey> = wallets.iter().filter_map(|it| it.parse().ok()).collect();
+    wallets.sort();
+    Ok(wallets)
+}
+
+/// The traders of the trades which are followed
+pub async fn followed_traders(
+    conn: &mut MultiplexedConnection,
+    trades: &[&TradeRecord],
+) -> Result<HashSet<Pubkey>> {
+    let traders: Vec<_> = trades
+        .iter()
+        .map(|it| it.trader)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    if traders.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let members: Vec<String> = traders.iter().map(Pubkey::to_string).collect();
+    let followed: Vec<bool> = conn.smismember(FOLLOWED_WALLETS_KEY, members).await?;
+    Ok(traders
+        .into_iter()
+        .zip(followed)
+        .filter_map(|(trader, followed)| followed.then_some(trader))
+        .collect())
+}
+
+/// Count the trades into the hourly stats of their traders, once per claimed batch `batch_id`
+pub async fn record_trader_stats(
+    conn: &mut MultiplexedConnection,
+    batch_id: &str,
+    trades: &[&TradeRecord],
+) -> Result<()> {
+    if trades.is_empty() {
+        return Ok(());
+    }
+
+    let ttl = TRADER_STATS_WINDOW_SECS + TRADER_STATS_SLOT_SECS;
+    let mut pipe = redis::pipe();
+    for trade in trades {
+        let key = slot_key(&trade.trader, slot_start(trade.blk_ts.timestamp()));
+        // the volumes are in SOL, `sol_amt` is zero for the other quote mints
+        pipe.hincr(&key, "t", 1).ignore();
+        if trade.is_buy {
+            pipe.hincr(&key, "b", 1).ignore();
+            pipe.hincr(&key, "vb", trade.sol_amt).ignore();
+        } else {
+            pipe.hincr(&key, "vs", trade.sol_amt).ignore();
+        }
+        pipe.expire(&key, ttl).ignore();
+    }
+    queue::query_once(conn, batch_id, "trader_stats", &mut pipe).await
+}
+
+/// Stats of each wallet over the `TRADER_STATS_WINDOW_SECS` before `now`
+pub async fn trader_stats(
+    conn: &mut MultiplexedConnection,
+    wallets: &[Pubkey],
+    now: DateTime<Utc>,
+) -> Result<HashMap<Pubkey, TraderStats>> {
+    let last = slot_start(now.timestamp());
+    let first = slot_start(now.timestamp() - TRADER_STATS_WINDOW_SECS) + TRADER_STATS_SLOT_SECS;
+    let slots: Vec<_> = (first..=last)
+        .step_by(TRADER_STATS_SLOT_SECS as usize)
+        .collect();
+
+    let mut pipe = redis::pipe();
+    for wallet in wallets {
+        for slot in &slots {
+            pipe.hgetall(slot_key(wallet, *slot));
+        }
+    }
+    let stats: Vec<HashMap<String, u64>> = pipe.query_async(conn).await?;
+    Ok(wallets
+        .iter()
+        .zip(stats.chunks(slots.len().max(1)))
+        .map(|(wallet, slots)| {
+            let stats = TraderStats::from_slots(TRADER_STATS_WINDOW_SECS, slots);
+            (*wallet, stats)
+        })
+        .collect())
+}
+
+/// Count the trades of the followed wallets into their stats, once per claimed batch
+/// `batch_id`, and return them with the stats
+pub async fn followed_wallet_trades(
+    conn: &mut MultiplexedConnection,
+    batch_id: &str,
+    trades: &[&TradeRecord],
+) -> Result<Vec<FollowedWalletTradeRecord>> {
+    let followed = followed_traders(conn, trades).await?;
+    if followed.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let trades: Vec<_> = trades
+        .iter()
+        .filter(|it| followed.contains(&it.trader))
+        .copied()
+        .collect();
+    record_trader_stats(conn, batch_id, &trades).await?;
+    let wallets: Vec<_> = followed.into_iter().collect();
+    let stats = trader_stats(conn, &wallets, Utc::now()).await?;
+    Ok(trades
+        .into_iter()
+        .map(|trade| FollowedWalletTradeRecord {
+            trade: trade.clone(),
+            stats: stats.get(&trade.trader).cloned().unwrap_or_default(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trader_stats_from_slots() {
+        let slots = vec![
+            HashMap::from([
+                ("t".to_string(), 3),
+                ("b".to_string(), 2),
+                ("vb".to_string(), 2 * LAMPORTS_PER_SOL),
+                ("vs".to_string(), LAMPORTS_PER_SOL / 2),
+            ]),
+            HashMap::from([("t".to_string(), 1), ("vs".to_string(), LAMPORTS_PER_SOL)]),
+            HashMap::new(),
+        ];
+        let stats = TraderStats::from_slots(TRADER_STATS_WINDOW_SECS, &slots);
+        assert_eq!(stats.trades, 4);
+        assert_eq!(stats.buys, 2);
+        assert_eq!(stats.sells, 2);
+        assert_eq!(stats.sol_bought, 2.0);
+        assert_eq!(stats.sol_sold, 1.5);
+        assert_eq!(stats.net_sol, -0.5);
+        assert_eq!(slot_start(7200 + 59), 7200);
+    }
+}
@@ -0,0 +1,165 @@
+use std::{collections::HashMap, str::FromStr};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc, serde::ts_seconds};
+use redis::aio::MultiplexedConnection;
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+use crate::common::Dex;
+
+use super::{DexEvent, DexEventKind, queue};
+
+const MINUTE_SECS: i64 = 60;
+/// Minutes are kept this long, for the coverage trends
+const INGESTION_AUDIT_TTL_SECS: i64 = 90 * 24 * 3600;
+/// Minutes returned by one query at most, a day
+pub const MAX_AUDIT_MINUTES: i64 = 24 * 60;
+
+/// hash of a minute by block timestamp, `{dex}:{kind}` counts of the events parsed
+fn ingestion_audit_key(minute_start: i64) -> String {
+    format!("hash:ingestion_audit:{minute_start}")
+}
+
+fn minute_start(ts: i64) -> i64 {
+    ts - ts.rem_euclid(MINUTE_SECS)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IngestionCount {
+    pub dex: Dex,
+    pub kind: DexEventKind,
+    pub events: u64,
+}
+
+/// Events parsed in a minute of block time, by dex and kind
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IngestionMinute {
+    #[serde(with = "ts_seconds")]
+    pub minute: DateTime<Utc>,
+    pub events: u64,
+    /// empty for a minute without events
+    pub counts: Vec<IngestionCount>,
+}
+
+impl IngestionMinute {
+    fn from_fields(minute: DateTime<Utc>, fields: &HashMap<String, u64>) -> Self {
+        let mut counts: Vec<_> = fields
+            .iter()
+            .filter_map(|(field, events)| {
+                let (dex, kind) = field.split_once(':')?;
+                Some(IngestionCount {
+                    dex: Dex::from_str(dex).ok()?,
+                    kind: DexEventKind::iter().find(|it| it.to_string() == kind)?,
+                    events: *events,
+                })
+            })
+            .collect();
+        counts.sort_by_key(|it| (it.dex.to_string(), it.kind.to_string()));
+        Self {
+            minute,
+            events: counts.iter().map(|it| it.events).sum(),
+            counts,
+        }
+    }
+}
+
+/// Count the events parsed from transactions by the minute of their block, their dex and kind
+pub async fn record_ingestion_audit(
+    conn: &mut MultiplexedConnection,
+    batch_id: &str,
+    events: &[DexEvent],
+) -> Result<()> {
+    let mut minutes: HashMap<(i64, Dex, DexEventKind), u64> = HashMap::new();
+    for evt in events {
+        let (Some(dex), Some(blk_ts)) = (evt.dex(), evt.blk_ts()) else {
+            continue;
+        };
+        let minute = minute_start(blk_ts.timestamp());
+        *minutes.entry((minute, dex, evt.kind())).or_default() += 1;
+    }
+    if minutes.is_empty() {
+        return Ok(());
+    }
+
+    let mut pipe = redis::pipe();
+    for ((minute, dex, kind), count) in minutes {
+        let key = ingestion_audit_key(minute);
+        pipe.hincr(&key, format!("{dex}:{kind}"), count).ignore();
+        pipe.expire(&key, INGESTION_AUDIT_TTL_SECS).ignore();
+    }
+    queue::query_once(conn, batch_id, "ingestion_audit", &mut pipe).await
+}
+
+/// The minutes from the one of `from` to the one of `to`, at most `MAX_AUDIT_MINUTES` from
+/// `from`, the minutes without events included
+pub async fn ingestion_audit(
+    conn: &mut MultiplexedConnection,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<IngestionMinute>> {
+    let first = minute_start(from.timestamp());
+    let last = minute_start(to.timestamp()).min(first + (MAX_AUDIT_MINUTES - 1) * MINUTE_SECS);
+    if last < first {
+        return Ok(vec![]);
+    }
+    let starts: Vec<_> = (first..=last).step_by(MINUTE_SECS as usize).collect();
+    let mut pipe = redis::pipe();
+    for start in &starts {
+        pipe.hgetall(ingestion_audit_key(*start));
+    }
+    let minutes: Vec<HashMap<String, u64>> = pipe.query_async(conn).await?;
+    Ok(starts
+        .into_iter()
+        .zip(minutes)
+        .filter_map(|(start, fields)| {
+            let minute = DateTime::from_timestamp(start, 0)?;
+            Some(IngestionMinute::from_fields(minute, &fields))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ingestion_minute() {
+        let minute = DateTime::from_timestamp(120, 0).unwrap();
+        let fields = HashMap::from([
+            ("PumpAmm:Trade".to_string(), 3),
+            ("Pumpfun:PoolCreated".to_string(), 1),
+            ("Pumpfun:Trade".to_string(), 5),
+            ("Unknown:Trade".to_string(), 2),
+            ("Pumpfun:Unknown".to_string(), 2),
+        ]);
+        let audit = IngestionMinute::from_fields(minute, &fields);
+        assert_eq!(audit.events, 9);
+        assert_eq!(
+            audit.counts,
+            vec![
+                IngestionCount {
+                    dex: Dex::PumpAmm,
+                    kind: DexEventKind::Trade,
+                    events: 3,
+                },
+                IngestionCount {
+                    dex: Dex::Pumpfun,
+                    kind: DexEventKind::PoolCreated,
+                    events: 1,
+                },
+                IngestionCount {
+                    dex: Dex::Pumpfun,
+                    kind: DexEventKind::Trade,
+                    events: 5,
+                },
+            ]
+        );
+        assert!(
+            IngestionMinute::from_fields(minute, &HashMap::new())
+                .counts
+                .is_empty()
+        );
+        assert_eq!(minute_start(179), 120);
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc, serde::ts_seconds};
+use redis::aio::MultiplexedConnection;
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::common::Dex;
+
+use super::TradeRecord;
+
+/// `change_5m_pct` compares the last price with the price this long ago
+pub const PRICE_CHANGE_SECS: i64 = 300;
+/// price samples are kept a while longer than the change window, for mints traded rarely
+const PRICE_SAMPLES_TTL_SECS: i64 = 2 * PRICE_CHANGE_SECS;
+
+/// Last price of a mint, or of a pool, by the address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PriceOf {
+    Mint,
+    Pool,
+}
+
+impl PriceOf {
+    /// hash of address -> `LastPrice` json
+    fn key(&self) -> &'static str {
+        match self {
+            PriceOf::Mint => "hash:last_prices:mint",
+            PriceOf::Pool => "hash:last_prices:pool",
+        }
+    }
+}
+
+/// sorted set of the recent prices of a mint or pool, `{slot}:{idx}:{price_sol}` scored by
+/// block timestamp
+fn price_samples_key(addr: &Pubkey) -> String {
+    format!("zset:price_samples:{addr}")
+}
+
+/// The price of the latest trade of a mint or pool
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LastPrice {
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub pool: Pubkey,
+    pub dex: Dex,
+    pub price_sol: f64,
+    /// `price_sol` as an exact decimal string
+    #[serde(default)]
+    pub price_sol_decimal: String,
+    pub slot: u64,
+    pub txid: String,
+    #[serde(with = "ts_seconds")]
+    pub blk_ts: DateTime<Utc>,
+}
+
+impl From<&TradeRecord> for LastPrice {
+    fn from(trade: &TradeRecord) -> Self {
+        Self {
+            mint: trade.mint,
+            pool: trade.pool,
+            dex: trade.dex,
+            price_sol: trade.price_sol,
+            price_sol_decimal: trade.price_sol_decimal.clone(),
+            slot: trade.slot,
+            txid: trade.txid.clone(),
+            blk_ts: trade.blk_ts,
+        }
+    }
+}
+
+/// The last price with its age and change over `PRICE_CHANGE_SECS`
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceQuote {
+    #[serde(flatten)]
+    pub last: LastPrice,
+    /// seconds since the block of the last trade
+    pub age_secs: i64,
+    /// none without a trade before the window
+    pub change_5m_pct: Option<f64>,
+}
+
+fn sample_price(sample: &str) -> Option<f64> {
+    sample.rsplit(':').next()?.parse().ok()
+}
+
+fn change_pct(price: f64, prev: f64) -> Option<f64> {
+    let change = (price - prev) / prev * 100.0;
+    change.is_finite().then_some(change)
+}
+
+/// Keep the price of the latest trade of each mint and pool, the outliers flagged by the
+/// price guard are left out
+pub async fn record_last_prices(
+    conn: &mut MultiplexedConnection,
+    trades: &[&TradeRecord],
+) -> Result<()> {
+    let mut latest: HashMap<(PriceOf, Pubkey), &TradeRecord> = HashMap::new();
+    for trade in trades.iter().filter(|it| !it.price_outlier) {
+        for key in [(PriceOf::Mint, trade.mint), (PriceOf::Pool, trade.pool)] {
+            let last = latest.entry(key).or_insert(trade);
+            if (trade.slot, trade.idx) > (last.slot, last.idx) {
+                *last = trade;
+            }
+        }
+    }
+    if latest.is_empty() {
+        return Ok(());
+    }
+
+    let mut pipe = redis::pipe();
+    for ((of, addr), trade) in latest {
+        let last = serde_json::to_string(&LastPrice::from(trade))?;
+        pipe.hset(of.key(), addr.to_string(), last).ignore();
+
+        let key = price_samples_key(&addr);
+        let ts = trade.blk_ts.timestamp();
+        let sample = format!("{}:{}:{}", trade.slot, trade.idx, trade.price_sol);
+        pipe.zadd(&key, sample, ts).ignore();
+        let expired = format!("({}", ts - PRICE_SAMPLES_TTL_SECS);
+        pipe.zrembyscore(&key, "-inf", expired).ignore();
+        pipe.expire(&key, PRICE_SAMPLES_TTL_SECS).ignore();
+    }
+    let _: () = pipe.query_async(conn).await?;
+    Ok(())
+}
+
+/// Last price of the mint or pool, in one round trip
+pub async fn price_quote(
+    conn: &mut MultiplexedConnection,
+    of: PriceOf,
+    addr: &Pubkey,
+    now: DateTime<Utc>,
+) -> Result<Option<PriceQuote>> {
+    let window_start = now.timestamp() - PRICE_CHANGE_SECS;
+    let (last, prev): (Option<String>, Vec<String>) = redis::pipe()
+        .hget(of.key(), addr.to_string())
+        .zrevrangebyscore_limit(price_samples_key(addr), window_start, "-inf", 0, 1)
+        .query_async(conn)
+        .await?;
+    let Some(last) = last else {
+        return Ok(None);
+    };
+
+    let last: LastPrice = serde_json::from_str(&last)?;
+    let change_5m_pct = prev
+        .first()
+        .and_then(|it| sample_price(it))
+        .and_then(|prev| change_pct(last.price_sol, prev));
+    Ok(Some(PriceQuote {
+        age_secs: (now - last.blk_ts).num_seconds().max(0),
+        change_5m_pct,
+        last,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_change() {
+        assert_eq!(sample_price("300:2:0.000125"), Some(0.000125));
+        assert_eq!(sample_price("300:2:"), None);
+        assert_eq!(change_pct(0.75, 0.5), Some(50.0));
+        assert_eq!(change_pct(0.25, 0.5), Some(-50.0));
+        assert_eq!(change_pct(0.5, 0.0), None);
+    }
+
+    #[test]
+    fn test_last_price_decimal() {
+        let trade: TradeRecord = serde_json::from_value(serde_json::json!({
+            "blk_ts": 0, "slot": 1, "txid": "tx", "idx": 0,
+            "mint": Pubkey::new_unique().to_string(), "decimals": 6,
+            "trader": Pubkey::new_unique().to_string(), "dex": "Pumpfun",
+            "pool": Pubkey::new_unique().to_string(), "pool_sol_amt": 0, "pool_token_amt": 0,
+            "is_buy": true, "sol_amt": 1, "token_amt": 1_000_000_000_000_000_000u64,
+            "price_sol": 1e-21, "price_sol_decimal": "0.000000000000000000001",
+        }))
+        .unwrap();
+        let last = LastPrice::from(&trade);
+        assert_eq!(last.price_sol_decimal, "0.000000000000000000001");
+
+        // prices kept before the decimal was added
+        let mut json = serde_json::to_value(&last).unwrap();
+        json.as_object_mut().unwrap().remove("price_sol_decimal");
+        let old: LastPrice = serde_json::from_value(json).unwrap();
+        assert_eq!(old.price_sol_decimal, "");
+    }
+}
@@ -0,0 +1,354 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redis::aio::MultiplexedConnection;
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+
+use super::{TradeRecord, queue};
+
+/// Trades are counted in buckets of this many seconds, by block timestamp
+const LEADERBOARD_BUCKET_SECS: i64 = 60;
+/// Buckets older than the longest window expire
+const LEADERBOARD_TTL_SECS: i64 = 3600 + LEADERBOARD_BUCKET_SECS;
+/// the union of the buckets of a window is only kept for the query
+const LEADERBOARD_UNION_TTL_SECS: i64 = 5;
+/// the buy sell ratio ranks the most traded mints of the window only, so a mint with a single
+/// buy doesn't lead
+const RATIO_CANDIDATES: usize = 500;
+
+/// counters of the buckets, `v` lamports traded and `c` trades
+const COUNTERS: [&str; 2] = ["v", "c"];
+
+/// sorted set of the mints traded in a bucket, scored by one counter
+fn bucket_key(counter: &str, bucket_start: i64) -> String {
+    format!("zset:leaderboard:{counter}:{bucket_start}")
+}
+
+/// hyperloglog of the distinct `buyers` or `sellers` of a mint in a bucket, their count over a
+/// window is the count of the union of its buckets
+fn traders_key(side: &str, mint: &str, bucket_start: i64) -> String {
+    format!("hll:leaderboard:{side}:{mint}:{bucket_start}")
+}
+
+fn union_key(counter: &str, window: LeaderboardWindow) -> String {
+    format!("zset:leaderboard:{counter}:{}", window.name())
+}
+
+fn bucket_start(ts: i64) -> i64 {
+    ts - ts.rem_euclid(LEADERBOARD_BUCKET_SECS)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardMetric {
+    /// SOL traded
+    #[default]
+    Volume,
+    Trades,
+    /// distinct buyers per distinct seller
+    BuySellRatio,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum LeaderboardWindow {
+    #[default]
+    #[serde(rename = "5m")]
+    FiveMins,
+    #[serde(rename = "1h")]
+    OneHour,
+}
+
+impl LeaderboardWindow {
+    fn name(&self) -> &'static str {
+        match self {
+            LeaderboardWindow::FiveMins => "5m",
+            LeaderboardWindow::OneHour => "1h",
+        }
+    }
+
+    fn secs(&self) -> i64 {
+        match self {
+            LeaderboardWindow::FiveMins => 300,
+            LeaderboardWindow::OneHour => 3600,
+        }
+    }
+
+    /// the buckets of the window up to the current one, which is still filling
+    fn bucket_starts(&self, now: i64) -> impl Iterator<Item = i64> {
+        let last = bucket_start(now);
+        let first = last - self.secs() + LEADERBOARD_BUCKET_SECS;
+        (first..=last).step_by(LEADERBOARD_BUCKET_SECS as usize)
+    }
+}
+
+#[serde_as]
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct LeaderboardEntry {
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    /// in SOL for the volume
+    pub value: f64,
+}
+
+/// The trades of a mint in a bucket
+#[derive(Debug, Default, PartialEq)]
+struct BucketTrades {
+    /// in the order of `COUNTERS`
+    counters: [u64; 2],
+    buyers: HashSet<Pubkey>,
+    sellers: HashSet<Pubkey>,
+}
+
+/// The trades by bucket and mint, the ones too old for any window left out
+fn bucket_trades(trades: &[&TradeRecord], now: i64) -> HashMap<(i64, Pubkey), BucketTrades> {
+    let oldest = bucket_start(now) - LeaderboardWindow::OneHour.secs();
+    let mut buckets: HashMap<(i64, Pubkey), BucketTrades> = HashMap::new();
+    for trade in trades {
+        let bucket = bucket_start(trade.blk_ts.timestamp());
+        if bucket <= oldest {
+            continue;
+        }
+        let it = buckets.entry((bucket, trade.mint)).or_default();
+        it.counters[0] += trade.sol_amt;
+        it.counters[1] += 1;
+        if trade.is_buy {
+            it.buyers.insert(trade.trader);
+        } else {
+            it.sellers.insert(trade.trader);
+        }
+    }
+    buckets
+}
+
+/// Add the SOL trades to the leaderboard buckets of their mints
+pub async fn record_leaderboard(
+    conn: &mut MultiplexedConnection,
+    batch_id: &str,
+    trades: &[&TradeRecord],
+) -> Result<()> {
+    let buckets = bucket_trades(trades, Utc::now().timestamp());
+    if buckets.is_empty() {
+        return Ok(());
+    }
+
+    let mut pipe = redis::pipe();
+    for ((bucket, mint), it) in &buckets {
+        let mint = mint.to_string();
+        for (counter, value) in COUNTERS.iter().zip(it.counters) {
+            if value > 0 {
+                pipe.zincr(bucket_key(counter, *bucket), &mint, value)
+                    .ignore();
+            }
+        }
+        for (side, traders) in [("buyers", &it.buyers), ("sellers", &it.sellers)] {
+            if traders.is_empty() {
+                continue;
+            }
+            let key = traders_key(side, &mint, *bucket);
+            let traders: Vec<_> = traders.iter().map(|it| it.to_string()).collect();
+            pipe.pfadd(&key, traders)
+                .ignore()
+                .expire(&key, LEADERBOARD_TTL_SECS)
+                .ignore();
+        }
+    }
+    let mut expired: Vec<_> = buckets.keys().map(|(bucket, _)| *bucket).collect();
+    expired.sort();
+    expired.dedup();
+    for bucket in expired {
+        for counter in COUNTERS {
+            pipe.expire(bucket_key(counter, bucket), LEADERBOARD_TTL_SECS)
+                .ignore();
+        }
+    }
+    queue::query_once(conn, batch_id, "leaderboard", &mut pipe).await
+}
+
+/// Sum the buckets of the window of a counter into its union with `pipe`, returning the key
+/// of the union
+fn union_window(
+    pipe: &mut redis::Pipeline,
+    counter: &str,
+    window: LeaderboardWindow,
+    now: DateTime<Utc>,
+) -> String {
+    let keys: Vec<_> = window
+        .bucket_starts(now.timestamp())
+        .map(|it| bucket_key(counter, it))
+        .collect();
+    let union = union_key(counter, window);
+    pipe.zunionstore(&union, &keys)
+        .ignore()
+        .expire(&union, LEADERBOARD_UNION_TTL_SECS)
+        .ignore();
+    union
+}
+
+/// The `limit` highest mints of the window by a counter with their sums
+async fn top_in_window(
+    conn: &mut MultiplexedConnection,
+    counter: &str,
+    window: LeaderboardWindow,
+    now: DateTime<Utc>,
+    limit: usize,
+) -> Result<Vec<(String, f64)>> {
+    // a stop of -1 would be the whole union
+    if limit == 0 {
+        return Ok(vec![]);
+    }
+    // atomic, so a concurrent query doesn't replace the union in between
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    let union = union_window(&mut pipe, counter, window, now);
+    let (top,): (Vec<(String, f64)>,) = pipe
+        .zrevrange_withscores(&union, 0, limit as isize - 1)
+        .query_async(conn)
+        .await?;
+    Ok(top)
+}
+
+fn buy_sell_ratio(buyers: u64, sellers: u64) -> f64 {
+    // a mint without sellers ranks by its buyers
+    buyers as f64 / sellers.max(1) as f64
+}
+
+/// Distinct buyers per distinct seller of the most traded mints of the window, the highest
+/// first. A trader buying many times is one buyer, so a single wallet can't lead.
+async fn top_ratios_in_window(
+    conn: &mut MultiplexedConnection,
+    window: LeaderboardWindow,
+    now: DateTime<Utc>,
+    limit: usize,
+) -> Result<Vec<(String, f64)>> {
+    let candidates = top_in_window(conn, "c", window, now, RATIO_CANDIDATES).await?;
+    if candidates.is_empty() {
+        return Ok(vec![]);
+    }
+    let buckets: Vec<_> = window.bucket_starts(now.timestamp()).collect();
+    let mut pipe = redis::pipe();
+    for (mint, _) in &candidates {
+        for side in ["buyers", "sellers"] {
+            let keys: Vec<_> = buckets
+                .iter()
+                .map(|it| traders_key(side, mint, *it))
+                .collect();
+            pipe.pfcount(keys);
+        }
+    }
+    let counts: Vec<u64> = pipe.query_async(conn).await?;
+    let mut ratios: Vec<_> = candidates
+        .into_iter()
+        .zip(counts.chunks(2))
+        .map(|((mint, _), counts)| (mint, buy_sell_ratio(counts[0], counts[1])))
+        .collect();
+    ratios.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ratios.truncate(limit);
+    Ok(ratios)
+}
+
+/// The `limit` highest mints of the window by the metric
+pub async fn leaderboard(
+    conn: &mut MultiplexedConnection,
+    metric: LeaderboardMetric,
+    window: LeaderboardWindow,
+    now: DateTime<Utc>,
+    limit: usize,
+) -> Result<Vec<LeaderboardEntry>> {
+    if limit == 0 {
+        return Ok(vec![]);
+    }
+    let entries = match metric {
+        LeaderboardMetric::Volume => top_in_window(conn, "v", window, now, limit)
+            .await?
+            .into_iter()
+            .map(|(mint, lamports)| (mint, lamports / LAMPORTS_PER_SOL as f64))
+            .collect(),
+        LeaderboardMetric::Trades => top_in_window(conn, "c", window, now, limit).await?,
+        LeaderboardMetric::BuySellRatio => top_ratios_in_window(conn, window, now, limit).await?,
+    };
+    Ok(entries
+        .into_iter()
+        .filter_map(|(mint, value)| {
+            Some(LeaderboardEntry {
+                mint: mint.parse().ok()?,
+                value,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{Dex, WSOL_MINT};
+
+    #[test]
+    fn test_bucket_counters() {
+        let now = 7_290;
+        let mint = Pubkey::new_unique();
+        let whale = Pubkey::new_unique();
+        let seller = Pubkey::new_unique();
+        let trade = |ts: i64, is_buy: bool, trader: Pubkey| TradeRecord {
+            blk_ts: DateTime::from_timestamp(ts, 0).unwrap(),
+            slot: 1,
+            txid: "tx".to_string(),
+            idx: 0,
+            mint,
+            decimals: 6,
+            trader,
+            dex: Dex::Pumpfun,
+            pool: Pubkey::new_unique(),
+            pool_sol_amt: 0,
+            pool_token_amt: 0,
+            pool_age_secs: None,
+            pool_trade_seq: 0,
+            is_buy,
+            sol_amt: LAMPORTS_PER_SOL,
+            token_amt: 1,
+            trader_sol_delta: 0,
+            trader_token_delta: 0,
+            price_sol: 1.0,
+            price_sol_decimal: String::new(),
+            quote_mint: WSOL_MINT,
+            price_quote: 1.0,
+            quote_amt: LAMPORTS_PER_SOL,
+            pool_quote_amt: 0,
+            price_usd: None,
+            sol_price_usd_at_trade: None,
+            price_outlier: false,
+            source: None,
+            avg_prices: vec![],
+            cluster_id: None,
+            pool_drained: false,
+        };
+        let trades = [
+            trade(7_260, true, whale),
+            trade(7_270, true, whale),
+            trade(7_280, false, seller),
+            trade(7_200, true, Pubkey::new_unique()),
+            // out of the longest window
+            trade(3_600, true, whale),
+        ];
+        let trades: Vec<_> = trades.iter().collect();
+        let buckets = bucket_trades(&trades, now);
+        assert_eq!(buckets.len(), 2);
+        let last = &buckets[&(7_260, mint)];
+        assert_eq!(last.counters, [3 * LAMPORTS_PER_SOL, 3]);
+        // the repeated buys of a trader are one buyer
+        assert_eq!(last.buyers, HashSet::from([whale]));
+        assert_eq!(last.sellers, HashSet::from([seller]));
+        let first = &buckets[&(7_200, mint)];
+        assert_eq!(first.counters, [LAMPORTS_PER_SOL, 1]);
+        assert_eq!(first.buyers.len(), 1);
+        assert!(first.sellers.is_empty());
+
+        let starts: Vec<_> = LeaderboardWindow::FiveMins.bucket_starts(now).collect();
+        assert_eq!(starts, vec![7_020, 7_080, 7_140, 7_200, 7_260]);
+        assert_eq!(LeaderboardWindow::OneHour.bucket_starts(now).count(), 60);
+        assert_eq!(buy_sell_ratio(3, 0), 3.0);
+        assert_eq!(buy_sell_ratio(3, 2), 1.5);
+    }
+}
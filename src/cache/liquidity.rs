@@ -0,0 +1,200 @@
+use chrono::{DateTime, Utc, serde::ts_seconds};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    common::{Dex, TxBaseMetaInfo, WSOL_MINT},
+    qn_req_processor::{EventSource, IxAccount},
+};
+
+use super::DexPoolRecord;
+
+/// Liquidity added to or removed from a WSOL pair pool
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiquidityChangeRecord {
+    #[serde(with = "ts_seconds")]
+    pub blk_ts: DateTime<Utc>,
+    pub slot: u64,
+    pub txid: String,
+    pub idx: u64,
+    pub dex: Dex,
+    #[serde_as(as = "DisplayFromStr")]
+    pub pool: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    pub decimals: u8,
+    #[serde_as(as = "DisplayFromStr")]
+    pub provider: Pubkey,
+    pub is_add: bool,
+    pub sol_amt: u64,
+    pub token_amt: u64,
+    /// lp tokens minted or burned, none for the dlmm positions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lp_amt: Option<u64>,
+    /// reserves after the change, none when the instruction doesn't have the pool vaults
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool_sol_amt: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool_token_amt: Option<u64>,
+    /// the stream batch the event was parsed from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<EventSource>,
+}
+
+impl LiquidityChangeRecord {
+    /// `amts` of the mint a and the mint b of the pool, none if the pool is not a WSOL pair or
+    /// nothing changed
+    pub fn new(
+        meta: TxBaseMetaInfo,
+        pool: &DexPoolRecord,
+        provider: Pubkey,
+        is_add: bool,
+        amts: (u64, u64),
+    ) -> Option<Self> {
+        let (sol_amt, token_amt) = if pool.mint_a == WSOL_MINT {
+            amts
+        } else if pool.mint_b == WSOL_MINT {
+            (amts.1, amts.0)
+        } else {
+            return None;
+        };
+        if sol_amt == 0 && token_amt == 0 {
+            return None;
+        }
+        let TxBaseMetaInfo {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+        } = meta;
+
+        Some(Self {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+            dex: pool.dex,
+            pool: pool.addr,
+            mint: pool.token_mint(),
+            decimals: pool.token_decimals(),
+            provider,
+            is_add,
+            sol_amt,
+            token_amt,
+            lp_amt: None,
+            pool_sol_amt: None,
+            pool_token_amt: None,
+            source: None,
+        })
+    }
+
+    pub fn with_lp_amt(mut self, lp_amt: u64) -> Self {
+        self.lp_amt = Some(lp_amt);
+        self
+    }
+
+    /// Reserves from the post balances of the accounts at `vault_idx`, the vaults of the mint
+    /// a and the mint b of the pool. Kept none if they're not token accounts of the pool mints.
+    pub fn with_vaults(
+        mut self,
+        pool: &DexPoolRecord,
+        accounts: &[IxAccount],
+        vault_idx: (usize, usize),
+    ) -> Self {
+        let vault_amt = |idx: usize, mint: &Pubkey| {
+            accounts
+                .get(idx)?
+                .post_amt
+                .token
+                .as_ref()
+                .filter(|it| it.mint == mint.to_string())
+                .map(|it| it.amt)
+        };
+        if let (Some(amt_a), Some(amt_b)) = (
+            vault_amt(vault_idx.0, &pool.mint_a),
+            vault_amt(vault_idx.1, &pool.mint_b),
+        ) {
+            let (sol_amt, token_amt) = if pool.mint_a == WSOL_MINT {
+                (amt_a, amt_b)
+            } else {
+                (amt_b, amt_a)
+            };
+            self.pool_sol_amt = Some(sol_amt);
+            self.pool_token_amt = Some(token_amt);
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qn_req_processor::{Amt, TokenAmt};
+
+    fn account(mint: &Pubkey, amt: u64) -> IxAccount {
+        let token = TokenAmt {
+            mint: mint.to_string(),
+            decimals: 6,
+            amt,
+        };
+        IxAccount {
+            pubkey: Pubkey::new_unique().to_string(),
+            pre_amt: Amt {
+                sol: 0,
+                token: None,
+            },
+            post_amt: Amt {
+                sol: 0,
+                token: Some(token),
+            },
+        }
+    }
+
+    #[test]
+    fn test_liquidity_change_record() {
+        let mint = Pubkey::new_unique();
+        let pool = DexPoolRecord {
+            addr: Pubkey::new_unique(),
+            dex: Dex::RaydiumAmm,
+            is_complete: false,
+            is_drained: false,
+            is_lp_locked: false,
+            mint_a: mint,
+            mint_b: WSOL_MINT,
+            decimals_a: 6,
+            decimals_b: 9,
+        };
+        let meta = TxBaseMetaInfo {
+            blk_ts: DateTime::from_timestamp(1, 0).unwrap(),
+            slot: 1,
+            txid: "tx".to_string(),
+            idx: 0,
+        };
+        let provider = Pubkey::new_unique();
+        let record = LiquidityChangeRecord::new(meta.clone(), &pool, provider, false, (5, 7))
+            .unwrap()
+            .with_lp_amt(3);
+        assert_eq!((record.mint, record.decimals), (mint, 6));
+        assert_eq!((record.sol_amt, record.token_amt), (7, 5));
+        assert_eq!(record.lp_amt, Some(3));
+
+        let accounts = [account(&mint, 100), account(&WSOL_MINT, 200)];
+        let with_vaults = record.clone().with_vaults(&pool, &accounts, (0, 1));
+        assert_eq!(
+            (with_vaults.pool_sol_amt, with_vaults.pool_token_amt),
+            (Some(200), Some(100))
+        );
+        // not the vaults of the pool
+        let with_vaults = record.with_vaults(&pool, &accounts, (1, 0));
+        assert_eq!(with_vaults.pool_sol_amt, None);
+
+        let other = DexPoolRecord {
+            mint_b: Pubkey::new_unique(),
+            ..pool.clone()
+        };
+        assert!(LiquidityChangeRecord::new(meta.clone(), &other, provider, true, (5, 7)).is_none());
+        assert!(LiquidityChangeRecord::new(meta, &pool, provider, true, (0, 0)).is_none());
+    }
+}
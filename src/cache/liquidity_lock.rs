@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc, serde::ts_seconds};
+use redis::{AsyncCommands, aio::MultiplexedConnection};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::common::Dex;
+
+/// pools whose lp tokens are watched, scored by their creation
+const LP_LOCK_WATCH_KEY: &str = "zset:lp_lock_watch";
+/// the safety metadata outlives the watch
+const POOL_SAFETY_TTL_SECS: i64 = 30 * 24 * 3600;
+
+fn pool_safety_key(pool: &Pubkey) -> String {
+    format!("hash:pool_safety:{pool}")
+}
+
+/// More lp tokens of a pool were burned or sent to a locker.
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiquidityLockedRecord {
+    /// when the lp holders were read
+    #[serde(with = "ts_seconds")]
+    pub ts: DateTime<Utc>,
+    pub slot: u64,
+    pub dex: Dex,
+    #[serde_as(as = "DisplayFromStr")]
+    pub pool: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub lp_mint: Pubkey,
+    /// percent of the lp tokens burned or held by the lockers
+    pub pct_locked: f64,
+    /// percent of the lp tokens burned, included in `pct_locked`
+    pub pct_burned: f64,
+    /// the locker holding the most lp tokens, none if they're only burned
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locker: Option<Pubkey>,
+}
+
+/// Latest lp lock state of a pool
+#[serde_as]
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PoolSafety {
+    pub lp_locked_pct: f64,
+    pub lp_burned_pct: f64,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub locker: Option<Pubkey>,
+    #[serde(with = "ts_seconds")]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl PoolSafety {
+    fn from_fields(fields: &HashMap<String, String>) -> Option<Self> {
+        Some(Self {
+            lp_locked_pct: fields.get("lp_locked_pct")?.parse().ok()?,
+            lp_burned_pct: fields.get("lp_burned_pct")?.parse().ok()?,
+            locker: fields.get("locker").and_then(|it| it.parse().ok()),
+            updated_at: DateTime::from_timestamp(fields.get("updated_at")?.parse().ok()?, 0)?,
+        })
+    }
+}
+
+/// Watch the lp tokens of the created pools
+pub async fn watch_lp_locks(
+    conn: &mut MultiplexedConnection,
+    pools: &[(Pubkey, DateTime<Utc>)],
+) -> Result<()> {
+    if pools.is_empty() {
+        return Ok(());
+    }
+    let members: Vec<_> = pools
+        .iter()
+        .map(|(pool, created)| (created.timestamp(), pool.to_string()))
+        .collect();
+    let _: () = conn.zadd_multiple(LP_LOCK_WATCH_KEY, &members).await?;
+    Ok(())
+}
+
+/// The watched pools created since `since`, the older ones are no longer watched
+pub async fn lp_lock_watched_pools(
+    conn: &mut MultiplexedConnection,
+    since: DateTime<Utc>,
+) -> Result<Vec<Pubkey>> {
+    let _: () = conn
+        .zrembyscore(LP_LOCK_WATCH_KEY, "-inf", format!("({}", since.timestamp()))
+        .await?;
+    let pools: Vec<String> = conn.zrange(LP_LOCK_WATCH_KEY, 0, -1).await?;
+    Ok(pools.iter().filter_map(|it| it.parse().ok()).collect())
+}
+
+pub async fn get_pool_safety(
+    conn: &mut MultiplexedConnection,
+    pool: &Pubkey,
+) -> Result<Option<PoolSafety>> {
+    let fields: HashMap<String, String> = conn.hgetall(pool_safety_key(pool)).await?;
+    Ok(PoolSafety::from_fields(&fields))
+}
+
+pub async fn save_pool_safety(
+    conn: &mut MultiplexedConnection,
+    pool: &Pubkey,
+    safety: &PoolSafety,
+) -> Result<()> {
+    let key = pool_safety_key(pool);
+    let mut fields = vec![
+        ("lp_locked_pct", safety.lp_locked_pct.to_string()),
+        ("lp_burned_pct", safety.lp_burned_pct.to_string()),
+        ("updated_at", safety.updated_at.timestamp().to_string()),
+    ];
+    if let Some(locker) = safety.locker {
+        fields.push(("locker", locker.to_string()));
+    }
+    let _: () = redis::pipe()
+        .hset_multiple(&key, &fields)
+        .ignore()
+        .expire(&key, POOL_SAFETY_TTL_SECS)
+        .ignore()
+        .query_async(conn)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_safety_from_fields() {
+        let locker = Pubkey::new_unique();
+        let fields = HashMap::from([
+            ("lp_locked_pct".to_string(), "99.5".to_string()),
+            ("lp_burned_pct".to_string(), "0".to_string()),
+            ("locker".to_string(), locker.to_string()),
+            ("updated_at".to_string(), "1700000000".to_string()),
+        ]);
+        let safety = PoolSafety::from_fields(&fields).unwrap();
+        assert_eq!(safety.lp_locked_pct, 99.5);
+        assert_eq!(safety.locker, Some(locker));
+        assert_eq!(safety.updated_at.timestamp(), 1_700_000_000);
+        assert_eq!(PoolSafety::from_fields(&HashMap::new()), None);
+    }
+}
@@ -0,0 +1,64 @@
+use chrono::{DateTime, Utc, serde::ts_seconds};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    common::TxBaseMetaInfo, meteora::dbc::event::MeteoraDbcCurveCompleteEvent,
+    qn_req_processor::EventSource,
+};
+
+/// The bonding curve of a meteora dbc pool completed, the pool is migrated to a damm pool next
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeteoraDbcCompleteRecord {
+    #[serde(with = "ts_seconds")]
+    pub blk_ts: DateTime<Utc>,
+    pub slot: u64,
+    pub txid: String,
+    pub idx: u64,
+    /// trader of the swap which completed the curve
+    #[serde_as(as = "DisplayFromStr")]
+    pub user: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub pool: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub config: Pubkey,
+    pub base_reserve: u64,
+    pub quote_reserve: u64,
+    /// the stream batch the event was parsed from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<EventSource>,
+}
+
+impl MeteoraDbcCompleteRecord {
+    pub fn new(
+        meta: TxBaseMetaInfo,
+        complete_evt: &MeteoraDbcCurveCompleteEvent,
+        user: Pubkey,
+        mint: Pubkey,
+    ) -> Self {
+        let TxBaseMetaInfo {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+        } = meta;
+
+        Self {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+            user,
+            mint,
+            pool: complete_evt.pool,
+            config: complete_evt.config,
+            base_reserve: complete_evt.base_reserve,
+            quote_reserve: complete_evt.quote_reserve,
+            source: None,
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc, serde::ts_seconds};
+use redis::aio::MultiplexedConnection;
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    common::{Dex, TxBaseMetaInfo},
+    qn_req_processor::EventSource,
+};
+
+use super::{
+    DexEvent, DexPoolCreatedRecord, PoolLiquidity, PumpfunCompleteRecord, RedisCacheRecord,
+};
+
+/// Pending migrations are forgotten if no pumpamm pool shows up in time
+pub const PENDING_MIGRATION_EXP_SECS: u64 = 3600 * 24;
+
+/// The pumpamm pool of a completed pumpfun bonding curve was created.
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationCompletedRecord {
+    #[serde(with = "ts_seconds")]
+    pub blk_ts: DateTime<Utc>,
+    pub slot: u64,
+    pub txid: String,
+    pub idx: u64,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub bonding_curve: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub pool: Pubkey,
+    #[serde(with = "ts_seconds")]
+    pub complete_blk_ts: DateTime<Utc>,
+    pub complete_txid: String,
+    pub secs_to_migrate: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub init_liquidity: Option<PoolLiquidity>,
+    /// the stream batch the event was parsed from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<EventSource>,
+}
+
+impl MigrationCompletedRecord {
+    pub fn new(
+        meta: TxBaseMetaInfo,
+        complete: &PumpfunCompleteRecord,
+        pool: &DexPoolCreatedRecord,
+    ) -> Self {
+        let TxBaseMetaInfo {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+        } = meta;
+
+        Self {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+            mint: complete.mint,
+            bonding_curve: complete.bonding_curve,
+            pool: pool.addr,
+            complete_blk_ts: complete.blk_ts,
+            complete_txid: complete.txid.clone(),
+            secs_to_migrate: (blk_ts - complete.blk_ts).num_seconds(),
+            init_liquidity: pool.init_liquidity,
+            source: None,
+        }
+    }
+}
+
+/// Pair the pumpamm pools created in a batch with the pumpfun curves completed before them in
+/// the same batch, which aren't pending in redis yet. The migrations are inserted before their
+/// pool created events, `event_logs` kept in step.
+pub fn pair_batch_migrations(events: &mut Vec<DexEvent>, event_logs: &mut Vec<usize>) {
+    let mut completes: HashMap<Pubkey, PumpfunCompleteRecord> = HashMap::new();
+    let mut migrated: HashSet<Pubkey> = HashSet::new();
+    let mut pos = 0;
+    while pos < events.len() {
+        match &events[pos] {
+            DexEvent::PumpfunComplete(complete) => {
+                completes.insert(complete.mint, complete.clone());
+            }
+            DexEvent::MigrationCompleted(migration) => {
+                migrated.insert(migration.mint);
+            }
+            DexEvent::PoolCreated(pool) if pool.dex == Dex::PumpAmm && pool.is_wsol_pool() => {
+                let mint = pool.as_pool_record().token_mint();
+                if let Some(complete) = completes.get(&mint)
+                    && migrated.insert(mint)
+                {
+                    let meta = TxBaseMetaInfo {
+                        blk_ts: pool.blk_ts,
+                        slot: pool.slot,
+                        txid: pool.txid.clone(),
+                        idx: pool.idx,
+                    };
+                    let mut migration = MigrationCompletedRecord::new(meta, complete, pool);
+                    migration.source = pool.source.clone();
+                    events.insert(pos, DexEvent::MigrationCompleted(migration));
+                    event_logs.insert(pos, event_logs[pos]);
+                    pos += 1;
+                }
+            }
+            _ => {}
+        }
+        pos += 1;
+    }
+}
+
+/// Keep the curves completed by the queued events pending until their pumpamm pool shows up,
+/// and forget the ones which migrated. Run once the events are queued, so a batch claimed
+/// again is parsed against the same pending migrations.
+pub async fn settle_pending_migrations(
+    conn: &mut MultiplexedConnection,
+    events: &[DexEvent],
+) -> Result<()> {
+    let migrated: HashSet<_> = events
+        .iter()
+        .filter_map(|it| match it {
+            DexEvent::MigrationCompleted(migration) => Some(migration.mint),
+            _ => None,
+        })
+        .collect();
+    let mut pipe = redis::pipe();
+    for evt in events {
+        if let DexEvent::PumpfunComplete(complete) = evt
+            && !migrated.contains(&complete.mint)
+        {
+            pipe.set_ex(complete.key(), complete.json()?, PENDING_MIGRATION_EXP_SECS)
+                .ignore();
+        }
+    }
+    for mint in &migrated {
+        pipe.del(PumpfunCompleteRecord::new_key(mint.to_string()))
+            .ignore();
+    }
+    if !pipe.is_empty() {
+        let _: () = pipe.query_async(conn).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::WSOL_MINT;
+
+    fn pool_created(mint: Pubkey, slot: u64) -> DexPoolCreatedRecord {
+        DexPoolCreatedRecord {
+            blk_ts: DateTime::from_timestamp(100, 0).unwrap(),
+            slot,
+            txid: "create".to_string(),
+            idx: 0,
+            creator: Pubkey::new_unique(),
+            addr: Pubkey::new_unique(),
+            dex: Dex::PumpAmm,
+            mint_a: mint,
+            mint_b: WSOL_MINT,
+            decimals_a: 6,
+            decimals_b: 9,
+            init_liquidity: None,
+            creator_funded_by: None,
+            source: None,
+        }
+    }
+
+    #[test]
+    fn test_pair_batch_migrations() {
+        let (migrating, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let complete = PumpfunCompleteRecord {
+            blk_ts: DateTime::from_timestamp(40, 0).unwrap(),
+            slot: 1,
+            txid: "complete".to_string(),
+            idx: 0,
+            user: Pubkey::new_unique(),
+            mint: migrating,
+            bonding_curve: Pubkey::new_unique(),
+            source: None,
+        };
+        let mut events = vec![
+            DexEvent::PumpfunComplete(complete),
+            DexEvent::PoolCreated(pool_created(other, 2)),
+            DexEvent::PoolCreated(pool_created(migrating, 3)),
+        ];
+        let mut event_logs = vec![0, 1, 2];
+        pair_batch_migrations(&mut events, &mut event_logs);
+
+        assert_eq!(events.len(), 4);
+        assert_eq!(event_logs, vec![0, 1, 2, 2]);
+        let DexEvent::MigrationCompleted(migration) = &events[2] else {
+            panic!("no migration before its pool: {events:?}");
+        };
+        assert_eq!((migration.mint, migration.slot), (migrating, 3));
+        assert_eq!(migration.secs_to_migrate, 60);
+        assert!(matches!(events[3], DexEvent::PoolCreated(_)));
+
+        // paired already, e.g. with the pending complete in redis
+        pair_batch_migrations(&mut events, &mut event_logs);
+        assert_eq!(events.len(), 4);
+    }
+}
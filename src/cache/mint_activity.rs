@@ -0,0 +1,151 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc, serde::ts_seconds};
+use redis::{Script, aio::MultiplexedConnection};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+
+/// sorted set of traded mints, scored by the block timestamp of their latest trade
+const MINT_LAST_TRADE_KEY: &str = "zset:mint_last_trade";
+
+/// Pop up to ARGV[2] members of sorted set KEYS[1] scored at most ARGV[1], with scores.
+const POP_BY_SCORE_SCRIPT: &str = r#"
+local items = redis.call('ZRANGEBYSCORE', KEYS[1], '-inf', ARGV[1], 'WITHSCORES', 'LIMIT', 0, ARGV[2])
+for i = 1, #items, 2 do
+    redis.call('ZREM', KEYS[1], items[i])
+end
+return items
+"#;
+
+/// A mint which was traded before didn't trade since `last_trade_ts`.
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MintInactiveRecord {
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde(with = "ts_seconds")]
+    pub last_trade_ts: DateTime<Utc>,
+}
+
+/// Record the latest trade time of the mints, earlier times don't overwrite later ones.
+pub async fn touch_traded_mints(
+    conn: &mut MultiplexedConnection,
+    trades: &[(Pubkey, DateTime<Utc>)],
+) -> Result<()> {
+    touch_last_trades(conn, MINT_LAST_TRADE_KEY, trades).await
+}
+
+pub(super) async fn touch_last_trades(
+    conn: &mut MultiplexedConnection,
+    key: &str,
+    trades: &[(Pubkey, DateTime<Utc>)],
+) -> Result<()> {
+    if trades.is_empty() {
+        return Ok(());
+    }
+
+    let mut cmd = redis::cmd("zadd");
+    cmd.arg(key).arg("GT");
+    for (member, blk_ts) in trades {
+        cmd.arg(blk_ts.timestamp()).arg(member.to_string());
+    }
+    let _: () = cmd.query_async(conn).await?;
+    Ok(())
+}
+
+/// Remove the members of `key` last traded before `before`
+pub(super) async fn trim_last_trades(
+    conn: &mut MultiplexedConnection,
+    key: &str,
+    before: DateTime<Utc>,
+) -> Result<()> {
+    let _: () = redis::cmd("ZREMRANGEBYSCORE")
+        .arg(key)
+        .arg("-inf")
+        .arg(format!("({}", before.timestamp()))
+        .query_async(conn)
+        .await?;
+    Ok(())
+}
+
+/// At most `max` members of `key` traded since `since`, the latest traded first.
+/// Members traded before are removed.
+pub(super) async fn recent_last_trades(
+    conn: &mut MultiplexedConnection,
+    key: &str,
+    since: DateTime<Utc>,
+    max: usize,
+) -> Result<Vec<Pubkey>> {
+    let (members,): (Vec<String>,) = redis::pipe()
+        .cmd("ZREMRANGEBYSCORE")
+        .arg(key)
+        .arg("-inf")
+        .arg(format!("({}", since.timestamp()))
+        .ignore()
+        .cmd("ZREVRANGEBYSCORE")
+        .arg(key)
+        .arg("+inf")
+        .arg(since.timestamp())
+        .arg("LIMIT")
+        .arg(0)
+        .arg(max)
+        .query_async(conn)
+        .await?;
+
+    let members = members
+        .iter()
+        .map(|it| it.parse())
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(members)
+}
+
+/// Remove and return at most `max` mints whose latest trade is not after `before`.
+pub async fn pop_inactive_mints(
+    conn: &mut MultiplexedConnection,
+    before: DateTime<Utc>,
+    max: usize,
+) -> Result<Vec<MintInactiveRecord>> {
+    let items: Vec<(String, i64)> = Script::new(POP_BY_SCORE_SCRIPT)
+        .key(MINT_LAST_TRADE_KEY)
+        .arg(before.timestamp())
+        .arg(max)
+        .invoke_async(conn)
+        .await?;
+
+    let mut records = vec![];
+    for (mint, ts) in items {
+        records.push(MintInactiveRecord {
+            mint: mint.parse()?,
+            last_trade_ts: DateTime::from_timestamp(ts, 0).unwrap_or_default(),
+        });
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[ignore = "needs redis, set TEST_REDIS_URL"]
+    async fn test_pop_inactive_mints() {
+        let redis_url = std::env::var("TEST_REDIS_URL").expect("TEST_REDIS_URL of a redis");
+        let client = redis::Client::open(redis_url).unwrap();
+        let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+        let (quiet, active) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let ts = |secs| DateTime::from_timestamp(secs, 0).unwrap();
+        // the earlier trade of the active mint, seen late, doesn't overwrite its latest
+        let trades = [(quiet, ts(100)), (active, ts(300)), (active, ts(150))];
+        touch_traded_mints(&mut conn, &trades).await.unwrap();
+
+        let records = pop_inactive_mints(&mut conn, ts(200), 1_000).await.unwrap();
+        let quiet_record = records.iter().find(|it| it.mint == quiet).unwrap();
+        assert_eq!(quiet_record.last_trade_ts, ts(100));
+        assert!(records.iter().all(|it| it.mint != active));
+        // popped once
+        let records = pop_inactive_mints(&mut conn, ts(200), 1_000).await.unwrap();
+        assert!(records.iter().all(|it| it.mint != quiet));
+        let records = pop_inactive_mints(&mut conn, ts(300), 1_000).await.unwrap();
+        assert!(records.iter().any(|it| it.mint == active));
+    }
+}
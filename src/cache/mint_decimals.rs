@@ -0,0 +1,122 @@
+use anyhow::Result;
+use redis::{AsyncCommands, aio::MultiplexedConnection};
+use solana_sdk::pubkey::Pubkey;
+use tracing::warn;
+
+use super::DexPoolRecord;
+
+/// decimals of the mints read from their onchain accounts, they never change
+const MINT_DECIMALS_KEY: &str = "hash:mint_decimals";
+/// pools whose decimals aren't checked against their mints yet
+const DECIMALS_CHECKS_KEY: &str = "set:decimals_checks";
+
+impl DexPoolRecord {
+    /// Replace the decimals of the mint if they differ, true if replaced
+    pub fn correct_decimals(&mut self, mint: &Pubkey, decimals: u8) -> bool {
+        let recorded = if *mint == self.mint_a {
+            &mut self.decimals_a
+        } else if *mint == self.mint_b {
+            &mut self.decimals_b
+        } else {
+            return false;
+        };
+        if *recorded == decimals {
+            return false;
+        }
+        warn!(
+            "{} pool {} recorded {recorded} decimals of mint {mint}, onchain {decimals}, corrected",
+            self.dex, self.addr
+        );
+        *recorded = decimals;
+        true
+    }
+}
+
+/// Onchain decimals of the mints, none for mints not checked yet
+pub async fn get_mint_decimals(
+    conn: &mut MultiplexedConnection,
+    mints: &[Pubkey],
+) -> Result<Vec<Option<u8>>> {
+    if mints.is_empty() {
+        return Ok(vec![]);
+    }
+    let fields: Vec<_> = mints.iter().map(|it| it.to_string()).collect();
+    let decimals: Vec<Option<u8>> = redis::cmd("HMGET")
+        .arg(MINT_DECIMALS_KEY)
+        .arg(fields)
+        .query_async(conn)
+        .await?;
+    Ok(decimals)
+}
+
+pub async fn save_mint_decimals(
+    conn: &mut MultiplexedConnection,
+    decimals: &[(Pubkey, u8)],
+) -> Result<()> {
+    if decimals.is_empty() {
+        return Ok(());
+    }
+    let items: Vec<_> = decimals
+        .iter()
+        .map(|(mint, decimals)| (mint.to_string(), *decimals))
+        .collect();
+    let _: () = conn.hset_multiple(MINT_DECIMALS_KEY, &items).await?;
+    Ok(())
+}
+
+/// Queue new pools for checking their decimals against the mint accounts
+pub async fn queue_decimals_checks(
+    conn: &mut MultiplexedConnection,
+    pools: &[Pubkey],
+) -> Result<()> {
+    if pools.is_empty() {
+        return Ok(());
+    }
+    let pools: Vec<_> = pools.iter().map(|it| it.to_string()).collect();
+    let _: () = conn.sadd(DECIMALS_CHECKS_KEY, pools).await?;
+    Ok(())
+}
+
+pub async fn pop_decimals_checks(
+    conn: &mut MultiplexedConnection,
+    max: usize,
+) -> Result<Vec<Pubkey>> {
+    let pools: Vec<String> = redis::cmd("SPOP")
+        .arg(DECIMALS_CHECKS_KEY)
+        .arg(max)
+        .query_async(conn)
+        .await?;
+    let pools = pools
+        .iter()
+        .map(|it| it.parse())
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(pools)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{Dex, WSOL_MINT};
+
+    use super::*;
+
+    #[test]
+    fn test_correct_decimals() {
+        let mint = Pubkey::new_unique();
+        let mut record = DexPoolRecord {
+            addr: Pubkey::new_unique(),
+            dex: Dex::PumpAmm,
+            is_complete: false,
+            is_drained: false,
+            is_lp_locked: false,
+            mint_a: mint,
+            mint_b: WSOL_MINT,
+            decimals_a: 9,
+            decimals_b: 9,
+        };
+        assert!(!record.correct_decimals(&WSOL_MINT, 9));
+        assert!(!record.correct_decimals(&Pubkey::new_unique(), 6));
+        assert!(record.correct_decimals(&mint, 6));
+        assert_eq!(record.decimals_a, 6);
+        assert_eq!(record.token_decimals(), 6);
+    }
+}
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc, serde::ts_seconds, serde::ts_seconds_option};
+use redis::{AsyncCommands, aio::MultiplexedConnection};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+use strum::IntoEnumIterator;
+
+use crate::common::Dex;
+
+use super::{DexPoolCreatedRecord, TradeRecord, queue};
+
+/// sorted set of the registered mints, scored by the block timestamp they were first seen
+const MINTS_BY_FIRST_SEEN_KEY: &str = "zset:mint_registry:first_seen";
+/// sorted set of the registered mints, scored by their traded lamports
+const MINTS_BY_VOLUME_KEY: &str = "zset:mint_registry:volume";
+
+/// hash of a registered mint, `fs` first seen, `fp` first pool, `ft` first and `lt` last trade
+/// block timestamps, `n` trades, `v` traded lamports and `dex:{dex}` trades per dex.
+/// Kept without expiry.
+fn mint_key(mint: &Pubkey) -> String {
+    format!("hash:mint_registry:{mint}")
+}
+
+/// Every mint quoted in WSOL seen in a created pool or a trade
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MintRegistryRecord {
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde(with = "ts_seconds")]
+    pub first_seen: DateTime<Utc>,
+    /// the created pool or the traded pool the mint was first seen in
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub first_pool: Option<Pubkey>,
+    #[serde(with = "ts_seconds_option")]
+    pub first_trade_ts: Option<DateTime<Utc>>,
+    #[serde(with = "ts_seconds_option")]
+    pub last_trade_ts: Option<DateTime<Utc>>,
+    pub dexes: Vec<Dex>,
+    pub trades: u64,
+    pub volume_sol: f64,
+}
+
+impl MintRegistryRecord {
+    fn from_fields(mint: Pubkey, fields: &HashMap<String, String>) -> Option<Self> {
+        let get = |field: &str| fields.get(field).and_then(|it| it.parse::<i64>().ok());
+        let ts = |field: &str| get(field).and_then(|it| DateTime::from_timestamp(it, 0));
+        Some(Self {
+            mint,
+            first_seen: ts("fs")?,
+            first_pool: fields.get("fp").and_then(|it| it.parse().ok()),
+            first_trade_ts: ts("ft"),
+            last_trade_ts: ts("lt"),
+            dexes: Dex::iter()
+                .filter(|dex| fields.contains_key(&format!("dex:{dex}")))
+                .collect(),
+            trades: get("n").unwrap_or_default() as u64,
+            volume_sol: get("v").unwrap_or_default() as f64 / LAMPORTS_PER_SOL as f64,
+        })
+    }
+}
+
+/// How `list_mints` sorts the registry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MintSort {
+    /// by first seen time
+    #[default]
+    New,
+    /// by traded sol
+    Volume,
+}
+
+impl MintSort {
+    fn key(&self) -> &'static str {
+        match self {
+            MintSort::New => MINTS_BY_FIRST_SEEN_KEY,
+            MintSort::Volume => MINTS_BY_VOLUME_KEY,
+        }
+    }
+}
+
+/// Register the mints of the created WSOL pools and the trades, the first seen fields of a
+/// mint are only set once.
+pub async fn record_mints(
+    conn: &mut MultiplexedConnection,
+    batch_id: &str,
+    created_pools: &[&DexPoolCreatedRecord],
+    trades: &[&TradeRecord],
+) -> Result<()> {
+    // (slot, idx, blk_ts, pool) of the earliest event of each mint
+    let mut first_seen: HashMap<Pubkey, (u64, u64, i64, Pubkey)> = HashMap::new();
+    let mut seen = |mint, seen: (u64, u64, i64, Pubkey)| {
+        let first = first_seen.entry(mint).or_insert(seen);
+        if (seen.0, seen.1) < (first.0, first.1) {
+            *first = seen;
+        }
+    };
+    for pool in created_pools.iter().filter(|it| it.is_wsol_pool()) {
+        let mint = pool.as_pool_record().token_mint();
+        seen(
+            mint,
+            (pool.slot, pool.idx, pool.blk_ts.timestamp(), pool.addr),
+        );
+    }
+    for trade in trades {
+        seen(
+            trade.mint,
+            (trade.slot, trade.idx, trade.blk_ts.timestamp(), trade.pool),
+        );
+    }
+    if first_seen.is_empty() {
+        return Ok(());
+    }
+
+    let mut pipe = redis::pipe();
+    for (mint, (_, _, ts, pool)) in &first_seen {
+        let key = mint_key(mint);
+        pipe.hset_nx(&key, "fs", ts).ignore();
+        pipe.hset_nx(&key, "fp", pool.to_string()).ignore();
+        pipe.cmd("ZADD")
+            .arg(MINTS_BY_FIRST_SEEN_KEY)
+            .arg("NX")
+            .arg(ts)
+            .arg(mint.to_string())
+            .ignore();
+    }
+    // (first, last) trade timestamps of each mint in the batch
+    let mut trade_range: HashMap<Pubkey, (i64, i64)> = HashMap::new();
+    for trade in trades {
+        let ts = trade.blk_ts.timestamp();
+        let range = trade_range.entry(trade.mint).or_insert((ts, ts));
+        *range = (range.0.min(ts), range.1.max(ts));
+
+        let key = mint_key(&trade.mint);
+        pipe.hincr(&key, "n", 1).ignore();
+        pipe.hincr(&key, "v", trade.sol_amt).ignore();
+        pipe.hincr(&key, format!("dex:{}", trade.dex), 1).ignore();
+        pipe.zincr(MINTS_BY_VOLUME_KEY, trade.mint.to_string(), trade.sol_amt)
+            .ignore();
+    }
+    for (mint, (first, last)) in trade_range {
+        let key = mint_key(&mint);
+        pipe.hset_nx(&key, "ft", first).ignore();
+        pipe.hset(&key, "lt", last).ignore();
+    }
+    queue::query_once(conn, batch_id, "mint_registry", &mut pipe).await
+}
+
+/// At most `limit` registered mints with scores in `[from, to]` after the first `skip`, the
+/// lowest score first or the highest first if `rev`. Returns the mints with their scores.
+pub async fn list_mints(
+    conn: &mut MultiplexedConnection,
+    sort: MintSort,
+    from: Option<i64>,
+    to: Option<i64>,
+    skip: usize,
+    limit: usize,
+    rev: bool,
+) -> Result<Vec<(MintRegistryRecord, i64)>> {
+    let from = from.map_or("-inf".to_string(), |it| it.to_string());
+    let to = to.map_or("+inf".to_string(), |it| it.to_string());
+    let (skip, limit) = (skip as isize, limit as isize);
+    let items: Vec<(String, f64)> = if rev {
+        conn.zrevrangebyscore_limit_withscores(sort.key(), to, from, skip, limit)
+            .await?
+    } else {
+        conn.zrangebyscore_limit_withscores(sort.key(), from, to, skip, limit)
+            .await?
+    };
+    let items: Vec<(Pubkey, i64)> = items
+        .into_iter()
+        .filter_map(|(mint, score)| Some((mint.parse().ok()?, score as i64)))
+        .collect();
+    if items.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut pipe = redis::pipe();
+    for (mint, _) in &items {
+        pipe.hgetall(mint_key(mint));
+    }
+    let fields: Vec<HashMap<String, String>> = pipe.query_async(conn).await?;
+    Ok(items
+        .into_iter()
+        .zip(fields)
+        .filter_map(|((mint, score), fields)| {
+            Some((MintRegistryRecord::from_fields(mint, &fields)?, score))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_from_fields() {
+        let mint = Pubkey::new_unique();
+        let pool = Pubkey::new_unique();
+        let fields = HashMap::from([
+            ("fs".to_string(), "1700000000".to_string()),
+            ("fp".to_string(), pool.to_string()),
+            ("lt".to_string(), "1700000100".to_string()),
+            ("n".to_string(), "3".to_string()),
+            ("v".to_string(), "1500000000".to_string()),
+            ("dex:Pumpfun".to_string(), "2".to_string()),
+            ("dex:PumpAmm".to_string(), "1".to_string()),
+        ]);
+        let record = MintRegistryRecord::from_fields(mint, &fields).unwrap();
+        assert_eq!(record.first_seen.timestamp(), 1_700_000_000);
+        assert_eq!(record.first_pool, Some(pool));
+        assert_eq!(record.first_trade_ts, None);
+        assert_eq!(
+            record.last_trade_ts.map(|it| it.timestamp()),
+            Some(1_700_000_100)
+        );
+        assert_eq!(record.dexes, vec![Dex::Pumpfun, Dex::PumpAmm]);
+        assert_eq!((record.trades, record.volume_sol), (3, 1.5));
+
+        // not registered
+        assert_eq!(MintRegistryRecord::from_fields(mint, &HashMap::new()), None);
+    }
+}
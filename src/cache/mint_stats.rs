@@ -0,0 +1,344 @@
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc, serde::ts_seconds};
+use redis::aio::MultiplexedConnection;
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+
+use super::{
+    TradeRecord,
+    mint_activity::{recent_last_trades, touch_last_trades, trim_last_trades},
+    queue,
+};
+
+/// sorted set of mints with stats, scored by the block timestamp of their latest trade
+const MINT_STATS_LAST_TRADE_KEY: &str = "zset:mint_stats_last_trade";
+
+/// Trades are counted in slots of this many seconds, by block timestamp
+pub const MINT_STATS_SLOT_SECS: i64 = 300;
+/// Slots older than the longest window expire
+const MINT_STATS_TTL_SECS: i64 = 24 * 3600 + MINT_STATS_SLOT_SECS;
+/// Upper bounds of the trade notional buckets in SOL, the last bucket has none
+pub const NOTIONAL_BUCKET_BOUNDS_SOL: [f64; 4] = [0.1, 1.0, 10.0, 100.0];
+const NOTIONAL_BUCKETS: usize = NOTIONAL_BUCKET_BOUNDS_SOL.len() + 1;
+
+/// hash of one stats slot of a mint, `c{bucket}` trade counts and `v{bucket}` volumes,
+/// `b` bought and `s` sold lamports
+fn slot_key(mint: &Pubkey, slot_start: i64) -> String {
+    format!("hash:mint_stats:{mint}:{slot_start}")
+}
+
+fn slot_start(ts: i64) -> i64 {
+    ts - ts.rem_euclid(MINT_STATS_SLOT_SECS)
+}
+
+pub fn notional_bucket(sol_amt: u64) -> usize {
+    let sol = sol_amt as f64 / LAMPORTS_PER_SOL as f64;
+    NOTIONAL_BUCKET_BOUNDS_SOL
+        .iter()
+        .position(|bound| sol < *bound)
+        .unwrap_or(NOTIONAL_BUCKET_BOUNDS_SOL.len())
+}
+
+/// Buy and sell volume of an actively traded mint over the last `window` seconds.
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PressureUpdateRecord {
+    #[serde(with = "ts_seconds")]
+    pub ts: DateTime<Utc>,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    /// seconds
+    pub window: i64,
+    pub buy_sol: f64,
+    pub sell_sol: f64,
+    /// buy_sol - sell_sol
+    pub net: f64,
+}
+
+impl PressureUpdateRecord {
+    fn new(ts: DateTime<Utc>, mint: Pubkey, window: i64, bought: u64, sold: u64) -> Self {
+        let buy_sol = bought as f64 / LAMPORTS_PER_SOL as f64;
+        let sell_sol = sold as f64 / LAMPORTS_PER_SOL as f64;
+        Self {
+            ts,
+            mint,
+            window,
+            buy_sol,
+            sell_sol,
+            net: buy_sol - sell_sol,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NotionalBucket {
+    pub min_sol: f64,
+    /// none for the largest trades
+    pub max_sol: Option<f64>,
+    pub trades: u64,
+    pub sol_volume: f64,
+}
+
+/// Trade sizes of a mint in the last `window_secs`
+#[derive(Debug, Clone, Serialize)]
+pub struct NotionalHistogram {
+    pub window_secs: i64,
+    pub trades: u64,
+    pub sol_volume: f64,
+    pub buckets: Vec<NotionalBucket>,
+}
+
+impl NotionalHistogram {
+    /// `slots` with the part of each inside the window, see `window_slots`
+    fn from_slots(window_secs: i64, slots: &[(f64, HashMap<String, u64>)]) -> Self {
+        let mut buckets: Vec<_> = (0..NOTIONAL_BUCKETS)
+            .map(|idx| NotionalBucket {
+                min_sol: idx
+                    .checked_sub(1)
+                    .map(|prev| NOTIONAL_BUCKET_BOUNDS_SOL[prev])
+                    .unwrap_or_default(),
+                max_sol: NOTIONAL_BUCKET_BOUNDS_SOL.get(idx).copied(),
+                trades: 0,
+                sol_volume: 0.0,
+            })
+            .collect();
+        let mut trades = [0.0; NOTIONAL_BUCKETS];
+        for (part, slot) in slots {
+            for (idx, bucket) in buckets.iter_mut().enumerate() {
+                let count = slot.get(&format!("c{idx}")).copied().unwrap_or_default();
+                trades[idx] += count as f64 * part;
+                let lamports = slot.get(&format!("v{idx}")).copied().unwrap_or_default();
+                bucket.sol_volume += lamports as f64 * part / LAMPORTS_PER_SOL as f64;
+            }
+        }
+        for (bucket, trades) in buckets.iter_mut().zip(trades) {
+            bucket.trades = trades.round() as u64;
+        }
+
+        Self {
+            window_secs,
+            trades: buckets.iter().map(|it| it.trades).sum(),
+            sol_volume: buckets.iter().map(|it| it.sol_volume).sum(),
+            buckets,
+        }
+    }
+}
+
+#[derive(Default)]
+struct SlotStats {
+    buckets: [(u64, u64); NOTIONAL_BUCKETS],
+    bought: u64,
+    sold: u64,
+}
+
+/// Count the trades into the notional buckets and buy / sell volumes of their mints.
+pub async fn record_trade_stats(
+    conn: &mut MultiplexedConnection,
+    batch_id: &str,
+    trades: &[&TradeRecord],
+) -> Result<()> {
+    if trades.is_empty() {
+        return Ok(());
+    }
+
+    let mut slots: HashMap<_, SlotStats> = HashMap::new();
+    for trade in trades {
+        let key = slot_key(&trade.mint, slot_start(trade.blk_ts.timestamp()));
+        let stats = slots.entry(key).or_default();
+        let (count, volume) = &mut stats.buckets[notional_bucket(trade.sol_amt)];
+        *count += 1;
+        *volume += trade.sol_amt;
+        if trade.is_buy {
+            stats.bought += trade.sol_amt;
+        } else {
+            stats.sold += trade.sol_amt;
+        }
+    }
+
+    let mut pipe = redis::pipe();
+    for (key, stats) in slots {
+        for (idx, (count, volume)) in stats.buckets.into_iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            pipe.hincr(&key, format!("c{idx}"), count).ignore();
+            pipe.hincr(&key, format!("v{idx}"), volume).ignore();
+        }
+        pipe.hincr(&key, "b", stats.bought).ignore();
+        pipe.hincr(&key, "s", stats.sold).ignore();
+        pipe.expire(&key, MINT_STATS_TTL_SECS).ignore();
+    }
+    queue::query_once(conn, batch_id, "mint_stats", &mut pipe).await?;
+
+    let traded_mints: Vec<_> = trades.iter().map(|it| (it.mint, it.blk_ts)).collect();
+    touch_last_trades(conn, MINT_STATS_LAST_TRADE_KEY, &traded_mints).await?;
+    // the mints whose stats slots all expired
+    let before = Utc::now() - Duration::from_secs(MINT_STATS_TTL_SECS as u64);
+    trim_last_trades(conn, MINT_STATS_LAST_TRADE_KEY, before).await
+}
+
+/// At most `max` mints traded since `since`, the latest traded first
+pub async fn active_stats_mints(
+    conn: &mut MultiplexedConnection,
+    since: DateTime<Utc>,
+    max: usize,
+) -> Result<Vec<Pubkey>> {
+    recent_last_trades(conn, MINT_STATS_LAST_TRADE_KEY, since, max).await
+}
+
+/// Slot starts of the `window_secs` before `now` with the part of each inside the window, the
+/// current slot included. The oldest slot is only partly inside, its trades are prorated as if
+/// spread evenly over it, so a window right after a slot rolled over still spans `window_secs`.
+fn window_slots(window_secs: i64, now: DateTime<Utc>) -> Vec<(i64, f64)> {
+    let window_start = now.timestamp() - window_secs;
+    let first = slot_start(window_start);
+    let last = slot_start(now.timestamp());
+    (first..=last)
+        .step_by(MINT_STATS_SLOT_SECS as usize)
+        .map(|slot| {
+            let outside = (window_start - slot).max(0);
+            let part = 1.0 - outside as f64 / MINT_STATS_SLOT_SECS as f64;
+            (slot, part)
+        })
+        .collect()
+}
+
+/// Buy / sell pressure of each mint over the `window_secs` before `now`
+pub async fn mint_pressures(
+    conn: &mut MultiplexedConnection,
+    mints: &[Pubkey],
+    window_secs: i64,
+    now: DateTime<Utc>,
+) -> Result<Vec<PressureUpdateRecord>> {
+    let slots = window_slots(window_secs, now);
+    let mut pipe = redis::pipe();
+    for mint in mints {
+        for (slot, _) in &slots {
+            pipe.hget(slot_key(mint, *slot), &["b", "s"]);
+        }
+    }
+    let flows: Vec<(Option<u64>, Option<u64>)> = pipe.query_async(conn).await?;
+
+    let records = mints
+        .iter()
+        .zip(flows.chunks(slots.len().max(1)))
+        .map(|(mint, flows)| {
+            let (bought, sold) = prorated_flows(&slots, flows);
+            PressureUpdateRecord::new(now, *mint, window_secs, bought, sold)
+        })
+        .collect();
+    Ok(records)
+}
+
+/// Bought and sold lamports of the `flows` of the `slots`, prorated by their part in the window
+fn prorated_flows(slots: &[(i64, f64)], flows: &[(Option<u64>, Option<u64>)]) -> (u64, u64) {
+    let (bought, sold) =
+        slots
+            .iter()
+            .zip(flows)
+            .fold((0.0, 0.0), |(bought, sold), ((_, part), (b, s))| {
+                (
+                    bought + b.unwrap_or_default() as f64 * part,
+                    sold + s.unwrap_or_default() as f64 * part,
+                )
+            });
+    (bought.round() as u64, sold.round() as u64)
+}
+
+/// Trade notional histogram of the mint over the `window_secs` before `now`
+pub async fn mint_notional_histogram(
+    conn: &mut MultiplexedConnection,
+    mint: &Pubkey,
+    window_secs: i64,
+    now: DateTime<Utc>,
+) -> Result<NotionalHistogram> {
+    let window_slots = window_slots(window_secs, now);
+    let mut pipe = redis::pipe();
+    for (slot, _) in &window_slots {
+        pipe.hgetall(slot_key(mint, *slot));
+    }
+    let slots: Vec<HashMap<String, u64>> = pipe.query_async(conn).await?;
+    let slots: Vec<_> = window_slots
+        .iter()
+        .map(|(_, part)| *part)
+        .zip(slots)
+        .collect();
+    Ok(NotionalHistogram::from_slots(window_secs, &slots))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notional_histogram() {
+        assert_eq!(notional_bucket(0), 0);
+        assert_eq!(notional_bucket(LAMPORTS_PER_SOL / 10), 1);
+        assert_eq!(notional_bucket(5 * LAMPORTS_PER_SOL), 2);
+        assert_eq!(notional_bucket(1000 * LAMPORTS_PER_SOL), 4);
+
+        let slots = vec![
+            (
+                0.5,
+                HashMap::from([
+                    ("c0".to_string(), 6),
+                    ("v0".to_string(), LAMPORTS_PER_SOL / 5),
+                ]),
+            ),
+            (
+                1.0,
+                HashMap::from([
+                    ("c0".to_string(), 0),
+                    ("c4".to_string(), 1),
+                    ("v4".to_string(), 150 * LAMPORTS_PER_SOL),
+                ]),
+            ),
+            (1.0, HashMap::new()),
+        ];
+        let histogram = NotionalHistogram::from_slots(3600, &slots);
+        assert_eq!(histogram.trades, 4);
+        // half of the oldest slot is inside the window
+        assert_eq!(histogram.buckets[0].sol_volume, 0.1);
+        assert_eq!(histogram.buckets.len(), 5);
+        assert_eq!(histogram.buckets[0].min_sol, 0.0);
+        assert_eq!(histogram.buckets[0].trades, 3);
+        assert_eq!(histogram.buckets[4].min_sol, 100.0);
+        assert_eq!(histogram.buckets[4].max_sol, None);
+        assert_eq!(histogram.buckets[4].sol_volume, 150.0);
+
+        let now = DateTime::from_timestamp(20 * MINT_STATS_SLOT_SECS + 30, 0).unwrap();
+        let slots = window_slots(3600, now);
+        assert_eq!(slots.len(), 13);
+        assert_eq!(slots[0], (8 * MINT_STATS_SLOT_SECS, 0.9));
+        assert!(slots[1..].iter().all(|(_, part)| *part == 1.0));
+        // right after the rollover the window still spans the previous slot
+        let slots = window_slots(MINT_STATS_SLOT_SECS, now);
+        assert_eq!(
+            slots,
+            vec![
+                (19 * MINT_STATS_SLOT_SECS, 0.9),
+                (20 * MINT_STATS_SLOT_SECS, 1.0)
+            ]
+        );
+        let flows = [
+            (Some(10 * LAMPORTS_PER_SOL), None),
+            (Some(LAMPORTS_PER_SOL), Some(2 * LAMPORTS_PER_SOL)),
+        ];
+        assert_eq!(
+            prorated_flows(&slots, &flows),
+            (10 * LAMPORTS_PER_SOL, 2 * LAMPORTS_PER_SOL)
+        );
+        // a window starting on a slot boundary takes that slot whole
+        let now = DateTime::from_timestamp(20 * MINT_STATS_SLOT_SECS, 0).unwrap();
+        assert_eq!(
+            window_slots(MINT_STATS_SLOT_SECS, now),
+            vec![
+                (19 * MINT_STATS_SLOT_SECS, 1.0),
+                (20 * MINT_STATS_SLOT_SECS, 1.0)
+            ]
+        );
+    }
+}
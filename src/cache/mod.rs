@@ -1,15 +1,88 @@
+mod api_key;
+mod api_usage;
+mod avg_price;
+mod bigquery_cursor;
+mod congestion;
+mod consumer;
+mod creator;
 mod dex_evt;
+mod dex_volume;
+mod event_dedup;
+mod event_pubsub;
+mod failed_swap;
+mod followed_wallet;
+mod ingestion_audit;
+mod last_price;
+mod leaderboard;
+mod liquidity;
+mod liquidity_lock;
+mod meteora_dbc_complete;
+mod migration;
+mod mint_activity;
+mod mint_decimals;
+mod mint_registry;
+mod mint_stats;
+mod parse_failure;
 mod pool;
+mod pool_cache;
+mod pool_drained;
+mod pool_reserves;
+mod pool_state;
+mod pool_trades;
+mod price_guard;
 mod pumpfun_complete;
+mod pumpfun_set_params;
 mod qn_req_body;
+mod quarantine;
+mod queue;
 mod redis;
+mod shadow_diff;
+mod sol_usd_price;
 mod token;
 mod trade;
+mod wallet_cluster;
+mod watchlist;
 
+pub use api_key::*;
+pub use api_usage::*;
+pub use avg_price::*;
+pub use bigquery_cursor::*;
+pub use congestion::*;
+pub use consumer::*;
+pub use creator::*;
 pub use dex_evt::*;
+pub use dex_volume::*;
+pub use event_dedup::*;
+pub use event_pubsub::*;
+pub use failed_swap::*;
+pub use followed_wallet::*;
+pub use ingestion_audit::*;
+pub use last_price::*;
+pub use leaderboard::*;
+pub use liquidity::*;
+pub use liquidity_lock::*;
+pub use meteora_dbc_complete::*;
+pub use migration::*;
+pub use mint_activity::*;
+pub use mint_decimals::*;
+pub use mint_registry::*;
+pub use mint_stats::*;
+pub use parse_failure::*;
 pub use pool::*;
+pub use pool_cache::*;
+pub use pool_drained::*;
+pub use pool_reserves::*;
+pub use pool_state::*;
+pub use pool_trades::*;
+pub use price_guard::*;
 pub use pumpfun_complete::*;
+pub use pumpfun_set_params::*;
 pub use qn_req_body::*;
+pub use quarantine::*;
 pub use redis::*;
+pub use shadow_diff::*;
+pub use sol_usd_price::*;
 pub use token::*;
 pub use trade::*;
+pub use wallet_cluster::*;
+pub use watchlist::*;
@@ -0,0 +1,146 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc, serde::ts_seconds};
+use redis::{AsyncCommands, aio::MultiplexedConnection};
+use serde::{Deserialize, Serialize};
+
+use crate::qn_req_processor::Tx;
+
+/// Failed transactions kept, the older ones are trimmed
+pub const PARSE_FAILURES_MAX_LEN: isize = 1000;
+
+/// dead letter list of the transactions which failed to parse, the latest first
+const PARSE_FAILURES_KEY: &str = "list:dex_parse_failures";
+
+/// A transaction which failed to parse as a whole or had logs quarantined, kept to be queued
+/// again once the decoder is fixed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParseFailure {
+    /// as queued by the stream
+    pub tx: Tx,
+    pub error: String,
+    #[serde(with = "ts_seconds")]
+    pub failed_at: DateTime<Utc>,
+    /// the quarantined logs, empty when the whole transaction failed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed_logs: Vec<usize>,
+}
+
+impl ParseFailure {
+    pub fn new(tx: Tx, error: String) -> Self {
+        Self {
+            tx,
+            error,
+            failed_at: Utc::now(),
+            failed_logs: vec![],
+        }
+    }
+
+    /// The transaction to queue again, only its failed logs are parsed then
+    pub fn into_requeued_tx(self) -> Tx {
+        let mut tx = self.tx;
+        if !self.failed_logs.is_empty() {
+            tx.requeued_logs = Some(self.failed_logs);
+        }
+        tx
+    }
+}
+
+pub async fn push_parse_failures(
+    conn: &mut MultiplexedConnection,
+    failures: &[ParseFailure],
+) -> Result<()> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    let mut pipe = redis::pipe();
+    for failure in failures {
+        pipe.lpush(PARSE_FAILURES_KEY, serde_json::to_string(failure)?)
+            .ignore();
+    }
+    pipe.ltrim(PARSE_FAILURES_KEY, 0, PARSE_FAILURES_MAX_LEN - 1)
+        .ignore();
+    let _: () = pipe.query_async(conn).await?;
+    Ok(())
+}
+
+/// The failures kept, the latest first
+pub async fn list_parse_failures(conn: &mut MultiplexedConnection) -> Result<Vec<ParseFailure>> {
+    let records: Vec<String> = conn.lrange(PARSE_FAILURES_KEY, 0, -1).await?;
+    Ok(records
+        .iter()
+        .filter_map(|it| serde_json::from_str(it).ok())
+        .collect())
+}
+
+/// The failures of the txids, or all of them without txids, to queue them again. A tx which
+/// failed twice is returned once. Returns the records too, to remove them with
+/// `remove_parse_failures` once queued.
+pub async fn find_parse_failures(
+    conn: &mut MultiplexedConnection,
+    txids: Option<&HashSet<String>>,
+) -> Result<(Vec<ParseFailure>, Vec<String>)> {
+    let records: Vec<String> = conn.lrange(PARSE_FAILURES_KEY, 0, -1).await?;
+    let mut found = vec![];
+    let mut found_records = vec![];
+    let mut seen = HashSet::new();
+    for record in records {
+        let Ok(failure) = serde_json::from_str::<ParseFailure>(&record) else {
+            continue;
+        };
+        if txids.is_some_and(|it| !it.contains(&failure.tx.signature)) {
+            continue;
+        }
+        if seen.insert(failure.tx.signature.clone()) {
+            found.push(failure);
+        }
+        found_records.push(record);
+    }
+    Ok((found, found_records))
+}
+
+pub async fn remove_parse_failures(
+    conn: &mut MultiplexedConnection,
+    records: &[String],
+) -> Result<()> {
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    let mut pipe = redis::pipe();
+    for record in records {
+        // by value, the processor may push failures meanwhile
+        pipe.lrem(PARSE_FAILURES_KEY, 1, record).ignore();
+    }
+    let _: () = pipe.query_async(conn).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_failure_keeps_stream_tx() {
+        let tx = json!({
+            "blkTs": 100,
+            "slot": 10,
+            "signature": "sig",
+            "logs": ["Program log: ray_log: A1x8"],
+            "ixs": [],
+        });
+        let failure = ParseFailure::new(serde_json::from_value(tx.clone()).unwrap(), "bad".into());
+        let record: serde_json::Value = serde_json::to_value(&failure).unwrap();
+        assert_eq!(record["tx"], tx);
+        assert_eq!(record["error"], "bad");
+
+        let mut failure = failure;
+        assert_eq!(failure.clone().into_requeued_tx().requeued_logs, None);
+        failure.failed_logs = vec![0];
+        assert_eq!(failure.into_requeued_tx().requeued_logs, Some(vec![0]));
+    }
+}
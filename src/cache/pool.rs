@@ -1,28 +1,36 @@
-use std::str::FromStr;
-
-use anyhow::{Result, anyhow};
+use anyhow::Result;
 use chrono::{DateTime, Utc, serde::ts_seconds};
-use redis::aio::MultiplexedConnection;
 use serde::{Deserialize, Serialize};
 use serde_with::{DisplayFromStr, serde_as};
 use solana_sdk::pubkey::Pubkey;
 
 use crate::{
-    common::{Dex, TxBaseMetaInfo, WSOL_MINT},
+    common::{self, Dex, TxBaseMetaInfo, WSOL_MINT, utils},
     meteora::{
         damm::{
             event::MeteoraDammPoolCreated,
             instruction::{INIT_WITH_CONFIG_IX_ID, INIT_WITH_CONFIG2_IX_ID},
         },
+        dbc::event::MeteoraDbcInitializePoolEvent,
         dlmm::event::MeteoraLbPairCreateEvent,
     },
+    moonshot::instruction::MoonshotTokenMint,
+    orca::{
+        event::WhirlpoolPoolInitializedEvent,
+        instruction::{SwapAccountIdx, pool_funder_idx},
+    },
+    parse_error::{DexParseError, LogAccounts},
+    pda,
     pumpamm::event::PumpAmmCreatePoolEvent,
     pumpfun::event::CreateEvent,
-    qn_req_processor::IxAccount,
-    raydium::event::InitLog,
+    qn_req_processor::{EventSource, IxAccount},
+    raydium::{
+        clmm::event::RaydiumClmmPoolCreatedEvent, cpmm::instruction::RaydiumCpmmInitialize,
+        event::InitLog,
+    },
 };
 
-use super::RedisCacheRecord;
+use super::{DexPoolCache, RedisCacheRecord};
 
 #[serde_as]
 #[derive(Debug, Serialize, Deserialize)]
@@ -43,6 +51,43 @@ pub struct DexPoolCreatedRecord {
     pub mint_b: Pubkey,
     pub decimals_a: u8,
     pub decimals_b: u8,
+    /// reserves when the pool was created, only known for some dexes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub init_liquidity: Option<PoolLiquidity>,
+    /// first funder of the creator, attached by the webhook with `first_funder_lookup` on once
+    /// it's cached
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub creator_funded_by: Option<Pubkey>,
+    /// the stream batch the event was parsed from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<EventSource>,
+}
+
+/// SOL and token reserves of a WSOL pair pool
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PoolLiquidity {
+    pub sol_amt: u64,
+    pub token_amt: u64,
+    pub price_sol: f64,
+}
+
+impl PoolLiquidity {
+    /// `(mint, amount, decimals)` of both sides, `None` if the pool is not a WSOL pair
+    pub fn new(a: (Pubkey, u64, u8), b: (Pubkey, u64, u8)) -> Option<Self> {
+        let ((_, sol_amt, _), (_, token_amt, token_decimals)) = if a.0 == WSOL_MINT {
+            (a, b)
+        } else if b.0 == WSOL_MINT {
+            (b, a)
+        } else {
+            return None;
+        };
+        Some(Self {
+            sol_amt,
+            token_amt,
+            price_sol: utils::calc_price_sol(sol_amt, token_amt, token_decimals),
+        })
+    }
 }
 
 pub const DEX_POOL_RECORD_EXP_SECS: u64 = 3600 * 12;
@@ -52,11 +97,20 @@ impl DexPoolCreatedRecord {
         self.mint_a == WSOL_MINT || self.mint_b == WSOL_MINT
     }
 
+    /// A pumpamm pool created by the migration of a bonding curve, its creator is the pool
+    /// authority of the pumpfun program
+    pub fn is_curve_migration(&self) -> bool {
+        self.dex == Dex::PumpAmm
+            && self.creator == pda::pumpfun_pool_authority(&self.as_pool_record().token_mint())
+    }
+
     pub fn as_pool_record(&self) -> DexPoolRecord {
         DexPoolRecord {
             addr: self.addr,
             dex: self.dex,
             is_complete: false,
+            is_drained: false,
+            is_lp_locked: false,
             mint_a: self.mint_a,
             mint_b: self.mint_b,
             decimals_a: self.decimals_a,
@@ -84,6 +138,9 @@ impl DexPoolCreatedRecord {
             mint_b: WSOL_MINT,
             decimals_a: 6,
             decimals_b: 9,
+            init_liquidity: None,
+            creator_funded_by: None,
+            source: None,
         }
     }
 
@@ -107,6 +164,16 @@ impl DexPoolCreatedRecord {
             mint_b: log.quote_mint,
             decimals_a: log.base_mint_decimals,
             decimals_b: log.quote_mint_decimals,
+            init_liquidity: PoolLiquidity::new(
+                (log.base_mint, log.pool_base_amount, log.base_mint_decimals),
+                (
+                    log.quote_mint,
+                    log.pool_quote_amount,
+                    log.quote_mint_decimals,
+                ),
+            ),
+            creator_funded_by: None,
+            source: None,
         }
     }
 
@@ -114,23 +181,12 @@ impl DexPoolCreatedRecord {
         tx_meta: TxBaseMetaInfo,
         log: InitLog,
         accounts: &[IxAccount],
-    ) -> Result<Self> {
-        let amm_acc = accounts
-            .get(4)
-            .ok_or_else(|| anyhow!("need amm addr in init raydium instruction accounts"))?;
-        let amm_pubkey = Pubkey::from_str(&amm_acc.pubkey)?;
-        let coin_mint_acc = accounts
-            .get(8)
-            .ok_or_else(|| anyhow!("need coin mint in init raydium instruction accounts"))?;
-        let coin_mint_pubkey = Pubkey::from_str(&coin_mint_acc.pubkey)?;
-        let pc_mint_acc = accounts
-            .get(9)
-            .ok_or_else(|| anyhow!("need pc mint in init raydium instruction accounts"))?;
-        let pc_mint_pubkey = Pubkey::from_str(&pc_mint_acc.pubkey)?;
-        let creator_acc = accounts
-            .get(17)
-            .ok_or_else(|| anyhow!("need pool creator in init raydium instruction accounts"))?;
-        let creator_pubkey = Pubkey::from_str(&creator_acc.pubkey)?;
+    ) -> Result<Self, DexParseError> {
+        let accounts = LogAccounts::new(accounts, Dex::RaydiumAmm, "init");
+        let amm_pubkey = accounts.pubkey(4)?;
+        let coin_mint_pubkey = accounts.pubkey(8)?;
+        let pc_mint_pubkey = accounts.pubkey(9)?;
+        let creator_pubkey = accounts.pubkey(17)?;
 
         let TxBaseMetaInfo {
             blk_ts,
@@ -150,6 +206,12 @@ impl DexPoolCreatedRecord {
             mint_b: pc_mint_pubkey,
             decimals_a: log.coin_decimals,
             decimals_b: log.pc_decimals,
+            init_liquidity: PoolLiquidity::new(
+                (coin_mint_pubkey, log.coin_amount, log.coin_decimals),
+                (pc_mint_pubkey, log.pc_amount, log.pc_decimals),
+            ),
+            creator_funded_by: None,
+            source: None,
         })
     }
 
@@ -157,7 +219,7 @@ impl DexPoolCreatedRecord {
         tx_meta: TxBaseMetaInfo,
         log: MeteoraLbPairCreateEvent,
         accounts: &[IxAccount],
-    ) -> Result<Self> {
+    ) -> Result<Self, DexParseError> {
         let TxBaseMetaInfo {
             blk_ts,
             slot,
@@ -172,28 +234,10 @@ impl DexPoolCreatedRecord {
             ..
         } = log;
 
-        let x_vault_acc = accounts.get(4).ok_or_else(|| {
-            anyhow!("need x vault in meteora dlmm create lb pair instruction accounts")
-        })?;
-        let x_vault_token_amt = x_vault_acc
-            .post_amt
-            .token
-            .clone()
-            .ok_or_else(|| anyhow!("meteora dlmm x vault should have token amt"))?;
-
-        let y_vault_acc = accounts.get(5).ok_or_else(|| {
-            anyhow!("need y vault in meteora dlmm create lb pair instruction accounts")
-        })?;
-        let y_vault_token_amt = y_vault_acc
-            .post_amt
-            .token
-            .clone()
-            .ok_or_else(|| anyhow!("meteora dlmm y vault should have token amt"))?;
-
-        let creator_acc = accounts.get(8).ok_or_else(|| {
-            anyhow!("need pool creator in meteora dlmm create lb pair instruction accounts")
-        })?;
-        let creator_pubkey = Pubkey::from_str(&creator_acc.pubkey)?;
+        let accounts = LogAccounts::new(accounts, Dex::MeteoraDlmm, "lb_pair_create");
+        let x_vault_token_amt = accounts.post_token_amt(4)?;
+        let y_vault_token_amt = accounts.post_token_amt(5)?;
+        let creator_pubkey = accounts.pubkey(8)?;
 
         Ok(Self {
             blk_ts,
@@ -207,6 +251,9 @@ impl DexPoolCreatedRecord {
             mint_b: token_y,
             decimals_a: x_vault_token_amt.decimals,
             decimals_b: y_vault_token_amt.decimals,
+            init_liquidity: None,
+            creator_funded_by: None,
+            source: None,
         })
     }
 
@@ -215,7 +262,7 @@ impl DexPoolCreatedRecord {
         log: MeteoraDammPoolCreated,
         accounts: &[IxAccount],
         ix_data: &str,
-    ) -> Result<Self> {
+    ) -> Result<Self, DexParseError> {
         let TxBaseMetaInfo {
             blk_ts,
             slot,
@@ -229,34 +276,18 @@ impl DexPoolCreatedRecord {
             token_b_mint,
             ..
         } = log;
-        let ix_bytes = bs58::decode(ix_data).into_vec()?;
+        let ix_bytes = bs58::decode(ix_data)
+            .into_vec()
+            .map_err(|err| DexParseError::corrupt(Dex::MeteoraDamm, err))?;
         let has_config = ix_bytes.starts_with(&INIT_WITH_CONFIG_IX_ID)
             || ix_bytes.starts_with(&INIT_WITH_CONFIG2_IX_ID);
         let (token_vault_a_idx, token_vault_b_idx) = if has_config { (7, 8) } else { (6, 7) };
 
-        let a_vault_acc = accounts.get(token_vault_a_idx).ok_or_else(|| {
-            anyhow!("need a token vault in meteora damm create pool instruction accounts")
-        })?;
-        let a_vault_token_amt = a_vault_acc
-            .post_amt
-            .token
-            .clone()
-            .ok_or_else(|| anyhow!("meteora damm a valult should have token amt"))?;
-
-        let b_vault_acc = accounts.get(token_vault_b_idx).ok_or_else(|| {
-            anyhow!("need b token vault in meteora damm create pool instruction accounts")
-        })?;
-        let b_vault_token_amt = b_vault_acc
-            .post_amt
-            .token
-            .clone()
-            .ok_or_else(|| anyhow!("meteora damm b token valult should have token amt"))?;
-
+        let accounts = LogAccounts::new(accounts, Dex::MeteoraDamm, "pool_created");
+        let a_vault_token_amt = accounts.post_token_amt(token_vault_a_idx)?;
+        let b_vault_token_amt = accounts.post_token_amt(token_vault_b_idx)?;
         let creator_idx = if has_config { 18 } else { 17 };
-        let creator_acc = accounts.get(creator_idx).ok_or_else(|| {
-            anyhow!("need pool creator in meteora damm create pool instruction accounts")
-        })?;
-        let creator_pubkey = Pubkey::from_str(&creator_acc.pubkey)?;
+        let creator_pubkey = accounts.pubkey(creator_idx)?;
 
         Ok(Self {
             blk_ts,
@@ -270,17 +301,217 @@ impl DexPoolCreatedRecord {
             mint_b: token_b_mint,
             decimals_a: a_vault_token_amt.decimals,
             decimals_b: b_vault_token_amt.decimals,
+            init_liquidity: None,
+            creator_funded_by: None,
+            source: None,
+        })
+    }
+
+    pub fn from_raydium_clmm_pool_created_log(
+        tx_meta: TxBaseMetaInfo,
+        log: RaydiumClmmPoolCreatedEvent,
+        accounts: &[IxAccount],
+    ) -> Result<Self, DexParseError> {
+        let TxBaseMetaInfo {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+        } = tx_meta;
+
+        let RaydiumClmmPoolCreatedEvent {
+            pool_state,
+            token_mint_0,
+            token_mint_1,
+            ..
+        } = log;
+
+        let accounts = LogAccounts::new(accounts, Dex::RaydiumClmm, "pool_created");
+        let creator_pubkey = accounts.pubkey(0)?;
+        let vault_0_token_amt = accounts.post_token_amt(5)?;
+        let vault_1_token_amt = accounts.post_token_amt(6)?;
+
+        Ok(Self {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+            addr: pool_state,
+            creator: creator_pubkey,
+            dex: Dex::RaydiumClmm,
+            mint_a: token_mint_0,
+            mint_b: token_mint_1,
+            decimals_a: vault_0_token_amt.decimals,
+            decimals_b: vault_1_token_amt.decimals,
+            // the liquidity is added by opening positions after the pool is created
+            init_liquidity: None,
+            creator_funded_by: None,
+            source: None,
+        })
+    }
+
+    pub fn from_orca_pool_initialized_log(
+        tx_meta: TxBaseMetaInfo,
+        log: WhirlpoolPoolInitializedEvent,
+        accounts: &[IxAccount],
+        ix_data: &str,
+    ) -> Result<Self, DexParseError> {
+        let TxBaseMetaInfo {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+        } = tx_meta;
+
+        let ix_bytes = bs58::decode(ix_data)
+            .into_vec()
+            .map_err(|err| DexParseError::corrupt(Dex::OrcaWhirlpool, err))?;
+        let accounts = LogAccounts::new(accounts, Dex::OrcaWhirlpool, "pool_initialized");
+        let creator_pubkey = accounts.pubkey(pool_funder_idx(&ix_bytes))?;
+
+        Ok(Self {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+            addr: log.whirlpool,
+            creator: creator_pubkey,
+            dex: Dex::OrcaWhirlpool,
+            mint_a: log.token_mint_a,
+            mint_b: log.token_mint_b,
+            decimals_a: log.decimals_a,
+            decimals_b: log.decimals_b,
+            // the liquidity is added by opening positions after the pool is initialized
+            init_liquidity: None,
+            creator_funded_by: None,
+            source: None,
+        })
+    }
+
+    pub fn from_meteora_dbc_initialize_pool_log(
+        tx_meta: TxBaseMetaInfo,
+        log: MeteoraDbcInitializePoolEvent,
+        accounts: &[IxAccount],
+    ) -> Result<Self, DexParseError> {
+        let TxBaseMetaInfo {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+        } = tx_meta;
+
+        // the spl token and token-2022 initialize instructions share the first accounts
+        let accounts = LogAccounts::new(accounts, Dex::MeteoraDbc, "initialize_pool");
+        let quote_mint = accounts.pubkey(4)?;
+        let base_vault_token_amt = accounts.post_token_amt(6)?;
+        let quote_vault_token_amt = accounts.post_token_amt(7)?;
+
+        Ok(Self {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+            addr: log.pool,
+            creator: log.creator,
+            dex: Dex::MeteoraDbc,
+            mint_a: log.base_mint,
+            mint_b: quote_mint,
+            decimals_a: base_vault_token_amt.decimals,
+            decimals_b: quote_vault_token_amt.decimals,
+            // the curve starts without quote reserve
+            init_liquidity: None,
+            creator_funded_by: None,
+            source: None,
+        })
+    }
+
+    pub fn from_raydium_cpmm_initialize(
+        tx_meta: TxBaseMetaInfo,
+        ix: RaydiumCpmmInitialize,
+        accounts: &[IxAccount],
+    ) -> Result<Self, DexParseError> {
+        let TxBaseMetaInfo {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+        } = tx_meta;
+
+        let accounts = LogAccounts::new(accounts, Dex::RaydiumCpmm, "initialize");
+        let creator_pubkey = accounts.pubkey(0)?;
+        let pool_pubkey = accounts.pubkey(3)?;
+        let mint_0 = accounts.pubkey(4)?;
+        let mint_1 = accounts.pubkey(5)?;
+        let vault_0_token_amt = accounts.post_token_amt(10)?;
+        let vault_1_token_amt = accounts.post_token_amt(11)?;
+
+        Ok(Self {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+            addr: pool_pubkey,
+            creator: creator_pubkey,
+            dex: Dex::RaydiumCpmm,
+            mint_a: mint_0,
+            mint_b: mint_1,
+            decimals_a: vault_0_token_amt.decimals,
+            decimals_b: vault_1_token_amt.decimals,
+            init_liquidity: PoolLiquidity::new(
+                (mint_0, ix.init_amount_0, vault_0_token_amt.decimals),
+                (mint_1, ix.init_amount_1, vault_1_token_amt.decimals),
+            ),
+            creator_funded_by: None,
+            source: None,
+        })
+    }
+
+    pub fn from_moonshot_token_mint(
+        tx_meta: TxBaseMetaInfo,
+        ix: &MoonshotTokenMint,
+        accounts: &[IxAccount],
+    ) -> Result<Self, DexParseError> {
+        let TxBaseMetaInfo {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+        } = tx_meta;
+
+        let accounts = LogAccounts::new(accounts, Dex::Moonshot, "token_mint");
+        Ok(Self {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+            addr: accounts.pubkey(2)?,
+            creator: accounts.pubkey(0)?,
+            dex: Dex::Moonshot,
+            mint_a: accounts.pubkey(3)?,
+            mint_b: WSOL_MINT,
+            decimals_a: ix.decimals,
+            decimals_b: 9,
+            // the curve starts with virtual sol reserves only, like pumpfun
+            init_liquidity: None,
+            creator_funded_by: None,
+            source: None,
         })
     }
 }
 
 #[serde_as]
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DexPoolRecord {
     #[serde_as(as = "DisplayFromStr")]
     pub addr: Pubkey,
     pub dex: Dex,
     pub is_complete: bool,
+    /// the SOL reserves fell under the floor of `pool_drained`, see `detect_drained_pools`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_drained: bool,
+    /// all the lp tokens are locked or burned, see `lp_lock_watcher`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_lp_locked: bool,
     #[serde_as(as = "DisplayFromStr")]
     pub mint_a: Pubkey,
     #[serde_as(as = "DisplayFromStr")]
@@ -293,46 +524,29 @@ impl DexPoolRecord {
     pub async fn from_meteora_swap_accounts(
         lbpair_pubkey: Pubkey,
         accounts: &[IxAccount],
-        redis_conn: &mut MultiplexedConnection,
-    ) -> Result<Self> {
-        let key = format!("{}{}", DexPoolRecord::prefix(), lbpair_pubkey);
-        let mut cached_pool = DexPoolRecord::from_redis(redis_conn, &key).await?;
+        pools: &mut DexPoolCache,
+    ) -> Result<Self, DexParseError> {
+        let mut cached_pool = pools.get(&lbpair_pubkey).await?;
         if cached_pool.is_none() {
-            let token_x_vault = accounts
-                .get(2)
-                .ok_or_else(|| anyhow!("need token x value in meteora dlmm swap log"))?;
-            let pool_token_x_amt = token_x_vault.post_amt.token.clone().ok_or_else(|| {
-                anyhow!(
-                    "meteora dlmm token x vault {} should have balance",
-                    token_x_vault.pubkey
-                )
-            })?;
-            let token_x_mint = Pubkey::from_str(&pool_token_x_amt.mint)?;
+            let accounts = LogAccounts::new(accounts, Dex::MeteoraDlmm, "swap");
+            let pool_token_x_amt = accounts.post_token_amt(2)?;
+            let token_x_mint = accounts.parse_pubkey(&pool_token_x_amt.mint)?;
             let token_x_decimals = pool_token_x_amt.decimals;
 
-            let token_y_vault = accounts
-                .get(3)
-                .ok_or_else(|| anyhow!("need token y value in meteora dlmm swap log"))?;
-            let pool_token_y_amt = token_y_vault.post_amt.token.clone().ok_or_else(|| {
-                anyhow!(
-                    "meteora dlmm token y vault {} should have balance",
-                    token_y_vault.pubkey
-                )
-            })?;
-            let token_y_mint = Pubkey::from_str(&pool_token_y_amt.mint)?;
+            let pool_token_y_amt = accounts.post_token_amt(3)?;
+            let token_y_mint = accounts.parse_pubkey(&pool_token_y_amt.mint)?;
             let token_y_decimals = pool_token_y_amt.decimals;
             let pool_record = Self {
                 addr: lbpair_pubkey,
                 dex: Dex::MeteoraDlmm,
                 is_complete: false,
+                is_drained: false,
+                is_lp_locked: false,
                 mint_a: token_x_mint,
                 mint_b: token_y_mint,
                 decimals_a: token_x_decimals,
                 decimals_b: token_y_decimals,
             };
-            pool_record
-                .save_ex(redis_conn, DEX_POOL_RECORD_EXP_SECS)
-                .await?;
             cached_pool = Some(pool_record);
         }
         Ok(cached_pool.unwrap())
@@ -341,46 +555,29 @@ impl DexPoolRecord {
     pub async fn from_meteora_damm_swap_accounts(
         pool: Pubkey,
         accounts: &[IxAccount],
-        redis_conn: &mut MultiplexedConnection,
-    ) -> Result<Self> {
-        let key = format!("{}{}", DexPoolRecord::prefix(), pool);
-        let mut cached_pool = DexPoolRecord::from_redis(redis_conn, &key).await?;
+        pools: &mut DexPoolCache,
+    ) -> Result<Self, DexParseError> {
+        let mut cached_pool = pools.get(&pool).await?;
         if cached_pool.is_none() {
-            let token_vault_a = accounts
-                .get(5)
-                .ok_or_else(|| anyhow!("need token a value in meteora damm swap log"))?;
-            let pool_token_a_amt = token_vault_a.post_amt.token.clone().ok_or_else(|| {
-                anyhow!(
-                    "meteora damm token a vault {} should have balance",
-                    token_vault_a.pubkey
-                )
-            })?;
-            let token_a_mint = Pubkey::from_str(&pool_token_a_amt.mint)?;
+            let accounts = LogAccounts::new(accounts, Dex::MeteoraDamm, "swap");
+            let pool_token_a_amt = accounts.post_token_amt(5)?;
+            let token_a_mint = accounts.parse_pubkey(&pool_token_a_amt.mint)?;
             let token_a_decimals = pool_token_a_amt.decimals;
 
-            let token_vault_b = accounts
-                .get(6)
-                .ok_or_else(|| anyhow!("need token b value in meteora damm swap log"))?;
-            let pool_token_b_amt = token_vault_b.post_amt.token.clone().ok_or_else(|| {
-                anyhow!(
-                    "meteora damm token b vault {} should have balance",
-                    token_vault_b.pubkey
-                )
-            })?;
-            let token_b_mint = Pubkey::from_str(&pool_token_b_amt.mint)?;
+            let pool_token_b_amt = accounts.post_token_amt(6)?;
+            let token_b_mint = accounts.parse_pubkey(&pool_token_b_amt.mint)?;
             let token_b_decimals = pool_token_b_amt.decimals;
             let pool_record = Self {
                 addr: pool,
                 dex: Dex::MeteoraDamm,
                 is_complete: false,
+                is_drained: false,
+                is_lp_locked: false,
                 mint_a: token_a_mint,
                 mint_b: token_b_mint,
                 decimals_a: token_a_decimals,
                 decimals_b: token_b_decimals,
             };
-            pool_record
-                .save_ex(redis_conn, DEX_POOL_RECORD_EXP_SECS)
-                .await?;
             cached_pool = Some(pool_record);
         }
         Ok(cached_pool.unwrap())
@@ -389,47 +586,33 @@ impl DexPoolRecord {
     pub async fn from_pumpamm_swap_accounts(
         pool_pubkey: Pubkey,
         accounts: &[IxAccount],
-        redis_conn: &mut MultiplexedConnection,
-    ) -> Result<Self> {
-        let key = format!("{}{}", Self::prefix(), pool_pubkey);
-        let mut cached_pool = Self::from_redis(redis_conn, &key).await?;
+        pools: &mut DexPoolCache,
+    ) -> Result<Self, DexParseError> {
+        let mut cached_pool = pools.get(&pool_pubkey).await?;
         if cached_pool.is_none() {
             let base_token_vault_idx = 7;
             let quote_token_vault_idx = 8;
 
-            let base_token_vault = accounts
-                .get(base_token_vault_idx)
-                .ok_or_else(|| anyhow!("need base token vault in pumpamm swap log"))?;
-            let base_token_amt = base_token_vault
-                .post_amt
-                .token
-                .clone()
-                .ok_or_else(|| anyhow!("base token should have balance in pumpamm swap log"))?;
-            let mint_a = Pubkey::from_str(&base_token_amt.mint)?;
+            let accounts = LogAccounts::new(accounts, Dex::PumpAmm, "swap");
+            let base_token_amt = accounts.post_token_amt(base_token_vault_idx)?;
+            let mint_a = accounts.parse_pubkey(&base_token_amt.mint)?;
             let decimals_a = base_token_amt.decimals;
 
-            let quote_token_vault = accounts
-                .get(quote_token_vault_idx)
-                .ok_or_else(|| anyhow!("need quote token vault in pumpamm swap log"))?;
-            let quote_token_amt =
-                quote_token_vault.post_amt.token.clone().ok_or_else(|| {
-                    anyhow!("quote token should have balance in pumpamm swap log")
-                })?;
-            let mint_b = Pubkey::from_str(&quote_token_amt.mint)?;
+            let quote_token_amt = accounts.post_token_amt(quote_token_vault_idx)?;
+            let mint_b = accounts.parse_pubkey(&quote_token_amt.mint)?;
             let decimals_b = quote_token_amt.decimals;
 
             let pool_record = Self {
                 addr: pool_pubkey,
                 dex: Dex::PumpAmm,
                 is_complete: false,
+                is_drained: false,
+                is_lp_locked: false,
                 mint_a,
                 mint_b,
                 decimals_a,
                 decimals_b,
             };
-            pool_record
-                .save_ex(redis_conn, DEX_POOL_RECORD_EXP_SECS)
-                .await?;
             cached_pool = Some(pool_record);
         }
 
@@ -439,10 +622,9 @@ impl DexPoolRecord {
     pub async fn from_raydium_amm_trade_accounts(
         amm_pubkey: Pubkey,
         accounts: &[IxAccount],
-        redis_conn: &mut MultiplexedConnection,
-    ) -> Result<Self> {
-        let key = format!("{}{}", DexPoolRecord::prefix(), amm_pubkey);
-        let mut cached_pool = DexPoolRecord::from_redis(redis_conn, &key).await?;
+        pools: &mut DexPoolCache,
+    ) -> Result<Self, DexParseError> {
+        let mut cached_pool = pools.get(&amm_pubkey).await?;
         if cached_pool.is_none() {
             let mut coin_token_vault_idx = 4;
             let mut pc_token_vault_idx = 5;
@@ -451,35 +633,157 @@ impl DexPoolRecord {
                 pc_token_vault_idx = 6;
             }
 
-            let coin_token_vault = accounts
-                .get(coin_token_vault_idx)
-                .ok_or_else(|| anyhow!("need coin token vault in raydium amm swap base in log"))?;
-            let coin_token_amt = coin_token_vault.post_amt.token.clone().ok_or_else(|| {
-                anyhow!("coin token should have balance in raydium amm base in swap")
-            })?;
-            let mint_a = Pubkey::from_str(&coin_token_amt.mint)?;
+            let accounts = LogAccounts::new(accounts, Dex::RaydiumAmm, "swap");
+            let coin_token_amt = accounts.post_token_amt(coin_token_vault_idx)?;
+            let mint_a = accounts.parse_pubkey(&coin_token_amt.mint)?;
             let decimals_a = coin_token_amt.decimals;
-            let pc_token_vault = accounts
-                .get(pc_token_vault_idx)
-                .ok_or_else(|| anyhow!("need pc token vault in raydium amm swap base in log"))?;
-            let pc_token_amt = pc_token_vault.post_amt.token.clone().ok_or_else(|| {
-                anyhow!("pc token should have balance in raydium amm base in swap log")
-            })?;
-            let mint_b = Pubkey::from_str(&pc_token_amt.mint)?;
+            let pc_token_amt = accounts.post_token_amt(pc_token_vault_idx)?;
+            let mint_b = accounts.parse_pubkey(&pc_token_amt.mint)?;
             let decimals_b = pc_token_amt.decimals;
 
             let pool_record = Self {
                 addr: amm_pubkey,
                 dex: Dex::RaydiumAmm,
                 is_complete: false,
+                is_drained: false,
+                is_lp_locked: false,
+                mint_a,
+                mint_b,
+                decimals_a,
+                decimals_b,
+            };
+            cached_pool = Some(pool_record);
+        }
+        Ok(cached_pool.unwrap())
+    }
+
+    /// The vaults of token 0 and token 1 are the input and output vaults of the swap, in the
+    /// order of its direction
+    pub async fn from_raydium_clmm_swap_accounts(
+        pool_pubkey: Pubkey,
+        zero_for_one: bool,
+        accounts: &[IxAccount],
+        pools: &mut DexPoolCache,
+    ) -> Result<Self, DexParseError> {
+        let mut cached_pool = pools.get(&pool_pubkey).await?;
+        if cached_pool.is_none() {
+            let (vault_0_idx, vault_1_idx) = if zero_for_one { (5, 6) } else { (6, 5) };
+
+            let accounts = LogAccounts::new(accounts, Dex::RaydiumClmm, "swap");
+            let vault_0_token_amt = accounts.post_token_amt(vault_0_idx)?;
+            let mint_a = accounts.parse_pubkey(&vault_0_token_amt.mint)?;
+            let vault_1_token_amt = accounts.post_token_amt(vault_1_idx)?;
+            let mint_b = accounts.parse_pubkey(&vault_1_token_amt.mint)?;
+
+            let pool_record = Self {
+                addr: pool_pubkey,
+                dex: Dex::RaydiumClmm,
+                is_complete: false,
+                is_drained: false,
+                is_lp_locked: false,
+                mint_a,
+                mint_b,
+                decimals_a: vault_0_token_amt.decimals,
+                decimals_b: vault_1_token_amt.decimals,
+            };
+            cached_pool = Some(pool_record);
+        }
+        Ok(cached_pool.unwrap())
+    }
+
+    pub async fn from_orca_swap_accounts(
+        whirlpool: Pubkey,
+        swap_accounts: SwapAccountIdx,
+        accounts: &[IxAccount],
+        pools: &mut DexPoolCache,
+    ) -> Result<Self, DexParseError> {
+        let mut cached_pool = pools.get(&whirlpool).await?;
+        if cached_pool.is_none() {
+            let accounts = LogAccounts::new(accounts, Dex::OrcaWhirlpool, "traded");
+            let vault_a_token_amt = accounts.post_token_amt(swap_accounts.token_vault_a)?;
+            let mint_a = accounts.parse_pubkey(&vault_a_token_amt.mint)?;
+            let vault_b_token_amt = accounts.post_token_amt(swap_accounts.token_vault_b)?;
+            let mint_b = accounts.parse_pubkey(&vault_b_token_amt.mint)?;
+
+            let pool_record = Self {
+                addr: whirlpool,
+                dex: Dex::OrcaWhirlpool,
+                is_complete: false,
+                is_drained: false,
+                is_lp_locked: false,
+                mint_a,
+                mint_b,
+                decimals_a: vault_a_token_amt.decimals,
+                decimals_b: vault_b_token_amt.decimals,
+            };
+            cached_pool = Some(pool_record);
+        }
+        Ok(cached_pool.unwrap())
+    }
+
+    pub async fn from_meteora_dbc_swap_accounts(
+        pool_pubkey: Pubkey,
+        accounts: &[IxAccount],
+        pools: &mut DexPoolCache,
+    ) -> Result<Self, DexParseError> {
+        let mut cached_pool = pools.get(&pool_pubkey).await?;
+        if cached_pool.is_none() {
+            let accounts = LogAccounts::new(accounts, Dex::MeteoraDbc, "swap");
+            let base_vault_token_amt = accounts.post_token_amt(5)?;
+            let quote_vault_token_amt = accounts.post_token_amt(6)?;
+
+            let pool_record = Self {
+                addr: pool_pubkey,
+                dex: Dex::MeteoraDbc,
+                is_complete: false,
+                is_drained: false,
+                is_lp_locked: false,
+                mint_a: accounts.pubkey(7)?,
+                mint_b: accounts.pubkey(8)?,
+                decimals_a: base_vault_token_amt.decimals,
+                decimals_b: quote_vault_token_amt.decimals,
+            };
+            cached_pool = Some(pool_record);
+        }
+        Ok(cached_pool.unwrap())
+    }
+
+    pub async fn from_raydium_cpmm_swap_accounts(
+        pool_pubkey: Pubkey,
+        accounts: &[IxAccount],
+        pools: &mut DexPoolCache,
+    ) -> Result<Self, DexParseError> {
+        let mut cached_pool = pools.get(&pool_pubkey).await?;
+        if cached_pool.is_none() {
+            let accounts = LogAccounts::new(accounts, Dex::RaydiumCpmm, "swap");
+            let input_vault_amt = accounts.post_token_amt(6)?;
+            let output_vault_amt = accounts.post_token_amt(7)?;
+            let input_mint = accounts.parse_pubkey(&input_vault_amt.mint)?;
+            let output_mint = accounts.parse_pubkey(&output_vault_amt.mint)?;
+
+            // token_0 of the pool is the mint sorting first
+            let ((mint_a, decimals_a), (mint_b, decimals_b)) = if input_mint < output_mint {
+                (
+                    (input_mint, input_vault_amt.decimals),
+                    (output_mint, output_vault_amt.decimals),
+                )
+            } else {
+                (
+                    (output_mint, output_vault_amt.decimals),
+                    (input_mint, input_vault_amt.decimals),
+                )
+            };
+            let pool_record = Self {
+                addr: pool_pubkey,
+                dex: Dex::RaydiumCpmm,
+                is_complete: false,
+                is_drained: false,
+                is_lp_locked: false,
                 mint_a,
                 mint_b,
                 decimals_a,
                 decimals_b,
             };
-            pool_record
-                .save_ex(redis_conn, DEX_POOL_RECORD_EXP_SECS)
-                .await?;
             cached_pool = Some(pool_record);
         }
         Ok(cached_pool.unwrap())
@@ -490,6 +794,8 @@ impl DexPoolRecord {
             addr: curve,
             dex: Dex::Pumpfun,
             is_complete,
+            is_drained: false,
+            is_lp_locked: false,
             mint_a: mint,
             mint_b: WSOL_MINT,
             decimals_a: 6,
@@ -499,31 +805,48 @@ impl DexPoolRecord {
 
     pub async fn from_pumpfun_trade_accounts(
         accounts: &[IxAccount],
-        redis_conn: &mut MultiplexedConnection,
-    ) -> Result<Self> {
-        let curve_acc = accounts
-            .get(3)
-            .ok_or_else(|| anyhow!("need curve addr in pumpfun trade accounts"))?;
-        let curve_pubkey = Pubkey::from_str(&curve_acc.pubkey)?;
-        let mint_acc = accounts
-            .get(2)
-            .ok_or_else(|| anyhow!("need token addr in pumpfun trade accounts"))?;
-        let mint_pubkey = Pubkey::from_str(&mint_acc.pubkey)?;
-        let key = format!("{}{}", DexPoolRecord::prefix(), curve_pubkey);
-        let mut cached_pool = DexPoolRecord::from_redis(redis_conn, &key).await?;
+        pools: &mut DexPoolCache,
+    ) -> Result<Self, DexParseError> {
+        let accounts = LogAccounts::new(accounts, Dex::Pumpfun, "trade");
+        let curve_pubkey = accounts.pubkey(3)?;
+        let mint_pubkey = accounts.pubkey(2)?;
+        let mut cached_pool = pools.get(&curve_pubkey).await?;
         if cached_pool.is_none() {
             let pool_record = Self {
                 addr: curve_pubkey,
                 dex: Dex::Pumpfun,
                 is_complete: false,
+                is_drained: false,
+                is_lp_locked: false,
                 mint_a: mint_pubkey,
                 mint_b: WSOL_MINT,
                 decimals_a: 6,
                 decimals_b: 9,
             };
-            pool_record
-                .save_ex(redis_conn, DEX_POOL_RECORD_EXP_SECS)
-                .await?;
+            cached_pool = Some(pool_record);
+        }
+        Ok(cached_pool.unwrap())
+    }
+
+    pub async fn from_moonshot_trade_accounts(
+        accounts: &[IxAccount],
+        pools: &mut DexPoolCache,
+    ) -> Result<Self, DexParseError> {
+        let accounts = LogAccounts::new(accounts, Dex::Moonshot, "trade");
+        let curve_pubkey = accounts.pubkey(2)?;
+        let mut cached_pool = pools.get(&curve_pubkey).await?;
+        if cached_pool.is_none() {
+            let pool_record = Self {
+                addr: curve_pubkey,
+                dex: Dex::Moonshot,
+                is_complete: false,
+                is_drained: false,
+                is_lp_locked: false,
+                mint_a: accounts.pubkey(6)?,
+                mint_b: WSOL_MINT,
+                decimals_a: accounts.post_token_amt(3)?.decimals,
+                decimals_b: 9,
+            };
             cached_pool = Some(pool_record);
         }
         Ok(cached_pool.unwrap())
@@ -533,16 +856,26 @@ impl DexPoolRecord {
         self.mint_a == WSOL_MINT || self.mint_b == WSOL_MINT
     }
 
+    /// The mint the pool is quoted in, the first of the accepted quote mints in the pair, none
+    /// if it's in neither
+    pub fn quote_mint(&self) -> Option<Pubkey> {
+        quote_mint_of(self.mint_a, self.mint_b, common::quote_mints())
+    }
+
+    fn is_quote_a(&self) -> bool {
+        self.quote_mint() == Some(self.mint_a)
+    }
+
     pub fn is_raydium_buy(&self, direction: u64) -> bool {
         // pc2coin
         if direction == 1 {
-            if self.mint_b == WSOL_MINT {
+            if !self.is_quote_a() {
                 return true;
             }
             return false;
         }
         // coin2pc
-        if self.mint_b == WSOL_MINT {
+        if !self.is_quote_a() {
             return false;
         }
 
@@ -551,13 +884,13 @@ impl DexPoolRecord {
 
     pub fn is_meteora_dlmm_buy(&self, swap_for_y: bool) -> bool {
         if swap_for_y {
-            if self.mint_a == WSOL_MINT {
+            if self.is_quote_a() {
                 return true;
             }
             return false;
         }
 
-        if self.mint_a == WSOL_MINT {
+        if self.is_quote_a() {
             return false;
         }
 
@@ -565,14 +898,23 @@ impl DexPoolRecord {
     }
 
     pub fn token_decimals(&self) -> u8 {
-        if self.mint_a == WSOL_MINT {
+        if self.is_quote_a() {
             return self.decimals_b;
         }
 
         self.decimals_a
     }
+
+    pub fn quote_decimals(&self) -> u8 {
+        if self.is_quote_a() {
+            return self.decimals_a;
+        }
+
+        self.decimals_b
+    }
+
     pub fn token_mint(&self) -> Pubkey {
-        if self.mint_a == WSOL_MINT {
+        if self.is_quote_a() {
             return self.mint_b;
         }
 
@@ -580,6 +922,14 @@ impl DexPoolRecord {
     }
 }
 
+/// The first of `quotes` paired in a pool of `mint_a` and `mint_b`
+fn quote_mint_of(mint_a: Pubkey, mint_b: Pubkey, quotes: &[Pubkey]) -> Option<Pubkey> {
+    quotes
+        .iter()
+        .find(|it| **it == mint_a || **it == mint_b)
+        .copied()
+}
+
 impl RedisCacheRecord for DexPoolRecord {
     fn key(&self) -> String {
         format!("{}{}", Self::prefix(), self.addr)
@@ -589,3 +939,39 @@ impl RedisCacheRecord for DexPoolRecord {
         "pool:"
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{USDC_MINT, USDT_MINT};
+
+    #[test]
+    fn test_quote_mint_of() {
+        let mint = Pubkey::new_unique();
+        let quotes = [WSOL_MINT, USDC_MINT, USDT_MINT];
+        assert_eq!(quote_mint_of(mint, WSOL_MINT, &quotes), Some(WSOL_MINT));
+        assert_eq!(quote_mint_of(USDC_MINT, mint, &quotes), Some(USDC_MINT));
+        // by priority when both are quote mints
+        assert_eq!(
+            quote_mint_of(USDC_MINT, WSOL_MINT, &quotes),
+            Some(WSOL_MINT)
+        );
+        assert_eq!(quote_mint_of(USDC_MINT, mint, &[WSOL_MINT]), None);
+    }
+
+    #[test]
+    fn test_pool_liquidity() {
+        let mint = Pubkey::new_unique();
+        let expected = PoolLiquidity {
+            sol_amt: 30_000_000_000,
+            token_amt: 1_000_000_000_000,
+            price_sol: 0.00003,
+        };
+        let token = (mint, 1_000_000_000_000, 6);
+        let sol = (WSOL_MINT, 30_000_000_000, 9);
+        // whichever side the WSOL is on
+        assert_eq!(PoolLiquidity::new(token, sol), Some(expected));
+        assert_eq!(PoolLiquidity::new(sol, token), Some(expected));
+        assert_eq!(PoolLiquidity::new(token, (USDC_MINT, 1_000_000, 6)), None);
+    }
+}
@@ -0,0 +1,395 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use redis::{AsyncCommands, Script, aio::MultiplexedConnection};
+use solana_sdk::pubkey::Pubkey;
+
+use super::{
+    DEX_POOL_RECORD_EXP_SECS, DexPoolRecord, RedisCacheRecord, get_mint_decimals,
+    queue_decimals_checks,
+};
+
+/// the archived records are kept 90 days after their last write, the pools expired from the
+/// cache still resolve when they're traded again
+const POOL_ARCHIVE_EXP_SECS: u64 = 90 * 24 * 3600;
+
+/// Set the pool record KEYS[1] and its archive KEYS[2] to ARGV[2], expiring after ARGV[3] and
+/// ARGV[4] seconds, if the record is still ARGV[1]. Returns 1 if set.
+const REPLACE_POOL_RECORD_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then
+    return 0
+end
+redis.call('SET', KEYS[1], ARGV[2], 'EX', ARGV[3])
+redis.call('SET', KEYS[2], ARGV[2], 'EX', ARGV[4])
+return 1
+"#;
+
+/// copy of a cached pool record, by pool address
+fn archive_key(addr: &Pubkey) -> String {
+    format!("str:pool_archive:{addr}")
+}
+
+/// The archived record of a pool expired from the cache
+pub async fn get_archived_pool(
+    conn: &mut MultiplexedConnection,
+    addr: &Pubkey,
+) -> Result<Option<DexPoolRecord>> {
+    let json: Option<String> = conn.get(archive_key(addr)).await?;
+    Ok(json.map(|it| serde_json::from_str(&it)).transpose()?)
+}
+
+/// Add the writes of a pool record to the cache and to the archive to the pipeline
+fn pipe_pool_record(pipe: &mut redis::Pipeline, record: &DexPoolRecord) -> Result<()> {
+    let json = record.json()?;
+    pipe.set_ex(record.key(), &json, DEX_POOL_RECORD_EXP_SECS)
+        .ignore()
+        .set_ex(archive_key(&record.addr), json, POOL_ARCHIVE_EXP_SECS)
+        .ignore();
+    Ok(())
+}
+
+/// Replace the cached record `read` of a pool by `record`, false if the record changed since it
+/// was read, e.g. by the processor caching new reserves
+pub async fn replace_pool_record(
+    conn: &mut MultiplexedConnection,
+    read: &str,
+    record: &DexPoolRecord,
+) -> Result<bool> {
+    let replaced: bool = Script::new(REPLACE_POOL_RECORD_SCRIPT)
+        .key(record.key())
+        .key(archive_key(&record.addr))
+        .arg(read)
+        .arg(record.json()?)
+        .arg(DEX_POOL_RECORD_EXP_SECS)
+        .arg(POOL_ARCHIVE_EXP_SECS)
+        .invoke_async(conn)
+        .await?;
+    Ok(replaced)
+}
+
+/// Pool records used while parsing one batch of transactions.
+///
+/// Pools referenced by the batch are fetched up front with one MGET by `prefetch`.
+/// Saved records are kept in memory and written back with one redis pipeline
+/// on `flush`, so later lookups in the same batch see them without a round trip.
+/// Prefetched pools get the onchain decimals of their mints once they're checked,
+/// pools new to the cache or read back from the archive are queued for the check.
+/// The written records also go to the pool archive, pools missing from the cache are read
+/// back from it and cached again.
+/// A read only copy writes nothing on `flush`, for a parse which mustn't change the cache.
+/// The tests use a detached cache of a few records, without redis.
+pub struct DexPoolCache {
+    /// none when detached
+    conn: Option<MultiplexedConnection>,
+    records: HashMap<Pubkey, DexPoolRecord>,
+    // prefetched pools not found in redis
+    missing: HashSet<Pubkey>,
+    dirty: HashSet<Pubkey>,
+    new_pools: HashSet<Pubkey>,
+    read_only: bool,
+}
+
+impl DexPoolCache {
+    pub fn new(conn: MultiplexedConnection) -> Self {
+        Self {
+            conn: Some(conn),
+            records: HashMap::new(),
+            missing: HashSet::new(),
+            dirty: HashSet::new(),
+            new_pools: HashSet::new(),
+            read_only: false,
+        }
+    }
+
+    /// Cache of the records only, the other pools are missing and nothing is written
+    #[cfg(test)]
+    pub fn detached(records: impl IntoIterator<Item = DexPoolRecord>) -> Self {
+        Self {
+            conn: None,
+            records: records.into_iter().map(|it| (it.addr, it)).collect(),
+            missing: HashSet::new(),
+            dirty: HashSet::new(),
+            new_pools: HashSet::new(),
+            read_only: false,
+        }
+    }
+
+    /// Copy of the records fetched so far whose saves are kept in memory only
+    pub fn read_only_copy(&self) -> Self {
+        Self {
+            conn: self.conn.clone(),
+            records: self.records.clone(),
+            missing: self.missing.clone(),
+            dirty: HashSet::new(),
+            new_pools: HashSet::new(),
+            read_only: true,
+        }
+    }
+
+    /// Copy of the records fetched so far for a worker parsing a part of the batch, its saves
+    /// are taken back by `merge`
+    pub fn worker_copy(&self) -> Self {
+        Self {
+            conn: self.conn.clone(),
+            records: self.records.clone(),
+            missing: self.missing.clone(),
+            dirty: HashSet::new(),
+            new_pools: HashSet::new(),
+            read_only: self.read_only,
+        }
+    }
+
+    /// Take back the records of a worker, its saves replace the records of the batch
+    pub fn merge(&mut self, worker: DexPoolCache) {
+        let DexPoolCache {
+            records,
+            dirty,
+            new_pools,
+            ..
+        } = worker;
+        for (addr, record) in records {
+            if dirty.contains(&addr) {
+                self.records.insert(addr, record);
+            } else {
+                self.records.entry(addr).or_insert(record);
+            }
+        }
+        self.missing.retain(|it| !self.records.contains_key(it));
+        self.dirty.extend(dirty);
+        self.new_pools.extend(new_pools);
+    }
+
+    /// the parse using the cache must not write to redis either
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// connection for the other records read while parsing the batch
+    pub fn conn(&mut self) -> &mut MultiplexedConnection {
+        self.conn
+            .as_mut()
+            .expect("a detached pool cache has no connection")
+    }
+
+    pub async fn prefetch(&mut self, addrs: impl IntoIterator<Item = Pubkey>) -> Result<()> {
+        let addrs: Vec<_> = addrs
+            .into_iter()
+            .filter(|it| !self.records.contains_key(it) && !self.missing.contains(it))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let Some(conn) = &mut self.conn else {
+            self.missing.extend(addrs);
+            return Ok(());
+        };
+        let keys: Vec<_> = addrs
+            .iter()
+            .map(|it| DexPoolRecord::new_key(it.to_string()))
+            .collect();
+        let records = DexPoolRecord::mget(conn, &keys).await?;
+        let mut found = vec![];
+        let mut expired = vec![];
+        for (addr, record) in addrs.into_iter().zip(records) {
+            match record {
+                Some(record) => {
+                    self.records.insert(addr, record);
+                    found.push(addr);
+                }
+                None => expired.push(addr),
+            }
+        }
+        if !expired.is_empty() {
+            let keys: Vec<_> = expired.iter().map(archive_key).collect();
+            // explicit MGET, like the one of the cached records
+            let archived: Vec<Option<String>> =
+                redis::cmd("MGET").arg(keys).query_async(conn).await?;
+            for (addr, json) in expired.into_iter().zip(archived) {
+                match json {
+                    Some(json) => {
+                        self.rehydrate(serde_json::from_str(&json)?);
+                        found.push(addr);
+                    }
+                    None => {
+                        self.missing.insert(addr);
+                    }
+                }
+            }
+        }
+
+        self.correct_decimals(&found).await
+    }
+
+    async fn correct_decimals(&mut self, addrs: &[Pubkey]) -> Result<()> {
+        let mints: Vec<_> = addrs
+            .iter()
+            .flat_map(|it| [self.records[it].mint_a, self.records[it].mint_b])
+            .collect();
+        let decimals = get_mint_decimals(self.conn(), &mints).await?;
+        for (addr, decimals) in addrs.iter().zip(decimals.chunks(2)) {
+            let record = self.records.get_mut(addr).expect("prefetched pool");
+            let (mint_a, mint_b) = (record.mint_a, record.mint_b);
+            let mut corrected = false;
+            if let Some(decimals) = decimals[0] {
+                corrected |= record.correct_decimals(&mint_a, decimals);
+            }
+            if let Some(decimals) = decimals[1] {
+                corrected |= record.correct_decimals(&mint_b, decimals);
+            }
+            if corrected {
+                self.dirty.insert(*addr);
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn get(&mut self, addr: &Pubkey) -> Result<Option<DexPoolRecord>> {
+        if let Some(record) = self.records.get(addr) {
+            return Ok(Some(record.clone()));
+        }
+        let Some(conn) = &mut self.conn else {
+            return Ok(None);
+        };
+        if self.missing.contains(addr) {
+            return Ok(None);
+        }
+
+        let key = DexPoolRecord::new_key(addr.to_string());
+        if let Some(record) = DexPoolRecord::from_redis(conn, &key).await? {
+            self.records.insert(*addr, record.clone());
+            return Ok(Some(record));
+        }
+        let record = get_archived_pool(conn, addr).await?;
+        if let Some(record) = &record {
+            self.rehydrate(record.clone());
+        }
+        Ok(record)
+    }
+
+    /// cache an archived record again, queued for the decimals check as it missed the checks
+    /// done while it was archived
+    fn rehydrate(&mut self, record: DexPoolRecord) {
+        self.dirty.insert(record.addr);
+        self.new_pools.insert(record.addr);
+        self.records.insert(record.addr, record);
+    }
+
+    pub fn save(&mut self, record: DexPoolRecord) {
+        if !self.records.contains_key(&record.addr) {
+            self.new_pools.insert(record.addr);
+        }
+        self.dirty.insert(record.addr);
+        self.records.insert(record.addr, record);
+    }
+
+    pub async fn flush(&mut self) -> Result<()> {
+        if self.read_only || self.conn.is_none() {
+            self.dirty.clear();
+            self.new_pools.clear();
+            return Ok(());
+        }
+        if self.dirty.is_empty() {
+            return Ok(());
+        }
+
+        let mut pipe = redis::pipe();
+        for addr in self.dirty.drain() {
+            pipe_pool_record(&mut pipe, &self.records[&addr])?;
+        }
+        let _: () = pipe.query_async(self.conn()).await?;
+
+        let new_pools: Vec<_> = self.new_pools.drain().collect();
+        queue_decimals_checks(self.conn(), &new_pools).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{Dex, WSOL_MINT};
+
+    use super::*;
+
+    fn pool(addr: Pubkey, is_complete: bool) -> DexPoolRecord {
+        DexPoolRecord {
+            addr,
+            dex: Dex::Pumpfun,
+            is_complete,
+            is_drained: false,
+            is_lp_locked: false,
+            mint_a: Pubkey::new_unique(),
+            mint_b: WSOL_MINT,
+            decimals_a: 6,
+            decimals_b: 9,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_merge_worker_saves() {
+        let (completed, untouched, created) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let mut batch = DexPoolCache::detached([pool(completed, false), pool(untouched, false)]);
+        batch.missing.insert(created);
+
+        // whichever worker merges first, the saved record wins over the stale copy of the other
+        for saver_first in [true, false] {
+            let mut batch = batch.worker_copy();
+            let mut saver = batch.worker_copy();
+            let stale = batch.worker_copy();
+            saver.save(pool(completed, true));
+            saver.save(pool(created, false));
+            let workers = if saver_first {
+                [saver, stale]
+            } else {
+                [stale, saver]
+            };
+            for worker in workers {
+                batch.merge(worker);
+            }
+
+            let record = batch.get(&completed).await.unwrap().unwrap();
+            assert!(record.is_complete, "saver merged first: {saver_first}");
+            assert!(batch.get(&created).await.unwrap().is_some());
+            assert!(!batch.missing.contains(&created));
+            assert_eq!(batch.dirty, HashSet::from([completed, created]));
+            assert_eq!(batch.new_pools, HashSet::from([created]));
+            assert!(!batch.get(&untouched).await.unwrap().unwrap().is_complete);
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs redis, set TEST_REDIS_URL"]
+    async fn test_read_back_archived_pools() {
+        let redis_url = std::env::var("TEST_REDIS_URL").expect("TEST_REDIS_URL of a redis");
+        let client = redis::Client::open(redis_url).unwrap();
+        let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+        let (archived, unknown) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut pools = DexPoolCache::new(conn.clone());
+        pools.save(pool(archived, false));
+        pools.flush().await.unwrap();
+        let record = get_archived_pool(&mut conn, &archived).await.unwrap();
+        assert_eq!(record.map(|it| it.addr), Some(archived));
+
+        // expired from the cache, prefetch and get read it back and cache it again
+        for prefetch in [true, false] {
+            let key = DexPoolRecord::new_key(archived.to_string());
+            let _: () = conn.del(&key).await.unwrap();
+            let mut pools = DexPoolCache::new(conn.clone());
+            if prefetch {
+                pools.prefetch([archived, unknown]).await.unwrap();
+                assert!(pools.missing.contains(&unknown));
+            }
+            let record = pools.get(&archived).await.unwrap().unwrap();
+            assert_eq!(record.addr, archived);
+            assert!(pools.get(&unknown).await.unwrap().is_none());
+            assert!(
+                pools.new_pools.contains(&archived),
+                "prefetched: {prefetch}"
+            );
+            pools.flush().await.unwrap();
+            let cached: bool = conn.exists(&key).await.unwrap();
+            assert!(cached, "prefetched: {prefetch}");
+        }
+    }
+}
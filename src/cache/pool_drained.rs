@@ -0,0 +1,345 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc, serde::ts_seconds};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+
+use crate::{common::Dex, config::PoolDrainedConfig, qn_req_processor::EventSource};
+
+use super::{DexEvent, DexPoolCache};
+
+/// peaks of the pools no longer traded are forgotten
+const POOL_SOL_PEAK_TTL_SECS: u64 = 7 * 24 * 3600;
+
+/// highest SOL reserves of a pool seen by the hub, in lamports
+fn pool_sol_peak_key(pool: &Pubkey) -> String {
+    format!("str:pool_sol_peak:{pool}")
+}
+
+/// The SOL reserves of a pool fell under the floor of the config after they had reached the
+/// armed amount, by the trade or the liquidity removal of the transaction.
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolDrainedRecord {
+    #[serde(with = "ts_seconds")]
+    pub blk_ts: DateTime<Utc>,
+    pub slot: u64,
+    pub txid: String,
+    pub idx: u64,
+    pub dex: Dex,
+    #[serde_as(as = "DisplayFromStr")]
+    pub pool: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    /// lamports left in the pool
+    pub pool_sol_amt: u64,
+    /// the highest reserves of the pool seen before, in lamports
+    pub peak_sol_amt: u64,
+    /// the stream batch the event was parsed from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<EventSource>,
+}
+
+impl PoolDrainedRecord {
+    fn new(evt: &DexEvent, peak_sol_amt: u64) -> Option<Self> {
+        let record = match evt {
+            DexEvent::Trade(it) => Self {
+                blk_ts: it.blk_ts,
+                slot: it.slot,
+                txid: it.txid.clone(),
+                idx: it.idx,
+                dex: it.dex,
+                pool: it.pool,
+                mint: it.mint,
+                pool_sol_amt: it.pool_sol_amt,
+                peak_sol_amt,
+                source: it.source.clone(),
+            },
+            DexEvent::LiquidityChange(it) => Self {
+                blk_ts: it.blk_ts,
+                slot: it.slot,
+                txid: it.txid.clone(),
+                idx: it.idx,
+                dex: it.dex,
+                pool: it.pool,
+                mint: it.mint,
+                pool_sol_amt: it.pool_sol_amt?,
+                peak_sol_amt,
+                source: it.source.clone(),
+            },
+            _ => return None,
+        };
+        Some(record)
+    }
+}
+
+/// Pool and its SOL reserves after the event, none for the other kinds and the pools not
+/// quoted in WSOL
+fn sol_reserves(evt: &DexEvent) -> Option<(Pubkey, u64)> {
+    match evt {
+        DexEvent::Trade(it) if it.is_sol_quoted() => Some((it.pool, it.pool_sol_amt)),
+        DexEvent::LiquidityChange(it) => Some((it.pool, it.pool_sol_amt?)),
+        _ => None,
+    }
+}
+
+/// Raise the peak to the reserves, true when the reserves fall under `floor` from a peak of
+/// at least `armed`
+fn check_drain(peak: &mut u64, sol_amt: u64, floor: u64, armed: u64) -> bool {
+    let drained = *peak >= armed && sol_amt < floor;
+    *peak = (*peak).max(sol_amt);
+    drained
+}
+
+/// What `detect_drained_pools` found in a batch, saved by `save` once its events are queued,
+/// so a batch claimed again detects the same drains
+#[derive(Debug, Default)]
+pub struct DrainedPools {
+    /// the peaks changed by the batch
+    peaks: HashMap<Pubkey, u64>,
+    /// the pools drained, true, or refilled, false, by the batch
+    marks: HashMap<Pubkey, bool>,
+    /// the `PoolDrained` events
+    pub events: Vec<DexEvent>,
+}
+
+impl DrainedPools {
+    /// Mark the pools drained or refilled in the cache, flushed by the caller, and keep the peaks
+    pub async fn save(self, pools: &mut DexPoolCache) -> Result<()> {
+        if pools.is_read_only() {
+            return Ok(());
+        }
+        for (pool, is_drained) in self.marks {
+            if let Some(mut record) = pools.get(&pool).await?
+                && record.is_drained != is_drained
+            {
+                record.is_drained = is_drained;
+                pools.save(record);
+            }
+        }
+        if self.peaks.is_empty() {
+            return Ok(());
+        }
+        let mut pipe = redis::pipe();
+        for (pool, peak) in self.peaks {
+            pipe.set_ex(pool_sol_peak_key(&pool), peak, POOL_SOL_PEAK_TTL_SECS)
+                .ignore();
+        }
+        let _: () = pipe.query_async(pools.conn()).await?;
+        Ok(())
+    }
+}
+
+/// Find the pools drained by the `updates` positions of the events, in the order of the
+/// transactions, from the cached drained marks and peaks, and tag the trades of the drained
+/// pools. A drained pool whose reserves are back to `armed` is refilled, and can be drained
+/// again.
+fn detect(
+    events: &mut [DexEvent],
+    updates: &[usize],
+    is_drained: &HashMap<Pubkey, bool>,
+    mut peaks: HashMap<Pubkey, u64>,
+    floor: u64,
+    armed: u64,
+) -> DrainedPools {
+    let mut found = DrainedPools::default();
+    for pos in updates {
+        let Some((pool, sol_amt)) = sol_reserves(&events[*pos]) else {
+            continue;
+        };
+        let Some(cached) = is_drained.get(&pool) else {
+            continue;
+        };
+        let peak = peaks.entry(pool).or_default();
+        let before = *peak;
+        if found.marks.get(&pool).copied().unwrap_or(*cached) {
+            if sol_amt < armed {
+                if let DexEvent::Trade(trade) = &mut events[*pos] {
+                    trade.pool_drained = true;
+                }
+                continue;
+            }
+            // armed again from the new reserves
+            *peak = sol_amt;
+            found.peaks.insert(pool, sol_amt);
+            found.marks.insert(pool, false);
+            continue;
+        }
+        let is_drain = check_drain(peak, sol_amt, floor, armed);
+        if *peak != before {
+            found.peaks.insert(pool, *peak);
+        }
+        if !is_drain {
+            continue;
+        }
+        found.marks.insert(pool, true);
+        if let Some(drained_record) = PoolDrainedRecord::new(&events[*pos], before) {
+            found.events.push(DexEvent::PoolDrained(drained_record));
+        }
+        if let DexEvent::Trade(trade) = &mut events[*pos] {
+            trade.pool_drained = true;
+        }
+    }
+    found
+}
+
+/// Find the pools drained by the events and tag their trades, the trades of the pools drained
+/// before are tagged too. Nothing is saved, see `DrainedPools::save`.
+pub async fn detect_drained_pools(
+    pools: &mut DexPoolCache,
+    events: &mut [DexEvent],
+    config: &PoolDrainedConfig,
+) -> Result<DrainedPools> {
+    let floor = (config.floor_sol * LAMPORTS_PER_SOL as f64) as u64;
+    let armed = (config.armed_sol * LAMPORTS_PER_SOL as f64) as u64;
+    // in the order of the transactions, the batch may not be
+    let mut updates: Vec<_> = events
+        .iter()
+        .enumerate()
+        .filter(|(_, it)| sol_reserves(it).is_some())
+        .map(|(pos, _)| pos)
+        .collect();
+    if updates.is_empty() {
+        return Ok(DrainedPools::default());
+    }
+    updates.sort_by_key(|pos| (events[*pos].slot(), events[*pos].idx()));
+
+    let addrs: Vec<Pubkey> = updates
+        .iter()
+        .filter_map(|pos| sol_reserves(&events[*pos]))
+        .map(|(pool, _)| pool)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let keys: Vec<_> = addrs.iter().map(pool_sol_peak_key).collect();
+    // explicit MGET, a single key would be a GET
+    let cached: Vec<Option<u64>> = redis::cmd("MGET")
+        .arg(&keys)
+        .query_async(pools.conn())
+        .await?;
+    let mut is_drained = HashMap::new();
+    for pool in &addrs {
+        if let Some(record) = pools.get(pool).await? {
+            is_drained.insert(*pool, record.is_drained);
+        }
+    }
+    let peaks: HashMap<Pubkey, u64> = addrs
+        .into_iter()
+        .zip(cached)
+        .map(|(pool, peak)| (pool, peak.unwrap_or_default()))
+        .collect();
+
+    Ok(detect(events, &updates, &is_drained, peaks, floor, armed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_drain() {
+        let (floor, armed) = (100, 1_000);
+        let mut peak = 0;
+        // never armed
+        assert!(!check_drain(&mut peak, 500, floor, armed));
+        assert!(!check_drain(&mut peak, 50, floor, armed));
+        assert_eq!(peak, 500);
+
+        assert!(!check_drain(&mut peak, 2_000, floor, armed));
+        assert!(!check_drain(&mut peak, 100, floor, armed));
+        assert!(check_drain(&mut peak, 99, floor, armed));
+        assert_eq!(peak, 2_000);
+    }
+
+    fn trade(pool: Pubkey, slot: u64, pool_sol_amt: u64) -> DexEvent {
+        DexEvent::Trade(
+            serde_json::from_value(serde_json::json!({
+                "blk_ts": 0, "slot": slot, "txid": format!("tx{slot}"), "idx": 0,
+                "mint": Pubkey::new_unique().to_string(), "decimals": 6,
+                "trader": Pubkey::new_unique().to_string(), "dex": "PumpAmm",
+                "pool": pool.to_string(), "pool_sol_amt": pool_sol_amt, "pool_token_amt": 1,
+                "is_buy": false, "sol_amt": 1, "token_amt": 1, "price_sol": 1.0,
+            }))
+            .unwrap(),
+        )
+    }
+
+    fn drained_flags(events: &[DexEvent]) -> Vec<bool> {
+        events
+            .iter()
+            .filter_map(|it| match it {
+                DexEvent::Trade(trade) => Some(trade.pool_drained),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_drained_pools() {
+        let (floor, armed) = (100, 1_000);
+        let pool = Pubkey::new_unique();
+        let batch = || {
+            vec![
+                trade(pool, 1, 2_000),
+                trade(pool, 2, 50),
+                trade(pool, 3, 60),
+            ]
+        };
+        let updates = [0, 1, 2];
+        let stored_drained = HashMap::from([(pool, false)]);
+
+        let mut events = batch();
+        let found = detect(
+            &mut events,
+            &updates,
+            &stored_drained,
+            HashMap::new(),
+            floor,
+            armed,
+        );
+        assert_eq!(found.events.len(), 1);
+        assert_eq!(found.marks, HashMap::from([(pool, true)]));
+        assert_eq!(found.peaks, HashMap::from([(pool, 2_000)]));
+        assert_eq!(drained_flags(&events), vec![false, true, true]);
+
+        // claimed again before it was saved, the same drain is found
+        let mut events = batch();
+        let retried = detect(
+            &mut events,
+            &updates,
+            &stored_drained,
+            HashMap::new(),
+            floor,
+            armed,
+        );
+        assert_eq!(retried.events.len(), 1);
+        let DexEvent::PoolDrained(record) = &retried.events[0] else {
+            panic!("not a drain: {:?}", retried.events);
+        };
+        assert_eq!((record.slot, record.peak_sol_amt), (2, 2_000));
+
+        // once saved, the later trades are only tagged, until the pool is refilled
+        let saved_drained = HashMap::from([(pool, true)]);
+        let saved_peaks = HashMap::from([(pool, 2_000)]);
+        let mut events = vec![
+            trade(pool, 4, 70),
+            trade(pool, 5, 1_500),
+            trade(pool, 6, 80),
+        ];
+        let found = detect(
+            &mut events,
+            &updates,
+            &saved_drained,
+            saved_peaks,
+            floor,
+            armed,
+        );
+        assert_eq!(drained_flags(&events), vec![true, false, true]);
+        // refilled and drained again in the batch
+        assert_eq!(found.events.len(), 1);
+        assert_eq!(found.marks, HashMap::from([(pool, true)]));
+        assert_eq!(found.peaks, HashMap::from([(pool, 1_500)]));
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc, serde::ts_seconds};
+use redis::{AsyncCommands, aio::MultiplexedConnection};
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::config::PoolReservesConfig;
+
+use super::TradeRecord;
+
+/// approximate, to expire the snapshots of pools no longer traded
+const SLOT_MILLIS: u64 = 400;
+
+/// sorted set of reserve snapshots of a pool, scored by slot
+fn pool_reserves_key(pool: &Pubkey) -> String {
+    format!("zset:pool_reserves:{pool}")
+}
+
+/// Reserves of a pool after a trade
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PoolReserveSnapshot {
+    pub slot: u64,
+    #[serde(with = "ts_seconds")]
+    pub blk_ts: DateTime<Utc>,
+    pub sol_amt: u64,
+    pub token_amt: u64,
+    pub price_sol: f64,
+}
+
+impl From<&TradeRecord> for PoolReserveSnapshot {
+    fn from(trade: &TradeRecord) -> Self {
+        Self {
+            slot: trade.slot,
+            blk_ts: trade.blk_ts,
+            sol_amt: trade.pool_sol_amt,
+            token_amt: trade.pool_token_amt,
+            price_sol: trade.price_sol,
+        }
+    }
+}
+
+/// Snapshot the reserves after the last trade of each pool. A snapshot replaces the one
+/// of the same interval, those older than the retention are removed.
+pub async fn record_pool_reserves(
+    conn: &mut MultiplexedConnection,
+    trades: &[&TradeRecord],
+    config: &PoolReservesConfig,
+) -> Result<()> {
+    let mut latest: HashMap<Pubkey, &TradeRecord> = HashMap::new();
+    for trade in trades {
+        let last = latest.entry(trade.pool).or_insert(trade);
+        if (trade.slot, trade.idx) > (last.slot, last.idx) {
+            *last = trade;
+        }
+    }
+    if latest.is_empty() {
+        return Ok(());
+    }
+
+    let ttl = (config.retention_slots * SLOT_MILLIS / 1000) as i64;
+    let mut pipe = redis::pipe();
+    for (pool, trade) in latest {
+        let key = pool_reserves_key(&pool);
+        let interval_start = trade.slot - trade.slot % config.interval_slots;
+        let interval_end = interval_start + config.interval_slots - 1;
+        let snapshot = serde_json::to_string(&PoolReserveSnapshot::from(trade))?;
+        pipe.zrembyscore(&key, interval_start, interval_end)
+            .ignore();
+        pipe.zadd(&key, snapshot, trade.slot).ignore();
+        let expired = format!("({}", trade.slot.saturating_sub(config.retention_slots));
+        pipe.zrembyscore(&key, "-inf", expired).ignore();
+        pipe.expire(&key, ttl).ignore();
+    }
+    let _: () = pipe.query_async(conn).await?;
+    Ok(())
+}
+
+fn nearest_snapshot(snapshots: Vec<PoolReserveSnapshot>, slot: u64) -> Option<PoolReserveSnapshot> {
+    snapshots
+        .into_iter()
+        .min_by_key(|it| it.slot.abs_diff(slot))
+}
+
+/// The snapshot nearest to `slot`, the earlier one on a tie, or the latest without a slot
+pub async fn pool_reserves_at(
+    conn: &mut MultiplexedConnection,
+    pool: &Pubkey,
+    slot: Option<u64>,
+) -> Result<Option<PoolReserveSnapshot>> {
+    let key = pool_reserves_key(pool);
+    let snapshots: Vec<String> = match slot {
+        Some(slot) => {
+            let before: Vec<String> = conn
+                .zrevrangebyscore_limit(&key, slot, "-inf", 0, 1)
+                .await?;
+            let after: Vec<String> = conn.zrangebyscore_limit(&key, slot, "+inf", 0, 1).await?;
+            before.into_iter().chain(after).collect()
+        }
+        None => conn.zrevrange(&key, 0, 0).await?,
+    };
+    let snapshots = snapshots
+        .iter()
+        .map(|it| serde_json::from_str(it))
+        .collect::<Result<Vec<PoolReserveSnapshot>, _>>()?;
+    let snapshot = match slot {
+        Some(slot) => nearest_snapshot(snapshots, slot),
+        None => snapshots.into_iter().next(),
+    };
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(slot: u64) -> PoolReserveSnapshot {
+        PoolReserveSnapshot {
+            slot,
+            blk_ts: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            sol_amt: slot * 10,
+            token_amt: slot * 1_000,
+            price_sol: 0.01,
+        }
+    }
+
+    #[test]
+    fn test_nearest_snapshot() {
+        let nearest = |slot| nearest_snapshot(vec![snapshot(100), snapshot(200)], slot);
+        assert_eq!(nearest(120).unwrap().slot, 100);
+        assert_eq!(nearest(160).unwrap().slot, 200);
+        assert_eq!(nearest(150).unwrap().slot, 100);
+        assert_eq!(nearest(1_000).unwrap().slot, 200);
+        assert_eq!(nearest_snapshot(vec![], 100), None);
+    }
+}
@@ -0,0 +1,74 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc, serde::ts_seconds};
+use redis::aio::MultiplexedConnection;
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::common::Dex;
+
+use super::mint_activity::{recent_last_trades, touch_last_trades};
+
+/// sorted set of traded pools, scored by the block timestamp of their latest trade
+const POOL_LAST_TRADE_KEY: &str = "zset:pool_last_trade";
+
+/// Onchain state of a tracked pool changed.
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolStateUpdatedRecord {
+    /// when the change was seen, account updates have no block time
+    #[serde(with = "ts_seconds")]
+    pub ts: DateTime<Utc>,
+    pub slot: u64,
+    #[serde_as(as = "DisplayFromStr")]
+    pub pool: Pubkey,
+    pub dex: Dex,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    pub state: PoolState,
+}
+
+/// The active bin of a meteora dlmm pool changed.
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BinPriceUpdateRecord {
+    #[serde(with = "ts_seconds")]
+    pub ts: DateTime<Utc>,
+    pub slot: u64,
+    #[serde_as(as = "DisplayFromStr")]
+    pub lb_pair: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    pub active_id: i32,
+    /// price of one token in SOL at the active bin
+    pub price_sol: f64,
+}
+
+/// The parts of a pool state which are known for its dex
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PoolState {
+    pub sol_reserve: Option<u64>,
+    pub token_reserve: Option<u64>,
+    /// meteora dlmm only
+    pub active_id: Option<i32>,
+    /// base swap fee
+    pub fee_bps: Option<f64>,
+}
+
+/// Record the latest trade time of the pools, earlier times don't overwrite later ones.
+pub async fn touch_traded_pools(
+    conn: &mut MultiplexedConnection,
+    trades: &[(Pubkey, DateTime<Utc>)],
+) -> Result<()> {
+    touch_last_trades(conn, POOL_LAST_TRADE_KEY, trades).await
+}
+
+/// At most `max` pools traded since `since`, the latest traded first.
+/// Pools traded before are forgotten.
+pub async fn active_pools(
+    conn: &mut MultiplexedConnection,
+    since: DateTime<Utc>,
+    max: usize,
+) -> Result<Vec<Pubkey>> {
+    recent_last_trades(conn, POOL_LAST_TRADE_KEY, since, max).await
+}
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use redis::{Script, aio::MultiplexedConnection};
+use solana_sdk::pubkey::Pubkey;
+
+use super::DexEvent;
+
+/// Pools without trade or creation for so long are forgotten, their next trade counts from 1
+const POOL_TRADES_TTL_SECS: i64 = 7 * 24 * 3600;
+
+/// The trade counts a batch started from are kept this long, for its retries
+const BATCH_SEQS_TTL_SECS: i64 = 24 * 3600;
+
+/// Count ARGV[3..] trades to the pools KEYS[2..], once per batch: the counts the batch started
+/// from are kept in KEYS[1] and returned again for a retry of the batch. Returns the count
+/// before the batch and the creation timestamp of each pool.
+const COUNT_POOL_TRADES_SCRIPT: &str = r#"
+local counted = redis.call('EXISTS', KEYS[1]) == 1
+local pools = {}
+for i = 2, #KEYS do
+    local before
+    if counted then
+        before = tonumber(redis.call('HGET', KEYS[1], KEYS[i]) or 0)
+    else
+        local trades = tonumber(ARGV[i + 1])
+        before = redis.call('HINCRBY', KEYS[i], 'n', trades) - trades
+        redis.call('HSET', KEYS[1], KEYS[i], before)
+    end
+    redis.call('EXPIRE', KEYS[i], ARGV[2])
+    pools[#pools + 1] = {before, redis.call('HGET', KEYS[i], 'c')}
+end
+redis.call('EXPIRE', KEYS[1], ARGV[1])
+return pools
+"#;
+
+/// hash of a pool, `c` block timestamp of its creation and `n` trades seen
+fn pool_trades_key(pool: &Pubkey) -> String {
+    format!("hash:pool_trades:{pool}")
+}
+
+/// hash of the pools traded by a claimed batch, the trades seen before it
+fn batch_seqs_key(batch_id: &str) -> String {
+    format!("hash:pool_trade_seqs:{batch_id}")
+}
+
+/// Set `pool_age_secs` and `pool_trade_seq` of the trades. The pools created by the events are
+/// recorded first, so a trade of a pool created in the same batch gets its age. A retry of the
+/// claimed batch `batch_id` numbers its trades the same.
+pub async fn annotate_pool_trades(
+    conn: &mut MultiplexedConnection,
+    batch_id: &str,
+    events: &mut [DexEvent],
+) -> Result<()> {
+    let mut pipe = redis::pipe();
+    // indexes of the trades of each pool
+    let mut pool_trades: HashMap<Pubkey, Vec<usize>> = HashMap::new();
+    for (i, evt) in events.iter().enumerate() {
+        match evt {
+            DexEvent::PoolCreated(record) => {
+                let key = pool_trades_key(&record.addr);
+                pipe.hset_nx(&key, "c", record.blk_ts.timestamp()).ignore();
+                pipe.expire(&key, POOL_TRADES_TTL_SECS).ignore();
+            }
+            DexEvent::Trade(trade) => pool_trades.entry(trade.pool).or_default().push(i),
+            _ => {}
+        }
+    }
+    if !pipe.is_empty() {
+        let _: () = pipe.query_async(conn).await?;
+    }
+    if pool_trades.is_empty() {
+        return Ok(());
+    }
+
+    let script = Script::new(COUNT_POOL_TRADES_SCRIPT);
+    let mut script = script.prepare_invoke();
+    script
+        .key(batch_seqs_key(batch_id))
+        .arg(BATCH_SEQS_TTL_SECS)
+        .arg(POOL_TRADES_TTL_SECS);
+    let mut pools = vec![];
+    for (pool, trades) in &mut pool_trades {
+        // numbered in transaction order
+        trades.sort_by_key(|i| (events[*i].slot(), events[*i].idx()));
+        script.key(pool_trades_key(pool)).arg(trades.len());
+        pools.push(*pool);
+    }
+    let counts: Vec<(u64, Option<i64>)> = script.invoke_async(conn).await?;
+    for (pool, (before, created_ts)) in pools.iter().zip(counts) {
+        let trades = &pool_trades[pool];
+        let first_seq = before + 1;
+        for (seq, i) in (first_seq..).zip(trades) {
+            if let DexEvent::Trade(trade) = &mut events[*i] {
+                trade.pool_trade_seq = seq;
+                trade.pool_age_secs = created_ts.map(|ts| (trade.blk_ts.timestamp() - ts).max(0));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::{cache::DexPoolCreatedRecord, common::Dex};
+
+    fn trade(pool: Pubkey, slot: u64, blk_ts: i64) -> DexEvent {
+        DexEvent::Trade(
+            serde_json::from_value(serde_json::json!({
+                "blk_ts": blk_ts, "slot": slot, "txid": format!("tx{slot}"), "idx": 0,
+                "mint": Pubkey::new_unique().to_string(), "decimals": 6,
+                "trader": Pubkey::new_unique().to_string(), "dex": "PumpAmm",
+                "pool": pool.to_string(), "pool_sol_amt": 1, "pool_token_amt": 1,
+                "is_buy": true, "sol_amt": 1, "token_amt": 1, "price_sol": 1.0,
+            }))
+            .unwrap(),
+        )
+    }
+
+    fn pool_created(pool: Pubkey, blk_ts: i64) -> DexEvent {
+        DexEvent::PoolCreated(DexPoolCreatedRecord {
+            blk_ts: DateTime::from_timestamp(blk_ts, 0).unwrap(),
+            slot: 1,
+            txid: "create".to_string(),
+            idx: 0,
+            creator: Pubkey::new_unique(),
+            addr: pool,
+            dex: Dex::PumpAmm,
+            mint_a: Pubkey::new_unique(),
+            mint_b: Pubkey::new_unique(),
+            decimals_a: 6,
+            decimals_b: 9,
+            init_liquidity: None,
+            creator_funded_by: None,
+            source: None,
+        })
+    }
+
+    fn annotations(events: &[DexEvent]) -> Vec<(u64, Option<i64>)> {
+        events
+            .iter()
+            .filter_map(|it| match it {
+                DexEvent::Trade(trade) => Some((trade.pool_trade_seq, trade.pool_age_secs)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs redis, set TEST_REDIS_URL"]
+    async fn test_annotate_pool_trades() {
+        let redis_url = std::env::var("TEST_REDIS_URL").expect("TEST_REDIS_URL of a redis");
+        let client = redis::Client::open(redis_url).unwrap();
+        let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+        let (created, unseen) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let batch = Pubkey::new_unique().to_string();
+        // out of transaction order, the pool created in the same batch
+        let mut events = vec![
+            trade(created, 3, 130),
+            pool_created(created, 100),
+            trade(created, 2, 110),
+            trade(unseen, 2, 110),
+        ];
+        annotate_pool_trades(&mut conn, &batch, &mut events)
+            .await
+            .unwrap();
+        let expected = vec![(2, Some(30)), (1, Some(10)), (1, None)];
+        assert_eq!(annotations(&events), expected);
+
+        // a retry of the batch numbers its trades the same
+        let mut retried = vec![
+            trade(created, 3, 130),
+            trade(created, 2, 110),
+            trade(unseen, 2, 110),
+        ];
+        annotate_pool_trades(&mut conn, &batch, &mut retried)
+            .await
+            .unwrap();
+        assert_eq!(annotations(&retried), expected);
+
+        let mut events = vec![trade(created, 4, 200)];
+        let next_batch = Pubkey::new_unique().to_string();
+        annotate_pool_trades(&mut conn, &next_batch, &mut events)
+            .await
+            .unwrap();
+        assert_eq!(annotations(&events), vec![(3, Some(100))]);
+    }
+}
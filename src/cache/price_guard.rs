@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use redis::aio::MultiplexedConnection;
+use solana_sdk::pubkey::Pubkey;
+use tracing::warn;
+
+use crate::config::{PriceGuardAction, PriceGuardConfig};
+
+use super::DexEvent;
+
+/// Recent prices kept per pool, the median is taken over them
+pub const MAX_PRICE_SAMPLES: usize = 50;
+/// prices of pools without trades for a day are forgotten
+const PRICE_SAMPLES_TTL_SECS: i64 = 24 * 3600;
+
+/// list of the recent accepted trade prices of a pool, the latest first
+fn pool_prices_key(pool: &Pubkey) -> String {
+    format!("list:pool_prices:{pool}")
+}
+
+/// list of the outliers of a pool close to each other since its last accepted price, the latest
+/// first
+fn pool_outliers_key(pool: &Pubkey) -> String {
+    format!("list:pool_outlier_prices:{pool}")
+}
+
+fn median(prices: &[f64]) -> Option<f64> {
+    let mut prices = prices.to_vec();
+    prices.sort_by(f64::total_cmp);
+    let mid = prices.len() / 2;
+    match prices.len() {
+        0 => None,
+        len if len % 2 == 0 => Some((prices[mid - 1] + prices[mid]) / 2.0),
+        _ => Some(prices[mid]),
+    }
+}
+
+fn deviates(price: f64, median: f64, max_deviation: f64) -> bool {
+    price > median * max_deviation || price * max_deviation < median
+}
+
+/// The price is over or under the median of the recent prices by more than `max_deviation`
+fn is_price_outlier(price: f64, recent: &[f64], config: &PriceGuardConfig) -> bool {
+    if recent.len() < config.min_samples {
+        return false;
+    }
+    median(recent).is_some_and(|median| deviates(price, median, config.max_deviation))
+}
+
+/// The recent prices of a pool and its outliers since the last accepted price, the latest first
+#[derive(Debug, Default)]
+struct PoolPrices {
+    recent: Vec<f64>,
+    outliers: Vec<f64>,
+}
+
+impl PoolPrices {
+    /// Accept the price unless it's an outlier. `confirmations` outliers in a row close to each
+    /// other are taken as the new price of the pool, they replace the recent prices, so a
+    /// lasting move isn't rejected forever.
+    fn accept(&mut self, price: f64, config: &PriceGuardConfig) -> bool {
+        if !is_price_outlier(price, &self.recent, config) {
+            self.recent.insert(0, price);
+            self.recent.truncate(MAX_PRICE_SAMPLES);
+            self.outliers.clear();
+            return true;
+        }
+
+        if median(&self.outliers).is_some_and(|it| deviates(price, it, config.max_deviation)) {
+            self.outliers.clear();
+        }
+        self.outliers.insert(0, price);
+        self.outliers.truncate(MAX_PRICE_SAMPLES);
+        if self.outliers.len() >= config.confirmations {
+            self.recent = std::mem::take(&mut self.outliers);
+            return true;
+        }
+        false
+    }
+}
+
+/// Flag or drop the trades priced far from the recent median price of their pool.
+/// Outliers are not added to the recent prices, unless they confirm a new price level.
+pub async fn guard_trade_prices(
+    conn: &mut MultiplexedConnection,
+    events: &mut Vec<DexEvent>,
+    config: &PriceGuardConfig,
+) -> Result<()> {
+    let mut pools: Vec<Pubkey> = events
+        .iter()
+        .filter_map(|it| match it {
+            DexEvent::Trade(trade) => Some(trade.pool),
+            _ => None,
+        })
+        .collect();
+    pools.sort();
+    pools.dedup();
+    if pools.is_empty() {
+        return Ok(());
+    }
+
+    let mut pipe = redis::pipe();
+    for pool in &pools {
+        pipe.lrange(pool_prices_key(pool), 0, MAX_PRICE_SAMPLES as isize - 1)
+            .lrange(pool_outliers_key(pool), 0, MAX_PRICE_SAMPLES as isize - 1);
+    }
+    let lists: Vec<Vec<f64>> = pipe.query_async(conn).await?;
+    let mut prices: HashMap<_, _> = pools
+        .into_iter()
+        .zip(lists.chunks(2))
+        .map(|(pool, lists)| {
+            let prices = PoolPrices {
+                recent: lists[0].clone(),
+                outliers: lists[1].clone(),
+            };
+            (pool, prices)
+        })
+        .collect();
+
+    events.retain_mut(|evt| {
+        let DexEvent::Trade(trade) = evt else {
+            return true;
+        };
+        let prices = prices.entry(trade.pool).or_default();
+        // in the quote mint of the pool
+        if prices.accept(trade.price_quote, config) {
+            return true;
+        }
+
+        warn!(
+            "price {} of {} in tx {} deviates from the recent prices of pool {}",
+            trade.price_quote, trade.mint, trade.txid, trade.pool
+        );
+        trade.price_outlier = true;
+        config.action == PriceGuardAction::Flag
+    });
+
+    // the lists are rewritten, the latest price first
+    let mut pipe = redis::pipe();
+    for (pool, prices) in prices {
+        for (key, list) in [
+            (pool_prices_key(&pool), prices.recent),
+            (pool_outliers_key(&pool), prices.outliers),
+        ] {
+            pipe.del(&key).ignore();
+            if !list.is_empty() {
+                pipe.rpush(&key, list)
+                    .ignore()
+                    .expire(&key, PRICE_SAMPLES_TTL_SECS)
+                    .ignore();
+            }
+        }
+    }
+    let _: () = pipe.query_async(conn).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_outlier() {
+        assert_eq!(median(&[]), None);
+        assert_eq!(median(&[3.0, 1.0, 2.0]), Some(2.0));
+        assert_eq!(median(&[4.0, 1.0, 2.0, 3.0]), Some(2.5));
+
+        let config = PriceGuardConfig {
+            max_deviation: 10.0,
+            min_samples: 3,
+            confirmations: 3,
+            action: PriceGuardAction::Drop,
+        };
+        let recent = [1.0, 1.2, 0.9, 50.0];
+        assert!(!is_price_outlier(5.0, &recent, &config));
+        assert!(is_price_outlier(20.0, &recent, &config));
+        assert!(is_price_outlier(0.01, &recent, &config));
+        assert!(!is_price_outlier(20.0, &recent[..2], &config));
+    }
+
+    #[test]
+    fn test_confirmed_outliers() {
+        let config = PriceGuardConfig {
+            max_deviation: 10.0,
+            min_samples: 3,
+            confirmations: 3,
+            action: PriceGuardAction::Drop,
+        };
+        let mut prices = PoolPrices {
+            recent: vec![1.0, 1.1, 0.9],
+            outliers: vec![],
+        };
+        assert!(!prices.accept(50.0, &config));
+        // far from the other outlier, they start over
+        assert!(!prices.accept(0.01, &config));
+        assert_eq!(prices.outliers, vec![0.01]);
+        assert!(prices.accept(1.0, &config));
+        assert!(prices.outliers.is_empty());
+
+        assert!(!prices.accept(50.0, &config));
+        assert!(!prices.accept(55.0, &config));
+        // the third in a row is the new price
+        assert!(prices.accept(52.0, &config));
+        assert_eq!(prices.recent, vec![52.0, 55.0, 50.0]);
+        assert!(prices.outliers.is_empty());
+        assert!(prices.accept(51.0, &config));
+        assert_eq!(prices.recent.len(), 4);
+    }
+}
@@ -1,12 +1,34 @@
-use chrono::{DateTime, Utc, serde::ts_seconds};
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, Utc, serde::ts_seconds};
+use redis::{AsyncCommands, Script, aio::MultiplexedConnection};
 use serde::{Deserialize, Serialize};
 use serde_with::{DisplayFromStr, serde_as};
 use solana_sdk::pubkey::Pubkey;
 
-use crate::{common::TxBaseMetaInfo, pumpfun::event::CompleteEvent};
+use crate::{common::TxBaseMetaInfo, pumpfun::event::CompleteEvent, qn_req_processor::EventSource};
 
+use super::RedisCacheRecord;
+
+/// sorted set of every complete record as json, scored by block timestamp
+const PUMPFUN_COMPLETES_KEY: &str = "zset:pumpfun_completes";
+/// completes per UTC day, `%Y-%m-%d`
+const PUMPFUN_COMPLETES_DAILY_KEY: &str = "hash:pumpfun_completes_daily";
+
+/// Add (ts, record, day) triples of ARGV to sorted set KEYS[1], counting the new ones per day in hash KEYS[2].
+const SAVE_COMPLETES_SCRIPT: &str = r#"
+local added = 0
+for i = 1, #ARGV, 3 do
+    if redis.call('ZADD', KEYS[1], ARGV[i], ARGV[i + 1]) == 1 then
+        redis.call('HINCRBY', KEYS[2], ARGV[i + 2], 1)
+        added = added + 1
+    end
+end
+return added
+"#;
+
+/// Also cached by mint until the pumpamm pool of the migration is created
 #[serde_as]
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PumpfunCompleteRecord {
     #[serde(with = "ts_seconds")]
     pub blk_ts: DateTime<Utc>,
@@ -19,6 +41,9 @@ pub struct PumpfunCompleteRecord {
     pub mint: Pubkey,
     #[serde_as(as = "DisplayFromStr")]
     pub bonding_curve: Pubkey,
+    /// the stream batch the event was parsed from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<EventSource>,
 }
 
 impl PumpfunCompleteRecord {
@@ -38,6 +63,129 @@ impl PumpfunCompleteRecord {
             user: complete_evt.user,
             mint: complete_evt.mint,
             bonding_curve: complete_evt.bonding_curve,
+            source: None,
         }
     }
 }
+
+impl RedisCacheRecord for PumpfunCompleteRecord {
+    fn key(&self) -> String {
+        format!("{}{}", Self::prefix(), self.mint)
+    }
+
+    fn prefix() -> &'static str {
+        "pumpfun_complete:"
+    }
+}
+
+/// Keep the complete records, a record saved again is not counted again. Returns the new ones.
+pub async fn save_pumpfun_completes(
+    conn: &mut MultiplexedConnection,
+    records: &[&PumpfunCompleteRecord],
+) -> Result<usize> {
+    if records.is_empty() {
+        return Ok(0);
+    }
+
+    let script = Script::new(SAVE_COMPLETES_SCRIPT);
+    let mut invocation = script.key(PUMPFUN_COMPLETES_KEY);
+    invocation.key(PUMPFUN_COMPLETES_DAILY_KEY);
+    for record in records {
+        invocation
+            .arg(record.blk_ts.timestamp())
+            .arg(record.json()?)
+            .arg(record.blk_ts.format("%Y-%m-%d").to_string());
+    }
+    let added: usize = invocation.invoke_async(conn).await?;
+    Ok(added)
+}
+
+/// At most `limit` records with block timestamps in `[from, to]` after the first `skip`,
+/// the earliest first or the latest first if `rev`
+pub async fn list_pumpfun_completes(
+    conn: &mut MultiplexedConnection,
+    from: Option<i64>,
+    to: Option<i64>,
+    skip: usize,
+    limit: usize,
+    rev: bool,
+) -> Result<Vec<PumpfunCompleteRecord>> {
+    let from = from.map_or("-inf".to_string(), |it| it.to_string());
+    let to = to.map_or("+inf".to_string(), |it| it.to_string());
+    let (skip, limit) = (skip as isize, limit as isize);
+    let items: Vec<String> = if rev {
+        conn.zrevrangebyscore_limit(PUMPFUN_COMPLETES_KEY, to, from, skip, limit)
+            .await?
+    } else {
+        conn.zrangebyscore_limit(PUMPFUN_COMPLETES_KEY, from, to, skip, limit)
+            .await?
+    };
+    let records = items
+        .iter()
+        .map(|it| serde_json::from_str(it))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(records)
+}
+
+pub async fn pumpfun_completes_on(conn: &mut MultiplexedConnection, day: NaiveDate) -> Result<u64> {
+    let count: Option<u64> = conn
+        .hget(
+            PUMPFUN_COMPLETES_DAILY_KEY,
+            day.format("%Y-%m-%d").to_string(),
+        )
+        .await?;
+    Ok(count.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[ignore = "needs redis, set TEST_REDIS_URL"]
+    async fn test_save_and_list_pumpfun_completes() {
+        let redis_url = std::env::var("TEST_REDIS_URL").expect("TEST_REDIS_URL of a redis");
+        let client = redis::Client::open(redis_url).unwrap();
+        let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+        // a range of block times no other record is in
+        let start = 4_000_000_000 + std::process::id() as i64 * 10;
+        let record = |secs| PumpfunCompleteRecord {
+            blk_ts: DateTime::from_timestamp(start + secs, 0).unwrap(),
+            slot: 1,
+            txid: format!("complete{secs}"),
+            idx: 0,
+            user: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            bonding_curve: Pubkey::new_unique(),
+            source: None,
+        };
+        let (first, second) = (record(1), record(2));
+        let day = first.blk_ts.date_naive();
+        let before = pumpfun_completes_on(&mut conn, day).await.unwrap();
+
+        let saved = save_pumpfun_completes(&mut conn, &[&second, &first])
+            .await
+            .unwrap();
+        assert_eq!(saved, 2);
+        // saved again, e.g. by a retried batch, it's not counted again
+        let saved = save_pumpfun_completes(&mut conn, &[&first]).await.unwrap();
+        assert_eq!(saved, 0);
+        assert_eq!(
+            pumpfun_completes_on(&mut conn, day).await.unwrap(),
+            before + 2
+        );
+
+        let range = (Some(start), Some(start + 9));
+        let txids = |records: Vec<PumpfunCompleteRecord>| {
+            records.into_iter().map(|it| it.txid).collect::<Vec<_>>()
+        };
+        let asc = list_pumpfun_completes(&mut conn, range.0, range.1, 0, 10, false)
+            .await
+            .unwrap();
+        assert_eq!(txids(asc), ["complete1", "complete2"]);
+        let desc = list_pumpfun_completes(&mut conn, range.0, range.1, 1, 10, true)
+            .await
+            .unwrap();
+        assert_eq!(txids(desc), ["complete1"]);
+    }
+}
@@ -0,0 +1,55 @@
+use chrono::{DateTime, Utc, serde::ts_seconds};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    common::TxBaseMetaInfo, pumpfun::event::SetParamsEvent, qn_req_processor::EventSource,
+};
+
+/// The global params of the pumpfun bonding curves were changed, the curves created after it
+/// start from the new reserves
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PumpfunSetParamsRecord {
+    #[serde(with = "ts_seconds")]
+    pub blk_ts: DateTime<Utc>,
+    pub slot: u64,
+    pub txid: String,
+    pub idx: u64,
+    #[serde_as(as = "DisplayFromStr")]
+    pub fee_recipient: Pubkey,
+    pub initial_virtual_token_reserves: u64,
+    pub initial_virtual_sol_reserves: u64,
+    pub initial_real_token_reserves: u64,
+    pub token_total_supply: u64,
+    pub fee_basis_points: u64,
+    /// the stream batch the event was parsed from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<EventSource>,
+}
+
+impl PumpfunSetParamsRecord {
+    pub fn new(meta: TxBaseMetaInfo, evt: &SetParamsEvent) -> Self {
+        let TxBaseMetaInfo {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+        } = meta;
+
+        Self {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+            fee_recipient: evt.fee_recipient,
+            initial_virtual_token_reserves: evt.initial_virtual_token_reserves,
+            initial_virtual_sol_reserves: evt.initial_virtual_sol_reserves,
+            initial_real_token_reserves: evt.initial_real_token_reserves,
+            token_total_supply: evt.token_total_supply,
+            fee_basis_points: evt.fee_basis_points,
+            source: None,
+        }
+    }
+}
@@ -1,50 +1,121 @@
-use anyhow::{Result, bail};
+use anyhow::{Result, anyhow, bail};
 use redis::aio::MultiplexedConnection;
+use solana_sdk::hash::hashv;
 use tracing::warn;
 
+use super::queue;
+
 const QN_REQ_LIST_KEY: &str = "list:qn_requests";
-pub const MAX_QN_REQ_LEN: u64 = 50;
-pub async fn rpush_qn_request(conn: &mut MultiplexedConnection, req: String) -> Result<()> {
+pub const MAX_QN_REQ_LEN: u64 = 200;
+/// Requests claimed in one batch, each body is decompressed in memory
+const QN_REQ_BATCH_LEN: usize = 50;
+const QN_REQ_ZSTD_LEVEL: i32 = 3;
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+/// Ingested stream batches are remembered this long, retries within it are ignored
+const QN_BATCH_SEEN_TTL_SECS: u64 = 600;
+
+fn qn_batch_seen_key(stream_id: &str, batch_start: u64, batch_end: u64) -> String {
+    format!("str:qn_batch_seen:{stream_id}:{batch_start}:{batch_end}")
+}
+
+fn compress_qn_request(req: &str) -> Result<Vec<u8>> {
+    let compressed = zstd::encode_all(req.as_bytes(), QN_REQ_ZSTD_LEVEL)?;
+    Ok(compressed)
+}
+
+fn decompress_qn_request(record: Vec<u8>) -> Result<String> {
+    // requests queued before compression was introduced are plain json
+    let bytes = if record.starts_with(&ZSTD_MAGIC) {
+        zstd::decode_all(record.as_slice())?
+    } else {
+        record
+    };
+    String::from_utf8(bytes).map_err(|err| anyhow!("qn request is not utf8 string: {err}"))
+}
+
+pub async fn llen_qn_requests(conn: &mut MultiplexedConnection) -> Result<u64> {
     let q_len: u64 = redis::cmd("llen")
         .arg(QN_REQ_LIST_KEY)
         .query_async(conn)
         .await?;
-    if q_len >= MAX_QN_REQ_LEN {
+    Ok(q_len)
+}
+
+pub async fn rpush_qn_request(conn: &mut MultiplexedConnection, req: String) -> Result<()> {
+    let record = compress_qn_request(&req)?;
+    if !queue::bounded_rpush(conn, QN_REQ_LIST_KEY, MAX_QN_REQ_LEN, &[record]).await? {
         warn!("qn request queue larger than {MAX_QN_REQ_LEN}");
         bail!("qn request queue larger than {MAX_QN_REQ_LEN}");
     }
 
-    // redis rpush
-    let mut cmd = redis::cmd("rpush");
-    cmd.arg(QN_REQ_LIST_KEY);
-    cmd.arg(req);
-
-    let _: () = cmd.query_async(conn).await?;
     Ok(())
 }
-pub async fn lrange_qn_requests(conn: &mut MultiplexedConnection) -> Result<Vec<String>> {
-    let llen: u64 = redis::cmd("llen")
-        .arg(QN_REQ_LIST_KEY)
-        .query_async(conn)
-        .await?;
-    if llen == 0 {
-        return Ok(vec![]);
-    }
-    let records: Vec<String> = redis::cmd("lrange")
-        .arg(QN_REQ_LIST_KEY)
-        .arg(0)
-        .arg(llen - 1)
+
+/// Remember an ingested stream batch, `false` if it was already seen recently
+pub async fn mark_qn_batch_seen(
+    conn: &mut MultiplexedConnection,
+    stream_id: &str,
+    batch_start: u64,
+    batch_end: u64,
+) -> Result<bool> {
+    let set: Option<String> = redis::cmd("set")
+        .arg(qn_batch_seen_key(stream_id, batch_start, batch_end))
+        .arg(1)
+        .arg("nx")
+        .arg("ex")
+        .arg(QN_BATCH_SEEN_TTL_SECS)
         .query_async(conn)
         .await?;
-    Ok(records)
+    Ok(set.is_some())
 }
 
-pub async fn ltrim_qn_requests(conn: &mut MultiplexedConnection, len: usize) -> Result<()> {
-    let _: () = redis::cmd("ltrim")
-        .arg(QN_REQ_LIST_KEY)
-        .arg(len)
-        .arg(-1)
+/// Forget a stream batch which failed to be queued, so its retry is accepted
+pub async fn forget_qn_batch(
+    conn: &mut MultiplexedConnection,
+    stream_id: &str,
+    batch_start: u64,
+    batch_end: u64,
+) -> Result<()> {
+    let _: () = redis::cmd("del")
+        .arg(qn_batch_seen_key(stream_id, batch_start, batch_end))
         .query_async(conn)
         .await?;
     Ok(())
 }
+
+/// Requests claimed from the queue, until acked
+pub struct QnReqBatch {
+    /// hash of the queued records, the same when an unacked batch is claimed again
+    pub id: String,
+    pub reqs: Vec<String>,
+}
+
+/// Claim the queued requests, they're claimed again until acked with `ack_qn_requests`.
+pub async fn claim_qn_requests(conn: &mut MultiplexedConnection) -> Result<QnReqBatch> {
+    let records = queue::claim_batch(conn, QN_REQ_LIST_KEY, QN_REQ_BATCH_LEN).await?;
+    let slices: Vec<&[u8]> = records.iter().map(Vec::as_slice).collect();
+    let id = hashv(&slices).to_string();
+    let reqs = records
+        .into_iter()
+        .map(decompress_qn_request)
+        .collect::<Result<_>>()?;
+    Ok(QnReqBatch { id, reqs })
+}
+
+pub async fn ack_qn_requests(conn: &mut MultiplexedConnection) -> Result<()> {
+    queue::ack_batch(conn, QN_REQ_LIST_KEY).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qn_request_compression() {
+        let req = r#"{"metadata":{},"txs":[]}"#.repeat(100);
+        let compressed = compress_qn_request(&req).unwrap();
+        assert!(compressed.len() < req.len());
+        assert_eq!(decompress_qn_request(compressed).unwrap(), req);
+        assert_eq!(decompress_qn_request(req.as_bytes().to_vec()).unwrap(), req);
+    }
+}
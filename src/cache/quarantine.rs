@@ -0,0 +1,44 @@
+use anyhow::Result;
+use redis::aio::MultiplexedConnection;
+use serde::{Deserialize, Serialize};
+
+use super::queue;
+
+/// Quarantined logs kept, the older ones are trimmed
+pub const QUARANTINE_MAX_LEN: isize = 1000;
+
+/// list of the logs which failed to parse with an error worth a look, the latest first
+const QUARANTINED_LOGS_KEY: &str = "list:quarantined_logs";
+
+/// A dex log skipped because it's corrupt or its instruction doesn't match the expected layout
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantinedLog {
+    pub txid: String,
+    pub slot: u64,
+    pub log_idx: usize,
+    pub program_id: String,
+    /// variant of the parse error, e.g. `MissingAccount`
+    pub kind: String,
+    pub error: String,
+    pub log: String,
+}
+
+/// Push the logs quarantined by the claimed batch `batch_id`, once if the batch is retried
+pub async fn quarantine_logs(
+    conn: &mut MultiplexedConnection,
+    batch_id: &str,
+    logs: &[QuarantinedLog],
+) -> Result<()> {
+    if logs.is_empty() {
+        return Ok(());
+    }
+
+    let mut pipe = redis::pipe();
+    for log in logs {
+        pipe.lpush(QUARANTINED_LOGS_KEY, serde_json::to_string(log)?)
+            .ignore();
+    }
+    pipe.ltrim(QUARANTINED_LOGS_KEY, 0, QUARANTINE_MAX_LEN - 1)
+        .ignore();
+    queue::query_once(conn, batch_id, "quarantine", &mut pipe).await
+}
@@ -0,0 +1,227 @@
+use anyhow::Result;
+use redis::{AsyncCommands, Pipeline, Script, aio::MultiplexedConnection};
+
+/// The steps applied by a claimed batch are remembered this long, past any retry of the batch
+const BATCH_STEP_TTL_SECS: u64 = 24 * 3600;
+
+/// Push ARGV[2..] to list KEYS[1] unless it already holds ARGV[1] items,
+/// returns the new length or -1 when the list is full.
+const BOUNDED_RPUSH_SCRIPT: &str = r#"
+local len = redis.call('LLEN', KEYS[1])
+if len >= tonumber(ARGV[1]) then
+    return -1
+end
+for i = 2, #ARGV, 1000 do
+    redis.call('RPUSH', KEYS[1], unpack(ARGV, i, math.min(i + 999, #ARGV)))
+end
+return len + #ARGV - 1
+"#;
+
+/// Push to each list KEYS[i] the ARGV[1 + #KEYS + ...] items counted by ARGV[1 + i], all or
+/// none: nothing is pushed if one of the lists already holds ARGV[1] items. Returns 0, or the
+/// position of the full list.
+const BOUNDED_RPUSH_ALL_SCRIPT: &str = r#"
+local max_len = tonumber(ARGV[1])
+for i = 1, #KEYS do
+    if tonumber(ARGV[1 + i]) > 0 and redis.call('LLEN', KEYS[i]) >= max_len then
+        return i
+    end
+end
+local first = #KEYS + 2
+for i = 1, #KEYS do
+    local last = first + tonumber(ARGV[1 + i]) - 1
+    for j = first, last, 1000 do
+        redis.call('RPUSH', KEYS[i], unpack(ARGV, j, math.min(j + 999, last)))
+    end
+    first = last + 1
+end
+return 0
+"#;
+
+/// Move up to ARGV[1] items from the head of list KEYS[1] to the processing list KEYS[2]
+/// and return them. A batch still in the processing list was claimed by a consumer
+/// which didn't ack it, e.g. crashed, it's returned again instead of a new one.
+const CLAIM_BATCH_SCRIPT: &str = r#"
+local pending = redis.call('LRANGE', KEYS[2], 0, -1)
+if #pending > 0 then
+    return pending
+end
+local items = redis.call('LRANGE', KEYS[1], 0, tonumber(ARGV[1]) - 1)
+if #items == 0 then
+    return items
+end
+redis.call('LTRIM', KEYS[1], #items, -1)
+for i = 1, #items, 1000 do
+    redis.call('RPUSH', KEYS[2], unpack(items, i, math.min(i + 999, #items)))
+end
+return items
+"#;
+
+/// Copy the batch claimed in the processing list KEYS[1] to each list KEYS[2..] holding less
+/// than ARGV[1] items, and ack it. Returns the positions of the full lists, from 1.
+const COPY_BATCH_SCRIPT: &str = r#"
+local items = redis.call('LRANGE', KEYS[1], 0, -1)
+local full = {}
+for i = 2, #KEYS do
+    if redis.call('LLEN', KEYS[i]) >= tonumber(ARGV[1]) then
+        table.insert(full, i - 1)
+    elseif #items > 0 then
+        for j = 1, #items, 1000 do
+            redis.call('RPUSH', KEYS[i], unpack(items, j, math.min(j + 999, #items)))
+        end
+    end
+end
+redis.call('DEL', KEYS[1])
+return full
+"#;
+
+fn processing_key(key: &str) -> String {
+    format!("{key}:processing")
+}
+
+fn batch_step_key(batch_id: &str, step: &str) -> String {
+    format!("str:batch_step:{batch_id}:{step}")
+}
+
+/// Atomic `llen` check and `rpush`, `false` if the list is full.
+pub async fn bounded_rpush(
+    conn: &mut MultiplexedConnection,
+    key: &str,
+    max_len: u64,
+    records: &[Vec<u8>],
+) -> Result<bool> {
+    if records.is_empty() {
+        return Ok(true);
+    }
+
+    let new_len: i64 = Script::new(BOUNDED_RPUSH_SCRIPT)
+        .key(key)
+        .arg(max_len)
+        .arg(records)
+        .invoke_async(conn)
+        .await?;
+    Ok(new_len >= 0)
+}
+
+/// `bounded_rpush` to several lists in one step, none of them is pushed if one is full.
+/// Returns the key of the full list.
+pub async fn bounded_rpush_all(
+    conn: &mut MultiplexedConnection,
+    lists: &[(&str, Vec<Vec<u8>>)],
+    max_len: u64,
+) -> Result<Option<String>> {
+    let lists: Vec<_> = lists.iter().filter(|(_, it)| !it.is_empty()).collect();
+    if lists.is_empty() {
+        return Ok(None);
+    }
+
+    let script = Script::new(BOUNDED_RPUSH_ALL_SCRIPT);
+    let mut script = script.prepare_invoke();
+    script.arg(max_len);
+    for (key, records) in &lists {
+        script.key(*key).arg(records.len());
+    }
+    for (_, records) in &lists {
+        script.arg(records);
+    }
+    let full: usize = script.invoke_async(conn).await?;
+    Ok(full.checked_sub(1).map(|it| lists[it].0.to_string()))
+}
+
+/// Atomically take a batch of at most `max` items from the head of the list.
+/// The batch stays claimed until `ack_batch`, and is claimed again if not acked.
+pub async fn claim_batch(
+    conn: &mut MultiplexedConnection,
+    key: &str,
+    max: usize,
+) -> Result<Vec<Vec<u8>>> {
+    let items: Vec<Vec<u8>> = Script::new(CLAIM_BATCH_SCRIPT)
+        .key(key)
+        .key(processing_key(key))
+        .arg(max)
+        .invoke_async(conn)
+        .await?;
+    Ok(items)
+}
+
+/// Copy the claimed batch to each of the `lists` holding less than `max_len` items and ack it,
+/// in one step. Returns the positions of the full lists, which missed the batch.
+pub async fn copy_claimed_batch(
+    conn: &mut MultiplexedConnection,
+    key: &str,
+    lists: &[String],
+    max_len: u64,
+) -> Result<Vec<usize>> {
+    let full: Vec<usize> = Script::new(COPY_BATCH_SCRIPT)
+        .key(processing_key(key))
+        .key(lists)
+        .arg(max_len)
+        .invoke_async(conn)
+        .await?;
+    Ok(full.into_iter().map(|it| it - 1).collect())
+}
+
+/// Run the writes of a step of the claimed batch `batch_id` in one transaction, with a mark of
+/// the step. A batch claimed again after an error skips the steps it applied already, so their
+/// counters aren't incremented twice.
+pub async fn query_once(
+    conn: &mut MultiplexedConnection,
+    batch_id: &str,
+    step: &str,
+    pipe: &mut Pipeline,
+) -> Result<()> {
+    if batch_step_applied(conn, batch_id, step).await? {
+        return Ok(());
+    }
+    pipe.atomic()
+        .set_ex(batch_step_key(batch_id, step), 1, BATCH_STEP_TTL_SECS)
+        .ignore();
+    let _: () = pipe.query_async(conn).await?;
+    Ok(())
+}
+
+/// Whether the step of the claimed batch was applied by `query_once`
+pub async fn batch_step_applied(
+    conn: &mut MultiplexedConnection,
+    batch_id: &str,
+    step: &str,
+) -> Result<bool> {
+    let applied: bool = conn.exists(batch_step_key(batch_id, step)).await?;
+    Ok(applied)
+}
+
+/// Remove the claimed batch after it has been processed.
+pub async fn ack_batch(conn: &mut MultiplexedConnection, key: &str) -> Result<()> {
+    let _: () = redis::cmd("del")
+        .arg(processing_key(key))
+        .query_async(conn)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[ignore = "needs redis, set TEST_REDIS_URL"]
+    async fn test_claim_batch_until_acked() {
+        let redis_url = std::env::var("TEST_REDIS_URL").expect("TEST_REDIS_URL of a redis");
+        let client = redis::Client::open(redis_url).unwrap();
+        let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+        let key = format!("list:test_claim_batch:{}", std::process::id());
+        let items: Vec<Vec<u8>> = vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()];
+        assert!(bounded_rpush(&mut conn, &key, 10, &items).await.unwrap());
+        // the list holds 3 items already
+        assert!(!bounded_rpush(&mut conn, &key, 3, &items).await.unwrap());
+
+        let batch = claim_batch(&mut conn, &key, 2).await.unwrap();
+        assert_eq!(batch, &items[..2]);
+        // not acked, e.g. the consumer crashed, the same batch is claimed again
+        assert_eq!(claim_batch(&mut conn, &key, 2).await.unwrap(), batch);
+        ack_batch(&mut conn, &key).await.unwrap();
+        assert_eq!(claim_batch(&mut conn, &key, 2).await.unwrap(), &items[2..]);
+        ack_batch(&mut conn, &key).await.unwrap();
+        assert!(claim_batch(&mut conn, &key, 2).await.unwrap().is_empty());
+    }
+}
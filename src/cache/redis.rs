@@ -49,17 +49,29 @@ pub trait RedisCacheRecord: Serialize + DeserializeOwned {
         }
     }
 
-    // TODO: impl it
-    //
-    // fn mget(
-    //     _conn: &mut MultiplexedConnection,
-    //     _keys: &[&str],
-    // ) -> impl Future<Output = Result<Vec<Option<Self>>>> + Send {
-    //     async move {
-    //         // let result: Vec<Option<String>> = conn.mget(keys).await?;
-    //         Ok(vec![])
-    //     }
-    // }
+    fn mget(
+        conn: &mut MultiplexedConnection,
+        keys: &[String],
+    ) -> impl Future<Output = Result<Vec<Option<Self>>>> + Send {
+        async move {
+            if keys.is_empty() {
+                return Ok(vec![]);
+            }
+
+            // explicit MGET, redis-rs sends GET for a single key which doesn't decode as a list
+            let resp: Vec<Option<String>> = redis::cmd("MGET").arg(keys).query_async(conn).await?;
+            let mut result = Vec::with_capacity(resp.len());
+            for json_str in resp {
+                let record = match json_str {
+                    Some(json_str) => Some(serde_json::from_str(&json_str)?),
+                    None => None,
+                };
+                result.push(record);
+            }
+
+            Ok(result)
+        }
+    }
 
     fn save(&self, conn: &mut MultiplexedConnection) -> impl Future<Output = Result<()>> {
         async {
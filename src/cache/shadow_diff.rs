@@ -0,0 +1,57 @@
+use anyhow::Result;
+use redis::aio::MultiplexedConnection;
+use serde::{Deserialize, Serialize};
+
+/// Shadow parser discrepancies kept, the older ones are trimmed
+pub const SHADOW_DIFFS_MAX_LEN: isize = 1000;
+
+/// list of the discrepancies between the production and the candidate parser, the latest first
+const SHADOW_DIFFS_KEY: &str = "list:shadow_parser_diffs";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShadowDiffKind {
+    /// emitted by the production parser only
+    Missing,
+    /// emitted by the candidate parser only
+    Extra,
+    /// emitted by both with different fields
+    Changed,
+    /// the candidate parser failed on the transaction
+    Error,
+}
+
+/// An event of a transaction the candidate parser doesn't emit like the production parser
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShadowDiff {
+    pub txid: String,
+    pub slot: u64,
+    pub kind: ShadowDiffKind,
+    /// the event emitted by the production parser
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current: Option<serde_json::Value>,
+    /// the event emitted by the candidate parser
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub candidate: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub async fn record_shadow_diffs(
+    conn: &mut MultiplexedConnection,
+    diffs: &[ShadowDiff],
+) -> Result<()> {
+    if diffs.is_empty() {
+        return Ok(());
+    }
+
+    let mut pipe = redis::pipe();
+    for diff in diffs {
+        pipe.lpush(SHADOW_DIFFS_KEY, serde_json::to_string(diff)?)
+            .ignore();
+    }
+    pipe.ltrim(SHADOW_DIFFS_KEY, 0, SHADOW_DIFFS_MAX_LEN - 1)
+        .ignore();
+    let _: () = pipe.query_async(conn).await?;
+    Ok(())
+}
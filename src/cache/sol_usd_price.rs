@@ -0,0 +1,37 @@
+use anyhow::Result;
+use redis::{AsyncCommands, aio::MultiplexedConnection};
+
+use super::DexEvent;
+
+/// the latest SOL/USD price, expires when the oracle stops updating it
+const SOL_USD_PRICE_KEY: &str = "sol_usd_price";
+
+pub async fn save_sol_usd_price(
+    conn: &mut MultiplexedConnection,
+    price: f64,
+    max_age_secs: u64,
+) -> Result<()> {
+    let _: () = conn.set_ex(SOL_USD_PRICE_KEY, price, max_age_secs).await?;
+    Ok(())
+}
+
+/// none when the price is older than `max_age_secs` of the oracle
+pub async fn get_sol_usd_price(conn: &mut MultiplexedConnection) -> Result<Option<f64>> {
+    Ok(conn.get(SOL_USD_PRICE_KEY).await?)
+}
+
+/// Set `price_usd` and `sol_price_usd_at_trade` of the trades, the trades quoted in a
+/// stablecoin are priced at their `price_quote`
+pub fn annotate_usd_prices(events: &mut [DexEvent], sol_usd: Option<f64>) {
+    for evt in events {
+        let DexEvent::Trade(trade) = evt else {
+            continue;
+        };
+        trade.sol_price_usd_at_trade = sol_usd;
+        trade.price_usd = if trade.is_sol_quoted() {
+            sol_usd.map(|it| trade.price_sol * it)
+        } else {
+            Some(trade.price_quote)
+        };
+    }
+}
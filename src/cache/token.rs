@@ -1,3 +1,5 @@
+use anyhow::Result;
+use redis::{AsyncCommands, aio::MultiplexedConnection};
 use serde::{Deserialize, Serialize};
 use serde_with::{DisplayFromStr, serde_as};
 use solana_sdk::pubkey::Pubkey;
@@ -25,3 +27,48 @@ impl RedisCacheRecord for TokenRecord {
         "token:"
     }
 }
+
+/// A proxied token image, an empty `content_type` marks a token without a usable image
+#[derive(Debug, Clone)]
+pub struct TokenImageRecord {
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+/// hash of the `content_type` and raw `data` of the image
+fn token_image_key(mint: &Pubkey) -> String {
+    format!("hash:token_image:{mint}")
+}
+
+pub async fn get_token_image(
+    conn: &mut MultiplexedConnection,
+    mint: &Pubkey,
+) -> Result<Option<TokenImageRecord>> {
+    let (content_type, data): (Option<String>, Option<Vec<u8>>) = conn
+        .hget(token_image_key(mint), &["content_type", "data"])
+        .await?;
+    let record = content_type.map(|content_type| TokenImageRecord {
+        content_type,
+        data: data.unwrap_or_default(),
+    });
+    Ok(record)
+}
+
+pub async fn save_token_image(
+    conn: &mut MultiplexedConnection,
+    mint: &Pubkey,
+    record: &TokenImageRecord,
+    seconds: i64,
+) -> Result<()> {
+    let key = token_image_key(mint);
+    let _: () = redis::pipe()
+        .hset(&key, "content_type", &record.content_type)
+        .ignore()
+        .hset(&key, "data", record.data.as_slice())
+        .ignore()
+        .expire(&key, seconds)
+        .ignore()
+        .query_async(conn)
+        .await?;
+    Ok(())
+}
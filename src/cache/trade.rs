@@ -1,26 +1,32 @@
-use std::{str::FromStr, sync::Arc};
-
-use anyhow::{Result, anyhow};
+use anyhow::Result;
 use chrono::{DateTime, Utc, serde::ts_seconds};
 use serde::{Deserialize, Serialize};
 use serde_with::{DisplayFromStr, serde_as};
 use tracing::warn;
 
 use crate::{
-    cache::{DexPoolRecord, RedisCacheRecord},
-    common::{Dex, TxBaseMetaInfo, WSOL_MINT, utils},
-    meteora::{damm::event::MeteoraDammSwap, dlmm::event::MeteoraDlmmSwapEvent},
+    cache::{AvgPrice, DexPoolCache, DexPoolRecord},
+    common::{self, Dex, TxBaseMetaInfo, WSOL_MINT, utils},
+    meteora::{
+        damm::event::MeteoraDammSwap, dbc::event::MeteoraDbcSwapEvent,
+        dlmm::event::MeteoraDlmmSwapEvent,
+    },
+    moonshot::instruction::MoonshotTrade,
+    orca::{event::WhirlpoolTradedEvent, instruction::SwapAccountIdx},
+    parse_error::{DexParseError, LogAccounts},
     pumpamm::event::{PumpAmmBuyEvent, PumpAmmSellEvent},
     pumpfun::event::TradeEvent,
-    qn_req_processor::IxAccount,
-    raydium::event::{SwapBaseInLog, SwapBaseOutLog},
+    qn_req_processor::{Amt, EventSource, IxAccount, TokenTransfer},
+    raydium::{
+        clmm::event::RaydiumClmmSwapEvent,
+        cpmm::instruction::RaydiumCpmmSwapAmt,
+        event::{SwapBaseInLog, SwapBaseOutLog},
+    },
 };
 use solana_sdk::pubkey::Pubkey;
 
-use super::DEX_POOL_RECORD_EXP_SECS;
-
 #[serde_as]
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TradeRecord {
     #[serde(with = "ts_seconds")]
     pub blk_ts: DateTime<Utc>,
@@ -35,15 +41,132 @@ pub struct TradeRecord {
     pub dex: Dex,
     #[serde_as(as = "DisplayFromStr")]
     pub pool: Pubkey,
+    /// lamports in the pool, zero when it isn't quoted in WSOL
     pub pool_sol_amt: u64,
     pub pool_token_amt: u64,
+    /// seconds from the creation of the pool to this trade, none when the hub didn't see the
+    /// pool created, see `annotate_pool_trades`
+    #[serde(default)]
+    pub pool_age_secs: Option<i64>,
+    /// this is the nth trade of the pool seen by the hub, from 1
+    #[serde(default)]
+    pub pool_trade_seq: u64,
     pub is_buy: bool,
+    /// lamports traded, zero when the pool isn't quoted in WSOL
     pub sol_amt: u64,
     pub token_amt: u64,
+    /// lamports the trader gained over the transaction, fees and WSOL token accounts included,
+    /// negative when spent
+    #[serde(default)]
+    pub trader_sol_delta: i64,
+    /// raw token amount the trader gained over the transaction, negative when sold
+    #[serde(default)]
+    pub trader_token_delta: i64,
+    /// zero when the pool isn't quoted in WSOL
     pub price_sol: f64,
+    /// `price_sol` as an exact decimal string, empty when the pool isn't quoted in WSOL
+    #[serde(default)]
+    pub price_sol_decimal: String,
+    /// the mint the pool is quoted in, WSOL or one of `quote_mints` of the config
+    #[serde_as(as = "DisplayFromStr")]
+    #[serde(default = "default_quote_mint")]
+    pub quote_mint: Pubkey,
+    /// price in ui units of `quote_mint`
+    #[serde(default)]
+    pub price_quote: f64,
+    /// raw amount of `quote_mint` traded, `sol_amt` in the WSOL pairs
+    #[serde(default)]
+    pub quote_amt: u64,
+    /// raw amount of `quote_mint` in the pool, `pool_sol_amt` in the WSOL pairs
+    #[serde(default)]
+    pub pool_quote_amt: u64,
+    /// `price_sol` in USD, or `price_quote` when quoted in a stablecoin, see `sol_usd` of the
+    /// config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_usd: Option<f64>,
+    /// the SOL/USD price when the trade was parsed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sol_price_usd_at_trade: Option<f64>,
+    /// the price deviates too far from the recent prices of the pool, see `guard_trade_prices`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub price_outlier: bool,
+    /// the stream batch the event was parsed from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<EventSource>,
+    /// averages of the mint up to this batch, attached with `avg_price.on_trades`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub avg_prices: Vec<AvgPrice>,
+    /// cluster of the trader, see `wallet_clusters` of the config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster_id: Option<String>,
+    /// the pool was drained by this trade or before, see `pool_drained` of the config
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pool_drained: bool,
+}
+
+fn default_quote_mint() -> Pubkey {
+    WSOL_MINT
+}
+
+/// Prices of a trade, the SOL prices are zero and empty unless it's quoted in WSOL
+struct TradePrices {
+    quote: f64,
+    sol: f64,
+    sol_decimal: String,
+    is_sol_quoted: bool,
+}
+
+impl TradePrices {
+    /// none if the price is not a normal float
+    fn new(
+        quote_mint: Pubkey,
+        quote_decimals: u8,
+        quote_amt: u64,
+        token_amt: u64,
+        token_decimals: u8,
+    ) -> Option<Self> {
+        if quote_mint == WSOL_MINT {
+            let sol = utils::calc_price_sol(quote_amt, token_amt, token_decimals);
+            return sol.is_normal().then(|| Self {
+                quote: sol,
+                sol,
+                sol_decimal: utils::calc_price_sol_decimal(quote_amt, token_amt, token_decimals),
+                is_sol_quoted: true,
+            });
+        }
+        let quote = utils::calc_price(quote_amt, quote_decimals, token_amt, token_decimals);
+        quote.is_normal().then(|| Self {
+            quote,
+            sol: 0.0,
+            sol_decimal: String::new(),
+            is_sol_quoted: false,
+        })
+    }
+
+    /// the amount of the quote mint as lamports, zero unless it's quoted in WSOL
+    fn lamports(&self, quote_amt: u64) -> u64 {
+        if self.is_sol_quoted { quote_amt } else { 0 }
+    }
 }
 
 impl TradeRecord {
+    /// the amounts and prices are in SOL
+    pub fn is_sol_quoted(&self) -> bool {
+        self.quote_mint == WSOL_MINT
+    }
+
+    /// `quote_amt` in ui units with the quote mint, e.g. `1.5 sol` or `20 usdc`
+    pub fn quote_ui_amt(&self) -> String {
+        match common::QuoteMint::from_mint(&self.quote_mint) {
+            Some(quote) => format!(
+                "{} {}",
+                self.quote_amt as f64 / 10f64.powi(quote.decimals() as i32),
+                quote.symbol()
+            ),
+            None => format!("{} sol", self.quote_amt as f64 / 1_000_000_000.0),
+        }
+    }
+
     pub async fn from_pumpamm_buy(
         TxBaseMetaInfo {
             blk_ts,
@@ -53,41 +176,22 @@ impl TradeRecord {
         }: TxBaseMetaInfo,
         log: PumpAmmBuyEvent,
         accounts: &[IxAccount],
-        redis_client: Arc<redis::Client>,
-    ) -> Result<Option<Self>> {
+        pools: &mut DexPoolCache,
+    ) -> Result<Option<Self>, DexParseError> {
         let pool = log.pool;
-        let mut redis_conn = redis_client.get_multiplexed_async_connection().await?;
-        let cached_pool =
-            DexPoolRecord::from_pumpamm_swap_accounts(pool, accounts, &mut redis_conn).await?;
-        cached_pool
-            .save_ex(&mut redis_conn, DEX_POOL_RECORD_EXP_SECS)
-            .await?;
-        drop(redis_conn);
-        if !cached_pool.is_wsol_pool() {
-            // only accept WSOL pair
+        let cached_pool = DexPoolRecord::from_pumpamm_swap_accounts(pool, accounts, pools).await?;
+        pools.save(cached_pool.clone());
+        let Some(quote_mint) = cached_pool.quote_mint() else {
+            // only accept pools quoted in the accepted quote mints
             return Ok(None);
-        }
+        };
 
-        let base_token_vault = accounts
-            .get(7)
-            .ok_or_else(|| anyhow!("need base token vault in pumpamm swap log"))?;
-        let base_token_amt = base_token_vault
-            .post_amt
-            .token
-            .clone()
-            .ok_or_else(|| anyhow!("base token should have balance in pumpamm swap log"))?;
-
-        let quote_token_vault = accounts
-            .get(8)
-            .ok_or_else(|| anyhow!("need quote token vault in pumpamm swap log"))?;
-        let quote_token_amt = quote_token_vault
-            .post_amt
-            .token
-            .clone()
-            .ok_or_else(|| anyhow!("quote token should have balance in pumpamm swap log"))?;
+        let log_accounts = LogAccounts::new(accounts, Dex::PumpAmm, "swap");
+        let base_token_amt = log_accounts.post_token_amt(7)?;
+        let quote_token_amt = log_accounts.post_token_amt(8)?;
 
         let (pool_sol_amt, pool_token_amt, sol_amt, token_amt, is_buy) =
-            if cached_pool.mint_a == WSOL_MINT {
+            if cached_pool.mint_a == quote_mint {
                 (
                     base_token_amt.amt,
                     quote_token_amt.amt,
@@ -106,14 +210,25 @@ impl TradeRecord {
             };
 
         let decimals = cached_pool.token_decimals();
-        let price_sol = utils::calc_price_sol(sol_amt, token_amt, decimals);
-        if !price_sol.is_normal() {
+        let quote_decimals = cached_pool.quote_decimals();
+        let Some(prices) =
+            TradePrices::new(quote_mint, quote_decimals, sol_amt, token_amt, decimals)
+        else {
             return Ok(None);
-        }
+        };
 
         let trader = log.user;
         let mint = cached_pool.token_mint();
 
+        let (trader_sol_delta, trader_token_delta) = trader_deltas(
+            accounts,
+            &trader,
+            &[5, 6],
+            &mint,
+            &txid,
+            !pools.is_read_only(),
+        );
+
         Ok(Some(Self {
             blk_ts,
             slot,
@@ -125,11 +240,27 @@ impl TradeRecord {
             dex: Dex::PumpAmm,
             pool,
             pool_token_amt,
-            pool_sol_amt,
+            pool_sol_amt: prices.lamports(pool_sol_amt),
+            pool_quote_amt: pool_sol_amt,
             is_buy,
-            sol_amt,
+            sol_amt: prices.lamports(sol_amt),
+            quote_amt: sol_amt,
             token_amt,
-            price_sol,
+            trader_sol_delta,
+            trader_token_delta,
+            price_sol: prices.sol,
+            price_sol_decimal: prices.sol_decimal,
+            quote_mint,
+            price_quote: prices.quote,
+            price_usd: None,
+            sol_price_usd_at_trade: None,
+            price_outlier: false,
+            source: None,
+            avg_prices: vec![],
+            cluster_id: None,
+            pool_drained: false,
+            pool_age_secs: None,
+            pool_trade_seq: 0,
         }))
     }
 
@@ -142,41 +273,22 @@ impl TradeRecord {
         }: TxBaseMetaInfo,
         log: PumpAmmSellEvent,
         accounts: &[IxAccount],
-        redis_client: Arc<redis::Client>,
-    ) -> Result<Option<Self>> {
+        pools: &mut DexPoolCache,
+    ) -> Result<Option<Self>, DexParseError> {
         let pool = log.pool;
-        let mut redis_conn = redis_client.get_multiplexed_async_connection().await?;
-        let cached_pool =
-            DexPoolRecord::from_pumpamm_swap_accounts(pool, accounts, &mut redis_conn).await?;
-        cached_pool
-            .save_ex(&mut redis_conn, DEX_POOL_RECORD_EXP_SECS)
-            .await?;
-        drop(redis_conn);
-        if !cached_pool.is_wsol_pool() {
-            // only accept WSOL pair
+        let cached_pool = DexPoolRecord::from_pumpamm_swap_accounts(pool, accounts, pools).await?;
+        pools.save(cached_pool.clone());
+        let Some(quote_mint) = cached_pool.quote_mint() else {
+            // only accept pools quoted in the accepted quote mints
             return Ok(None);
-        }
+        };
 
-        let base_token_vault = accounts
-            .get(7)
-            .ok_or_else(|| anyhow!("need base token vault in pumpamm swap log"))?;
-        let base_token_amt = base_token_vault
-            .post_amt
-            .token
-            .clone()
-            .ok_or_else(|| anyhow!("base token should have balance in pumpamm swap log"))?;
-
-        let quote_token_vault = accounts
-            .get(8)
-            .ok_or_else(|| anyhow!("need quote token vault in pumpamm swap log"))?;
-        let quote_token_amt = quote_token_vault
-            .post_amt
-            .token
-            .clone()
-            .ok_or_else(|| anyhow!("quote token should have balance in pumpamm swap log"))?;
+        let log_accounts = LogAccounts::new(accounts, Dex::PumpAmm, "swap");
+        let base_token_amt = log_accounts.post_token_amt(7)?;
+        let quote_token_amt = log_accounts.post_token_amt(8)?;
 
         let (pool_sol_amt, pool_token_amt, sol_amt, token_amt, is_buy) =
-            if cached_pool.mint_a == WSOL_MINT {
+            if cached_pool.mint_a == quote_mint {
                 (
                     base_token_amt.amt,
                     quote_token_amt.amt,
@@ -195,14 +307,25 @@ impl TradeRecord {
             };
 
         let decimals = cached_pool.token_decimals();
-        let price_sol = utils::calc_price_sol(sol_amt, token_amt, decimals);
-        if !price_sol.is_normal() {
+        let quote_decimals = cached_pool.quote_decimals();
+        let Some(prices) =
+            TradePrices::new(quote_mint, quote_decimals, sol_amt, token_amt, decimals)
+        else {
             return Ok(None);
-        }
+        };
 
         let trader = log.user;
         let mint = cached_pool.token_mint();
 
+        let (trader_sol_delta, trader_token_delta) = trader_deltas(
+            accounts,
+            &trader,
+            &[5, 6],
+            &mint,
+            &txid,
+            !pools.is_read_only(),
+        );
+
         Ok(Some(Self {
             blk_ts,
             slot,
@@ -214,11 +337,27 @@ impl TradeRecord {
             dex: Dex::PumpAmm,
             pool,
             pool_token_amt,
-            pool_sol_amt,
+            pool_sol_amt: prices.lamports(pool_sol_amt),
+            pool_quote_amt: pool_sol_amt,
             is_buy,
-            sol_amt,
+            sol_amt: prices.lamports(sol_amt),
+            quote_amt: sol_amt,
             token_amt,
-            price_sol,
+            trader_sol_delta,
+            trader_token_delta,
+            price_sol: prices.sol,
+            price_sol_decimal: prices.sol_decimal,
+            quote_mint,
+            price_quote: prices.quote,
+            price_usd: None,
+            sol_price_usd_at_trade: None,
+            price_outlier: false,
+            source: None,
+            avg_prices: vec![],
+            cluster_id: None,
+            pool_drained: false,
+            pool_age_secs: None,
+            pool_trade_seq: 0,
         }))
     }
 
@@ -231,70 +370,42 @@ impl TradeRecord {
         }: TxBaseMetaInfo,
         log: MeteoraDlmmSwapEvent,
         accounts: &[IxAccount],
-        redis_client: Arc<redis::Client>,
-    ) -> Result<Option<Self>> {
-        let pool_acc = accounts
-            .first()
-            .ok_or_else(|| anyhow!("need meteora dlmm lbpair pubkey in swap log"))?;
-        let lb_pair_pubkey = Pubkey::from_str(&pool_acc.pubkey)?;
-        let mut redis_conn = redis_client.get_multiplexed_async_connection().await?;
+        pools: &mut DexPoolCache,
+    ) -> Result<Option<Self>, DexParseError> {
+        let log_accounts = LogAccounts::new(accounts, Dex::MeteoraDlmm, "swap");
+        let lb_pair_pubkey = log_accounts.pubkey(0)?;
         let cached_pool =
-            DexPoolRecord::from_meteora_swap_accounts(lb_pair_pubkey, accounts, &mut redis_conn)
-                .await
-                .map_err(|err| anyhow!("error while parse pool from tx {txid}: {err}"))?;
-        cached_pool
-            .save_ex(&mut redis_conn, DEX_POOL_RECORD_EXP_SECS)
-            .await?;
-        drop(redis_conn);
-        if !cached_pool.is_wsol_pool() {
-            // only accept WSOL pair
+            DexPoolRecord::from_meteora_swap_accounts(lb_pair_pubkey, accounts, pools).await?;
+        pools.save(cached_pool.clone());
+        let Some(quote_mint) = cached_pool.quote_mint() else {
+            // only accept pools quoted in the accepted quote mints
             return Ok(None);
-        }
-
-        let trader_acc = accounts
-            .get(10)
-            .ok_or_else(|| anyhow!("need trader pubkey in meteora dlmm swap log"))?;
-        let trader = Pubkey::from_str(&trader_acc.pubkey)?;
+        };
 
-        let token_x_vault = accounts
-            .get(2)
-            .ok_or_else(|| anyhow!("need token x value in meteora dlmm swap log"))?;
-        let pool_token_x_amt = token_x_vault.post_amt.token.clone().ok_or_else(|| {
-            anyhow!(
-                "meteora dlmm token x vault {} should have balance, txid: {txid}",
-                token_x_vault.pubkey
-            )
-        })?;
-        let token_y_vault = accounts
-            .get(3)
-            .ok_or_else(|| anyhow!("need token y value in meteora dlmm swap log"))?;
-        let pool_token_y_amt = token_y_vault.post_amt.token.clone().ok_or_else(|| {
-            anyhow!(
-                "meteora dlmm token y vault {} should have balance",
-                token_y_vault.pubkey
-            )
-        })?;
-        let is_token_x_sol = pool_token_x_amt.mint == WSOL_MINT.to_string();
+        let trader = log_accounts.pubkey(10)?;
+        let pool_token_x_amt = log_accounts.post_token_amt(2)?;
+        let pool_token_y_amt = log_accounts.post_token_amt(3)?;
+        let is_token_x_sol = pool_token_x_amt.mint == quote_mint.to_string();
 
         let is_buy = cached_pool.is_meteora_dlmm_buy(log.swap_for_y);
         let sol_amt = if log.swap_for_y {
-            if cached_pool.mint_a == WSOL_MINT {
+            if cached_pool.mint_a == quote_mint {
                 log.amount_in
             } else {
                 log.amount_out
             }
-        } else if cached_pool.mint_a == WSOL_MINT {
+        } else if cached_pool.mint_a == quote_mint {
             log.amount_out
         } else {
             log.amount_in
         };
         let token_amt = if log.swap_for_y {
-            if cached_pool.mint_a == WSOL_MINT {
+            if cached_pool.mint_a == quote_mint {
                 log.amount_out
             } else {
                 log.amount_in
             }
-        } else if cached_pool.mint_a == WSOL_MINT {
+        } else if cached_pool.mint_a == quote_mint {
             log.amount_in
         } else {
             log.amount_out
@@ -306,10 +417,12 @@ impl TradeRecord {
         let mint = cached_pool.token_mint();
 
         let decimals = cached_pool.token_decimals();
-        let price_sol = utils::calc_price_sol(sol_amt, token_amt, decimals);
-        if !price_sol.is_normal() {
+        let quote_decimals = cached_pool.quote_decimals();
+        let Some(prices) =
+            TradePrices::new(quote_mint, quote_decimals, sol_amt, token_amt, decimals)
+        else {
             return Ok(None);
-        }
+        };
 
         let (pool_token_amt, pool_sol_amt) = if is_token_x_sol {
             (pool_token_y_amt.amt, pool_token_x_amt.amt)
@@ -317,6 +430,15 @@ impl TradeRecord {
             (pool_token_x_amt.amt, pool_token_y_amt.amt)
         };
 
+        let (trader_sol_delta, trader_token_delta) = trader_deltas(
+            accounts,
+            &trader,
+            &[4, 5],
+            &mint,
+            &txid,
+            !pools.is_read_only(),
+        );
+
         Ok(Some(Self {
             blk_ts,
             slot,
@@ -328,11 +450,27 @@ impl TradeRecord {
             dex: Dex::MeteoraDlmm,
             pool: lb_pair_pubkey,
             pool_token_amt,
-            pool_sol_amt,
+            pool_sol_amt: prices.lamports(pool_sol_amt),
+            pool_quote_amt: pool_sol_amt,
             is_buy,
-            sol_amt,
+            sol_amt: prices.lamports(sol_amt),
+            quote_amt: sol_amt,
             token_amt,
-            price_sol,
+            trader_sol_delta,
+            trader_token_delta,
+            price_sol: prices.sol,
+            price_sol_decimal: prices.sol_decimal,
+            quote_mint,
+            price_quote: prices.quote,
+            price_usd: None,
+            sol_price_usd_at_trade: None,
+            price_outlier: false,
+            source: None,
+            avg_prices: vec![],
+            cluster_id: None,
+            pool_drained: false,
+            pool_age_secs: None,
+            pool_trade_seq: 0,
         }))
     }
 
@@ -345,48 +483,21 @@ impl TradeRecord {
         }: TxBaseMetaInfo,
         log: MeteoraDammSwap,
         accounts: &[IxAccount],
-        redis_client: Arc<redis::Client>,
-    ) -> Result<Option<Self>> {
-        let pool_acc = accounts
-            .first()
-            .ok_or_else(|| anyhow!("need meteora damm pool pubkey in swap log"))?;
-        let pool_pubkey = Pubkey::from_str(&pool_acc.pubkey)?;
-        let mut redis_conn = redis_client.get_multiplexed_async_connection().await?;
+        pools: &mut DexPoolCache,
+    ) -> Result<Option<Self>, DexParseError> {
+        let log_accounts = LogAccounts::new(accounts, Dex::MeteoraDamm, "swap");
+        let pool_pubkey = log_accounts.pubkey(0)?;
         let cached_pool =
-            DexPoolRecord::from_meteora_damm_swap_accounts(pool_pubkey, accounts, &mut redis_conn)
-                .await?;
-        cached_pool
-            .save_ex(&mut redis_conn, DEX_POOL_RECORD_EXP_SECS)
-            .await?;
-        drop(redis_conn);
-        if !cached_pool.is_wsol_pool() {
-            // only accept WSOL pair
+            DexPoolRecord::from_meteora_damm_swap_accounts(pool_pubkey, accounts, pools).await?;
+        pools.save(cached_pool.clone());
+        let Some(quote_mint) = cached_pool.quote_mint() else {
+            // only accept pools quoted in the accepted quote mints
             return Ok(None);
-        }
+        };
 
-        let trader_acc = accounts
-            .get(12)
-            .ok_or_else(|| anyhow!("need trader pubkey in meteora damm swap log"))?;
-        let trader = Pubkey::from_str(&trader_acc.pubkey)?;
-
-        let token_a_vault = accounts
-            .get(5)
-            .ok_or_else(|| anyhow!("need token x value in meteora damm swap log"))?;
-        let pool_token_a_amt = token_a_vault.post_amt.token.clone().ok_or_else(|| {
-            anyhow!(
-                "meteora damm token a vault {} should have balance",
-                token_a_vault.pubkey
-            )
-        })?;
-        let token_b_vault = accounts
-            .get(6)
-            .ok_or_else(|| anyhow!("need token b value in meteora damm swap log"))?;
-        let pool_token_b_amt = token_b_vault.post_amt.token.clone().ok_or_else(|| {
-            anyhow!(
-                "meteora damm token b vault {} should have balance",
-                token_b_vault.pubkey
-            )
-        })?;
+        let trader = log_accounts.pubkey(12)?;
+        let pool_token_a_amt = log_accounts.post_token_amt(5)?;
+        let pool_token_b_amt = log_accounts.post_token_amt(6)?;
 
         let user_source_token_mint = accounts
             .get(1)
@@ -415,14 +526,22 @@ impl TradeRecord {
         }
 
         let is_buy = if let Some(user_source_token_mint) = user_source_token_mint {
-            user_source_token_mint == WSOL_MINT.to_string()
+            user_source_token_mint == quote_mint.to_string()
         } else {
-            user_dest_token_mint.unwrap() != WSOL_MINT.to_string()
+            user_dest_token_mint.unwrap() != quote_mint.to_string()
+        };
+        let Some(in_amount) = utils::checked_amount_sub(
+            log.in_amount,
+            log.protocol_fee,
+            &txid,
+            !pools.is_read_only(),
+        ) else {
+            return Ok(None);
         };
         let (sol_amt, token_amt) = if is_buy {
-            (log.in_amount - log.protocol_fee, log.out_amount)
+            (in_amount, log.out_amount)
         } else {
-            (log.out_amount, log.in_amount - log.protocol_fee)
+            (log.out_amount, in_amount)
         };
         if sol_amt == 0 || token_amt == 0 {
             return Ok(None);
@@ -430,18 +549,29 @@ impl TradeRecord {
 
         let mint = cached_pool.token_mint();
         let decimals = cached_pool.token_decimals();
-        let price_sol = utils::calc_price_sol(sol_amt, token_amt, decimals);
-        if !price_sol.is_normal() {
+        let quote_decimals = cached_pool.quote_decimals();
+        let Some(prices) =
+            TradePrices::new(quote_mint, quote_decimals, sol_amt, token_amt, decimals)
+        else {
             return Ok(None);
-        }
+        };
 
-        let is_token_a_sol = pool_token_a_amt.mint == WSOL_MINT.to_string();
+        let is_token_a_sol = pool_token_a_amt.mint == quote_mint.to_string();
         let (pool_token_amt, pool_sol_amt) = if is_token_a_sol {
             (pool_token_b_amt.amt, pool_token_a_amt.amt)
         } else {
             (pool_token_a_amt.amt, pool_token_b_amt.amt)
         };
 
+        let (trader_sol_delta, trader_token_delta) = trader_deltas(
+            accounts,
+            &trader,
+            &[1, 2],
+            &mint,
+            &txid,
+            !pools.is_read_only(),
+        );
+
         Ok(Some(Self {
             blk_ts,
             slot,
@@ -453,48 +583,561 @@ impl TradeRecord {
             dex: Dex::MeteoraDamm,
             pool: pool_pubkey,
             pool_token_amt,
-            pool_sol_amt,
+            pool_sol_amt: prices.lamports(pool_sol_amt),
+            pool_quote_amt: pool_sol_amt,
             is_buy,
-            sol_amt,
+            sol_amt: prices.lamports(sol_amt),
+            quote_amt: sol_amt,
             token_amt,
-            price_sol,
+            trader_sol_delta,
+            trader_token_delta,
+            price_sol: prices.sol,
+            price_sol_decimal: prices.sol_decimal,
+            quote_mint,
+            price_quote: prices.quote,
+            price_usd: None,
+            sol_price_usd_at_trade: None,
+            price_outlier: false,
+            source: None,
+            avg_prices: vec![],
+            cluster_id: None,
+            pool_drained: false,
+            pool_age_secs: None,
+            pool_trade_seq: 0,
         }))
     }
 
-    pub async fn from_raydium_amm_swap_base_in(
+    /// `swap` and `swap_v2` share the leading accounts, the router swaps through several
+    /// pools are not decoded
+    pub async fn from_raydium_clmm_swap(
         TxBaseMetaInfo {
             blk_ts,
             slot,
             txid,
             idx,
         }: TxBaseMetaInfo,
-        log: SwapBaseInLog,
+        log: RaydiumClmmSwapEvent,
         accounts: &[IxAccount],
-        redis_client: Arc<redis::Client>,
-    ) -> Result<Option<Self>> {
-        let pool_acc = accounts
-            .get(1)
-            .ok_or_else(|| anyhow!("need amm pubkey in swap base in log"))?;
-        let amm_pubkey = Pubkey::from_str(&pool_acc.pubkey)?;
-        let mut redis_conn = redis_client.get_multiplexed_async_connection().await?;
+        pools: &mut DexPoolCache,
+    ) -> Result<Option<Self>, DexParseError> {
+        let pool_pubkey = log.pool_state;
+        let cached_pool = DexPoolRecord::from_raydium_clmm_swap_accounts(
+            pool_pubkey,
+            log.zero_for_one,
+            accounts,
+            pools,
+        )
+        .await?;
+        pools.save(cached_pool.clone());
+        let Some(quote_mint) = cached_pool.quote_mint() else {
+            // only accept pools quoted in the accepted quote mints
+            return Ok(None);
+        };
+
+        let log_accounts = LogAccounts::new(accounts, Dex::RaydiumClmm, "swap");
+        let input_vault_amt = log_accounts.post_token_amt(5)?;
+        let output_vault_amt = log_accounts.post_token_amt(6)?;
+
+        let is_token_0_sol = cached_pool.mint_a == quote_mint;
+        let is_buy = is_token_0_sol == log.zero_for_one;
+        let (sol_amt, token_amt) = if is_token_0_sol {
+            (log.amount_0, log.amount_1)
+        } else {
+            (log.amount_1, log.amount_0)
+        };
+        if sol_amt == 0 || token_amt == 0 {
+            return Ok(None);
+        }
+
+        let mint = cached_pool.token_mint();
+        let decimals = cached_pool.token_decimals();
+        let quote_decimals = cached_pool.quote_decimals();
+        let Some(prices) =
+            TradePrices::new(quote_mint, quote_decimals, sol_amt, token_amt, decimals)
+        else {
+            return Ok(None);
+        };
+
+        // sol goes into the pool on buys
+        let (pool_sol_amt, pool_token_amt) = if is_buy {
+            (input_vault_amt.amt, output_vault_amt.amt)
+        } else {
+            (output_vault_amt.amt, input_vault_amt.amt)
+        };
+
+        let trader = log.sender;
+        let (trader_sol_delta, trader_token_delta) = trader_deltas(
+            accounts,
+            &trader,
+            &[3, 4],
+            &mint,
+            &txid,
+            !pools.is_read_only(),
+        );
+
+        Ok(Some(Self {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+            mint,
+            decimals,
+            trader,
+            dex: Dex::RaydiumClmm,
+            pool: pool_pubkey,
+            pool_sol_amt: prices.lamports(pool_sol_amt),
+            pool_quote_amt: pool_sol_amt,
+            pool_token_amt,
+            is_buy,
+            sol_amt: prices.lamports(sol_amt),
+            quote_amt: sol_amt,
+            token_amt,
+            trader_sol_delta,
+            trader_token_delta,
+            price_sol: prices.sol,
+            price_sol_decimal: prices.sol_decimal,
+            quote_mint,
+            price_quote: prices.quote,
+            price_usd: None,
+            sol_price_usd_at_trade: None,
+            price_outlier: false,
+            source: None,
+            avg_prices: vec![],
+            cluster_id: None,
+            pool_drained: false,
+            pool_age_secs: None,
+            pool_trade_seq: 0,
+        }))
+    }
+
+    pub async fn from_orca_whirlpool_traded(
+        TxBaseMetaInfo {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+        }: TxBaseMetaInfo,
+        log: WhirlpoolTradedEvent,
+        swap_accounts: SwapAccountIdx,
+        accounts: &[IxAccount],
+        pools: &mut DexPoolCache,
+    ) -> Result<Option<Self>, DexParseError> {
+        let whirlpool = log.whirlpool;
         let cached_pool =
-            DexPoolRecord::from_raydium_amm_trade_accounts(amm_pubkey, accounts, &mut redis_conn)
+            DexPoolRecord::from_orca_swap_accounts(whirlpool, swap_accounts, accounts, pools)
                 .await?;
-        cached_pool
-            .save_ex(&mut redis_conn, DEX_POOL_RECORD_EXP_SECS)
-            .await?;
-        drop(redis_conn);
+        pools.save(cached_pool.clone());
+        let Some(quote_mint) = cached_pool.quote_mint() else {
+            // only accept pools quoted in the accepted quote mints
+            return Ok(None);
+        };
 
-        if !cached_pool.is_wsol_pool() {
-            // only accept WSOL pair
+        let log_accounts = LogAccounts::new(accounts, Dex::OrcaWhirlpool, "traded");
+        let trader = log_accounts.pubkey(swap_accounts.token_authority)?;
+        let vault_a_amt = log_accounts.post_token_amt(swap_accounts.token_vault_a)?;
+        let vault_b_amt = log_accounts.post_token_amt(swap_accounts.token_vault_b)?;
+
+        let (amt_a, amt_b) = if log.a_to_b {
+            (log.input_amount, log.output_amount)
+        } else {
+            (log.output_amount, log.input_amount)
+        };
+        let is_token_a_sol = cached_pool.mint_a == quote_mint;
+        let is_buy = is_token_a_sol == log.a_to_b;
+        let (sol_amt, token_amt, pool_sol_amt, pool_token_amt) = if is_token_a_sol {
+            (amt_a, amt_b, vault_a_amt.amt, vault_b_amt.amt)
+        } else {
+            (amt_b, amt_a, vault_b_amt.amt, vault_a_amt.amt)
+        };
+        if sol_amt == 0 || token_amt == 0 {
             return Ok(None);
         }
 
+        let mint = cached_pool.token_mint();
+        let decimals = cached_pool.token_decimals();
+        let quote_decimals = cached_pool.quote_decimals();
+        let Some(prices) =
+            TradePrices::new(quote_mint, quote_decimals, sol_amt, token_amt, decimals)
+        else {
+            return Ok(None);
+        };
+
+        let (trader_sol_delta, trader_token_delta) = trader_deltas(
+            accounts,
+            &trader,
+            &[
+                swap_accounts.token_owner_account_a,
+                swap_accounts.token_owner_account_b,
+            ],
+            &mint,
+            &txid,
+            !pools.is_read_only(),
+        );
+
+        Ok(Some(Self {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+            mint,
+            decimals,
+            trader,
+            dex: Dex::OrcaWhirlpool,
+            pool: whirlpool,
+            pool_sol_amt: prices.lamports(pool_sol_amt),
+            pool_quote_amt: pool_sol_amt,
+            pool_token_amt,
+            is_buy,
+            sol_amt: prices.lamports(sol_amt),
+            quote_amt: sol_amt,
+            token_amt,
+            trader_sol_delta,
+            trader_token_delta,
+            price_sol: prices.sol,
+            price_sol_decimal: prices.sol_decimal,
+            quote_mint,
+            price_quote: prices.quote,
+            price_usd: None,
+            sol_price_usd_at_trade: None,
+            price_outlier: false,
+            source: None,
+            avg_prices: vec![],
+            cluster_id: None,
+            pool_drained: false,
+            pool_age_secs: None,
+            pool_trade_seq: 0,
+        }))
+    }
+
+    pub async fn from_meteora_dbc_swap(
+        TxBaseMetaInfo {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+        }: TxBaseMetaInfo,
+        log: MeteoraDbcSwapEvent,
+        accounts: &[IxAccount],
+        pools: &mut DexPoolCache,
+    ) -> Result<Option<Self>, DexParseError> {
+        let pool_pubkey = log.pool;
+        let cached_pool =
+            DexPoolRecord::from_meteora_dbc_swap_accounts(pool_pubkey, accounts, pools).await?;
+        pools.save(cached_pool.clone());
+        let quote_mint = cached_pool.mint_b;
+        if !common::quote_mints().contains(&quote_mint) {
+            // only accept curves quoted in the accepted quote mints
+            return Ok(None);
+        }
+
+        let log_accounts = LogAccounts::new(accounts, Dex::MeteoraDbc, "swap");
+        let trader = log_accounts.pubkey(9)?;
+        let base_vault_amt = log_accounts.post_token_amt(5)?;
+        let quote_vault_amt = log_accounts.post_token_amt(6)?;
+
+        // the amounts swapped on the curve, fees taken from the input excluded
+        let is_buy = log.is_buy();
+        let (sol_amt, token_amt) = if is_buy {
+            (
+                log.swap_result.actual_input_amount,
+                log.swap_result.output_amount,
+            )
+        } else {
+            (
+                log.swap_result.output_amount,
+                log.swap_result.actual_input_amount,
+            )
+        };
+        if sol_amt == 0 || token_amt == 0 {
+            return Ok(None);
+        }
+
+        let mint = cached_pool.mint_a;
+        let decimals = cached_pool.decimals_a;
+        let quote_decimals = cached_pool.quote_decimals();
+        let Some(prices) =
+            TradePrices::new(quote_mint, quote_decimals, sol_amt, token_amt, decimals)
+        else {
+            return Ok(None);
+        };
+
+        let (trader_sol_delta, trader_token_delta) = trader_deltas(
+            accounts,
+            &trader,
+            &[3, 4],
+            &mint,
+            &txid,
+            !pools.is_read_only(),
+        );
+
+        Ok(Some(Self {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+            mint,
+            decimals,
+            trader,
+            dex: Dex::MeteoraDbc,
+            pool: pool_pubkey,
+            pool_sol_amt: prices.lamports(quote_vault_amt.amt),
+            pool_quote_amt: quote_vault_amt.amt,
+            pool_token_amt: base_vault_amt.amt,
+            is_buy,
+            sol_amt: prices.lamports(sol_amt),
+            quote_amt: sol_amt,
+            token_amt,
+            trader_sol_delta,
+            trader_token_delta,
+            price_sol: prices.sol,
+            price_sol_decimal: prices.sol_decimal,
+            quote_mint,
+            price_quote: prices.quote,
+            price_usd: None,
+            sol_price_usd_at_trade: None,
+            price_outlier: false,
+            source: None,
+            avg_prices: vec![],
+            cluster_id: None,
+            pool_drained: false,
+            pool_age_secs: None,
+            pool_trade_seq: 0,
+        }))
+    }
+
+    /// Moonshot logs no trade event, the amounts are the changes of the curve balances, so two
+    /// trades of a curve in one transaction share them
+    pub async fn from_moonshot_trade(
+        TxBaseMetaInfo {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+        }: TxBaseMetaInfo,
+        is_buy: bool,
+        args: MoonshotTrade,
+        accounts: &[IxAccount],
+        pools: &mut DexPoolCache,
+    ) -> Result<Option<Self>, DexParseError> {
+        let cached_pool = DexPoolRecord::from_moonshot_trade_accounts(accounts, pools).await?;
+        pools.save(cached_pool.clone());
+        // the curves are quoted in SOL
+        let quote_mint = WSOL_MINT;
+
+        let log_accounts = LogAccounts::new(accounts, Dex::Moonshot, "trade");
+        let trader = log_accounts.pubkey(0)?;
+        let curve = log_accounts.get(2)?;
+        let curve_token_post = log_accounts.post_token_amt(3)?;
+        // the dev buy of a token minted by the same transaction: the curve balances add the
+        // minted supply and the rent, so the amounts are the ones of the instruction
+        let (sol_amt, token_amt) = match &log_accounts.get(3)?.pre_amt.token {
+            Some(curve_token_pre) if curve.pre_amt.sol > 0 => (
+                curve.post_amt.sol.abs_diff(curve.pre_amt.sol),
+                curve_token_post.amt.abs_diff(curve_token_pre.amt),
+            ),
+            _ => (args.collateral_amount, args.token_amount),
+        };
+        if sol_amt == 0 || token_amt == 0 {
+            return Ok(None);
+        }
+
+        let mint = cached_pool.token_mint();
+        let decimals = cached_pool.token_decimals();
+        let quote_decimals = cached_pool.quote_decimals();
+        let Some(prices) =
+            TradePrices::new(quote_mint, quote_decimals, sol_amt, token_amt, decimals)
+        else {
+            return Ok(None);
+        };
+
+        let (trader_sol_delta, trader_token_delta) =
+            trader_deltas(accounts, &trader, &[1], &mint, &txid, !pools.is_read_only());
+
+        Ok(Some(Self {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+            mint,
+            decimals,
+            trader,
+            dex: Dex::Moonshot,
+            pool: cached_pool.addr,
+            pool_sol_amt: prices.lamports(curve.post_amt.sol),
+            pool_quote_amt: curve.post_amt.sol,
+            pool_token_amt: curve_token_post.amt,
+            is_buy,
+            sol_amt: prices.lamports(sol_amt),
+            quote_amt: sol_amt,
+            token_amt,
+            trader_sol_delta,
+            trader_token_delta,
+            price_sol: prices.sol,
+            price_sol_decimal: prices.sol_decimal,
+            quote_mint,
+            price_quote: prices.quote,
+            price_usd: None,
+            sol_price_usd_at_trade: None,
+            price_outlier: false,
+            source: None,
+            avg_prices: vec![],
+            cluster_id: None,
+            pool_drained: false,
+            pool_age_secs: None,
+            pool_trade_seq: 0,
+        }))
+    }
+
+    /// The amount not fixed by the instruction is the change of its vault balance, so two
+    /// swaps of a pool in one transaction share it
+    pub async fn from_raydium_cpmm_swap(
+        TxBaseMetaInfo {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+        }: TxBaseMetaInfo,
+        swap_amt: RaydiumCpmmSwapAmt,
+        accounts: &[IxAccount],
+        transfers: &[TokenTransfer],
+        pools: &mut DexPoolCache,
+    ) -> Result<Option<Self>, DexParseError> {
+        let log_accounts = LogAccounts::new(accounts, Dex::RaydiumCpmm, "swap");
+        let pool_pubkey = log_accounts.pubkey(3)?;
+        let cached_pool =
+            DexPoolRecord::from_raydium_cpmm_swap_accounts(pool_pubkey, accounts, pools).await?;
+        pools.save(cached_pool.clone());
+        let Some(quote_mint) = cached_pool.quote_mint() else {
+            // only accept pools quoted in the accepted quote mints
+            return Ok(None);
+        };
+
+        let trader = log_accounts.pubkey(0)?;
+        let input_vault_pre = log_accounts.pre_token_amt(6)?;
+        let input_vault_post = log_accounts.post_token_amt(6)?;
+        let output_vault_pre = log_accounts.pre_token_amt(7)?;
+        let output_vault_post = log_accounts.post_token_amt(7)?;
+        // the transfers of this swap into and out of the vaults, the vault balances are the ones
+        // of the transaction, which also count the other swaps of the pool
+        let (input_vault, output_vault) = (log_accounts.get(6)?, log_accounts.get(7)?);
+        let transferred_in = transfers
+            .iter()
+            .find(|it| it.destination == input_vault.pubkey)
+            .map(|it| it.amt);
+        let transferred_out = transfers
+            .iter()
+            .find(|it| it.source == output_vault.pubkey)
+            .map(|it| it.amt);
+        let (amount_in, amount_out) = match (transferred_in, transferred_out, swap_amt) {
+            (Some(amount_in), Some(amount_out), _) => (amount_in, amount_out),
+            // transactions converted before the transfers were kept
+            (_, _, RaydiumCpmmSwapAmt::In(amt)) => (
+                amt,
+                output_vault_pre.amt.saturating_sub(output_vault_post.amt),
+            ),
+            (_, _, RaydiumCpmmSwapAmt::Out(amt)) => (
+                input_vault_post.amt.saturating_sub(input_vault_pre.amt),
+                amt,
+            ),
+        };
+
+        let is_buy = input_vault_post.mint == quote_mint.to_string();
+        let (sol_amt, token_amt, pool_sol_amt, pool_token_amt) = if is_buy {
+            (
+                amount_in,
+                amount_out,
+                input_vault_post.amt,
+                output_vault_post.amt,
+            )
+        } else {
+            (
+                amount_out,
+                amount_in,
+                output_vault_post.amt,
+                input_vault_post.amt,
+            )
+        };
+        if sol_amt == 0 || token_amt == 0 {
+            return Ok(None);
+        }
+
+        let mint = cached_pool.token_mint();
+        let decimals = cached_pool.token_decimals();
+        let quote_decimals = cached_pool.quote_decimals();
+        let Some(prices) =
+            TradePrices::new(quote_mint, quote_decimals, sol_amt, token_amt, decimals)
+        else {
+            return Ok(None);
+        };
+
+        let (trader_sol_delta, trader_token_delta) = trader_deltas(
+            accounts,
+            &trader,
+            &[4, 5],
+            &mint,
+            &txid,
+            !pools.is_read_only(),
+        );
+
+        Ok(Some(Self {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+            mint,
+            decimals,
+            trader,
+            dex: Dex::RaydiumCpmm,
+            pool: pool_pubkey,
+            pool_sol_amt: prices.lamports(pool_sol_amt),
+            pool_quote_amt: pool_sol_amt,
+            pool_token_amt,
+            is_buy,
+            sol_amt: prices.lamports(sol_amt),
+            quote_amt: sol_amt,
+            token_amt,
+            trader_sol_delta,
+            trader_token_delta,
+            price_sol: prices.sol,
+            price_sol_decimal: prices.sol_decimal,
+            quote_mint,
+            price_quote: prices.quote,
+            price_usd: None,
+            sol_price_usd_at_trade: None,
+            price_outlier: false,
+            source: None,
+            avg_prices: vec![],
+            cluster_id: None,
+            pool_drained: false,
+            pool_age_secs: None,
+            pool_trade_seq: 0,
+        }))
+    }
+
+    pub async fn from_raydium_amm_swap_base_in(
+        TxBaseMetaInfo {
+            blk_ts,
+            slot,
+            txid,
+            idx,
+        }: TxBaseMetaInfo,
+        log: SwapBaseInLog,
+        accounts: &[IxAccount],
+        pools: &mut DexPoolCache,
+    ) -> Result<Option<Self>, DexParseError> {
+        let log_accounts = LogAccounts::new(accounts, Dex::RaydiumAmm, "swap_base_in");
+        let amm_pubkey = log_accounts.pubkey(1)?;
+        let cached_pool =
+            DexPoolRecord::from_raydium_amm_trade_accounts(amm_pubkey, accounts, pools).await?;
+        pools.save(cached_pool.clone());
+
+        let Some(quote_mint) = cached_pool.quote_mint() else {
+            // only accept pools quoted in the accepted quote mints
+            return Ok(None);
+        };
+
         // example tx: 3JwTJ11gDVicXmyjGoemuy3NP7zypiq3FvWQWyR99wdi3iRcrhf3kcEwszpjn5P8MX5uiKLYKr8HnegPynR6mL4y
-        let trader_acc = accounts
-            .last()
-            .ok_or_else(|| anyhow!("need trader pubkey in swap base in log"))?;
-        let trader = Pubkey::from_str(&trader_acc.pubkey)?;
+        let trader = log_accounts.last_pubkey()?;
 
         let mut coin_token_vault_idx = 4;
         let mut pc_token_vault_idx = 5;
@@ -503,32 +1146,21 @@ impl TradeRecord {
             pc_token_vault_idx = 6;
         }
 
-        let coin_token_vault = accounts
-            .get(coin_token_vault_idx)
-            .ok_or_else(|| anyhow!("need coin token vault in raydium amm swap base in log"))?;
-        let coin_token_amt =
-            coin_token_vault.post_amt.token.clone().ok_or_else(|| {
-                anyhow!("coin token should have balance in raydium amm base in swap")
-            })?;
-        let pc_token_vault = accounts
-            .get(pc_token_vault_idx)
-            .ok_or_else(|| anyhow!("need pc token vault in raydium amm swap base in log"))?;
-        let pc_token_amt = pc_token_vault.post_amt.token.clone().ok_or_else(|| {
-            anyhow!("pc token should have balance in raydium amm base in swap in txid: {txid}")
-        })?;
-        let is_coin_token_sol = coin_token_amt.mint == WSOL_MINT.to_string();
+        let coin_token_amt = log_accounts.post_token_amt(coin_token_vault_idx)?;
+        let pc_token_amt = log_accounts.post_token_amt(pc_token_vault_idx)?;
+        let is_coin_token_sol = coin_token_amt.mint == quote_mint.to_string();
 
         let is_buy = cached_pool.is_raydium_buy(log.direction);
         let sol_amt = if log.direction == 1 {
             // pc2coin
-            if cached_pool.mint_b == WSOL_MINT {
+            if cached_pool.mint_b == quote_mint {
                 log.amount_in
             } else {
                 log.out_amount
             }
         } else {
             // coin2pc
-            if cached_pool.mint_b == WSOL_MINT {
+            if cached_pool.mint_b == quote_mint {
                 log.out_amount
             } else {
                 log.amount_in
@@ -536,14 +1168,14 @@ impl TradeRecord {
         };
         let token_amt = if log.direction == 1 {
             // pc2coin
-            if cached_pool.mint_b == WSOL_MINT {
+            if cached_pool.mint_b == quote_mint {
                 log.out_amount
             } else {
                 log.amount_in
             }
         } else {
             // coin2pc
-            if cached_pool.mint_b == WSOL_MINT {
+            if cached_pool.mint_b == quote_mint {
                 log.amount_in
             } else {
                 log.out_amount
@@ -555,10 +1187,12 @@ impl TradeRecord {
 
         let mint = cached_pool.token_mint();
         let decimals = cached_pool.token_decimals();
-        let price_sol = utils::calc_price_sol(sol_amt, token_amt, decimals);
-        if !price_sol.is_normal() {
+        let quote_decimals = cached_pool.quote_decimals();
+        let Some(prices) =
+            TradePrices::new(quote_mint, quote_decimals, sol_amt, token_amt, decimals)
+        else {
             return Ok(None);
-        }
+        };
 
         let (pool_token_amt, pool_sol_amt) = if is_coin_token_sol {
             (pc_token_amt.amt, coin_token_amt.amt)
@@ -566,6 +1200,15 @@ impl TradeRecord {
             (coin_token_amt.amt, pc_token_amt.amt)
         };
 
+        let (trader_sol_delta, trader_token_delta) = trader_deltas(
+            accounts,
+            &trader,
+            &[accounts.len() - 3, accounts.len() - 2],
+            &mint,
+            &txid,
+            !pools.is_read_only(),
+        );
+
         Ok(Some(Self {
             blk_ts,
             slot,
@@ -576,12 +1219,28 @@ impl TradeRecord {
             trader,
             dex: Dex::RaydiumAmm,
             pool: amm_pubkey,
-            pool_sol_amt,
+            pool_sol_amt: prices.lamports(pool_sol_amt),
+            pool_quote_amt: pool_sol_amt,
             pool_token_amt,
             is_buy,
-            sol_amt,
+            sol_amt: prices.lamports(sol_amt),
+            quote_amt: sol_amt,
             token_amt,
-            price_sol,
+            trader_sol_delta,
+            trader_token_delta,
+            price_sol: prices.sol,
+            price_sol_decimal: prices.sol_decimal,
+            quote_mint,
+            price_quote: prices.quote,
+            price_usd: None,
+            sol_price_usd_at_trade: None,
+            price_outlier: false,
+            source: None,
+            avg_prices: vec![],
+            cluster_id: None,
+            pool_drained: false,
+            pool_age_secs: None,
+            pool_trade_seq: 0,
         }))
     }
 
@@ -594,31 +1253,21 @@ impl TradeRecord {
         }: TxBaseMetaInfo,
         log: SwapBaseOutLog,
         accounts: &[IxAccount],
-        redis_client: Arc<redis::Client>,
-    ) -> Result<Option<Self>> {
-        let pool_acc = accounts
-            .get(1)
-            .ok_or_else(|| anyhow!("need amm pubkey in swap base out log"))?;
-        let amm_pubkey = Pubkey::from_str(&pool_acc.pubkey)?;
-        let mut redis_conn = redis_client.get_multiplexed_async_connection().await?;
+        pools: &mut DexPoolCache,
+    ) -> Result<Option<Self>, DexParseError> {
+        let log_accounts = LogAccounts::new(accounts, Dex::RaydiumAmm, "swap_base_out");
+        let amm_pubkey = log_accounts.pubkey(1)?;
         let cached_pool =
-            DexPoolRecord::from_raydium_amm_trade_accounts(amm_pubkey, accounts, &mut redis_conn)
-                .await?;
-        cached_pool
-            .save_ex(&mut redis_conn, DEX_POOL_RECORD_EXP_SECS)
-            .await?;
-        drop(redis_conn);
+            DexPoolRecord::from_raydium_amm_trade_accounts(amm_pubkey, accounts, pools).await?;
+        pools.save(cached_pool.clone());
 
-        if !cached_pool.is_wsol_pool() {
-            // only accept WSOL pair
+        let Some(quote_mint) = cached_pool.quote_mint() else {
+            // only accept pools quoted in the accepted quote mints
             return Ok(None);
-        }
+        };
 
         // example tx: 2ff5Kxnu2V2Pa7TEsvJ9aDQF6VWYWiB9zR954PszxRNg52kiXavYU7AAUaCcEsGYU9GU7mHRYuSdjHvXege5dGWM
-        let trader_acc = accounts
-            .last()
-            .ok_or_else(|| anyhow!("need trader pubkey in swap base out log"))?;
-        let trader = Pubkey::from_str(&trader_acc.pubkey)?;
+        let trader = log_accounts.last_pubkey()?;
 
         let mut coin_token_vault_idx = 4;
         let mut pc_token_vault_idx = 5;
@@ -627,32 +1276,21 @@ impl TradeRecord {
             pc_token_vault_idx = 6;
         }
 
-        let coin_token_vault = accounts
-            .get(coin_token_vault_idx)
-            .ok_or_else(|| anyhow!("need coin token vault in raydium amm swap base out log"))?;
-        let coin_token_amt = coin_token_vault.post_amt.token.clone().ok_or_else(|| {
-            anyhow!("coin token should have balance in raydium amm base out swap")
-        })?;
-        let pc_token_vault = accounts
-            .get(pc_token_vault_idx)
-            .ok_or_else(|| anyhow!("need pc token vault in raydium amm swap base out log"))?;
-        let pc_token_amt =
-            pc_token_vault.post_amt.token.clone().ok_or_else(|| {
-                anyhow!("pc token should have balance in raydium amm base out swap")
-            })?;
-        let is_coin_token_sol = coin_token_amt.mint == WSOL_MINT.to_string();
+        let coin_token_amt = log_accounts.post_token_amt(coin_token_vault_idx)?;
+        let pc_token_amt = log_accounts.post_token_amt(pc_token_vault_idx)?;
+        let is_coin_token_sol = coin_token_amt.mint == quote_mint.to_string();
 
         let is_buy = cached_pool.is_raydium_buy(log.direction);
         let sol_amt = if log.direction == 1 {
             // pc2coin
-            if cached_pool.mint_b == WSOL_MINT {
+            if cached_pool.mint_b == quote_mint {
                 log.deduct_in
             } else {
                 log.amount_out
             }
         } else {
             // coin2pc
-            if cached_pool.mint_b == WSOL_MINT {
+            if cached_pool.mint_b == quote_mint {
                 log.amount_out
             } else {
                 log.deduct_in
@@ -660,14 +1298,14 @@ impl TradeRecord {
         };
         let token_amt = if log.direction == 1 {
             // pc2coin
-            if cached_pool.mint_b == WSOL_MINT {
+            if cached_pool.mint_b == quote_mint {
                 log.amount_out
             } else {
                 log.deduct_in
             }
         } else {
             // coin2pc
-            if cached_pool.mint_b == WSOL_MINT {
+            if cached_pool.mint_b == quote_mint {
                 log.deduct_in
             } else {
                 log.amount_out
@@ -679,10 +1317,12 @@ impl TradeRecord {
 
         let mint = cached_pool.token_mint();
         let decimals = cached_pool.token_decimals();
-        let price_sol = utils::calc_price_sol(sol_amt, token_amt, decimals);
-        if !price_sol.is_normal() {
+        let quote_decimals = cached_pool.quote_decimals();
+        let Some(prices) =
+            TradePrices::new(quote_mint, quote_decimals, sol_amt, token_amt, decimals)
+        else {
             return Ok(None);
-        }
+        };
 
         let (pool_token_amt, pool_sol_amt) = if is_coin_token_sol {
             (pc_token_amt.amt, coin_token_amt.amt)
@@ -690,6 +1330,15 @@ impl TradeRecord {
             (coin_token_amt.amt, pc_token_amt.amt)
         };
 
+        let (trader_sol_delta, trader_token_delta) = trader_deltas(
+            accounts,
+            &trader,
+            &[accounts.len() - 3, accounts.len() - 2],
+            &mint,
+            &txid,
+            !pools.is_read_only(),
+        );
+
         Ok(Some(Self {
             blk_ts,
             slot,
@@ -700,12 +1349,28 @@ impl TradeRecord {
             trader,
             dex: Dex::RaydiumAmm,
             pool: amm_pubkey,
-            pool_sol_amt,
+            pool_sol_amt: prices.lamports(pool_sol_amt),
+            pool_quote_amt: pool_sol_amt,
             pool_token_amt,
             is_buy,
-            sol_amt,
+            sol_amt: prices.lamports(sol_amt),
+            quote_amt: sol_amt,
             token_amt,
-            price_sol,
+            trader_sol_delta,
+            trader_token_delta,
+            price_sol: prices.sol,
+            price_sol_decimal: prices.sol_decimal,
+            quote_mint,
+            price_quote: prices.quote,
+            price_usd: None,
+            sol_price_usd_at_trade: None,
+            price_outlier: false,
+            source: None,
+            avg_prices: vec![],
+            cluster_id: None,
+            pool_drained: false,
+            pool_age_secs: None,
+            pool_trade_seq: 0,
         }))
     }
 
@@ -718,29 +1383,19 @@ impl TradeRecord {
         }: TxBaseMetaInfo,
         log: TradeEvent,
         accounts: &[IxAccount],
-        redis_client: Arc<redis::Client>,
-    ) -> Result<Option<Self>> {
-        let pool_acc = accounts
-            .get(3)
-            .ok_or_else(|| anyhow!("need curve pubkey in pumpfun trade"))?;
-        let curve_pubkey = Pubkey::from_str(&pool_acc.pubkey)?;
-        let mut redis_conn = redis_client.get_multiplexed_async_connection().await?;
-        let cached_pool =
-            DexPoolRecord::from_pumpfun_trade_accounts(accounts, &mut redis_conn).await?;
-        cached_pool
-            .save_ex(&mut redis_conn, DEX_POOL_RECORD_EXP_SECS)
-            .await?;
-        drop(redis_conn);
+        pools: &mut DexPoolCache,
+    ) -> Result<Option<Self>, DexParseError> {
+        let log_accounts = LogAccounts::new(accounts, Dex::Pumpfun, "trade");
+        let curve_pubkey = log_accounts.pubkey(3)?;
+        let cached_pool = DexPoolRecord::from_pumpfun_trade_accounts(accounts, pools).await?;
+        pools.save(cached_pool.clone());
 
-        if !cached_pool.is_wsol_pool() {
-            // only accept WSOL pair
+        let Some(quote_mint) = cached_pool.quote_mint() else {
+            // only accept pools quoted in the accepted quote mints
             return Ok(None);
-        }
+        };
 
-        let trader_acc = accounts
-            .get(6)
-            .ok_or_else(|| anyhow!("need trader pubkey in pumpfun trade"))?;
-        let trader = Pubkey::from_str(&trader_acc.pubkey)?;
+        let trader = log_accounts.pubkey(6)?;
         let is_buy = log.is_buy;
         let sol_amt = log.sol_amount;
         let token_amt = log.token_amount;
@@ -752,10 +1407,15 @@ impl TradeRecord {
 
         let mint = cached_pool.token_mint();
         let decimals = cached_pool.token_decimals();
-        let price_sol = utils::calc_price_sol(sol_amt, token_amt, decimals);
-        if !price_sol.is_normal() {
+        let quote_decimals = cached_pool.quote_decimals();
+        let Some(prices) =
+            TradePrices::new(quote_mint, quote_decimals, sol_amt, token_amt, decimals)
+        else {
             return Ok(None);
-        }
+        };
+
+        let (trader_sol_delta, trader_token_delta) =
+            trader_deltas(accounts, &trader, &[5], &mint, &txid, !pools.is_read_only());
 
         Ok(Some(Self {
             blk_ts,
@@ -767,12 +1427,534 @@ impl TradeRecord {
             trader,
             dex: Dex::Pumpfun,
             pool: curve_pubkey,
-            pool_sol_amt,
+            pool_sol_amt: prices.lamports(pool_sol_amt),
+            pool_quote_amt: pool_sol_amt,
             pool_token_amt,
             is_buy,
-            sol_amt,
+            sol_amt: prices.lamports(sol_amt),
+            quote_amt: sol_amt,
             token_amt,
-            price_sol,
+            trader_sol_delta,
+            trader_token_delta,
+            price_sol: prices.sol,
+            price_sol_decimal: prices.sol_decimal,
+            quote_mint,
+            price_quote: prices.quote,
+            price_usd: None,
+            sol_price_usd_at_trade: None,
+            price_outlier: false,
+            source: None,
+            avg_prices: vec![],
+            cluster_id: None,
+            pool_drained: false,
+            pool_age_secs: None,
+            pool_trade_seq: 0,
         }))
     }
 }
+
+/// Balance changes of the trader over the transaction as `(sol, token)`: lamports of the trader
+/// plus its WSOL token accounts, and its token accounts of `mint`, among `user_token_accs`.
+/// Balance changes which overflow are left out, and counted as amount anomalies with `count`,
+/// the production parse's only.
+fn trader_deltas(
+    accounts: &[IxAccount],
+    trader: &Pubkey,
+    user_token_accs: &[usize],
+    mint: &Pubkey,
+    txid: &str,
+    count: bool,
+) -> (i64, i64) {
+    let trader = trader.to_string();
+    let mut sol_delta = accounts
+        .iter()
+        .find(|it| it.pubkey == trader)
+        .and_then(|it| utils::checked_balance_delta(it.pre_amt.sol, it.post_amt.sol, txid, count))
+        .unwrap_or_default();
+    let mut token_delta = 0;
+    let token_amt = |amt: &Amt| amt.token.as_ref().map(|it| it.amt).unwrap_or_default();
+    for acc in user_token_accs.iter().filter_map(|idx| accounts.get(*idx)) {
+        let Some(acc_mint) = acc.pre_amt.token.as_ref().or(acc.post_amt.token.as_ref()) else {
+            continue;
+        };
+        let Some(delta) = utils::checked_balance_delta(
+            token_amt(&acc.pre_amt),
+            token_amt(&acc.post_amt),
+            txid,
+            count,
+        ) else {
+            continue;
+        };
+        let total = if acc_mint.mint == WSOL_MINT.to_string() {
+            &mut sol_delta
+        } else if acc_mint.mint == mint.to_string() {
+            &mut token_delta
+        } else {
+            continue;
+        };
+        if let Some(sum) = utils::checked_delta_add(*total, delta, txid, count) {
+            *total = sum;
+        }
+    }
+    (sol_delta, token_delta)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::qn_req_processor::TokenAmt;
+
+    use super::*;
+
+    fn account(
+        pubkey: &Pubkey,
+        pre: (u64, Option<u64>),
+        post: (u64, Option<u64>),
+        mint: &Pubkey,
+    ) -> IxAccount {
+        let amt = |(sol, token): (u64, Option<u64>)| Amt {
+            sol,
+            token: token.map(|amt| TokenAmt {
+                mint: mint.to_string(),
+                decimals: 6,
+                amt,
+            }),
+        };
+        IxAccount {
+            pubkey: pubkey.to_string(),
+            pre_amt: amt(pre),
+            post_amt: amt(post),
+        }
+    }
+
+    /// Token account of `mint` with `decimals`, its `(pre, post)` amounts
+    fn token_account(pubkey: &Pubkey, mint: &Pubkey, decimals: u8, amts: (u64, u64)) -> IxAccount {
+        let amt = |amt: u64| Amt {
+            sol: 0,
+            token: Some(TokenAmt {
+                mint: mint.to_string(),
+                decimals,
+                amt,
+            }),
+        };
+        IxAccount {
+            pubkey: pubkey.to_string(),
+            pre_amt: amt(amts.0),
+            post_amt: amt(amts.1),
+        }
+    }
+
+    fn meta() -> TxBaseMetaInfo {
+        TxBaseMetaInfo {
+            blk_ts: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            slot: 1,
+            txid: "tx".to_string(),
+            idx: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_raydium_clmm_swap() {
+        let (trader, pool, mint) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (sol_vault, token_vault) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (user_sol, user_token) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let event = |zero_for_one: bool, amount_0: u64, amount_1: u64| RaydiumClmmSwapEvent {
+            pool_state: pool,
+            sender: trader,
+            token_account_0: user_sol,
+            token_account_1: user_token,
+            amount_0,
+            transfer_fee_0: 0,
+            amount_1,
+            transfer_fee_1: 0,
+            zero_for_one,
+            sqrt_price_x64: 0,
+            liquidity: 0,
+            tick: 0,
+        };
+        // payer, amm config, pool, input and output token accounts, input and output vaults
+        let swap_accounts = |input: [IxAccount; 2], output: [IxAccount; 2]| {
+            let [input_acc, input_vault] = input;
+            let [output_acc, output_vault] = output;
+            vec![
+                account(&trader, (0, None), (0, None), &mint),
+                account(&Pubkey::new_unique(), (0, None), (0, None), &mint),
+                account(&pool, (0, None), (0, None), &mint),
+                input_acc,
+                output_acc,
+                input_vault,
+                output_vault,
+            ]
+        };
+
+        // token 0 is WSOL, a zero for one swap pays SOL in: a buy. The vaults of the new pool
+        // are oriented by the direction.
+        let mut pools = DexPoolCache::detached([]);
+        let accounts = swap_accounts(
+            [
+                token_account(&user_sol, &WSOL_MINT, 9, (3_000_000_000, 1_000_000_000)),
+                token_account(&sol_vault, &WSOL_MINT, 9, (8_000_000_000, 10_000_000_000)),
+            ],
+            [
+                token_account(&user_token, &mint, 6, (0, 4_000_000)),
+                token_account(&token_vault, &mint, 6, (24_000_000, 20_000_000)),
+            ],
+        );
+        let buy = TradeRecord::from_raydium_clmm_swap(
+            meta(),
+            event(true, 2_000_000_000, 4_000_000),
+            &accounts,
+            &mut pools,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(buy.is_buy);
+        assert_eq!(
+            (buy.mint, buy.decimals, buy.quote_mint),
+            (mint, 6, WSOL_MINT)
+        );
+        assert_eq!((buy.sol_amt, buy.token_amt), (2_000_000_000, 4_000_000));
+        assert_eq!(
+            (buy.pool_sol_amt, buy.pool_token_amt),
+            (10_000_000_000, 20_000_000)
+        );
+        assert_eq!(
+            (buy.trader_sol_delta, buy.trader_token_delta),
+            (-2_000_000_000, 4_000_000)
+        );
+        assert_eq!(buy.price_sol, 0.5);
+        let record = pools.get(&pool).await.unwrap().unwrap();
+        assert_eq!((record.mint_a, record.mint_b), (WSOL_MINT, mint));
+
+        // one for zero pays the token in: a sell, the vaults swap places
+        let accounts = swap_accounts(
+            [
+                token_account(&user_token, &mint, 6, (4_000_000, 0)),
+                token_account(&token_vault, &mint, 6, (20_000_000, 24_000_000)),
+            ],
+            [
+                token_account(&user_sol, &WSOL_MINT, 9, (1_000_000_000, 2_900_000_000)),
+                token_account(&sol_vault, &WSOL_MINT, 9, (10_000_000_000, 8_100_000_000)),
+            ],
+        );
+        let sell = TradeRecord::from_raydium_clmm_swap(
+            meta(),
+            event(false, 1_900_000_000, 4_000_000),
+            &accounts,
+            &mut pools,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(!sell.is_buy);
+        assert_eq!((sell.sol_amt, sell.token_amt), (1_900_000_000, 4_000_000));
+        assert_eq!(
+            (sell.pool_sol_amt, sell.pool_token_amt),
+            (8_100_000_000, 24_000_000)
+        );
+        assert_eq!(
+            (sell.trader_sol_delta, sell.trader_token_delta),
+            (1_900_000_000, -4_000_000)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_meteora_dbc_swap() {
+        use crate::meteora::dbc::event::{
+            MeteoraDbcSwapParameters, MeteoraDbcSwapResult, TRADE_DIRECTION_QUOTE_TO_BASE,
+        };
+
+        let (trader, pool, mint) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (base_vault, quote_vault) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (user_sol, user_token) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let event =
+            |direction: u8, amount_in: u64, actual_input: u64, output: u64| MeteoraDbcSwapEvent {
+                pool,
+                config: Pubkey::new_unique(),
+                trade_direction: direction,
+                has_referral: false,
+                params: MeteoraDbcSwapParameters {
+                    amount_in,
+                    minimum_amount_out: 0,
+                },
+                swap_result: MeteoraDbcSwapResult {
+                    actual_input_amount: actual_input,
+                    output_amount: output,
+                    next_sqrt_price: 0,
+                    trading_fee: amount_in - actual_input,
+                    protocol_fee: 0,
+                    referral_fee: 0,
+                },
+                amount_in,
+                current_timestamp: 0,
+            };
+        // pool authority, config, pool, input and output token accounts, base and quote vaults,
+        // base and quote mints, payer
+        let swap_accounts = |input: IxAccount, output: IxAccount, vaults: [(u64, u64); 2]| {
+            let none = |pubkey: &Pubkey| account(pubkey, (0, None), (0, None), &mint);
+            vec![
+                none(&Pubkey::new_unique()),
+                none(&Pubkey::new_unique()),
+                none(&pool),
+                input,
+                output,
+                token_account(&base_vault, &mint, 6, vaults[0]),
+                token_account(&quote_vault, &WSOL_MINT, 9, vaults[1]),
+                none(&mint),
+                none(&WSOL_MINT),
+                none(&trader),
+            ]
+        };
+
+        // quote to base is a buy, the fees taken from the input are left out of the amount
+        let mut pools = DexPoolCache::detached([]);
+        let accounts = swap_accounts(
+            token_account(&user_sol, &WSOL_MINT, 9, (2_000_000_000, 990_000_000)),
+            token_account(&user_token, &mint, 6, (0, 5_000_000)),
+            [(105_000_000, 100_000_000), (3_000_000_000, 4_010_000_000)],
+        );
+        let buy = TradeRecord::from_meteora_dbc_swap(
+            meta(),
+            event(
+                TRADE_DIRECTION_QUOTE_TO_BASE,
+                1_010_000_000,
+                1_000_000_000,
+                5_000_000,
+            ),
+            &accounts,
+            &mut pools,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(buy.is_buy);
+        assert_eq!(
+            (buy.trader, buy.mint, buy.quote_mint),
+            (trader, mint, WSOL_MINT)
+        );
+        assert_eq!((buy.sol_amt, buy.token_amt), (1_000_000_000, 5_000_000));
+        assert_eq!(
+            (buy.pool_sol_amt, buy.pool_token_amt),
+            (4_010_000_000, 100_000_000)
+        );
+        assert_eq!(
+            (buy.trader_sol_delta, buy.trader_token_delta),
+            (-1_010_000_000, 5_000_000)
+        );
+        assert_eq!(buy.price_sol, 0.2);
+
+        // base to quote is a sell, the vaults keep their places
+        let accounts = swap_accounts(
+            token_account(&user_token, &mint, 6, (5_000_000, 0)),
+            token_account(&user_sol, &WSOL_MINT, 9, (990_000_000, 1_940_000_000)),
+            [(100_000_000, 105_000_000), (4_010_000_000, 3_060_000_000)],
+        );
+        let sell = TradeRecord::from_meteora_dbc_swap(
+            meta(),
+            event(0, 5_000_000, 5_000_000, 950_000_000),
+            &accounts,
+            &mut pools,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(!sell.is_buy);
+        assert_eq!((sell.sol_amt, sell.token_amt), (950_000_000, 5_000_000));
+        assert_eq!(
+            (sell.pool_sol_amt, sell.pool_token_amt),
+            (3_060_000_000, 105_000_000)
+        );
+        assert_eq!(
+            (sell.trader_sol_delta, sell.trader_token_delta),
+            (950_000_000, -5_000_000)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_moonshot_trade() {
+        use crate::moonshot::instruction::MoonshotTrade;
+
+        let (trader, curve, mint) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (user_token, curve_token) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let args = |token_amount: u64, collateral_amount: u64| MoonshotTrade {
+            token_amount,
+            collateral_amount,
+            fixed_side: 0,
+            slippage_bps: 100,
+        };
+        // sender, sender token account, curve, curve token account, dex fee, helio fee, mint
+        let trade_accounts = |trader_sol: (u64, u64),
+                              user: (u64, u64),
+                              curve_sol: (u64, u64),
+                              curve_tokens: IxAccount| {
+            let none = |pubkey: &Pubkey| account(pubkey, (0, None), (0, None), &mint);
+            vec![
+                account(&trader, (trader_sol.0, None), (trader_sol.1, None), &mint),
+                token_account(&user_token, &mint, 9, user),
+                account(&curve, (curve_sol.0, None), (curve_sol.1, None), &mint),
+                curve_tokens,
+                none(&Pubkey::new_unique()),
+                none(&Pubkey::new_unique()),
+                none(&mint),
+            ]
+        };
+
+        // the amounts are the curve balance changes, not the slippage bounded args
+        let mut pools = DexPoolCache::detached([]);
+        let accounts = trade_accounts(
+            (5_000_000_000, 3_990_000_000),
+            (0, 2_000_000_000),
+            (10_000_000_000, 11_000_000_000),
+            token_account(&curve_token, &mint, 9, (800_000_000_000, 798_000_000_000)),
+        );
+        let buy = TradeRecord::from_moonshot_trade(
+            meta(),
+            true,
+            args(2_000_000_000, 1_100_000_000),
+            &accounts,
+            &mut pools,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(buy.is_buy);
+        assert_eq!((buy.trader, buy.mint, buy.pool), (trader, mint, curve));
+        assert_eq!((buy.sol_amt, buy.token_amt), (1_000_000_000, 2_000_000_000));
+        assert_eq!(
+            (buy.pool_sol_amt, buy.pool_token_amt),
+            (11_000_000_000, 798_000_000_000)
+        );
+        assert_eq!(
+            (buy.trader_sol_delta, buy.trader_token_delta),
+            (-1_010_000_000, 2_000_000_000)
+        );
+        assert_eq!(buy.price_sol, 0.5);
+
+        let accounts = trade_accounts(
+            (3_990_000_000, 4_980_000_000),
+            (2_000_000_000, 0),
+            (11_000_000_000, 10_000_000_000),
+            token_account(&curve_token, &mint, 9, (798_000_000_000, 800_000_000_000)),
+        );
+        let sell = TradeRecord::from_moonshot_trade(
+            meta(),
+            false,
+            args(2_000_000_000, 900_000_000),
+            &accounts,
+            &mut pools,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(!sell.is_buy);
+        assert_eq!(
+            (sell.sol_amt, sell.token_amt),
+            (1_000_000_000, 2_000_000_000)
+        );
+        assert_eq!(
+            (sell.trader_sol_delta, sell.trader_token_delta),
+            (990_000_000, -2_000_000_000)
+        );
+
+        // the dev buy of a curve created by the same transaction takes the args
+        let mut pools = DexPoolCache::detached([]);
+        let mut curve_tokens = token_account(&curve_token, &mint, 9, (0, 798_000_000_000));
+        curve_tokens.pre_amt.token = None;
+        let accounts = trade_accounts(
+            (5_000_000_000, 3_980_000_000),
+            (0, 2_000_000_000),
+            (0, 1_002_000_000),
+            curve_tokens,
+        );
+        let dev_buy = TradeRecord::from_moonshot_trade(
+            meta(),
+            true,
+            args(2_000_000_000, 1_000_000_000),
+            &accounts,
+            &mut pools,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            (dev_buy.sol_amt, dev_buy.token_amt),
+            (1_000_000_000, 2_000_000_000)
+        );
+        assert_eq!(dev_buy.pool_token_amt, 798_000_000_000);
+    }
+
+    #[test]
+    fn test_trader_deltas() {
+        let trader = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let accounts = vec![
+            account(&trader, (100, None), (95, None), &mint),
+            account(
+                &Pubkey::new_unique(),
+                (0, Some(50)),
+                (0, Some(10)),
+                &WSOL_MINT,
+            ),
+            account(&Pubkey::new_unique(), (0, None), (0, Some(300)), &mint),
+            // pool vault
+            account(
+                &Pubkey::new_unique(),
+                (0, Some(1000)),
+                (0, Some(700)),
+                &mint,
+            ),
+        ];
+        assert_eq!(
+            trader_deltas(&accounts, &trader, &[1, 2], &mint, "tx", true),
+            (-45, 300)
+        );
+        assert_eq!(
+            trader_deltas(&accounts, &trader, &[9], &mint, "tx", true),
+            (-5, 0)
+        );
+
+        // malformed balances are left out instead of wrapping
+        let accounts = vec![
+            account(&trader, (u64::MAX, None), (0, None), &mint),
+            account(
+                &Pubkey::new_unique(),
+                (0, Some(0)),
+                (0, Some(u64::MAX)),
+                &mint,
+            ),
+            account(&Pubkey::new_unique(), (0, Some(10)), (0, Some(40)), &mint),
+        ];
+        assert_eq!(
+            trader_deltas(&accounts, &trader, &[1, 2], &mint, "tx", true),
+            (0, 30)
+        );
+    }
+
+    #[test]
+    fn test_trade_prices() {
+        let prices = TradePrices::new(WSOL_MINT, 9, 1_000_000_000, 2_000_000, 6).unwrap();
+        assert_eq!((prices.quote, prices.sol), (0.5, 0.5));
+        assert_eq!(prices.sol_decimal, "0.5");
+        assert_eq!(prices.lamports(7), 7);
+
+        let usdc = crate::common::USDC_MINT;
+        let prices = TradePrices::new(usdc, 6, 3_000_000, 2_000_000, 6).unwrap();
+        assert_eq!((prices.quote, prices.sol), (1.5, 0.0));
+        assert!(prices.sol_decimal.is_empty());
+        assert_eq!(prices.lamports(3_000_000), 0);
+
+        assert!(TradePrices::new(usdc, 6, 0, 2_000_000, 6).is_none());
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redis::{AsyncCommands, aio::MultiplexedConnection};
+use solana_sdk::pubkey::Pubkey;
+
+use super::{DexEvent, TradeRecord};
+
+/// mints with recorded early buyers, scored by their first buy
+const CLUSTER_MINTS_KEY: &str = "zset:cluster_mints";
+/// cluster id of each clustered wallet
+const WALLET_CLUSTER_KEY: &str = "hash:wallet_cluster";
+/// the clusters being saved, renamed over `WALLET_CLUSTER_KEY` once complete
+const WALLET_CLUSTER_NEXT_KEY: &str = "hash:wallet_cluster:next";
+const WALLET_CLUSTER_SAVE_CHUNK: usize = 1_000;
+
+fn early_buyers_key(mint: &Pubkey) -> String {
+    format!("zset:early_buyers:{mint}")
+}
+
+/// Record the first `max_buyers` buyers of each mint seen by the hub, scored by their first
+/// buy, for `ttl_secs`
+pub async fn record_early_buyers(
+    conn: &mut MultiplexedConnection,
+    trades: &[&TradeRecord],
+    max_buyers: usize,
+    ttl_secs: u64,
+) -> Result<()> {
+    let mut mints = HashSet::new();
+    let mut pipe = redis::pipe();
+    for trade in trades.iter().filter(|it| it.is_buy) {
+        let ts = trade.blk_ts.timestamp();
+        pipe.cmd("ZADD")
+            .arg(early_buyers_key(&trade.mint))
+            .arg("NX")
+            .arg(ts)
+            .arg(trade.trader.to_string())
+            .ignore();
+        pipe.cmd("ZADD")
+            .arg(CLUSTER_MINTS_KEY)
+            .arg("NX")
+            .arg(ts)
+            .arg(trade.mint.to_string())
+            .ignore();
+        mints.insert(trade.mint);
+    }
+    if mints.is_empty() {
+        return Ok(());
+    }
+    for mint in mints {
+        let key = early_buyers_key(&mint);
+        // the earliest are kept
+        pipe.zremrangebyrank(&key, max_buyers as isize, -1).ignore();
+        pipe.expire(&key, ttl_secs as i64).ignore();
+    }
+    let _: () = pipe.query_async(conn).await?;
+    Ok(())
+}
+
+/// Mints first bought since `since`, the older ones are forgotten
+pub async fn cluster_mints(
+    conn: &mut MultiplexedConnection,
+    since: DateTime<Utc>,
+) -> Result<Vec<Pubkey>> {
+    let _: () = conn
+        .zrembyscore(CLUSTER_MINTS_KEY, "-inf", format!("({}", since.timestamp()))
+        .await?;
+    let mints: Vec<String> = conn.zrange(CLUSTER_MINTS_KEY, 0, -1).await?;
+    Ok(mints.iter().filter_map(|it| it.parse().ok()).collect())
+}
+
+/// The early buyers of each mint with the timestamp of their first buy, in the order of `mints`
+pub async fn early_buyers(
+    conn: &mut MultiplexedConnection,
+    mints: &[Pubkey],
+) -> Result<Vec<Vec<(Pubkey, i64)>>> {
+    if mints.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut pipe = redis::pipe();
+    for mint in mints {
+        pipe.zrange_withscores(early_buyers_key(mint), 0, -1);
+    }
+    let buyers: Vec<Vec<(String, i64)>> = pipe.query_async(conn).await?;
+    Ok(buyers
+        .into_iter()
+        .map(|buyers| {
+            buyers
+                .into_iter()
+                .filter_map(|(wallet, ts)| Some((wallet.parse().ok()?, ts)))
+                .collect()
+        })
+        .collect())
+}
+
+/// Replace the saved clusters, the wallets left out are no longer clustered
+pub async fn save_wallet_clusters(
+    conn: &mut MultiplexedConnection,
+    clusters: &HashMap<Pubkey, String>,
+) -> Result<()> {
+    if clusters.is_empty() {
+        let _: () = conn.del(WALLET_CLUSTER_KEY).await?;
+        return Ok(());
+    }
+    let _: () = conn.del(WALLET_CLUSTER_NEXT_KEY).await?;
+    let clusters: Vec<_> = clusters
+        .iter()
+        .map(|(wallet, cluster_id)| (wallet.to_string(), cluster_id.as_str()))
+        .collect();
+    for chunk in clusters.chunks(WALLET_CLUSTER_SAVE_CHUNK) {
+        let _: () = conn.hset_multiple(WALLET_CLUSTER_NEXT_KEY, chunk).await?;
+    }
+    let _: () = conn
+        .rename(WALLET_CLUSTER_NEXT_KEY, WALLET_CLUSTER_KEY)
+        .await?;
+    Ok(())
+}
+
+/// Set `cluster_id` of the trades of clustered traders
+pub async fn annotate_wallet_clusters(
+    conn: &mut MultiplexedConnection,
+    events: &mut [DexEvent],
+) -> Result<()> {
+    let traders: Vec<_> = events
+        .iter()
+        .filter_map(|it| match it {
+            DexEvent::Trade(trade) => Some(trade.trader.to_string()),
+            _ => None,
+        })
+        .collect();
+    if traders.is_empty() {
+        return Ok(());
+    }
+    let cluster_ids: Vec<Option<String>> = redis::cmd("HMGET")
+        .arg(WALLET_CLUSTER_KEY)
+        .arg(traders)
+        .query_async(conn)
+        .await?;
+    let trades = events.iter_mut().filter_map(|it| match it {
+        DexEvent::Trade(trade) => Some(trade),
+        _ => None,
+    });
+    for (trade, cluster_id) in trades.zip(cluster_ids) {
+        trade.cluster_id = cluster_id;
+    }
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc, serde::ts_milliseconds};
+use redis::{AsyncCommands, aio::MultiplexedConnection};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+
+/// hash of watchlist name -> watchlist json
+const WATCHLISTS_KEY: &str = "hash:watchlists";
+
+/// Named set of mints and wallets, referenced as `watchlist:{name}` by the websocket
+/// channels and the rules
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Watchlist {
+    pub name: String,
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default)]
+    pub mints: Vec<Pubkey>,
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default)]
+    pub wallets: Vec<Pubkey>,
+    #[serde(with = "ts_milliseconds")]
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Create or replace the watchlist of the name
+pub async fn save_watchlist(conn: &mut MultiplexedConnection, watchlist: &Watchlist) -> Result<()> {
+    let _: () = conn
+        .hset(
+            WATCHLISTS_KEY,
+            &watchlist.name,
+            serde_json::to_string(watchlist)?,
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn get_watchlist(
+    conn: &mut MultiplexedConnection,
+    name: &str,
+) -> Result<Option<Watchlist>> {
+    let watchlist: Option<String> = conn.hget(WATCHLISTS_KEY, name).await?;
+    let watchlist = watchlist.map(|it| serde_json::from_str(&it)).transpose()?;
+    Ok(watchlist)
+}
+
+/// Every watchlist, by name
+pub async fn list_watchlists(conn: &mut MultiplexedConnection) -> Result<Vec<Watchlist>> {
+    let watchlists: Vec<String> = conn.hvals(WATCHLISTS_KEY).await?;
+    let mut watchlists = watchlists
+        .iter()
+        .map(|it| serde_json::from_str(it))
+        .collect::<Result<Vec<Watchlist>, _>>()?;
+    watchlists.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(watchlists)
+}
+
+pub async fn delete_watchlist(conn: &mut MultiplexedConnection, name: &str) -> Result<bool> {
+    let deleted: u64 = conn.hdel(WATCHLISTS_KEY, name).await?;
+    Ok(deleted > 0)
+}
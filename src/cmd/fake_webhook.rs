@@ -7,26 +7,14 @@ use axum::{Router, extract::DefaultBodyLimit, routing::post};
 use tokio::net::TcpListener;
 use tower_http::{decompression::RequestDecompressionLayer, trace::TraceLayer};
 use tracing::info;
-use tracing_subscriber::{EnvFilter, Registry, fmt::Layer, layer::SubscriberExt};
-
-#[tokio::main]
-async fn main() -> Result<()> {
-    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-    let subscriber = Registry::default().with(env_filter).with(
-        Layer::default()
-            .with_writer(std::io::stdout)
-            .with_ansi(false),
-    );
-
-    tracing::subscriber::set_global_default(subscriber)?;
 
+pub async fn run(listen_on: &str) -> Result<()> {
     let app = Router::new()
         .route("/webhook", post(webhook))
         .layer(DefaultBodyLimit::max(1024 * 1024 * 300))
         .layer(TraceLayer::new_for_http())
         .layer(RequestDecompressionLayer::new());
 
-    let listen_on = "0.0.0.0:9999";
     let listener = TcpListener::bind(listen_on).await?;
 
     info!("fake webhook server started, listen on: {}", listen_on);
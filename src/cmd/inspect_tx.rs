@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+
+use crate::{
+    cache::DexPoolCache, common, config::AppConfig, qn_req_processor, rpc_provider::RpcProvider,
+};
+
+/// Fetch a transaction from rpc, parse it like a quicknode stream transaction
+/// and print the dex events, or the reason why no event was produced.
+pub async fn run(config: AppConfig, signature: String) -> Result<()> {
+    common::accept_quote_mints(&config.quote_mints);
+    let rpc_client = RpcClient::new_with_timeout_and_commitment(
+        config.sol_rpc_url.clone(),
+        Duration::from_secs(30),
+        CommitmentConfig::confirmed(),
+    );
+    let rpc_tx = RpcProvider::get_transaction(&rpc_client, &signature).await?;
+    let rpc_tx = rpc_tx.ok_or_else(|| anyhow!("transaction {signature} not found"))?;
+    if let Some(err) = rpc_tx.tx.tx_err() {
+        println!("transaction failed with error: {err}, no dex event produced");
+        return Ok(());
+    }
+
+    let tx = rpc_tx.into_tx()?;
+    if tx.logs.is_empty() {
+        println!("no event log of supported dex programs found in transaction");
+        return Ok(());
+    }
+    let logs = tx.logs.clone();
+    let program_ids: Vec<_> = tx.ixs.iter().map(|it| it.program_id.clone()).collect();
+
+    let redis_client = redis::Client::open(config.redis_url.as_str())?;
+    let conn = redis_client.get_multiplexed_async_connection().await?;
+    let mut pools = DexPoolCache::new(conn);
+    pools.prefetch(tx.pool_addrs()).await?;
+    let outcome = qn_req_processor::parse_tx(&tx, &mut pools).await?;
+    pools.flush().await?;
+
+    println!("{} dex events produced", outcome.events.len());
+    for evt in &outcome.events {
+        println!("{}", serde_json::to_string_pretty(evt)?);
+    }
+    for (idx, reason) in &outcome.skipped {
+        println!(
+            "log #{idx} of program {} produced no event: {reason}\n  log: {}",
+            program_ids
+                .get(*idx)
+                .map(|it| it.as_str())
+                .unwrap_or("unknown"),
+            logs[*idx]
+        );
+    }
+
+    Ok(())
+}
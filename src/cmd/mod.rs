@@ -0,0 +1,5 @@
+pub mod fake_webhook;
+pub mod inspect_tx;
+pub mod qn_filter;
+pub mod replay;
+pub mod serve;
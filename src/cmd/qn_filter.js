@@ -0,0 +1,223 @@
+// QuickNode Streams filter for sol-dex-hub, generated by `sol-dex-hub qn-filter`.
+// Regenerate it instead of editing by hand, so it keeps matching the hub's parser.
+const DEX_PROGRAMS = __DEX_PROGRAMS__;
+const ANCHOR_EVENT_IX_TAG = __ANCHOR_EVENT_IX_TAG__;
+// emit the failed transactions with their dex instructions, for `failed_swaps` of the hub
+const KEEP_FAILED = __KEEP_FAILED__;
+const COMPUTE_BUDGET_PROGRAM_ID = "ComputeBudget111111111111111111111111111111";
+const TOKEN_PROGRAM_IDS = [
+  "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+  "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb",
+];
+const BS58_ALPHABET = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+function bs58Decode(str) {
+  const bytes = [];
+  for (const char of str) {
+    let carry = BS58_ALPHABET.indexOf(char);
+    if (carry < 0) return [];
+    for (let i = 0; i < bytes.length; i++) {
+      carry += bytes[i] * 58;
+      bytes[i] = carry & 0xff;
+      carry >>= 8;
+    }
+    while (carry > 0) {
+      bytes.push(carry & 0xff);
+      carry >>= 8;
+    }
+  }
+  for (const char of str) {
+    if (char !== "1") break;
+    bytes.push(0);
+  }
+  return bytes.reverse();
+}
+
+function isAnchorEvent(data) {
+  const bytes = bs58Decode(data);
+  return ANCHOR_EVENT_IX_TAG.every((b, i) => bytes[i] === b);
+}
+
+// group `Program log:` and `Program data:` lines by the flattened instruction which emitted them
+function splitLogsByInvocation(logMessages, ixsLen) {
+  const result = Array.from({ length: ixsLen }, () => []);
+  const stack = [];
+  let invokeCount = 0;
+  for (const log of logMessages || []) {
+    if (log.startsWith("Program log: ") || log.startsWith("Program data: ")) {
+      const top = stack[stack.length - 1];
+      if (top !== undefined && top < ixsLen) result[top].push(log);
+      continue;
+    }
+    const parts = log.split(" ");
+    if (parts[0] !== "Program" || parts.length < 3) continue;
+    if (parts[2] === "invoke") {
+      stack.push(invokeCount);
+      invokeCount += 1;
+    } else if (parts[2] === "success" || parts[2] === "failed:") {
+      stack.pop();
+    }
+  }
+  return result;
+}
+
+function readLe(bytes, start, len) {
+  let value = 0;
+  for (let i = len - 1; i >= 0; i--) value = value * 256 + bytes[start + i];
+  return value;
+}
+
+// a u64 as a decimal string, numbers lose the precision past 2^53
+function readU64(bytes, start) {
+  let value = 0n;
+  for (let i = 7; i >= 0; i--) value = value * 256n + BigInt(bytes[start + i]);
+  return value.toString();
+}
+
+// the compute unit limit and price set by the top level instructions, the last one of a kind wins
+function computeBudget(instructions, accountKeys) {
+  const budget = {};
+  for (const ix of instructions) {
+    if (accountKeys[ix.programIdIndex] !== COMPUTE_BUDGET_PROGRAM_ID) continue;
+    const bytes = bs58Decode(ix.data);
+    if (bytes[0] === 2 && bytes.length >= 5) budget.cuLimit = readLe(bytes, 1, 4);
+    if (bytes[0] === 3 && bytes.length >= 9) budget.cuPrice = readLe(bytes, 1, 8);
+  }
+  return Object.keys(budget).length === 0 ? undefined : budget;
+}
+
+function amt(balances, tokenBalances, accountIdx) {
+  const token = (tokenBalances || []).find((it) => it.accountIndex === accountIdx);
+  return {
+    sol: balances[accountIdx] || 0,
+    token: token
+      ? { mint: token.mint, decimals: token.uiTokenAmount.decimals, amt: token.uiTokenAmount.amount }
+      : null,
+  };
+}
+
+function invocation(flatIxs, flatIdx, programId, accountKeys, meta) {
+  const [index, ix] = flatIxs[flatIdx];
+  return {
+    programId,
+    instruction: {
+      accounts: ix.accounts.map((accountIdx) => ({
+        pubkey: accountKeys[accountIdx],
+        preAmt: amt(meta.preBalances, meta.preTokenBalances, accountIdx),
+        postAmt: amt(meta.postBalances, meta.postTokenBalances, accountIdx),
+      })),
+      data: ix.data,
+      index,
+    },
+  };
+}
+
+// the token transfers invoked by the instruction at `flatIdx`, the ones after it up to the next
+// top level instruction or the next instruction of its program
+function tokenTransfers(flatIxs, flatIdx, accountKeys) {
+  const [index, ix] = flatIxs[flatIdx];
+  const transfers = [];
+  for (const [itIndex, it] of flatIxs.slice(flatIdx + 1)) {
+    if (itIndex !== index || it.programIdIndex === ix.programIdIndex) break;
+    if (!TOKEN_PROGRAM_IDS.includes(accountKeys[it.programIdIndex])) continue;
+    const bytes = bs58Decode(it.data);
+    if (bytes.length < 9) continue;
+    // transfer: source, destination, authority, transferChecked: source, mint, destination, authority
+    const destination = { 3: it.accounts[1], 12: it.accounts[2] }[bytes[0]];
+    if (destination === undefined) continue;
+    transfers.push({
+      source: accountKeys[it.accounts[0]],
+      destination: accountKeys[destination],
+      amt: readU64(bytes, 1),
+    });
+  }
+  return transfers;
+}
+
+function convertTx(tx, slot, blkTs) {
+  const meta = tx.meta;
+  const message = tx.transaction.message;
+  const loaded = meta.loadedAddresses || { writable: [], readonly: [] };
+  const accountKeys = message.accountKeys.concat(loaded.writable, loaded.readonly);
+
+  // flatten instructions in execution order, with their top level instruction index
+  const innerIxs = {};
+  for (const inner of meta.innerInstructions || []) innerIxs[inner.index] = inner.instructions;
+  const flatIxs = [];
+  message.instructions.forEach((ix, idx) => {
+    flatIxs.push([idx, ix]);
+    for (const innerIx of innerIxs[idx] || []) flatIxs.push([idx, innerIx]);
+  });
+  const signature = tx.transaction.signatures[0];
+  const budget = computeBudget(message.instructions, accountKeys);
+
+  // failed transactions log no event, the hub decodes the swaps from the dex instructions
+  if (meta.err) {
+    const ixs = [];
+    flatIxs.forEach(([, ix], flatIdx) => {
+      const programId = accountKeys[ix.programIdIndex];
+      if (DEX_PROGRAMS[programId]) ixs.push(invocation(flatIxs, flatIdx, programId, accountKeys, meta));
+    });
+    if (ixs.length === 0) return null;
+    return { blkTs, slot, signature, logs: [], ixs, err: meta.err, computeBudget: budget };
+  }
+
+  const ixLogs = splitLogsByInvocation(meta.logMessages, flatIxs.length);
+
+  const ixs = [];
+  const logs = [];
+  // flattened instruction index of the latest invocation of each anchor program
+  const latestInvocations = {};
+  flatIxs.forEach(([, ix], flatIdx) => {
+    const programId = accountKeys[ix.programIdIndex];
+    const dex = DEX_PROGRAMS[programId];
+    if (!dex) return;
+
+    if (dex.source === "instruction") {
+      const invoked = invocation(flatIxs, flatIdx, programId, accountKeys, meta);
+      invoked.instruction.transfers = tokenTransfers(flatIxs, flatIdx, accountKeys);
+      ixs.push(invoked);
+      logs.push(dex.logPrefix + ix.data);
+      return;
+    }
+
+    if (dex.source === "anchor_cpi") {
+      if (!isAnchorEvent(ix.data)) {
+        latestInvocations[programId] = flatIdx;
+        return;
+      }
+      const invocationIdx = latestInvocations[programId];
+      if (invocationIdx === undefined) return;
+      ixs.push(invocation(flatIxs, invocationIdx, programId, accountKeys, meta));
+      logs.push(dex.logPrefix + ix.data);
+      return;
+    }
+
+    const linePrefix = dex.source === "ray_log" ? "Program log: ray_log: " : "Program data: ";
+    for (const log of ixLogs[flatIdx]) {
+      if (!log.startsWith(linePrefix)) continue;
+      ixs.push(invocation(flatIxs, flatIdx, programId, accountKeys, meta));
+      logs.push(dex.logPrefix + log.slice(linePrefix.length));
+    }
+  });
+
+  if (ixs.length === 0) return null;
+  return { blkTs, slot, signature, logs, ixs, computeBudget: budget };
+}
+
+function main(stream) {
+  const txs = [];
+  for (const block of stream.data || []) {
+    if (!block) continue;
+    // fall back to parent slot + 1 when the dataset has no slot field
+    const slot = block.slot !== undefined ? block.slot : block.parentSlot + 1;
+    for (const tx of block.transactions || []) {
+      if (!tx.meta || (tx.meta.err && !KEEP_FAILED)) continue;
+      const converted = convertTx(tx, slot, block.blockTime);
+      if (converted) txs.push(converted);
+    }
+  }
+  if (txs.length === 0) return null;
+  // the hub only accepts bodies with `metadata` near the start
+  return { metadata: stream.metadata, txs };
+}
@@ -0,0 +1,71 @@
+use anyhow::Result;
+use serde_json::json;
+use strum::IntoEnumIterator;
+
+use crate::{
+    common::{Dex, DexEventSource},
+    rpc_tx::ANCHOR_EVENT_IX_TAG,
+};
+
+const FILTER_TEMPLATE: &str = include_str!("qn_filter.js");
+
+/// Generate the quicknode streams filter function for the given dexes, all
+/// supported dexes when empty. The failed transactions are kept with `keep_failed`.
+pub fn generate(dexes: &[Dex], keep_failed: bool) -> Result<String> {
+    let dexes: Vec<_> = if dexes.is_empty() {
+        Dex::iter().collect()
+    } else {
+        dexes.to_vec()
+    };
+
+    let mut programs = serde_json::Map::new();
+    for dex in dexes {
+        let source = match dex.event_source() {
+            DexEventSource::RayLog => "ray_log",
+            DexEventSource::ProgramData => "program_data",
+            DexEventSource::AnchorCpi => "anchor_cpi",
+            DexEventSource::Instruction => "instruction",
+        };
+        programs.insert(
+            dex.program_id().to_string(),
+            json!({
+                "dex": dex.to_string(),
+                "source": source,
+                "logPrefix": dex.log_prefix(),
+            }),
+        );
+    }
+
+    let filter = FILTER_TEMPLATE
+        .replace(
+            "__DEX_PROGRAMS__",
+            &serde_json::to_string_pretty(&programs)?,
+        )
+        .replace(
+            "__ANCHOR_EVENT_IX_TAG__",
+            &serde_json::to_string(&ANCHOR_EVENT_IX_TAG)?,
+        )
+        .replace("__KEEP_FAILED__", &keep_failed.to_string());
+    Ok(filter)
+}
+
+pub fn run(dexes: &[Dex], keep_failed: bool) -> Result<()> {
+    println!("{}", generate(dexes, keep_failed)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_filter() {
+        let filter = generate(&[Dex::Pumpfun], false).unwrap();
+        assert!(filter.contains(&Dex::Pumpfun.program_id().to_string()));
+        assert!(!filter.contains(&Dex::RaydiumAmm.program_id().to_string()));
+        assert!(!filter.contains("__DEX_PROGRAMS__"));
+        assert!(filter.contains("const KEEP_FAILED = false;"));
+        let filter = generate(&[Dex::Pumpfun], true).unwrap();
+        assert!(filter.contains("const KEEP_FAILED = true;"));
+    }
+}
@@ -0,0 +1,33 @@
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::{Result, anyhow};
+use tokio::fs;
+use tracing::{info, warn};
+
+use crate::{
+    cache::{self, MAX_QN_REQ_LEN},
+    config::AppConfig,
+};
+
+/// Push saved quicknode stream request bodies back into the request queue,
+/// so they are parsed again by a running `serve` process.
+pub async fn run(config: AppConfig, files: Vec<PathBuf>) -> Result<()> {
+    let redis_client = redis::Client::open(config.redis_url.as_str())?;
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+
+    for file in files {
+        let req_body = fs::read_to_string(&file)
+            .await
+            .map_err(|err| anyhow!("read qn request file {} error: {err}", file.display()))?;
+
+        // wait for the processor to drain the queue instead of dropping requests
+        while cache::llen_qn_requests(&mut conn).await? >= MAX_QN_REQ_LEN {
+            warn!("qn request queue is full, wait for processor");
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+        cache::rpush_qn_request(&mut conn, req_body).await?;
+        info!("replayed qn request from: {}", file.display());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,441 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Result, bail};
+use strum::IntoEnumIterator;
+use tracing::{error, info};
+
+use crate::{
+    account_violation_watcher::{self, DEFAULT_ACCOUNT_VIOLATION_ALERT_PER_MIN},
+    bigquery::{BIGQUERY_CONSUMER, BIGQUERY_KINDS, BigQuerySink},
+    cache::{self, DexEventKind},
+    common,
+    config::{AppConfig, DEFAULT_CONSUMER, MQTT_CONSUMER},
+    creator_graph, decimals_checker, inactive_mint_watcher,
+    ingest::backfill::{self, SlotRange},
+    lp_lock_watcher, pressure_watcher,
+    qn_req_processor::{self, PipelineStatus, ProcessorOptions},
+    redis_monitor::{self, DEFAULT_REDIS_MEMORY_ALERT_RATIO},
+    sol_usd_oracle, wallet_cluster, watchlist,
+    web::{self, WebAppContext},
+    webhook::{self, DexEvtWebhook, EventPlugins},
+};
+
+pub async fn run(config: AppConfig, backfill: Option<SlotRange>) -> Result<()> {
+    config.validate().await?;
+    if backfill.is_some() && config.offline {
+        bail!("backfill needs the rpc, it can't run offline");
+    }
+    common::accept_quote_mints(&config.quote_mints);
+    if config.redis_pubsub {
+        cache::enable_event_pubsub(config.naming.redis_pubsub.clone());
+    }
+    let plugins = Arc::new(EventPlugins::load(&config.plugins)?);
+    let context = WebAppContext::init(&config).await?;
+
+    let redis_client = context.redis_client.clone();
+    let processor_options = ProcessorOptions::new(&config);
+    let status = Arc::new(PipelineStatus::default());
+    let processor_status = status.clone();
+    // process quick node stream
+    tokio::spawn(async move {
+        loop {
+            let redis_client = redis_client.clone();
+            let status = processor_status.clone();
+            match qn_req_processor::start(redis_client, &processor_options, status).await {
+                Ok(_) => info!("qn request processor succeeded"),
+                Err(err) => error!("qn reqwest processor error: {err}"),
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    });
+
+    let ws_hub = context.ws_hub.clone();
+    tokio::spawn(async move {
+        loop {
+            match web::publish_status(ws_hub.clone(), status.clone()).await {
+                Ok(_) => info!("ws status broadcast succeeded"),
+                Err(err) => error!("ws status broadcast error: {err}"),
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    });
+
+    let redis_client = context.redis_client.clone();
+    let redis_health = context.redis_health.clone();
+    let ws_hub = context.ws_hub.clone();
+    let alert_ratio = config
+        .redis_memory_alert_ratio
+        .unwrap_or(DEFAULT_REDIS_MEMORY_ALERT_RATIO);
+    tokio::spawn(async move {
+        loop {
+            let redis_client = redis_client.clone();
+            let health = redis_health.clone();
+            match redis_monitor::start(redis_client, health, ws_hub.clone(), alert_ratio).await {
+                Ok(_) => info!("redis monitor succeeded"),
+                Err(err) => error!("redis monitor error: {err}"),
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    });
+
+    let ws_hub = context.ws_hub.clone();
+    let violation_threshold = config
+        .account_violation_alert_per_min
+        .unwrap_or(DEFAULT_ACCOUNT_VIOLATION_ALERT_PER_MIN);
+    tokio::spawn(async move {
+        loop {
+            match account_violation_watcher::start(ws_hub.clone(), violation_threshold).await {
+                Ok(_) => info!("account violation watcher succeeded"),
+                Err(err) => error!("account violation watcher error: {err}"),
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    });
+
+    let redis_client = context.redis_client.clone();
+    let watchlists = context.watchlists.clone();
+    tokio::spawn(async move {
+        loop {
+            let redis_client = redis_client.clone();
+            match watchlist::start_refresh(redis_client, watchlists.clone()).await {
+                Ok(_) => info!("watchlist refresh succeeded"),
+                Err(err) => error!("watchlist refresh error: {err}"),
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    });
+
+    let redis_client = context.redis_client.clone();
+    let usage_meter = context.usage_meter.clone();
+    tokio::spawn(async move {
+        loop {
+            let redis_client = redis_client.clone();
+            match web::start_usage_flush(redis_client, usage_meter.clone()).await {
+                Ok(_) => info!("api usage flush succeeded"),
+                Err(err) => error!("api usage flush error: {err}"),
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    });
+
+    // the mint accounts can't be fetched offline
+    if !config.offline {
+        let redis_client = context.redis_client.clone();
+        let rpc_client = context.sol_rpc_client.clone();
+        tokio::spawn(async move {
+            loop {
+                let redis_client = redis_client.clone();
+                let rpc_client = rpc_client.clone();
+                match decimals_checker::start(redis_client, rpc_client).await {
+                    Ok(_) => info!("pool decimals checker succeeded"),
+                    Err(err) => error!("pool decimals checker error: {err}"),
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        });
+    }
+
+    if let Some(inactive_secs) = config.mint_inactive_secs {
+        let redis_client = context.redis_client.clone();
+        tokio::spawn(async move {
+            loop {
+                let redis_client = redis_client.clone();
+                match inactive_mint_watcher::start(redis_client, inactive_secs).await {
+                    Ok(_) => info!("inactive mint watcher succeeded"),
+                    Err(err) => error!("inactive mint watcher error: {err}"),
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        });
+    }
+
+    if config.first_funder_lookup {
+        let redis_client = context.redis_client.clone();
+        let rpc_client = context.sol_rpc_client.clone();
+        tokio::spawn(async move {
+            loop {
+                let redis_client = redis_client.clone();
+                let rpc_client = rpc_client.clone();
+                match creator_graph::start_funder_warmer(redis_client, rpc_client).await {
+                    Ok(_) => info!("first funder warmer succeeded"),
+                    Err(err) => error!("first funder warmer error: {err}"),
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        });
+    }
+
+    if let Some(pressure) = config.pressure.clone() {
+        let redis_client = context.redis_client.clone();
+        tokio::spawn(async move {
+            loop {
+                let redis_client = redis_client.clone();
+                match pressure_watcher::start(redis_client, pressure.clone()).await {
+                    Ok(_) => info!("pressure watcher succeeded"),
+                    Err(err) => error!("pressure watcher error: {err}"),
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        });
+    }
+
+    if let Some(sol_usd) = config.sol_usd.clone() {
+        let redis_client = context.redis_client.clone();
+        let rpc_client = context.sol_rpc_client.clone();
+        tokio::spawn(async move {
+            loop {
+                let redis_client = redis_client.clone();
+                let rpc_client = rpc_client.clone();
+                match sol_usd_oracle::start(redis_client, rpc_client, sol_usd.clone()).await {
+                    Ok(_) => info!("sol usd oracle succeeded"),
+                    Err(err) => error!("sol usd oracle error: {err}"),
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        });
+    }
+
+    #[cfg(feature = "yellowstone")]
+    if let Some(yellowstone) = config.yellowstone.clone() {
+        let redis_client = context.redis_client.clone();
+        let failed_swaps = config.failed_swaps;
+        tokio::spawn(async move {
+            loop {
+                let redis_client = redis_client.clone();
+                let config = yellowstone.clone();
+                match crate::ingest::yellowstone::start(redis_client, config, failed_swaps).await {
+                    Ok(_) => info!("yellowstone ingestion succeeded"),
+                    Err(err) => error!("yellowstone ingestion error: {err}"),
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        });
+    }
+
+    #[cfg(feature = "pubsub")]
+    if let Some(rpc_ws) = config.rpc_ws.clone() {
+        let redis_client = context.redis_client.clone();
+        let rpc_client = context.sol_rpc_client.clone();
+        let failed_swaps = config.failed_swaps;
+        tokio::spawn(async move {
+            loop {
+                let redis_client = redis_client.clone();
+                let rpc_client = rpc_client.clone();
+                let config = rpc_ws.clone();
+                match crate::ingest::rpc_ws::start(redis_client, rpc_client, config, failed_swaps)
+                    .await
+                {
+                    Ok(_) => info!("rpc logs ingestion succeeded"),
+                    Err(err) => error!("rpc logs ingestion error: {err}"),
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        });
+    }
+
+    // once, unlike the sources above
+    if let Some(range) = backfill {
+        let redis_client = context.redis_client.clone();
+        let rpc_client = context.sol_rpc_client.clone();
+        let failed_swaps = config.failed_swaps;
+        tokio::spawn(async move {
+            match backfill::start(redis_client, rpc_client, range, failed_swaps).await {
+                Ok(_) => info!("backfill succeeded"),
+                Err(err) => error!("backfill error: {err}"),
+            }
+        });
+    }
+
+    #[cfg(feature = "pubsub")]
+    if let Some(state_tracker) = config.state_tracker.clone() {
+        let redis_client = context.redis_client.clone();
+        let rpc_client = context.sol_rpc_client.clone();
+        tokio::spawn(async move {
+            loop {
+                let redis_client = redis_client.clone();
+                let rpc_client = rpc_client.clone();
+                let config = state_tracker.clone();
+                match crate::state_tracker::start(redis_client, rpc_client, config).await {
+                    Ok(_) => info!("pool state tracker succeeded"),
+                    Err(err) => error!("pool state tracker error: {err}"),
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        });
+    }
+
+    if let Some(clusters) = config.wallet_clusters.clone() {
+        let redis_client = context.redis_client.clone();
+        tokio::spawn(async move {
+            loop {
+                let redis_client = redis_client.clone();
+                match wallet_cluster::start(redis_client, clusters.clone()).await {
+                    Ok(_) => info!("wallet clustering succeeded"),
+                    Err(err) => error!("wallet clustering error: {err}"),
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        });
+    }
+
+    if let Some(lp_lock) = config.lp_lock.clone() {
+        let redis_client = context.redis_client.clone();
+        let rpc_client = context.sol_rpc_client.clone();
+        tokio::spawn(async move {
+            loop {
+                let redis_client = redis_client.clone();
+                let rpc_client = rpc_client.clone();
+                match lp_lock_watcher::start(redis_client, rpc_client, lp_lock.clone()).await {
+                    Ok(_) => info!("lp lock watcher succeeded"),
+                    Err(err) => error!("lp lock watcher error: {err}"),
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        });
+    }
+
+    let http_client = Arc::new(
+        reqwest::ClientBuilder::new()
+            .connect_timeout(Duration::from_millis(200))
+            .timeout(Duration::from_secs(1))
+            .build()?,
+    );
+    let creator_funders = |kind| config.first_funder_lookup && kind == DexEventKind::PoolCreated;
+
+    let consumers = config.routed_consumers();
+    let mut webhooks = vec![];
+    if consumers.is_empty() {
+        let rules = Arc::new(config.rules.clone());
+        for kind in DexEventKind::iter() {
+            webhooks.push(DexEvtWebhook {
+                redis_client: context.redis_client.clone(),
+                http_client: http_client.clone(),
+                endpoint: config.webhook_endpoint.clone(),
+                format: config.webhook_format,
+                fields: config.webhook_fields,
+                rules: rules.clone(),
+                plugins: plugins.clone(),
+                kind,
+                ws_hub: Some(context.ws_hub.clone()),
+                watchlists: context.watchlists.clone(),
+                creator_funders: creator_funders(kind),
+                consumer: None,
+            });
+        }
+    }
+    for consumer in &consumers {
+        let rules = Arc::new(consumer.rules.clone());
+        for kind in DexEventKind::iter().filter(|it| consumer.accepts(*it)) {
+            webhooks.push(DexEvtWebhook {
+                redis_client: context.redis_client.clone(),
+                http_client: http_client.clone(),
+                endpoint: consumer.endpoint.clone(),
+                format: consumer.format,
+                fields: consumer.fields,
+                rules: rules.clone(),
+                plugins: plugins.clone(),
+                kind,
+                // the websocket clients get the events of the default consumer once
+                ws_hub: (consumer.name == DEFAULT_CONSUMER).then(|| context.ws_hub.clone()),
+                watchlists: context.watchlists.clone(),
+                creator_funders: creator_funders(kind),
+                consumer: Some(consumer.name.clone()),
+            });
+        }
+    }
+
+    // the consumers read copies of the shared queues
+    if !consumers.is_empty() {
+        for kind in DexEventKind::iter() {
+            let redis_client = context.redis_client.clone();
+            let mut names: Vec<_> = consumers
+                .iter()
+                .filter(|it| it.accepts(kind))
+                .map(|it| it.name.clone())
+                .collect();
+            if config.bigquery.is_some() && BIGQUERY_KINDS.contains(&kind) {
+                names.push(BIGQUERY_CONSUMER.to_string());
+            }
+            if let Some(mqtt) = &config.mqtt
+                && mqtt.kinds.contains(&kind)
+            {
+                names.push(MQTT_CONSUMER.to_string());
+            }
+            tokio::spawn(async move {
+                loop {
+                    let redis_client = redis_client.clone();
+                    match webhook::start_router(redis_client, kind, names.clone()).await {
+                        Ok(_) => info!("{kind} event router succeeded"),
+                        Err(err) => error!("{kind} event router error: {err}"),
+                    }
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            });
+        }
+    }
+
+    if let Some(bigquery) = &config.bigquery {
+        for kind in BIGQUERY_KINDS {
+            let sink = BigQuerySink {
+                redis_client: context.redis_client.clone(),
+                config: bigquery.clone(),
+                kind,
+            };
+            tokio::spawn(async move {
+                loop {
+                    match sink.start().await {
+                        Ok(_) => info!("{kind} bigquery export succeeded"),
+                        Err(err) => error!("{kind} bigquery export error: {err}"),
+                    }
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            });
+        }
+    }
+
+    #[cfg(feature = "mqtt")]
+    if let Some(mqtt) = &config.mqtt {
+        let (client, eventloop) = crate::mqtt::connect(mqtt)?;
+        tokio::spawn(crate::mqtt::drive(eventloop));
+        for &kind in &mqtt.kinds {
+            let sink = crate::mqtt::MqttSink {
+                redis_client: context.redis_client.clone(),
+                client: client.clone(),
+                topic: config.naming.mqtt.clone(),
+                qos: mqtt.qos,
+                fields: mqtt.fields,
+                kind,
+            };
+            tokio::spawn(async move {
+                loop {
+                    match sink.start().await {
+                        Ok(_) => info!("{kind} mqtt publish succeeded"),
+                        Err(err) => error!("{kind} mqtt publish error: {err}"),
+                    }
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            });
+        }
+    }
+
+    for webhook in webhooks {
+        let kind = webhook.kind;
+        let name = match &webhook.consumer {
+            Some(consumer) => format!("{consumer} {kind}"),
+            None => kind.to_string(),
+        };
+        tokio::spawn(async move {
+            loop {
+                match webhook.start().await {
+                    Ok(_) => info!("{name} webhook processor succeeded"),
+                    Err(err) => error!("{name} webhook processor error: {err}"),
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        });
+    }
+
+    web::start(context, &config.listen_on).await?;
+
+    Ok(())
+}
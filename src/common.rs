@@ -1,18 +1,200 @@
+use std::sync::OnceLock;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use solana_sdk::pubkey;
 use solana_sdk::pubkey::Pubkey;
-use strum::{Display, EnumString};
+use strum::{Display, EnumIter, EnumString, IntoEnumIterator};
+
+use crate::{
+    cache::DexEventKind,
+    meteora::{METEORA_DAMM_PROGRAM_ID, METEORA_DBC_PROGRAM_ID, METEORA_DLMM_PROGRAM_ID},
+    moonshot::MOONSHOT_PROGRAM_ID,
+    orca::ORCA_WHIRLPOOL_PROGRAM_ID,
+    pumpamm::PUMPAMM_PROGRAM_ID,
+    pumpfun::PUMPFUN_PROGRAM_ID,
+    raydium::{RAYDIUM_AMM_PROGRAM_ID, RAYDIUM_CLMM_PROGRAM_ID, RAYDIUM_CPMM_PROGRAM_ID},
+};
 
 pub const WSOL_MINT: Pubkey = pubkey!("So11111111111111111111111111111111111111112");
+pub const USDC_MINT: Pubkey = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+pub const USDT_MINT: Pubkey = pubkey!("Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB");
+
+/// Stablecoins a pool can be quoted in besides WSOL, by priority when a pool pairs two of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuoteMint {
+    Usdc,
+    Usdt,
+}
+
+impl QuoteMint {
+    pub fn mint(&self) -> Pubkey {
+        match self {
+            QuoteMint::Usdc => USDC_MINT,
+            QuoteMint::Usdt => USDT_MINT,
+        }
+    }
+
+    pub fn from_mint(mint: &Pubkey) -> Option<Self> {
+        [QuoteMint::Usdc, QuoteMint::Usdt]
+            .into_iter()
+            .find(|it| it.mint() == *mint)
+    }
+
+    /// both stablecoins have 6 decimals
+    pub fn decimals(&self) -> u8 {
+        6
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            QuoteMint::Usdc => "usdc",
+            QuoteMint::Usdt => "usdt",
+        }
+    }
+}
+
+static QUOTE_MINTS: OnceLock<Vec<Pubkey>> = OnceLock::new();
+
+/// Accept the pools quoted in `quotes` besides the WSOL pairs, set once at the start
+pub fn accept_quote_mints(quotes: &[QuoteMint]) {
+    let mut quotes = quotes.to_vec();
+    quotes.sort();
+    quotes.dedup();
+    let mints = std::iter::once(WSOL_MINT)
+        .chain(quotes.iter().map(QuoteMint::mint))
+        .collect();
+    let _ = QUOTE_MINTS.set(mints);
+}
+
+/// Mints the accepted pools are quoted in by priority, WSOL first
+pub fn quote_mints() -> &'static [Pubkey] {
+    QUOTE_MINTS.get().map_or(&[WSOL_MINT], |it| it.as_slice())
+}
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, Display, EnumString)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Display, EnumString, EnumIter,
+)]
 pub enum Dex {
     RaydiumAmm,
     Pumpfun,
     PumpAmm,
     MeteoraDlmm,
     MeteoraDamm,
+    RaydiumClmm,
+    OrcaWhirlpool,
+    RaydiumCpmm,
+    MeteoraDbc,
+    Moonshot,
+}
+
+/// Where the event of a dex program is found in a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DexEventSource {
+    /// `Program log: ray_log: <base64>` line in log messages
+    RayLog,
+    /// `Program data: <base64>` line in log messages
+    ProgramData,
+    /// anchor `emit_cpi!` self invocation instruction data
+    AnchorCpi,
+    /// the data of every invocation of the program, for programs without events
+    Instruction,
+}
+
+impl Dex {
+    pub fn program_id(&self) -> Pubkey {
+        match self {
+            Dex::RaydiumAmm => RAYDIUM_AMM_PROGRAM_ID,
+            Dex::Pumpfun => PUMPFUN_PROGRAM_ID,
+            Dex::PumpAmm => PUMPAMM_PROGRAM_ID,
+            Dex::MeteoraDlmm => METEORA_DLMM_PROGRAM_ID,
+            Dex::MeteoraDamm => METEORA_DAMM_PROGRAM_ID,
+            Dex::RaydiumClmm => RAYDIUM_CLMM_PROGRAM_ID,
+            Dex::OrcaWhirlpool => ORCA_WHIRLPOOL_PROGRAM_ID,
+            Dex::RaydiumCpmm => RAYDIUM_CPMM_PROGRAM_ID,
+            Dex::MeteoraDbc => METEORA_DBC_PROGRAM_ID,
+            Dex::Moonshot => MOONSHOT_PROGRAM_ID,
+        }
+    }
+
+    pub fn from_program_id(program_id: &str) -> Option<Self> {
+        Self::iter().find(|it| it.program_id().to_string() == program_id)
+    }
+
+    pub fn event_source(&self) -> DexEventSource {
+        match self {
+            Dex::RaydiumAmm => DexEventSource::RayLog,
+            Dex::MeteoraDamm | Dex::RaydiumClmm | Dex::OrcaWhirlpool => DexEventSource::ProgramData,
+            Dex::Pumpfun | Dex::PumpAmm | Dex::MeteoraDlmm | Dex::MeteoraDbc => {
+                DexEventSource::AnchorCpi
+            }
+            Dex::RaydiumCpmm | Dex::Moonshot => DexEventSource::Instruction,
+        }
+    }
+
+    /// Prefix of the event payload in the logs of quicknode stream transactions
+    pub fn log_prefix(&self) -> &'static str {
+        match self {
+            Dex::RaydiumAmm => "Program log: ray_log: ",
+            Dex::Pumpfun => "pumpfun cpi log: ",
+            Dex::PumpAmm => "pumpamm cpi log: ",
+            Dex::MeteoraDlmm => "meteora dlmm cpi log: ",
+            Dex::MeteoraDamm => "meteora damm log Program data: ",
+            Dex::RaydiumClmm => "raydium clmm log Program data: ",
+            Dex::OrcaWhirlpool => "orca whirlpool log Program data: ",
+            Dex::RaydiumCpmm => "raydium cpmm ix: ",
+            Dex::MeteoraDbc => "meteora dbc cpi log: ",
+            Dex::Moonshot => "moonshot ix: ",
+        }
+    }
+
+    /// Kinds of the events decoded from the transactions of the dex
+    pub fn event_kinds(&self) -> Vec<DexEventKind> {
+        let mut kinds = vec![DexEventKind::Trade, DexEventKind::PoolCreated];
+        match self {
+            Dex::Pumpfun => kinds.extend([
+                DexEventKind::PumpfunComplete,
+                DexEventKind::PumpfunSetParams,
+            ]),
+            Dex::PumpAmm => kinds.extend([
+                DexEventKind::MigrationCompleted,
+                DexEventKind::LiquidityChange,
+            ]),
+            Dex::MeteoraDbc => kinds.push(DexEventKind::MeteoraDbcComplete),
+            Dex::RaydiumAmm | Dex::MeteoraDlmm | Dex::MeteoraDamm => {
+                kinds.push(DexEventKind::LiquidityChange)
+            }
+            Dex::RaydiumClmm | Dex::OrcaWhirlpool | Dex::RaydiumCpmm | Dex::Moonshot => {}
+        }
+        kinds
+    }
+
+    /// Kinds of the events the pool state tracker emits for the pools of the dex
+    pub fn state_event_kinds(&self) -> Vec<DexEventKind> {
+        match self {
+            Dex::MeteoraDlmm => vec![DexEventKind::PoolStateUpdated, DexEventKind::BinPriceUpdate],
+            Dex::MeteoraDamm
+            | Dex::RaydiumClmm
+            | Dex::OrcaWhirlpool
+            | Dex::RaydiumCpmm
+            | Dex::MeteoraDbc
+            | Dex::Moonshot => vec![],
+            Dex::RaydiumAmm | Dex::Pumpfun | Dex::PumpAmm => vec![DexEventKind::PoolStateUpdated],
+        }
+    }
+
+    /// Index of the pool account in the swap instruction accounts
+    pub fn swap_pool_account_idx(&self) -> usize {
+        match self {
+            Dex::RaydiumAmm => 1,
+            Dex::Pumpfun => 3,
+            // of `swap` and `swap_v2`, whirlpool `swap_v2` has it at 4
+            Dex::RaydiumClmm | Dex::OrcaWhirlpool | Dex::MeteoraDbc | Dex::Moonshot => 2,
+            Dex::RaydiumCpmm => 3,
+            Dex::PumpAmm | Dex::MeteoraDlmm | Dex::MeteoraDamm => 0,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -24,11 +206,128 @@ pub struct TxBaseMetaInfo {
 }
 
 pub mod utils {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use rust_decimal::Decimal;
+    use tracing::warn;
+
+    static AMOUNT_ANOMALIES: AtomicU64 = AtomicU64::new(0);
+
+    /// Amounts of decoded events found inconsistent since the start
+    pub fn amount_anomalies() -> u64 {
+        AMOUNT_ANOMALIES.load(Ordering::Relaxed)
+    }
+
+    /// `amount - fee`, none and counted as an anomaly, with `count`, when the fee is larger than
+    /// the amount
+    pub fn checked_amount_sub(amount: u64, fee: u64, txid: &str, count: bool) -> Option<u64> {
+        let net = amount.checked_sub(fee);
+        if net.is_none() && count {
+            AMOUNT_ANOMALIES.fetch_add(1, Ordering::Relaxed);
+            warn!("fee {fee} is larger than amount {amount}, txid: {txid}");
+        }
+        net
+    }
+
+    /// `post - pre` as a signed balance change, none and counted as an anomaly, with `count`, when
+    /// it doesn't fit an i64
+    pub fn checked_balance_delta(pre: u64, post: u64, txid: &str, count: bool) -> Option<i64> {
+        let delta = i64::try_from(post as i128 - pre as i128).ok();
+        if delta.is_none() && count {
+            AMOUNT_ANOMALIES.fetch_add(1, Ordering::Relaxed);
+            warn!("balance change from {pre} to {post} overflows, txid: {txid}");
+        }
+        delta
+    }
+
+    /// `total + delta`, none and counted as an anomaly, with `count`, on overflow
+    pub fn checked_delta_add(total: i64, delta: i64, txid: &str, count: bool) -> Option<i64> {
+        let sum = total.checked_add(delta);
+        if sum.is_none() && count {
+            AMOUNT_ANOMALIES.fetch_add(1, Ordering::Relaxed);
+            warn!("balance change {total} + {delta} overflows, txid: {txid}");
+        }
+        sum
+    }
+
+    /// Amount in ui units of a token with `decimals`
+    pub fn ui_amount(amount: u64, decimals: u8) -> f64 {
+        amount as f64 / 10f64.powi(decimals as i32)
+    }
+
+    /// Amount of a spl token or token-2022 account
+    pub fn token_account_amount(data: &[u8]) -> Option<u64> {
+        let amount = data.get(64..72)?;
+        Some(u64::from_le_bytes(amount.try_into().ok()?))
+    }
+
     pub fn calc_price_sol(sol_amount: u64, token_amount: u64, token_decimals: u8) -> f64 {
-        let sol_amount = sol_amount as f64 / 1_000_000_000.0f64;
+        calc_price(sol_amount, 9, token_amount, token_decimals)
+    }
+
+    /// Price of the token in ui units of the quote mint
+    pub fn calc_price(
+        quote_amount: u64,
+        quote_decimals: u8,
+        token_amount: u64,
+        token_decimals: u8,
+    ) -> f64 {
+        ui_amount(quote_amount, quote_decimals) / ui_amount(token_amount, token_decimals)
+    }
+
+    /// `calc_price_sol` without the float rounding, empty when the price can't be represented
+    pub fn calc_price_sol_decimal(
+        sol_amount: u64,
+        token_amount: u64,
+        token_decimals: u8,
+    ) -> String {
+        let sol_amount = Decimal::try_from_i128_with_scale(sol_amount as i128, 9);
+        let token_amount =
+            Decimal::try_from_i128_with_scale(token_amount as i128, token_decimals as u32);
+        let (Ok(sol_amount), Ok(token_amount)) = (sol_amount, token_amount) else {
+            return String::new();
+        };
+        sol_amount
+            .checked_div(token_amount)
+            .map(|it| it.normalize().to_string())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::utils::*;
+
+    #[test]
+    fn test_amount_helpers() {
+        assert_eq!(checked_amount_sub(10, 3, "tx", true), Some(7));
+        let anomalies = amount_anomalies();
+        assert_eq!(checked_amount_sub(3, 10, "tx", true), None);
+        assert!(amount_anomalies() > anomalies);
+        // the shadow parse's aren't counted
+        assert_eq!(checked_amount_sub(3, 10, "tx", false), None);
+
+        assert_eq!(checked_balance_delta(100, 95, "tx", true), Some(-5));
+        assert_eq!(
+            checked_balance_delta(0, i64::MAX as u64, "tx", true),
+            Some(i64::MAX)
+        );
+        let anomalies = amount_anomalies();
+        assert_eq!(checked_balance_delta(0, u64::MAX, "tx", true), None);
+        assert_eq!(checked_delta_add(i64::MAX, 1, "tx", true), None);
+        assert!(amount_anomalies() >= anomalies + 2);
+        assert_eq!(checked_delta_add(-5, 3, "tx", true), Some(-2));
 
-        let token_amount = token_amount as f64 / 10u64.pow(token_decimals as u32) as f64;
+        assert_eq!(calc_price_sol(1_000_000_000, 2_000_000, 6), 0.5);
+        assert_eq!(calc_price(3_000_000, 6, 2_000_000, 6), 1.5);
+        assert!(ui_amount(u64::MAX, 30) > 0.0);
 
-        sol_amount / token_amount
+        assert_eq!(calc_price_sol_decimal(1_000_000_000, 2_000_000, 6), "0.5");
+        assert_eq!(
+            calc_price_sol_decimal(1, 1_000_000_000_000_000_000, 6),
+            "0.000000000000000000001"
+        );
+        assert_eq!(calc_price_sol_decimal(1, 0, 6), "");
+        assert_eq!(calc_price_sol_decimal(1, 1, 30), "");
     }
 }
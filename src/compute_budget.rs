@@ -0,0 +1,87 @@
+//! The compute budget instructions of the transactions, the requested compute unit limit and
+//! price, for the congestion metrics.
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::{pubkey, pubkey::Pubkey};
+
+pub const COMPUTE_BUDGET_PROGRAM_ID: Pubkey =
+    pubkey!("ComputeBudget111111111111111111111111111111");
+
+const SET_COMPUTE_UNIT_LIMIT_IX_ID: u8 = 2;
+const SET_COMPUTE_UNIT_PRICE_IX_ID: u8 = 3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComputeBudget {
+    /// compute units requested, the runtime default if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cu_limit: Option<u32>,
+    /// priority fee in micro lamports per compute unit, no priority fee if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cu_price: Option<u64>,
+}
+
+impl ComputeBudget {
+    /// The budget set by the top level instructions, as `(program id, bs58 data)`, none for a
+    /// transaction without compute budget instructions. The last one of a kind wins, as the
+    /// runtime rejects duplicates anyway.
+    pub fn from_ixs<'a>(ixs: impl IntoIterator<Item = (&'a str, &'a str)>) -> Option<Self> {
+        let program_id = COMPUTE_BUDGET_PROGRAM_ID.to_string();
+        let mut budget = None;
+        for (ix_program_id, data) in ixs {
+            if ix_program_id != program_id {
+                continue;
+            }
+            let Ok(data) = bs58::decode(data).into_vec() else {
+                continue;
+            };
+            let budget = budget.get_or_insert_with(Self::default);
+            match data.split_first() {
+                Some((&SET_COMPUTE_UNIT_LIMIT_IX_ID, args)) => {
+                    if let Some(bytes) = args.get(..4) {
+                        budget.cu_limit = Some(u32::from_le_bytes(bytes.try_into().unwrap()));
+                    }
+                }
+                Some((&SET_COMPUTE_UNIT_PRICE_IX_ID, args)) => {
+                    if let Some(bytes) = args.get(..8) {
+                        budget.cu_price = Some(u64::from_le_bytes(bytes.try_into().unwrap()));
+                    }
+                }
+                // the heap frame and the loaded accounts data size
+                _ => {}
+            }
+        }
+        budget.filter(|it| it.cu_limit.is_some() || it.cu_price.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_budget_from_ixs() {
+        let program_id = COMPUTE_BUDGET_PROGRAM_ID.to_string();
+        let limit = bs58::encode([&[2][..], &400_000u32.to_le_bytes()].concat()).into_string();
+        let price = bs58::encode([&[3][..], &25_000u64.to_le_bytes()].concat()).into_string();
+        let heap = bs58::encode([&[1][..], &(256 * 1024u32).to_le_bytes()].concat()).into_string();
+        let budget = ComputeBudget::from_ixs([
+            (program_id.as_str(), limit.as_str()),
+            ("11111111111111111111111111111111", price.as_str()),
+            (program_id.as_str(), price.as_str()),
+        ]);
+        assert_eq!(
+            budget,
+            Some(ComputeBudget {
+                cu_limit: Some(400_000),
+                cu_price: Some(25_000),
+            })
+        );
+
+        let budget = ComputeBudget::from_ixs([(program_id.as_str(), heap.as_str())]);
+        assert_eq!(budget, None);
+        let truncated = bs58::encode([3, 1, 2]).into_string();
+        let budget = ComputeBudget::from_ixs([(program_id.as_str(), truncated.as_str())]);
+        assert_eq!(budget, None);
+    }
+}
@@ -1,9 +1,1129 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use reqwest::Url;
 use serde::Deserialize;
+use serde_json::Value;
+use solana_sdk::pubkey::Pubkey;
+use tokio::{fs, net::TcpListener, time::timeout};
+
+use crate::{
+    bigquery::BIGQUERY_CONSUMER,
+    cache::{AVG_PRICE_SLOT_SECS, DexEventKind, MAX_PRICE_SAMPLES, MINT_STATS_SLOT_SECS},
+    common::{Dex, QuoteMint},
+    naming::NameTemplate,
+    web::{ApiQuota, ApiScope, WS_CANDLE_1M_CHANNEL, WS_DEX_EVENTS_CHANNEL},
+    webhook::{EventRule, FieldNames, WebhookFormat},
+};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct AppConfig {
     pub listen_on: String,
     pub webhook_endpoint: String,
+    #[serde(default)]
+    pub webhook_format: WebhookFormat,
+    /// field names of the webhook payloads
+    #[serde(default)]
+    pub webhook_fields: FieldNames,
     pub redis_url: String,
+    /// not needed when `offline`
+    #[serde(default)]
     pub sol_rpc_url: String,
+    /// never call the solana rpc, the rpc backed lookups find nothing and the workers
+    /// needing them must be off
+    #[serde(default)]
+    pub offline: bool,
+    /// rules applied to dex events before sending them to webhook
+    #[serde(default)]
+    pub rules: Vec<EventRule>,
+    /// wasm modules transforming dex events before the rules, see `EventPlugins`
+    #[serde(default)]
+    pub plugins: Vec<PathBuf>,
+    /// emit `MintInactive` when a traded mint has no trade for so many seconds, off if unset
+    #[serde(default)]
+    pub mint_inactive_secs: Option<u64>,
+    /// alert when redis used memory reaches this share of maxmemory, 0.9 if unset
+    #[serde(default)]
+    pub redis_memory_alert_ratio: Option<f64>,
+    /// alert when an instruction account expected by an event is missing this many times a
+    /// minute, 10 if unset
+    #[serde(default)]
+    pub account_violation_alert_per_min: Option<u64>,
+    #[serde(default)]
+    pub log: LogConfig,
+    /// track the onchain state of actively traded pools, needs the `pubsub` feature
+    #[serde(default)]
+    pub state_tracker: Option<StateTrackerConfig>,
+    /// emit `PressureUpdate` for actively traded mints, off if unset
+    #[serde(default)]
+    pub pressure: Option<PressureConfig>,
+    /// cluster the early buyers of new tokens and attach `cluster_id` to the trades, off if unset
+    #[serde(default)]
+    pub wallet_clusters: Option<WalletClustersConfig>,
+    /// watch the lp tokens of new raydium amm pools over rpc and emit `LiquidityLocked`, off if unset
+    #[serde(default)]
+    pub lp_lock: Option<LpLockConfig>,
+    /// emit `PoolDrained` when the SOL reserves of a pool fall under a floor, off if unset
+    #[serde(default)]
+    pub pool_drained: Option<PoolDrainedConfig>,
+    /// decode the swaps attempted by the failed transactions into `FailedSwapAttempt`, the failed
+    /// transactions are dropped if unset
+    #[serde(default)]
+    pub failed_swaps: bool,
+    /// record the compute budgets of the dex transactions for `/congestion`, off if unset
+    #[serde(default)]
+    pub congestion: bool,
+    /// look up the funders of pool creators and early buyers over rpc in the background, and
+    /// attach the cached ones as `creator_funded_by` to `PoolCreated`
+    #[serde(default)]
+    pub first_funder_lookup: bool,
+    /// gateway serving `ipfs://` token metadata and images, https://ipfs.io/ipfs/ if unset
+    #[serde(default)]
+    pub ipfs_gateway: Option<String>,
+    /// api keys required by every endpoint but `/`, the endpoints are open if unset
+    #[serde(default)]
+    pub auth: Option<AuthConfig>,
+    /// more webhooks with their own filters and queues, the events of every kind go to
+    /// `webhook_endpoint` only if empty
+    #[serde(default)]
+    pub consumers: Vec<ConsumerConfig>,
+    /// flag or drop trades priced far from the recent prices of their pool, off if unset
+    #[serde(default)]
+    pub price_guard: Option<PriceGuardConfig>,
+    /// events of these dexes are dropped after decoding
+    #[serde(default)]
+    pub disabled_dexes: Vec<Dex>,
+    /// keep snapshots of the reserves of traded pools for `/pool/{addr}/reserves`, off if unset
+    #[serde(default)]
+    pub pool_reserves: Option<PoolReservesConfig>,
+    /// export the trades and created pools to bigquery, off if unset
+    #[serde(default)]
+    pub bigquery: Option<BigQueryConfig>,
+    /// keep the rolling vwap and twap of traded mints for `/token_stats/{mint}`, off if unset
+    #[serde(default)]
+    pub avg_price: Option<AvgPriceConfig>,
+    /// also parse every batch with the candidate parser and report where its events differ,
+    /// the emitted events are the production parser's
+    #[serde(default)]
+    pub shadow_parser: bool,
+    /// parse one batch in this many with the candidate parser, it's a second parse on the ack
+    /// path
+    #[serde(default = "default_shadow_parser_sample")]
+    pub shadow_parser_sample: u64,
+    /// parse the transactions of a batch on this many concurrent tasks, the transactions of a
+    /// pool or a mint on the same one, serially if unset
+    #[serde(default)]
+    pub parse_workers: Option<usize>,
+    /// trades of the pools quoted in these mints are parsed besides the WSOL pairs, e.g.
+    /// `["usdc", "usdt"]`
+    #[serde(default)]
+    pub quote_mints: Vec<QuoteMint>,
+    /// keep the SOL/USD price from a SOL/USDC pool and price the trades in USD, off if unset
+    #[serde(default)]
+    pub sol_usd: Option<SolUsdConfig>,
+    /// publish the events to an mqtt broker, off if unset
+    #[serde(default)]
+    pub mqtt: Option<MqttConfig>,
+    /// ingest the transactions of the dex programs from a yellowstone geyser grpc endpoint,
+    /// besides or instead of the stream POSTs, off if unset
+    #[serde(default)]
+    pub yellowstone: Option<YellowstoneConfig>,
+    /// ingest the transactions of the dex programs from the logs subscriptions of a solana
+    /// websocket rpc and `getTransaction`, for deployments without a stream, off if unset
+    #[serde(default)]
+    pub rpc_ws: Option<RpcWsConfig>,
+    /// drop the events queued already in the last seconds, keyed by txid, log and kind, off if
+    /// unset
+    #[serde(default)]
+    pub event_dedup_secs: Option<u64>,
+    /// publish the queued events to the redis pub/sub channels of their kind, off if unset
+    #[serde(default)]
+    pub redis_pubsub: bool,
+    /// channel and topic names of the sinks
+    #[serde(default)]
+    pub naming: NamingConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PriceGuardConfig {
+    /// a trade is an outlier when its price is over or under the median by this factor
+    #[serde(default = "default_price_guard_max_deviation")]
+    pub max_deviation: f64,
+    /// recent prices of the pool needed before the guard applies
+    #[serde(default = "default_price_guard_min_samples")]
+    pub min_samples: usize,
+    /// outliers in a row close to each other taken as the new price of the pool
+    #[serde(default = "default_price_guard_confirmations")]
+    pub confirmations: usize,
+    #[serde(default)]
+    pub action: PriceGuardAction,
+}
+
+fn default_price_guard_max_deviation() -> f64 {
+    10.0
+}
+
+fn default_price_guard_min_samples() -> usize {
+    5
+}
+
+fn default_shadow_parser_sample() -> u64 {
+    10
+}
+
+fn default_price_guard_confirmations() -> usize {
+    3
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceGuardAction {
+    /// set `price_outlier` on the trade
+    #[default]
+    Flag,
+    Drop,
+}
+
+/// Name of the consumer of `webhook_endpoint` when consumers are configured
+pub const DEFAULT_CONSUMER: &str = "default";
+/// Name of the consumer whose queues are published to the mqtt broker
+pub const MQTT_CONSUMER: &str = "mqtt";
+
+/// A webhook receiving a copy of the events, from its own queue so a slow consumer
+/// doesn't delay the others.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConsumerConfig {
+    pub name: String,
+    pub endpoint: String,
+    #[serde(default)]
+    pub format: WebhookFormat,
+    #[serde(default)]
+    pub fields: FieldNames,
+    /// kinds of the events sent to the consumer, all kinds if empty
+    #[serde(default)]
+    pub kinds: Vec<DexEventKind>,
+    /// rules of the consumer, instead of the top level ones
+    #[serde(default)]
+    pub rules: Vec<EventRule>,
+}
+
+impl ConsumerConfig {
+    pub fn accepts(&self, kind: DexEventKind) -> bool {
+        self.kinds.is_empty() || self.kinds.contains(&kind)
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuthConfig {
+    /// more keys can be created by the admin endpoints
+    #[serde(default)]
+    pub keys: Vec<ApiKeyConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiKeyConfig {
+    pub name: String,
+    pub key: String,
+    pub scopes: Vec<ApiScope>,
+    #[serde(default)]
+    pub quota: ApiQuota,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StateTrackerConfig {
+    pub sol_ws_url: String,
+    /// pools traded in so many seconds are tracked
+    #[serde(default = "default_state_tracker_active_secs")]
+    pub active_secs: u64,
+    #[serde(default = "default_state_tracker_max_pools")]
+    pub max_pools: usize,
+}
+
+fn default_state_tracker_active_secs() -> u64 {
+    600
+}
+
+fn default_state_tracker_max_pools() -> usize {
+    200
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PoolReservesConfig {
+    /// at most one snapshot of a pool per so many slots, the last one
+    #[serde(default = "default_pool_reserves_interval_slots")]
+    pub interval_slots: u64,
+    /// older snapshots are removed
+    #[serde(default = "default_pool_reserves_retention_slots")]
+    pub retention_slots: u64,
+}
+
+fn default_pool_reserves_interval_slots() -> u64 {
+    150
+}
+
+fn default_pool_reserves_retention_slots() -> u64 {
+    // about a day
+    216_000
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BigQueryConfig {
+    pub project_id: String,
+    pub dataset: String,
+    #[serde(default = "default_bigquery_trades_table")]
+    pub trades_table: String,
+    #[serde(default = "default_bigquery_pools_table")]
+    pub pools_table: String,
+    /// oauth token of the bigquery api, fetched from the GCE metadata server if unset
+    #[serde(default)]
+    pub access_token: Option<String>,
+}
+
+fn default_bigquery_trades_table() -> String {
+    "trades".to_string()
+}
+
+fn default_bigquery_pools_table() -> String {
+    "pools".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MqttConfig {
+    /// broker url with the client id, e.g. `mqtt://localhost:1883?client_id=sol-dex-hub`,
+    /// `mqtts://` for tls
+    pub url: String,
+    /// the published kinds, to the topic of `naming.mqtt`
+    pub kinds: Vec<DexEventKind>,
+    /// 0 at most once, 1 at least once or 2 exactly once
+    #[serde(default = "default_mqtt_qos")]
+    pub qos: u8,
+    /// field names of the payloads
+    #[serde(default)]
+    pub fields: FieldNames,
+}
+
+fn default_mqtt_qos() -> u8 {
+    1
+}
+
+/// Names of the channels and topics the events are published to, see `NameTemplate`
+#[derive(Debug, Clone, Deserialize)]
+pub struct NamingConfig {
+    /// websocket channel of the events sent to webhook but the followed wallet trades
+    #[serde(default = "default_ws_channel")]
+    pub ws: NameTemplate,
+    /// websocket channel of the 1 minute candles of a mint, `{kind}` is `candle_1m`
+    #[serde(default = "default_ws_candle_channel")]
+    pub ws_candle: NameTemplate,
+    #[serde(default = "default_redis_pubsub_channel")]
+    pub redis_pubsub: NameTemplate,
+    #[serde(default = "default_mqtt_topic")]
+    pub mqtt: NameTemplate,
+}
+
+impl Default for NamingConfig {
+    fn default() -> Self {
+        Self {
+            ws: default_ws_channel(),
+            ws_candle: default_ws_candle_channel(),
+            redis_pubsub: default_redis_pubsub_channel(),
+            mqtt: default_mqtt_topic(),
+        }
+    }
+}
+
+fn default_ws_channel() -> NameTemplate {
+    NameTemplate::new(WS_DEX_EVENTS_CHANNEL)
+}
+
+fn default_ws_candle_channel() -> NameTemplate {
+    NameTemplate::new(WS_CANDLE_1M_CHANNEL)
+}
+
+fn default_redis_pubsub_channel() -> NameTemplate {
+    NameTemplate::new("evt:{kind}")
+}
+
+fn default_mqtt_topic() -> NameTemplate {
+    NameTemplate::new("dex/{dex}/{mint}/{kind}")
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct YellowstoneConfig {
+    pub endpoint: String,
+    #[serde(default)]
+    pub x_token: Option<String>,
+    /// the programs of these dexes are subscribed to, of every dex if empty
+    #[serde(default)]
+    pub dexes: Vec<Dex>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RpcWsConfig {
+    pub sol_ws_url: String,
+    /// the logs of the programs of these dexes are subscribed to, of every dex if empty
+    #[serde(default)]
+    pub dexes: Vec<Dex>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AvgPriceConfig {
+    /// one average per window, in seconds
+    #[serde(default = "default_avg_price_windows_secs")]
+    pub windows_secs: Vec<i64>,
+    /// attach the averages to the trade events
+    #[serde(default)]
+    pub on_trades: bool,
+}
+
+/// each window is one running sum per traded mint
+pub const MAX_AVG_PRICE_WINDOWS: usize = 8;
+
+fn default_avg_price_windows_secs() -> Vec<i64> {
+    vec![300, 3600]
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SolUsdConfig {
+    /// WSOL and USDC vaults of the pool, of the raydium amm SOL/USDC pool by default
+    #[serde(default = "default_sol_usd_sol_vault")]
+    pub sol_vault: String,
+    #[serde(default = "default_sol_usd_usdc_vault")]
+    pub usdc_vault: String,
+    #[serde(default = "default_sol_usd_interval_secs")]
+    pub interval_secs: u64,
+    /// the trades get no USD price from an older price
+    #[serde(default = "default_sol_usd_max_age_secs")]
+    pub max_age_secs: u64,
+}
+
+fn default_sol_usd_sol_vault() -> String {
+    "DQyrAcCrDXQ7NeoqGgDCZwBvWDcYmFCjSb9JtteuvPpz".to_string()
+}
+
+fn default_sol_usd_usdc_vault() -> String {
+    "HLmqeL62xR1QoZ1HKKbXRrdN1p3phKpxRMb2VVopvBBz".to_string()
+}
+
+fn default_sol_usd_interval_secs() -> u64 {
+    10
+}
+
+fn default_sol_usd_max_age_secs() -> u64 {
+    60
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PressureConfig {
+    #[serde(default = "default_pressure_interval_secs")]
+    pub interval_secs: u64,
+    /// one update per window, in seconds
+    #[serde(default = "default_pressure_windows_secs")]
+    pub windows_secs: Vec<i64>,
+    /// mints traded in the shortest window are updated, at most so many
+    #[serde(default = "default_pressure_max_mints")]
+    pub max_mints: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WalletClustersConfig {
+    #[serde(default = "default_wallet_clusters_interval_secs")]
+    pub interval_secs: u64,
+    /// the first buyers of a mint recorded
+    #[serde(default = "default_wallet_clusters_early_buyers")]
+    pub early_buyers: usize,
+    /// the mints first bought in this window are clustered
+    #[serde(default = "default_wallet_clusters_lookback_secs")]
+    pub lookback_secs: u64,
+    /// first buys of a mint this close are a co-trade
+    #[serde(default = "default_wallet_clusters_co_trade_secs")]
+    pub co_trade_secs: i64,
+    /// mints two wallets co-traded to be clustered
+    #[serde(default = "default_wallet_clusters_min_co_trades")]
+    pub min_co_trades: usize,
+    /// a first funder of more wallets, e.g. an exchange, doesn't cluster them
+    #[serde(default = "default_wallet_clusters_max_funded_wallets")]
+    pub max_funded_wallets: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LpLockConfig {
+    #[serde(default = "default_lp_lock_interval_secs")]
+    pub interval_secs: u64,
+    /// the pools created in this window are watched
+    #[serde(default = "default_lp_lock_watch_secs")]
+    pub watch_secs: u64,
+    /// owners of the locked lp tokens, e.g. the vaults of locker programs, besides the incinerator
+    #[serde(default)]
+    pub lockers: Vec<String>,
+    /// percent of the lp tokens locked since the last event to emit another
+    #[serde(default = "default_lp_lock_min_pct_change")]
+    pub min_pct_change: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PoolDrainedConfig {
+    /// a pool is drained when its reserves fall under this, in SOL
+    #[serde(default = "default_pool_drained_floor_sol")]
+    pub floor_sol: f64,
+    /// only the pools whose reserves have reached this are watched, in SOL
+    #[serde(default = "default_pool_drained_armed_sol")]
+    pub armed_sol: f64,
+}
+
+fn default_pool_drained_floor_sol() -> f64 {
+    0.1
+}
+
+fn default_pool_drained_armed_sol() -> f64 {
+    10.0
+}
+
+fn default_lp_lock_interval_secs() -> u64 {
+    60
+}
+
+fn default_lp_lock_watch_secs() -> u64 {
+    24 * 3600
+}
+
+fn default_lp_lock_min_pct_change() -> f64 {
+    1.0
+}
+
+fn default_wallet_clusters_interval_secs() -> u64 {
+    300
+}
+
+fn default_wallet_clusters_early_buyers() -> usize {
+    50
+}
+
+fn default_wallet_clusters_lookback_secs() -> u64 {
+    24 * 3600
+}
+
+fn default_wallet_clusters_co_trade_secs() -> i64 {
+    5
+}
+
+fn default_wallet_clusters_min_co_trades() -> usize {
+    3
+}
+
+fn default_wallet_clusters_max_funded_wallets() -> usize {
+    20
+}
+
+fn default_pressure_interval_secs() -> u64 {
+    60
+}
+
+fn default_pressure_windows_secs() -> Vec<i64> {
+    vec![300, 3600]
+}
+
+fn default_pressure_max_mints() -> usize {
+    500
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LogConfig {
+    /// stdout log filter, `RUST_LOG` takes precedence, info if unset
+    #[serde(default)]
+    pub stdout_level: Option<String>,
+    /// also write logs to rolling files if set
+    #[serde(default)]
+    pub file: Option<LogFileConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LogFileConfig {
+    pub dir: PathBuf,
+    #[serde(default = "default_log_file_prefix")]
+    pub prefix: String,
+    #[serde(default)]
+    pub rotation: LogRotation,
+    /// oldest files beyond this count are deleted on rotation, all kept if unset
+    #[serde(default)]
+    pub max_files: Option<usize>,
+    /// log filter of the files, independent of stdout
+    #[serde(default = "default_log_file_level")]
+    pub level: String,
+}
+
+fn default_log_file_prefix() -> String {
+    "sol-dex-hub.log".to_string()
+}
+
+fn default_log_file_level() -> String {
+    "info".to_string()
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogRotation {
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+}
+
+impl AppConfig {
+    pub async fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let content = fs::read_to_string(path).await?;
+        let mut value = serde_json::from_str::<Value>(&content)
+            .map_err(|err| anyhow!("parse config json file error: {err}"))?;
+        let mut problems = vec![];
+        resolve_secrets(&mut value, &|name| std::env::var(name).ok(), &mut problems);
+        if !problems.is_empty() {
+            return Err(anyhow!("config secrets error: {}", problems.join(", ")));
+        }
+        let config = serde_json::from_value::<AppConfig>(value)
+            .map_err(|err| anyhow!("parse config json file error: {err}"))?;
+        Ok(config)
+    }
+
+    /// `webhook_endpoint` as the default consumer followed by the configured consumers,
+    /// empty if none is configured and bigquery and mqtt are off.
+    pub fn routed_consumers(&self) -> Vec<ConsumerConfig> {
+        if self.consumers.is_empty() && self.bigquery.is_none() && self.mqtt.is_none() {
+            return vec![];
+        }
+        let default = ConsumerConfig {
+            name: DEFAULT_CONSUMER.to_string(),
+            endpoint: self.webhook_endpoint.clone(),
+            format: self.webhook_format,
+            fields: self.webhook_fields,
+            kinds: vec![],
+            rules: self.rules.clone(),
+        };
+        [default]
+            .into_iter()
+            .chain(self.consumers.clone())
+            .collect()
+    }
+
+    /// Check every setting before starting the workers, reporting all problems at once.
+    pub async fn validate(&self) -> Result<()> {
+        let mut problems = vec![];
+
+        if let Err(err) = TcpListener::bind(&self.listen_on).await {
+            problems.push(format!(
+                "listen_on {} can't be bound: {err}",
+                self.listen_on
+            ));
+        }
+
+        match Url::parse(&self.webhook_endpoint) {
+            Ok(url) => {
+                if let Err(err) = check_webhook(url).await {
+                    problems.push(format!(
+                        "webhook_endpoint {} is unreachable: {err}",
+                        self.webhook_endpoint
+                    ));
+                }
+            }
+            Err(err) => problems.push(format!(
+                "webhook_endpoint {} is not a valid url: {err}",
+                self.webhook_endpoint
+            )),
+        }
+
+        match redis::Client::open(self.redis_url.as_str()) {
+            Ok(client) => {
+                if let Err(err) = check_redis(client).await {
+                    problems.push(format!("redis_url {} ping failed: {err}", self.redis_url));
+                }
+            }
+            Err(err) => problems.push(format!(
+                "redis_url {} is not a valid redis url: {err}",
+                self.redis_url
+            )),
+        }
+
+        if self.offline {
+            if self.state_tracker.is_some() {
+                problems.push("state_tracker needs the rpc, it can't run offline".to_string());
+            }
+            if self.first_funder_lookup {
+                problems
+                    .push("first_funder_lookup needs the rpc, it can't run offline".to_string());
+            }
+            if self.sol_usd.is_some() {
+                problems.push("sol_usd needs the rpc, it can't run offline".to_string());
+            }
+            if self.rpc_ws.is_some() {
+                problems.push("rpc_ws needs the rpc, it can't run offline".to_string());
+            }
+            if self.lp_lock.is_some() {
+                problems.push("lp_lock needs the rpc, it can't run offline".to_string());
+            }
+        } else if let Err(err) = Url::parse(&self.sol_rpc_url) {
+            problems.push(format!(
+                "sol_rpc_url {} is not a valid url: {err}",
+                self.sol_rpc_url
+            ));
+        }
+
+        if let Some(state_tracker) = &self.state_tracker {
+            if !cfg!(feature = "pubsub") {
+                problems.push("state_tracker needs the pubsub feature".to_string());
+            }
+            if let Err(err) = Url::parse(&state_tracker.sol_ws_url) {
+                problems.push(format!(
+                    "state_tracker.sol_ws_url {} is not a valid url: {err}",
+                    state_tracker.sol_ws_url
+                ));
+            }
+        }
+
+        if let Some(sol_usd) = &self.sol_usd {
+            for vault in [&sol_usd.sol_vault, &sol_usd.usdc_vault] {
+                if let Err(err) = Pubkey::from_str(vault) {
+                    problems.push(format!(
+                        "sol_usd vault {vault} is not a valid pubkey: {err}"
+                    ));
+                }
+            }
+        }
+
+        if let Some(mqtt) = &self.mqtt {
+            if !cfg!(feature = "mqtt") {
+                problems.push("mqtt needs the mqtt feature".to_string());
+            }
+            match Url::parse(&mqtt.url) {
+                Ok(url) => {
+                    if !["mqtt", "mqtts"].contains(&url.scheme()) {
+                        problems.push(format!(
+                            "mqtt.url {} should be mqtt:// or mqtts://",
+                            mqtt.url
+                        ));
+                    }
+                    if !url.query_pairs().any(|(name, _)| name == "client_id") {
+                        problems.push(format!("mqtt.url {} has no client_id", mqtt.url));
+                    }
+                }
+                Err(err) => {
+                    problems.push(format!("mqtt.url {} is not a valid url: {err}", mqtt.url))
+                }
+            }
+            if mqtt.kinds.is_empty() {
+                problems.push("mqtt.kinds is empty".to_string());
+            }
+            if self.naming.mqtt.as_str().contains(['+', '#']) {
+                problems.push("naming.mqtt has a wildcard".to_string());
+            }
+            if mqtt.qos > 2 {
+                problems.push(format!("mqtt.qos {} should be 0, 1 or 2", mqtt.qos));
+            }
+        }
+
+        if let Some(yellowstone) = &self.yellowstone {
+            if !cfg!(feature = "yellowstone") {
+                problems.push("yellowstone needs the yellowstone feature".to_string());
+            }
+            if let Err(err) = Url::parse(&yellowstone.endpoint) {
+                problems.push(format!(
+                    "yellowstone.endpoint {} is not a valid url: {err}",
+                    yellowstone.endpoint
+                ));
+            }
+        }
+
+        if let Some(rpc_ws) = &self.rpc_ws {
+            if !cfg!(feature = "pubsub") {
+                problems.push("rpc_ws needs the pubsub feature".to_string());
+            }
+            if let Err(err) = Url::parse(&rpc_ws.sol_ws_url) {
+                problems.push(format!(
+                    "rpc_ws.sol_ws_url {} is not a valid url: {err}",
+                    rpc_ws.sol_ws_url
+                ));
+            }
+        }
+
+        for (name, template) in [
+            ("ws", &self.naming.ws),
+            ("ws_candle", &self.naming.ws_candle),
+            ("redis_pubsub", &self.naming.redis_pubsub),
+            ("mqtt", &self.naming.mqtt),
+        ] {
+            if template.as_str().is_empty() {
+                problems.push(format!("naming.{name} is empty"));
+            }
+            for placeholder in template.unknown_placeholders() {
+                problems.push(format!("naming.{name} has an unknown {{{placeholder}}}"));
+            }
+        }
+        if !self.naming.ws_candle.as_str().contains("{mint}") {
+            problems.push("naming.ws_candle has no {mint}".to_string());
+        }
+
+        if let Some(auth) = &self.auth {
+            let mut names = HashSet::new();
+            for key in &auth.keys {
+                if key.key.len() < 16 {
+                    problems.push(format!("api key {} is shorter than 16 chars", key.name));
+                }
+                if !names.insert(&key.name) {
+                    problems.push(format!("api key name {} is duplicated", key.name));
+                }
+            }
+        }
+
+        let mut consumer_names =
+            HashSet::from([DEFAULT_CONSUMER, BIGQUERY_CONSUMER, MQTT_CONSUMER]);
+        for consumer in &self.consumers {
+            let valid_name = !consumer.name.is_empty()
+                && consumer
+                    .name
+                    .chars()
+                    .all(|it| it.is_ascii_alphanumeric() || it == '_' || it == '-');
+            if !valid_name {
+                problems.push(format!(
+                    "consumer name {:?} should be letters, digits, _ or -",
+                    consumer.name
+                ));
+            }
+            if !consumer_names.insert(&consumer.name) {
+                problems.push(format!(
+                    "consumer name {} is duplicated or reserved",
+                    consumer.name
+                ));
+            }
+            if let Err(err) = Url::parse(&consumer.endpoint) {
+                problems.push(format!(
+                    "consumer {} endpoint {} is not a valid url: {err}",
+                    consumer.name, consumer.endpoint
+                ));
+            }
+        }
+
+        if let Some(price_guard) = &self.price_guard {
+            if price_guard.max_deviation <= 1.0 {
+                problems.push("price_guard.max_deviation should be more than 1".to_string());
+            }
+            if price_guard.min_samples > MAX_PRICE_SAMPLES {
+                problems.push(format!(
+                    "price_guard.min_samples should be at most {MAX_PRICE_SAMPLES}"
+                ));
+            }
+            if !(2..=MAX_PRICE_SAMPLES).contains(&price_guard.confirmations) {
+                problems.push(format!(
+                    "price_guard.confirmations should be between 2 and {MAX_PRICE_SAMPLES}"
+                ));
+            }
+        }
+
+        if let Some(pool_reserves) = &self.pool_reserves {
+            if pool_reserves.interval_slots == 0 {
+                problems.push("pool_reserves.interval_slots should be positive".to_string());
+            }
+            if pool_reserves.retention_slots < pool_reserves.interval_slots {
+                problems.push(
+                    "pool_reserves.retention_slots should be at least interval_slots".to_string(),
+                );
+            }
+        }
+
+        if let Some(pressure) = &self.pressure {
+            if pressure.interval_secs == 0 {
+                problems.push("pressure.interval_secs should be positive".to_string());
+            }
+            if pressure.windows_secs.is_empty() {
+                problems.push("pressure.windows_secs is empty".to_string());
+            }
+            for window in &pressure.windows_secs {
+                if !(MINT_STATS_SLOT_SECS..=24 * 3600).contains(window) {
+                    problems.push(format!(
+                        "pressure window {window} is not between {MINT_STATS_SLOT_SECS} and 86400 seconds"
+                    ));
+                }
+            }
+        }
+
+        if let Some(clusters) = &self.wallet_clusters {
+            for (name, value) in [
+                ("interval_secs", clusters.interval_secs),
+                ("early_buyers", clusters.early_buyers as u64),
+                ("lookback_secs", clusters.lookback_secs),
+                ("min_co_trades", clusters.min_co_trades as u64),
+            ] {
+                if value == 0 {
+                    problems.push(format!("wallet_clusters.{name} should be positive"));
+                }
+            }
+            if clusters.co_trade_secs < 0 {
+                problems.push("wallet_clusters.co_trade_secs is negative".to_string());
+            }
+        }
+
+        if self.shadow_parser && self.shadow_parser_sample == 0 {
+            problems.push("shadow_parser_sample should be positive".to_string());
+        }
+
+        if let Some(lp_lock) = &self.lp_lock {
+            if lp_lock.interval_secs == 0 || lp_lock.watch_secs == 0 {
+                problems
+                    .push("lp_lock.interval_secs and watch_secs should be positive".to_string());
+            }
+            if lp_lock.min_pct_change <= 0.0 {
+                problems.push("lp_lock.min_pct_change should be positive".to_string());
+            }
+            for locker in &lp_lock.lockers {
+                if let Err(err) = Pubkey::from_str(locker) {
+                    problems.push(format!(
+                        "lp_lock locker {locker} is not a valid pubkey: {err}"
+                    ));
+                }
+            }
+        }
+
+        if let Some(pool_drained) = &self.pool_drained {
+            if pool_drained.floor_sol <= 0.0 {
+                problems.push("pool_drained.floor_sol should be positive".to_string());
+            }
+            if pool_drained.armed_sol <= pool_drained.floor_sol {
+                problems.push("pool_drained.armed_sol should be over floor_sol".to_string());
+            }
+        }
+
+        if let Some(avg_price) = &self.avg_price {
+            let windows = &avg_price.windows_secs;
+            if windows.is_empty() || windows.len() > MAX_AVG_PRICE_WINDOWS {
+                problems.push(format!(
+                    "avg_price.windows_secs should have 1 to {MAX_AVG_PRICE_WINDOWS} windows"
+                ));
+            }
+            if windows.iter().collect::<HashSet<_>>().len() < windows.len() {
+                problems.push("avg_price.windows_secs has duplicate windows".to_string());
+            }
+            for window in &avg_price.windows_secs {
+                if !(AVG_PRICE_SLOT_SECS..=24 * 3600).contains(window)
+                    || window % AVG_PRICE_SLOT_SECS != 0
+                {
+                    problems.push(format!(
+                        "avg_price window {window} is not a multiple of {AVG_PRICE_SLOT_SECS} seconds up to 86400"
+                    ));
+                }
+            }
+        }
+
+        if let Some(bigquery) = &self.bigquery {
+            let settings = [
+                ("project_id", &bigquery.project_id),
+                ("dataset", &bigquery.dataset),
+                ("trades_table", &bigquery.trades_table),
+                ("pools_table", &bigquery.pools_table),
+            ];
+            for (name, value) in settings {
+                if value.is_empty() {
+                    problems.push(format!("bigquery.{name} is empty"));
+                }
+            }
+            if bigquery.trades_table == bigquery.pools_table {
+                problems.push("bigquery.trades_table and pools_table should differ".to_string());
+            }
+        }
+
+        if self.account_violation_alert_per_min == Some(0) {
+            problems.push("account_violation_alert_per_min should be positive".to_string());
+        }
+        if self.parse_workers == Some(0) {
+            problems.push("parse_workers should be positive".to_string());
+        }
+        if self.event_dedup_secs == Some(0) {
+            problems.push("event_dedup_secs should be positive".to_string());
+        }
+
+        if problems.is_empty() {
+            return Ok(());
+        }
+        let report = problems
+            .iter()
+            .map(|it| format!("  - {it}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Err(anyhow!("invalid config:\n{report}"))
+    }
+}
+
+const ENV_REF_START: &str = "${env:";
+const FILE_REF_PREFIX: &str = "file://";
+
+/// Replace the secret references in the string values of the config: `${env:NAME}` anywhere
+/// in a value by the environment variable, and a whole `file://path` value by the content of
+/// the file without the trailing newline. The problems name the references, never the secrets.
+fn resolve_secrets(
+    value: &mut Value,
+    env: &impl Fn(&str) -> Option<String>,
+    problems: &mut Vec<String>,
+) {
+    match value {
+        Value::String(text) => {
+            if let Some(path) = text.strip_prefix(FILE_REF_PREFIX) {
+                match std::fs::read_to_string(path) {
+                    Ok(secret) => *text = secret.trim_end_matches(['\r', '\n']).to_string(),
+                    Err(err) => problems.push(format!("secret file {path} can't be read: {err}")),
+                }
+            } else if text.contains(ENV_REF_START) {
+                match resolve_env_refs(text, env) {
+                    Ok(resolved) => *text = resolved,
+                    Err(problem) => problems.push(problem),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                resolve_secrets(item, env, problems);
+            }
+        }
+        Value::Object(fields) => {
+            for field in fields.values_mut() {
+                resolve_secrets(field, env, problems);
+            }
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
+
+fn resolve_env_refs(
+    text: &str,
+    env: &impl Fn(&str) -> Option<String>,
+) -> std::result::Result<String, String> {
+    let mut resolved = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(ENV_REF_START) {
+        resolved.push_str(&rest[..start]);
+        let after = &rest[start + ENV_REF_START.len()..];
+        let Some(end) = after.find('}') else {
+            return Err(format!("{ENV_REF_START} without a closing brace"));
+        };
+        let name = &after[..end];
+        let secret = env(name).ok_or_else(|| format!("environment variable {name} is unset"))?;
+        resolved.push_str(&secret);
+        rest = &after[end + 1..];
+    }
+    resolved.push_str(rest);
+    Ok(resolved)
+}
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// any http response counts as reachable, the endpoint may not accept HEAD
+async fn check_webhook(url: Url) -> Result<()> {
+    let client = reqwest::ClientBuilder::new()
+        .timeout(CHECK_TIMEOUT)
+        .build()?;
+    client.head(url).send().await?;
+    Ok(())
+}
+
+async fn check_redis(client: redis::Client) -> Result<()> {
+    timeout(CHECK_TIMEOUT, async {
+        let mut conn = client.get_multiplexed_async_connection().await?;
+        let _: String = redis::cmd("PING").query_async(&mut conn).await?;
+        Ok(())
+    })
+    .await
+    .map_err(|_| anyhow!("timeout after {CHECK_TIMEOUT:?}"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_routed_consumers() {
+        let mut config: AppConfig = serde_json::from_value(json!({
+            "listen_on": "0.0.0.0:8080",
+            "webhook_endpoint": "http://127.0.0.1:7000",
+            "redis_url": "redis://127.0.0.1",
+            "sol_rpc_url": "http://127.0.0.1:8899",
+            "consumers": [
+                {"name": "sniper", "endpoint": "http://127.0.0.1:7001", "kinds": ["PoolCreated"]}
+            ]
+        }))
+        .unwrap();
+
+        let consumers = config.routed_consumers();
+        assert_eq!(consumers.len(), 2);
+        assert_eq!(consumers[0].name, DEFAULT_CONSUMER);
+        assert!(consumers[0].accepts(DexEventKind::Trade));
+        assert!(consumers[1].accepts(DexEventKind::PoolCreated));
+        assert!(!consumers[1].accepts(DexEventKind::Trade));
+
+        config.consumers.clear();
+        assert!(config.routed_consumers().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_validate() {
+        let config: AppConfig = serde_json::from_value(json!({
+            "listen_on": "127.0.0.1:0",
+            "webhook_endpoint": "not a url",
+            "redis_url": "redis://127.0.0.1:1",
+            "sol_rpc_url": "http://127.0.0.1:8899",
+            "offline": true,
+            "first_funder_lookup": true,
+            "parse_workers": 0,
+            "consumers": [
+                {"name": "default", "endpoint": "http://127.0.0.1:7001"},
+                {"name": "bad name", "endpoint": "http://127.0.0.1:7002"}
+            ]
+        }))
+        .unwrap();
+
+        // every problem is reported at once
+        let report = config.validate().await.unwrap_err().to_string();
+        let problems: Vec<_> = report.lines().skip(1).collect();
+        assert_eq!(problems.len(), 6, "{report}");
+        for problem in [
+            "webhook_endpoint not a url is not a valid url",
+            "redis_url redis://127.0.0.1:1 ping failed",
+            "first_funder_lookup needs the rpc",
+            "consumer name default is duplicated or reserved",
+            "consumer name \"bad name\" should be letters",
+            "parse_workers should be positive",
+        ] {
+            assert!(report.contains(problem), "{problem} missing in {report}");
+        }
+    }
+
+    #[test]
+    fn test_resolve_secrets() {
+        let path = std::env::temp_dir().join(format!("sol-dex-hub-secret-{}", std::process::id()));
+        std::fs::write(&path, "file-secret\n").unwrap();
+        let env = |name: &str| (name == "REDIS_PASSWORD").then(|| "env-secret".to_string());
+
+        let mut value = json!({
+            "redis_url": "redis://:${env:REDIS_PASSWORD}@127.0.0.1",
+            "auth": {"keys": [{"name": "ops", "key": format!("file://{}", path.display())}]},
+            "mint_inactive_secs": 60,
+        });
+        let mut problems = vec![];
+        resolve_secrets(&mut value, &env, &mut problems);
+        std::fs::remove_file(&path).unwrap();
+        assert!(problems.is_empty(), "{problems:?}");
+        assert_eq!(value["redis_url"], "redis://:env-secret@127.0.0.1");
+        assert_eq!(value["auth"]["keys"][0]["key"], "file-secret");
+
+        let mut value = json!([
+            "${env:MISSING}",
+            "${env:REDIS_PASSWORD",
+            "file:///no/such/secret"
+        ]);
+        resolve_secrets(&mut value, &env, &mut problems);
+        assert_eq!(problems.len(), 3);
+        assert_eq!(problems[0], "environment variable MISSING is unset");
+        assert_eq!(value[0], "${env:MISSING}");
+    }
 }
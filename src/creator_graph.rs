@@ -0,0 +1,343 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::{Result, anyhow};
+use futures::StreamExt;
+use redis::aio::MultiplexedConnection;
+use serde::Serialize;
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+use tracing::{info, warn};
+
+use crate::{
+    cache::{self, DexEvent, DexPoolCreatedRecord},
+    rpc_provider::RpcProvider,
+    rpc_tx::RpcTx,
+};
+
+const SIGNATURES_PAGE_LEN: usize = 1_000;
+/// Wallets with more transactions are not traced back to their first one
+const MAX_SIGNATURE_PAGES: usize = 10;
+const LOOKUP_CONCURRENCY: usize = 4;
+const MAX_WARM_LOOKUPS: usize = 20;
+/// wallets without a first funder found are looked up again after this, they may be too busy
+/// to trace back or not funded yet
+const NO_FUNDER_TTL_SECS: u64 = 6 * 3600;
+
+/// `funder` sent the first SOL to `wallet`
+#[serde_as]
+#[derive(Debug, Clone, Serialize)]
+pub struct FundingLink {
+    #[serde_as(as = "DisplayFromStr")]
+    pub wallet: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub funder: Pubkey,
+}
+
+/// The funder of `wallet` in its first transaction: the fee payer if it's another account,
+/// otherwise the account losing most lamports while the wallet gains.
+pub fn first_funder(tx: &RpcTx, wallet: &Pubkey) -> Option<Pubkey> {
+    let keys = &tx.tx.transaction.message.account_keys;
+    let meta = tx.tx.meta.as_ref()?;
+    let wallet = wallet.to_string();
+    let wallet_idx = keys.iter().position(|it| *it == wallet)?;
+    let gained = meta.post_balances.get(wallet_idx)? > meta.pre_balances.get(wallet_idx)?;
+    if !gained {
+        return None;
+    }
+
+    let funder_idx = if wallet_idx != 0 {
+        0
+    } else {
+        meta.pre_balances
+            .iter()
+            .zip(&meta.post_balances)
+            .enumerate()
+            .filter(|(idx, (pre, post))| *idx != wallet_idx && post < pre)
+            .max_by_key(|(_, (pre, post))| *pre - *post)
+            .map(|(idx, _)| idx)?
+    };
+    keys.get(funder_idx)?.parse().ok()
+}
+
+/// First funder of the wallet, looked up from its oldest transaction and cached in redis. A
+/// wallet without one is not looked up again for a while.
+pub async fn lookup_first_funder(
+    rpc_client: &dyn RpcProvider,
+    conn: &mut MultiplexedConnection,
+    wallet: &Pubkey,
+) -> Result<Option<Pubkey>> {
+    if let Some(funder) = cache::get_first_funder(conn, wallet).await? {
+        return Ok(Some(funder));
+    }
+    if cache::has_no_first_funder(conn, wallet).await? {
+        return Ok(None);
+    }
+    let funder = fetch_first_funder(rpc_client, wallet).await?;
+    match &funder {
+        Some(funder) => cache::save_first_funder(conn, wallet, funder).await?,
+        None => cache::save_no_first_funder(conn, wallet, NO_FUNDER_TTL_SECS).await?,
+    }
+    Ok(funder)
+}
+
+/// The wallet's funder, the funder's funder and so on, at most `depth` links
+pub async fn funder_chain(
+    rpc_client: &dyn RpcProvider,
+    conn: &mut MultiplexedConnection,
+    wallet: &Pubkey,
+    depth: usize,
+) -> Result<Vec<FundingLink>> {
+    let mut links: Vec<FundingLink> = vec![];
+    let mut wallet = *wallet;
+    while links.len() < depth {
+        let Some(funder) = lookup_first_funder(rpc_client, conn, &wallet).await? else {
+            break;
+        };
+        let seen = funder == wallet || links.iter().any(|it| it.wallet == funder);
+        links.push(FundingLink { wallet, funder });
+        if seen {
+            break;
+        }
+        wallet = funder;
+    }
+    Ok(links)
+}
+
+/// Attach the cached first funders of the creators to the `PoolCreated` events, without rpc.
+/// The creators are looked up by the warmer once their pools are parsed, a creator not looked up
+/// yet is left unset. The pools migrated from a bonding curve are skipped, their creator is the
+/// pumpfun pool authority.
+pub async fn attach_creator_funders(
+    conn: &mut MultiplexedConnection,
+    events: &mut [DexEvent],
+) -> Result<()> {
+    let creators: Vec<_> = created_pools(events).map(|it| it.creator).collect();
+    if creators.is_empty() {
+        return Ok(());
+    }
+    let funders = cache::get_first_funders(conn, &creators).await?;
+    let funders: HashMap<_, _> = creators
+        .into_iter()
+        .zip(funders)
+        .filter_map(|(creator, funder)| Some((creator, funder?)))
+        .collect();
+    attach_funders(events, &funders);
+    Ok(())
+}
+
+/// The `PoolCreated` events but those of the bonding curve migrations
+fn created_pools(events: &mut [DexEvent]) -> impl Iterator<Item = &mut DexPoolCreatedRecord> {
+    events.iter_mut().filter_map(|evt| match evt {
+        DexEvent::PoolCreated(record) if !record.is_curve_migration() => Some(record),
+        _ => None,
+    })
+}
+
+fn attach_funders(events: &mut [DexEvent], funders: &HashMap<Pubkey, Pubkey>) {
+    for record in created_pools(events) {
+        record.creator_funded_by = funders.get(&record.creator).copied();
+    }
+}
+
+/// Look up the first funders of queued wallets, so they're cached before being queried.
+pub async fn start_funder_warmer(
+    redis_client: Arc<redis::Client>,
+    rpc_client: Arc<dyn RpcProvider>,
+) -> Result<()> {
+    info!("start first funder warmer........");
+    loop {
+        let mut conn = redis_client.get_multiplexed_async_connection().await?;
+        let wallets = cache::pop_funder_lookups(&mut conn, MAX_WARM_LOOKUPS).await?;
+        if wallets.is_empty() {
+            drop(conn);
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            continue;
+        }
+
+        futures::stream::iter(&wallets)
+            .for_each_concurrent(LOOKUP_CONCURRENCY, |wallet| {
+                let rpc_client = rpc_client.clone();
+                let mut conn = conn.clone();
+                async move {
+                    if let Err(err) =
+                        lookup_first_funder(rpc_client.as_ref(), &mut conn, wallet).await
+                    {
+                        warn!("lookup first funder of {wallet} error: {err}");
+                    }
+                }
+            })
+            .await;
+    }
+}
+
+/// First funder of the wallet from its oldest transaction over rpc, none if it's too busy to trace
+/// back or has no funder
+async fn fetch_first_funder(
+    rpc_client: &dyn RpcProvider,
+    wallet: &Pubkey,
+) -> Result<Option<Pubkey>> {
+    let Some(signature) = oldest_signature(rpc_client, wallet).await? else {
+        return Ok(None);
+    };
+    let tx = rpc_client.get_transaction(&signature).await?;
+    let tx = tx.ok_or_else(|| anyhow!("transaction {signature} not found"))?;
+    Ok(first_funder(&tx, wallet))
+}
+
+async fn oldest_signature(rpc_client: &dyn RpcProvider, wallet: &Pubkey) -> Result<Option<String>> {
+    let mut before: Option<String> = None;
+    for _ in 0..MAX_SIGNATURE_PAGES {
+        let page = rpc_client
+            .get_signatures_for_address(wallet, before.as_deref(), SIGNATURES_PAGE_LEN)
+            .await?;
+        let Some(last) = page.last() else {
+            return Ok(before);
+        };
+        if page.len() < SIGNATURES_PAGE_LEN {
+            return Ok(Some(last.clone()));
+        }
+        before = Some(last.clone());
+    }
+
+    warn!(
+        "{wallet} has more than {} transactions",
+        SIGNATURES_PAGE_LEN * MAX_SIGNATURE_PAGES
+    );
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::{
+        common::{Dex, WSOL_MINT},
+        pda,
+        rpc_provider::MockRpcProvider,
+    };
+
+    fn transfer_tx(keys: &[Pubkey], pre: &[u64], post: &[u64]) -> RpcTx {
+        serde_json::from_value(transfer_tx_json(keys, pre, post)).unwrap()
+    }
+
+    /// `getTransaction` result in json encoding
+    fn transfer_tx_json(keys: &[Pubkey], pre: &[u64], post: &[u64]) -> serde_json::Value {
+        json!({
+            "slot": 1,
+            "blockTime": 1,
+            "transaction": {
+                "signatures": ["sig"],
+                "message": {
+                    "accountKeys": keys.iter().map(|it| it.to_string()).collect::<Vec<_>>(),
+                    "instructions": []
+                }
+            },
+            "meta": {"err": null, "preBalances": pre, "postBalances": post}
+        })
+    }
+
+    fn pool_created(creator: Pubkey, dex: Dex, mint: Pubkey) -> DexEvent {
+        DexEvent::PoolCreated(DexPoolCreatedRecord {
+            blk_ts: DateTime::from_timestamp(100, 0).unwrap(),
+            slot: 1,
+            txid: "create".to_string(),
+            idx: 0,
+            creator,
+            addr: Pubkey::new_unique(),
+            dex,
+            mint_a: mint,
+            mint_b: WSOL_MINT,
+            decimals_a: 6,
+            decimals_b: 9,
+            init_liquidity: None,
+            creator_funded_by: None,
+            source: None,
+        })
+    }
+
+    #[test]
+    fn test_attach_funders() {
+        let (creator, funder, unknown, mint) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let authority = pda::pumpfun_pool_authority(&mint);
+        let mut events = vec![
+            pool_created(creator, Dex::Pumpfun, Pubkey::new_unique()),
+            pool_created(unknown, Dex::RaydiumCpmm, Pubkey::new_unique()),
+            // the migration's creator is the pool authority, even when it's cached
+            pool_created(authority, Dex::PumpAmm, mint),
+        ];
+        let funders = HashMap::from([(creator, funder), (authority, funder)]);
+        attach_funders(&mut events, &funders);
+
+        let funded: Vec<_> = events
+            .iter()
+            .map(|evt| match evt {
+                DexEvent::PoolCreated(record) => record.creator_funded_by,
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(funded, [Some(funder), None, None]);
+        let creators: Vec<_> = created_pools(&mut events).map(|it| it.creator).collect();
+        assert_eq!(creators, [creator, unknown]);
+    }
+
+    #[test]
+    fn test_first_funder() {
+        let payer = Pubkey::new_unique();
+        let wallet = Pubkey::new_unique();
+        let system = Pubkey::default();
+
+        let tx = transfer_tx(&[payer, wallet, system], &[10, 0, 1], &[4, 5, 1]);
+        assert_eq!(first_funder(&tx, &wallet), Some(payer));
+        assert_eq!(first_funder(&tx, &payer), None);
+
+        // the wallet paid the fee, the sender signed too
+        let tx = transfer_tx(&[wallet, payer, system], &[0, 10, 1], &[5, 4, 1]);
+        assert_eq!(first_funder(&tx, &wallet), Some(payer));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_first_funder() {
+        let (payer, wallet, busy, fresh) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let system = Pubkey::default();
+        let tx = transfer_tx_json;
+        // two pages of signatures, the oldest last
+        let signatures: Vec<_> = (0..SIGNATURES_PAGE_LEN + 5)
+            .map(|it| format!("sig{it}"))
+            .collect();
+        let oldest = signatures.last().unwrap().clone();
+        let too_many: Vec<_> = (0..SIGNATURES_PAGE_LEN * MAX_SIGNATURE_PAGES)
+            .map(|it| format!("busy{it}"))
+            .collect();
+        let rpc_client = MockRpcProvider::default()
+            .with_signatures(wallet, signatures)
+            .with_tx("sig0", tx(&[wallet, system], &[10, 1], &[4, 1]))
+            .with_tx(
+                &oldest,
+                tx(&[payer, wallet, system], &[10, 0, 1], &[4, 5, 1]),
+            )
+            .with_signatures(busy, too_many);
+
+        let funder = |wallet| fetch_first_funder(&rpc_client, wallet);
+        assert_eq!(funder(&wallet).await.unwrap(), Some(payer));
+        // too busy to trace back
+        assert_eq!(funder(&busy).await.unwrap(), None);
+        // no transaction yet
+        assert_eq!(funder(&fresh).await.unwrap(), None);
+
+        let missing_tx = MockRpcProvider::default().with_signatures(fresh, vec!["gone".into()]);
+        assert!(fetch_first_funder(&missing_tx, &fresh).await.is_err());
+    }
+}
@@ -0,0 +1,169 @@
+//! Check the decimals recorded for new pools against the onchain mint accounts,
+//! a wrong value would corrupt the `price_sol` of every trade of the pool.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use itertools::Itertools;
+use solana_sdk::pubkey::Pubkey;
+use tracing::{info, warn};
+
+use crate::cache::{self, DexPoolRecord, RedisCacheRecord};
+use crate::rpc_provider::RpcProvider;
+
+const MAX_ACCOUNTS_PER_RPC: usize = 100;
+/// spl token and token-2022 mints share the base layout
+const MINT_LEN: usize = 82;
+const MINT_DECIMALS_OFFSET: usize = 44;
+
+pub fn mint_account_decimals(data: &[u8]) -> Option<u8> {
+    (data.len() >= MINT_LEN).then(|| data[MINT_DECIMALS_OFFSET])
+}
+
+/// Decimals of the mint accounts over rpc, the mints not found are left out
+async fn fetch_mint_decimals(
+    rpc_client: &dyn RpcProvider,
+    mints: &[Pubkey],
+) -> Result<Vec<(Pubkey, u8)>> {
+    let mut fetched = vec![];
+    for mints in mints.chunks(MAX_ACCOUNTS_PER_RPC) {
+        let accounts = rpc_client.get_multiple_accounts(mints).await?;
+        for (mint, account) in mints.iter().zip(accounts) {
+            match account.and_then(|it| mint_account_decimals(&it.data)) {
+                Some(mint_decimals) => fetched.push((*mint, mint_decimals)),
+                None => warn!("mint account {mint} not found"),
+            }
+        }
+    }
+    Ok(fetched)
+}
+
+/// Each mint is read over rpc once, its decimals are kept in redis.
+pub async fn start(
+    redis_client: Arc<redis::Client>,
+    rpc_client: Arc<dyn RpcProvider>,
+) -> Result<()> {
+    info!("start pool decimals checker........");
+    loop {
+        let mut conn = redis_client.get_multiplexed_async_connection().await?;
+        let pools = cache::pop_decimals_checks(&mut conn, MAX_ACCOUNTS_PER_RPC).await?;
+        if pools.is_empty() {
+            drop(conn);
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            continue;
+        }
+
+        let keys: Vec<_> = pools
+            .iter()
+            .map(|it| DexPoolRecord::new_key(it.to_string()))
+            .collect();
+        // the records as read, a record is only replaced if it's unchanged
+        let jsons: Vec<Option<String>> =
+            redis::cmd("MGET").arg(&keys).query_async(&mut conn).await?;
+        let records = jsons
+            .into_iter()
+            .flatten()
+            .map(|json| Ok((serde_json::from_str::<DexPoolRecord>(&json)?, json)))
+            .collect::<Result<Vec<_>>>()?;
+        let mints: Vec<_> = records
+            .iter()
+            .flat_map(|(it, _)| [it.mint_a, it.mint_b])
+            .unique()
+            .collect();
+        let known = cache::get_mint_decimals(&mut conn, &mints).await?;
+        let mut decimals: HashMap<Pubkey, u8> = mints
+            .iter()
+            .zip(known)
+            .filter_map(|(mint, decimals)| Some((*mint, decimals?)))
+            .collect();
+
+        let unknown: Vec<_> = mints
+            .iter()
+            .filter(|it| !decimals.contains_key(it))
+            .copied()
+            .collect();
+        let fetched = fetch_mint_decimals(rpc_client.as_ref(), &unknown).await?;
+        decimals.extend(fetched.iter().copied());
+        cache::save_mint_decimals(&mut conn, &fetched).await?;
+
+        let mut changed = vec![];
+        for (mut record, json) in records {
+            let mut corrected = false;
+            for mint in [record.mint_a, record.mint_b] {
+                if let Some(mint_decimals) = decimals.get(&mint) {
+                    corrected |= record.correct_decimals(&mint, *mint_decimals);
+                }
+            }
+            if corrected && !cache::replace_pool_record(&mut conn, &json, &record).await? {
+                changed.push(record.addr);
+            }
+        }
+        // checked again from the new record, the mint decimals are saved by now
+        cache::queue_decimals_checks(&mut conn, &changed).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::account::Account;
+
+    use super::*;
+    use crate::rpc_provider::MockRpcProvider;
+
+    #[test]
+    fn test_mint_account_decimals() {
+        let mut data = vec![0u8; MINT_LEN];
+        data[MINT_DECIMALS_OFFSET] = 6;
+        assert_eq!(mint_account_decimals(&data), Some(6));
+
+        // token-2022 extensions follow the base layout
+        data.extend_from_slice(&[1u8; 100]);
+        assert_eq!(mint_account_decimals(&data), Some(6));
+        assert_eq!(mint_account_decimals(&data[..40]), None);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_mint_decimals() {
+        let mint_account = |decimals: u8, len: usize| {
+            let mut data = vec![0u8; len];
+            data[MINT_DECIMALS_OFFSET] = decimals;
+            Account {
+                data,
+                ..Default::default()
+            }
+        };
+        let mut rpc_client = MockRpcProvider::default();
+        // more mints than a call takes
+        let mints: Vec<_> = (0..MAX_ACCOUNTS_PER_RPC + 2)
+            .map(|_| Pubkey::new_unique())
+            .collect();
+        for (idx, mint) in mints.iter().enumerate() {
+            rpc_client = rpc_client.with_account(*mint, mint_account(idx as u8 % 10, MINT_LEN));
+        }
+        // a token-2022 mint with extensions, an account too short for a mint, a missing one
+        let (token_2022, short, missing) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let rpc_client = rpc_client
+            .with_account(token_2022, mint_account(9, MINT_LEN + 100))
+            .with_account(
+                short,
+                Account {
+                    data: vec![6; 40],
+                    ..Default::default()
+                },
+            );
+        let mut all = mints.clone();
+        all.extend([token_2022, short, missing]);
+
+        let fetched = fetch_mint_decimals(&rpc_client, &all).await.unwrap();
+        assert_eq!(fetched.len(), mints.len() + 1);
+        assert_eq!(
+            fetched[MAX_ACCOUNTS_PER_RPC],
+            (mints[MAX_ACCOUNTS_PER_RPC], 0)
+        );
+        assert_eq!(fetched.last(), Some(&(token_2022, 9)));
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::{
+    common::Dex,
+    compute_budget::ComputeBudget,
+    qn_req_processor::{Amt, Instruction, IxAccount, ProgramInvocation, TokenAmt, Tx},
+    rpc_tx::{ANCHOR_EVENT_IX_TAG, RpcTx},
+};
+
+/// Transaction item of a Helius webhook payload, `raw` webhooks deliver the
+/// `getTransaction` shape, `enhanced` webhooks deliver decoded instructions only.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum HeliusWebhookTx {
+    Raw(Box<RpcTx>),
+    Enhanced(HeliusEnhancedWebhookTx),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeliusEnhancedWebhookTx {
+    pub signature: String,
+    pub slot: u64,
+    pub timestamp: i64,
+    #[serde(default)]
+    pub transaction_error: Option<serde_json::Value>,
+    #[serde(default)]
+    pub instructions: Vec<HeliusInstruction>,
+    #[serde(default)]
+    pub account_data: Vec<HeliusAccountData>,
+}
+
+/// Balance changes of an account over the transaction, the balances themselves aren't sent
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeliusAccountData {
+    pub account: String,
+    #[serde(default)]
+    pub native_balance_change: i64,
+    #[serde(default)]
+    pub token_balance_changes: Vec<HeliusTokenBalanceChange>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeliusTokenBalanceChange {
+    pub token_account: String,
+    pub mint: String,
+    pub raw_token_amount: HeliusRawTokenChange,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeliusRawTokenChange {
+    /// signed
+    pub token_amount: String,
+    pub decimals: u8,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeliusInstruction {
+    pub program_id: String,
+    pub accounts: Vec<String>,
+    pub data: String,
+    #[serde(default)]
+    pub inner_instructions: Vec<HeliusInstruction>,
+}
+
+impl HeliusWebhookTx {
+    pub fn signature(&self) -> &str {
+        match self {
+            Self::Raw(tx) => tx.tx.signature(),
+            Self::Enhanced(tx) => &tx.signature,
+        }
+    }
+
+    /// Convert to the quicknode stream transaction model, `None` for failed transactions
+    /// unless the raw ones are kept with `keep_failed`.
+    pub fn into_tx(self, keep_failed: bool) -> Result<Option<Tx>> {
+        match self {
+            Self::Raw(tx) => tx.into_kept_tx(keep_failed),
+            Self::Enhanced(tx) => {
+                if tx.transaction_error.is_some() {
+                    return Ok(None);
+                }
+                Ok(Some(tx.into_tx()?))
+            }
+        }
+    }
+}
+
+fn zero_amts() -> (Amt, Amt) {
+    let zero = || Amt {
+        sol: 0,
+        token: None,
+    };
+    (zero(), zero())
+}
+
+/// A balance change as amounts from or to 0, their difference is right but not the balances
+fn change_amts(change: i64) -> (u64, u64) {
+    if change < 0 {
+        (change.unsigned_abs(), 0)
+    } else {
+        (0, change as u64)
+    }
+}
+
+impl HeliusEnhancedWebhookTx {
+    /// `(pre, post)` amounts of the accounts from their balance changes in `accountData`
+    fn account_amts(&self) -> Result<HashMap<&str, (Amt, Amt)>> {
+        let mut amts: HashMap<&str, (Amt, Amt)> = HashMap::new();
+        for data in &self.account_data {
+            let (pre, post) = change_amts(data.native_balance_change);
+            let amt = amts.entry(data.account.as_str()).or_insert_with(zero_amts);
+            (amt.0.sol, amt.1.sol) = (pre, post);
+            for change in &data.token_balance_changes {
+                let (pre, post) = change_amts(change.raw_token_amount.token_amount.parse()?);
+                let token_amt = |amt: u64| TokenAmt {
+                    mint: change.mint.clone(),
+                    decimals: change.raw_token_amount.decimals,
+                    amt,
+                };
+                let amt = amts
+                    .entry(change.token_account.as_str())
+                    .or_insert_with(zero_amts);
+                (amt.0.token, amt.1.token) = (Some(token_amt(pre)), Some(token_amt(post)));
+            }
+        }
+        Ok(amts)
+    }
+
+    /// Enhanced transactions have no program logs, so only events which carry everything needed
+    /// in their self cpi instruction data are kept, which are the pumpfun events for now. They
+    /// have no balances either, the accounts get their balance changes in `accountData` as
+    /// amounts from or to 0, so the trader deltas are right but not the balances.
+    pub fn into_tx(self) -> Result<Tx> {
+        let amts = self.account_amts()?;
+        let mut ixs = vec![];
+        let mut logs = vec![];
+        for (idx, outer_ix) in self.instructions.iter().enumerate() {
+            let mut latest_invocation: Option<&HeliusInstruction> = None;
+            for ix in std::iter::once(outer_ix).chain(outer_ix.inner_instructions.iter()) {
+                let Some(dex) = Dex::from_program_id(&ix.program_id) else {
+                    continue;
+                };
+                if dex != Dex::Pumpfun {
+                    debug!(
+                        "ignore {dex} instruction in helius enhanced tx: {}",
+                        self.signature
+                    );
+                    continue;
+                }
+                let data = bs58::decode(&ix.data).into_vec()?;
+                if !data.starts_with(&ANCHOR_EVENT_IX_TAG) {
+                    latest_invocation = Some(ix);
+                    continue;
+                }
+                let Some(invocation) = latest_invocation else {
+                    continue;
+                };
+
+                let accounts = invocation
+                    .accounts
+                    .iter()
+                    .map(|pubkey| {
+                        let (pre_amt, post_amt) =
+                            amts.get(pubkey.as_str()).cloned().unwrap_or_else(zero_amts);
+                        IxAccount {
+                            pubkey: pubkey.clone(),
+                            pre_amt,
+                            post_amt,
+                        }
+                    })
+                    .collect();
+                ixs.push(ProgramInvocation {
+                    program_id: invocation.program_id.clone(),
+                    instruction: Instruction {
+                        accounts,
+                        data: invocation.data.clone(),
+                        index: idx as u64,
+                        transfers: vec![],
+                    },
+                });
+                logs.push(format!("{}{}", dex.log_prefix(), ix.data));
+            }
+        }
+
+        Ok(Tx {
+            blk_ts: self.timestamp,
+            slot: self.slot,
+            signature: self.signature,
+            logs,
+            ixs,
+            err: None,
+            compute_budget: ComputeBudget::from_ixs(
+                self.instructions
+                    .iter()
+                    .map(|it| (it.program_id.as_str(), it.data.as_str())),
+            ),
+            requeued_logs: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+    use crate::{pumpfun::PUMPFUN_PROGRAM_ID, raydium::RAYDIUM_AMM_PROGRAM_ID};
+
+    #[test]
+    fn test_enhanced_tx_into_tx() {
+        let accounts: Vec<_> = (0..8).map(|_| Pubkey::new_unique().to_string()).collect();
+        let (user_token_acc, trader) = (&accounts[5], &accounts[6]);
+        let mint = Pubkey::new_unique().to_string();
+        let mut event = ANCHOR_EVENT_IX_TAG.to_vec();
+        event.extend_from_slice(&[1, 2, 3]);
+        let event = bs58::encode(event).into_string();
+        let ix = |program_id: Pubkey, accounts: &[String], data: &str, inner: Vec<_>| {
+            json!({
+                "programId": program_id.to_string(),
+                "accounts": accounts,
+                "data": data,
+                "innerInstructions": inner,
+            })
+        };
+        let payload = json!({
+            "signature": "sig",
+            "slot": 250_000_000,
+            "timestamp": 1_700_000_000,
+            "transactionError": null,
+            "type": "SWAP",
+            "instructions": [
+                ix(RAYDIUM_AMM_PROGRAM_ID, &accounts, "3Bxs", vec![]),
+                ix(
+                    PUMPFUN_PROGRAM_ID,
+                    &accounts,
+                    "3Bxs4h24hBtQy9rw",
+                    vec![ix(PUMPFUN_PROGRAM_ID, &accounts[..1], &event, vec![])],
+                ),
+            ],
+            "accountData": [
+                {"account": trader, "nativeBalanceChange": -1_000_000, "tokenBalanceChanges": []},
+                {"account": user_token_acc, "nativeBalanceChange": 0, "tokenBalanceChanges": [{
+                    "userAccount": trader,
+                    "tokenAccount": user_token_acc,
+                    "mint": mint,
+                    "rawTokenAmount": {"tokenAmount": "500", "decimals": 6},
+                }]},
+            ],
+        });
+        let helius_tx: HeliusWebhookTx = serde_json::from_value(payload.clone()).unwrap();
+        assert!(matches!(helius_tx, HeliusWebhookTx::Enhanced(_)));
+        let tx = helius_tx.into_tx(false).unwrap().unwrap();
+
+        // the raydium instruction is skipped, the pumpfun invocation paired with its event
+        assert_eq!(tx.ixs.len(), 1);
+        assert_eq!(tx.ixs[0].instruction.index, 1);
+        assert_eq!(
+            tx.logs,
+            vec![format!("{}{event}", Dex::Pumpfun.log_prefix())]
+        );
+        let ix_accounts = &tx.ixs[0].instruction.accounts;
+        let trader = &ix_accounts[6];
+        assert_eq!((trader.pre_amt.sol, trader.post_amt.sol), (1_000_000, 0));
+        let user_token_acc = &ix_accounts[5];
+        let token = |amt: &Amt| amt.token.as_ref().map(|it| (it.mint.clone(), it.amt));
+        assert_eq!(token(&user_token_acc.pre_amt), Some((mint.clone(), 0)));
+        assert_eq!(token(&user_token_acc.post_amt), Some((mint, 500)));
+        assert_eq!(ix_accounts[0].post_amt.sol, 0);
+
+        let mut failed = payload;
+        failed["transactionError"] = json!({"InstructionError": [0, "Custom"]});
+        let helius_tx: HeliusWebhookTx = serde_json::from_value(failed).unwrap();
+        assert!(helius_tx.into_tx(true).unwrap().is_none());
+    }
+}
@@ -0,0 +1,31 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use chrono::Utc;
+use tracing::info;
+
+use crate::cache::{self, DexEvent};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+const MAX_MINTS_PER_CHECK: usize = 5_000;
+
+/// Emit `MintInactive` events for the traded mints without trades in the last `inactive_secs`.
+pub async fn start(redis_client: Arc<redis::Client>, inactive_secs: u64) -> Result<()> {
+    info!("start inactive mint watcher, inactive after {inactive_secs} seconds........");
+    loop {
+        let before = Utc::now() - Duration::from_secs(inactive_secs);
+        let mut conn = redis_client.get_multiplexed_async_connection().await?;
+        let records = cache::pop_inactive_mints(&mut conn, before, MAX_MINTS_PER_CHECK).await?;
+        let records_len = records.len();
+        if records_len > 0 {
+            let events: Vec<_> = records.into_iter().map(DexEvent::MintInactive).collect();
+            cache::rpush_dex_evts(&mut conn, None, &events).await?;
+            info!("{records_len} mints inactive since {before}");
+        }
+        drop(conn);
+
+        if records_len < MAX_MINTS_PER_CHECK {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Backfill of a slot range missed during a downtime. The confirmed blocks of the range are fetched
+//! with `getBlock` and their dex transactions queued as one request per block, like the stream
+//! requests, so they go through the same processor.
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Result, bail};
+use futures::StreamExt;
+use tracing::{info, warn};
+
+use crate::{ingest, qn_req_processor::Tx, rpc_provider::RpcProvider};
+
+/// `dataset` and `stream_name` of the queued requests
+const BACKFILL_DATASET: &str = "backfill";
+/// Slots listed per `getBlocks` call
+const SLOTS_PAGE: u64 = 1000;
+/// `getBlock` calls in flight, blocks are large
+const MAX_FETCHES: usize = 4;
+const FETCH_ATTEMPTS: u32 = 3;
+const FETCH_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Slots to backfill, both inclusive
+#[derive(Debug, Clone, Copy)]
+pub struct SlotRange {
+    pub from_slot: u64,
+    pub to_slot: u64,
+}
+
+/// The dex transactions of the block, none for a block the rpc node doesn't have. The failed ones
+/// are kept with `keep_failed`, see `failed_swaps` of the config.
+async fn fetch_block_txs(
+    rpc_client: &dyn RpcProvider,
+    slot: u64,
+    keep_failed: bool,
+) -> Result<Vec<Tx>> {
+    let mut attempt = 1;
+    loop {
+        match rpc_client.get_block(slot).await {
+            Ok(Some(block)) => return block.into_txs(slot, keep_failed),
+            Ok(None) => return Ok(vec![]),
+            Err(err) if attempt < FETCH_ATTEMPTS => warn!("get block {slot} error: {err}"),
+            Err(err) => return Err(err),
+        }
+        tokio::time::sleep(FETCH_RETRY_INTERVAL * attempt).await;
+        attempt += 1;
+    }
+}
+
+/// Queue the dex transactions of the confirmed blocks of the range, in slot order. A block which
+/// still fails after the retries is skipped and reported at the end.
+pub async fn start(
+    redis_client: Arc<redis::Client>,
+    rpc_client: Arc<dyn RpcProvider>,
+    range: SlotRange,
+    failed_swaps: bool,
+) -> Result<()> {
+    let SlotRange { from_slot, to_slot } = range;
+    if from_slot > to_slot {
+        bail!("backfill from slot {from_slot} is after to slot {to_slot}");
+    }
+    info!("start backfill of slots {from_slot}..={to_slot}........");
+
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let mut queued = 0;
+    let mut failed_slots = vec![];
+    for page_start in (from_slot..=to_slot).step_by(SLOTS_PAGE as usize) {
+        let page_end = (page_start + SLOTS_PAGE - 1).min(to_slot);
+        let slots = rpc_client.get_blocks(page_start, page_end).await?;
+        let rpc_client = rpc_client.as_ref();
+        let mut blocks = futures::stream::iter(slots)
+            .map(
+                |slot| async move { (slot, fetch_block_txs(rpc_client, slot, failed_swaps).await) },
+            )
+            .buffered(MAX_FETCHES);
+        while let Some((slot, txs)) = blocks.next().await {
+            match txs {
+                Ok(txs) if txs.is_empty() => {}
+                Ok(txs) => {
+                    queued += txs.len();
+                    ingest::push_txs(&mut conn, BACKFILL_DATASET, BACKFILL_DATASET, txs).await?;
+                }
+                Err(err) => {
+                    warn!("backfill block {slot} error: {err}, skip it");
+                    failed_slots.push(slot);
+                }
+            }
+        }
+        info!("backfilled slots {page_start}..={page_end}, {queued} txs queued so far");
+    }
+
+    if !failed_slots.is_empty() {
+        bail!(
+            "backfill of slots {from_slot}..={to_slot} queued {queued} txs, failed blocks: {failed_slots:?}"
+        );
+    }
+    info!("backfill of slots {from_slot}..={to_slot} done, {queued} txs queued");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{raydium::RAYDIUM_AMM_PROGRAM_ID, rpc_provider::MockRpcProvider};
+
+    fn block_tx(signature: &str, program_id: &str, err: Option<Value>) -> Value {
+        json!({
+            "transaction": {
+                "signatures": [signature],
+                "message": {
+                    "accountKeys": ["11111111111111111111111111111111", program_id],
+                    "instructions": [{"programIdIndex": 1, "accounts": [0], "data": ""}],
+                },
+            },
+            "meta": {
+                "err": err,
+                "preBalances": [1, 1],
+                "postBalances": [1, 1],
+                "logMessages": [
+                    format!("Program {program_id} invoke [1]"),
+                    "Program log: ray_log: A1x8BAAAAAAAqgAAAAAAAAABAAAAAAAAAFx8BAAAAAAA",
+                    format!("Program {program_id} success"),
+                ],
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn test_fetch_block_txs() {
+        let raydium = RAYDIUM_AMM_PROGRAM_ID.to_string();
+        let block = json!({
+            "blockTime": 100,
+            "transactions": [
+                block_tx("failed", &raydium, Some(json!({"InstructionError": [0, "Custom"]}))),
+                block_tx("other", "Vote111111111111111111111111111111111111111", None),
+                block_tx("swap", &raydium, None),
+            ],
+        });
+        let rpc_client = MockRpcProvider::default().with_block(10, block);
+
+        let txs = fetch_block_txs(&rpc_client, 10, false).await.unwrap();
+        let txs: Vec<_> = txs
+            .iter()
+            .map(|it| (it.signature.as_str(), it.slot, it.blk_ts, it.logs.len()))
+            .collect();
+        assert_eq!(txs, vec![("swap", 10, 100, 1)]);
+        assert!(
+            fetch_block_txs(&rpc_client, 11, false)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        // with the dex instruction it attempted
+        let txs = fetch_block_txs(&rpc_client, 10, true).await.unwrap();
+        let txs: Vec<_> = txs
+            .iter()
+            .map(|it| (it.signature.as_str(), it.ixs.len(), it.err.is_some()))
+            .collect();
+        assert_eq!(txs, vec![("failed", 1, true), ("swap", 1, false)]);
+        assert_eq!(rpc_client.get_blocks(0, 20).await.unwrap(), vec![10]);
+    }
+}
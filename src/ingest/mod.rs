@@ -0,0 +1,44 @@
+//! Transaction sources besides the stream POSTs, queueing their transactions as stream requests.
+
+pub mod backfill;
+#[cfg(feature = "pubsub")]
+pub mod rpc_ws;
+#[cfg(feature = "yellowstone")]
+pub mod yellowstone;
+
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use redis::aio::MultiplexedConnection;
+use tracing::warn;
+
+use crate::{
+    cache::{self, MAX_QN_REQ_LEN},
+    qn_req_processor::{QnSolDexDatahubWebhookReq, QnStreamMetadata, Tx, new_batch_id},
+};
+
+/// Queue the transactions as one request of the processor, waiting while the queue is full so
+/// the source is slowed down instead of dropping transactions. Returns the batch id.
+pub async fn push_txs(
+    conn: &mut MultiplexedConnection,
+    dataset: &str,
+    stream_name: &str,
+    txs: Vec<Tx>,
+) -> Result<String> {
+    let batch_id = new_batch_id();
+    let metadata = QnStreamMetadata::for_txs(dataset, stream_name, &txs)
+        .ok_or_else(|| anyhow!("no transaction to queue"))?;
+    let req = QnSolDexDatahubWebhookReq {
+        batch_id: Some(batch_id.clone()),
+        txs,
+        metadata,
+    };
+    let req_body = serde_json::to_string(&req)?;
+
+    while cache::llen_qn_requests(conn).await? >= MAX_QN_REQ_LEN {
+        warn!("qn request queue is full, wait for processor");
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    cache::rpush_qn_request(conn, req_body).await?;
+    Ok(batch_id)
+}
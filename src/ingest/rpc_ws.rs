@@ -0,0 +1,167 @@
+//! Websocket rpc ingestion, for deployments without a stream. The signatures logged by the dex
+//! programs are fetched with `getTransaction` and queued in batches like the stream requests.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    mem,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use futures::{StreamExt, future};
+use solana_pubsub_client::nonblocking::pubsub_client::PubsubClient;
+use solana_rpc_client_api::config::{RpcTransactionLogsConfig, RpcTransactionLogsFilter};
+use solana_sdk::commitment_config::CommitmentConfig;
+use strum::IntoEnumIterator;
+use tracing::{info, warn};
+
+use crate::{
+    common::Dex, config::RpcWsConfig, ingest, qn_req_processor::Tx, rpc_provider::RpcProvider,
+};
+
+/// `dataset` and `stream_name` of the queued requests
+const RPC_WS_DATASET: &str = "rpc_ws";
+/// `getTransaction` calls in flight
+const MAX_FETCHES: usize = 16;
+/// the transaction can reach the rpc node after its logs
+const FETCH_ATTEMPTS: u32 = 3;
+const FETCH_RETRY_INTERVAL: Duration = Duration::from_millis(500);
+/// Transactions queued as one request, or those fetched on the flush interval
+const BATCH_TXS: usize = 100;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// Signatures remembered to fetch a transaction logged by several programs once
+const MAX_SEEN_SIGNATURES: usize = 10_000;
+
+/// Recently seen signatures, the oldest forgotten first
+#[derive(Default)]
+struct SeenSignatures {
+    set: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+impl SeenSignatures {
+    /// false if the signature was seen already
+    fn insert(&mut self, signature: &str) -> bool {
+        if !self.set.insert(signature.to_string()) {
+            return false;
+        }
+        self.order.push_back(signature.to_string());
+        if self.order.len() > MAX_SEEN_SIGNATURES
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.set.remove(&oldest);
+        }
+        true
+    }
+}
+
+async fn fetch_tx(
+    rpc_client: &dyn RpcProvider,
+    signature: String,
+    keep_failed: bool,
+) -> Option<Tx> {
+    for attempt in 1..=FETCH_ATTEMPTS {
+        match rpc_client.get_transaction(&signature).await {
+            Ok(Some(tx)) => match tx.into_kept_tx(keep_failed) {
+                Ok(tx) => return tx,
+                Err(err) => {
+                    warn!("convert rpc tx {signature} error: {err}");
+                    return None;
+                }
+            },
+            Ok(None) => {}
+            Err(err) => warn!("get rpc tx {signature} error: {err}"),
+        }
+        tokio::time::sleep(FETCH_RETRY_INTERVAL * attempt).await;
+    }
+    warn!("rpc tx {signature} not found after {FETCH_ATTEMPTS} attempts, skip it");
+    None
+}
+
+/// Subscribe to the logs of the dex programs and queue their transactions until a subscription
+/// ends. The failed transactions are queued too with `failed_swaps`.
+pub async fn start(
+    redis_client: Arc<redis::Client>,
+    rpc_client: Arc<dyn RpcProvider>,
+    config: RpcWsConfig,
+    failed_swaps: bool,
+) -> Result<()> {
+    let pubsub = PubsubClient::new(&config.sol_ws_url).await?;
+    let dexes: Vec<Dex> = if config.dexes.is_empty() {
+        Dex::iter().collect()
+    } else {
+        config.dexes.clone()
+    };
+    let mut streams = vec![];
+    for dex in &dexes {
+        let filter = RpcTransactionLogsFilter::Mentions(vec![dex.program_id().to_string()]);
+        let logs_config = RpcTransactionLogsConfig {
+            commitment: Some(CommitmentConfig::confirmed()),
+        };
+        let (stream, _unsubscribe) = pubsub.logs_subscribe(filter, logs_config).await?;
+        streams.push(stream);
+    }
+    info!(
+        "start rpc logs ingestion of {} programs from {}........",
+        dexes.len(),
+        config.sol_ws_url
+    );
+
+    let mut seen = SeenSignatures::default();
+    // fetched concurrently, a retried fetch doesn't hold back the others
+    let mut txs = futures::stream::select_all(streams)
+        .filter_map(|resp| {
+            let logs = resp.value;
+            let new = (failed_swaps || logs.err.is_none()) && seen.insert(&logs.signature);
+            future::ready(new.then_some(logs.signature))
+        })
+        .map(|signature| fetch_tx(rpc_client.as_ref(), signature, failed_swaps))
+        .buffer_unordered(MAX_FETCHES);
+
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let mut pending = vec![];
+    let mut flush = tokio::time::interval(FLUSH_INTERVAL);
+    loop {
+        tokio::select! {
+            tx = txs.next() => {
+                let Some(tx) = tx else {
+                    return Err(anyhow!("rpc logs subscriptions ended"));
+                };
+                pending.extend(tx);
+                if pending.len() < BATCH_TXS {
+                    continue;
+                }
+            }
+            _ = flush.tick() => {
+                if pending.is_empty() {
+                    continue;
+                }
+            }
+        }
+        let mut batch = mem::take(&mut pending);
+        // back in slot order, the order of the transactions of a slot isn't known from the logs
+        batch.sort_by_key(|it: &Tx| it.slot);
+        let txs_len = batch.len();
+        let batch_id = ingest::push_txs(&mut conn, RPC_WS_DATASET, RPC_WS_DATASET, batch).await?;
+        info!("{txs_len} rpc logs txs queued as {batch_id}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seen_signatures() {
+        let mut seen = SeenSignatures::default();
+        assert!(seen.insert("a"));
+        assert!(!seen.insert("a"));
+        for i in 0..MAX_SEEN_SIGNATURES {
+            assert!(seen.insert(&i.to_string()));
+        }
+        // forgotten as the oldest
+        assert!(seen.insert("a"));
+        assert_eq!(seen.set.len(), MAX_SEEN_SIGNATURES);
+    }
+}
@@ -0,0 +1,282 @@
+//! Geyser grpc ingestion. The transactions of the dex programs are buffered by slot and queued
+//! as one stream request per slot once the block meta of the slot brings its block time.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use anyhow::{Result, anyhow};
+use chrono::Utc;
+use futures::{SinkExt, StreamExt};
+use strum::IntoEnumIterator;
+use tracing::{info, warn};
+use yellowstone_grpc_client::{ClientTlsConfig, GeyserGrpcClient};
+use yellowstone_grpc_proto::prelude::{
+    CommitmentLevel, SubscribeRequest, SubscribeRequestFilterBlocksMeta,
+    SubscribeRequestFilterTransactions, SubscribeRequestPing, SubscribeUpdateTransactionInfo,
+    TokenBalance, subscribe_update::UpdateOneof,
+};
+
+use crate::{
+    common::Dex,
+    config::YellowstoneConfig,
+    ingest,
+    qn_req_processor::Tx,
+    rpc_tx::{
+        RpcCompiledIx, RpcInnerIxs, RpcLoadedAddresses, RpcTokenBalance, RpcTxBody, RpcTxMessage,
+        RpcTxMeta, RpcTxWithMeta, RpcUiTokenAmount,
+    },
+};
+
+/// `dataset` and `stream_name` of the queued requests
+const YELLOWSTONE_DATASET: &str = "yellowstone";
+
+/// The failed transactions are subscribed to with `failed_swaps` only
+fn subscribe_request(config: &YellowstoneConfig, failed_swaps: bool) -> SubscribeRequest {
+    let dexes: Vec<Dex> = if config.dexes.is_empty() {
+        Dex::iter().collect()
+    } else {
+        config.dexes.clone()
+    };
+    let transactions = SubscribeRequestFilterTransactions {
+        vote: Some(false),
+        failed: (!failed_swaps).then_some(false),
+        account_include: dexes.iter().map(|it| it.program_id().to_string()).collect(),
+        ..Default::default()
+    };
+    SubscribeRequest {
+        transactions: HashMap::from([("dexes".to_string(), transactions)]),
+        blocks_meta: HashMap::from([(
+            "blocks".to_string(),
+            SubscribeRequestFilterBlocksMeta::default(),
+        )]),
+        commitment: Some(CommitmentLevel::Confirmed as i32),
+        ..Default::default()
+    }
+}
+
+/// The transaction update in the `getTransaction` shape, converted like the rpc transactions
+fn rpc_tx(info: SubscribeUpdateTransactionInfo) -> Result<RpcTxWithMeta> {
+    let signature = bs58::encode(&info.signature).into_string();
+    let tx = info
+        .transaction
+        .ok_or_else(|| anyhow!("transaction update {signature} has no transaction"))?;
+    let message = tx
+        .message
+        .ok_or_else(|| anyhow!("transaction update {signature} has no message"))?;
+    let meta = info
+        .meta
+        .ok_or_else(|| anyhow!("transaction update {signature} has no meta"))?;
+
+    let pubkeys = |keys: &[Vec<u8>]| -> Vec<String> {
+        keys.iter()
+            .map(|it| bs58::encode(it).into_string())
+            .collect()
+    };
+    let compiled_ix = |program_id_index: u32, accounts: &[u8], data: &[u8]| RpcCompiledIx {
+        program_id_index: program_id_index as usize,
+        accounts: accounts.iter().map(|it| *it as usize).collect(),
+        data: bs58::encode(data).into_string(),
+    };
+    let token_balances = |balances: Vec<TokenBalance>| -> Vec<RpcTokenBalance> {
+        balances
+            .into_iter()
+            .filter_map(|it| {
+                let amount = it.ui_token_amount?;
+                Some(RpcTokenBalance {
+                    account_index: it.account_index as usize,
+                    mint: it.mint,
+                    ui_token_amount: RpcUiTokenAmount {
+                        amount: amount.amount,
+                        decimals: amount.decimals as u8,
+                    },
+                })
+            })
+            .collect()
+    };
+
+    Ok(RpcTxWithMeta {
+        transaction: RpcTxBody {
+            signatures: vec![signature],
+            message: RpcTxMessage {
+                account_keys: pubkeys(&message.account_keys),
+                instructions: message
+                    .instructions
+                    .iter()
+                    .map(|it| compiled_ix(it.program_id_index, &it.accounts, &it.data))
+                    .collect(),
+            },
+        },
+        meta: Some(RpcTxMeta {
+            err: meta
+                .err
+                .map(|it| serde_json::Value::String(bs58::encode(it.err).into_string())),
+            pre_balances: meta.pre_balances,
+            post_balances: meta.post_balances,
+            pre_token_balances: token_balances(meta.pre_token_balances),
+            post_token_balances: token_balances(meta.post_token_balances),
+            inner_instructions: meta
+                .inner_instructions
+                .iter()
+                .map(|inner| RpcInnerIxs {
+                    index: inner.index as usize,
+                    instructions: inner
+                        .instructions
+                        .iter()
+                        .map(|it| compiled_ix(it.program_id_index, &it.accounts, &it.data))
+                        .collect(),
+                })
+                .collect(),
+            log_messages: meta.log_messages,
+            loaded_addresses: RpcLoadedAddresses {
+                writable: pubkeys(&meta.loaded_writable_addresses),
+                readonly: pubkeys(&meta.loaded_readonly_addresses),
+            },
+        }),
+    })
+}
+
+/// Transactions waiting for the block time of their slot
+#[derive(Default)]
+struct PendingSlots {
+    txs: BTreeMap<u64, Vec<RpcTxWithMeta>>,
+    /// convert the failed transactions too, see `failed_swaps` of the config
+    keep_failed: bool,
+}
+
+impl PendingSlots {
+    fn push(&mut self, slot: u64, tx: RpcTxWithMeta) {
+        self.txs.entry(slot).or_default().push(tx);
+    }
+
+    /// The transactions of the slot at its block time, with those of the earlier slots whose
+    /// block meta never came, at the same time.
+    fn complete(&mut self, slot: u64, blk_ts: i64) -> Vec<Tx> {
+        let later = self.txs.split_off(&(slot + 1));
+        let done = std::mem::replace(&mut self.txs, later);
+        let mut txs = vec![];
+        for (tx_slot, slot_txs) in done {
+            if tx_slot != slot {
+                warn!("no yellowstone block meta of slot {tx_slot}, use that of slot {slot}");
+            }
+            for tx in slot_txs {
+                let signature = tx.signature().to_string();
+                match tx.into_kept_tx(tx_slot, blk_ts, self.keep_failed) {
+                    Ok(tx) => txs.extend(tx),
+                    Err(err) => warn!("convert yellowstone tx {signature} error: {err}"),
+                }
+            }
+        }
+        txs
+    }
+}
+
+/// Subscribe to the dex programs and queue their transactions until the stream fails.
+pub async fn start(
+    redis_client: Arc<redis::Client>,
+    config: YellowstoneConfig,
+    failed_swaps: bool,
+) -> Result<()> {
+    let mut client = GeyserGrpcClient::build_from_shared(config.endpoint.clone())?
+        .x_token(config.x_token.clone())?
+        .tls_config(ClientTlsConfig::new().with_native_roots())?
+        .connect()
+        .await?;
+    let (mut subscribe_tx, mut stream) = client
+        .subscribe_with_request(Some(subscribe_request(&config, failed_swaps)))
+        .await?;
+    info!(
+        "start yellowstone ingestion from {}........",
+        config.endpoint
+    );
+
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let mut pending = PendingSlots {
+        keep_failed: failed_swaps,
+        ..Default::default()
+    };
+    while let Some(update) = stream.next().await {
+        match update?.update_oneof {
+            Some(UpdateOneof::Transaction(update)) => {
+                let Some(info) = update.transaction else {
+                    continue;
+                };
+                match rpc_tx(info) {
+                    Ok(tx) => pending.push(update.slot, tx),
+                    Err(err) => warn!("invalid yellowstone transaction update: {err}"),
+                }
+            }
+            Some(UpdateOneof::BlockMeta(meta)) => {
+                let blk_ts = meta
+                    .block_time
+                    .map_or(Utc::now().timestamp(), |it| it.timestamp);
+                let txs = pending.complete(meta.slot, blk_ts);
+                if txs.is_empty() {
+                    continue;
+                }
+                let txs_len = txs.len();
+                let batch_id =
+                    ingest::push_txs(&mut conn, YELLOWSTONE_DATASET, YELLOWSTONE_DATASET, txs)
+                        .await?;
+                info!(
+                    "{txs_len} yellowstone txs of slot {} queued as {batch_id}",
+                    meta.slot
+                );
+            }
+            // keeps the load balancers in front of the endpoint from closing the stream
+            Some(UpdateOneof::Ping(_)) => {
+                let ping = SubscribeRequest {
+                    ping: Some(SubscribeRequestPing { id: 1 }),
+                    ..Default::default()
+                };
+                subscribe_tx.send(ping).await?;
+            }
+            _ => {}
+        }
+    }
+    Err(anyhow!("yellowstone stream ended"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_tx(signature: &str) -> RpcTxWithMeta {
+        RpcTxWithMeta {
+            transaction: RpcTxBody {
+                signatures: vec![signature.to_string()],
+                message: RpcTxMessage {
+                    account_keys: vec![],
+                    instructions: vec![],
+                },
+            },
+            meta: Some(RpcTxMeta {
+                err: None,
+                pre_balances: vec![],
+                post_balances: vec![],
+                pre_token_balances: vec![],
+                post_token_balances: vec![],
+                inner_instructions: vec![],
+                log_messages: vec![],
+                loaded_addresses: RpcLoadedAddresses::default(),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_pending_slots() {
+        let mut pending = PendingSlots::default();
+        pending.push(9, empty_tx("a"));
+        pending.push(10, empty_tx("b"));
+        pending.push(11, empty_tx("c"));
+
+        let txs = pending.complete(10, 100);
+        let txs: Vec<_> = txs
+            .iter()
+            .map(|it| (it.signature.as_str(), it.slot, it.blk_ts))
+            .collect();
+        assert_eq!(txs, vec![("a", 9, 100), ("b", 10, 100)]);
+        assert_eq!(pending.txs.keys().collect::<Vec<_>>(), vec![&11]);
+    }
+}
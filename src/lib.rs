@@ -1,10 +1,42 @@
+pub mod account_violation_watcher;
+pub mod bigquery;
 pub mod cache;
+pub mod cmd;
 pub mod common;
+pub mod compute_budget;
 pub mod config;
+pub mod creator_graph;
+pub mod decimals_checker;
+pub mod helius_tx;
+pub mod inactive_mint_watcher;
+pub mod ingest;
+pub mod lp_lock_watcher;
+pub mod metaplex;
 pub mod meteora;
+pub mod moonshot;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod naming;
+pub mod orca;
+pub mod parse_error;
+pub mod pda;
+pub mod pool_resolver;
+pub mod pressure_watcher;
 pub mod pumpamm;
 pub mod pumpfun;
 pub mod qn_req_processor;
 pub mod raydium;
+pub mod redis_monitor;
+pub mod rpc_provider;
+pub mod rpc_tx;
+pub mod shadow_parser;
+pub mod sol_usd_oracle;
+#[cfg(feature = "pubsub")]
+pub mod state_tracker;
+#[cfg(test)]
+mod test_utils;
+pub mod token_image;
+pub mod wallet_cluster;
+pub mod watchlist;
 pub mod web;
 pub mod webhook;
@@ -0,0 +1,329 @@
+//! Liquidity lock detection of new raydium amm pools. The lp tokens burned or sent to a locker
+//! don't go through the amm program, so the lp mints and their largest holders are read over rpc
+//! instead, for a while after the creation of the pools.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Result;
+use chrono::Utc;
+use futures::{StreamExt, TryStreamExt};
+use solana_sdk::{pubkey, pubkey::Pubkey};
+use tracing::{info, warn};
+
+use crate::{
+    cache::{self, DexEvent, DexPoolCache, LiquidityLockedRecord, PoolSafety},
+    common::{self, Dex},
+    config::LpLockConfig,
+    raydium::accounts::AmmInfo,
+    rpc_provider::RpcProvider,
+};
+
+const MAX_ACCOUNTS_PER_RPC: usize = 100;
+/// `getTokenLargestAccounts` takes one mint, so the lp mints are read this many at a time
+const LARGEST_ACCOUNTS_CONCURRENCY: usize = 4;
+/// spl token and token-2022 share the base layouts
+const MINT_SUPPLY_OFFSET: usize = 36;
+const TOKEN_ACCOUNT_OWNER_OFFSET: usize = 32;
+/// the lp tokens sent to the incinerator can't be moved again
+pub const INCINERATOR: Pubkey = pubkey!("1nc1nerator11111111111111111111111111111111");
+
+fn mint_supply(data: &[u8]) -> Option<u64> {
+    let bytes = data.get(MINT_SUPPLY_OFFSET..MINT_SUPPLY_OFFSET + 8)?;
+    Some(u64::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn token_account_owner(data: &[u8]) -> Option<Pubkey> {
+    let bytes = data.get(TOKEN_ACCOUNT_OWNER_OFFSET..TOKEN_ACCOUNT_OWNER_OFFSET + 32)?;
+    Some(Pubkey::new_from_array(bytes.try_into().ok()?))
+}
+
+#[derive(Debug, PartialEq)]
+struct LpLock {
+    pct_locked: f64,
+    pct_burned: f64,
+    locker: Option<Pubkey>,
+}
+
+/// `lp_amount` is the lp tokens minted by the pool, those missing from the supply were burned.
+/// `holders` are the owners of the largest lp token accounts with their amounts.
+fn lp_lock(
+    lp_amount: u64,
+    supply: u64,
+    holders: &[(Pubkey, u64)],
+    lockers: &HashSet<Pubkey>,
+) -> Option<LpLock> {
+    if lp_amount == 0 {
+        return None;
+    }
+    let burned = lp_amount.saturating_sub(supply);
+    let mut locked_by: HashMap<Pubkey, u64> = HashMap::new();
+    for (owner, amount) in holders {
+        if *owner == INCINERATOR || lockers.contains(owner) {
+            *locked_by.entry(*owner).or_default() += amount;
+        }
+    }
+    let locked = burned + locked_by.values().sum::<u64>();
+    let pct = |amount: u64| (amount as f64 * 100.0 / lp_amount as f64).min(100.0);
+    Some(LpLock {
+        pct_locked: pct(locked),
+        pct_burned: pct(burned),
+        locker: locked_by
+            .into_iter()
+            .max_by_key(|(_, amount)| *amount)
+            .map(|(owner, _)| owner),
+    })
+}
+
+/// The owners of the largest token accounts of each lp mint with their amounts, in the order of
+/// `lp_mints`. The largest accounts are read a few mints at a time, the owners in batches.
+async fn lp_holders(
+    rpc_client: &dyn RpcProvider,
+    lp_mints: &[Pubkey],
+) -> Result<Vec<Vec<(Pubkey, u64)>>> {
+    let largest: Vec<Vec<(Pubkey, u64)>> = futures::stream::iter(lp_mints.iter().copied())
+        .map(|mint| async move { rpc_client.get_token_largest_accounts(&mint).await })
+        .buffered(LARGEST_ACCOUNTS_CONCURRENCY)
+        .try_collect()
+        .await?;
+    let token_accounts: Vec<_> = largest.iter().flatten().map(|(addr, _)| *addr).collect();
+    let mut owners = Vec::with_capacity(token_accounts.len());
+    for chunk in token_accounts.chunks(MAX_ACCOUNTS_PER_RPC) {
+        let accounts = rpc_client.get_multiple_accounts(chunk).await?;
+        owners.extend(
+            accounts
+                .into_iter()
+                .map(|it| it.and_then(|it| token_account_owner(&it.data))),
+        );
+    }
+    let mut owners = owners.into_iter();
+    Ok(largest
+        .into_iter()
+        .map(|accounts| {
+            accounts
+                .into_iter()
+                .zip(owners.by_ref())
+                .filter_map(|((_, amount), owner)| Some((owner?, amount)))
+                .collect()
+        })
+        .collect())
+}
+
+/// Check the watched pools every `interval_secs`, emitting `LiquidityLocked` when more of the lp
+/// tokens of a pool are locked than at the previous check. The pools whose lp tokens are all
+/// locked or burned can't lock more, they're marked on their pool record and skipped.
+pub async fn start(
+    redis_client: Arc<redis::Client>,
+    rpc_client: Arc<dyn RpcProvider>,
+    config: LpLockConfig,
+) -> Result<()> {
+    info!(
+        "start lp lock watcher of the raydium amm pools of the last {} seconds, every {} seconds........",
+        config.watch_secs, config.interval_secs
+    );
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_secs));
+    loop {
+        interval.tick().await;
+        let since = Utc::now() - Duration::from_secs(config.watch_secs);
+        let mut conn = redis_client.get_multiplexed_async_connection().await?;
+        let watched = cache::lp_lock_watched_pools(&mut conn, since).await?;
+        let mut pools = DexPoolCache::new(conn.clone());
+        pools.prefetch(watched.iter().copied()).await?;
+        let mut unlocked = vec![];
+        for pool in watched {
+            if !pools.get(&pool).await?.is_some_and(|it| it.is_lp_locked) {
+                unlocked.push(pool);
+            }
+        }
+        if unlocked.is_empty() {
+            continue;
+        }
+
+        let slot = rpc_client.get_slot().await?;
+        let mut events = vec![];
+        for chunk in unlocked.chunks(MAX_ACCOUNTS_PER_RPC) {
+            let accounts = rpc_client.get_multiple_accounts(chunk).await?;
+            let amms: Vec<_> = chunk
+                .iter()
+                .zip(accounts)
+                .filter_map(|(pool, account)| {
+                    Some((*pool, AmmInfo::from_account_data(&account?.data).ok()?))
+                })
+                .collect();
+            let lp_mints: Vec<_> = amms.iter().map(|(_, amm)| amm.lp_mint).collect();
+            let lp_mint_accounts = rpc_client.get_multiple_accounts(&lp_mints).await?;
+            let supplied: Vec<_> = amms
+                .iter()
+                .zip(lp_mint_accounts)
+                .filter_map(|((pool, amm), lp_mint_account)| {
+                    let supply = lp_mint_account.and_then(|it| mint_supply(&it.data));
+                    if supply.is_none() {
+                        warn!("lp mint of pool {pool} not found");
+                    }
+                    Some((*pool, amm, supply?))
+                })
+                .collect();
+            let lp_mints: Vec<_> = supplied.iter().map(|(_, amm, _)| amm.lp_mint).collect();
+            let holders = lp_holders(rpc_client.as_ref(), &lp_mints).await?;
+            for ((pool, amm, supply), holders) in supplied.into_iter().zip(holders) {
+                let checked = check_pool(&mut pools, &config, pool, amm, supply, &holders, slot);
+                match checked.await {
+                    Ok(Some(record)) => events.push(DexEvent::LiquidityLocked(record)),
+                    Ok(None) => {}
+                    Err(err) => warn!("check lp lock of pool {pool} error: {err}"),
+                }
+            }
+        }
+        pools.flush().await?;
+        cache::rpush_dex_evts(&mut conn, None, &events).await?;
+        info!(
+            "lp locks of {} pools checked, {} locked more",
+            unlocked.len(),
+            events.len()
+        );
+    }
+}
+
+/// Save the lp lock state of the pool, with the event if more is locked than before
+async fn check_pool(
+    pools: &mut DexPoolCache,
+    config: &LpLockConfig,
+    pool: Pubkey,
+    amm: &AmmInfo,
+    supply: u64,
+    holders: &[(Pubkey, u64)],
+    slot: u64,
+) -> Result<Option<LiquidityLockedRecord>> {
+    // validated with the config
+    let lockers: HashSet<Pubkey> = config
+        .lockers
+        .iter()
+        .filter_map(|it| it.parse().ok())
+        .collect();
+    let Some(lock) = lp_lock(amm.lp_amount, supply, holders, &lockers) else {
+        return Ok(None);
+    };
+
+    let conn = pools.conn();
+    let previous = cache::get_pool_safety(conn, &pool)
+        .await?
+        .map_or(0.0, |it| it.lp_locked_pct);
+    let now = Utc::now();
+    let safety = PoolSafety {
+        lp_locked_pct: lock.pct_locked,
+        lp_burned_pct: lock.pct_burned,
+        locker: lock.locker,
+        updated_at: now,
+    };
+    cache::save_pool_safety(conn, &pool, &safety).await?;
+    if lock.pct_locked >= 100.0
+        && let Some(mut record) = pools.get(&pool).await?
+    {
+        record.is_lp_locked = true;
+        pools.save(record);
+    }
+    if lock.pct_locked < previous + config.min_pct_change {
+        return Ok(None);
+    }
+
+    let lp_mint = amm.lp_mint;
+    let (coin_mint, pc_mint) = (amm.coin_vault_mint, amm.pc_vault_mint);
+    let mint = if common::quote_mints().contains(&coin_mint) {
+        pc_mint
+    } else {
+        coin_mint
+    };
+    Ok(Some(LiquidityLockedRecord {
+        ts: now,
+        slot,
+        dex: Dex::RaydiumAmm,
+        pool,
+        mint,
+        lp_mint,
+        pct_locked: lock.pct_locked,
+        pct_burned: lock.pct_burned,
+        locker: lock.locker,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::account::Account;
+
+    use super::*;
+    use crate::rpc_provider::MockRpcProvider;
+
+    #[test]
+    fn test_lp_lock() {
+        let locker = Pubkey::new_unique();
+        let lockers = HashSet::from([locker]);
+        let holders = [
+            (Pubkey::new_unique(), 300),
+            (locker, 200),
+            (INCINERATOR, 100),
+        ];
+        let lock = lp_lock(1_000, 900, &holders, &lockers).unwrap();
+        assert_eq!(
+            lock,
+            LpLock {
+                pct_locked: 40.0,
+                pct_burned: 10.0,
+                locker: Some(locker),
+            }
+        );
+
+        let lock = lp_lock(1_000, 0, &[], &lockers).unwrap();
+        assert_eq!((lock.pct_locked, lock.locker), (100.0, None));
+        assert_eq!(lp_lock(0, 0, &[], &lockers), None);
+
+        let mut mint = vec![0; 82];
+        mint[MINT_SUPPLY_OFFSET..MINT_SUPPLY_OFFSET + 8].copy_from_slice(&900u64.to_le_bytes());
+        assert_eq!(mint_supply(&mint), Some(900));
+        assert_eq!(mint_supply(&[0; 10]), None);
+    }
+
+    #[tokio::test]
+    async fn test_lp_holders() {
+        let token_account = |owner: Pubkey| {
+            let mut data = vec![0; 165];
+            data[TOKEN_ACCOUNT_OWNER_OFFSET..TOKEN_ACCOUNT_OWNER_OFFSET + 32]
+                .copy_from_slice(owner.as_ref());
+            Account {
+                data,
+                ..Default::default()
+            }
+        };
+        let (lp_a, lp_b, empty) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (acc_a, acc_b, acc_c, closed) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let owner = Pubkey::new_unique();
+        let rpc_client = MockRpcProvider::default()
+            .with_largest_accounts(lp_a, vec![(acc_a, 300), (closed, 200), (acc_b, 100)])
+            .with_largest_accounts(lp_b, vec![(acc_c, 50)])
+            .with_account(acc_a, token_account(owner))
+            .with_account(acc_b, token_account(INCINERATOR))
+            .with_account(acc_c, token_account(owner));
+
+        let holders = lp_holders(&rpc_client, &[lp_a, empty, lp_b]).await.unwrap();
+        // the owners of each mint, the accounts not found left out
+        assert_eq!(
+            holders,
+            vec![
+                vec![(owner, 300), (INCINERATOR, 100)],
+                vec![],
+                vec![(owner, 50)],
+            ]
+        );
+    }
+}
@@ -1,81 +1,142 @@
-use std::{path::PathBuf, sync::Arc, time::Duration};
+use std::path::PathBuf;
 
-use anyhow::{Result, anyhow};
-use clap::Parser;
+use anyhow::Result;
+use clap::{Parser, Subcommand};
 use sol_dex_data_hub::{
-    config::AppConfig,
-    qn_req_processor,
-    web::{self, WebAppContext},
-    webhook::DexEvtWebhook,
+    cmd,
+    common::Dex,
+    config::{AppConfig, LogConfig, LogRotation},
+    ingest::backfill::SlotRange,
 };
-use tokio::fs;
-use tracing::{error, info};
-use tracing_subscriber::{EnvFilter, Registry, fmt::Layer, layer::SubscriberExt};
+use tracing_appender::{non_blocking::WorkerGuard, rolling};
+use tracing_subscriber::{EnvFilter, Layer as _, Registry, fmt::Layer, layer::SubscriberExt};
 
 #[derive(Debug, Parser)]
-#[command(version, about)]
+#[command(name = "sol-dex-hub", version, about)]
 struct Cli {
-    #[arg(long, short)]
-    pub config: PathBuf,
+    #[command(subcommand)]
+    pub command: Command,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-    let subscriber = Registry::default().with(env_filter).with(
-        Layer::default()
-            .with_writer(std::io::stdout)
-            .with_ansi(false),
-    );
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run web server, quicknode stream processor and dex events webhook
+    Serve {
+        #[arg(long, short)]
+        config: PathBuf,
+        /// also queue the dex transactions of the confirmed blocks from this slot, to repair a gap
+        #[arg(long, requires = "backfill_to_slot")]
+        backfill_from_slot: Option<u64>,
+        /// last slot of the backfill, inclusive
+        #[arg(long, requires = "backfill_from_slot")]
+        backfill_to_slot: Option<u64>,
+    },
+    /// Push saved quicknode stream request bodies into the request queue
+    Replay {
+        #[arg(long, short)]
+        config: PathBuf,
+        files: Vec<PathBuf>,
+    },
+    /// Fetch a transaction from rpc and print the dex events parsed from it
+    InspectTx {
+        #[arg(long, short)]
+        config: PathBuf,
+        signature: String,
+    },
+    /// Print the quicknode streams filter function matching the dex parsers
+    QnFilter {
+        /// only include these dexes, all supported dexes by default
+        #[arg(long = "dex")]
+        dexes: Vec<Dex>,
+        /// also emit the failed transactions, for `failed_swaps` in config.json
+        #[arg(long)]
+        failed_swaps: bool,
+    },
+    /// Run a webhook server which only logs received dex events
+    FakeWebhook {
+        #[arg(long, default_value = "0.0.0.0:9999")]
+        listen_on: String,
+    },
+}
 
-    tracing::subscriber::set_global_default(subscriber)?;
+/// The returned guard flushes the file logs on drop, keep it until exit.
+fn init_tracing(log: &LogConfig) -> Result<Option<WorkerGuard>> {
+    let stdout_filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(log.stdout_level.as_deref().unwrap_or("info")))?;
+    let stdout_layer = Layer::default()
+        .with_writer(std::io::stdout)
+        .with_ansi(false)
+        .with_filter(stdout_filter);
 
-    let cli = Cli::parse();
-    let content = fs::read_to_string(cli.config).await?;
-    let config = serde_json::from_str::<AppConfig>(&content)
-        .map_err(|err| anyhow!("parse config json file error: {err}"))?;
+    let Some(file) = &log.file else {
+        tracing::subscriber::set_global_default(Registry::default().with(stdout_layer))?;
+        return Ok(None);
+    };
 
-    let context = WebAppContext::init(&config).await?;
+    let rotation = match file.rotation {
+        LogRotation::Hourly => rolling::Rotation::HOURLY,
+        LogRotation::Daily => rolling::Rotation::DAILY,
+        LogRotation::Never => rolling::Rotation::NEVER,
+    };
+    let mut appender = rolling::Builder::new()
+        .rotation(rotation)
+        .filename_prefix(&file.prefix);
+    if let Some(max_files) = file.max_files {
+        appender = appender.max_log_files(max_files);
+    }
+    let appender = appender.build(&file.dir)?;
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+    let file_layer = Layer::default()
+        .with_writer(writer)
+        .with_ansi(false)
+        .with_filter(EnvFilter::try_new(&file.level)?);
 
-    let redis_client = context.redis_client.clone();
-    // process quick node stream
-    tokio::spawn(async move {
-        loop {
-            let redis_client = redis_client.clone();
-            match qn_req_processor::start(redis_client).await {
-                Ok(_) => info!("qn request processor succeeded"),
-                Err(err) => error!("qn reqwest processor error: {err}"),
-            }
-            tokio::time::sleep(Duration::from_millis(100)).await;
-        }
-    });
+    let subscriber = Registry::default().with(stdout_layer).with(file_layer);
+    tracing::subscriber::set_global_default(subscriber)?;
+    Ok(Some(guard))
+}
 
-    let redis_client = context.redis_client.clone();
-    let webhook_endpoint = config.webhook_endpoint.clone();
-    let http_client = Arc::new(
-        reqwest::ClientBuilder::new()
-            .connect_timeout(Duration::from_millis(200))
-            .timeout(Duration::from_secs(1))
-            .build()?,
-    );
+async fn load_config(path: PathBuf) -> Result<(AppConfig, Option<WorkerGuard>)> {
+    let config = AppConfig::from_file(path).await?;
+    let guard = init_tracing(&config.log)?;
+    Ok((config, guard))
+}
 
-    tokio::spawn(async move {
-        loop {
-            let redis_client = redis_client.clone();
-            let webhook = DexEvtWebhook {
-                redis_client,
-                http_client: http_client.clone(),
-                endpoint: webhook_endpoint.clone(),
-            };
-            match webhook.start().await {
-                Ok(_) => info!("webhook processor succeeded"),
-                Err(err) => error!("webhook processor error: {err}"),
-            }
-            tokio::time::sleep(Duration::from_millis(100)).await;
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    match cli.command {
+        Command::Serve {
+            config,
+            backfill_from_slot,
+            backfill_to_slot,
+        } => {
+            let (config, _guard) = load_config(config).await?;
+            let backfill = backfill_from_slot
+                .zip(backfill_to_slot)
+                .map(|(from_slot, to_slot)| SlotRange { from_slot, to_slot });
+            cmd::serve::run(config, backfill).await?;
         }
-    });
-
-    web::start(context, &config.listen_on).await?;
+        Command::Replay { config, files } => {
+            let (config, _guard) = load_config(config).await?;
+            cmd::replay::run(config, files).await?;
+        }
+        Command::InspectTx { config, signature } => {
+            let (config, _guard) = load_config(config).await?;
+            cmd::inspect_tx::run(config, signature).await?;
+        }
+        Command::QnFilter {
+            dexes,
+            failed_swaps,
+        } => {
+            init_tracing(&LogConfig::default())?;
+            cmd::qn_filter::run(&dexes, failed_swaps)?;
+        }
+        Command::FakeWebhook { listen_on } => {
+            init_tracing(&LogConfig::default())?;
+            cmd::fake_webhook::run(&listen_on).await?;
+        }
+    }
 
     Ok(())
 }
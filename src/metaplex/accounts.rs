@@ -0,0 +1,65 @@
+use anyhow::{Result, anyhow};
+use borsh::BorshDeserialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{pda, rpc_provider::RpcProvider};
+
+/// Leading fields of a token metadata account, the strings are padded with `\0`
+#[derive(Debug, Clone, BorshDeserialize)]
+pub struct TokenMetadata {
+    pub key: u8,
+    pub update_authority: Pubkey,
+    pub mint: Pubkey,
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+}
+
+impl TokenMetadata {
+    pub async fn from_rpc(rpc_client: &dyn RpcProvider, mint: &Pubkey) -> Result<Self> {
+        let account = rpc_client
+            .get_account(&pda::metaplex_metadata(mint))
+            .await?;
+        Self::from_account_data(&account.data)
+    }
+
+    /// Fields after the uri are ignored
+    pub fn from_account_data(mut data: &[u8]) -> Result<Self> {
+        let mut result = Self::deserialize(&mut data)
+            .map_err(|err| anyhow!("deserialize token metadata error: {err}"))?;
+        for field in [&mut result.name, &mut result.symbol, &mut result.uri] {
+            *field = field.trim_end_matches('\0').to_string();
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_metadata_from_account_data() {
+        let mint = Pubkey::new_unique();
+        let mut data = vec![4u8];
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(mint.as_ref());
+        for (text, padded_len) in [
+            ("Token", 32),
+            ("TKN", 10),
+            ("https://example.com/t.json", 200),
+        ] {
+            let mut field = text.as_bytes().to_vec();
+            field.resize(padded_len, 0);
+            data.extend_from_slice(&(padded_len as u32).to_le_bytes());
+            data.extend_from_slice(&field);
+        }
+        // seller fee bps and the rest
+        data.extend_from_slice(&[0u8; 16]);
+
+        let metadata = TokenMetadata::from_account_data(&data).unwrap();
+        assert_eq!(metadata.mint, mint);
+        assert_eq!(metadata.symbol, "TKN");
+        assert_eq!(metadata.uri, "https://example.com/t.json");
+    }
+}
@@ -0,0 +1,7 @@
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+
+pub mod accounts;
+
+pub const METAPLEX_METADATA_PROGRAM_ID: Pubkey =
+    pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
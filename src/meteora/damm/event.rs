@@ -3,6 +3,8 @@ use base64::{Engine, prelude::BASE64_STANDARD};
 use borsh::BorshDeserialize;
 use solana_sdk::pubkey::Pubkey;
 
+use crate::{common::Dex, parse_error::DexParseError};
+
 use super::MeteoraDammPoolType;
 
 #[derive(Debug, BorshDeserialize)]
@@ -33,36 +35,76 @@ pub struct MeteoraDammPoolCreated {
     pub pool: Pubkey,
 }
 
+#[derive(Debug, BorshDeserialize)]
+pub struct MeteoraDammAddLiquidity {
+    /// LP token user received from the pool.
+    pub lp_mint_amount: u64,
+    /// Token A amount user deposited to the pool.
+    pub token_a_amount: u64,
+    /// Token B amount user deposited to the pool.
+    pub token_b_amount: u64,
+}
+
+#[derive(Debug, BorshDeserialize)]
+pub struct MeteoraDammRemoveLiquidity {
+    /// LP token user burned.
+    pub lp_unmint_amount: u64,
+    /// Token A amount user received from the pool.
+    pub token_a_out_amount: u64,
+    /// Token B amount user received from the pool.
+    pub token_b_out_amount: u64,
+}
+
 #[derive(Debug)]
 pub enum MeteoraDammEvents {
     Swap(MeteoraDammSwap),
     PoolCreated(MeteoraDammPoolCreated),
+    AddLiquidity(MeteoraDammAddLiquidity),
+    RemoveLiquidity(MeteoraDammRemoveLiquidity),
 }
 
 impl MeteoraDammEvents {
-    pub fn from_log(log: &str) -> Result<Self> {
-        let bytes = BASE64_STANDARD.decode(log)?;
+    pub fn from_log(log: &str) -> Result<Self, DexParseError> {
+        let bytes = BASE64_STANDARD
+            .decode(log)
+            .map_err(|err| DexParseError::corrupt(Dex::MeteoraDamm, err))?;
+        let (discriminator, fields) = bytes.split_at_checked(8).ok_or_else(|| {
+            DexParseError::corrupt(Dex::MeteoraDamm, "meteora damm log is too short")
+        })?;
 
-        let result = match &bytes[..8] {
+        let result = match discriminator {
             [81, 108, 227, 190, 205, 208, 10, 196] => {
-                let evt: MeteoraDammSwap = borsh::from_slice(&bytes[8..])?;
+                let evt: MeteoraDammSwap = borsh::from_slice(fields).map_err(corrupt)?;
                 Self::Swap(evt)
             }
             [202, 44, 41, 88, 104, 220, 157, 82] => {
-                let evt: MeteoraDammPoolCreated = borsh::from_slice(&bytes[8..])?;
+                let evt: MeteoraDammPoolCreated = borsh::from_slice(fields).map_err(corrupt)?;
                 Self::PoolCreated(evt)
             }
-            _ => anyhow::bail!("log is not recognized as meteora damm log: {log}"),
+            [31, 94, 125, 90, 227, 52, 61, 186] => {
+                let evt: MeteoraDammAddLiquidity = borsh::from_slice(fields).map_err(corrupt)?;
+                Self::AddLiquidity(evt)
+            }
+            [116, 244, 97, 232, 103, 31, 152, 58] => {
+                let evt: MeteoraDammRemoveLiquidity = borsh::from_slice(fields).map_err(corrupt)?;
+                Self::RemoveLiquidity(evt)
+            }
+            _ => return Err(DexParseError::unknown(Dex::MeteoraDamm, discriminator)),
         };
 
         Ok(result)
     }
 }
 
+fn corrupt(err: std::io::Error) -> DexParseError {
+    DexParseError::corrupt(Dex::MeteoraDamm, err)
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use crate::test_utils::{Encoding, decodes_arbitrary_bytes};
 
     #[test]
     pub fn test_decode_swap_evt() {
@@ -87,4 +129,59 @@ mod tests {
         let created_evt = MeteoraDammEvents::from_log(encoded_evt).unwrap();
         println!("pool created evt: {created_evt:#?}");
     }
+
+    #[test]
+    fn test_decode_liquidity_evts() {
+        let fields: Vec<u8> = [10u64, 20, 30]
+            .iter()
+            .flat_map(|it| it.to_le_bytes())
+            .collect();
+        let mut data = vec![31, 94, 125, 90, 227, 52, 61, 186];
+        data.extend_from_slice(&fields);
+        let Ok(MeteoraDammEvents::AddLiquidity(evt)) =
+            MeteoraDammEvents::from_log(&BASE64_STANDARD.encode(&data))
+        else {
+            panic!("should be an add liquidity event");
+        };
+        assert_eq!(
+            (evt.lp_mint_amount, evt.token_a_amount, evt.token_b_amount),
+            (10, 20, 30)
+        );
+
+        let mut data = vec![116, 244, 97, 232, 103, 31, 152, 58];
+        data.extend_from_slice(&fields);
+        let Ok(MeteoraDammEvents::RemoveLiquidity(evt)) =
+            MeteoraDammEvents::from_log(&BASE64_STANDARD.encode(&data))
+        else {
+            panic!("should be a remove liquidity event");
+        };
+        assert_eq!(evt.token_b_out_amount, 30);
+    }
+
+    #[test]
+    fn test_decode_short_log() {
+        assert!(MeteoraDammEvents::from_log("").is_err());
+        assert!(MeteoraDammEvents::from_log(&BASE64_STANDARD.encode([81, 108, 227])).is_err());
+    }
+
+    const DISCRIMINATORS: [[u8; 8]; 4] = [
+        [81, 108, 227, 190, 205, 208, 10, 196],
+        [202, 44, 41, 88, 104, 220, 157, 82],
+        [31, 94, 125, 90, 227, 52, 61, 186],
+        [116, 244, 97, 232, 103, 31, 152, 58],
+    ];
+
+    quickcheck::quickcheck! {
+        fn test_decode_arbitrary_bytes(kind: u8, fields: Vec<u8>, log: String) -> bool {
+            decodes_arbitrary_bytes(
+                Encoding::Base64,
+                &[],
+                &DISCRIMINATORS,
+                kind,
+                &fields,
+                &log,
+                MeteoraDammEvents::from_log,
+            )
+        }
+    }
 }
@@ -0,0 +1,190 @@
+use anyhow::Result;
+use borsh::BorshDeserialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{common::Dex, parse_error::DexParseError};
+
+/// Swaps of quote for base tokens are buys of the base token
+pub const TRADE_DIRECTION_QUOTE_TO_BASE: u8 = 1;
+
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct MeteoraDbcInitializePoolEvent {
+    // Virtual pool of the bonding curve
+    pub pool: Pubkey,
+    // Config of the launchpad, with the quote mint and the curve
+    pub config: Pubkey,
+    pub creator: Pubkey,
+    pub base_mint: Pubkey,
+    // 0 spl token, 1 token-2022
+    pub pool_type: u8,
+    pub activation_point: u64,
+}
+
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct MeteoraDbcSwapParameters {
+    pub amount_in: u64,
+    pub minimum_amount_out: u64,
+}
+
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct MeteoraDbcSwapResult {
+    // Input amount after the fees taken from the input
+    pub actual_input_amount: u64,
+    pub output_amount: u64,
+    pub next_sqrt_price: u128,
+    pub trading_fee: u64,
+    pub protocol_fee: u64,
+    pub referral_fee: u64,
+}
+
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct MeteoraDbcSwapEvent {
+    pub pool: Pubkey,
+    pub config: Pubkey,
+    // 0 base to quote, 1 quote to base
+    pub trade_direction: u8,
+    pub has_referral: bool,
+    pub params: MeteoraDbcSwapParameters,
+    pub swap_result: MeteoraDbcSwapResult,
+    // Amount transferred in by the trader, the fees included
+    pub amount_in: u64,
+    pub current_timestamp: u64,
+}
+
+impl MeteoraDbcSwapEvent {
+    pub fn is_buy(&self) -> bool {
+        self.trade_direction == TRADE_DIRECTION_QUOTE_TO_BASE
+    }
+}
+
+/// The curve reached its migration threshold, the pool graduates to a damm pool
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct MeteoraDbcCurveCompleteEvent {
+    pub pool: Pubkey,
+    pub config: Pubkey,
+    pub base_reserve: u64,
+    pub quote_reserve: u64,
+}
+
+#[derive(Debug)]
+pub enum MeteoraDbcEvents {
+    InitializePool(MeteoraDbcInitializePoolEvent),
+    Swap(MeteoraDbcSwapEvent),
+    CurveComplete(MeteoraDbcCurveCompleteEvent),
+}
+
+impl MeteoraDbcEvents {
+    pub fn from_cpi_log(log: &str) -> Result<Self, DexParseError> {
+        let bytes = bs58::decode(log)
+            .into_vec()
+            .map_err(|err| DexParseError::corrupt(Dex::MeteoraDbc, err))?;
+        let (discriminator, fields) = bytes
+            .get(8..)
+            .and_then(|it| it.split_at_checked(8))
+            .ok_or_else(|| {
+                DexParseError::corrupt(Dex::MeteoraDbc, "meteora dbc log is too short")
+            })?;
+
+        let result = match discriminator {
+            [228, 50, 246, 85, 203, 66, 134, 37] => {
+                let evt: MeteoraDbcInitializePoolEvent =
+                    borsh::from_slice(fields).map_err(corrupt)?;
+                Self::InitializePool(evt)
+            }
+            [27, 60, 21, 213, 138, 170, 187, 147] => {
+                let evt: MeteoraDbcSwapEvent = borsh::from_slice(fields).map_err(corrupt)?;
+                Self::Swap(evt)
+            }
+            [229, 231, 86, 84, 156, 134, 75, 24] => {
+                let evt: MeteoraDbcCurveCompleteEvent =
+                    borsh::from_slice(fields).map_err(corrupt)?;
+                Self::CurveComplete(evt)
+            }
+            _ => return Err(DexParseError::unknown(Dex::MeteoraDbc, discriminator)),
+        };
+
+        Ok(result)
+    }
+}
+
+fn corrupt(err: std::io::Error) -> DexParseError {
+    DexParseError::corrupt(Dex::MeteoraDbc, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rpc_tx::ANCHOR_EVENT_IX_TAG,
+        test_utils::{Encoding, decodes_arbitrary_bytes},
+    };
+
+    const DISCRIMINATORS: [[u8; 8]; 3] = [
+        [228, 50, 246, 85, 203, 66, 134, 37],
+        [27, 60, 21, 213, 138, 170, 187, 147],
+        [229, 231, 86, 84, 156, 134, 75, 24],
+    ];
+
+    fn cpi_log(discriminator: [u8; 8], fields: &[u8]) -> String {
+        let mut data = vec![0xe4, 0x45, 0xa5, 0x2e, 0x51, 0xcb, 0x9a, 0x1d];
+        data.extend_from_slice(&discriminator);
+        data.extend_from_slice(fields);
+        bs58::encode(data).into_string()
+    }
+
+    #[test]
+    fn test_decode_swap_and_complete_evts() {
+        let pool = Pubkey::new_unique();
+        let mut swap = vec![];
+        swap.extend_from_slice(pool.as_ref());
+        swap.extend_from_slice(Pubkey::new_unique().as_ref());
+        swap.extend_from_slice(&[TRADE_DIRECTION_QUOTE_TO_BASE, 0]);
+        for amt in [1_000_000_000u64, 1, 990_000_000, 35_000_000_000_000] {
+            swap.extend_from_slice(&amt.to_le_bytes());
+        }
+        swap.extend_from_slice(&[0u8; 16]);
+        for amt in [10_000_000u64, 0, 0, 1_000_000_000, 1_750_000_000] {
+            swap.extend_from_slice(&amt.to_le_bytes());
+        }
+
+        let Ok(MeteoraDbcEvents::Swap(evt)) =
+            MeteoraDbcEvents::from_cpi_log(&cpi_log(DISCRIMINATORS[1], &swap))
+        else {
+            panic!("should be a swap event");
+        };
+        assert_eq!(evt.pool, pool);
+        assert!(evt.is_buy());
+        assert_eq!(evt.swap_result.actual_input_amount, 990_000_000);
+        assert_eq!(evt.swap_result.output_amount, 35_000_000_000_000);
+        assert_eq!(evt.amount_in, 1_000_000_000);
+
+        let mut complete = vec![];
+        complete.extend_from_slice(pool.as_ref());
+        complete.extend_from_slice(Pubkey::new_unique().as_ref());
+        complete.extend_from_slice(&200_000_000_000_000u64.to_le_bytes());
+        complete.extend_from_slice(&85_000_000_000u64.to_le_bytes());
+        let Ok(MeteoraDbcEvents::CurveComplete(evt)) =
+            MeteoraDbcEvents::from_cpi_log(&cpi_log(DISCRIMINATORS[2], &complete))
+        else {
+            panic!("should be a curve complete event");
+        };
+        assert_eq!(evt.pool, pool);
+        assert_eq!(evt.quote_reserve, 85_000_000_000);
+
+        assert!(MeteoraDbcEvents::from_cpi_log(&cpi_log([0; 8], &complete)).is_err());
+    }
+
+    quickcheck::quickcheck! {
+        fn test_decode_arbitrary_bytes(kind: u8, fields: Vec<u8>, log: String) -> bool {
+            decodes_arbitrary_bytes(
+                Encoding::Base58,
+                &ANCHOR_EVENT_IX_TAG,
+                &DISCRIMINATORS,
+                kind,
+                &fields,
+                &log,
+                MeteoraDbcEvents::from_cpi_log,
+            )
+        }
+    }
+}
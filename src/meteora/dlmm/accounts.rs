@@ -2,9 +2,10 @@ use std::str::FromStr;
 
 use anyhow::Result;
 use borsh::BorshDeserialize;
-use solana_rpc_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::pubkey::Pubkey;
 
+use crate::rpc_provider::RpcProvider;
+
 #[derive(Debug, Clone, Copy, BorshDeserialize)]
 pub struct StaticParameters {
     /// Used for base fee calculation. base_fee_rate = base_factor * bin_step
@@ -137,13 +138,46 @@ pub struct LbPair {
 }
 
 impl LbPair {
-    pub async fn from_rpc(rpc_client: &RpcClient, lb_pair_addr: &str) -> Result<Self> {
+    pub async fn from_rpc(rpc_client: &dyn RpcProvider, lb_pair_addr: &str) -> Result<Self> {
         let pubkey = Pubkey::from_str(lb_pair_addr)?;
         let account = rpc_client.get_account(&pubkey).await?;
 
-        let result: LbPair = borsh::from_slice(&account.data)
+        Self::from_account_data(&account.data)
+    }
+
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        let result: LbPair = borsh::from_slice(data)
             .map_err(|err| anyhow::anyhow!("deserialize meteora dlmm lbpair error: {err}"))?;
 
         Ok(result)
     }
+
+    /// Price of one token x in token y of the active bin, in ui amounts
+    pub fn active_price(&self, decimals_x: u8, decimals_y: u8) -> f64 {
+        bin_price(self.active_id, self.bin_step, decimals_x, decimals_y)
+    }
+
+    /// Base fee without the volatile part, in bps
+    pub fn base_fee_bps(&self) -> f64 {
+        self.parameters.base_factor as f64 * self.bin_step as f64 / 10_000.0
+    }
+}
+
+/// Price of one token x in token y of bin `bin_id`, in ui amounts
+pub fn bin_price(bin_id: i32, bin_step: u16, decimals_x: u8, decimals_y: u8) -> f64 {
+    let price = (1.0 + bin_step as f64 / 10_000.0).powi(bin_id);
+    price * 10f64.powi(decimals_x as i32 - decimals_y as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bin_price() {
+        assert_eq!(bin_price(0, 25, 9, 9), 1.0);
+        // 1.0025^-3000 * 10^(6 - 9)
+        let price = bin_price(-3000, 25, 6, 9);
+        assert!((price - 5.583e-7).abs() < 1e-10, "{price}");
+    }
 }
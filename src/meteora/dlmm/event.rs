@@ -2,6 +2,8 @@ use anyhow::Result;
 use borsh::BorshDeserialize;
 use solana_sdk::pubkey::Pubkey;
 
+use crate::{common::Dex, parse_error::DexParseError};
+
 #[derive(Debug, Clone, Copy, BorshDeserialize)]
 pub struct MeteoraDlmmSwapEvent {
     // Liquidity pool pair
@@ -40,36 +42,88 @@ pub struct MeteoraLbPairCreateEvent {
     pub token_y: Pubkey,
 }
 
+/// `AddLiquidity` and `RemoveLiquidity` have the same fields
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct MeteoraDlmmLiquidityEvent {
+    // Liquidity pool pair
+    pub lb_pair: Pubkey,
+    // Address of the liquidity provider
+    pub from: Pubkey,
+    // Position receiving or giving the liquidity
+    pub position: Pubkey,
+    // Amounts of token X and token Y
+    pub amounts: [u64; 2],
+    // Active bin ID
+    pub active_bin_id: i32,
+}
+
+/// `Swap2` and `AddLiquidity2` of the token-2022 instructions start with the fields of
+/// `Swap` and `AddLiquidity`, their extensions (transfer fees, fee changes) are ignored.
 #[derive(Debug)]
 pub enum MeteoraDlmmEvents {
     Swap(MeteoraDlmmSwapEvent),
+    Swap2(MeteoraDlmmSwapEvent),
     LbPairCreate(MeteoraLbPairCreateEvent),
+    AddLiquidity(MeteoraDlmmLiquidityEvent),
+    AddLiquidity2(MeteoraDlmmLiquidityEvent),
+    RemoveLiquidity(MeteoraDlmmLiquidityEvent),
 }
 
 impl MeteoraDlmmEvents {
-    pub fn from_cpi_log(log: &str) -> Result<Self> {
-        let bytes = bs58::decode(log).into_vec()?;
-        let bytes = &bytes[8..];
+    pub fn from_cpi_log(log: &str) -> Result<Self, DexParseError> {
+        let bytes = bs58::decode(log)
+            .into_vec()
+            .map_err(|err| DexParseError::corrupt(Dex::MeteoraDlmm, err))?;
+        let (discriminator, mut fields) = bytes
+            .get(8..)
+            .and_then(|it| it.split_at_checked(8))
+            .ok_or_else(|| {
+                DexParseError::corrupt(Dex::MeteoraDlmm, "meteora dlmm log is too short")
+            })?;
 
-        let result = match &bytes[..8] {
+        let result = match discriminator {
             [81, 108, 227, 190, 205, 208, 10, 196] => {
-                let evt: MeteoraDlmmSwapEvent = borsh::from_slice(&bytes[8..])?;
+                let evt: MeteoraDlmmSwapEvent = borsh::from_slice(fields).map_err(corrupt)?;
                 Self::Swap(evt)
             }
+            [49, 22, 214, 181, 97, 215, 44, 123] => {
+                let evt = MeteoraDlmmSwapEvent::deserialize(&mut fields).map_err(corrupt)?;
+                Self::Swap2(evt)
+            }
             [185, 74, 252, 125, 27, 215, 188, 111] => {
-                let evt: MeteoraLbPairCreateEvent = borsh::from_slice(&bytes[8..])?;
+                let evt: MeteoraLbPairCreateEvent = borsh::from_slice(fields).map_err(corrupt)?;
                 Self::LbPairCreate(evt)
             }
-            _ => anyhow::bail!("log is not recognized as meteora dlmm log: {log}"),
+            [31, 94, 125, 90, 227, 52, 61, 186] => {
+                let evt: MeteoraDlmmLiquidityEvent = borsh::from_slice(fields).map_err(corrupt)?;
+                Self::AddLiquidity(evt)
+            }
+            [78, 44, 6, 161, 98, 213, 227, 87] => {
+                let evt = MeteoraDlmmLiquidityEvent::deserialize(&mut fields).map_err(corrupt)?;
+                Self::AddLiquidity2(evt)
+            }
+            [116, 244, 97, 232, 103, 31, 152, 58] => {
+                let evt: MeteoraDlmmLiquidityEvent = borsh::from_slice(fields).map_err(corrupt)?;
+                Self::RemoveLiquidity(evt)
+            }
+            _ => return Err(DexParseError::unknown(Dex::MeteoraDlmm, discriminator)),
         };
 
         Ok(result)
     }
 }
 
+fn corrupt(err: std::io::Error) -> DexParseError {
+    DexParseError::corrupt(Dex::MeteoraDlmm, err)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        rpc_tx::ANCHOR_EVENT_IX_TAG,
+        test_utils::{Encoding, decodes_arbitrary_bytes},
+    };
 
     #[test]
     fn test_decode_swap_evt() {
@@ -85,4 +139,92 @@ mod tests {
         let evt = MeteoraDlmmEvents::from_cpi_log(evt_data).unwrap();
         println!("meteora dlmm lb pair created event: {evt:#?}");
     }
+
+    fn cpi_log(discriminator: [u8; 8], fields: &[u8]) -> String {
+        let mut data = vec![0xe4, 0x45, 0xa5, 0x2e, 0x51, 0xcb, 0x9a, 0x1d];
+        data.extend_from_slice(&discriminator);
+        data.extend_from_slice(fields);
+        bs58::encode(data).into_string()
+    }
+
+    #[test]
+    fn test_decode_v2_evts() {
+        let lb_pair = Pubkey::new_unique();
+        let mut swap = vec![];
+        swap.extend_from_slice(lb_pair.as_ref());
+        swap.extend_from_slice(Pubkey::new_unique().as_ref());
+        swap.extend_from_slice(&5i32.to_le_bytes());
+        swap.extend_from_slice(&6i32.to_le_bytes());
+        swap.extend_from_slice(&100u64.to_le_bytes());
+        swap.extend_from_slice(&90u64.to_le_bytes());
+        swap.push(1);
+        swap.extend_from_slice(&[0u8; 8 + 8 + 16 + 8]);
+
+        let log = cpi_log([81, 108, 227, 190, 205, 208, 10, 196], &swap);
+        let Ok(MeteoraDlmmEvents::Swap(evt)) = MeteoraDlmmEvents::from_cpi_log(&log) else {
+            panic!("should be a swap event");
+        };
+        assert_eq!(evt.lb_pair, lb_pair);
+
+        // the token-2022 extension follows the v1 fields
+        swap.extend_from_slice(&[7u8; 24]);
+        let log = cpi_log([49, 22, 214, 181, 97, 215, 44, 123], &swap);
+        let Ok(MeteoraDlmmEvents::Swap2(evt)) = MeteoraDlmmEvents::from_cpi_log(&log) else {
+            panic!("should be a swap2 event");
+        };
+        assert_eq!(evt.lb_pair, lb_pair);
+        assert_eq!(evt.amount_in, 100);
+        assert_eq!(evt.amount_out, 90);
+        assert!(evt.swap_for_y);
+
+        let mut add_liquidity = vec![];
+        add_liquidity.extend_from_slice(lb_pair.as_ref());
+        add_liquidity.extend_from_slice(Pubkey::new_unique().as_ref());
+        add_liquidity.extend_from_slice(Pubkey::new_unique().as_ref());
+        add_liquidity.extend_from_slice(&1u64.to_le_bytes());
+        add_liquidity.extend_from_slice(&2u64.to_le_bytes());
+        add_liquidity.extend_from_slice(&(-3i32).to_le_bytes());
+        add_liquidity.extend_from_slice(&[7u8; 16]);
+        let log = cpi_log([78, 44, 6, 161, 98, 213, 227, 87], &add_liquidity);
+        let Ok(MeteoraDlmmEvents::AddLiquidity2(evt)) = MeteoraDlmmEvents::from_cpi_log(&log)
+        else {
+            panic!("should be an add liquidity2 event");
+        };
+        assert_eq!(evt.amounts, [1, 2]);
+        assert_eq!(evt.active_bin_id, -3);
+
+        let log = cpi_log(
+            [116, 244, 97, 232, 103, 31, 152, 58],
+            &add_liquidity[..add_liquidity.len() - 16],
+        );
+        let Ok(MeteoraDlmmEvents::RemoveLiquidity(evt)) = MeteoraDlmmEvents::from_cpi_log(&log)
+        else {
+            panic!("should be a remove liquidity event");
+        };
+        assert_eq!(evt.lb_pair, lb_pair);
+        assert_eq!(evt.amounts, [1, 2]);
+    }
+
+    const DISCRIMINATORS: [[u8; 8]; 6] = [
+        [81, 108, 227, 190, 205, 208, 10, 196],
+        [49, 22, 214, 181, 97, 215, 44, 123],
+        [185, 74, 252, 125, 27, 215, 188, 111],
+        [31, 94, 125, 90, 227, 52, 61, 186],
+        [78, 44, 6, 161, 98, 213, 227, 87],
+        [116, 244, 97, 232, 103, 31, 152, 58],
+    ];
+
+    quickcheck::quickcheck! {
+        fn test_decode_arbitrary_bytes(kind: u8, fields: Vec<u8>, log: String) -> bool {
+            decodes_arbitrary_bytes(
+                Encoding::Base58,
+                &ANCHOR_EVENT_IX_TAG,
+                &DISCRIMINATORS,
+                kind,
+                &fields,
+                &log,
+                MeteoraDlmmEvents::from_cpi_log,
+            )
+        }
+    }
 }
@@ -1,4 +1,5 @@
 pub mod damm;
+pub mod dbc;
 pub mod dlmm;
 
 use solana_sdk::pubkey;
@@ -6,3 +7,4 @@ use solana_sdk::pubkey::Pubkey;
 
 pub const METEORA_DLMM_PROGRAM_ID: Pubkey = pubkey!("LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo");
 pub const METEORA_DAMM_PROGRAM_ID: Pubkey = pubkey!("Eo7WjKq67rjJQSZxS6z3YkapzY3eMj6Xy8X5EQVn5UaB");
+pub const METEORA_DBC_PROGRAM_ID: Pubkey = pubkey!("dbcij3LWUppWqq96dh6gJWwBifmcGfLSB5D4DuSMaqN");
@@ -0,0 +1,141 @@
+use borsh::BorshDeserialize;
+
+use crate::{common::Dex, parse_error::DexParseError};
+
+// tokenMint             # data prefix: 032ca4b87b0df5b3 [3,44,164,184,123,13,245,179]
+// buy                   # data prefix: 66063d1201daebea [102,6,61,18,1,218,235,234]
+// sell                  # data prefix: 33e685a4017f83ad [51,230,133,164,1,127,131,173]
+
+pub const TOKEN_MINT_IX_ID: [u8; 8] = [3, 44, 164, 184, 123, 13, 245, 179];
+pub const BUY_IX_ID: [u8; 8] = [102, 6, 61, 18, 1, 218, 235, 234];
+pub const SELL_IX_ID: [u8; 8] = [51, 230, 133, 164, 1, 127, 131, 173];
+
+#[derive(Debug, Clone, BorshDeserialize)]
+pub struct MoonshotTokenMint {
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub decimals: u8,
+    pub collateral_currency: u8,
+    /// minted into the curve token account
+    pub amount: u64,
+    pub curve_type: u8,
+    pub migration_target: u8,
+}
+
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct MoonshotTrade {
+    pub token_amount: u64,
+    pub collateral_amount: u64,
+    /// 0 when the token amount is exact, 1 when the collateral amount is
+    pub fixed_side: u8,
+    pub slippage_bps: u64,
+}
+
+/// The moonshot program logs no event for the tokens it mints, so its instructions are
+/// decoded, with the traded amounts taken from the curve balances, or from the args for a
+/// curve created by the same transaction
+#[derive(Debug)]
+pub enum MoonshotInstructions {
+    TokenMint(MoonshotTokenMint),
+    Buy(MoonshotTrade),
+    Sell(MoonshotTrade),
+    /// migrations and the config instructions, by discriminator
+    Other([u8; 8]),
+}
+
+impl MoonshotInstructions {
+    /// Decode the bs58 instruction data
+    pub fn from_ix_data(ix_data: &str) -> Result<Self, DexParseError> {
+        let bytes = bs58::decode(ix_data)
+            .into_vec()
+            .map_err(|err| DexParseError::corrupt(Dex::Moonshot, err))?;
+        let (discriminator, args) = bytes.split_at_checked(8).ok_or_else(|| {
+            DexParseError::corrupt(Dex::Moonshot, "moonshot instruction is too short")
+        })?;
+
+        let result = match discriminator {
+            d if d == TOKEN_MINT_IX_ID => {
+                Self::TokenMint(borsh::from_slice(args).map_err(corrupt)?)
+            }
+            d if d == BUY_IX_ID => Self::Buy(borsh::from_slice(args).map_err(corrupt)?),
+            d if d == SELL_IX_ID => Self::Sell(borsh::from_slice(args).map_err(corrupt)?),
+            _ => Self::Other(discriminator.try_into().unwrap_or_default()),
+        };
+
+        Ok(result)
+    }
+}
+
+fn corrupt(err: std::io::Error) -> DexParseError {
+    DexParseError::corrupt(Dex::Moonshot, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use borsh::BorshSerialize;
+
+    use super::*;
+    use crate::test_utils::{Encoding, decodes_arbitrary_bytes};
+
+    const IX_IDS: [[u8; 8]; 3] = [TOKEN_MINT_IX_ID, BUY_IX_ID, SELL_IX_ID];
+
+    #[test]
+    fn test_decode_moonshot_ix() {
+        let mut data = TOKEN_MINT_IX_ID.to_vec();
+        (
+            "Moon".to_string(),
+            "MOON".to_string(),
+            "https://moon".to_string(),
+        )
+            .serialize(&mut data)
+            .unwrap();
+        (9u8, 0u8, 1_000_000_000_000_000_000u64, 0u8, 1u8)
+            .serialize(&mut data)
+            .unwrap();
+        let Ok(MoonshotInstructions::TokenMint(ix)) =
+            MoonshotInstructions::from_ix_data(&bs58::encode(&data).into_string())
+        else {
+            panic!("should be a token mint instruction");
+        };
+        assert_eq!((ix.symbol.as_str(), ix.decimals), ("MOON", 9));
+        assert_eq!(ix.amount, 1_000_000_000_000_000_000);
+
+        let mut data = SELL_IX_ID.to_vec();
+        (5_000_000u64, 42u64, 0u8, 100u64)
+            .serialize(&mut data)
+            .unwrap();
+        let Ok(MoonshotInstructions::Sell(ix)) =
+            MoonshotInstructions::from_ix_data(&bs58::encode(&data).into_string())
+        else {
+            panic!("should be a sell instruction");
+        };
+        assert_eq!((ix.token_amount, ix.collateral_amount), (5_000_000, 42));
+
+        // trailing bytes are corrupt
+        data.push(0);
+        assert!(MoonshotInstructions::from_ix_data(&bs58::encode(&data).into_string()).is_err());
+
+        let migrate = [42, 229, 10, 231, 189, 62, 193, 174];
+        assert!(matches!(
+            MoonshotInstructions::from_ix_data(&bs58::encode(migrate).into_string()),
+            Ok(MoonshotInstructions::Other([
+                42, 229, 10, 231, 189, 62, 193, 174
+            ]))
+        ));
+    }
+
+    quickcheck::quickcheck! {
+        fn test_decode_arbitrary_bytes(kind: u8, args: Vec<u8>, ix_data: String) -> bool {
+            decodes_arbitrary_bytes(
+                Encoding::Base58,
+                &[],
+                &IX_IDS,
+                kind,
+                &args,
+                &ix_data,
+                MoonshotInstructions::from_ix_data,
+            )
+        }
+    }
+}
@@ -0,0 +1,6 @@
+pub mod instruction;
+
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+
+pub const MOONSHOT_PROGRAM_ID: Pubkey = pubkey!("MoonCVVNZFSYkqNXP6bxHLPL6QQJiMagDL3qcqUQTrG");
@@ -0,0 +1,95 @@
+//! Publish of the events to an mqtt broker, from the queues of the `mqtt` consumer.
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Result, anyhow};
+use rumqttc::{AsyncClient, EventLoop, MqttOptions, QoS};
+use tracing::{info, warn};
+
+use crate::{
+    cache::{self, ConsumerCounter, DexEventKind},
+    config::{MQTT_CONSUMER, MqttConfig},
+    naming::NameTemplate,
+    webhook::FieldNames,
+};
+
+/// Publishes waiting for the event loop, the sinks wait when it's full
+const MQTT_REQUEST_CAPACITY: usize = 1000;
+const MQTT_MAX_PACKET_SIZE: usize = 1024 * 1024;
+
+/// The client shared by the sinks and its event loop, which has to be driven by `drive`
+pub fn connect(config: &MqttConfig) -> Result<(AsyncClient, EventLoop)> {
+    let mut options = MqttOptions::parse_url(&config.url)
+        .map_err(|err| anyhow!("invalid mqtt url {}: {err}", config.url))?;
+    options.set_keep_alive(Duration::from_secs(30));
+    options.set_max_packet_size(MQTT_MAX_PACKET_SIZE, MQTT_MAX_PACKET_SIZE);
+    Ok(AsyncClient::new(options, MQTT_REQUEST_CAPACITY))
+}
+
+/// Send the publishes and keep the connection, reconnecting after a second when it fails
+pub async fn drive(mut eventloop: EventLoop) {
+    loop {
+        if let Err(err) = eventloop.poll().await {
+            warn!("mqtt connection error: {err}");
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+}
+
+/// Publishes the events of one kind queued for the `mqtt` consumer to their topics.
+pub struct MqttSink {
+    pub redis_client: Arc<redis::Client>,
+    pub client: AsyncClient,
+    pub topic: NameTemplate,
+    pub qos: u8,
+    pub fields: FieldNames,
+    pub kind: DexEventKind,
+}
+
+impl MqttSink {
+    /// A batch is acked once its events are handed to the event loop, which delivers them
+    /// with the configured qos.
+    pub async fn start(&self) -> Result<()> {
+        let qos: QoS = rumqttc::qos(self.qos).map_err(|err| anyhow!("invalid mqtt qos: {err}"))?;
+        info!(
+            "start {} mqtt publish to {}........",
+            self.kind,
+            self.topic.as_str()
+        );
+
+        loop {
+            let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+            let batch = cache::claim_dex_evts(&mut conn, self.kind, Some(MQTT_CONSUMER))
+                .await
+                .map_err(|err| anyhow!("claim dex events error: {err}"))?;
+            if batch.events.is_empty() {
+                drop(conn);
+                tokio::time::sleep(self.kind.poll_interval()).await;
+                continue;
+            }
+
+            for evt in &batch.events {
+                let payload = self.fields.to_vec(evt)?;
+                if let Err(err) = self
+                    .client
+                    .publish(self.topic.render(evt), qos, false, payload)
+                    .await
+                {
+                    cache::incr_consumer_counter(
+                        &mut conn,
+                        MQTT_CONSUMER,
+                        ConsumerCounter::Failed,
+                        1,
+                    )
+                    .await?;
+                    return Err(anyhow!("mqtt publish error: {err}"));
+                }
+            }
+            let sent = batch.events.len() as u64;
+            cache::ack_dex_evts(&mut conn, self.kind, Some(MQTT_CONSUMER)).await?;
+            cache::incr_consumer_counter(&mut conn, MQTT_CONSUMER, ConsumerCounter::Sent, sent)
+                .await?;
+            info!("{sent} {} events published to mqtt", self.kind);
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Names of the channels and topics the events are published to, shared by the sinks so they
+//! follow one convention.
+
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    cache::{DexEvent, DexEventKind},
+    common::Dex,
+};
+
+const PLACEHOLDERS: [&str; 3] = ["kind", "dex", "mint"];
+
+/// `{kind}` of the 1 minute candle channels, which have no dex
+pub const CANDLE_1M_KIND: &str = "candle_1m";
+
+/// Name of the kind in the channels and topics
+pub fn kind_name(kind: DexEventKind) -> &'static str {
+    match kind {
+        DexEventKind::Trade => "trade",
+        DexEventKind::PoolCreated => "pool",
+        DexEventKind::PumpfunComplete => "pumpfun_complete",
+        DexEventKind::MigrationCompleted => "migration_completed",
+        DexEventKind::MintInactive => "mint_inactive",
+        DexEventKind::PoolStateUpdated => "pool_state_updated",
+        DexEventKind::BinPriceUpdate => "bin_price_update",
+        DexEventKind::PressureUpdate => "pressure_update",
+        DexEventKind::FollowedWalletTrade => "followed_wallet_trade",
+        DexEventKind::MeteoraDbcComplete => "meteora_dbc_complete",
+        DexEventKind::LiquidityChange => "liquidity_change",
+        DexEventKind::PumpfunSetParams => "pumpfun_set_params",
+        DexEventKind::LiquidityLocked => "liquidity_locked",
+        DexEventKind::PoolDrained => "pool_drained",
+        DexEventKind::FailedSwapAttempt => "failed_swap_attempt",
+    }
+}
+
+/// A channel or topic name with `{kind}`, `{dex}` and `{mint}` replaced by those of the event,
+/// `none` for an event without dex or mint
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct NameTemplate(String);
+
+impl NameTemplate {
+    pub fn new(template: &str) -> Self {
+        Self(template.to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn render(&self, evt: &DexEvent) -> String {
+        self.render_parts(kind_name(evt.kind()), evt.dex(), evt.mint())
+    }
+
+    /// The name of the 1 minute candles of the mint
+    pub fn render_candle(&self, mint: &Pubkey) -> String {
+        self.render_parts(CANDLE_1M_KIND, None, Some(*mint))
+    }
+
+    fn render_parts(&self, kind: &str, dex: Option<Dex>, mint: Option<Pubkey>) -> String {
+        let mut name = self.0.replace("{kind}", kind);
+        if name.contains("{dex}") {
+            let dex = dex.map_or("none".to_string(), |it| it.to_string());
+            name = name.replace("{dex}", &dex);
+        }
+        if name.contains("{mint}") {
+            let mint = mint.map_or("none".to_string(), |it| it.to_string());
+            name = name.replace("{mint}", &mint);
+        }
+        name
+    }
+
+    /// The `{...}` of the template which aren't placeholders
+    pub fn unknown_placeholders(&self) -> Vec<&str> {
+        let mut unknown = vec![];
+        let mut rest = self.0.as_str();
+        while let Some(start) = rest.find('{') {
+            let Some(len) = rest[start..].find('}') else {
+                break;
+            };
+            let name = &rest[start + 1..start + len];
+            if !PLACEHOLDERS.contains(&name) {
+                unknown.push(name);
+            }
+            rest = &rest[start + len + 1..];
+        }
+        unknown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+    use crate::cache::MintInactiveRecord;
+
+    #[test]
+    fn test_name_template() {
+        let mint = Pubkey::new_unique();
+        let evt = DexEvent::MintInactive(MintInactiveRecord {
+            mint,
+            last_trade_ts: DateTime::from_timestamp(1, 0).unwrap(),
+        });
+        let template = NameTemplate::new("dex/{dex}/{mint}/{kind}");
+        assert_eq!(
+            template.render(&evt),
+            format!("dex/none/{mint}/mint_inactive")
+        );
+        assert!(template.unknown_placeholders().is_empty());
+        assert_eq!(
+            template.render_candle(&mint),
+            format!("dex/none/{mint}/candle_1m")
+        );
+        assert_eq!(
+            NameTemplate::new("evt:{kind}:{pool}:{mint").unknown_placeholders(),
+            vec!["pool"]
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::Result;
+use borsh::BorshDeserialize;
+use solana_sdk::pubkey::Pubkey;
+
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct WhirlpoolRewardInfo {
+    /// Reward token mint.
+    pub mint: Pubkey,
+    /// Reward vault token account.
+    pub vault: Pubkey,
+    /// Authority account that has permission to initialize the reward and set emissions.
+    pub authority: Pubkey,
+    /// Q64.64 number that indicates how many tokens per second are earned per unit of liquidity.
+    pub emissions_per_second_x64: u128,
+    /// Q64.64 number that tracks the total tokens earned per unit of liquidity since the reward
+    /// emissions were turned on.
+    pub growth_global_x64: u128,
+}
+
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct Whirlpool {
+    pub discriminator: u64,
+    pub whirlpools_config: Pubkey,
+    pub whirlpool_bump: [u8; 1],
+    pub tick_spacing: u16,
+    pub fee_tier_index_seed: [u8; 2],
+    /// Stored as hundredths of a basis point, u16::MAX corresponds to ~6.5%
+    pub fee_rate: u16,
+    /// Portion of fee rate taken stored as basis points
+    pub protocol_fee_rate: u16,
+    /// Maximum amount that can be held by Solana account
+    pub liquidity: u128,
+    /// MAX/MIN at Q32.64, but using Q64.64 for rounder bytes
+    /// Q64.64
+    pub sqrt_price: u128,
+    pub tick_current_index: i32,
+    pub protocol_fee_owed_a: u64,
+    pub protocol_fee_owed_b: u64,
+    pub token_mint_a: Pubkey,
+    pub token_vault_a: Pubkey,
+    /// Q64.64
+    pub fee_growth_global_a: u128,
+    pub token_mint_b: Pubkey,
+    pub token_vault_b: Pubkey,
+    /// Q64.64
+    pub fee_growth_global_b: u128,
+    pub reward_last_updated_timestamp: u64,
+    pub reward_infos: [WhirlpoolRewardInfo; 3],
+}
+
+impl Whirlpool {
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        let result = Whirlpool::deserialize(&mut &data[..])
+            .map_err(|err| anyhow::anyhow!("deserialize orca whirlpool error: {err}"))?;
+
+        Ok(result)
+    }
+
+    /// Fee rate in bps
+    pub fn fee_bps(&self) -> f64 {
+        self.fee_rate as f64 / 100.0
+    }
+}
@@ -0,0 +1,130 @@
+use anyhow::Result;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use borsh::BorshDeserialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{common::Dex, parse_error::DexParseError};
+
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct WhirlpoolTradedEvent {
+    pub whirlpool: Pubkey,
+    /// token a is swapped for token b
+    pub a_to_b: bool,
+    pub pre_sqrt_price: u128,
+    pub post_sqrt_price: u128,
+    /// amount taken from the trader, before the transfer fee of token-2022 mints
+    pub input_amount: u64,
+    /// amount sent to the trader, before the transfer fee of token-2022 mints
+    pub output_amount: u64,
+    pub input_transfer_fee: u64,
+    pub output_transfer_fee: u64,
+    pub lp_fee: u64,
+    pub protocol_fee: u64,
+}
+
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct WhirlpoolPoolInitializedEvent {
+    pub whirlpool: Pubkey,
+    pub whirlpools_config: Pubkey,
+    pub token_mint_a: Pubkey,
+    pub token_mint_b: Pubkey,
+    pub tick_spacing: u16,
+    pub token_program_a: Pubkey,
+    pub token_program_b: Pubkey,
+    pub decimals_a: u8,
+    pub decimals_b: u8,
+    pub initial_sqrt_price: u128,
+}
+
+/// Events emitted by the whirlpool program since it logs them, older transactions have none
+#[derive(Debug)]
+pub enum WhirlpoolEvents {
+    Traded(WhirlpoolTradedEvent),
+    PoolInitialized(WhirlpoolPoolInitializedEvent),
+}
+
+impl WhirlpoolEvents {
+    pub fn from_log(log: &str) -> Result<Self, DexParseError> {
+        let bytes = BASE64_STANDARD
+            .decode(log)
+            .map_err(|err| DexParseError::corrupt(Dex::OrcaWhirlpool, err))?;
+        let (discriminator, fields) = bytes.split_at_checked(8).ok_or_else(|| {
+            DexParseError::corrupt(Dex::OrcaWhirlpool, "orca whirlpool log is too short")
+        })?;
+
+        let result = match discriminator {
+            [225, 202, 73, 175, 147, 43, 160, 150] => {
+                let evt: WhirlpoolTradedEvent = borsh::from_slice(fields).map_err(corrupt)?;
+                Self::Traded(evt)
+            }
+            [100, 118, 173, 87, 12, 198, 254, 229] => {
+                let evt: WhirlpoolPoolInitializedEvent =
+                    borsh::from_slice(fields).map_err(corrupt)?;
+                Self::PoolInitialized(evt)
+            }
+            _ => return Err(DexParseError::unknown(Dex::OrcaWhirlpool, discriminator)),
+        };
+
+        Ok(result)
+    }
+}
+
+fn corrupt(err: std::io::Error) -> DexParseError {
+    DexParseError::corrupt(Dex::OrcaWhirlpool, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{Encoding, decodes_arbitrary_bytes};
+
+    const DISCRIMINATORS: [[u8; 8]; 2] = [
+        [225, 202, 73, 175, 147, 43, 160, 150],
+        [100, 118, 173, 87, 12, 198, 254, 229],
+    ];
+
+    #[test]
+    fn test_decode_pool_initialized_evt() {
+        let (whirlpool, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut data = DISCRIMINATORS[1].to_vec();
+        data.extend_from_slice(whirlpool.as_ref());
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(crate::common::WSOL_MINT.as_ref());
+        data.extend_from_slice(mint.as_ref());
+        data.extend_from_slice(&64u16.to_le_bytes());
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(&[9, 6]);
+        data.extend_from_slice(&(1u128 << 64).to_le_bytes());
+
+        let Ok(WhirlpoolEvents::PoolInitialized(evt)) =
+            WhirlpoolEvents::from_log(&BASE64_STANDARD.encode(&data))
+        else {
+            panic!("should be a pool initialized event");
+        };
+        assert_eq!(evt.whirlpool, whirlpool);
+        assert_eq!(evt.token_mint_b, mint);
+        assert_eq!(evt.tick_spacing, 64);
+        assert_eq!((evt.decimals_a, evt.decimals_b), (9, 6));
+
+        let unknown = BASE64_STANDARD.encode([1u8; 16]);
+        assert!(matches!(
+            WhirlpoolEvents::from_log(&unknown),
+            Err(DexParseError::UnknownDiscriminator { .. })
+        ));
+    }
+
+    quickcheck::quickcheck! {
+        fn test_decode_arbitrary_bytes(kind: u8, fields: Vec<u8>, log: String) -> bool {
+            decodes_arbitrary_bytes(
+                Encoding::Base64,
+                &[],
+                &DISCRIMINATORS,
+                kind,
+                &fields,
+                &log,
+                WhirlpoolEvents::from_log,
+            )
+        }
+    }
+}
@@ -0,0 +1,55 @@
+// swap                  # data prefix: f8c69e91e17587c8 [248,198,158,145,225,117,135,200]
+// swapV2                # data prefix: 2b04ed0b1ac91e62 [43,4,237,11,26,201,30,98]
+// initializePool        # data prefix: 5fb40aac54aee828 [95,180,10,172,84,174,232,40]
+// initializePoolV2      # data prefix: cf2d57f21b3fcc43 [207,45,87,242,27,63,204,67]
+
+pub const SWAP_IX_ID: [u8; 8] = [248, 198, 158, 145, 225, 117, 135, 200];
+pub const SWAP_V2_IX_ID: [u8; 8] = [43, 4, 237, 11, 26, 201, 30, 98];
+pub const INITIALIZE_POOL_IX_ID: [u8; 8] = [95, 180, 10, 172, 84, 174, 232, 40];
+
+/// Indexes of the accounts of a swap instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapAccountIdx {
+    pub token_authority: usize,
+    pub whirlpool: usize,
+    pub token_owner_account_a: usize,
+    pub token_vault_a: usize,
+    pub token_owner_account_b: usize,
+    pub token_vault_b: usize,
+}
+
+/// Account layout of `swap` and `swap_v2`, none for the two hop swaps which trade through
+/// two pools in one instruction
+pub fn swap_account_idx(ix_data: &[u8]) -> Option<SwapAccountIdx> {
+    if ix_data.starts_with(&SWAP_IX_ID) {
+        Some(SwapAccountIdx {
+            token_authority: 1,
+            whirlpool: 2,
+            token_owner_account_a: 3,
+            token_vault_a: 4,
+            token_owner_account_b: 5,
+            token_vault_b: 6,
+        })
+    } else if ix_data.starts_with(&SWAP_V2_IX_ID) {
+        Some(SwapAccountIdx {
+            token_authority: 3,
+            whirlpool: 4,
+            token_owner_account_a: 7,
+            token_vault_a: 8,
+            token_owner_account_b: 9,
+            token_vault_b: 10,
+        })
+    } else {
+        None
+    }
+}
+
+/// Index of the funder of the pool initialize instructions, `initialize_pool_v2` and
+/// `initialize_pool_with_adaptive_fee` add the token badges before it
+pub fn pool_funder_idx(ix_data: &[u8]) -> usize {
+    if ix_data.starts_with(&INITIALIZE_POOL_IX_ID) {
+        3
+    } else {
+        5
+    }
+}
@@ -0,0 +1,9 @@
+pub mod accounts;
+pub mod event;
+pub mod instruction;
+
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+
+pub const ORCA_WHIRLPOOL_PROGRAM_ID: Pubkey =
+    pubkey!("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc");
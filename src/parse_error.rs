@@ -0,0 +1,342 @@
+//! Errors of parsing the dex event logs and the accounts of their instructions.
+
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fmt::Display,
+    str::FromStr,
+    sync::{
+        LazyLock, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use strum::IntoStaticStr;
+use thiserror::Error;
+
+use crate::{
+    common::Dex,
+    qn_req_processor::{IxAccount, TokenAmt},
+};
+
+#[derive(Debug, Error, IntoStaticStr)]
+pub enum DexParseError {
+    /// A well formed log of an event the decoder doesn't know, e.g. added by a program upgrade
+    #[error("unknown {dex} event discriminator {discriminator:?}")]
+    UnknownDiscriminator { dex: Dex, discriminator: Vec<u8> },
+    /// The log is badly encoded, truncated or its fields don't deserialize
+    #[error("corrupt {dex} event payload: {reason}")]
+    CorruptPayload { dex: Dex, reason: String },
+    /// The instruction of the event has fewer accounts than its layout
+    #[error("{dex} {event} needs the instruction account #{idx}")]
+    MissingAccount {
+        dex: Dex,
+        event: &'static str,
+        idx: usize,
+    },
+    /// The account is expected to be a token account
+    #[error("{dex} {event} needs the token balance of the instruction account #{idx}")]
+    MissingBalance {
+        dex: Dex,
+        event: &'static str,
+        idx: usize,
+    },
+    #[error("{dex} {event} has a bad pubkey {value}")]
+    BadPubkey {
+        dex: Dex,
+        event: &'static str,
+        value: String,
+    },
+    /// The transaction itself is malformed, none of its logs can be parsed
+    #[error("bad transaction: {0}")]
+    BadTx(String),
+    /// Redis failed while looking up the cached pools
+    #[error("pool cache error: {0}")]
+    Cache(#[from] anyhow::Error),
+}
+
+/// What the processor does with a log which failed to parse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorAction {
+    /// skip the log, nothing is wrong with it
+    Drop,
+    /// skip the log and keep it for inspection, the decoder or the layout may be outdated
+    Quarantine,
+    /// fail the batch, it's retried
+    Retry,
+}
+
+impl DexParseError {
+    pub fn unknown(dex: Dex, discriminator: &[u8]) -> Self {
+        Self::UnknownDiscriminator {
+            dex,
+            discriminator: discriminator.to_vec(),
+        }
+    }
+
+    pub fn corrupt(dex: Dex, reason: impl Display) -> Self {
+        Self::CorruptPayload {
+            dex,
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Name of the variant, to label the metrics
+    pub fn kind(&self) -> &'static str {
+        self.into()
+    }
+
+    /// none for the cache errors
+    pub fn dex(&self) -> Option<Dex> {
+        match self {
+            Self::UnknownDiscriminator { dex, .. }
+            | Self::CorruptPayload { dex, .. }
+            | Self::MissingAccount { dex, .. }
+            | Self::MissingBalance { dex, .. }
+            | Self::BadPubkey { dex, .. } => Some(*dex),
+            Self::BadTx(_) | Self::Cache(_) => None,
+        }
+    }
+
+    pub fn action(&self) -> ParseErrorAction {
+        match self {
+            Self::UnknownDiscriminator { .. } | Self::BadTx(_) => ParseErrorAction::Drop,
+            Self::CorruptPayload { .. }
+            | Self::MissingAccount { .. }
+            | Self::MissingBalance { .. }
+            | Self::BadPubkey { .. } => ParseErrorAction::Quarantine,
+            Self::Cache(_) => ParseErrorAction::Retry,
+        }
+    }
+}
+
+/// The accounts of the instruction of an event log, by the layout of the event
+#[derive(Debug, Clone, Copy)]
+pub struct LogAccounts<'a> {
+    pub accounts: &'a [IxAccount],
+    pub dex: Dex,
+    pub event: &'static str,
+}
+
+impl<'a> LogAccounts<'a> {
+    pub fn new(accounts: &'a [IxAccount], dex: Dex, event: &'static str) -> Self {
+        Self {
+            accounts,
+            dex,
+            event,
+        }
+    }
+
+    pub fn get(&self, idx: usize) -> Result<&'a IxAccount, DexParseError> {
+        self.accounts.get(idx).ok_or(DexParseError::MissingAccount {
+            dex: self.dex,
+            event: self.event,
+            idx,
+        })
+    }
+
+    pub fn pubkey(&self, idx: usize) -> Result<Pubkey, DexParseError> {
+        self.parse_pubkey(&self.get(idx)?.pubkey)
+    }
+
+    pub fn last_pubkey(&self) -> Result<Pubkey, DexParseError> {
+        self.pubkey(self.accounts.len().saturating_sub(1))
+    }
+
+    /// Token balance of the account before the transaction
+    pub fn pre_token_amt(&self, idx: usize) -> Result<TokenAmt, DexParseError> {
+        self.get(idx)?
+            .pre_amt
+            .token
+            .clone()
+            .ok_or(DexParseError::MissingBalance {
+                dex: self.dex,
+                event: self.event,
+                idx,
+            })
+    }
+
+    /// Token balance of the account after the transaction
+    pub fn post_token_amt(&self, idx: usize) -> Result<TokenAmt, DexParseError> {
+        self.get(idx)?
+            .post_amt
+            .token
+            .clone()
+            .ok_or(DexParseError::MissingBalance {
+                dex: self.dex,
+                event: self.event,
+                idx,
+            })
+    }
+
+    pub fn parse_pubkey(&self, value: &str) -> Result<Pubkey, DexParseError> {
+        Pubkey::from_str(value).map_err(|_| DexParseError::BadPubkey {
+            dex: self.dex,
+            event: self.event,
+            value: value.to_string(),
+        })
+    }
+}
+
+/// An instruction account expected by the layout of an event which was absent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct AccountViolation {
+    pub dex: Dex,
+    pub event: &'static str,
+    pub idx: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountViolationCount {
+    #[serde(flatten)]
+    pub violation: AccountViolation,
+    pub count: u64,
+}
+
+/// parse errors by kind and dex
+static PARSE_ERRORS: LazyLock<Mutex<HashMap<&'static str, HashMap<Dex, u64>>>> =
+    LazyLock::new(Default::default);
+static ACCOUNT_VIOLATIONS: LazyLock<Mutex<HashMap<AccountViolation, u64>>> =
+    LazyLock::new(Default::default);
+static FAILED_TXS: AtomicU64 = AtomicU64::new(0);
+
+/// Count the parse error by its kind and dex, and the missing accounts by their event and
+/// index. The cache errors are not counted.
+pub fn record_parse_error(err: &DexParseError) {
+    let Some(dex) = err.dex() else {
+        return;
+    };
+    let mut counts = PARSE_ERRORS.lock().unwrap_or_else(|it| it.into_inner());
+    *counts
+        .entry(err.kind())
+        .or_default()
+        .entry(dex)
+        .or_default() += 1;
+    drop(counts);
+
+    if let DexParseError::MissingAccount { dex, event, idx } = *err {
+        let violation = AccountViolation { dex, event, idx };
+        let mut violations = ACCOUNT_VIOLATIONS
+            .lock()
+            .unwrap_or_else(|it| it.into_inner());
+        *violations.entry(violation).or_default() += 1;
+    }
+}
+
+/// Count a transaction skipped as a whole, the rest of its batch still ships
+pub fn record_failed_tx() {
+    FAILED_TXS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Transactions skipped as a whole since the start
+pub fn failed_txs() -> u64 {
+    FAILED_TXS.load(Ordering::Relaxed)
+}
+
+/// Parse errors by kind and dex since the start
+pub fn parse_errors() -> HashMap<&'static str, HashMap<Dex, u64>> {
+    PARSE_ERRORS
+        .lock()
+        .unwrap_or_else(|it| it.into_inner())
+        .clone()
+}
+
+/// Missing accounts by the event and index since the start
+pub fn account_violations() -> HashMap<AccountViolation, u64> {
+    ACCOUNT_VIOLATIONS
+        .lock()
+        .unwrap_or_else(|it| it.into_inner())
+        .clone()
+}
+
+/// Missing accounts since the start, the most frequent first
+pub fn account_violation_counts() -> Vec<AccountViolationCount> {
+    let mut counts: Vec<_> = account_violations()
+        .into_iter()
+        .map(|(violation, count)| AccountViolationCount { violation, count })
+        .collect();
+    counts.sort_by_key(|it| Reverse(it.count));
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_record_parse_error() {
+        let count = |kind| {
+            parse_errors()
+                .get(kind)
+                .and_then(|it| it.get(&Dex::MeteoraDamm).copied())
+                .unwrap_or_default()
+        };
+        let before = count("CorruptPayload");
+        record_parse_error(&DexParseError::corrupt(Dex::MeteoraDamm, "too short"));
+        record_parse_error(&DexParseError::Cache(anyhow::anyhow!("redis is down")));
+        assert_eq!(count("CorruptPayload"), before + 1);
+
+        let err = DexParseError::unknown(Dex::RaydiumAmm, &[9]);
+        assert_eq!(
+            err.to_string(),
+            "unknown RaydiumAmm event discriminator [9]"
+        );
+        assert_eq!(err.action(), ParseErrorAction::Drop);
+        // a bad tx is skipped alone, unlike a failing cache
+        let err = DexParseError::BadTx("no block timestamp".to_string());
+        assert_eq!((err.action(), err.dex()), (ParseErrorAction::Drop, None));
+        assert_eq!(
+            DexParseError::Cache(anyhow::anyhow!("redis is down")).action(),
+            ParseErrorAction::Retry
+        );
+    }
+
+    #[test]
+    fn test_log_accounts() {
+        let pubkey = Pubkey::new_unique();
+        let accounts: Vec<IxAccount> = serde_json::from_value(json!([
+            {"pubkey": "not a pubkey", "preAmt": {"sol": 1}, "postAmt": {"sol": 1}},
+            {
+                "pubkey": pubkey.to_string(),
+                "preAmt": {"sol": 1},
+                "postAmt": {"sol": 1, "token": {"mint": pubkey.to_string(), "decimals": 6, "amt": "5"}},
+            },
+        ]))
+        .unwrap();
+        let accounts = LogAccounts::new(&accounts, Dex::PumpAmm, "swap");
+
+        assert_eq!(accounts.pubkey(1).unwrap(), pubkey);
+        assert_eq!(accounts.post_token_amt(1).unwrap().amt, 5);
+        assert!(matches!(
+            accounts.pubkey(0),
+            Err(DexParseError::BadPubkey { .. })
+        ));
+        assert!(matches!(
+            accounts.post_token_amt(0),
+            Err(DexParseError::MissingBalance { idx: 0, .. })
+        ));
+        let err = accounts.get(7).unwrap_err();
+        assert!(matches!(err, DexParseError::MissingAccount { idx: 7, .. }));
+        let violation = AccountViolation {
+            dex: Dex::PumpAmm,
+            event: "swap",
+            idx: 7,
+        };
+        let before = account_violations()
+            .get(&violation)
+            .copied()
+            .unwrap_or_default();
+        record_parse_error(&err);
+        assert_eq!(account_violations()[&violation], before + 1);
+        assert_eq!(err.kind(), "MissingAccount");
+        assert_eq!(err.action(), ParseErrorAction::Quarantine);
+        assert_eq!(
+            err.to_string(),
+            "PumpAmm swap needs the instruction account #7"
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Program derived addresses of the supported dex programs.
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    common::WSOL_MINT, metaplex::METAPLEX_METADATA_PROGRAM_ID, meteora::METEORA_DLMM_PROGRAM_ID,
+    pumpamm::PUMPAMM_PROGRAM_ID, pumpfun::PUMPFUN_PROGRAM_ID,
+};
+
+/// Bins in one meteora dlmm bin array
+pub const MAX_BIN_PER_ARRAY: i32 = 70;
+
+fn find(seeds: &[&[u8]], program_id: &Pubkey) -> Pubkey {
+    let (pda, _) = Pubkey::find_program_address(seeds, program_id);
+    pda
+}
+
+pub fn pumpfun_global() -> Pubkey {
+    find(&[b"global"], &PUMPFUN_PROGRAM_ID)
+}
+
+pub fn pumpfun_bonding_curve(mint: &Pubkey) -> Pubkey {
+    find(&[b"bonding-curve", mint.as_ref()], &PUMPFUN_PROGRAM_ID)
+}
+
+pub fn pumpfun_creator_vault(creator: &Pubkey) -> Pubkey {
+    find(&[b"creator-vault", creator.as_ref()], &PUMPFUN_PROGRAM_ID)
+}
+
+/// Creator of the pumpamm pool a completed bonding curve migrates to
+pub fn pumpfun_pool_authority(mint: &Pubkey) -> Pubkey {
+    find(&[b"pool-authority", mint.as_ref()], &PUMPFUN_PROGRAM_ID)
+}
+
+pub fn pumpamm_global_config() -> Pubkey {
+    find(&[b"global_config"], &PUMPAMM_PROGRAM_ID)
+}
+
+pub fn pumpamm_pool(
+    index: u16,
+    creator: &Pubkey,
+    base_mint: &Pubkey,
+    quote_mint: &Pubkey,
+) -> Pubkey {
+    find(
+        &[
+            b"pool",
+            &index.to_le_bytes(),
+            creator.as_ref(),
+            base_mint.as_ref(),
+            quote_mint.as_ref(),
+        ],
+        &PUMPAMM_PROGRAM_ID,
+    )
+}
+
+/// The pumpamm pool created by the migration of the pumpfun bonding curve of `mint`
+pub fn pumpamm_migration_pool(mint: &Pubkey) -> Pubkey {
+    pumpamm_pool(0, &pumpfun_pool_authority(mint), mint, &WSOL_MINT)
+}
+
+pub fn pumpamm_creator_vault(coin_creator: &Pubkey) -> Pubkey {
+    find(
+        &[b"creator_vault", coin_creator.as_ref()],
+        &PUMPAMM_PROGRAM_ID,
+    )
+}
+
+/// Index of the bin array holding `bin_id`
+pub fn meteora_dlmm_bin_array_index(bin_id: i32) -> i64 {
+    bin_id.div_euclid(MAX_BIN_PER_ARRAY) as i64
+}
+
+pub fn meteora_dlmm_bin_array(lb_pair: &Pubkey, index: i64) -> Pubkey {
+    find(
+        &[b"bin_array", lb_pair.as_ref(), &index.to_le_bytes()],
+        &METEORA_DLMM_PROGRAM_ID,
+    )
+}
+
+pub fn metaplex_metadata(mint: &Pubkey) -> Pubkey {
+    find(
+        &[
+            b"metadata",
+            METAPLEX_METADATA_PROGRAM_ID.as_ref(),
+            mint.as_ref(),
+        ],
+        &METAPLEX_METADATA_PROGRAM_ID,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::pubkey;
+
+    use super::*;
+
+    #[test]
+    fn test_pdas() {
+        assert_eq!(
+            pumpfun_global(),
+            pubkey!("4wTV1YmiEkRvAtNtsSGPtUrqRYQMe5SKy2uB4Jjaxnjf")
+        );
+        assert_eq!(
+            pumpamm_global_config(),
+            pubkey!("ADyA8hdefvWN2dbGGWFotbzWxrAvLW83WG6QCVXvJKqw")
+        );
+
+        assert_eq!(meteora_dlmm_bin_array_index(0), 0);
+        assert_eq!(meteora_dlmm_bin_array_index(69), 0);
+        assert_eq!(meteora_dlmm_bin_array_index(70), 1);
+        assert_eq!(meteora_dlmm_bin_array_index(-1), -1);
+        assert_eq!(meteora_dlmm_bin_array_index(-70), -1);
+        assert_eq!(meteora_dlmm_bin_array_index(-71), -2);
+    }
+}
@@ -0,0 +1,250 @@
+//! Identify the dex of a pool from its onchain account, with the layout of the owner program.
+
+use borsh::BorshDeserialize;
+use serde::Serialize;
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    common::Dex,
+    meteora::{damm::accounts::MeteoraDammPool, dlmm::accounts::LbPair},
+    orca::accounts::Whirlpool,
+    pumpamm::accounts::PumpAmmPool,
+    pumpfun::accounts::BondingCurveAccount,
+    raydium::{accounts::AmmInfo, clmm::accounts::PoolState},
+};
+
+/// anchor discriminators of the pool accounts, raydium amm accounts have none
+const LB_PAIR_DISCRIMINATOR: [u8; 8] = [33, 11, 49, 98, 181, 101, 177, 13];
+/// shared by the pumpamm and meteora damm pools, both named `Pool`
+const POOL_DISCRIMINATOR: [u8; 8] = [241, 154, 109, 4, 17, 177, 109, 188];
+const BONDING_CURVE_DISCRIMINATOR: [u8; 8] = [23, 183, 248, 55, 96, 216, 172, 96];
+const CLMM_POOL_STATE_DISCRIMINATOR: [u8; 8] = [247, 237, 227, 245, 215, 195, 222, 70];
+const WHIRLPOOL_DISCRIMINATOR: [u8; 8] = [63, 149, 209, 12, 225, 128, 99, 9];
+
+/// The main fields of a pool account
+#[serde_as]
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "layout")]
+pub enum PoolAccountSummary {
+    AmmInfo {
+        status: u64,
+        #[serde_as(as = "DisplayFromStr")]
+        coin_mint: Pubkey,
+        #[serde_as(as = "DisplayFromStr")]
+        pc_mint: Pubkey,
+        #[serde_as(as = "DisplayFromStr")]
+        coin_vault: Pubkey,
+        #[serde_as(as = "DisplayFromStr")]
+        pc_vault: Pubkey,
+        coin_decimals: u64,
+        pc_decimals: u64,
+        swap_fee_bps: f64,
+    },
+    LbPair {
+        #[serde_as(as = "DisplayFromStr")]
+        token_x_mint: Pubkey,
+        #[serde_as(as = "DisplayFromStr")]
+        token_y_mint: Pubkey,
+        #[serde_as(as = "DisplayFromStr")]
+        reserve_x: Pubkey,
+        #[serde_as(as = "DisplayFromStr")]
+        reserve_y: Pubkey,
+        active_id: i32,
+        bin_step: u16,
+        base_fee_bps: f64,
+    },
+    MeteoraDammPool {
+        enabled: bool,
+        #[serde_as(as = "DisplayFromStr")]
+        token_a_mint: Pubkey,
+        #[serde_as(as = "DisplayFromStr")]
+        token_b_mint: Pubkey,
+        #[serde_as(as = "DisplayFromStr")]
+        a_vault: Pubkey,
+        #[serde_as(as = "DisplayFromStr")]
+        b_vault: Pubkey,
+    },
+    /// raydium clmm pool, `price` is token 1 per token 0 in ui amounts
+    ClmmPoolState {
+        status: u8,
+        #[serde_as(as = "DisplayFromStr")]
+        token_mint_0: Pubkey,
+        #[serde_as(as = "DisplayFromStr")]
+        token_mint_1: Pubkey,
+        #[serde_as(as = "DisplayFromStr")]
+        token_vault_0: Pubkey,
+        #[serde_as(as = "DisplayFromStr")]
+        token_vault_1: Pubkey,
+        tick_spacing: u16,
+        tick_current: i32,
+        price: f64,
+    },
+    Whirlpool {
+        #[serde_as(as = "DisplayFromStr")]
+        token_mint_a: Pubkey,
+        #[serde_as(as = "DisplayFromStr")]
+        token_mint_b: Pubkey,
+        #[serde_as(as = "DisplayFromStr")]
+        token_vault_a: Pubkey,
+        #[serde_as(as = "DisplayFromStr")]
+        token_vault_b: Pubkey,
+        tick_spacing: u16,
+        tick_current_index: i32,
+        fee_bps: f64,
+    },
+    PumpAmmPool {
+        #[serde_as(as = "DisplayFromStr")]
+        creator: Pubkey,
+        #[serde_as(as = "DisplayFromStr")]
+        base_mint: Pubkey,
+        #[serde_as(as = "DisplayFromStr")]
+        quote_mint: Pubkey,
+        #[serde_as(as = "DisplayFromStr")]
+        pool_base_token_account: Pubkey,
+        #[serde_as(as = "DisplayFromStr")]
+        pool_quote_token_account: Pubkey,
+        lp_supply: u64,
+    },
+    /// the mint is only a seed of the bonding curve address, it's not in the account
+    BondingCurve {
+        virtual_sol_reserves: u64,
+        virtual_token_reserves: u64,
+        real_sol_reserves: u64,
+        real_token_reserves: u64,
+        token_total_supply: u64,
+        complete: bool,
+    },
+}
+
+/// Parse the account with the pool layout of its owner program,
+/// none if it's not the pool account of a supported dex.
+pub fn parse_pool_account(owner: &Pubkey, data: &[u8]) -> Option<(Dex, PoolAccountSummary)> {
+    let dex = Dex::from_program_id(&owner.to_string())?;
+    let discriminator = data.get(..8)?;
+    let summary = match dex {
+        Dex::RaydiumAmm => {
+            let amm = AmmInfo::from_account_data(data).ok()?;
+            PoolAccountSummary::AmmInfo {
+                status: amm.status,
+                coin_mint: amm.coin_vault_mint,
+                pc_mint: amm.pc_vault_mint,
+                coin_vault: amm.coin_vault,
+                pc_vault: amm.pc_vault,
+                coin_decimals: amm.coin_decimals,
+                pc_decimals: amm.pc_decimals,
+                swap_fee_bps: amm.swap_fee_bps(),
+            }
+        }
+        Dex::MeteoraDlmm if discriminator == LB_PAIR_DISCRIMINATOR => {
+            let lb_pair = LbPair::from_account_data(data).ok()?;
+            PoolAccountSummary::LbPair {
+                token_x_mint: lb_pair.token_x_mint,
+                token_y_mint: lb_pair.token_y_mint,
+                reserve_x: lb_pair.reserve_x,
+                reserve_y: lb_pair.reserve_y,
+                active_id: lb_pair.active_id,
+                bin_step: lb_pair.bin_step,
+                base_fee_bps: lb_pair.base_fee_bps(),
+            }
+        }
+        Dex::MeteoraDamm if discriminator == POOL_DISCRIMINATOR => {
+            let pool = MeteoraDammPool::deserialize(&mut &data[..]).ok()?;
+            PoolAccountSummary::MeteoraDammPool {
+                enabled: pool.enabled,
+                token_a_mint: pool.token_a_mint,
+                token_b_mint: pool.token_b_mint,
+                a_vault: pool.a_vault,
+                b_vault: pool.b_vault,
+            }
+        }
+        Dex::RaydiumClmm if discriminator == CLMM_POOL_STATE_DISCRIMINATOR => {
+            let pool = PoolState::from_account_data(data).ok()?;
+            PoolAccountSummary::ClmmPoolState {
+                status: pool.status,
+                token_mint_0: pool.token_mint_0,
+                token_mint_1: pool.token_mint_1,
+                token_vault_0: pool.token_vault_0,
+                token_vault_1: pool.token_vault_1,
+                tick_spacing: pool.tick_spacing,
+                tick_current: pool.tick_current,
+                price: pool.price(),
+            }
+        }
+        Dex::OrcaWhirlpool if discriminator == WHIRLPOOL_DISCRIMINATOR => {
+            let pool = Whirlpool::from_account_data(data).ok()?;
+            PoolAccountSummary::Whirlpool {
+                token_mint_a: pool.token_mint_a,
+                token_mint_b: pool.token_mint_b,
+                token_vault_a: pool.token_vault_a,
+                token_vault_b: pool.token_vault_b,
+                tick_spacing: pool.tick_spacing,
+                tick_current_index: pool.tick_current_index,
+                fee_bps: pool.fee_bps(),
+            }
+        }
+        Dex::PumpAmm if discriminator == POOL_DISCRIMINATOR => {
+            let pool = PumpAmmPool::from_account_data(data).ok()?;
+            PoolAccountSummary::PumpAmmPool {
+                creator: pool.creator,
+                base_mint: pool.base_mint,
+                quote_mint: pool.quote_mint,
+                pool_base_token_account: pool.pool_base_token_account,
+                pool_quote_token_account: pool.pool_quote_token_account,
+                lp_supply: pool.lp_supply,
+            }
+        }
+        Dex::Pumpfun if discriminator == BONDING_CURVE_DISCRIMINATOR => {
+            let curve = BondingCurveAccount::from_account_data(data).ok()?;
+            PoolAccountSummary::BondingCurve {
+                virtual_sol_reserves: curve.virtual_sol_reserves,
+                virtual_token_reserves: curve.virtual_token_reserves,
+                real_sol_reserves: curve.real_sol_reserves,
+                real_token_reserves: curve.real_token_reserves,
+                token_total_supply: curve.token_total_supply,
+                complete: curve.complete,
+            }
+        }
+        _ => return None,
+    };
+    Some((dex, summary))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pool_account() {
+        let mut data = BONDING_CURVE_DISCRIMINATOR.to_vec();
+        for amt in [
+            1_073_000_000_000_000u64,
+            30_000_000_000,
+            793_100_000_000_000,
+            0,
+        ] {
+            data.extend(amt.to_le_bytes());
+        }
+        data.extend(1_000_000_000_000_000u64.to_le_bytes());
+        // complete, then the fields added later
+        data.push(0);
+        data.extend([7; 32]);
+
+        let pumpfun = Dex::Pumpfun.program_id();
+        let (dex, summary) = parse_pool_account(&pumpfun, &data).unwrap();
+        assert_eq!(dex, Dex::Pumpfun);
+        assert!(matches!(
+            summary,
+            PoolAccountSummary::BondingCurve {
+                virtual_sol_reserves: 30_000_000_000,
+                complete: false,
+                ..
+            }
+        ));
+
+        assert!(parse_pool_account(&Pubkey::new_unique(), &data).is_none());
+        assert!(parse_pool_account(&Dex::PumpAmm.program_id(), &data).is_none());
+        data[0] = 0;
+        assert!(parse_pool_account(&pumpfun, &data).is_none());
+    }
+}
@@ -0,0 +1,40 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use chrono::Utc;
+use tracing::info;
+
+use crate::{
+    cache::{self, DexEvent},
+    config::PressureConfig,
+};
+
+/// Emit `PressureUpdate` events of every window for the mints traded in the shortest window.
+pub async fn start(redis_client: Arc<redis::Client>, config: PressureConfig) -> Result<()> {
+    info!(
+        "start pressure watcher, windows: {:?} seconds, every {} seconds........",
+        config.windows_secs, config.interval_secs
+    );
+    let Some(shortest) = config.windows_secs.iter().min().copied() else {
+        return Ok(());
+    };
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_secs));
+    loop {
+        interval.tick().await;
+        let now = Utc::now();
+        let since = now - Duration::from_secs(shortest as u64);
+        let mut conn = redis_client.get_multiplexed_async_connection().await?;
+        let mints = cache::active_stats_mints(&mut conn, since, config.max_mints).await?;
+        if mints.is_empty() {
+            continue;
+        }
+
+        let mut events = vec![];
+        for window in &config.windows_secs {
+            let records = cache::mint_pressures(&mut conn, &mints, *window, now).await?;
+            events.extend(records.into_iter().map(DexEvent::PressureUpdate));
+        }
+        cache::rpush_dex_evts(&mut conn, None, &events).await?;
+        info!("{} pressure updates of {} mints", events.len(), mints.len());
+    }
+}
@@ -1,6 +1,9 @@
+use anyhow::{Result, anyhow};
 use borsh::BorshDeserialize;
 use solana_sdk::pubkey::Pubkey;
 
+use crate::{pda, rpc_provider::RpcProvider};
+
 #[derive(Debug, Clone, Copy, BorshDeserialize)]
 pub struct PumpAmmPool {
     pub pool_bump: u8,
@@ -13,3 +16,91 @@ pub struct PumpAmmPool {
     pub pool_quote_token_account: Pubkey,
     pub lp_supply: u64,
 }
+
+impl PumpAmmPool {
+    /// Fields added to the account later are ignored
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        let mut fields = data
+            .get(8..)
+            .ok_or_else(|| anyhow!("pumpamm pool account data too short"))?;
+        let result = Self::deserialize(&mut fields)
+            .map_err(|err| anyhow!("deserialize pumpamm pool error: {err}"))?;
+        Ok(result)
+    }
+}
+
+/// Protocol fee recipients kept in the global config
+pub const PUMPAMM_PROTOCOL_FEE_RECIPIENTS: usize = 8;
+
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct PumpAmmGlobalConfig {
+    pub admin: Pubkey,
+    pub lp_fee_basis_points: u64,
+    pub protocol_fee_basis_points: u64,
+    pub disable_flags: u8,
+    pub protocol_fee_recipients: [Pubkey; PUMPAMM_PROTOCOL_FEE_RECIPIENTS],
+    /// added with creator fees, none in older account data
+    #[borsh(skip)]
+    pub coin_creator_fee_basis_points: Option<u64>,
+}
+
+impl PumpAmmGlobalConfig {
+    pub fn address() -> Pubkey {
+        pda::pumpamm_global_config()
+    }
+
+    pub async fn from_rpc(rpc_client: &dyn RpcProvider) -> Result<Self> {
+        let account = rpc_client.get_account(&Self::address()).await?;
+        Self::from_account_data(&account.data)
+    }
+
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        let mut fields = data
+            .get(8..)
+            .ok_or_else(|| anyhow!("pumpamm global config account data too short"))?;
+        let mut result = Self::deserialize(&mut fields)
+            .map_err(|err| anyhow!("deserialize pumpamm global config error: {err}"))?;
+        result.coin_creator_fee_basis_points = u64::deserialize(&mut fields).ok();
+        Ok(result)
+    }
+
+    /// Fee bps charged on a swap
+    pub fn total_fee_bps(&self) -> u64 {
+        self.lp_fee_basis_points
+            + self.protocol_fee_basis_points
+            + self.coin_creator_fee_basis_points.unwrap_or_default()
+    }
+
+    pub fn is_protocol_fee_recipient(&self, recipient: &Pubkey) -> bool {
+        *recipient != Pubkey::default() && self.protocol_fee_recipients.contains(recipient)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_global_config_from_account_data() {
+        let recipient = Pubkey::new_unique();
+        let mut data = vec![0u8; 8];
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(&20u64.to_le_bytes());
+        data.extend_from_slice(&5u64.to_le_bytes());
+        data.push(0);
+        data.extend_from_slice(recipient.as_ref());
+        data.extend_from_slice(&[0u8; 32 * (PUMPAMM_PROTOCOL_FEE_RECIPIENTS - 1)]);
+
+        let config = PumpAmmGlobalConfig::from_account_data(&data).unwrap();
+        assert_eq!(config.coin_creator_fee_basis_points, None);
+        assert_eq!(config.total_fee_bps(), 25);
+        assert!(config.is_protocol_fee_recipient(&recipient));
+        assert!(!config.is_protocol_fee_recipient(&Pubkey::default()));
+
+        data.extend_from_slice(&5u64.to_le_bytes());
+        data.extend_from_slice(&[9u8; 32]);
+        let config = PumpAmmGlobalConfig::from_account_data(&data).unwrap();
+        assert_eq!(config.coin_creator_fee_basis_points, Some(5));
+        assert_eq!(config.total_fee_bps(), 30);
+    }
+}
@@ -3,6 +3,8 @@ use borsh::BorshDeserialize;
 use solana_sdk::pubkey::Pubkey;
 use tracing::debug;
 
+use crate::{common::Dex, parse_error::DexParseError};
+
 #[derive(Debug, Clone, Copy, BorshDeserialize)]
 pub struct PumpAmmCreatePoolEvent {
     pub timestamp: i64,
@@ -48,6 +50,20 @@ pub struct PumpAmmBuyEvent {
     pub user_quote_token_account: Pubkey,
     pub protocol_fee_recipient: Pubkey,
     pub protocol_fee_recipient_token_account: Pubkey,
+    /// added with creator fees, none in older events
+    #[borsh(skip)]
+    pub coin_creator_fee: Option<PumpAmmCoinCreatorFee>,
+    /// added with volume tracking after the creator fees, none in older events
+    #[borsh(skip)]
+    pub track_volume: Option<bool>,
+}
+
+/// Creator fee fields appended to the buy and sell events
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct PumpAmmCoinCreatorFee {
+    pub coin_creator: Pubkey,
+    pub coin_creator_fee_basis_points: u64,
+    pub coin_creator_fee: u64,
 }
 
 #[derive(Debug, Clone, Copy, BorshDeserialize)]
@@ -72,6 +88,49 @@ pub struct PumpAmmSellEvent {
     pub user_quote_token_account: Pubkey,
     pub protocol_fee_recipient: Pubkey,
     pub protocol_fee_recipient_token_account: Pubkey,
+    /// added with creator fees, none in older events
+    #[borsh(skip)]
+    pub coin_creator_fee: Option<PumpAmmCoinCreatorFee>,
+}
+
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct PumpAmmDepositEvent {
+    pub timestamp: i64,
+    pub lp_token_amount_out: u64,
+    pub max_base_amount_in: u64,
+    pub max_quote_amount_in: u64,
+    pub user_base_token_reserves: u64,
+    pub user_quote_token_reserves: u64,
+    pub pool_base_token_reserves: u64,
+    pub pool_quote_token_reserves: u64,
+    pub base_amount_in: u64,
+    pub quote_amount_in: u64,
+    pub lp_mint_supply: u64,
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub user_base_token_account: Pubkey,
+    pub user_quote_token_account: Pubkey,
+    pub user_pool_token_account: Pubkey,
+}
+
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct PumpAmmWithdrawEvent {
+    pub timestamp: i64,
+    pub lp_token_amount_in: u64,
+    pub min_base_amount_out: u64,
+    pub min_quote_amount_out: u64,
+    pub user_base_token_reserves: u64,
+    pub user_quote_token_reserves: u64,
+    pub pool_base_token_reserves: u64,
+    pub pool_quote_token_reserves: u64,
+    pub base_amount_out: u64,
+    pub quote_amount_out: u64,
+    pub lp_mint_supply: u64,
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub user_base_token_account: Pubkey,
+    pub user_quote_token_account: Pubkey,
+    pub user_pool_token_account: Pubkey,
 }
 
 #[derive(Debug)]
@@ -79,40 +138,68 @@ pub enum PumpAmmEvents {
     CreatePool(PumpAmmCreatePoolEvent),
     Buy(PumpAmmBuyEvent),
     Sell(PumpAmmSellEvent),
+    Deposit(PumpAmmDepositEvent),
+    Withdraw(PumpAmmWithdrawEvent),
 }
 
 impl PumpAmmEvents {
-    pub fn from_cpi_log(log: &str) -> Result<Self> {
+    /// Fields appended to the events by later program versions are ignored,
+    /// the known optional ones are read when present.
+    pub fn from_cpi_log(log: &str) -> Result<Self, DexParseError> {
         debug!("parse pumpamm log: {log}");
-        let bytes = bs58::decode(log).into_vec()?;
-        let bytes = &bytes[8..];
+        let bytes = bs58::decode(log)
+            .into_vec()
+            .map_err(|err| DexParseError::corrupt(Dex::PumpAmm, err))?;
+        let (discriminator, mut fields) = bytes
+            .get(8..)
+            .filter(|it| it.len() >= 8)
+            .map(|it| it.split_at(8))
+            .ok_or_else(|| DexParseError::corrupt(Dex::PumpAmm, "pump amm log is too short"))?;
 
-        let result = match &bytes[..8] {
+        let result = match discriminator {
             [177, 49, 12, 210, 160, 118, 167, 116] => {
-                let evt: PumpAmmCreatePoolEvent = borsh::from_slice(&bytes[8..])?;
+                let evt = PumpAmmCreatePoolEvent::deserialize(&mut fields).map_err(corrupt)?;
                 Self::CreatePool(evt)
             }
             [103, 244, 82, 31, 44, 245, 119, 119] => {
-                let evt: PumpAmmBuyEvent = borsh::from_slice(&bytes[8..])?;
+                let mut evt = PumpAmmBuyEvent::deserialize(&mut fields).map_err(corrupt)?;
+                evt.coin_creator_fee = PumpAmmCoinCreatorFee::deserialize(&mut fields).ok();
+                if evt.coin_creator_fee.is_some() {
+                    evt.track_volume = bool::deserialize(&mut fields).ok();
+                }
                 Self::Buy(evt)
             }
             [62, 47, 55, 10, 165, 3, 220, 42] => {
-                let evt: PumpAmmSellEvent = borsh::from_slice(&bytes[8..])?;
+                let mut evt = PumpAmmSellEvent::deserialize(&mut fields).map_err(corrupt)?;
+                evt.coin_creator_fee = PumpAmmCoinCreatorFee::deserialize(&mut fields).ok();
                 Self::Sell(evt)
             }
-            _ => {
-                let msg = format!("log is not recognized as pump amm log: {log}");
-                anyhow::bail!(msg)
+            [120, 248, 61, 83, 31, 142, 107, 144] => {
+                let evt = PumpAmmDepositEvent::deserialize(&mut fields).map_err(corrupt)?;
+                Self::Deposit(evt)
+            }
+            [22, 9, 133, 26, 160, 44, 71, 192] => {
+                let evt = PumpAmmWithdrawEvent::deserialize(&mut fields).map_err(corrupt)?;
+                Self::Withdraw(evt)
             }
+            _ => return Err(DexParseError::unknown(Dex::PumpAmm, discriminator)),
         };
 
         Ok(result)
     }
 }
 
+fn corrupt(err: std::io::Error) -> DexParseError {
+    DexParseError::corrupt(Dex::PumpAmm, err)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        rpc_tx::ANCHOR_EVENT_IX_TAG,
+        test_utils::{Encoding, decodes_arbitrary_bytes},
+    };
     #[test]
     fn test_create_pool_evt() {
         let evt_data = "rLaD5MVJGTSekbeMDJ6HPu2vjcD1CxmDA1gQymYBcRq6XBB4xCkgHtGtWK2Q4cJCJaqU3cbnFFpYE1VuvorWUEyvmRvi3822c3tEnKFiNEkgEhy2eiGskn9DhuyyMPURFDGNQCMfqurSm39XCu5HRsKgPi8pWxrzpDf6XaAaw1F8ti4D2CDJCQU5wKUqiGTcUt5phxnyNHAx13V4YWW6RjU5yoY5aXFeE7vwhkPnVGdJSKFioPEydYHWJnXLydcvKL5w91kkPSCPeGtFhV1nJSHW8WV48x32xd3DQgHS8yyniBjbenhF7M9Lw7Nu1969mk71vKMhes8BzPN4tQbbBQNSeKfxRb3nqkiLKUFaSqezDDLsc1W6LJpv3rh1tKHd1CFEMeMoa73twgb73aZ7cem9mrV2cuutYtqsNr";
@@ -135,4 +222,113 @@ mod tests {
         let evt = PumpAmmEvents::from_cpi_log(evt_data).unwrap();
         println!("pump amm sell event: {evt:#?}");
     }
+
+    /// The mainnet log with fields appended like a later program version
+    fn with_appended(log: &str, fields: &[u8]) -> String {
+        let mut data = bs58::decode(log).into_vec().unwrap();
+        data.extend_from_slice(fields);
+        bs58::encode(data).into_string()
+    }
+
+    #[test]
+    fn test_appended_fields() {
+        let buy_log = "w1295DLPcEG5wn5ZTAu91vQ18djDpDL3tybTWvQVi2WRAVj2ozjJ175VoKUrAn3DL6fvGfri2FxUBCkCtQW1945U26ADQX8fEBMBgHySLwbXxZodRxUYB4hBfD5MJK3CU3i7Un2vmZAKjCGAjZXggLmCdPdN5BAUZVC2p793gzEAkvAF7uugNXHDJ1KWPWLj1f7HGcQEhUKEwZAumW9YoPWfikc3Rf22mA5KQNZkhbk4XbDuASKSarMEEmjnXcp3Sxo2RarcE5nBj8Vn73VdDsfAFBHzPqHrxQ9MU1Zka3cSupvF4iwH5Sz1DJ9Da97EQthDTX6nP2uHB3UemQobL5NJ1Sk5tL5Kp13dv1NhLCggsJ5HUCy5nSpGwYPniDyPUvMEL6peWf2V6jWuAQ6ctS4pPAnpT5eTKGKpeECae3cZ55ot62ErQ";
+        let Ok(PumpAmmEvents::Buy(old)) = PumpAmmEvents::from_cpi_log(buy_log) else {
+            panic!("should be a buy event");
+        };
+        assert!(old.coin_creator_fee.is_none());
+        assert!(old.track_volume.is_none());
+
+        let coin_creator = Pubkey::new_unique();
+        let mut creator_fee = coin_creator.to_bytes().to_vec();
+        creator_fee.extend_from_slice(&5u64.to_le_bytes());
+        creator_fee.extend_from_slice(&1_000u64.to_le_bytes());
+        let mut appended = creator_fee.clone();
+        // track_volume, then volume counters not known here
+        appended.push(1);
+        appended.extend_from_slice(&[9u8; 32]);
+        let Ok(PumpAmmEvents::Buy(new)) =
+            PumpAmmEvents::from_cpi_log(&with_appended(buy_log, &appended))
+        else {
+            panic!("should be a buy event");
+        };
+        assert_eq!(new.quote_amount_in, old.quote_amount_in);
+        assert_eq!(new.pool, old.pool);
+        let fee = new.coin_creator_fee.unwrap();
+        assert_eq!(fee.coin_creator, coin_creator);
+        assert_eq!(fee.coin_creator_fee, 1_000);
+        assert_eq!(new.track_volume, Some(true));
+
+        let sell_log = "w1295DLPcEFrZVGvC9FAJRzkesEEPkg7dr1Fip6zXypBg16aNJWJEi5ocDmYTrudzSikvC4HkiEfMpkYgHGPeZiVmAxrXDHyAjCQLoeYDSmTAgNXahrdmDcZvc2xzp5osdZwF3YJwkAw9Lx5MVwzeA6xgLEM1h2fXEXwLgZ3MtswS5WLKcZDKcogZa7rp29BdpjXUkAvCkbCFEiwTTNLSdyXo5eLRUUqco4dt3oaPcNqDqsyxRZZ9PMoh3pXHHFifQjtbX4uMLkepryCvZA9tF4GVhYGS4sm2wkDTZ6HrBroaqCt1uNfpK7MFmBDvKung5oLsUdJPFGutVLA9AHC1fnnR89fMRmwZpwf8T4jHR2GBCbJwDHS6pK1BkmBpKUoLyn7oC3wpdG8u98qzN7oSBZMNgXDfWdpq4cQFj814zC4gB49RDcWH";
+        let Ok(PumpAmmEvents::Sell(new)) =
+            PumpAmmEvents::from_cpi_log(&with_appended(sell_log, &creator_fee))
+        else {
+            panic!("should be a sell event");
+        };
+        assert_eq!(
+            new.coin_creator_fee.unwrap().coin_creator_fee_basis_points,
+            5
+        );
+
+        // a truncated event is still an error
+        let truncated = &bs58::decode(sell_log).into_vec().unwrap()[..100];
+        assert!(PumpAmmEvents::from_cpi_log(&bs58::encode(truncated).into_string()).is_err());
+    }
+
+    #[test]
+    fn test_liquidity_evts() {
+        let pool = Pubkey::new_unique();
+        let mut fields = 1_700_000_000i64.to_le_bytes().to_vec();
+        for amt in 1..=10u64 {
+            fields.extend_from_slice(&amt.to_le_bytes());
+        }
+        fields.extend_from_slice(pool.as_ref());
+        for _ in 0..4 {
+            fields.extend_from_slice(Pubkey::new_unique().as_ref());
+        }
+        let cpi_log = |discriminator: [u8; 8]| {
+            let mut data = vec![0xe4, 0x45, 0xa5, 0x2e, 0x51, 0xcb, 0x9a, 0x1d];
+            data.extend(discriminator);
+            data.extend_from_slice(&fields);
+            bs58::encode(data).into_string()
+        };
+
+        let Ok(PumpAmmEvents::Deposit(evt)) =
+            PumpAmmEvents::from_cpi_log(&cpi_log([120, 248, 61, 83, 31, 142, 107, 144]))
+        else {
+            panic!("should be a deposit event");
+        };
+        assert_eq!((evt.base_amount_in, evt.quote_amount_in), (8, 9));
+        assert_eq!(evt.pool, pool);
+
+        let Ok(PumpAmmEvents::Withdraw(evt)) =
+            PumpAmmEvents::from_cpi_log(&cpi_log([22, 9, 133, 26, 160, 44, 71, 192]))
+        else {
+            panic!("should be a withdraw event");
+        };
+        assert_eq!(evt.lp_token_amount_in, 1);
+        assert_eq!(evt.pool, pool);
+    }
+
+    const DISCRIMINATORS: [[u8; 8]; 5] = [
+        [177, 49, 12, 210, 160, 118, 167, 116],
+        [103, 244, 82, 31, 44, 245, 119, 119],
+        [62, 47, 55, 10, 165, 3, 220, 42],
+        [120, 248, 61, 83, 31, 142, 107, 144],
+        [22, 9, 133, 26, 160, 44, 71, 192],
+    ];
+
+    quickcheck::quickcheck! {
+        fn test_decode_arbitrary_bytes(kind: u8, fields: Vec<u8>, log: String) -> bool {
+            decodes_arbitrary_bytes(
+                Encoding::Base58,
+                &ANCHOR_EVENT_IX_TAG,
+                &DISCRIMINATORS,
+                kind,
+                &fields,
+                &log,
+                PumpAmmEvents::from_cpi_log,
+            )
+        }
+    }
 }
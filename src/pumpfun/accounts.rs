@@ -1,10 +1,9 @@
 use anyhow::Result;
 use borsh::BorshDeserialize;
 use serde::Serialize;
-use solana_rpc_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::pubkey::Pubkey;
 
-use super::PUMPFUN_PROGRAM_ID;
+use crate::{pda, rpc_provider::RpcProvider};
 
 #[derive(Debug, Clone, Copy, BorshDeserialize, Serialize)]
 pub struct BondingCurveAccount {
@@ -20,21 +19,19 @@ pub struct BondingCurveAccount {
 #[allow(unused)]
 impl BondingCurveAccount {
     pub fn find_pda(mint: Pubkey) -> Pubkey {
-        let (pda, _) = Pubkey::find_program_address(
-            &[
-                &[98, 111, 110, 100, 105, 110, 103, 45, 99, 117, 114, 118, 101],
-                &mint.to_bytes(),
-            ],
-            &PUMPFUN_PROGRAM_ID,
-        );
-
-        pda
+        pda::pumpfun_bonding_curve(&mint)
     }
 
-    pub async fn from_rpc(rpc_client: &RpcClient, curve: &Pubkey) -> Result<Self> {
+    pub async fn from_rpc(rpc_client: &dyn RpcProvider, curve: &Pubkey) -> Result<Self> {
         let account = rpc_client.get_account(curve).await?;
 
         let result: BondingCurveAccount = borsh::from_slice(&account.data)?;
         Ok(result)
     }
+
+    /// Fields added to the account later are ignored
+    pub fn from_account_data(mut data: &[u8]) -> Result<Self> {
+        let result = Self::deserialize(&mut data)?;
+        Ok(result)
+    }
 }
@@ -3,7 +3,9 @@ use anyhow::Result;
 use borsh::BorshDeserialize;
 use solana_sdk::borsh1;
 use solana_sdk::pubkey::Pubkey;
-use tracing::{debug, warn};
+use tracing::debug;
+
+use crate::{common::Dex, parse_error::DexParseError};
 
 #[derive(Debug, BorshDeserialize)]
 pub struct TradeEvent {
@@ -69,33 +71,23 @@ pub enum PumpFunEventKind {
 }
 
 impl PumpFunEvents {
-    pub fn from_cpi_log(log: &str) -> Result<Self> {
+    pub fn from_cpi_log(log: &str) -> Result<Self, DexParseError> {
         debug!("parse pumpfun log: {log}");
-        let bytes = bs58::decode(log).into_vec()?;
-        let bytes = &bytes[8..];
-
-        let result = match &bytes[..8] {
-            [189, 219, 127, 211, 78, 230, 97, 238] => {
-                let evt: TradeEvent = borsh1::try_from_slice_unchecked(bytes)?;
-                Self::Trade(evt)
-            }
-            [27, 114, 169, 77, 222, 235, 99, 118] => {
-                let evt: CreateEvent = borsh1::try_from_slice_unchecked(bytes)?;
-                Self::Create(evt)
-            }
-            [95, 114, 97, 156, 212, 46, 152, 8] => {
-                let evt: CompleteEvent = borsh1::try_from_slice_unchecked(bytes)?;
-                Self::Complete(evt)
-            }
-            [223, 195, 159, 246, 62, 48, 143, 131] => {
-                let evt: SetParamsEvent = borsh1::try_from_slice_unchecked(bytes)?;
-                Self::SetParams(evt)
-            }
-            _ => {
-                let msg = format!("log is not pumpfun log: {log}");
-                warn!(msg);
-                anyhow::bail!(msg)
-            }
+        let bytes = bs58::decode(log)
+            .into_vec()
+            .map_err(|err| DexParseError::corrupt(Dex::Pumpfun, err))?;
+        let (discriminator, bytes) = bytes
+            .get(8..)
+            .and_then(|it| Some((it.get(..8)?, it)))
+            .ok_or_else(|| DexParseError::corrupt(Dex::Pumpfun, "pumpfun log is too short"))?;
+
+        // fields appended by later program versions are ignored
+        let result = match discriminator {
+            [189, 219, 127, 211, 78, 230, 97, 238] => Self::Trade(deserialize(bytes)?),
+            [27, 114, 169, 77, 222, 235, 99, 118] => Self::Create(deserialize(bytes)?),
+            [95, 114, 97, 156, 212, 46, 152, 8] => Self::Complete(deserialize(bytes)?),
+            [223, 195, 159, 246, 62, 48, 143, 131] => Self::SetParams(deserialize(bytes)?),
+            _ => return Err(DexParseError::unknown(Dex::Pumpfun, discriminator)),
         };
 
         Ok(result)
@@ -112,11 +104,19 @@ impl PumpFunEvents {
     }
 }
 
+fn deserialize<T: BorshDeserialize>(bytes: &[u8]) -> Result<T, DexParseError> {
+    borsh1::try_from_slice_unchecked(bytes).map_err(|err| DexParseError::corrupt(Dex::Pumpfun, err))
+}
+
 #[cfg(test)]
 mod tests {
     use base64::prelude::BASE64_STANDARD;
 
     use super::*;
+    use crate::{
+        rpc_tx::ANCHOR_EVENT_IX_TAG,
+        test_utils::{Encoding, decodes_arbitrary_bytes},
+    };
 
     #[test]
     fn test_decode_pump_trade_evt() {
@@ -138,4 +138,90 @@ mod tests {
         let evt = PumpFunEvents::from_cpi_log(encoded_complete_evt).unwrap();
         println!("pumpfun complete event: {evt:#?}");
     }
+
+    #[test]
+    fn test_decode_pump_trade_evt_appended_fields() {
+        let encoded_evt = "2K7nL28PxCW8ejnyCeuMpbXwJKzXo9q1ecEyRsXKe7VYaxLjCqTrMCp9pnwrwTG7rmaRTa1vcTqa8LGDfNZ9bpcKgSPgNDe3MrFn57HPpTzriKWACnH99YDM7dfTpxwRoCQTrs6BSdGSXgusW9Jbz1yAV9D32MZ62azsiK16Gksbq7cinYkugTfQDJM5";
+        let PumpFunEvents::Trade(old) = PumpFunEvents::from_cpi_log(encoded_evt).unwrap() else {
+            panic!("should be a trade event");
+        };
+
+        // fee recipient, fee bps, fee, creator, creator fee bps, creator fee, track_volume
+        let mut data = bs58::decode(encoded_evt).into_vec().unwrap();
+        data.extend_from_slice(&[3u8; 32 + 8 + 8 + 32 + 8 + 8]);
+        data.push(1);
+        let encoded_evt = bs58::encode(data).into_string();
+        let PumpFunEvents::Trade(new) = PumpFunEvents::from_cpi_log(&encoded_evt).unwrap() else {
+            panic!("should be a trade event");
+        };
+        assert_eq!(new.mint, old.mint);
+        assert_eq!(new.sol_amount, old.sol_amount);
+        assert_eq!(new.real_token_reserves, old.real_token_reserves);
+
+        assert!(PumpFunEvents::from_cpi_log("1111").is_err());
+    }
+
+    #[test]
+    fn test_decode_pump_set_params_evt() {
+        let fee_recipient = Pubkey::new_unique();
+        let mut data = vec![0xe4, 0x45, 0xa5, 0x2e, 0x51, 0xcb, 0x9a, 0x1d];
+        data.extend([223, 195, 159, 246, 62, 48, 143, 131]);
+        data.extend_from_slice(fee_recipient.as_ref());
+        for value in [
+            1_073_000_000_000_000u64,
+            30_000_000_000,
+            793_100_000_000_000,
+            1_000_000_000_000_000,
+            95,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        let evt = PumpFunEvents::from_cpi_log(&bs58::encode(data).into_string()).unwrap();
+        let PumpFunEvents::SetParams(evt) = evt else {
+            panic!("should be a set params event");
+        };
+        assert_eq!(evt.fee_recipient, fee_recipient);
+        assert_eq!(evt.initial_virtual_sol_reserves, 30_000_000_000);
+        assert_eq!(evt.fee_basis_points, 95);
+    }
+
+    #[test]
+    fn test_decode_unknown_or_truncated_evt() {
+        let encoded_evt = "2K7nL28PxCW8ejnyCeuMpbXwJKzXo9q1ecEyRsXKe7VYaxLjCqTrMCp9pnwrwTG7rmaRTa1vcTqa8LGDfNZ9bpcKgSPgNDe3MrFn57HPpTzriKWACnH99YDM7dfTpxwRoCQTrs6BSdGSXgusW9Jbz1yAV9D32MZ62azsiK16Gksbq7cinYkugTfQDJM5";
+        let data = bs58::decode(encoded_evt).into_vec().unwrap();
+        let truncated = bs58::encode(&data[..40]).into_string();
+        assert!(matches!(
+            PumpFunEvents::from_cpi_log(&truncated),
+            Err(DexParseError::CorruptPayload { .. })
+        ));
+
+        let mut unknown = data.clone();
+        unknown[8..16].copy_from_slice(&[7; 8]);
+        let unknown = bs58::encode(unknown).into_string();
+        assert!(matches!(
+            PumpFunEvents::from_cpi_log(&unknown),
+            Err(DexParseError::UnknownDiscriminator { .. })
+        ));
+    }
+
+    const DISCRIMINATORS: [[u8; 8]; 4] = [
+        [189, 219, 127, 211, 78, 230, 97, 238],
+        [27, 114, 169, 77, 222, 235, 99, 118],
+        [95, 114, 97, 156, 212, 46, 152, 8],
+        [223, 195, 159, 246, 62, 48, 143, 131],
+    ];
+
+    quickcheck::quickcheck! {
+        fn test_decode_arbitrary_bytes(kind: u8, fields: Vec<u8>, log: String) -> bool {
+            decodes_arbitrary_bytes(
+                Encoding::Base58,
+                &ANCHOR_EVENT_IX_TAG,
+                &DISCRIMINATORS,
+                kind,
+                &fields,
+                &log,
+                PumpFunEvents::from_cpi_log,
+            )
+        }
+    }
 }
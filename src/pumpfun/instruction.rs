@@ -0,0 +1,85 @@
+use borsh::BorshDeserialize;
+
+use crate::{common::Dex, parse_error::DexParseError};
+
+// buy                   # data prefix: 66063d1201daebea [102,6,61,18,1,218,235,234]
+// sell                  # data prefix: 33e685a4017f83ad [51,230,133,164,1,127,131,173]
+
+pub const BUY_IX_ID: [u8; 8] = [102, 6, 61, 18, 1, 218, 235, 234];
+pub const SELL_IX_ID: [u8; 8] = [51, 230, 133, 164, 1, 127, 131, 173];
+
+/// The args of a buy or a sell, pump amm shares the layout
+#[derive(Debug, Clone, Copy, PartialEq, BorshDeserialize)]
+pub struct PumpfunTradeArgs {
+    /// exact tokens bought or sold
+    pub token_amount: u64,
+    /// the most SOL a buy pays, the least a sell receives
+    pub sol_limit: u64,
+}
+
+/// The swaps of a pump program, decoded from the instructions of the failed transactions which
+/// log no trade event
+#[derive(Debug, PartialEq)]
+pub enum PumpfunInstructions {
+    Buy(PumpfunTradeArgs),
+    Sell(PumpfunTradeArgs),
+    /// the other instructions, by discriminator
+    Other([u8; 8]),
+}
+
+impl PumpfunInstructions {
+    /// Decode the bs58 instruction data of the pumpfun or the pump amm program. The args added
+    /// by newer versions of the programs are ignored.
+    pub fn from_ix_data(ix_data: &str, dex: Dex) -> Result<Self, DexParseError> {
+        let bytes = bs58::decode(ix_data)
+            .into_vec()
+            .map_err(|err| DexParseError::corrupt(dex, err))?;
+        let (discriminator, mut args) = bytes
+            .split_at_checked(8)
+            .ok_or_else(|| DexParseError::corrupt(dex, "pump instruction is too short"))?;
+        let mut trade_args = || {
+            PumpfunTradeArgs::deserialize(&mut args).map_err(|err| DexParseError::corrupt(dex, err))
+        };
+
+        let result = match discriminator {
+            d if d == BUY_IX_ID => Self::Buy(trade_args()?),
+            d if d == SELL_IX_ID => Self::Sell(trade_args()?),
+            _ => Self::Other(discriminator.try_into().unwrap_or_default()),
+        };
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use borsh::BorshSerialize;
+
+    use super::*;
+
+    #[test]
+    fn test_decode_pumpfun_ix() {
+        let mut data = BUY_IX_ID.to_vec();
+        (5_000_000u64, 42u64).serialize(&mut data).unwrap();
+        let args = PumpfunTradeArgs {
+            token_amount: 5_000_000,
+            sol_limit: 42,
+        };
+        let decode = |data: &[u8]| {
+            PumpfunInstructions::from_ix_data(&bs58::encode(data).into_string(), Dex::Pumpfun)
+        };
+        assert_eq!(decode(&data).unwrap(), PumpfunInstructions::Buy(args));
+
+        // the track volume flag of the newer buys
+        data.push(1);
+        assert_eq!(decode(&data).unwrap(), PumpfunInstructions::Buy(args));
+
+        data[..8].copy_from_slice(&SELL_IX_ID);
+        assert_eq!(decode(&data).unwrap(), PumpfunInstructions::Sell(args));
+        assert!(decode(&data[..12]).is_err());
+        assert!(decode(&data[..4]).is_err());
+
+        let create = [24, 30, 200, 40, 5, 28, 7, 119];
+        assert_eq!(decode(&create).unwrap(), PumpfunInstructions::Other(create));
+    }
+}
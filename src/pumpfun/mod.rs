@@ -3,6 +3,7 @@ use solana_sdk::pubkey::Pubkey;
 
 pub mod accounts;
 pub mod event;
+pub mod instruction;
 
 pub const PUMPFUN_PROGRAM_ID: Pubkey = pubkey!("6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P");
 pub const CREATE_LOG_PREFIX: &str = "Program data: G3KpTd7r";
@@ -1,33 +1,53 @@
 use std::{
-    collections::HashSet,
-    sync::Arc,
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    sync::{
+        Arc,
+        atomic::{AtomicI64, AtomicU64, Ordering},
+    },
     time::{Duration, Instant},
 };
 
-use anyhow::{ Result, anyhow};
+use anyhow::Result;
 use chrono::{DateTime, Utc};
-use futures::{StreamExt, TryStreamExt};
-use itertools::{Itertools};
-use serde::Deserialize;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+use tokio::task::JoinSet;
 use tracing::{info, warn};
 
 use crate::{
     cache::{
-        self, DexEvent, DexPoolCreatedRecord, DexPoolRecord, PumpfunCompleteRecord,
-        RedisCacheRecord, TradeRecord,
+        self, DexEvent, DexPoolCache, DexPoolCreatedRecord, DexPoolRecord, LiquidityChangeRecord,
+        MeteoraDbcCompleteRecord, MigrationCompletedRecord, ParseFailure, PumpfunCompleteRecord,
+        PumpfunSetParamsRecord, QuarantinedLog, RedisCacheRecord, ShadowDiff, TradeRecord,
+    },
+    common::{self, Dex, TxBaseMetaInfo},
+    compute_budget::ComputeBudget,
+    config::{
+        AppConfig, AvgPriceConfig, PoolDrainedConfig, PoolReservesConfig, PriceGuardConfig,
+        WalletClustersConfig,
     },
-    common::TxBaseMetaInfo,
     meteora::{
-        METEORA_DAMM_PROGRAM_ID, METEORA_DLMM_PROGRAM_ID, damm::event::MeteoraDammEvents,
-        dlmm::event::MeteoraDlmmEvents,
+        METEORA_DAMM_PROGRAM_ID, METEORA_DBC_PROGRAM_ID, METEORA_DLMM_PROGRAM_ID,
+        damm::event::MeteoraDammEvents,
+        dbc::event::MeteoraDbcEvents,
+        dlmm::event::{MeteoraDlmmEvents, MeteoraDlmmLiquidityEvent},
     },
+    moonshot::{MOONSHOT_PROGRAM_ID, instruction::MoonshotInstructions},
+    orca::{ORCA_WHIRLPOOL_PROGRAM_ID, event::WhirlpoolEvents, instruction::swap_account_idx},
+    parse_error::{self, DexParseError, LogAccounts, ParseErrorAction},
     pumpamm::{PUMPAMM_PROGRAM_ID, event::PumpAmmEvents},
     pumpfun::{PUMPFUN_PROGRAM_ID, event::PumpFunEvents},
-    raydium::{RAYDIUM_AMM_PROGRAM_ID, event::RayLogs},
+    raydium::{
+        RAYDIUM_AMM_PROGRAM_ID, RAYDIUM_CLMM_PROGRAM_ID, RAYDIUM_CPMM_PROGRAM_ID,
+        clmm::event::RaydiumClmmEvents, cpmm::instruction::RaydiumCpmmInstructions, event::RayLogs,
+    },
+    shadow_parser,
 };
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Tx {
     pub blk_ts: i64,
@@ -35,16 +55,38 @@ pub struct Tx {
     pub signature: String,
     pub logs: Vec<String>,
     pub ixs: Vec<ProgramInvocation>,
+    /// error of a failed transaction, which has no event logs but the dex instructions it
+    /// attempted, see `failed_swaps` of the config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub err: Option<serde_json::Value>,
+    /// set by the compute budget instructions, for the congestion metrics
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compute_budget: Option<ComputeBudget>,
+    /// the only logs parsed of a transaction queued again from the parse failures, its other
+    /// logs emitted their events the first time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requeued_logs: Option<Vec<usize>>,
+}
+
+impl Tx {
+    /// Pool accounts of the dex instructions, used to prefetch cached pools
+    pub fn pool_addrs(&self) -> impl Iterator<Item = Pubkey> + '_ {
+        self.ixs.iter().filter_map(|it| {
+            let dex = Dex::from_program_id(&it.program_id)?;
+            let account = it.instruction.accounts.get(dex.swap_pool_account_idx())?;
+            Pubkey::from_str(&account.pubkey).ok()
+        })
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ProgramInvocation {
     pub program_id: String,
     pub instruction: Instruction,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct IxAccount {
     pub pubkey: String,
@@ -52,7 +94,7 @@ pub struct IxAccount {
     pub post_amt: Amt,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Amt {
     pub sol: u64,
@@ -60,7 +102,7 @@ pub struct Amt {
 }
 
 #[serde_as]
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TokenAmt {
     pub mint: String,
@@ -69,15 +111,30 @@ pub struct TokenAmt {
     pub amt: u64,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Instruction {
     pub accounts: Vec<IxAccount>,
     pub data: String,
     pub index: u64,
+    /// token transfers invoked by the instructions of the dexes decoded without an event, in
+    /// execution order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transfers: Vec<TokenTransfer>,
+}
+
+/// A `transfer` or `transferChecked` of a token program, between two token accounts
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenTransfer {
+    pub source: String,
+    pub destination: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub amt: u64,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct QnStreamMetadata {
     pub batch_end_range: u64,
     pub batch_start_range: u64,
@@ -92,355 +149,1305 @@ pub struct QnStreamMetadata {
     pub stream_region: String,
 }
 
-#[derive(Debug, Deserialize)]
+/// The stream batch an event was parsed from, to attribute the event and detect batch boundaries
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventSource {
+    pub stream_id: String,
+    pub batch_id: String,
+    pub batch_start_range: u64,
+    pub batch_end_range: u64,
+}
+
+impl QnStreamMetadata {
+    pub fn event_source(&self, batch_id: &str) -> EventSource {
+        EventSource {
+            stream_id: self.stream_id.clone(),
+            batch_id: batch_id.to_string(),
+            batch_start_range: self.batch_start_range,
+            batch_end_range: self.batch_end_range,
+        }
+    }
+
+    /// Metadata of transactions which don't come from a quicknode stream, none without txs
+    pub fn for_txs(dataset: &str, stream_name: &str, txs: &[Tx]) -> Option<Self> {
+        let (min_slot, max_slot) = txs.iter().map(|it| it.slot).minmax().into_option()?;
+        Some(Self {
+            batch_end_range: max_slot,
+            batch_start_range: min_slot,
+            dataset: dataset.to_string(),
+            end_range: -1,
+            keep_distance_from_tip: 0,
+            network: "solana-mainnet".to_string(),
+            start_range: min_slot,
+            stream_id: String::new(),
+            stream_name: stream_name.to_string(),
+            stream_region: String::new(),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct QnSolDexDatahubWebhookReq {
+    /// assigned on ingest, none for requests queued before batch ids were introduced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_id: Option<String>,
     pub txs: Vec<Tx>,
     pub metadata: QnStreamMetadata,
 }
 
-const DEX_POOL_EXP_SECS: u64 = 3600 * 12;
+static BATCH_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Id of an ingested request, returned to the stream and logged through the pipeline.
+pub fn new_batch_id() -> String {
+    let seq = BATCH_SEQ.fetch_add(1, Ordering::Relaxed);
+    format!("{}-{seq}", Utc::now().format("%Y%m%d%H%M%S%3f"))
+}
+
+impl QnSolDexDatahubWebhookReq {
+    /// Parse an ingested request body and assign it `batch_id`, replacing one sent by the
+    /// stream. A body with a duplicate key is rejected.
+    pub fn from_ingested(body: &str, batch_id: &str) -> serde_json::Result<Self> {
+        let mut req: Self = serde_json::from_str(body)?;
+        req.batch_id = Some(batch_id.to_string());
+        Ok(req)
+    }
+}
+
+/// Progress of the processor, read by the status broadcast.
+#[derive(Debug, Default)]
+pub struct PipelineStatus {
+    processed_slot: AtomicU64,
+    processed_blk_ts: AtomicI64,
+    processed_events: AtomicU64,
+}
+
+impl PipelineStatus {
+    pub fn record(&self, max_slot: u64, max_blk_ts: i64, events: usize) {
+        self.processed_slot.fetch_max(max_slot, Ordering::Relaxed);
+        self.processed_blk_ts
+            .fetch_max(max_blk_ts, Ordering::Relaxed);
+        self.processed_events
+            .fetch_add(events as u64, Ordering::Relaxed);
+    }
 
-pub async fn start(redis_client: Arc<redis::Client>) -> Result<()> {
+    pub fn processed_slot(&self) -> u64 {
+        self.processed_slot.load(Ordering::Relaxed)
+    }
+
+    /// block timestamp of the latest processed transaction, 0 before the first one
+    pub fn processed_blk_ts(&self) -> i64 {
+        self.processed_blk_ts.load(Ordering::Relaxed)
+    }
+
+    /// total events parsed since start
+    pub fn processed_events(&self) -> u64 {
+        self.processed_events.load(Ordering::Relaxed)
+    }
+}
+
+/// What the processor does with the parsed events besides queueing them
+#[derive(Debug, Clone, Default)]
+pub struct ProcessorOptions {
+    /// record the latest trade time of mints for the inactive mint watcher
+    pub track_mint_activity: bool,
+    /// record the latest trade time of pools for the state tracker
+    pub track_pool_activity: bool,
+    /// queue the creators of the pools and the buyers of pools created in the same batch for the
+    /// funder warmer
+    pub queue_funder_lookups: bool,
+    /// flag or drop the trades priced far from the recent prices of their pool
+    pub price_guard: Option<PriceGuardConfig>,
+    /// drop the events of these dexes
+    pub disabled_dexes: Vec<Dex>,
+    /// snapshot the reserves of traded pools
+    pub pool_reserves: Option<PoolReservesConfig>,
+    /// sum the trades into the rolling averages of their mint
+    pub avg_price: Option<AvgPriceConfig>,
+    /// diff the events of the candidate parser against the production parser
+    pub shadow_parser: bool,
+    /// diff one batch in this many
+    pub shadow_parser_sample: u64,
+    /// price the trades in USD with the SOL/USD price of the oracle
+    pub sol_usd: bool,
+    /// drop the events queued already in the last seconds
+    pub event_dedup_secs: Option<u64>,
+    /// record the early buyers of the mints and attach the clusters of the traders
+    pub wallet_clusters: Option<WalletClustersConfig>,
+    /// watch the lp tokens of the created raydium amm pools
+    pub lp_lock: bool,
+    /// mark the pools whose SOL reserves fell under the floor as drained
+    pub pool_drained: Option<PoolDrainedConfig>,
+    /// decode the swaps attempted by the failed transactions
+    pub failed_swaps: bool,
+    /// record the compute budgets of the transactions by slot
+    pub congestion: bool,
+    /// parse the transactions of a batch on this many concurrent tasks, serially if unset
+    pub parse_workers: Option<usize>,
+}
+
+impl ProcessorOptions {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            track_mint_activity: config.mint_inactive_secs.is_some(),
+            track_pool_activity: config.state_tracker.is_some(),
+            queue_funder_lookups: config.first_funder_lookup,
+            price_guard: config.price_guard.clone(),
+            disabled_dexes: config.disabled_dexes.clone(),
+            pool_reserves: config.pool_reserves.clone(),
+            avg_price: config.avg_price.clone(),
+            shadow_parser: config.shadow_parser,
+            shadow_parser_sample: config.shadow_parser_sample,
+            sol_usd: config.sol_usd.is_some(),
+            event_dedup_secs: config.event_dedup_secs,
+            wallet_clusters: config.wallet_clusters.clone(),
+            lp_lock: config.lp_lock.is_some(),
+            pool_drained: config.pool_drained.clone(),
+            failed_swaps: config.failed_swaps,
+            congestion: config.congestion,
+            parse_workers: config.parse_workers,
+        }
+    }
+}
+
+pub async fn start(
+    redis_client: Arc<redis::Client>,
+    options: &ProcessorOptions,
+    status: Arc<PipelineStatus>,
+) -> Result<()> {
     info!("start qn request processor........");
+    // one connection for every batch, the processor is started again with a new one when
+    // it breaks
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    // of the non-empty batches, to sample the shadow parse
+    let mut batches: u64 = 0;
     loop {
         let start = Instant::now();
-        let mut conn = redis_client.get_multiplexed_async_connection().await?;
-        let reqs = cache::lrange_qn_requests(&mut conn).await?;
-        drop(conn);
-
-        let webhook_reqs: Vec<_> = futures::stream::iter(reqs)
-            .map(|it| async move { serde_json::from_str::<QnSolDexDatahubWebhookReq>(&it) })
-            .buffered(5)
-            .try_collect::<Vec<_>>()
-            .await?;
-        let webhook_req_len = webhook_reqs.len();
+        let batch = cache::claim_qn_requests(&mut conn).await?;
+        let reqs = batch.reqs;
+        if reqs.is_empty() {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            continue;
+        }
+
+        // a malformed request is skipped, failing the batch would claim it again forever
+        let webhook_reqs: Vec<_> = reqs
+            .iter()
+            .filter_map(
+                |it| match serde_json::from_str::<QnSolDexDatahubWebhookReq>(it) {
+                    Ok(req) => Some(req),
+                    Err(err) => {
+                        warn!("malformed stream request, skip it: {err}");
+                        None
+                    }
+                },
+            )
+            .collect();
 
-        let (metas, txs): (Vec<_>, Vec<_>) = webhook_reqs
-            .into_iter()
-            .map(|it| (it.metadata, it.txs))
-            .unzip();
-        for meta in metas {
+        let mut batch_ids = vec![];
+        let mut txs = vec![];
+        for req in webhook_reqs {
+            let meta = req.metadata;
+            let batch_id = req.batch_id.unwrap_or_else(|| "-".to_string());
             info!(
-                "process slot range: [{} - {}] {} transactions from stream region: {}",
+                "process batch {batch_id} slot range: [{} - {}] {} transactions from stream region: {}",
                 meta.batch_start_range, meta.batch_end_range, meta.network, meta.stream_region
             );
+            let source = meta.event_source(&batch_id);
+            batch_ids.push(batch_id);
+            txs.extend(req.txs.into_iter().map(|tx| (source.clone(), tx)));
         }
 
-        let txs: Vec<_> = txs.into_iter().flatten().collect();
         if txs.is_empty() {
+            cache::ack_qn_requests(&mut conn).await?;
             tokio::time::sleep(Duration::from_millis(300)).await;
             continue;
         }
 
-        let max_blk_ts = txs.iter().map(|it| it.blk_ts).max().unwrap_or_default();
+        let max_blk_ts = txs
+            .iter()
+            .map(|(_, it)| it.blk_ts)
+            .max()
+            .unwrap_or_default();
         let time_diff = Utc::now().timestamp() - max_blk_ts;
         let (min_slot, max_slot) = txs
             .iter()
-            .map(|it| it.slot)
+            .map(|(_, it)| it.slot)
             .minmax()
             .into_option()
             .expect("find min_slot and max_slot error");
-        let mut all_events = vec![];
-        let mut mints = HashSet::new();
-
-        for tx in txs {
-            let slot = tx.slot;
-            let txid = tx.signature;
-            let blk_ts = DateTime::from_timestamp(tx.blk_ts, 0)
-                .ok_or_else(|| anyhow!("block timestamp error in quicknode stream"))?;
-            let ixs: Vec<_> = tx
-                .ixs
+        // of the failed txs too, they competed for the slot as well. A tx without compute budget
+        // instructions pays no priority fee, its price is 0 like that of the limit only ones.
+        if options.congestion {
+            let budgets: Vec<_> = txs
                 .iter()
-                .filter(|it| {
-                    // exclude meteora dlmm initBinArray Instruction
-                    !(it.program_id == METEORA_DLMM_PROGRAM_ID.to_string()
-                        && it.instruction.data.starts_with("5N5iEh8c"))
+                .map(|(_, it)| {
+                    let budget = it.compute_budget.unwrap_or_default();
+                    (it.slot, it.signature.as_str(), budget)
                 })
                 .collect();
-            for (idx, log) in tx.logs.into_iter().enumerate() {
-                let invocation = ixs.get(idx);
-                if invocation.is_none() {
-                    continue;
+            cache::record_compute_budgets(&mut conn, &budgets).await?;
+        }
+        // a clone shares the connection
+        let mut pools = DexPoolCache::new(conn.clone());
+        pools
+            .prefetch(txs.iter().flat_map(|(_, it)| it.pool_addrs()))
+            .await?;
+        batches += 1;
+        // parsed before the production parse, which changes the pools
+        let candidates =
+            if options.shadow_parser && batches.is_multiple_of(options.shadow_parser_sample) {
+                shadow_parser::parse_candidate(&txs, &pools).await
+            } else {
+                vec![]
+            };
+        let BatchParse {
+            events: mut all_events,
+            mut event_logs,
+            quarantined,
+            failures,
+            shadow_diffs,
+        } = parse_batch(txs, candidates, &mut pools, options).await?;
+        cache::pair_batch_migrations(&mut all_events, &mut event_logs);
+        // before the stats, so a duplicate isn't counted twice either
+        let mut seen_keys = vec![];
+        if options.event_dedup_secs.is_some() {
+            let parsed = all_events.len();
+            seen_keys = cache::drop_seen_dex_evts(&mut conn, &mut all_events, &event_logs).await?;
+            if all_events.len() < parsed {
+                warn!(
+                    "{} events queued already, dropped",
+                    parsed - all_events.len()
+                );
+            }
+        }
+        // saved with the pools of the batch once its events are queued
+        let mut drained_pools = None;
+        if let Some(pool_drained) = &options.pool_drained {
+            let mut drained =
+                cache::detect_drained_pools(&mut pools, &mut all_events, pool_drained).await?;
+            if !drained.events.is_empty() {
+                info!("{} pools drained", drained.events.len());
+            }
+            all_events.append(&mut drained.events);
+            drained_pools = Some(drained);
+        }
+        pools.flush().await?;
+        all_events.retain(|it| {
+            it.dex()
+                .is_none_or(|dex| !options.disabled_dexes.contains(&dex))
+        });
+
+        cache::quarantine_logs(&mut conn, &batch.id, &quarantined).await?;
+        cache::push_parse_failures(&mut conn, &failures).await?;
+        cache::record_shadow_diffs(&mut conn, &shadow_diffs).await?;
+        if let Some(price_guard) = &options.price_guard {
+            cache::guard_trade_prices(&mut conn, &mut all_events, price_guard).await?;
+        }
+        cache::annotate_pool_trades(&mut conn, &batch.id, &mut all_events).await?;
+        if options.wallet_clusters.is_some() {
+            cache::annotate_wallet_clusters(&mut conn, &mut all_events).await?;
+        }
+        if options.sol_usd {
+            let sol_usd = cache::get_sol_usd_price(&mut conn).await?;
+            cache::annotate_usd_prices(&mut all_events, sol_usd);
+        }
+        if let Some(avg_price) = &options.avg_price {
+            cache::record_avg_prices(&mut conn, &batch.id, &mut all_events, avg_price).await?;
+        }
+
+        let events_len = all_events.len();
+        let trades: Vec<_> = all_events
+            .iter()
+            .filter_map(|it| match it {
+                DexEvent::Trade(trade) => Some(trade),
+                _ => None,
+            })
+            .collect();
+        let created_pools: Vec<_> = all_events
+            .iter()
+            .filter_map(|it| match it {
+                DexEvent::PoolCreated(record) => Some(record),
+                _ => None,
+            })
+            .collect();
+        // the stats are in SOL
+        let sol_trades: Vec<_> = trades
+            .iter()
+            .filter(|it| it.is_sol_quoted())
+            .copied()
+            .collect();
+        cache::record_trade_stats(&mut conn, &batch.id, &sol_trades).await?;
+        let followed_trades = cache::followed_wallet_trades(&mut conn, &batch.id, &trades).await?;
+        cache::record_last_prices(&mut conn, &sol_trades).await?;
+        cache::record_dex_volumes(&mut conn, &batch.id, &sol_trades).await?;
+        cache::record_leaderboard(&mut conn, &batch.id, &sol_trades).await?;
+        cache::record_ingestion_audit(&mut conn, &batch.id, &all_events).await?;
+        cache::record_created_pools(&mut conn, &created_pools).await?;
+        cache::record_mints(&mut conn, &batch.id, &created_pools, &sol_trades).await?;
+        if let Some(pool_reserves) = &options.pool_reserves {
+            cache::record_pool_reserves(&mut conn, &sol_trades, pool_reserves).await?;
+        }
+        let completes: Vec<_> = all_events
+            .iter()
+            .filter_map(|it| match it {
+                DexEvent::PumpfunComplete(record) => Some(record),
+                _ => None,
+            })
+            .collect();
+        cache::save_pumpfun_completes(&mut conn, &completes).await?;
+        if options.queue_funder_lookups {
+            let created: HashSet<_> = created_pools.iter().map(|it| it.addr).collect();
+            let wallets: Vec<_> = created_pools
+                .iter()
+                .filter(|it| !it.is_curve_migration())
+                .map(|it| it.creator)
+                .chain(
+                    trades
+                        .iter()
+                        .filter(|it| it.is_buy && created.contains(&it.pool))
+                        .map(|it| it.trader),
+                )
+                .collect();
+            cache::queue_funder_lookups(&mut conn, &wallets).await?;
+        }
+        if options.lp_lock {
+            let amm_pools: Vec<_> = created_pools
+                .iter()
+                .filter(|it| it.dex == Dex::RaydiumAmm)
+                .map(|it| (it.addr, it.blk_ts))
+                .collect();
+            cache::watch_lp_locks(&mut conn, &amm_pools).await?;
+        }
+        if let Some(clusters) = &options.wallet_clusters {
+            cache::record_early_buyers(
+                &mut conn,
+                &sol_trades,
+                clusters.early_buyers,
+                clusters.lookback_secs,
+            )
+            .await?;
+        }
+        if options.track_mint_activity {
+            let traded_mints: Vec<_> = trades.iter().map(|it| (it.mint, it.blk_ts)).collect();
+            cache::touch_traded_mints(&mut conn, &traded_mints).await?;
+        }
+        if options.track_pool_activity {
+            let traded_pools: Vec<_> = trades.iter().map(|it| (it.pool, it.blk_ts)).collect();
+            cache::touch_traded_pools(&mut conn, &traded_pools).await?;
+        }
+        all_events.extend(
+            followed_trades
+                .into_iter()
+                .map(DexEvent::FollowedWalletTrade),
+        );
+        cache::rpush_dex_evts(&mut conn, Some(&batch.id), &all_events).await?;
+        cache::settle_pending_migrations(&mut conn, &all_events).await?;
+        if let Some(drained) = drained_pools {
+            drained.save(&mut pools).await?;
+            pools.flush().await?;
+        }
+        drop(pools);
+        if let Some(ttl_secs) = options.event_dedup_secs {
+            cache::mark_dex_evts_seen(&mut conn, &seen_keys, ttl_secs).await?;
+        }
+        cache::ack_qn_requests(&mut conn).await?;
+        status.record(max_slot, max_blk_ts, events_len);
+        if events_len > 0 {
+            let ms = start.elapsed().as_millis();
+            info!(
+                "parsed events: {events_len}, parse take time: {ms} ms, slot range: [{min_slot} - {max_slot}] time diff: {time_diff} seconds, batches: {}",
+                batch_ids.join(",")
+            );
+        }
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+    }
+}
+
+/// A transaction of a batch with its position and the events of the candidate parser
+type BatchTx = (usize, EventSource, Tx, Option<Result<Vec<DexEvent>>>);
+
+/// What the transactions of a batch produced, in the order of the transactions
+#[derive(Debug, Default)]
+struct BatchParse {
+    events: Vec<DexEvent>,
+    /// the log of its transaction each event was parsed from, see `TxParseOutcome`
+    event_logs: Vec<usize>,
+    quarantined: Vec<QuarantinedLog>,
+    failures: Vec<ParseFailure>,
+    shadow_diffs: Vec<ShadowDiff>,
+}
+
+impl BatchParse {
+    fn extend(&mut self, other: BatchParse) {
+        self.events.extend(other.events);
+        self.event_logs.extend(other.event_logs);
+        self.quarantined.extend(other.quarantined);
+        self.failures.extend(other.failures);
+        self.shadow_diffs.extend(other.shadow_diffs);
+    }
+}
+
+/// Parse the transactions of a batch, on `parse_workers` concurrent tasks if set. The saved
+/// pools of the workers are merged back into `pools`, and what they parsed is merged in the
+/// order of the transactions, as if parsed serially.
+async fn parse_batch(
+    txs: Vec<(EventSource, Tx)>,
+    candidates: Vec<Result<Vec<DexEvent>>>,
+    pools: &mut DexPoolCache,
+    options: &ProcessorOptions,
+) -> Result<BatchParse> {
+    // the candidates are in step with the txs
+    let mut candidates = candidates.into_iter();
+    let txs: Vec<BatchTx> = txs
+        .into_iter()
+        .enumerate()
+        .map(|(pos, (source, tx))| (pos, source, tx, candidates.next()))
+        .collect();
+    let failed_swaps = options.failed_swaps;
+    let workers = options.parse_workers.unwrap_or(1);
+    let mut parsed = if workers <= 1 {
+        parse_batch_txs(txs, pools, failed_swaps).await?
+    } else {
+        let mut tasks = JoinSet::new();
+        for chunk in partition_txs(txs, workers) {
+            let mut worker_pools = pools.worker_copy();
+            tasks.spawn(async move {
+                let parsed = parse_batch_txs(chunk, &mut worker_pools, failed_swaps).await;
+                (worker_pools, parsed)
+            });
+        }
+        let mut parsed = vec![];
+        while let Some(joined) = tasks.join_next().await {
+            let (worker_pools, chunk_parsed) = joined?;
+            parsed.extend(chunk_parsed?);
+            pools.merge(worker_pools);
+        }
+        parsed
+    };
+    parsed.sort_by_key(|(pos, _)| *pos);
+
+    let mut batch = BatchParse::default();
+    for (_, tx_parsed) in parsed {
+        batch.extend(tx_parsed);
+    }
+    Ok(batch)
+}
+
+/// Split the transactions into at most `workers` chunks, each in the order of the batch. The
+/// transactions sharing a pool or a token mint other than the quote mints land in the same
+/// chunk, so the pools created and traded in the batch are parsed in order.
+fn partition_txs(txs: Vec<BatchTx>, workers: usize) -> Vec<Vec<BatchTx>> {
+    let quote_mints: HashSet<_> = common::quote_mints()
+        .iter()
+        .map(|it| it.to_string())
+        .collect();
+    // union find of the txs by the keys they share
+    let mut parents: Vec<usize> = (0..txs.len()).collect();
+    let mut first_tx_of: HashMap<String, usize> = HashMap::new();
+    for (idx, (_, _, tx, _)) in txs.iter().enumerate() {
+        let mints = tx
+            .ixs
+            .iter()
+            .flat_map(|it| &it.instruction.accounts)
+            .filter_map(|it| Some(it.pre_amt.token.as_ref()?.mint.clone()))
+            .filter(|it| !quote_mints.contains(it));
+        for key in tx.pool_addrs().map(|it| it.to_string()).chain(mints) {
+            let first = *first_tx_of.entry(key).or_insert(idx);
+            let (a, b) = (root(&mut parents, first), root(&mut parents, idx));
+            parents[b] = a;
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<BatchTx>> = HashMap::new();
+    for (idx, tx) in txs.into_iter().enumerate() {
+        groups.entry(root(&mut parents, idx)).or_default().push(tx);
+    }
+    // the largest groups first, each to the smallest chunk
+    let mut groups: Vec<_> = groups.into_values().collect();
+    groups.sort_by_key(|it| (std::cmp::Reverse(it.len()), it[0].0));
+    let mut chunks: Vec<Vec<BatchTx>> = (0..workers.min(groups.len())).map(|_| vec![]).collect();
+    for group in groups {
+        if let Some(chunk) = chunks.iter_mut().min_by_key(|it| it.len()) {
+            chunk.extend(group);
+        }
+    }
+    for chunk in &mut chunks {
+        chunk.sort_by_key(|(pos, ..)| *pos);
+    }
+    chunks
+}
+
+/// Root of the union find set of the tx, halving the path on the way
+fn root(parents: &mut [usize], mut idx: usize) -> usize {
+    while parents[idx] != idx {
+        parents[idx] = parents[parents[idx]];
+        idx = parents[idx];
+    }
+    idx
+}
+
+/// Parse the transactions in order, with what each of them produced by position. Only a
+/// failing cache fails them all.
+async fn parse_batch_txs(
+    txs: Vec<BatchTx>,
+    pools: &mut DexPoolCache,
+    failed_swaps: bool,
+) -> Result<Vec<(usize, BatchParse)>> {
+    let mut parsed = Vec::with_capacity(txs.len());
+    for (pos, source, tx, candidate) in txs {
+        let (txid, slot) = (tx.signature.clone(), tx.slot);
+        let mut tx_parsed = BatchParse::default();
+        // a failed tx logs no event, only the swaps it attempted are kept
+        if tx.err.is_some() {
+            if failed_swaps {
+                match cache::parse_failed_swaps(&tx) {
+                    Ok(attempts) => {
+                        // no logs, the attempts by position
+                        tx_parsed.event_logs = (0..attempts.len()).collect();
+                        tx_parsed.events.extend(attempts.into_iter().map(|it| {
+                            let mut evt = DexEvent::FailedSwapAttempt(it);
+                            evt.set_source(&source);
+                            evt
+                        }));
+                    }
+                    Err(err) => {
+                        warn!("{err}, failed tx: {txid}, skip it");
+                        parse_error::record_parse_error(&err);
+                    }
                 }
-                let invocation = invocation.unwrap();
-                let accounts = &invocation.instruction.accounts;
-                let ix_data = invocation.instruction.data.as_str();
+            }
+            parsed.push((pos, tx_parsed));
+            continue;
+        }
+        // a failing tx is skipped alone
+        let mut outcome = match parse_tx(&tx, pools).await {
+            Ok(outcome) => outcome,
+            Err(err) if err.action() == ParseErrorAction::Retry => return Err(err.into()),
+            Err(err) => {
+                warn!("{err}, tx: {txid}, skip it");
+                parse_error::record_failed_tx();
+                tx_parsed
+                    .failures
+                    .push(ParseFailure::new(tx, err.to_string()));
+                parsed.push((pos, tx_parsed));
+                continue;
+            }
+        };
+        if !outcome.quarantined.is_empty() {
+            let error = outcome
+                .quarantined
+                .iter()
+                .map(|it| format!("log {}: {}", it.log_idx, it.error))
+                .join("; ");
+            let mut failure = ParseFailure::new(tx, error);
+            failure.failed_logs = outcome.quarantined.iter().map(|it| it.log_idx).collect();
+            tx_parsed.failures.push(failure);
+        }
+        if let Some(candidate) = candidate {
+            let diffs = shadow_parser::diff_events(&txid, slot, &outcome.events, &candidate);
+            if !diffs.is_empty() {
+                warn!("shadow parser: {} events differ, tx: {txid}", diffs.len());
+            }
+            tx_parsed.shadow_diffs = diffs;
+        }
+        for evt in &mut outcome.events {
+            evt.set_source(&source);
+        }
+        tx_parsed.events = outcome.events;
+        tx_parsed.event_logs = outcome.event_logs;
+        tx_parsed.quarantined = outcome.quarantined;
+        parsed.push((pos, tx_parsed));
+    }
+    Ok(parsed)
+}
 
-                let tx_meta = TxBaseMetaInfo {
-                    blk_ts,
-                    slot,
+/// Dex events parsed from one transaction, along with the reason why each
+/// other log of the transaction didn't produce an event.
+#[derive(Debug, Default)]
+pub struct TxParseOutcome {
+    pub events: Vec<DexEvent>,
+    /// the log each event was parsed from, in step with `events`
+    pub event_logs: Vec<usize>,
+    pub skipped: Vec<(usize, String)>,
+    /// skipped logs which may be parsed wrongly, kept for inspection
+    pub quarantined: Vec<QuarantinedLog>,
+}
+
+impl TxParseOutcome {
+    fn skip(&mut self, log_idx: usize, reason: impl Into<String>) {
+        self.skipped.push((log_idx, reason.into()));
+    }
+
+    fn push_trade(&mut self, log_idx: usize, trade: Option<TradeRecord>) {
+        match trade {
+            Some(trade) => self.events.push(DexEvent::Trade(trade)),
+            None => self.skip(
+                log_idx,
+                "trade is not in a WSOL pair or has zero amount or abnormal price",
+            ),
+        }
+    }
+
+    fn push_liquidity(&mut self, log_idx: usize, record: Option<LiquidityChangeRecord>) {
+        match record {
+            Some(record) => self.events.push(DexEvent::LiquidityChange(record)),
+            None => self.skip(
+                log_idx,
+                "liquidity change is not in a WSOL pair or has zero amount",
+            ),
+        }
+    }
+
+    fn push_pool_created(&mut self, log_idx: usize, record: DexPoolCreatedRecord) {
+        if record.is_wsol_pool() {
+            self.events.push(DexEvent::PoolCreated(record));
+        } else {
+            self.skip(log_idx, format!("pool {} is not a WSOL pair", record.addr));
+        }
+    }
+}
+
+pub async fn parse_tx(tx: &Tx, pools: &mut DexPoolCache) -> Result<TxParseOutcome, DexParseError> {
+    let mut outcome = TxParseOutcome::default();
+    let slot = tx.slot;
+    let txid = &tx.signature;
+    let blk_ts = DateTime::from_timestamp(tx.blk_ts, 0).ok_or_else(|| {
+        DexParseError::BadTx(format!("block timestamp {} out of range", tx.blk_ts))
+    })?;
+    let ixs: Vec<_> = tx
+        .ixs
+        .iter()
+        .filter(|it| {
+            // exclude meteora dlmm initBinArray Instruction
+            !(it.program_id == METEORA_DLMM_PROGRAM_ID.to_string()
+                && it.instruction.data.starts_with("5N5iEh8c"))
+        })
+        .collect();
+    for (idx, log) in tx.logs.iter().enumerate() {
+        let invocation = ixs.get(idx);
+        if invocation.is_none() {
+            outcome.skip(idx, "no program invocation matched with the log");
+            continue;
+        }
+        let invocation = invocation.unwrap();
+        if tx
+            .requeued_logs
+            .as_ref()
+            .is_some_and(|it| !it.contains(&idx))
+        {
+            outcome.skip(idx, "parsed before the transaction was queued again");
+            continue;
+        }
+
+        let tx_meta = TxBaseMetaInfo {
+            blk_ts,
+            slot,
+            txid: txid.clone(),
+            idx: invocation.instruction.index,
+        };
+
+        let parsed = parse_log(log, invocation, tx_meta, idx, pools, &mut outcome).await;
+        outcome.event_logs.resize(outcome.events.len(), idx);
+        let Err(err) = parsed else {
+            continue;
+        };
+        match err.action() {
+            ParseErrorAction::Retry => return Err(err),
+            ParseErrorAction::Quarantine => {
+                warn!("{err}, tx: {txid}, log: {idx}");
+                outcome.quarantined.push(QuarantinedLog {
                     txid: txid.clone(),
-                    idx: invocation.instruction.index,
+                    slot,
+                    log_idx: idx,
+                    program_id: invocation.program_id.clone(),
+                    kind: err.kind().to_string(),
+                    error: err.to_string(),
+                    log: log.clone(),
+                });
+            }
+            ParseErrorAction::Drop => {}
+        }
+        if !pools.is_read_only() {
+            parse_error::record_parse_error(&err);
+        }
+        outcome.skip(idx, err.to_string());
+    }
+
+    Ok(outcome)
+}
+
+/// Parse the log of a dex program into events of the outcome
+async fn parse_log(
+    log: &str,
+    invocation: &ProgramInvocation,
+    tx_meta: TxBaseMetaInfo,
+    idx: usize,
+    pools: &mut DexPoolCache,
+    outcome: &mut TxParseOutcome,
+) -> Result<(), DexParseError> {
+    let accounts = &invocation.instruction.accounts;
+    let ix_data = invocation.instruction.data.as_str();
+    if invocation.program_id == RAYDIUM_AMM_PROGRAM_ID.to_string() {
+        match RayLogs::decode(&log.replace(Dex::RaydiumAmm.log_prefix(), "")) {
+            Ok(RayLogs::Init(evt)) => {
+                // example tx: 5SPKmhBHCBphyVietx4yu3FyJ7odwLDqv5UD2sGCJpGfQu8oiVtMxiKtCvecS91G3th4nbiZz1APa8TMLncbbD6Z
+                let pool_created_record =
+                    DexPoolCreatedRecord::from_raydium_init_log(tx_meta.clone(), evt, accounts)?;
+                let pool_record: DexPoolRecord = pool_created_record.as_pool_record();
+                pools.save(pool_record);
+
+                outcome.push_pool_created(idx, pool_created_record);
+            }
+            Ok(RayLogs::SwapBaseIn(evt)) => {
+                let trade = TradeRecord::from_raydium_amm_swap_base_in(
+                    tx_meta.clone(),
+                    evt,
+                    accounts,
+                    pools,
+                )
+                .await?;
+                outcome.push_trade(idx, trade);
+            }
+            Ok(RayLogs::SwapBaseOut(evt)) => {
+                let trade = TradeRecord::from_raydium_amm_swap_base_out(
+                    tx_meta.clone(),
+                    evt,
+                    accounts,
+                    pools,
+                )
+                .await?;
+                outcome.push_trade(idx, trade);
+            }
+            Ok(RayLogs::Deposit(evt)) => {
+                let log_accounts = LogAccounts::new(accounts, Dex::RaydiumAmm, "deposit");
+                let amm = log_accounts.pubkey(1)?;
+                let Some(pool) = liquidity_pool(amm, idx, pools, outcome).await? else {
+                    return Ok(());
+                };
+                let record = LiquidityChangeRecord::new(
+                    tx_meta.clone(),
+                    &pool,
+                    log_accounts.pubkey(12)?,
+                    true,
+                    (evt.deduct_coin, evt.deduct_pc),
+                )
+                .map(|it| {
+                    it.with_lp_amt(evt.mint_lp)
+                        .with_vaults(&pool, accounts, (6, 7))
+                });
+                outcome.push_liquidity(idx, record);
+            }
+            Ok(RayLogs::Withdraw(evt)) => {
+                let log_accounts = LogAccounts::new(accounts, Dex::RaydiumAmm, "withdraw");
+                let amm = log_accounts.pubkey(1)?;
+                let Some(pool) = liquidity_pool(amm, idx, pools, outcome).await? else {
+                    return Ok(());
                 };
+                // the owner is followed by the serum event queue, bids and asks
+                let provider = log_accounts.pubkey(accounts.len().saturating_sub(4))?;
+                let record = LiquidityChangeRecord::new(
+                    tx_meta.clone(),
+                    &pool,
+                    provider,
+                    false,
+                    (evt.out_coin, evt.out_pc),
+                )
+                .map(|it| {
+                    it.with_lp_amt(evt.withdraw_lp)
+                        .with_vaults(&pool, accounts, (6, 7))
+                });
+                outcome.push_liquidity(idx, record);
+            }
+            Err(err) => return Err(err),
+        }
+    } else if invocation.program_id == PUMPFUN_PROGRAM_ID.to_string() {
+        match PumpFunEvents::from_cpi_log(&log.replace(Dex::Pumpfun.log_prefix(), "")) {
+            Ok(PumpFunEvents::Create(evt)) => {
+                let pool_created_record =
+                    DexPoolCreatedRecord::from_pumpfun_create_log(tx_meta.clone(), evt);
 
-                if invocation.program_id == RAYDIUM_AMM_PROGRAM_ID.to_string() {
-                    match RayLogs::decode(&log.replace("Program log: ray_log: ", "")) {
-                        Ok(RayLogs::Init(evt)) => {
-                            // example tx: 5SPKmhBHCBphyVietx4yu3FyJ7odwLDqv5UD2sGCJpGfQu8oiVtMxiKtCvecS91G3th4nbiZz1APa8TMLncbbD6Z
-                            let pool_created_record = DexPoolCreatedRecord::from_raydium_init_log(
-                                tx_meta.clone(),
-                                evt,
-                                accounts,
-                            )?;
-                            let pool_record: DexPoolRecord = pool_created_record.as_pool_record();
-                            let mut redis_conn =
-                                redis_client.get_multiplexed_async_connection().await?;
-                            pool_record
-                                .save_ex(&mut redis_conn, DEX_POOL_EXP_SECS)
-                                .await?;
-                            drop(redis_conn);
-
-                            if pool_created_record.is_wsol_pool() {
-                                mints.insert(pool_created_record.mint_a);
-                                mints.insert(pool_created_record.mint_b);
-                                all_events.push(DexEvent::PoolCreated(pool_created_record));
-                            }
-                        }
-                        Ok(RayLogs::SwapBaseIn(evt)) => {
-                            let trade = TradeRecord::from_raydium_amm_swap_base_in(
-                                tx_meta.clone(),
-                                evt,
-                                accounts,
-                                redis_client.clone(),
-                            )
-                            .await?;
-                            if let Some(trade) = trade {
-                                mints.insert(trade.mint);
-                                all_events.push(DexEvent::Trade(trade));
-                            }
-                        }
-                        Ok(RayLogs::SwapBaseOut(evt)) => {
-                            let trade = TradeRecord::from_raydium_amm_swap_base_out(
-                                tx_meta.clone(),
-                                evt,
-                                accounts,
-                                redis_client.clone(),
-                            )
-                            .await?;
-                            if let Some(trade) = trade {
-                                mints.insert(trade.mint);
-                                all_events.push(DexEvent::Trade(trade));
-                            }
-                        }
-                        Err(err) => {
-                            warn!("!!!!!!!!!!!!! parse ray amm log error: {err}, tx: {txid}");
-                            continue;
-                        }
-                        _ => continue,
-                    }
-                } else if invocation.program_id == PUMPFUN_PROGRAM_ID.to_string() {
-                    match PumpFunEvents::from_cpi_log(&log.replace("pumpfun cpi log: ", "")) {
-                        Ok(PumpFunEvents::Create(evt)) => {
-                            let pool_created_record =
-                                DexPoolCreatedRecord::from_pumpfun_create_log(tx_meta.clone(), evt);
-
-                            let pool_record = pool_created_record.as_pool_record();
-                            let mut redis_conn =
-                                redis_client.get_multiplexed_async_connection().await?;
-                            pool_record
-                                .save_ex(&mut redis_conn, DEX_POOL_EXP_SECS)
-                                .await?;
-                            drop(redis_conn);
-
-                            if pool_created_record.is_wsol_pool() {
-                                mints.insert(pool_created_record.mint_a);
-                                mints.insert(pool_created_record.mint_b);
-                                all_events.push(DexEvent::PoolCreated(pool_created_record));
-                            }
-                        }
-                        Ok(PumpFunEvents::Trade(evt)) => {
-                            let trade = TradeRecord::from_pumpfun_trade(
-                                tx_meta.clone(),
-                                evt,
-                                accounts,
-                                redis_client.clone(),
-                            )
-                            .await?;
-                            if let Some(trade) = trade {
-                                mints.insert(trade.mint);
-                                all_events.push(DexEvent::Trade(trade));
-                            }
-                        }
-                        Ok(PumpFunEvents::Complete(evt)) => {
-                            let pool_record = DexPoolRecord::from_pumpfun_curve_and_mint(
-                                evt.bonding_curve,
-                                evt.mint,
-                                true,
-                            );
-                            let mut redis_conn =
-                                redis_client.get_multiplexed_async_connection().await?;
-                            pool_record
-                                .save_ex(&mut redis_conn, DEX_POOL_EXP_SECS)
-                                .await?;
-                            drop(redis_conn);
-
-                            let complete_evt = PumpfunCompleteRecord::new(tx_meta.clone(), &evt);
-                            mints.insert(complete_evt.mint);
-                            all_events.push(DexEvent::PumpfunComplete(complete_evt))
-                        }
-                        Err(_err) => {
-                            // warn!("!!!!!!!!!!!!! parse pumpfun log error: {err}, tx: {txid}");
-                            continue;
-                        }
-                        _ => continue,
-                    }
-                } else if invocation.program_id == PUMPAMM_PROGRAM_ID.to_string() {
-                    match PumpAmmEvents::from_cpi_log(&log.replace("pumpamm cpi log: ", "")) {
-                        Ok(PumpAmmEvents::CreatePool(evt)) => {
-                            let pool_created_record =
-                                DexPoolCreatedRecord::from_pumpamm_create_log(tx_meta.clone(), evt);
-
-                            let pool_record = pool_created_record.as_pool_record();
-                            let mut redis_conn =
-                                redis_client.get_multiplexed_async_connection().await?;
-                            pool_record
-                                .save_ex(&mut redis_conn, DEX_POOL_EXP_SECS)
-                                .await?;
-                            drop(redis_conn);
-
-                            if pool_created_record.is_wsol_pool() {
-                                mints.insert(pool_created_record.mint_a);
-                                mints.insert(pool_created_record.mint_b);
-                                all_events.push(DexEvent::PoolCreated(pool_created_record));
-                            }
-                        }
-                        Ok(PumpAmmEvents::Buy(evt)) => {
-                            let trade = TradeRecord::from_pumpamm_buy(
-                                tx_meta.clone(),
-                                evt,
-                                accounts,
-                                redis_client.clone(),
-                            )
-                            .await?;
-                            if let Some(trade) = trade {
-                                mints.insert(trade.mint);
-                                all_events.push(DexEvent::Trade(trade));
-                            }
-                        }
-                        Ok(PumpAmmEvents::Sell(evt)) => {
-                            let trade = TradeRecord::from_pumpamm_sell(
-                                tx_meta.clone(),
-                                evt,
-                                accounts,
-                                redis_client.clone(),
-                            )
-                            .await?;
-                            if let Some(trade) = trade {
-                                mints.insert(trade.mint);
-                                all_events.push(DexEvent::Trade(trade));
-                            }
-                        }
-                        Err(_err) => {
-                            // warn!("!!!!!!!!!!!!! parse pumpamm log error: {err}, tx: {txid}");
-                            continue;
-                        }
-                    }
-                } else if invocation.program_id == METEORA_DLMM_PROGRAM_ID.to_string() {
-                    match MeteoraDlmmEvents::from_cpi_log(
-                        &log.replace("meteora dlmm cpi log: ", ""),
-                    ) {
-                        Ok(MeteoraDlmmEvents::LbPairCreate(evt)) => {
-                            let pool_created_record =
-                                DexPoolCreatedRecord::from_meteora_dlmm_lp_create_log(
-                                    tx_meta.clone(),
-                                    evt,
-                                    accounts,
-                                )?;
-                            let pool_record: DexPoolRecord = pool_created_record.as_pool_record();
-                            let mut redis_conn =
-                                redis_client.get_multiplexed_async_connection().await?;
-                            pool_record
-                                .save_ex(&mut redis_conn, DEX_POOL_EXP_SECS)
-                                .await?;
-                            drop(redis_conn);
-
-                            if pool_created_record.is_wsol_pool() {
-                                mints.insert(pool_created_record.mint_a);
-                                mints.insert(pool_created_record.mint_b);
-                                all_events.push(DexEvent::PoolCreated(pool_created_record));
-                            }
-                        }
-                        Ok(MeteoraDlmmEvents::Swap(evt)) => {
-                            let trade = TradeRecord::from_meteora_dlmm_swap(
-                                tx_meta.clone(),
-                                evt,
-                                accounts,
-                                redis_client.clone(),
-                            )
-                            .await?;
-                            if let Some(trade) = trade {
-                                mints.insert(trade.mint);
-                                all_events.push(DexEvent::Trade(trade));
-                            }
-                        }
-                        Err(_err) => {
-                            // warn!("!!!!!!!!!!!!! parse meteora dlmm log error: {err}, tx: {txid}");
-                            continue;
-                        }
-                    }
-                } else if invocation.program_id == METEORA_DAMM_PROGRAM_ID.to_string() {
-                    match MeteoraDammEvents::from_log(
-                        &log.replace("meteora damm log Program data: ", ""),
-                    ) {
-                        Ok(MeteoraDammEvents::PoolCreated(evt)) => {
-                            let pool_created_record =
-                                DexPoolCreatedRecord::from_meteora_damm_pool_create_log(
-                                    tx_meta.clone(),
-                                    evt,
-                                    accounts,
-                                    ix_data,
-                                )?;
-                            let pool_record: DexPoolRecord = pool_created_record.as_pool_record();
-                            let mut redis_conn =
-                                redis_client.get_multiplexed_async_connection().await?;
-                            pool_record
-                                .save_ex(&mut redis_conn, DEX_POOL_EXP_SECS)
-                                .await?;
-                            drop(redis_conn);
-
-                            if pool_created_record.is_wsol_pool() {
-                                mints.insert(pool_created_record.mint_a);
-                                mints.insert(pool_created_record.mint_b);
-                                all_events.push(DexEvent::PoolCreated(pool_created_record));
-                            }
-                        }
-                        Ok(MeteoraDammEvents::Swap(evt)) => {
-                            let trade = TradeRecord::from_meteora_damm_swap(
-                                tx_meta.clone(),
-                                evt,
-                                accounts,
-                                redis_client.clone(),
-                            )
-                            .await
-                            .map_err(|err| {
-                                anyhow!("parse meteora amm swap in tx {txid} error: {err}")
-                            })?;
-                            if let Some(trade) = trade {
-                                mints.insert(trade.mint);
-                                all_events.push(DexEvent::Trade(trade));
-                            }
-                        }
-                        Err(_err) => {
-                            // warn!("!!!!!!!!!!!!! parse meteora damm log error: {err}, tx: {txid}");
-                            continue;
-                        }
+                let pool_record = pool_created_record.as_pool_record();
+                pools.save(pool_record);
+
+                outcome.push_pool_created(idx, pool_created_record);
+            }
+            Ok(PumpFunEvents::Trade(evt)) => {
+                let trade =
+                    TradeRecord::from_pumpfun_trade(tx_meta.clone(), evt, accounts, pools).await?;
+                outcome.push_trade(idx, trade);
+            }
+            Ok(PumpFunEvents::Complete(evt)) => {
+                let pool_record =
+                    DexPoolRecord::from_pumpfun_curve_and_mint(evt.bonding_curve, evt.mint, true);
+                pools.save(pool_record);
+
+                // pending for the pumpamm pool of the migration once queued, see
+                // `settle_pending_migrations`
+                let complete_evt = PumpfunCompleteRecord::new(tx_meta.clone(), &evt);
+                outcome.events.push(DexEvent::PumpfunComplete(complete_evt))
+            }
+            Ok(PumpFunEvents::SetParams(evt)) => {
+                let set_params_evt = PumpfunSetParamsRecord::new(tx_meta.clone(), &evt);
+                outcome
+                    .events
+                    .push(DexEvent::PumpfunSetParams(set_params_evt))
+            }
+            Err(err) => return Err(err),
+        }
+    } else if invocation.program_id == PUMPAMM_PROGRAM_ID.to_string() {
+        match PumpAmmEvents::from_cpi_log(&log.replace(Dex::PumpAmm.log_prefix(), "")) {
+            Ok(PumpAmmEvents::CreatePool(evt)) => {
+                let pool_created_record =
+                    DexPoolCreatedRecord::from_pumpamm_create_log(tx_meta.clone(), evt);
+
+                let pool_record = pool_created_record.as_pool_record();
+                if pool_record.is_wsol_pool() {
+                    let key = PumpfunCompleteRecord::new_key(pool_record.token_mint());
+                    if let Some(complete) =
+                        PumpfunCompleteRecord::from_redis(pools.conn(), &key).await?
+                    {
+                        // forgotten once queued, a batch claimed again finds it still
+                        let migration = MigrationCompletedRecord::new(
+                            tx_meta.clone(),
+                            &complete,
+                            &pool_created_record,
+                        );
+                        outcome.events.push(DexEvent::MigrationCompleted(migration));
                     }
                 }
+                pools.save(pool_record);
+
+                outcome.push_pool_created(idx, pool_created_record);
+            }
+            Ok(PumpAmmEvents::Buy(evt)) => {
+                let trade =
+                    TradeRecord::from_pumpamm_buy(tx_meta.clone(), evt, accounts, pools).await?;
+                outcome.push_trade(idx, trade);
+            }
+            Ok(PumpAmmEvents::Sell(evt)) => {
+                let trade =
+                    TradeRecord::from_pumpamm_sell(tx_meta.clone(), evt, accounts, pools).await?;
+                outcome.push_trade(idx, trade);
+            }
+            // the pool vaults follow the user token accounts of the deposit and the withdraw
+            Ok(PumpAmmEvents::Deposit(evt)) => {
+                let Some(pool) = liquidity_pool(evt.pool, idx, pools, outcome).await? else {
+                    return Ok(());
+                };
+                let record = LiquidityChangeRecord::new(
+                    tx_meta.clone(),
+                    &pool,
+                    evt.user,
+                    true,
+                    (evt.base_amount_in, evt.quote_amount_in),
+                )
+                .map(|it| {
+                    it.with_lp_amt(evt.lp_token_amount_out)
+                        .with_vaults(&pool, accounts, (9, 10))
+                });
+                outcome.push_liquidity(idx, record);
+            }
+            Ok(PumpAmmEvents::Withdraw(evt)) => {
+                let Some(pool) = liquidity_pool(evt.pool, idx, pools, outcome).await? else {
+                    return Ok(());
+                };
+                let record = LiquidityChangeRecord::new(
+                    tx_meta.clone(),
+                    &pool,
+                    evt.user,
+                    false,
+                    (evt.base_amount_out, evt.quote_amount_out),
+                )
+                .map(|it| {
+                    it.with_lp_amt(evt.lp_token_amount_in)
+                        .with_vaults(&pool, accounts, (9, 10))
+                });
+                outcome.push_liquidity(idx, record);
+            }
+            Err(err) => return Err(err),
+        }
+    } else if invocation.program_id == METEORA_DLMM_PROGRAM_ID.to_string() {
+        match MeteoraDlmmEvents::from_cpi_log(&log.replace(Dex::MeteoraDlmm.log_prefix(), "")) {
+            Ok(MeteoraDlmmEvents::LbPairCreate(evt)) => {
+                let pool_created_record = DexPoolCreatedRecord::from_meteora_dlmm_lp_create_log(
+                    tx_meta.clone(),
+                    evt,
+                    accounts,
+                )?;
+                let pool_record: DexPoolRecord = pool_created_record.as_pool_record();
+                pools.save(pool_record);
+
+                outcome.push_pool_created(idx, pool_created_record);
+            }
+            // swap2 has the accounts of swap in the same order
+            Ok(MeteoraDlmmEvents::Swap(evt) | MeteoraDlmmEvents::Swap2(evt)) => {
+                let trade =
+                    TradeRecord::from_meteora_dlmm_swap(tx_meta.clone(), evt, accounts, pools)
+                        .await?;
+                outcome.push_trade(idx, trade);
+            }
+            Ok(MeteoraDlmmEvents::AddLiquidity(evt) | MeteoraDlmmEvents::AddLiquidity2(evt)) => {
+                push_dlmm_liquidity(tx_meta.clone(), evt, true, accounts, idx, pools, outcome)
+                    .await?;
+            }
+            Ok(MeteoraDlmmEvents::RemoveLiquidity(evt)) => {
+                push_dlmm_liquidity(tx_meta.clone(), evt, false, accounts, idx, pools, outcome)
+                    .await?;
+            }
+            Err(err) => return Err(err),
+        }
+    } else if invocation.program_id == METEORA_DAMM_PROGRAM_ID.to_string() {
+        match MeteoraDammEvents::from_log(&log.replace(Dex::MeteoraDamm.log_prefix(), "")) {
+            Ok(MeteoraDammEvents::PoolCreated(evt)) => {
+                let pool_created_record = DexPoolCreatedRecord::from_meteora_damm_pool_create_log(
+                    tx_meta.clone(),
+                    evt,
+                    accounts,
+                    ix_data,
+                )?;
+                let pool_record: DexPoolRecord = pool_created_record.as_pool_record();
+                pools.save(pool_record);
+
+                outcome.push_pool_created(idx, pool_created_record);
+            }
+            Ok(MeteoraDammEvents::Swap(evt)) => {
+                let trade =
+                    TradeRecord::from_meteora_damm_swap(tx_meta.clone(), evt, accounts, pools)
+                        .await?;
+                outcome.push_trade(idx, trade);
+            }
+            Ok(MeteoraDammEvents::AddLiquidity(evt)) => {
+                let amts = (evt.token_a_amount, evt.token_b_amount, evt.lp_mint_amount);
+                push_damm_liquidity(tx_meta.clone(), amts, true, accounts, idx, pools, outcome)
+                    .await?;
+            }
+            Ok(MeteoraDammEvents::RemoveLiquidity(evt)) => {
+                let amts = (
+                    evt.token_a_out_amount,
+                    evt.token_b_out_amount,
+                    evt.lp_unmint_amount,
+                );
+                push_damm_liquidity(tx_meta.clone(), amts, false, accounts, idx, pools, outcome)
+                    .await?;
+            }
+            Err(err) => return Err(err),
+        }
+    } else if invocation.program_id == RAYDIUM_CLMM_PROGRAM_ID.to_string() {
+        match RaydiumClmmEvents::from_log(&log.replace(Dex::RaydiumClmm.log_prefix(), "")) {
+            Ok(RaydiumClmmEvents::PoolCreated(evt)) => {
+                let pool_created_record = DexPoolCreatedRecord::from_raydium_clmm_pool_created_log(
+                    tx_meta.clone(),
+                    evt,
+                    accounts,
+                )?;
+                let pool_record: DexPoolRecord = pool_created_record.as_pool_record();
+                pools.save(pool_record);
+
+                outcome.push_pool_created(idx, pool_created_record);
+            }
+            Ok(RaydiumClmmEvents::Swap(evt)) => {
+                // the router swaps have the pools in the remaining accounts
+                if accounts
+                    .get(2)
+                    .is_none_or(|it| it.pubkey != evt.pool_state.to_string())
+                {
+                    outcome.skip(idx, "raydium clmm router swaps are not decoded");
+                    return Ok(());
+                }
+                let trade =
+                    TradeRecord::from_raydium_clmm_swap(tx_meta.clone(), evt, accounts, pools)
+                        .await?;
+                outcome.push_trade(idx, trade);
             }
+            Err(err) => return Err(err),
         }
+    } else if invocation.program_id == ORCA_WHIRLPOOL_PROGRAM_ID.to_string() {
+        match WhirlpoolEvents::from_log(&log.replace(Dex::OrcaWhirlpool.log_prefix(), "")) {
+            Ok(WhirlpoolEvents::PoolInitialized(evt)) => {
+                let pool_created_record = DexPoolCreatedRecord::from_orca_pool_initialized_log(
+                    tx_meta.clone(),
+                    evt,
+                    accounts,
+                    ix_data,
+                )?;
+                let pool_record: DexPoolRecord = pool_created_record.as_pool_record();
+                pools.save(pool_record);
 
-        let events_len = all_events.len();
-        if events_len > 0 {
-            let mut conn = redis_client.get_multiplexed_async_connection().await?;
-            cache::rpush_dex_evts(&mut conn, &all_events).await?;
-            cache::ltrim_qn_requests(&mut conn, webhook_req_len).await?;
-            drop(conn);
-            let ms = start.elapsed().as_millis();
-            info!(
-                "parsed events: {events_len}, parse take time: {ms} ms, slot range: [{min_slot} - {max_slot}] time diff: {time_diff} seconds"
-            );
+                outcome.push_pool_created(idx, pool_created_record);
+            }
+            Ok(WhirlpoolEvents::Traded(evt)) => {
+                let ix_bytes = bs58::decode(ix_data)
+                    .into_vec()
+                    .map_err(|err| DexParseError::corrupt(Dex::OrcaWhirlpool, err))?;
+                let Some(swap_accounts) = swap_account_idx(&ix_bytes) else {
+                    outcome.skip(idx, "orca whirlpool two hop swaps are not decoded");
+                    return Ok(());
+                };
+                let trade = TradeRecord::from_orca_whirlpool_traded(
+                    tx_meta.clone(),
+                    evt,
+                    swap_accounts,
+                    accounts,
+                    pools,
+                )
+                .await?;
+                outcome.push_trade(idx, trade);
+            }
+            Err(err) => return Err(err),
         }
+    } else if invocation.program_id == METEORA_DBC_PROGRAM_ID.to_string() {
+        match MeteoraDbcEvents::from_cpi_log(&log.replace(Dex::MeteoraDbc.log_prefix(), "")) {
+            Ok(MeteoraDbcEvents::InitializePool(evt)) => {
+                let pool_created_record =
+                    DexPoolCreatedRecord::from_meteora_dbc_initialize_pool_log(
+                        tx_meta.clone(),
+                        evt,
+                        accounts,
+                    )?;
+                let pool_record: DexPoolRecord = pool_created_record.as_pool_record();
+                pools.save(pool_record);
 
-        tokio::time::sleep(Duration::from_millis(300)).await;
+                outcome.push_pool_created(idx, pool_created_record);
+            }
+            Ok(MeteoraDbcEvents::Swap(evt)) => {
+                let trade =
+                    TradeRecord::from_meteora_dbc_swap(tx_meta.clone(), evt, accounts, pools)
+                        .await?;
+                outcome.push_trade(idx, trade);
+            }
+            // emitted by the swap which completed the curve
+            Ok(MeteoraDbcEvents::CurveComplete(evt)) => {
+                let mut pool_record =
+                    DexPoolRecord::from_meteora_dbc_swap_accounts(evt.pool, accounts, pools)
+                        .await?;
+                pool_record.is_complete = true;
+                let log_accounts = LogAccounts::new(accounts, Dex::MeteoraDbc, "curve_complete");
+                let complete_evt = MeteoraDbcCompleteRecord::new(
+                    tx_meta.clone(),
+                    &evt,
+                    log_accounts.pubkey(9)?,
+                    pool_record.mint_a,
+                );
+                pools.save(pool_record);
+                outcome
+                    .events
+                    .push(DexEvent::MeteoraDbcComplete(complete_evt));
+            }
+            Err(err) => return Err(err),
+        }
+    } else if invocation.program_id == RAYDIUM_CPMM_PROGRAM_ID.to_string() {
+        match RaydiumCpmmInstructions::from_ix_data(&log.replace(Dex::RaydiumCpmm.log_prefix(), ""))
+        {
+            Ok(RaydiumCpmmInstructions::Initialize(ix)) => {
+                let pool_created_record = DexPoolCreatedRecord::from_raydium_cpmm_initialize(
+                    tx_meta.clone(),
+                    ix,
+                    accounts,
+                )?;
+                let pool_record: DexPoolRecord = pool_created_record.as_pool_record();
+                pools.save(pool_record);
+
+                outcome.push_pool_created(idx, pool_created_record);
+            }
+            Ok(RaydiumCpmmInstructions::SwapBaseInput(ix)) => {
+                let trade = TradeRecord::from_raydium_cpmm_swap(
+                    tx_meta.clone(),
+                    ix.into(),
+                    accounts,
+                    &invocation.instruction.transfers,
+                    pools,
+                )
+                .await?;
+                outcome.push_trade(idx, trade);
+            }
+            Ok(RaydiumCpmmInstructions::SwapBaseOutput(ix)) => {
+                let trade = TradeRecord::from_raydium_cpmm_swap(
+                    tx_meta.clone(),
+                    ix.into(),
+                    accounts,
+                    &invocation.instruction.transfers,
+                    pools,
+                )
+                .await?;
+                outcome.push_trade(idx, trade);
+            }
+            Ok(RaydiumCpmmInstructions::Other(discriminator)) => {
+                outcome.skip(
+                    idx,
+                    format!("ignored raydium cpmm instruction: {discriminator:?}"),
+                );
+            }
+            Err(err) => return Err(err),
+        }
+    } else if invocation.program_id == MOONSHOT_PROGRAM_ID.to_string() {
+        match MoonshotInstructions::from_ix_data(&log.replace(Dex::Moonshot.log_prefix(), "")) {
+            Ok(MoonshotInstructions::TokenMint(ix)) => {
+                let pool_created_record =
+                    DexPoolCreatedRecord::from_moonshot_token_mint(tx_meta.clone(), &ix, accounts)?;
+                let pool_record: DexPoolRecord = pool_created_record.as_pool_record();
+                pools.save(pool_record);
+
+                outcome.push_pool_created(idx, pool_created_record);
+            }
+            Ok(MoonshotInstructions::Buy(ix)) => {
+                let trade =
+                    TradeRecord::from_moonshot_trade(tx_meta.clone(), true, ix, accounts, pools)
+                        .await?;
+                outcome.push_trade(idx, trade);
+            }
+            Ok(MoonshotInstructions::Sell(ix)) => {
+                let trade =
+                    TradeRecord::from_moonshot_trade(tx_meta.clone(), false, ix, accounts, pools)
+                        .await?;
+                outcome.push_trade(idx, trade);
+            }
+            Ok(MoonshotInstructions::Other(discriminator)) => {
+                outcome.skip(
+                    idx,
+                    format!("ignored moonshot instruction: {discriminator:?}"),
+                );
+            }
+            Err(err) => return Err(err),
+        }
+    } else {
+        outcome.skip(
+            idx,
+            format!("program {} is not supported", invocation.program_id),
+        );
+    }
+
+    Ok(())
+}
+
+/// The cached pool of a liquidity change, the log is skipped if the pool was never seen
+async fn liquidity_pool(
+    addr: Pubkey,
+    idx: usize,
+    pools: &mut DexPoolCache,
+    outcome: &mut TxParseOutcome,
+) -> Result<Option<DexPoolRecord>, DexParseError> {
+    let pool = pools.get(&addr).await?;
+    if pool.is_none() {
+        outcome.skip(idx, format!("liquidity change of unknown pool {addr}"));
+    }
+    Ok(pool)
+}
+
+/// The add and remove liquidity instructions have the reserves of the lb pair after the
+/// position, the lb pair, the bitmap extension and the user token accounts
+async fn push_dlmm_liquidity(
+    tx_meta: TxBaseMetaInfo,
+    evt: MeteoraDlmmLiquidityEvent,
+    is_add: bool,
+    accounts: &[IxAccount],
+    idx: usize,
+    pools: &mut DexPoolCache,
+    outcome: &mut TxParseOutcome,
+) -> Result<(), DexParseError> {
+    let Some(pool) = liquidity_pool(evt.lb_pair, idx, pools, outcome).await? else {
+        return Ok(());
+    };
+    let amts = (evt.amounts[0], evt.amounts[1]);
+    let record = LiquidityChangeRecord::new(tx_meta, &pool, evt.from, is_add, amts)
+        .map(|it| it.with_vaults(&pool, accounts, (5, 6)));
+    outcome.push_liquidity(idx, record);
+    Ok(())
+}
+
+/// The pool is the first account of the liquidity instructions and the user the third last,
+/// the token vaults are shared by the pools of the meteora vaults so the reserves are unknown.
+/// The amounts are of the token a, the token b and the lp token.
+async fn push_damm_liquidity(
+    tx_meta: TxBaseMetaInfo,
+    (amt_a, amt_b, lp_amt): (u64, u64, u64),
+    is_add: bool,
+    accounts: &[IxAccount],
+    idx: usize,
+    pools: &mut DexPoolCache,
+    outcome: &mut TxParseOutcome,
+) -> Result<(), DexParseError> {
+    let log_accounts = LogAccounts::new(accounts, Dex::MeteoraDamm, "liquidity");
+    let Some(pool) = liquidity_pool(log_accounts.pubkey(0)?, idx, pools, outcome).await? else {
+        return Ok(());
+    };
+    let provider = log_accounts.pubkey(accounts.len().saturating_sub(3))?;
+    let record = LiquidityChangeRecord::new(tx_meta, &pool, provider, is_add, (amt_a, amt_b))
+        .map(|it| it.with_lp_amt(lp_amt));
+    outcome.push_liquidity(idx, record);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::WSOL_MINT;
+
+    #[test]
+    fn test_from_ingested() {
+        let metadata = r#""metadata":{"batch_end_range":2,"batch_start_range":1,"dataset":"block",
+            "end_range":-1,"keep_distance_from_tip":0,"network":"solana-mainnet","start_range":0,
+            "stream_id":"s","stream_name":"n","stream_region":"r"}"#;
+        let body = format!(r#"{{"batch_id":"sent","txs":[],{metadata}}}"#);
+        let req = QnSolDexDatahubWebhookReq::from_ingested(&body, "b1").unwrap();
+        assert_eq!(req.batch_id.as_deref(), Some("b1"));
+        assert_eq!(req.metadata.stream_id, "s");
+        let queued: serde_json::Value = serde_json::to_value(&req).unwrap();
+        assert_eq!(queued["batch_id"], "b1");
+
+        let body = format!(r#"{{"batch_id":"a","batch_id":"b","txs":[],{metadata}}}"#);
+        let err = QnSolDexDatahubWebhookReq::from_ingested(&body, "b1").unwrap_err();
+        assert!(
+            err.to_string().contains("duplicate field `batch_id`"),
+            "{err}"
+        );
+        assert!(QnSolDexDatahubWebhookReq::from_ingested("[]", "b1").is_err());
+    }
+
+    #[test]
+    fn test_partition_txs() {
+        let (mint_a, mint_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let tx = |pos: usize, mints: &[Pubkey]| -> BatchTx {
+            let accounts: Vec<_> = mints
+                .iter()
+                .map(|it| {
+                    let token =
+                        serde_json::json!({"mint": it.to_string(), "decimals": 6, "amt": "1"});
+                    serde_json::json!({"pubkey": Pubkey::new_unique().to_string(),
+                        "preAmt": {"sol": 0, "token": token}, "postAmt": {"sol": 0}})
+                })
+                .collect();
+            let tx = serde_json::json!({
+                "blkTs": 100,
+                "slot": 10,
+                "signature": format!("tx{pos}"),
+                "logs": [],
+                "ixs": [{
+                    "programId": "11111111111111111111111111111111",
+                    "instruction": {"accounts": accounts, "data": "", "index": 0},
+                }],
+            });
+            let source = EventSource {
+                stream_id: "s1".to_string(),
+                batch_id: "b1".to_string(),
+                batch_start_range: 10,
+                batch_end_range: 10,
+            };
+            (pos, source, serde_json::from_value(tx).unwrap(), None)
+        };
+        let txs = vec![
+            tx(0, &[mint_a, WSOL_MINT]),
+            tx(1, &[mint_b, WSOL_MINT]),
+            tx(2, &[mint_a]),
+            tx(3, &[WSOL_MINT]),
+            tx(4, &[mint_a, mint_b]),
+        ];
+        // joined by the second mint of the last tx
+        let chunks = partition_txs(txs, 4);
+        let positions: Vec<Vec<_>> = chunks
+            .iter()
+            .map(|it| it.iter().map(|(pos, ..)| *pos).collect())
+            .collect();
+        assert_eq!(positions, vec![vec![0, 1, 2, 4], vec![3]]);
     }
 }
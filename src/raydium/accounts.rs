@@ -2,9 +2,10 @@ use std::str::FromStr;
 
 use anyhow::Result;
 use bytemuck::{Pod, Zeroable};
-use solana_rpc_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::pubkey::Pubkey;
 
+use crate::rpc_provider::RpcProvider;
+
 #[derive(Debug, Copy, Clone, Default, Pod, Zeroable)]
 #[repr(C, packed)]
 pub struct Fees {
@@ -139,15 +140,32 @@ pub struct AmmInfo {
 }
 
 impl AmmInfo {
-    pub async fn from_rpc(rpc_client: &RpcClient, amm_addr: &str) -> Result<Self> {
+    pub async fn from_rpc(rpc_client: &dyn RpcProvider, amm_addr: &str) -> Result<Self> {
         let pubkey = Pubkey::from_str(amm_addr)?;
         let account = rpc_client.get_account(&pubkey).await?;
 
-        let result: &AmmInfo = bytemuck::checked::try_from_bytes::<AmmInfo>(&account.data)
+        Self::from_account_data(&account.data)
+    }
+
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        let result: &AmmInfo = bytemuck::checked::try_from_bytes::<AmmInfo>(data)
             .map_err(|err| anyhow::anyhow!("deserialize amm info error: {err}"))?;
 
         Ok(*result)
     }
+
+    /// Swap fee in bps
+    pub fn swap_fee_bps(&self) -> f64 {
+        let Fees {
+            swap_fee_numerator,
+            swap_fee_denominator,
+            ..
+        } = self.fees;
+        if swap_fee_denominator == 0 {
+            return 0.0;
+        }
+        swap_fee_numerator as f64 * 10_000.0 / swap_fee_denominator as f64
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,73 @@
+use anyhow::Result;
+use borsh::BorshDeserialize;
+use solana_sdk::pubkey::Pubkey;
+
+/// The leading fields of the clmm pool account, the rewards and fee counters after them are
+/// not read. The account is `repr(C, packed)`, so the fields are laid out as borsh reads them.
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct PoolState {
+    pub discriminator: u64,
+    /// Bump to identify PDA
+    pub bump: [u8; 1],
+    /// Which config the pool belongs
+    pub amm_config: Pubkey,
+    /// Pool creator
+    pub owner: Pubkey,
+    /// Token pair of the pool, where token_mint_0 address < token_mint_1 address
+    pub token_mint_0: Pubkey,
+    pub token_mint_1: Pubkey,
+    /// Token pair vault
+    pub token_vault_0: Pubkey,
+    pub token_vault_1: Pubkey,
+    /// observation account key
+    pub observation_key: Pubkey,
+    /// mint0 and mint1 decimals
+    pub mint_decimals_0: u8,
+    pub mint_decimals_1: u8,
+    /// The minimum number of ticks between initialized ticks
+    pub tick_spacing: u16,
+    /// The currently in range liquidity available to the pool.
+    pub liquidity: u128,
+    /// The current price of the pool as a sqrt(token_1/token_0) Q64.64 value
+    pub sqrt_price_x64: u128,
+    /// The current tick of the pool, i.e. according to the last tick transition that was run.
+    pub tick_current: i32,
+    pub padding3: u16,
+    pub padding4: u16,
+    /// The fee growth as a Q64.64 number, i.e. fees of token_0 and token_1 collected per
+    /// unit of liquidity for the entire life of the pool.
+    pub fee_growth_global_0_x64: u128,
+    pub fee_growth_global_1_x64: u128,
+    /// The amounts of token_0 and token_1 that are owed to the protocol.
+    pub protocol_fees_token_0: u64,
+    pub protocol_fees_token_1: u64,
+    /// The amounts in and out of swap token_0 and token_1
+    pub swap_in_amount_token_0: u128,
+    pub swap_out_amount_token_1: u128,
+    pub swap_in_amount_token_1: u128,
+    pub swap_out_amount_token_0: u128,
+    /// Bitwise representation of the state of the pool
+    /// bit0, 1: disable open position and increase liquidity, 0: normal
+    /// bit1, 1: disable decrease liquidity, 0: normal
+    /// bit2, 1: disable collect fee, 0: normal
+    /// bit3, 1: disable collect reward, 0: normal
+    /// bit4, 1: disable swap, 0: normal
+    pub status: u8,
+}
+
+impl PoolState {
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        let result = PoolState::deserialize(&mut &data[..])
+            .map_err(|err| anyhow::anyhow!("deserialize raydium clmm pool state error: {err}"))?;
+
+        Ok(result)
+    }
+
+    /// Price of one token_0 in token_1, in ui amounts
+    pub fn price(&self) -> f64 {
+        let sqrt_price = self.sqrt_price_x64 as f64 / 2f64.powi(64);
+        sqrt_price
+            * sqrt_price
+            * 10f64.powi(self.mint_decimals_0 as i32 - self.mint_decimals_1 as i32)
+    }
+}
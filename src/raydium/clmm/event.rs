@@ -0,0 +1,148 @@
+use anyhow::Result;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use borsh::BorshDeserialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{common::Dex, parse_error::DexParseError};
+
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct RaydiumClmmSwapEvent {
+    /// The pool for which token_0 and token_1 were swapped
+    pub pool_state: Pubkey,
+    /// The address that initiated the swap call, and that received the callback
+    pub sender: Pubkey,
+    /// The payer token account in zero for one swaps, or the recipient token account
+    /// in one for zero swaps
+    pub token_account_0: Pubkey,
+    /// The payer token account in one for zero swaps, or the recipient token account
+    /// in zero for one swaps
+    pub token_account_1: Pubkey,
+    /// The real delta amount of the token_0 of the pool
+    pub amount_0: u64,
+    /// The transfer fee charged by the withheld_amount of the token_0
+    pub transfer_fee_0: u64,
+    /// The real delta of the token_1 of the pool
+    pub amount_1: u64,
+    /// The transfer fee charged by the withheld_amount of the token_1
+    pub transfer_fee_1: u64,
+    /// if true, amount_0 is negative and amount_1 is positive
+    pub zero_for_one: bool,
+    /// The sqrt(price) of the pool after the swap, as a Q64.64
+    pub sqrt_price_x64: u128,
+    /// The liquidity of the pool after the swap
+    pub liquidity: u128,
+    /// The log base 1.0001 of price of the pool after the swap
+    pub tick: i32,
+}
+
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct RaydiumClmmPoolCreatedEvent {
+    /// The first token of the pool by address sort order
+    pub token_mint_0: Pubkey,
+    /// The second token of the pool by address sort order
+    pub token_mint_1: Pubkey,
+    /// The minimum number of ticks between initialized ticks
+    pub tick_spacing: u16,
+    /// The address of the created pool
+    pub pool_state: Pubkey,
+    /// The initial sqrt price of the pool, as a Q64.64
+    pub sqrt_price_x64: u128,
+    /// The initial tick of the pool, i.e. log base 1.0001 of the starting price of the pool
+    pub tick: i32,
+    /// Vault of token_0
+    pub token_vault_0: Pubkey,
+    /// Vault of token_1
+    pub token_vault_1: Pubkey,
+}
+
+#[derive(Debug)]
+pub enum RaydiumClmmEvents {
+    Swap(RaydiumClmmSwapEvent),
+    PoolCreated(RaydiumClmmPoolCreatedEvent),
+}
+
+impl RaydiumClmmEvents {
+    pub fn from_log(log: &str) -> Result<Self, DexParseError> {
+        let bytes = BASE64_STANDARD
+            .decode(log)
+            .map_err(|err| DexParseError::corrupt(Dex::RaydiumClmm, err))?;
+        let (discriminator, fields) = bytes.split_at_checked(8).ok_or_else(|| {
+            DexParseError::corrupt(Dex::RaydiumClmm, "raydium clmm log is too short")
+        })?;
+
+        let result = match discriminator {
+            [64, 198, 205, 232, 38, 8, 113, 226] => {
+                let evt: RaydiumClmmSwapEvent = borsh::from_slice(fields).map_err(corrupt)?;
+                Self::Swap(evt)
+            }
+            [25, 94, 75, 47, 112, 99, 53, 63] => {
+                let evt: RaydiumClmmPoolCreatedEvent =
+                    borsh::from_slice(fields).map_err(corrupt)?;
+                Self::PoolCreated(evt)
+            }
+            _ => return Err(DexParseError::unknown(Dex::RaydiumClmm, discriminator)),
+        };
+
+        Ok(result)
+    }
+}
+
+fn corrupt(err: std::io::Error) -> DexParseError {
+    DexParseError::corrupt(Dex::RaydiumClmm, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{Encoding, decodes_arbitrary_bytes};
+
+    const DISCRIMINATORS: [[u8; 8]; 2] = [
+        [64, 198, 205, 232, 38, 8, 113, 226],
+        [25, 94, 75, 47, 112, 99, 53, 63],
+    ];
+
+    #[test]
+    fn test_decode_swap_evt() {
+        let pool_state = Pubkey::new_unique();
+        let mut data = DISCRIMINATORS[0].to_vec();
+        data.extend_from_slice(pool_state.as_ref());
+        for _ in 0..3 {
+            data.extend_from_slice(Pubkey::new_unique().as_ref());
+        }
+        for amt in [1_000_000_000u64, 0, 250_000_000, 0] {
+            data.extend_from_slice(&amt.to_le_bytes());
+        }
+        data.push(1);
+        data.extend_from_slice(&[0u8; 16 + 16]);
+        data.extend_from_slice(&(-20i32).to_le_bytes());
+
+        let Ok(RaydiumClmmEvents::Swap(evt)) =
+            RaydiumClmmEvents::from_log(&BASE64_STANDARD.encode(&data))
+        else {
+            panic!("should be a swap event");
+        };
+        assert_eq!(evt.pool_state, pool_state);
+        assert_eq!(evt.amount_0, 1_000_000_000);
+        assert_eq!(evt.amount_1, 250_000_000);
+        assert!(evt.zero_for_one);
+        assert_eq!(evt.tick, -20);
+
+        // trailing bytes are corrupt
+        data.push(0);
+        assert!(RaydiumClmmEvents::from_log(&BASE64_STANDARD.encode(&data)).is_err());
+    }
+
+    quickcheck::quickcheck! {
+        fn test_decode_arbitrary_bytes(kind: u8, fields: Vec<u8>, log: String) -> bool {
+            decodes_arbitrary_bytes(
+                Encoding::Base64,
+                &[],
+                &DISCRIMINATORS,
+                kind,
+                &fields,
+                &log,
+                RaydiumClmmEvents::from_log,
+            )
+        }
+    }
+}
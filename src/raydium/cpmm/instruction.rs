@@ -0,0 +1,144 @@
+use borsh::BorshDeserialize;
+
+use crate::{common::Dex, parse_error::DexParseError};
+
+// initialize            # data prefix: afaf6d1f0d989bed [175,175,109,31,13,152,155,237]
+// swapBaseInput         # data prefix: 8fbe5adac41e33de [143,190,90,218,196,30,51,222]
+// swapBaseOutput        # data prefix: 37d96256a34ab4ad [55,217,98,86,163,74,180,173]
+
+pub const INITIALIZE_IX_ID: [u8; 8] = [175, 175, 109, 31, 13, 152, 155, 237];
+pub const SWAP_BASE_INPUT_IX_ID: [u8; 8] = [143, 190, 90, 218, 196, 30, 51, 222];
+pub const SWAP_BASE_OUTPUT_IX_ID: [u8; 8] = [55, 217, 98, 86, 163, 74, 180, 173];
+
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct RaydiumCpmmInitialize {
+    pub init_amount_0: u64,
+    pub init_amount_1: u64,
+    pub open_time: u64,
+}
+
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct RaydiumCpmmSwapBaseInput {
+    pub amount_in: u64,
+    pub minimum_amount_out: u64,
+}
+
+#[derive(Debug, Clone, Copy, BorshDeserialize)]
+pub struct RaydiumCpmmSwapBaseOutput {
+    pub max_amount_in: u64,
+    pub amount_out: u64,
+}
+
+/// The amount fixed by a swap instruction, the other side of the swap comes from the vault
+/// balances
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RaydiumCpmmSwapAmt {
+    In(u64),
+    Out(u64),
+}
+
+impl From<RaydiumCpmmSwapBaseInput> for RaydiumCpmmSwapAmt {
+    fn from(ix: RaydiumCpmmSwapBaseInput) -> Self {
+        Self::In(ix.amount_in)
+    }
+}
+
+impl From<RaydiumCpmmSwapBaseOutput> for RaydiumCpmmSwapAmt {
+    fn from(ix: RaydiumCpmmSwapBaseOutput) -> Self {
+        Self::Out(ix.amount_out)
+    }
+}
+
+/// The cp swap program emits no event for the pools it creates, so its instructions are
+/// decoded, with the swapped amounts taken from the vault balances
+#[derive(Debug)]
+pub enum RaydiumCpmmInstructions {
+    Initialize(RaydiumCpmmInitialize),
+    SwapBaseInput(RaydiumCpmmSwapBaseInput),
+    SwapBaseOutput(RaydiumCpmmSwapBaseOutput),
+    /// deposits, withdraws and the admin instructions, by discriminator
+    Other([u8; 8]),
+}
+
+impl RaydiumCpmmInstructions {
+    /// Decode the bs58 instruction data
+    pub fn from_ix_data(ix_data: &str) -> Result<Self, DexParseError> {
+        let bytes = bs58::decode(ix_data)
+            .into_vec()
+            .map_err(|err| DexParseError::corrupt(Dex::RaydiumCpmm, err))?;
+        let (discriminator, args) = bytes.split_at_checked(8).ok_or_else(|| {
+            DexParseError::corrupt(Dex::RaydiumCpmm, "raydium cpmm instruction is too short")
+        })?;
+
+        let result = match discriminator {
+            d if d == INITIALIZE_IX_ID => {
+                Self::Initialize(borsh::from_slice(args).map_err(corrupt)?)
+            }
+            d if d == SWAP_BASE_INPUT_IX_ID => {
+                Self::SwapBaseInput(borsh::from_slice(args).map_err(corrupt)?)
+            }
+            d if d == SWAP_BASE_OUTPUT_IX_ID => {
+                Self::SwapBaseOutput(borsh::from_slice(args).map_err(corrupt)?)
+            }
+            _ => Self::Other(discriminator.try_into().unwrap_or_default()),
+        };
+
+        Ok(result)
+    }
+}
+
+fn corrupt(err: std::io::Error) -> DexParseError {
+    DexParseError::corrupt(Dex::RaydiumCpmm, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{Encoding, decodes_arbitrary_bytes};
+
+    const IX_IDS: [[u8; 8]; 3] = [
+        INITIALIZE_IX_ID,
+        SWAP_BASE_INPUT_IX_ID,
+        SWAP_BASE_OUTPUT_IX_ID,
+    ];
+
+    #[test]
+    fn test_decode_swap_ix() {
+        let mut data = SWAP_BASE_INPUT_IX_ID.to_vec();
+        data.extend_from_slice(&1_000_000_000u64.to_le_bytes());
+        data.extend_from_slice(&42u64.to_le_bytes());
+        let Ok(RaydiumCpmmInstructions::SwapBaseInput(ix)) =
+            RaydiumCpmmInstructions::from_ix_data(&bs58::encode(&data).into_string())
+        else {
+            panic!("should be a swap base input instruction");
+        };
+        assert_eq!(ix.amount_in, 1_000_000_000);
+        assert_eq!(ix.minimum_amount_out, 42);
+
+        // trailing bytes are corrupt
+        data.push(0);
+        assert!(RaydiumCpmmInstructions::from_ix_data(&bs58::encode(&data).into_string()).is_err());
+
+        let deposit = [242, 35, 198, 137, 82, 225, 242, 182, 1, 2];
+        assert!(matches!(
+            RaydiumCpmmInstructions::from_ix_data(&bs58::encode(deposit).into_string()),
+            Ok(RaydiumCpmmInstructions::Other([
+                242, 35, 198, 137, 82, 225, 242, 182
+            ]))
+        ));
+    }
+
+    quickcheck::quickcheck! {
+        fn test_decode_arbitrary_bytes(kind: u8, args: Vec<u8>, ix_data: String) -> bool {
+            decodes_arbitrary_bytes(
+                Encoding::Base58,
+                &[],
+                &IX_IDS,
+                kind,
+                &args,
+                &ix_data,
+                RaydiumCpmmInstructions::from_ix_data,
+            )
+        }
+    }
+}
@@ -3,6 +3,8 @@ use base64::{Engine, engine::general_purpose::STANDARD};
 use serde::{Deserialize, Serialize};
 use solana_sdk::pubkey::Pubkey;
 
+use crate::{common::Dex, parse_error::DexParseError};
+
 /// LogType enum
 #[derive(Debug)]
 pub enum LogType {
@@ -15,14 +17,14 @@ pub enum LogType {
 
 #[allow(unused)]
 impl LogType {
-    pub fn from_u8(log_type: u8) -> Self {
+    pub fn from_u8(log_type: u8) -> Option<Self> {
         match log_type {
-            0 => LogType::Init,
-            1 => LogType::Deposit,
-            2 => LogType::Withdraw,
-            3 => LogType::SwapBaseIn,
-            4 => LogType::SwapBaseOut,
-            _ => unreachable!(),
+            0 => Some(LogType::Init),
+            1 => Some(LogType::Deposit),
+            2 => Some(LogType::Withdraw),
+            3 => Some(LogType::SwapBaseIn),
+            4 => Some(LogType::SwapBaseOut),
+            _ => None,
         }
     }
 
@@ -129,38 +131,35 @@ pub enum RayLogs {
 }
 
 impl RayLogs {
-    pub fn decode(log: &str) -> Result<Self> {
-        let bytes = STANDARD.decode(log)?;
-        let result = match LogType::from_u8(bytes[0]) {
-            LogType::Init => {
-                let log: InitLog = bincode::deserialize(&bytes)?;
-                RayLogs::Init(log)
-            }
-            LogType::Deposit => {
-                let log: DepositLog = bincode::deserialize(&bytes)?;
-                RayLogs::Deposit(log)
-            }
-            LogType::Withdraw => {
-                let log: WithdrawLog = bincode::deserialize(&bytes)?;
-                RayLogs::Withdraw(log)
-            }
-            LogType::SwapBaseIn => {
-                let log: SwapBaseInLog = bincode::deserialize(&bytes)?;
-                RayLogs::SwapBaseIn(log)
-            }
-            LogType::SwapBaseOut => {
-                let log: SwapBaseOutLog = bincode::deserialize(&bytes)?;
-                RayLogs::SwapBaseOut(log)
-            }
+    pub fn decode(log: &str) -> Result<Self, DexParseError> {
+        let bytes = STANDARD
+            .decode(log)
+            .map_err(|err| DexParseError::corrupt(Dex::RaydiumAmm, err))?;
+        let log_type = *bytes
+            .first()
+            .ok_or_else(|| DexParseError::corrupt(Dex::RaydiumAmm, "empty ray log"))?;
+        let log_type = LogType::from_u8(log_type)
+            .ok_or_else(|| DexParseError::unknown(Dex::RaydiumAmm, &[log_type]))?;
+        let result = match log_type {
+            LogType::Init => RayLogs::Init(deserialize(&bytes)?),
+            LogType::Deposit => RayLogs::Deposit(deserialize(&bytes)?),
+            LogType::Withdraw => RayLogs::Withdraw(deserialize(&bytes)?),
+            LogType::SwapBaseIn => RayLogs::SwapBaseIn(deserialize(&bytes)?),
+            LogType::SwapBaseOut => RayLogs::SwapBaseOut(deserialize(&bytes)?),
         };
 
         Ok(result)
     }
 }
 
+fn deserialize<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, DexParseError> {
+    bincode::deserialize(bytes).map_err(|err| DexParseError::corrupt(Dex::RaydiumAmm, err))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::{Encoding, decodes_arbitrary_bytes};
 
     #[test]
     fn test_decode_swap_basein() {
@@ -211,4 +210,38 @@ mod tests {
             })
         ))
     }
+
+    #[test]
+    fn test_decode_short_or_unknown_log() {
+        let corrupt = |log: &str| {
+            matches!(
+                RayLogs::decode(log),
+                Err(DexParseError::CorruptPayload { .. })
+            )
+        };
+        assert!(corrupt(""));
+        assert!(corrupt("not base64!"));
+        assert!(corrupt(&STANDARD.encode([3, 1])));
+        assert!(matches!(
+            RayLogs::decode(&STANDARD.encode([9])),
+            Err(DexParseError::UnknownDiscriminator { .. })
+        ));
+    }
+
+    /// the log types and an unknown one
+    const LOG_TYPES: [[u8; 1]; 6] = [[0], [1], [2], [3], [4], [5]];
+
+    quickcheck::quickcheck! {
+        fn test_decode_arbitrary_bytes(log_type: u8, fields: Vec<u8>, log: String) -> bool {
+            decodes_arbitrary_bytes(
+                Encoding::Base64,
+                &[],
+                &LOG_TYPES,
+                log_type,
+                &fields,
+                &log,
+                RayLogs::decode,
+            )
+        }
+    }
 }
@@ -0,0 +1,140 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use anyhow::Result;
+use redis::InfoDict;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::web::{SystemFrame, WS_SYSTEM_CHANNEL, WsHub};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+/// Alert when used memory reaches this share of maxmemory, unless configured
+pub const DEFAULT_REDIS_MEMORY_ALERT_RATIO: f64 = 0.9;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RedisInfo {
+    pub used_memory: u64,
+    /// 0 means no limit
+    pub maxmemory: u64,
+    pub evicted_keys: u64,
+    pub connected_clients: u64,
+}
+
+impl RedisInfo {
+    fn from_info_dicts(memory: &InfoDict, stats: &InfoDict, clients: &InfoDict) -> Self {
+        Self {
+            used_memory: memory.get("used_memory").unwrap_or_default(),
+            maxmemory: memory.get("maxmemory").unwrap_or_default(),
+            evicted_keys: stats.get("evicted_keys").unwrap_or_default(),
+            connected_clients: clients.get("connected_clients").unwrap_or_default(),
+        }
+    }
+
+    /// used memory / maxmemory, none without maxmemory
+    pub fn memory_usage(&self) -> Option<f64> {
+        (self.maxmemory > 0).then(|| self.used_memory as f64 / self.maxmemory as f64)
+    }
+}
+
+/// Latest redis info of the monitor, none until the first check.
+#[derive(Debug, Default)]
+pub struct RedisHealth {
+    latest: RwLock<Option<RedisInfo>>,
+}
+
+impl RedisHealth {
+    pub fn latest(&self) -> Option<RedisInfo> {
+        self.latest.read().ok()?.clone()
+    }
+
+    fn update(&self, info: RedisInfo) {
+        if let Ok(mut latest) = self.latest.write() {
+            *latest = Some(info);
+        }
+    }
+}
+
+/// Poll redis INFO, keep the latest values in `health` and alert when memory gets near maxmemory.
+pub async fn start(
+    redis_client: Arc<redis::Client>,
+    health: Arc<RedisHealth>,
+    hub: Arc<WsHub>,
+    alert_ratio: f64,
+) -> Result<()> {
+    info!("start redis monitor, alert at {alert_ratio} of maxmemory........");
+    let mut alerting = false;
+    loop {
+        let mut conn = redis_client.get_multiplexed_async_connection().await?;
+        let (memory, stats, clients): (InfoDict, InfoDict, InfoDict) = redis::pipe()
+            .cmd("INFO")
+            .arg("memory")
+            .cmd("INFO")
+            .arg("stats")
+            .cmd("INFO")
+            .arg("clients")
+            .query_async(&mut conn)
+            .await?;
+        drop(conn);
+
+        let info = RedisInfo::from_info_dicts(&memory, &stats, &clients);
+        let usage = info.memory_usage();
+        match usage {
+            Some(usage) if usage >= alert_ratio => {
+                warn!(
+                    "redis memory usage {:.1}%: {} of {} bytes, evicted keys: {}",
+                    usage * 100.0,
+                    info.used_memory,
+                    info.maxmemory,
+                    info.evicted_keys
+                );
+                if !alerting {
+                    hub.publish(
+                        WS_SYSTEM_CHANNEL,
+                        &SystemFrame::RedisMemoryAlert {
+                            used_memory: info.used_memory,
+                            maxmemory: info.maxmemory,
+                        },
+                    )?;
+                }
+                alerting = true;
+            }
+            _ => alerting = false,
+        }
+        health.update(info);
+
+        tokio::time::sleep(CHECK_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use redis::{FromRedisValue, Value};
+
+    use super::*;
+
+    #[test]
+    fn test_redis_info_from_info_dicts() {
+        let dict = |text: &str| {
+            InfoDict::from_redis_value(&Value::BulkString(text.as_bytes().to_vec())).unwrap()
+        };
+        let memory =
+            dict("# Memory\r\nused_memory:900\r\nused_memory_human:900B\r\nmaxmemory:1000\r\n");
+        let stats = dict("# Stats\r\nevicted_keys:3\r\n");
+        let clients = dict("# Clients\r\nconnected_clients:7\r\n");
+
+        let info = RedisInfo::from_info_dicts(&memory, &stats, &clients);
+        assert_eq!(info.used_memory, 900);
+        assert_eq!(info.evicted_keys, 3);
+        assert_eq!(info.connected_clients, 7);
+        assert_eq!(info.memory_usage(), Some(0.9));
+
+        let info = RedisInfo {
+            maxmemory: 0,
+            ..info
+        };
+        assert_eq!(info.memory_usage(), None);
+    }
+}
@@ -0,0 +1,330 @@
+//! The solana rpc calls of the app, behind a trait so tests and the offline mode can serve them
+//! without network.
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{Result, anyhow};
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_rpc_client_api::request::RpcRequest;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+
+use crate::rpc_tx::{RpcBlock, RpcTx};
+
+pub trait RpcProvider: Send + Sync {
+    fn get_slot(&self) -> BoxFuture<'_, Result<u64>>;
+
+    /// In the order of `addrs`, none for the accounts which don't exist
+    fn get_multiple_accounts<'a>(
+        &'a self,
+        addrs: &'a [Pubkey],
+    ) -> BoxFuture<'a, Result<Vec<Option<Account>>>>;
+
+    /// Confirmed transaction in json encoding, none if not found
+    fn get_transaction<'a>(&'a self, signature: &'a str) -> BoxFuture<'a, Result<Option<RpcTx>>>;
+
+    /// Signatures of the transactions of the address, newest first, older than `before`
+    fn get_signatures_for_address<'a>(
+        &'a self,
+        addr: &'a Pubkey,
+        before: Option<&'a str>,
+        limit: usize,
+    ) -> BoxFuture<'a, Result<Vec<String>>>;
+
+    /// Confirmed slots with a block between `start_slot` and `end_slot` inclusive, ascending
+    fn get_blocks(&self, start_slot: u64, end_slot: u64) -> BoxFuture<'_, Result<Vec<u64>>>;
+
+    /// Confirmed block in json encoding with full transaction details, none if not found
+    fn get_block(&self, slot: u64) -> BoxFuture<'_, Result<Option<RpcBlock>>>;
+
+    /// The 20 largest token accounts of the mint with their raw amounts, largest first
+    fn get_token_largest_accounts<'a>(
+        &'a self,
+        mint: &'a Pubkey,
+    ) -> BoxFuture<'a, Result<Vec<(Pubkey, u64)>>>;
+
+    fn get_account<'a>(&'a self, addr: &'a Pubkey) -> BoxFuture<'a, Result<Account>> {
+        Box::pin(async move {
+            self.get_multiple_accounts(std::slice::from_ref(addr))
+                .await?
+                .pop()
+                .flatten()
+                .ok_or_else(|| anyhow!("account {addr} not found"))
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcSignatureInfo {
+    signature: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcTokenAccounts {
+    value: Vec<RpcTokenAccount>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcTokenAccount {
+    address: String,
+    amount: String,
+}
+
+impl RpcProvider for RpcClient {
+    fn get_slot(&self) -> BoxFuture<'_, Result<u64>> {
+        Box::pin(async move { Ok(RpcClient::get_slot(self).await?) })
+    }
+
+    fn get_multiple_accounts<'a>(
+        &'a self,
+        addrs: &'a [Pubkey],
+    ) -> BoxFuture<'a, Result<Vec<Option<Account>>>> {
+        Box::pin(async move { Ok(RpcClient::get_multiple_accounts(self, addrs).await?) })
+    }
+
+    fn get_transaction<'a>(&'a self, signature: &'a str) -> BoxFuture<'a, Result<Option<RpcTx>>> {
+        Box::pin(async move {
+            let tx = self
+                .send(
+                    RpcRequest::GetTransaction,
+                    json!([signature, {
+                        "encoding": "json",
+                        "commitment": "confirmed",
+                        "maxSupportedTransactionVersion": 0,
+                    }]),
+                )
+                .await?;
+            Ok(tx)
+        })
+    }
+
+    fn get_signatures_for_address<'a>(
+        &'a self,
+        addr: &'a Pubkey,
+        before: Option<&'a str>,
+        limit: usize,
+    ) -> BoxFuture<'a, Result<Vec<String>>> {
+        Box::pin(async move {
+            let page: Vec<RpcSignatureInfo> = self
+                .send(
+                    RpcRequest::GetSignaturesForAddress,
+                    json!([addr.to_string(), {
+                        "limit": limit,
+                        "before": before,
+                        "commitment": "confirmed",
+                    }]),
+                )
+                .await?;
+            Ok(page.into_iter().map(|it| it.signature).collect())
+        })
+    }
+
+    fn get_blocks(&self, start_slot: u64, end_slot: u64) -> BoxFuture<'_, Result<Vec<u64>>> {
+        Box::pin(async move {
+            let slots = self
+                .send(
+                    RpcRequest::GetBlocks,
+                    json!([start_slot, end_slot, {"commitment": "confirmed"}]),
+                )
+                .await?;
+            Ok(slots)
+        })
+    }
+
+    fn get_block(&self, slot: u64) -> BoxFuture<'_, Result<Option<RpcBlock>>> {
+        Box::pin(async move {
+            let block = self
+                .send(
+                    RpcRequest::GetBlock,
+                    json!([slot, {
+                        "encoding": "json",
+                        "transactionDetails": "full",
+                        "rewards": false,
+                        "commitment": "confirmed",
+                        "maxSupportedTransactionVersion": 0,
+                    }]),
+                )
+                .await?;
+            Ok(block)
+        })
+    }
+
+    fn get_token_largest_accounts<'a>(
+        &'a self,
+        mint: &'a Pubkey,
+    ) -> BoxFuture<'a, Result<Vec<(Pubkey, u64)>>> {
+        Box::pin(async move {
+            let accounts: RpcTokenAccounts = self
+                .send(
+                    RpcRequest::GetTokenLargestAccounts,
+                    json!([mint.to_string(), {"commitment": "confirmed"}]),
+                )
+                .await?;
+            accounts
+                .value
+                .into_iter()
+                .map(|it| Ok((it.address.parse()?, it.amount.parse()?)))
+                .collect()
+        })
+    }
+}
+
+/// Serves the accounts and transactions it's given, everything else is not found.
+/// Used by the tests and, empty, by the offline mode.
+#[derive(Debug, Default)]
+pub struct MockRpcProvider {
+    pub slot: u64,
+    pub accounts: HashMap<Pubkey, Account>,
+    /// `getTransaction` results in json encoding
+    pub txs: HashMap<String, Value>,
+    /// newest first
+    pub signatures: HashMap<Pubkey, Vec<String>>,
+    /// `getBlock` results in json encoding
+    pub blocks: BTreeMap<u64, Value>,
+    /// largest token accounts of the mints
+    pub largest_accounts: HashMap<Pubkey, Vec<(Pubkey, u64)>>,
+}
+
+impl MockRpcProvider {
+    pub fn with_account(mut self, addr: Pubkey, account: Account) -> Self {
+        self.accounts.insert(addr, account);
+        self
+    }
+
+    pub fn with_tx(mut self, signature: &str, tx: Value) -> Self {
+        self.txs.insert(signature.to_string(), tx);
+        self
+    }
+
+    pub fn with_signatures(mut self, addr: Pubkey, signatures: Vec<String>) -> Self {
+        self.signatures.insert(addr, signatures);
+        self
+    }
+
+    pub fn with_block(mut self, slot: u64, block: Value) -> Self {
+        self.blocks.insert(slot, block);
+        self
+    }
+
+    pub fn with_largest_accounts(mut self, mint: Pubkey, accounts: Vec<(Pubkey, u64)>) -> Self {
+        self.largest_accounts.insert(mint, accounts);
+        self
+    }
+}
+
+impl RpcProvider for MockRpcProvider {
+    fn get_slot(&self) -> BoxFuture<'_, Result<u64>> {
+        Box::pin(async move { Ok(self.slot) })
+    }
+
+    fn get_multiple_accounts<'a>(
+        &'a self,
+        addrs: &'a [Pubkey],
+    ) -> BoxFuture<'a, Result<Vec<Option<Account>>>> {
+        Box::pin(async move {
+            Ok(addrs
+                .iter()
+                .map(|it| self.accounts.get(it).cloned())
+                .collect())
+        })
+    }
+
+    fn get_transaction<'a>(&'a self, signature: &'a str) -> BoxFuture<'a, Result<Option<RpcTx>>> {
+        Box::pin(async move {
+            let Some(tx) = self.txs.get(signature) else {
+                return Ok(None);
+            };
+            Ok(Some(RpcTx::deserialize(tx)?))
+        })
+    }
+
+    fn get_signatures_for_address<'a>(
+        &'a self,
+        addr: &'a Pubkey,
+        before: Option<&'a str>,
+        limit: usize,
+    ) -> BoxFuture<'a, Result<Vec<String>>> {
+        Box::pin(async move {
+            let signatures = self.signatures.get(addr).map(Vec::as_slice).unwrap_or(&[]);
+            let start = match before {
+                Some(before) => signatures
+                    .iter()
+                    .position(|it| it == before)
+                    .map_or(signatures.len(), |it| it + 1),
+                None => 0,
+            };
+            Ok(signatures[start..].iter().take(limit).cloned().collect())
+        })
+    }
+
+    fn get_blocks(&self, start_slot: u64, end_slot: u64) -> BoxFuture<'_, Result<Vec<u64>>> {
+        Box::pin(async move {
+            Ok(self
+                .blocks
+                .range(start_slot..=end_slot)
+                .map(|(slot, _)| *slot)
+                .collect())
+        })
+    }
+
+    fn get_block(&self, slot: u64) -> BoxFuture<'_, Result<Option<RpcBlock>>> {
+        Box::pin(async move {
+            let Some(block) = self.blocks.get(&slot) else {
+                return Ok(None);
+            };
+            Ok(Some(RpcBlock::deserialize(block)?))
+        })
+    }
+
+    fn get_token_largest_accounts<'a>(
+        &'a self,
+        mint: &'a Pubkey,
+    ) -> BoxFuture<'a, Result<Vec<(Pubkey, u64)>>> {
+        Box::pin(async move { Ok(self.largest_accounts.get(mint).cloned().unwrap_or_default()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_rpc_provider() {
+        let addr = Pubkey::new_unique();
+        let account = Account {
+            lamports: 1,
+            ..Default::default()
+        };
+        let signatures = (0..5).map(|it| format!("sig{it}")).collect();
+        let provider = MockRpcProvider::default()
+            .with_account(addr, account.clone())
+            .with_signatures(addr, signatures);
+
+        let missing = Pubkey::new_unique();
+        let accounts = provider
+            .get_multiple_accounts(&[missing, addr])
+            .await
+            .unwrap();
+        assert_eq!(accounts, vec![None, Some(account)]);
+        assert!(provider.get_account(&missing).await.is_err());
+        assert!(provider.get_transaction("sig0").await.unwrap().is_none());
+
+        let page = provider
+            .get_signatures_for_address(&addr, None, 2)
+            .await
+            .unwrap();
+        assert_eq!(page, ["sig0", "sig1"]);
+        let page = provider
+            .get_signatures_for_address(&addr, Some("sig1"), 10)
+            .await
+            .unwrap();
+        assert_eq!(page, ["sig2", "sig3", "sig4"]);
+        let page = provider
+            .get_signatures_for_address(&missing, None, 10)
+            .await
+            .unwrap();
+        assert!(page.is_empty());
+    }
+}
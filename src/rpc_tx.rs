@@ -0,0 +1,566 @@
+use std::collections::HashMap;
+
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    common::{Dex, DexEventSource},
+    compute_budget::ComputeBudget,
+    qn_req_processor::{
+        Amt, Instruction, IxAccount, ProgramInvocation, TokenAmt, TokenTransfer, Tx,
+    },
+};
+
+/// anchor `emit_cpi!` self invocation instruction data prefix
+pub const ANCHOR_EVENT_IX_TAG: [u8; 8] = [228, 69, 165, 46, 81, 203, 154, 29];
+
+pub const TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+pub const TOKEN_2022_PROGRAM_ID: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
+/// instruction tags of the token programs
+const TRANSFER_IX_TAG: u8 = 3;
+const TRANSFER_CHECKED_IX_TAG: u8 = 12;
+
+/// `getTransaction` response with `json` encoding
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcTx {
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    #[serde(flatten)]
+    pub tx: RpcTxWithMeta,
+}
+
+/// `getBlock` response with `json` encoding and full transaction details
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcBlock {
+    pub block_time: Option<i64>,
+    #[serde(default)]
+    pub transactions: Vec<RpcTxWithMeta>,
+}
+
+/// transaction item of `getTransaction` and `getBlock` responses with `json` encoding
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcTxWithMeta {
+    pub transaction: RpcTxBody,
+    pub meta: Option<RpcTxMeta>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcTxBody {
+    pub signatures: Vec<String>,
+    pub message: RpcTxMessage,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcTxMessage {
+    pub account_keys: Vec<String>,
+    pub instructions: Vec<RpcCompiledIx>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcCompiledIx {
+    pub program_id_index: usize,
+    pub accounts: Vec<usize>,
+    pub data: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcTxMeta {
+    pub err: Option<serde_json::Value>,
+    pub pre_balances: Vec<u64>,
+    pub post_balances: Vec<u64>,
+    #[serde(default)]
+    pub pre_token_balances: Vec<RpcTokenBalance>,
+    #[serde(default)]
+    pub post_token_balances: Vec<RpcTokenBalance>,
+    #[serde(default)]
+    pub inner_instructions: Vec<RpcInnerIxs>,
+    #[serde(default)]
+    pub log_messages: Vec<String>,
+    #[serde(default)]
+    pub loaded_addresses: RpcLoadedAddresses,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcTokenBalance {
+    pub account_index: usize,
+    pub mint: String,
+    pub ui_token_amount: RpcUiTokenAmount,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcUiTokenAmount {
+    pub amount: String,
+    pub decimals: u8,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcInnerIxs {
+    pub index: usize,
+    pub instructions: Vec<RpcCompiledIx>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcLoadedAddresses {
+    pub writable: Vec<String>,
+    pub readonly: Vec<String>,
+}
+
+impl RpcTx {
+    pub fn into_tx(self) -> Result<Tx> {
+        let blk_ts = self
+            .block_time
+            .ok_or_else(|| anyhow!("transaction has no block time"))?;
+        self.tx.into_tx(self.slot, blk_ts)
+    }
+
+    /// `None` for a failed transaction unless it's kept with `keep_failed`
+    pub fn into_kept_tx(self, keep_failed: bool) -> Result<Option<Tx>> {
+        let blk_ts = self
+            .block_time
+            .ok_or_else(|| anyhow!("transaction has no block time"))?;
+        self.tx.into_kept_tx(self.slot, blk_ts, keep_failed)
+    }
+}
+
+impl RpcBlock {
+    /// The successful transactions of the block with dex event logs, and with `keep_failed` the
+    /// failed ones with dex instructions, in block order
+    pub fn into_txs(self, slot: u64, keep_failed: bool) -> Result<Vec<Tx>> {
+        let blk_ts = self
+            .block_time
+            .ok_or_else(|| anyhow!("block {slot} has no block time"))?;
+        let mut txs = vec![];
+        for tx in self.transactions {
+            let Some(tx) = tx.into_kept_tx(slot, blk_ts, keep_failed)? else {
+                continue;
+            };
+            let failed = tx.err.is_some();
+            if (failed && !tx.ixs.is_empty()) || (!failed && !tx.logs.is_empty()) {
+                txs.push(tx);
+            }
+        }
+        Ok(txs)
+    }
+}
+
+impl RpcTxWithMeta {
+    pub fn signature(&self) -> &str {
+        self.transaction
+            .signatures
+            .first()
+            .map(|it| it.as_str())
+            .unwrap_or_default()
+    }
+
+    pub fn tx_err(&self) -> Option<&serde_json::Value> {
+        self.meta.as_ref().and_then(|it| it.err.as_ref())
+    }
+
+    /// Convert the transaction, `None` for a failed one unless it's kept with `keep_failed`
+    pub fn into_kept_tx(self, slot: u64, blk_ts: i64, keep_failed: bool) -> Result<Option<Tx>> {
+        if self.tx_err().is_none() {
+            return Ok(Some(self.into_tx(slot, blk_ts)?));
+        }
+        if !keep_failed {
+            return Ok(None);
+        }
+        Ok(Some(self.into_failed_tx(slot, blk_ts)?))
+    }
+
+    /// Convert to the quicknode stream transaction model, which keeps one
+    /// (program invocation, event log) pair per dex event emitted in the transaction.
+    pub fn into_tx(self, slot: u64, blk_ts: i64) -> Result<Tx> {
+        let signature = self.signature().to_string();
+        let meta = self
+            .meta
+            .ok_or_else(|| anyhow!("transaction {signature} has no meta"))?;
+        let RpcTxMessage {
+            mut account_keys,
+            instructions,
+        } = self.transaction.message;
+        account_keys.extend(meta.loaded_addresses.writable.iter().cloned());
+        account_keys.extend(meta.loaded_addresses.readonly.iter().cloned());
+
+        let compute_budget = compute_budget(&instructions, &account_keys);
+        let flat_ixs = flatten_ixs(instructions, &meta.inner_instructions);
+        let ix_logs = split_logs_by_invocation(&meta.log_messages, flat_ixs.len());
+
+        let program_id_of = |ix: &RpcCompiledIx| -> Result<String> {
+            account_keys
+                .get(ix.program_id_index)
+                .cloned()
+                .ok_or_else(|| anyhow!("program id index out of range in tx {signature}"))
+        };
+
+        let mut ixs = vec![];
+        let mut logs = vec![];
+        // flattened instruction index of the latest invocation of each anchor program
+        let mut latest_invocations: HashMap<String, usize> = HashMap::new();
+        for (flat_idx, (_, ix)) in flat_ixs.iter().enumerate() {
+            let program_id = program_id_of(ix)?;
+            let Some(dex) = Dex::from_program_id(&program_id) else {
+                continue;
+            };
+
+            let line_prefix = match dex.event_source() {
+                DexEventSource::RayLog => "Program log: ray_log: ",
+                DexEventSource::ProgramData => "Program data: ",
+                DexEventSource::AnchorCpi => {
+                    let data = bs58::decode(&ix.data).into_vec()?;
+                    if !data.starts_with(&ANCHOR_EVENT_IX_TAG) {
+                        latest_invocations.insert(program_id, flat_idx);
+                        continue;
+                    }
+                    let Some(&invocation_idx) = latest_invocations.get(&program_id) else {
+                        continue;
+                    };
+                    ixs.push(invocation(
+                        &flat_ixs,
+                        invocation_idx,
+                        &program_id,
+                        &account_keys,
+                        &meta,
+                    )?);
+                    logs.push(format!("{}{}", dex.log_prefix(), ix.data));
+                    continue;
+                }
+                DexEventSource::Instruction => {
+                    let mut invocation =
+                        invocation(&flat_ixs, flat_idx, &program_id, &account_keys, &meta)?;
+                    invocation.instruction.transfers =
+                        token_transfers(&flat_ixs, flat_idx, &account_keys);
+                    ixs.push(invocation);
+                    logs.push(format!("{}{}", dex.log_prefix(), ix.data));
+                    continue;
+                }
+            };
+            for log in &ix_logs[flat_idx] {
+                if let Some(payload) = log.strip_prefix(line_prefix) {
+                    ixs.push(invocation(
+                        &flat_ixs,
+                        flat_idx,
+                        &program_id,
+                        &account_keys,
+                        &meta,
+                    )?);
+                    logs.push(format!("{}{payload}", dex.log_prefix()));
+                }
+            }
+        }
+
+        Ok(Tx {
+            blk_ts,
+            slot,
+            signature,
+            logs,
+            ixs,
+            err: None,
+            compute_budget,
+            requeued_logs: None,
+        })
+    }
+
+    /// Convert a failed transaction, which has no event logs, keeping its error and the dex
+    /// instructions it attempted, the top level ones and the inner ones recorded before it failed
+    pub fn into_failed_tx(self, slot: u64, blk_ts: i64) -> Result<Tx> {
+        let signature = self.signature().to_string();
+        let meta = self
+            .meta
+            .ok_or_else(|| anyhow!("transaction {signature} has no meta"))?;
+        let RpcTxMessage {
+            mut account_keys,
+            instructions,
+        } = self.transaction.message;
+        account_keys.extend(meta.loaded_addresses.writable.iter().cloned());
+        account_keys.extend(meta.loaded_addresses.readonly.iter().cloned());
+
+        let compute_budget = compute_budget(&instructions, &account_keys);
+        let flat_ixs = flatten_ixs(instructions, &meta.inner_instructions);
+        let mut ixs = vec![];
+        for (flat_idx, (_, ix)) in flat_ixs.iter().enumerate() {
+            let Some(program_id) = account_keys.get(ix.program_id_index) else {
+                return Err(anyhow!("program id index out of range in tx {signature}"));
+            };
+            if Dex::from_program_id(program_id).is_some() {
+                ixs.push(invocation(
+                    &flat_ixs,
+                    flat_idx,
+                    program_id,
+                    &account_keys,
+                    &meta,
+                )?);
+            }
+        }
+
+        Ok(Tx {
+            blk_ts,
+            slot,
+            signature,
+            logs: vec![],
+            ixs,
+            err: meta.err.clone(),
+            compute_budget,
+            requeued_logs: None,
+        })
+    }
+}
+
+/// The budget set by the top level instructions
+fn compute_budget(
+    instructions: &[RpcCompiledIx],
+    account_keys: &[String],
+) -> Option<ComputeBudget> {
+    ComputeBudget::from_ixs(instructions.iter().filter_map(|ix| {
+        let program_id = account_keys.get(ix.program_id_index)?;
+        Some((program_id.as_str(), ix.data.as_str()))
+    }))
+}
+
+/// The instructions in execution order, with their top level instruction index
+fn flatten_ixs(
+    instructions: Vec<RpcCompiledIx>,
+    inner_instructions: &[RpcInnerIxs],
+) -> Vec<(usize, RpcCompiledIx)> {
+    let mut inner_ixs: HashMap<usize, Vec<RpcCompiledIx>> = inner_instructions
+        .iter()
+        .map(|it| (it.index, it.instructions.clone()))
+        .collect();
+    let mut flat_ixs = vec![];
+    for (idx, ix) in instructions.into_iter().enumerate() {
+        flat_ixs.push((idx, ix));
+        for inner_ix in inner_ixs.remove(&idx).unwrap_or_default() {
+            flat_ixs.push((idx, inner_ix));
+        }
+    }
+    flat_ixs
+}
+
+fn invocation(
+    flat_ixs: &[(usize, RpcCompiledIx)],
+    flat_idx: usize,
+    program_id: &str,
+    account_keys: &[String],
+    meta: &RpcTxMeta,
+) -> Result<ProgramInvocation> {
+    let (index, ix) = &flat_ixs[flat_idx];
+    let mut accounts = vec![];
+    for &account_idx in &ix.accounts {
+        let pubkey = account_keys
+            .get(account_idx)
+            .ok_or_else(|| anyhow!("account index {account_idx} out of range"))?;
+        accounts.push(IxAccount {
+            pubkey: pubkey.clone(),
+            pre_amt: Amt {
+                sol: meta
+                    .pre_balances
+                    .get(account_idx)
+                    .copied()
+                    .unwrap_or_default(),
+                token: token_amt(&meta.pre_token_balances, account_idx)?,
+            },
+            post_amt: Amt {
+                sol: meta
+                    .post_balances
+                    .get(account_idx)
+                    .copied()
+                    .unwrap_or_default(),
+                token: token_amt(&meta.post_token_balances, account_idx)?,
+            },
+        });
+    }
+
+    Ok(ProgramInvocation {
+        program_id: program_id.to_string(),
+        instruction: Instruction {
+            accounts,
+            data: ix.data.clone(),
+            index: *index as u64,
+            transfers: vec![],
+        },
+    })
+}
+
+/// The token transfers invoked by the instruction at `flat_idx`, the ones after it up to the
+/// next top level instruction or the next instruction of its program
+fn token_transfers(
+    flat_ixs: &[(usize, RpcCompiledIx)],
+    flat_idx: usize,
+    account_keys: &[String],
+) -> Vec<TokenTransfer> {
+    let (index, ix) = &flat_ixs[flat_idx];
+    let program_id_of = |ix: &RpcCompiledIx| account_keys.get(ix.program_id_index);
+    flat_ixs[flat_idx + 1..]
+        .iter()
+        .take_while(|(it_index, it)| it_index == index && program_id_of(it) != program_id_of(ix))
+        .filter_map(|(_, it)| {
+            let program_id = program_id_of(it)?;
+            if program_id != TOKEN_PROGRAM_ID && program_id != TOKEN_2022_PROGRAM_ID {
+                return None;
+            }
+            token_transfer(it, account_keys)
+        })
+        .collect()
+}
+
+fn token_transfer(ix: &RpcCompiledIx, account_keys: &[String]) -> Option<TokenTransfer> {
+    let data = bs58::decode(&ix.data).into_vec().ok()?;
+    let (tag, amt) = data.split_first()?;
+    let amt = u64::from_le_bytes(amt.get(..8)?.try_into().ok()?);
+    // source, destination, authority, or source, mint, destination, authority for the checked one
+    let (source, destination) = match *tag {
+        TRANSFER_IX_TAG => (ix.accounts.first()?, ix.accounts.get(1)?),
+        TRANSFER_CHECKED_IX_TAG => (ix.accounts.first()?, ix.accounts.get(2)?),
+        _ => return None,
+    };
+    Some(TokenTransfer {
+        source: account_keys.get(*source)?.clone(),
+        destination: account_keys.get(*destination)?.clone(),
+        amt,
+    })
+}
+
+fn token_amt(balances: &[RpcTokenBalance], account_idx: usize) -> Result<Option<TokenAmt>> {
+    let Some(balance) = balances.iter().find(|it| it.account_index == account_idx) else {
+        return Ok(None);
+    };
+
+    Ok(Some(TokenAmt {
+        mint: balance.mint.clone(),
+        decimals: balance.ui_token_amount.decimals,
+        amt: balance.ui_token_amount.amount.parse()?,
+    }))
+}
+
+/// Group `Program log:` and `Program data:` lines by the flattened instruction which emitted them.
+fn split_logs_by_invocation(log_messages: &[String], ixs_len: usize) -> Vec<Vec<String>> {
+    let mut result: Vec<Vec<String>> = vec![vec![]; ixs_len];
+    let mut stack: Vec<usize> = vec![];
+    let mut invoke_count = 0;
+    for log in log_messages {
+        if log.starts_with("Program log: ") || log.starts_with("Program data: ") {
+            if let Some(&flat_idx) = stack.last()
+                && let Some(logs) = result.get_mut(flat_idx)
+            {
+                logs.push(log.clone());
+            }
+            continue;
+        }
+
+        let mut parts = log.split_whitespace();
+        if parts.next() != Some("Program") {
+            continue;
+        }
+        let is_program_id = parts.next().is_some_and(|it| it.parse::<Pubkey>().is_ok());
+        if !is_program_id {
+            continue;
+        }
+        match parts.next() {
+            Some("invoke") => {
+                stack.push(invoke_count);
+                invoke_count += 1;
+            }
+            Some("success") | Some("failed:") => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_logs_by_invocation() {
+        let logs: Vec<String> = [
+            "Program ComputeBudget111111111111111111111111111111 invoke [1]",
+            "Program ComputeBudget111111111111111111111111111111 success",
+            "Program 675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8 invoke [1]",
+            "Program log: ray_log: A1x8BAAAAAAAqgAAAAAAAAABAAAAAAAAAFx8BAAAAAAA4kxOVRsAAADq2uJNY4UAAOoAAAAAAAAA",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA invoke [2]",
+            "Program log: Instruction: Transfer",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA success",
+            "Program 675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8 consumed 2000 of 200000 compute units",
+            "Program 675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8 success",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+        let result = split_logs_by_invocation(&logs, 3);
+        assert!(result[0].is_empty());
+        assert_eq!(result[1].len(), 1);
+        assert!(result[1][0].starts_with("Program log: ray_log: "));
+        assert_eq!(
+            result[2],
+            vec!["Program log: Instruction: Transfer".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_token_transfers() {
+        let account_keys: Vec<String> = [
+            "user",
+            "CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C",
+            TOKEN_PROGRAM_ID,
+            "user_in",
+            "vault_in",
+            "vault_out",
+            "user_out",
+            "mint",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        let ix = |program_id_index, accounts: Vec<usize>, tag: u8, amt: u64| {
+            let mut data = vec![tag];
+            data.extend(amt.to_le_bytes());
+            RpcCompiledIx {
+                program_id_index,
+                accounts,
+                data: bs58::encode(data).into_string(),
+            }
+        };
+        let flat_ixs = vec![
+            (0, ix(1, vec![0, 4, 5], 0, 0)),
+            (0, ix(2, vec![3, 7, 4, 0], TRANSFER_CHECKED_IX_TAG, 1_000)),
+            (0, ix(2, vec![5, 6, 0], TRANSFER_IX_TAG, 42)),
+            // approve
+            (0, ix(2, vec![5, 6, 0], 4, 1)),
+            (0, ix(1, vec![0, 4, 5], 0, 0)),
+            (0, ix(2, vec![5, 6, 0], TRANSFER_IX_TAG, 7)),
+            (1, ix(2, vec![5, 6, 0], TRANSFER_IX_TAG, 8)),
+        ];
+        let transfer = |source: &str, destination: &str, amt| TokenTransfer {
+            source: source.to_string(),
+            destination: destination.to_string(),
+            amt,
+        };
+        assert_eq!(
+            token_transfers(&flat_ixs, 0, &account_keys),
+            vec![
+                transfer("user_in", "vault_in", 1_000),
+                transfer("vault_out", "user_out", 42)
+            ]
+        );
+        assert_eq!(
+            token_transfers(&flat_ixs, 4, &account_keys),
+            vec![transfer("vault_out", "user_out", 7)]
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::{
+    collections::BTreeMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use anyhow::Result;
+
+use crate::{
+    cache::{DexEvent, DexPoolCache, ShadowDiff, ShadowDiffKind},
+    qn_req_processor::{self, EventSource, Tx, TxParseOutcome},
+};
+
+static SHADOW_DIFFS: AtomicU64 = AtomicU64::new(0);
+
+/// Discrepancies found between the production and the candidate parser since the start
+pub fn shadow_diffs() -> u64 {
+    SHADOW_DIFFS.load(Ordering::Relaxed)
+}
+
+/// The parser under test, it's the production parser until a new version is wired in here
+async fn candidate_parse_tx(tx: &Tx, pools: &mut DexPoolCache) -> Result<TxParseOutcome> {
+    Ok(qn_req_processor::parse_tx(tx, pools).await?)
+}
+
+/// Events of each transaction of the batch parsed by the candidate parser, or the error it
+/// failed with. It parses with a read only copy of the pools, so neither the cache nor the
+/// production parse of the batch see its saves.
+pub async fn parse_candidate(
+    txs: &[(EventSource, Tx)],
+    pools: &DexPoolCache,
+) -> Vec<Result<Vec<DexEvent>>> {
+    let mut pools = pools.read_only_copy();
+    let mut parsed = Vec::with_capacity(txs.len());
+    for (_, tx) in txs {
+        let outcome = candidate_parse_tx(tx, &mut pools).await;
+        parsed.push(outcome.map(|it| it.events));
+    }
+    parsed
+}
+
+/// Compare the events of a transaction by kind and instruction index, events of the same kind
+/// and index are paired in order.
+pub fn diff_events(
+    txid: &str,
+    slot: u64,
+    current: &[DexEvent],
+    candidate: &Result<Vec<DexEvent>>,
+) -> Vec<ShadowDiff> {
+    let diff = |kind, current, candidate, error| ShadowDiff {
+        txid: txid.to_string(),
+        slot,
+        kind,
+        current,
+        candidate,
+        error,
+    };
+    let candidate = match candidate {
+        Ok(events) => events,
+        Err(err) => {
+            let diffs = vec![diff(
+                ShadowDiffKind::Error,
+                None,
+                None,
+                Some(err.to_string()),
+            )];
+            SHADOW_DIFFS.fetch_add(1, Ordering::Relaxed);
+            return diffs;
+        }
+    };
+
+    // (current, candidate) events by instruction index and kind
+    type Paired = (Vec<serde_json::Value>, Vec<serde_json::Value>);
+    let mut events: BTreeMap<(u64, String), Paired> = BTreeMap::new();
+    let value = |evt: &DexEvent| serde_json::to_value(evt).unwrap_or_default();
+    for evt in current {
+        let key = (evt.idx(), evt.kind().to_string());
+        events.entry(key).or_default().0.push(value(evt));
+    }
+    for evt in candidate {
+        let key = (evt.idx(), evt.kind().to_string());
+        events.entry(key).or_default().1.push(value(evt));
+    }
+
+    let mut diffs = vec![];
+    for (current, candidate) in events.into_values() {
+        let mut current = current.into_iter();
+        let mut candidate = candidate.into_iter();
+        loop {
+            match (current.next(), candidate.next()) {
+                (Some(a), Some(b)) if a == b => {}
+                (Some(a), Some(b)) => {
+                    diffs.push(diff(ShadowDiffKind::Changed, Some(a), Some(b), None));
+                }
+                (Some(a), None) => diffs.push(diff(ShadowDiffKind::Missing, Some(a), None, None)),
+                (None, Some(b)) => diffs.push(diff(ShadowDiffKind::Extra, None, Some(b), None)),
+                (None, None) => break,
+            }
+        }
+    }
+    SHADOW_DIFFS.fetch_add(diffs.len() as u64, Ordering::Relaxed);
+    diffs
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+    use chrono::DateTime;
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+    use crate::cache::PumpfunSetParamsRecord;
+
+    fn set_params(idx: u64, fee_basis_points: u64) -> DexEvent {
+        DexEvent::PumpfunSetParams(PumpfunSetParamsRecord {
+            blk_ts: DateTime::from_timestamp(1, 0).unwrap(),
+            slot: 1,
+            txid: "tx".to_string(),
+            idx,
+            fee_recipient: Pubkey::default(),
+            initial_virtual_token_reserves: 1,
+            initial_virtual_sol_reserves: 1,
+            initial_real_token_reserves: 1,
+            token_total_supply: 1,
+            fee_basis_points,
+            source: None,
+        })
+    }
+
+    #[test]
+    fn test_diff_events() {
+        let current = [set_params(0, 100), set_params(1, 100), set_params(2, 100)];
+        let same = Ok(vec![
+            set_params(0, 100),
+            set_params(1, 100),
+            set_params(2, 100),
+        ]);
+        assert!(diff_events("tx", 1, &current, &same).is_empty());
+
+        let candidate = Ok(vec![
+            set_params(0, 100),
+            set_params(1, 95),
+            set_params(3, 100),
+        ]);
+        let diffs = diff_events("tx", 1, &current, &candidate);
+        let kinds: Vec<_> = diffs.iter().map(|it| it.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ShadowDiffKind::Changed,
+                ShadowDiffKind::Missing,
+                ShadowDiffKind::Extra
+            ]
+        );
+        assert_eq!(diffs[0].candidate.as_ref().unwrap()["fee_basis_points"], 95);
+
+        let failed = Err(anyhow!("candidate failed"));
+        let diffs = diff_events("tx", 1, &current, &failed);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].kind, ShadowDiffKind::Error);
+    }
+}
@@ -0,0 +1,87 @@
+//! Keep the SOL/USD price in redis from the reserves of a SOL/USDC pool, to annotate the
+//! trades with their USD prices.
+
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use anyhow::{Result, anyhow};
+use solana_sdk::pubkey::Pubkey;
+use tracing::{info, warn};
+
+use crate::{
+    cache,
+    common::{USDC_MINT, WSOL_MINT, utils},
+    config::SolUsdConfig,
+    rpc_provider::RpcProvider,
+};
+
+/// spl token accounts start with the mint
+const TOKEN_ACCOUNT_MINT_LEN: usize = 32;
+
+/// USD per SOL from the data of the WSOL and USDC vaults of a pool, none if they're not
+/// token accounts of these mints or one is empty
+pub fn vault_price(sol_vault: &[u8], usdc_vault: &[u8]) -> Option<f64> {
+    let amount = |data: &[u8], mint: &Pubkey| {
+        let account_mint = data.get(..TOKEN_ACCOUNT_MINT_LEN)?;
+        (account_mint == mint.as_ref())
+            .then(|| utils::token_account_amount(data))
+            .flatten()
+            .filter(|it| *it > 0)
+    };
+    let sol = utils::ui_amount(amount(sol_vault, &WSOL_MINT)?, 9);
+    let usdc = utils::ui_amount(amount(usdc_vault, &USDC_MINT)?, 6);
+    Some(usdc / sol)
+}
+
+pub async fn start(
+    redis_client: Arc<redis::Client>,
+    rpc_client: Arc<dyn RpcProvider>,
+    config: SolUsdConfig,
+) -> Result<()> {
+    info!(
+        "start sol usd oracle, every {} seconds........",
+        config.interval_secs
+    );
+    let vaults = [
+        Pubkey::from_str(&config.sol_vault)?,
+        Pubkey::from_str(&config.usdc_vault)?,
+    ];
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_secs));
+    loop {
+        interval.tick().await;
+        let accounts = rpc_client.get_multiple_accounts(&vaults).await?;
+        let [Some(sol_vault), Some(usdc_vault)] = accounts.as_slice() else {
+            return Err(anyhow!("sol usd vaults {vaults:?} not found"));
+        };
+        let Some(price) = vault_price(&sol_vault.data, &usdc_vault.data) else {
+            warn!("sol usd vaults {vaults:?} are not WSOL and USDC token accounts with balance");
+            continue;
+        };
+        let mut conn = redis_client.get_multiplexed_async_connection().await?;
+        cache::save_sol_usd_price(&mut conn, price, config.max_age_secs).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token_account(mint: &Pubkey, amount: u64) -> Vec<u8> {
+        let mut data = vec![0; 165];
+        data[..32].copy_from_slice(mint.as_ref());
+        data[64..72].copy_from_slice(&amount.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_vault_price() {
+        let sol_vault = token_account(&WSOL_MINT, 2_000_000_000);
+        let usdc_vault = token_account(&USDC_MINT, 300_000_000);
+        assert_eq!(vault_price(&sol_vault, &usdc_vault), Some(150.0));
+        // swapped vaults
+        assert_eq!(vault_price(&usdc_vault, &sol_vault), None);
+        assert_eq!(
+            vault_price(&token_account(&WSOL_MINT, 0), &usdc_vault),
+            None
+        );
+    }
+}
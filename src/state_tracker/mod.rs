@@ -0,0 +1,232 @@
+mod tracked_pool;
+
+pub use tracked_pool::*;
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{Result, bail};
+use chrono::Utc;
+use futures::StreamExt;
+use solana_account_decoder_client_types::UiAccountEncoding;
+use solana_pubsub_client::nonblocking::pubsub_client::PubsubClient;
+use solana_rpc_client_api::config::RpcAccountInfoConfig;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{info, warn};
+
+use crate::{
+    cache::{self, DexPoolRecord, RedisCacheRecord},
+    config::StateTrackerConfig,
+    rpc_provider::RpcProvider,
+};
+
+/// How often the set of actively traded pools is checked
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_ACCOUNTS_PER_RPC: usize = 100;
+const ACCOUNT_MSG_CAPACITY: usize = 10_000;
+
+enum AccountMsg {
+    Updated {
+        pool: Pubkey,
+        account: WatchedAccount,
+        slot: u64,
+        data: Vec<u8>,
+    },
+    Closed {
+        reason: String,
+    },
+}
+
+struct SubscribedPool {
+    pool: TrackedPool,
+    /// dropping them unsubscribes the accounts of the pool
+    _stops: Vec<oneshot::Sender<()>>,
+}
+
+/// Subscribe to the accounts of actively traded pools and emit `PoolStateUpdated` on change.
+pub async fn start(
+    redis_client: Arc<redis::Client>,
+    rpc_client: Arc<dyn RpcProvider>,
+    config: StateTrackerConfig,
+) -> Result<()> {
+    info!(
+        "start pool state tracker, up to {} pools traded in {} seconds........",
+        config.max_pools, config.active_secs
+    );
+    let pubsub = Arc::new(PubsubClient::new(&config.sol_ws_url).await?);
+    let (msg_tx, mut msg_rx) = mpsc::channel(ACCOUNT_MSG_CAPACITY);
+    let mut subscribed: HashMap<Pubkey, SubscribedPool> = HashMap::new();
+    let mut events = vec![];
+    let mut refresh = tokio::time::interval(REFRESH_INTERVAL);
+    let mut flush = tokio::time::interval(FLUSH_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = refresh.tick() => {
+                let since = Utc::now() - Duration::from_secs(config.active_secs);
+                let mut conn = redis_client.get_multiplexed_async_connection().await?;
+                let active = cache::active_pools(&mut conn, since, config.max_pools).await?;
+                let active_set: HashSet<_> = active.iter().copied().collect();
+                subscribed.retain(|addr, _| active_set.contains(addr));
+
+                let keys: Vec<_> = active
+                    .iter()
+                    .filter(|it| !subscribed.contains_key(it))
+                    .map(|it| DexPoolRecord::new_key(it.to_string()))
+                    .collect();
+                let records: Vec<_> = DexPoolRecord::mget(&mut conn, &keys)
+                    .await?
+                    .into_iter()
+                    .flatten()
+                    .filter(|it| TrackedPool::is_supported(it.dex))
+                    .collect();
+                drop(conn);
+
+                for pool in load_pools(rpc_client.as_ref(), records).await? {
+                    let stops = pool
+                        .accounts()
+                        .into_iter()
+                        .map(|(account, watched)| {
+                            subscribe_account(
+                                pubsub.clone(),
+                                pool.record.addr,
+                                account,
+                                watched,
+                                msg_tx.clone(),
+                            )
+                        })
+                        .collect();
+                    subscribed.insert(pool.record.addr, SubscribedPool { pool, _stops: stops });
+                }
+                info!("tracking state of {} pools", subscribed.len());
+            }
+            Some(msg) = msg_rx.recv() => {
+                match msg {
+                    AccountMsg::Updated { pool, account, slot, data } => {
+                        let Some(subscribed_pool) = subscribed.get_mut(&pool) else {
+                            continue;
+                        };
+                        match subscribed_pool.pool.apply(account, &data, slot) {
+                            Ok(updates) => events.extend(updates),
+                            Err(err) => warn!("apply {account:?} update of pool {pool} error: {err}"),
+                        }
+                    }
+                    // start again with a new connection
+                    AccountMsg::Closed { reason } => bail!("{reason}"),
+                }
+            }
+            _ = flush.tick() => {
+                if events.is_empty() {
+                    continue;
+                }
+                let mut conn = redis_client.get_multiplexed_async_connection().await?;
+                cache::rpush_dex_evts(&mut conn, None, &events).await?;
+                events.clear();
+            }
+        }
+    }
+}
+
+/// Read the current state of the pools and their vaults
+async fn load_pools(
+    rpc_client: &dyn RpcProvider,
+    records: Vec<DexPoolRecord>,
+) -> Result<Vec<TrackedPool>> {
+    let mut pools = vec![];
+    for records in records.chunks(MAX_ACCOUNTS_PER_RPC) {
+        let addrs: Vec<_> = records.iter().map(|it| it.addr).collect();
+        let accounts = rpc_client.get_multiple_accounts(&addrs).await?;
+        for (record, account) in records.iter().zip(accounts) {
+            let Some(account) = account else {
+                continue;
+            };
+            match TrackedPool::new(record.clone(), &account.data) {
+                Ok(pool) => pools.push(pool),
+                Err(err) => warn!("read {} pool {} error: {err}", record.dex, record.addr),
+            }
+        }
+    }
+
+    let vaults: Vec<_> = pools
+        .iter()
+        .enumerate()
+        .flat_map(|(idx, pool)| {
+            pool.accounts()
+                .into_iter()
+                .filter(|(_, watched)| *watched != WatchedAccount::Pool)
+                .map(move |(vault, watched)| (idx, vault, watched))
+        })
+        .collect();
+    for vaults in vaults.chunks(MAX_ACCOUNTS_PER_RPC) {
+        let addrs: Vec<_> = vaults.iter().map(|(_, vault, _)| *vault).collect();
+        let accounts = rpc_client.get_multiple_accounts(&addrs).await?;
+        for ((idx, vault, watched), account) in vaults.iter().zip(accounts) {
+            let Some(account) = account else {
+                continue;
+            };
+            if let Err(err) = pools[*idx].apply(*watched, &account.data, 0) {
+                warn!("read vault {vault} error: {err}");
+            }
+        }
+    }
+
+    Ok(pools)
+}
+
+/// Forward the updates of the account until the returned sender is dropped
+fn subscribe_account(
+    pubsub: Arc<PubsubClient>,
+    pool: Pubkey,
+    account: Pubkey,
+    watched: WatchedAccount,
+    msg_tx: mpsc::Sender<AccountMsg>,
+) -> oneshot::Sender<()> {
+    let (stop_tx, mut stop_rx) = oneshot::channel::<()>();
+    tokio::spawn(async move {
+        let config = RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(CommitmentConfig::confirmed()),
+            ..Default::default()
+        };
+        let (mut updates, unsubscribe) =
+            match pubsub.account_subscribe(&account, Some(config)).await {
+                Ok(it) => it,
+                Err(err) => {
+                    let reason = format!("subscribe account {account} error: {err}");
+                    let _ = msg_tx.send(AccountMsg::Closed { reason }).await;
+                    return;
+                }
+            };
+        loop {
+            tokio::select! {
+                update = updates.next() => {
+                    let Some(update) = update else {
+                        let reason = format!("subscription of account {account} ended");
+                        let _ = msg_tx.send(AccountMsg::Closed { reason }).await;
+                        break;
+                    };
+                    let Some(data) = update.value.data.decode() else {
+                        continue;
+                    };
+                    let msg = AccountMsg::Updated {
+                        pool,
+                        account: watched,
+                        slot: update.context.slot,
+                        data,
+                    };
+                    if msg_tx.send(msg).await.is_err() {
+                        break;
+                    }
+                }
+                _ = &mut stop_rx => break,
+            }
+        }
+        drop(updates);
+        unsubscribe().await;
+    });
+    stop_tx
+}
@@ -0,0 +1,253 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    cache::{BinPriceUpdateRecord, DexEvent, DexPoolRecord, PoolState, PoolStateUpdatedRecord},
+    common::{Dex, WSOL_MINT, utils},
+    meteora::dlmm::accounts::{LbPair, bin_price},
+    pumpamm::accounts::PumpAmmPool,
+    pumpfun::accounts::BondingCurveAccount,
+    raydium::accounts::AmmInfo,
+};
+
+/// Role of a subscribed account in its pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchedAccount {
+    Pool,
+    SolVault,
+    TokenVault,
+}
+
+/// State of a pool built from its pool and vault accounts.
+#[derive(Debug)]
+pub struct TrackedPool {
+    pub record: DexPoolRecord,
+    sol_vault: Option<Pubkey>,
+    token_vault: Option<Pubkey>,
+    /// meteora dlmm only
+    bin_step: Option<u16>,
+    state: PoolState,
+}
+
+impl TrackedPool {
+    pub fn is_supported(dex: Dex) -> bool {
+        !dex.state_event_kinds().is_empty()
+    }
+
+    /// Find the vaults and read the state of the pool from its account
+    pub fn new(record: DexPoolRecord, pool_data: &[u8]) -> Result<Self> {
+        let vaults = match record.dex {
+            Dex::MeteoraDlmm => {
+                let lb_pair = LbPair::from_account_data(pool_data)?;
+                vec![
+                    (lb_pair.token_x_mint, lb_pair.reserve_x),
+                    (lb_pair.token_y_mint, lb_pair.reserve_y),
+                ]
+            }
+            Dex::RaydiumAmm => {
+                let amm = AmmInfo::from_account_data(pool_data)?;
+                vec![
+                    (amm.coin_vault_mint, amm.coin_vault),
+                    (amm.pc_vault_mint, amm.pc_vault),
+                ]
+            }
+            Dex::PumpAmm => {
+                let pool = PumpAmmPool::from_account_data(pool_data)?;
+                vec![
+                    (pool.base_mint, pool.pool_base_token_account),
+                    (pool.quote_mint, pool.pool_quote_token_account),
+                ]
+            }
+            // reserves are kept in the bonding curve account
+            Dex::Pumpfun => vec![],
+            Dex::MeteoraDamm
+            | Dex::RaydiumClmm
+            | Dex::OrcaWhirlpool
+            | Dex::RaydiumCpmm
+            | Dex::MeteoraDbc
+            | Dex::Moonshot => {
+                return Err(anyhow!("{} pool state is not tracked", record.dex));
+            }
+        };
+
+        let mut pool = Self {
+            record,
+            sol_vault: None,
+            token_vault: None,
+            bin_step: None,
+            state: PoolState::default(),
+        };
+        for (mint, vault) in vaults {
+            if mint == WSOL_MINT {
+                pool.sol_vault = Some(vault);
+            } else {
+                pool.token_vault = Some(vault);
+            }
+        }
+        pool.apply(WatchedAccount::Pool, pool_data, 0)?;
+        Ok(pool)
+    }
+
+    /// Accounts to subscribe, the pool account is left out when it has no tracked state
+    pub fn accounts(&self) -> Vec<(Pubkey, WatchedAccount)> {
+        let mut accounts = vec![];
+        if self.record.dex != Dex::PumpAmm {
+            accounts.push((self.record.addr, WatchedAccount::Pool));
+        }
+        if let Some(vault) = self.sol_vault {
+            accounts.push((vault, WatchedAccount::SolVault));
+        }
+        if let Some(vault) = self.token_vault {
+            accounts.push((vault, WatchedAccount::TokenVault));
+        }
+        accounts
+    }
+
+    /// Apply new account data, returns `PoolStateUpdated` if the state changed
+    /// and `BinPriceUpdate` if the active bin of a dlmm pool changed
+    pub fn apply(
+        &mut self,
+        account: WatchedAccount,
+        data: &[u8],
+        slot: u64,
+    ) -> Result<Vec<DexEvent>> {
+        let prev = self.state;
+        match account {
+            WatchedAccount::Pool => self.apply_pool(data)?,
+            WatchedAccount::SolVault => {
+                self.state.sol_reserve = Some(token_account_amount(data)?);
+            }
+            WatchedAccount::TokenVault => {
+                self.state.token_reserve = Some(token_account_amount(data)?);
+            }
+        }
+        if self.state == prev {
+            return Ok(vec![]);
+        }
+
+        let ts = Utc::now();
+        let mut events = vec![DexEvent::PoolStateUpdated(PoolStateUpdatedRecord {
+            ts,
+            slot,
+            pool: self.record.addr,
+            dex: self.record.dex,
+            mint: self.record.token_mint(),
+            state: self.state,
+        })];
+        if self.state.active_id != prev.active_id
+            && let Some(bin_price) = self.bin_price(ts, slot)
+        {
+            events.push(DexEvent::BinPriceUpdate(bin_price));
+        }
+        Ok(events)
+    }
+
+    fn bin_price(&self, ts: DateTime<Utc>, slot: u64) -> Option<BinPriceUpdateRecord> {
+        let active_id = self.state.active_id?;
+        let DexPoolRecord {
+            mint_a: mint_x,
+            decimals_a: decimals_x,
+            decimals_b: decimals_y,
+            ..
+        } = self.record;
+        let price_y = bin_price(active_id, self.bin_step?, decimals_x, decimals_y);
+        let price_sol = if mint_x == WSOL_MINT {
+            1.0 / price_y
+        } else {
+            price_y
+        };
+        if !price_sol.is_normal() {
+            return None;
+        }
+
+        Some(BinPriceUpdateRecord {
+            ts,
+            slot,
+            lb_pair: self.record.addr,
+            mint: self.record.token_mint(),
+            active_id,
+            price_sol,
+        })
+    }
+
+    fn apply_pool(&mut self, data: &[u8]) -> Result<()> {
+        match self.record.dex {
+            Dex::MeteoraDlmm => {
+                let lb_pair = LbPair::from_account_data(data)?;
+                self.bin_step = Some(lb_pair.bin_step);
+                self.state.active_id = Some(lb_pair.active_id);
+                self.state.fee_bps = Some(lb_pair.base_fee_bps());
+            }
+            Dex::RaydiumAmm => {
+                let amm = AmmInfo::from_account_data(data)?;
+                self.state.fee_bps = Some(amm.swap_fee_bps());
+            }
+            Dex::Pumpfun => {
+                let curve = BondingCurveAccount::from_account_data(data)?;
+                self.state.sol_reserve = Some(curve.real_sol_reserves);
+                self.state.token_reserve = Some(curve.real_token_reserves);
+            }
+            Dex::PumpAmm
+            | Dex::MeteoraDamm
+            | Dex::RaydiumClmm
+            | Dex::OrcaWhirlpool
+            | Dex::RaydiumCpmm
+            | Dex::MeteoraDbc
+            | Dex::Moonshot => {}
+        }
+        Ok(())
+    }
+}
+
+fn token_account_amount(data: &[u8]) -> Result<u64> {
+    utils::token_account_amount(data).ok_or_else(|| anyhow!("invalid token account data"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curve_data(real_sol_reserves: u64) -> Vec<u8> {
+        let mut data = vec![0u8; 8];
+        for amt in [
+            1_073_000_000_000_000u64,
+            30_000_000_000,
+            793_100_000_000_000,
+        ] {
+            data.extend_from_slice(&amt.to_le_bytes());
+        }
+        data.extend_from_slice(&real_sol_reserves.to_le_bytes());
+        data.extend_from_slice(&1_000_000_000_000_000u64.to_le_bytes());
+        // complete, then the creator added to the account later
+        data.push(0);
+        data.extend_from_slice(&[7u8; 32]);
+        data
+    }
+
+    #[test]
+    fn test_tracked_pumpfun_curve() {
+        let record = DexPoolRecord::from_pumpfun_curve_and_mint(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            false,
+        );
+        let mut pool = TrackedPool::new(record, &curve_data(0)).unwrap();
+        assert_eq!(pool.accounts().len(), 1);
+
+        let events = pool
+            .apply(WatchedAccount::Pool, &curve_data(5_000_000_000), 42)
+            .unwrap();
+        let [DexEvent::PoolStateUpdated(update)] = events.as_slice() else {
+            panic!("expect one pool state update: {events:?}");
+        };
+        assert_eq!(update.slot, 42);
+        assert_eq!(update.state.sol_reserve, Some(5_000_000_000));
+        assert_eq!(update.state.token_reserve, Some(793_100_000_000_000));
+
+        let unchanged = pool
+            .apply(WatchedAccount::Pool, &curve_data(5_000_000_000), 43)
+            .unwrap();
+        assert!(unchanged.is_empty());
+    }
+}
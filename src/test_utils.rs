@@ -0,0 +1,38 @@
+use base64::{Engine, prelude::BASE64_STANDARD};
+
+/// Text encoding of the logs and of the instruction data a decoder takes
+#[derive(Debug, Clone, Copy)]
+pub enum Encoding {
+    Base58,
+    Base64,
+}
+
+impl Encoding {
+    fn encode(self, data: &[u8]) -> String {
+        match self {
+            Encoding::Base58 => bs58::encode(data).into_string(),
+            Encoding::Base64 => BASE64_STANDARD.encode(data),
+        }
+    }
+}
+
+/// The property test of the decoders: every truncation of `prefix`, the discriminator picked by
+/// `kind` and `fields`, and arbitrary `text`, decode to an event or an error, without panicking
+pub fn decodes_arbitrary_bytes<D: AsRef<[u8]>, T, E>(
+    encoding: Encoding,
+    prefix: &[u8],
+    discriminators: &[D],
+    kind: u8,
+    fields: &[u8],
+    text: &str,
+    decode: impl Fn(&str) -> Result<T, E>,
+) -> bool {
+    let mut data = prefix.to_vec();
+    data.extend(discriminators[kind as usize % discriminators.len()].as_ref());
+    data.extend(fields);
+    for len in 0..=data.len() {
+        let _ = decode(&encoding.encode(&data[..len]));
+    }
+    let _ = decode(text);
+    true
+}
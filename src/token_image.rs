@@ -0,0 +1,371 @@
+use std::{
+    fmt::Display,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{Result, bail};
+use redis::aio::MultiplexedConnection;
+use reqwest::{
+    StatusCode, Url,
+    dns::{Addrs, Name, Resolve, Resolving},
+    header, redirect,
+};
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use thiserror::Error;
+use tracing::warn;
+
+use crate::{
+    cache::{self, TokenImageRecord},
+    metaplex::accounts::TokenMetadata,
+    pda,
+    rpc_provider::RpcProvider,
+};
+
+pub const DEFAULT_IPFS_GATEWAY: &str = "https://ipfs.io/ipfs/";
+const MAX_METADATA_LEN: usize = 64 * 1024;
+pub const MAX_TOKEN_IMAGE_LEN: usize = 2 * 1024 * 1024;
+const IMAGE_TTL_SECS: i64 = 24 * 3600;
+/// tokens without a usable image are checked again after this
+const MISSING_IMAGE_TTL_SECS: i64 = 600;
+const MAX_REDIRECTS: usize = 5;
+
+#[derive(Debug, Deserialize)]
+struct OffchainMetadata {
+    image: Option<String>,
+}
+
+/// The token has no usable image, e.g. no metadata, a 404 or not an image. Unlike the transient
+/// errors, e.g. timeouts or 5xx of a gateway, it's cached.
+#[derive(Debug, Error)]
+#[error("{0}")]
+struct MissingImage(String);
+
+fn missing(err: impl Display) -> anyhow::Error {
+    MissingImage(err.to_string()).into()
+}
+
+/// The client errors but a timeout or a rate limit won't change when asked again
+fn is_missing_status(status: StatusCode) -> bool {
+    status.is_client_error()
+        && !matches!(
+            status,
+            StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS
+        )
+}
+
+/// Image of the token from the uri in its metadata account, cached in redis.
+/// None if the token has no image, or it's too large or not an image, which is cached for a
+/// while too. The transient errors are returned, so the next request tries again.
+pub async fn token_image(
+    rpc_client: &dyn RpcProvider,
+    http_client: &reqwest::Client,
+    conn: &mut MultiplexedConnection,
+    mint: &Pubkey,
+    ipfs_gateway: &str,
+) -> Result<Option<TokenImageRecord>> {
+    if let Some(record) = cache::get_token_image(conn, mint).await? {
+        return Ok((!record.content_type.is_empty()).then_some(record));
+    }
+
+    match fetch_token_image(rpc_client, http_client, mint, ipfs_gateway).await {
+        Ok(record) => {
+            cache::save_token_image(conn, mint, &record, IMAGE_TTL_SECS).await?;
+            Ok(Some(record))
+        }
+        Err(err) if err.is::<MissingImage>() => {
+            warn!("token {mint} has no usable image: {err}");
+            let missing = TokenImageRecord {
+                content_type: String::new(),
+                data: vec![],
+            };
+            cache::save_token_image(conn, mint, &missing, MISSING_IMAGE_TTL_SECS).await?;
+            Ok(None)
+        }
+        Err(err) => Err(err.context(format!("fetch image of token {mint}"))),
+    }
+}
+
+async fn fetch_token_image(
+    rpc_client: &dyn RpcProvider,
+    http_client: &reqwest::Client,
+    mint: &Pubkey,
+    ipfs_gateway: &str,
+) -> Result<TokenImageRecord> {
+    let metadata_addr = pda::metaplex_metadata(mint);
+    let account = rpc_client
+        .get_multiple_accounts(std::slice::from_ref(&metadata_addr))
+        .await?
+        .pop()
+        .flatten()
+        .ok_or_else(|| missing(format!("metadata {metadata_addr} not found")))?;
+    let metadata = TokenMetadata::from_account_data(&account.data).map_err(missing)?;
+    let url = gateway_url(&metadata.uri, ipfs_gateway).map_err(missing)?;
+    check_ip_host(&url, gateway_host(ipfs_gateway).as_deref()).map_err(missing)?;
+    let (_, body) = fetch_limited(http_client, url, MAX_METADATA_LEN).await?;
+    let offchain: OffchainMetadata = serde_json::from_slice(&body).map_err(missing)?;
+    let image = offchain
+        .image
+        .ok_or_else(|| missing(format!("no image in metadata {}", metadata.uri)))?;
+
+    let url = gateway_url(&image, ipfs_gateway).map_err(missing)?;
+    check_ip_host(&url, gateway_host(ipfs_gateway).as_deref()).map_err(missing)?;
+    let (content_type, data) = fetch_limited(http_client, url, MAX_TOKEN_IMAGE_LEN).await?;
+    let content_type = content_type.unwrap_or_default();
+    // svg can carry scripts
+    if !content_type.starts_with("image/") || content_type.starts_with("image/svg") {
+        return Err(missing(format!(
+            "{image} is not a supported image: {content_type:?}"
+        )));
+    }
+    Ok(TokenImageRecord { content_type, data })
+}
+
+fn gateway_host(ipfs_gateway: &str) -> Option<String> {
+    Url::parse(ipfs_gateway).ok()?.host_str().map(Into::into)
+}
+
+/// http(s) url of the uri, `ipfs://` uris are served by the gateway
+fn gateway_url(uri: &str, ipfs_gateway: &str) -> Result<Url> {
+    let uri = uri.trim();
+    let url = match uri.strip_prefix("ipfs://") {
+        Some(path) => {
+            let path = path.strip_prefix("ipfs/").unwrap_or(path);
+            Url::parse(ipfs_gateway)?.join(path)?
+        }
+        None => Url::parse(uri)?,
+    };
+    if !matches!(url.scheme(), "http" | "https") {
+        bail!("unsupported uri: {uri}");
+    }
+    Ok(url)
+}
+
+/// The token uris are set by anyone, so the client for them connects to public addresses only,
+/// through redirects too. The host of the ipfs gateway is configured, it may be a local node.
+pub fn http_client(ipfs_gateway: &str) -> Result<reqwest::Client> {
+    let gateway_host: Option<Arc<str>> = Url::parse(ipfs_gateway)?.host_str().map(Into::into);
+    let redirect_host = gateway_host.clone();
+    let redirect_policy = redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else if let Err(err) = check_ip_host(attempt.url(), redirect_host.as_deref()) {
+            attempt.error(err)
+        } else {
+            attempt.follow()
+        }
+    });
+    let client = reqwest::ClientBuilder::new()
+        .connect_timeout(Duration::from_secs(2))
+        .timeout(Duration::from_secs(10))
+        .redirect(redirect_policy)
+        .dns_resolver(Arc::new(PublicResolver { gateway_host }))
+        .build()?;
+    Ok(client)
+}
+
+/// Resolves the host names to their public addresses only
+struct PublicResolver {
+    gateway_host: Option<Arc<str>>,
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let trusted = self.gateway_host.as_deref() == Some(name.as_str());
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| trusted || is_public_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// The hosts given as ip addresses are not resolved, they must be public ones
+fn check_ip_host(url: &Url, gateway_host: Option<&str>) -> Result<()> {
+    let ip = match url.host() {
+        Some(url::Host::Ipv4(ip)) => IpAddr::V4(ip),
+        Some(url::Host::Ipv6(ip)) => IpAddr::V6(ip),
+        _ => return Ok(()),
+    };
+    if !is_public_ip(ip) && url.host_str() != gateway_host {
+        bail!("{url} is not a public address");
+    }
+    Ok(())
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // shared address space
+                || (a == 100 && (64..128).contains(&b))
+                // reserved, "this network"
+                || a >= 240
+                || a == 0)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(ip));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                // unique local
+                || (first & 0xfe00) == 0xfc00
+                // link local
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// GET the url, failing when the body is longer than `max_len`. A client error status or a
+/// body too long is a `MissingImage`.
+async fn fetch_limited(
+    http_client: &reqwest::Client,
+    url: Url,
+    max_len: usize,
+) -> Result<(Option<String>, Vec<u8>)> {
+    let mut resp = http_client.get(url.clone()).send().await?;
+    if is_missing_status(resp.status()) {
+        return Err(missing(format!("{url} returned {}", resp.status())));
+    }
+    resp = resp.error_for_status()?;
+    if resp
+        .content_length()
+        .is_some_and(|len| len > max_len as u64)
+    {
+        return Err(missing(format!("{url} is larger than {max_len} bytes")));
+    }
+    let content_type = resp
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|it| it.to_str().ok())
+        .map(|it| it.to_ascii_lowercase());
+
+    let mut body = vec![];
+    while let Some(chunk) = resp.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() > max_len {
+            return Err(missing(format!("{url} is larger than {max_len} bytes")));
+        }
+    }
+    Ok((content_type, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::account::Account;
+
+    use super::*;
+    use crate::rpc_provider::MockRpcProvider;
+
+    #[test]
+    fn test_gateway_url() {
+        let gateway = DEFAULT_IPFS_GATEWAY;
+        assert_eq!(
+            gateway_url("ipfs://Qmabc", gateway).unwrap().as_str(),
+            "https://ipfs.io/ipfs/Qmabc"
+        );
+        assert_eq!(
+            gateway_url("ipfs://ipfs/Qmabc", gateway).unwrap().as_str(),
+            "https://ipfs.io/ipfs/Qmabc"
+        );
+        assert_eq!(
+            gateway_url(" https://arweave.net/x ", gateway)
+                .unwrap()
+                .as_str(),
+            "https://arweave.net/x"
+        );
+        assert!(gateway_url("file:///etc/passwd", gateway).is_err());
+        assert!(gateway_url("", gateway).is_err());
+    }
+
+    #[test]
+    fn test_public_ip() {
+        let public = |ip: &str| is_public_ip(ip.parse().unwrap());
+        assert!(public("1.1.1.1"));
+        assert!(public("2606:4700::1111"));
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!public(ip), "{ip}");
+        }
+
+        let gateway = Some("127.0.0.1");
+        let url = |url: &str| Url::parse(url).unwrap();
+        assert!(check_ip_host(&url("http://169.254.169.254/latest"), gateway).is_err());
+        assert!(check_ip_host(&url("http://[::1]:8080/"), gateway).is_err());
+        assert!(check_ip_host(&url("http://127.0.0.1:8080/ipfs/x"), gateway).is_ok());
+        assert!(check_ip_host(&url("https://arweave.net/x"), None).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_missing_image() {
+        for status in [
+            StatusCode::NOT_FOUND,
+            StatusCode::GONE,
+            StatusCode::FORBIDDEN,
+        ] {
+            assert!(is_missing_status(status), "{status}");
+        }
+        for status in [
+            StatusCode::REQUEST_TIMEOUT,
+            StatusCode::TOO_MANY_REQUESTS,
+            StatusCode::BAD_GATEWAY,
+            StatusCode::SERVICE_UNAVAILABLE,
+        ] {
+            assert!(!is_missing_status(status), "{status}");
+        }
+
+        let metadata = |uri: &str| {
+            let mut data = vec![4u8];
+            data.extend_from_slice(&[0; 64]);
+            for text in ["Token", "TKN", uri] {
+                data.extend_from_slice(&(text.len() as u32).to_le_bytes());
+                data.extend_from_slice(text.as_bytes());
+            }
+            data.extend_from_slice(&[0u8; 16]);
+            Account {
+                data,
+                ..Default::default()
+            }
+        };
+        let (no_metadata, local_uri) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let rpc_client = MockRpcProvider::default().with_account(
+            pda::metaplex_metadata(&local_uri),
+            metadata("http://169.254.169.254/latest"),
+        );
+        let http_client = http_client(DEFAULT_IPFS_GATEWAY).unwrap();
+        for mint in [no_metadata, local_uri] {
+            let err = fetch_token_image(&rpc_client, &http_client, &mint, DEFAULT_IPFS_GATEWAY)
+                .await
+                .unwrap_err();
+            assert!(err.is::<MissingImage>(), "{err}");
+        }
+    }
+}
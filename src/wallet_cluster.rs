@@ -0,0 +1,181 @@
+//! Clusters of wallets likely run by one group: wallets buying the same new tokens within
+//! seconds of each other, and wallets sent their first SOL by the same funder.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Result;
+use chrono::Utc;
+use solana_sdk::pubkey::Pubkey;
+use tracing::info;
+
+use crate::{cache, config::WalletClustersConfig};
+
+/// Mints whose early buyers are read per round trip
+const MINTS_CHUNK: usize = 500;
+/// Wallets whose first funders are read per round trip
+const FUNDERS_CHUNK: usize = 1_000;
+
+/// The wallet pairs of the early buyers of a mint which bought within `co_trade_secs` of each
+/// other, the lower wallet first
+fn co_trade_pairs(buyers: &[(Pubkey, i64)], co_trade_secs: i64) -> Vec<(Pubkey, Pubkey)> {
+    let mut buyers = buyers.to_vec();
+    buyers.sort_by_key(|(_, ts)| *ts);
+    let mut pairs = vec![];
+    for (i, (wallet, ts)) in buyers.iter().enumerate() {
+        for (other, other_ts) in &buyers[i + 1..] {
+            if other_ts - ts > co_trade_secs {
+                break;
+            }
+            pairs.push((*wallet.min(other), *wallet.max(other)));
+        }
+    }
+    pairs
+}
+
+/// Union find over the linked wallets
+#[derive(Default)]
+struct Clusters {
+    parents: HashMap<Pubkey, Pubkey>,
+}
+
+impl Clusters {
+    fn root(&mut self, wallet: Pubkey) -> Pubkey {
+        let mut root = wallet;
+        while let Some(parent) = self.parents.get(&root).copied() {
+            if parent == root {
+                break;
+            }
+            root = parent;
+        }
+        // path compression
+        let mut node = wallet;
+        while node != root {
+            let parent = self.parents.insert(node, root).unwrap_or(root);
+            node = parent;
+        }
+        root
+    }
+
+    fn link(&mut self, a: Pubkey, b: Pubkey) {
+        let (a, b) = (self.root(a), self.root(b));
+        if a != b {
+            // the lower wallet is the root, so the cluster id doesn't depend on the link order
+            self.parents.insert(a.max(b), a.min(b));
+            self.parents.insert(a.min(b), a.min(b));
+        }
+    }
+
+    /// The cluster id of each linked wallet, the lowest wallet of its cluster
+    fn into_cluster_ids(mut self) -> HashMap<Pubkey, String> {
+        let wallets: Vec<_> = self.parents.keys().copied().collect();
+        wallets
+            .into_iter()
+            .map(|wallet| (wallet, self.root(wallet).to_string()))
+            .collect()
+    }
+}
+
+/// Cluster the early buyers of the recent mints every `interval_secs`, replacing the saved
+/// clusters each round.
+pub async fn start(redis_client: Arc<redis::Client>, config: WalletClustersConfig) -> Result<()> {
+    info!(
+        "start wallet clustering of the first {} buyers of the mints of the last {} seconds, every {} seconds........",
+        config.early_buyers, config.lookback_secs, config.interval_secs
+    );
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_secs));
+    loop {
+        interval.tick().await;
+        let since = Utc::now() - Duration::from_secs(config.lookback_secs);
+        let mut conn = redis_client.get_multiplexed_async_connection().await?;
+        let mints = cache::cluster_mints(&mut conn, since).await?;
+
+        let mut co_trades: HashMap<(Pubkey, Pubkey), usize> = HashMap::new();
+        let mut wallets = HashSet::new();
+        for chunk in mints.chunks(MINTS_CHUNK) {
+            for buyers in cache::early_buyers(&mut conn, chunk).await? {
+                wallets.extend(buyers.iter().map(|(wallet, _)| *wallet));
+                // a pair counts once per mint
+                let pairs: HashSet<_> = co_trade_pairs(&buyers, config.co_trade_secs)
+                    .into_iter()
+                    .collect();
+                for pair in pairs {
+                    *co_trades.entry(pair).or_default() += 1;
+                }
+            }
+        }
+
+        let mut clusters = Clusters::default();
+        for ((a, b), count) in co_trades {
+            if count >= config.min_co_trades {
+                clusters.link(a, b);
+            }
+        }
+        let wallets: Vec<_> = wallets.into_iter().collect();
+        let mut funded: HashMap<Pubkey, Vec<Pubkey>> = HashMap::new();
+        for chunk in wallets.chunks(FUNDERS_CHUNK) {
+            let funders = cache::get_first_funders(&mut conn, chunk).await?;
+            for (wallet, funder) in chunk.iter().zip(funders) {
+                if let Some(funder) = funder {
+                    funded.entry(funder).or_default().push(*wallet);
+                }
+            }
+        }
+        for funded_wallets in funded.values() {
+            // exchanges and faucets fund wallets of everyone
+            if funded_wallets.len() > config.max_funded_wallets {
+                continue;
+            }
+            for pair in funded_wallets.windows(2) {
+                clusters.link(pair[0], pair[1]);
+            }
+        }
+
+        let cluster_ids = clusters.into_cluster_ids();
+        cache::save_wallet_clusters(&mut conn, &cluster_ids).await?;
+        let clusters_len = cluster_ids.values().collect::<HashSet<_>>().len();
+        info!(
+            "{} wallets in {clusters_len} clusters, from the early buyers of {} mints",
+            cluster_ids.len(),
+            mints.len()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wallet_clusters() {
+        let wallets: Vec<_> = (0..5).map(|_| Pubkey::new_unique()).collect();
+        let buyers = vec![(wallets[2], 7), (wallets[0], 0), (wallets[1], 3)];
+        let pairs: HashSet<_> = co_trade_pairs(&buyers, 5).into_iter().collect();
+        let expected = |a: Pubkey, b: Pubkey| (a.min(b), a.max(b));
+        assert_eq!(
+            pairs,
+            HashSet::from([
+                expected(wallets[0], wallets[1]),
+                expected(wallets[1], wallets[2])
+            ])
+        );
+
+        let mut clusters = Clusters::default();
+        clusters.link(wallets[3], wallets[1]);
+        clusters.link(wallets[0], wallets[3]);
+        clusters.link(wallets[2], wallets[4]);
+        let ids = clusters.into_cluster_ids();
+        let lowest = [wallets[0], wallets[1], wallets[3]]
+            .into_iter()
+            .min()
+            .unwrap();
+        for wallet in [wallets[0], wallets[1], wallets[3]] {
+            assert_eq!(ids[&wallet], lowest.to_string());
+        }
+        assert_eq!(ids[&wallets[2]], wallets[2].min(wallets[4]).to_string());
+        assert_ne!(ids[&wallets[2]], ids[&wallets[0]]);
+    }
+}
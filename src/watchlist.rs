@@ -0,0 +1,163 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use anyhow::Result;
+use solana_sdk::pubkey::Pubkey;
+use tracing::info;
+
+use crate::cache::{self, DexEvent, Watchlist};
+
+/// Prefix of the websocket channel of a watchlist and of its references in rules
+pub const WATCHLIST_PREFIX: &str = "watchlist:";
+/// the watchlists changed by other processes are seen after this long at most
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Websocket channel of the events of the mints and wallets of the watchlist
+pub fn watchlist_channel(name: &str) -> String {
+    format!("{WATCHLIST_PREFIX}{name}")
+}
+
+/// Mints and wallets of an event which watchlists are matched against
+fn event_keys(evt: &DexEvent) -> Vec<Pubkey> {
+    match evt {
+        DexEvent::Trade(it) => vec![it.mint, it.trader],
+        DexEvent::PoolCreated(it) => vec![it.mint_a, it.mint_b, it.creator],
+        DexEvent::PumpfunComplete(it) => vec![it.mint, it.user],
+        DexEvent::MeteoraDbcComplete(it) => vec![it.mint, it.user],
+        DexEvent::LiquidityChange(it) => vec![it.mint, it.provider],
+        DexEvent::PumpfunSetParams(_) => vec![],
+        DexEvent::MigrationCompleted(it) => vec![it.mint],
+        DexEvent::LiquidityLocked(it) => vec![it.mint],
+        DexEvent::PoolDrained(it) => vec![it.mint],
+        DexEvent::FailedSwapAttempt(it) => vec![it.mint, it.trader],
+        DexEvent::MintInactive(it) => vec![it.mint],
+        DexEvent::PoolStateUpdated(it) => vec![it.mint],
+        DexEvent::BinPriceUpdate(it) => vec![it.mint],
+        DexEvent::PressureUpdate(it) => vec![it.mint],
+        DexEvent::FollowedWalletTrade(it) => vec![it.trade.mint, it.trade.trader],
+    }
+}
+
+/// In-process copy of the watchlists kept in redis, mints and wallets together by name
+#[derive(Debug, Default)]
+pub struct Watchlists {
+    lists: RwLock<HashMap<String, HashSet<Pubkey>>>,
+}
+
+fn keys(watchlist: &Watchlist) -> HashSet<Pubkey> {
+    watchlist
+        .mints
+        .iter()
+        .chain(&watchlist.wallets)
+        .copied()
+        .collect()
+}
+
+impl Watchlists {
+    pub fn replace_all(&self, watchlists: &[Watchlist]) {
+        let lists = watchlists
+            .iter()
+            .map(|it| (it.name.clone(), keys(it)))
+            .collect();
+        *self.lists.write().unwrap_or_else(|it| it.into_inner()) = lists;
+    }
+
+    pub fn set(&self, watchlist: &Watchlist) {
+        self.lists
+            .write()
+            .unwrap_or_else(|it| it.into_inner())
+            .insert(watchlist.name.clone(), keys(watchlist));
+    }
+
+    pub fn remove(&self, name: &str) {
+        self.lists
+            .write()
+            .unwrap_or_else(|it| it.into_inner())
+            .remove(name);
+    }
+
+    /// false if the watchlist doesn't exist
+    pub fn contains(&self, name: &str, key: &Pubkey) -> bool {
+        self.lists
+            .read()
+            .unwrap_or_else(|it| it.into_inner())
+            .get(name)
+            .is_some_and(|it| it.contains(key))
+    }
+
+    /// Names of the watchlists holding a mint or wallet of the event
+    pub fn matching(&self, evt: &DexEvent) -> Vec<String> {
+        let lists = self.lists.read().unwrap_or_else(|it| it.into_inner());
+        if lists.is_empty() {
+            return vec![];
+        }
+        let keys = event_keys(evt);
+        lists
+            .iter()
+            .filter(|(_, list)| keys.iter().any(|it| list.contains(it)))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+}
+
+/// Reload the watchlists from redis, so the changes made through other processes apply here.
+pub async fn start_refresh(
+    redis_client: Arc<redis::Client>,
+    watchlists: Arc<Watchlists>,
+) -> Result<()> {
+    info!("start watchlist refresh every {REFRESH_INTERVAL:?}........");
+    let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+    loop {
+        interval.tick().await;
+        let mut conn = redis_client.get_multiplexed_async_connection().await?;
+        let lists = cache::list_watchlists(&mut conn).await?;
+        watchlists.replace_all(&lists);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::cache::MintInactiveRecord;
+
+    use super::*;
+
+    #[test]
+    fn test_matching() {
+        let (mint, wallet) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let watchlists = Watchlists::default();
+        watchlists.replace_all(&[
+            Watchlist {
+                name: "snipes".to_string(),
+                mints: vec![mint],
+                wallets: vec![],
+                updated_at: Utc::now(),
+            },
+            Watchlist {
+                name: "whales".to_string(),
+                mints: vec![],
+                wallets: vec![wallet],
+                updated_at: Utc::now(),
+            },
+        ]);
+        let inactive = |mint| {
+            DexEvent::MintInactive(MintInactiveRecord {
+                mint,
+                last_trade_ts: Utc::now(),
+            })
+        };
+
+        assert_eq!(watchlists.matching(&inactive(mint)), vec!["snipes"]);
+        assert_eq!(watchlists.matching(&inactive(wallet)), vec!["whales"]);
+        assert!(watchlists.contains("whales", &wallet));
+        assert!(!watchlists.contains("whales", &mint));
+
+        watchlists.remove("snipes");
+        assert!(watchlists.matching(&inactive(mint)).is_empty());
+        assert!(!watchlists.contains("missing", &mint));
+    }
+}
@@ -0,0 +1,380 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{OptionalFromRequestParts, Request, State},
+    http::{HeaderMap, header, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+use http_body::{Frame, SizeHint};
+use serde::{Deserialize, Serialize};
+use solana_sdk::signature::{Keypair, Signer};
+use strum::Display;
+use tracing::warn;
+
+use crate::{
+    cache::{self, ApiKeyRecord, ApiUsage},
+    config::AuthConfig,
+    web::{ApiQuota, UsageMeter, WebAppContext, WebAppError},
+};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+/// for websocket clients which can't set headers
+const API_KEY_QUERY: &str = "api_key";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ApiScope {
+    /// push transactions into the pipeline
+    Ingest,
+    /// query endpoints and websocket
+    Read,
+    /// change the saved state of the endpoints, e.g. the watchlists and the follows
+    Write,
+    /// manage api keys, allows every other scope too
+    Admin,
+}
+
+impl ApiScope {
+    pub fn allowed_by(self, scopes: &[ApiScope]) -> bool {
+        scopes.contains(&self) || scopes.contains(&ApiScope::Admin)
+    }
+}
+
+/// Keys of the config file, the rest are kept in redis by the admin endpoints.
+/// Keys are only known by their hashes.
+#[derive(Debug, Default)]
+pub struct ApiKeys {
+    enabled: bool,
+    config_keys: HashMap<String, ApiKeyRecord>,
+}
+
+impl ApiKeys {
+    pub fn new(config: Option<&AuthConfig>) -> Self {
+        let Some(config) = config else {
+            warn!("no auth in config, every endpoint is open");
+            return Self::default();
+        };
+        let config_keys = config
+            .keys
+            .iter()
+            .map(|it| {
+                let record = ApiKeyRecord {
+                    name: it.name.clone(),
+                    scopes: it.scopes.clone(),
+                    quota: it.quota,
+                };
+                (hash_api_key(&it.key), record)
+            })
+            .collect();
+        Self {
+            enabled: true,
+            config_keys,
+        }
+    }
+
+    pub fn config_keys(&self) -> impl Iterator<Item = &ApiKeyRecord> {
+        self.config_keys.values()
+    }
+}
+
+/// The api key of an authorized request, a request extension for metering websocket messages
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    pub name: String,
+    pub quota: ApiQuota,
+}
+
+/// `Option<ApiClient>` in a handler, none when auth is off
+impl<S: Send + Sync> OptionalFromRequestParts<S> for ApiClient {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        Ok(parts.extensions.get::<ApiClient>().cloned())
+    }
+}
+
+pub fn hash_api_key(key: &str) -> String {
+    solana_sdk::hash::hash(key.as_bytes()).to_string()
+}
+
+pub fn new_api_key() -> String {
+    format!("sdh_{}", Keypair::new().pubkey())
+}
+
+/// `x-api-key` header, `Authorization` header with or without `Bearer`, or `api_key` query param
+fn request_api_key(headers: &HeaderMap, query: Option<&str>) -> Option<String> {
+    if let Some(key) = headers.get(API_KEY_HEADER).and_then(|it| it.to_str().ok()) {
+        return Some(key.to_string());
+    }
+    if let Some(auth) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|it| it.to_str().ok())
+    {
+        return Some(
+            auth.strip_prefix("Bearer ")
+                .unwrap_or(auth)
+                .trim()
+                .to_string(),
+        );
+    }
+    url::form_urlencoded::parse(query?.as_bytes())
+        .find(|(name, _)| name == API_KEY_QUERY)
+        .map(|(_, key)| key.into_owned())
+}
+
+async fn authorize(
+    context: WebAppContext,
+    scope: ApiScope,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, WebAppError> {
+    let api_keys = &context.api_keys;
+    if !api_keys.enabled {
+        return Ok(next.run(req).await);
+    }
+
+    let key = request_api_key(req.headers(), req.uri().query())
+        .ok_or_else(|| WebAppError::unauth("api key required"))?;
+    let key_hash = hash_api_key(&key);
+    let record = match api_keys.config_keys.get(&key_hash) {
+        Some(record) => Some(record.clone()),
+        None => {
+            let mut conn = context
+                .redis_client
+                .get_multiplexed_async_connection()
+                .await?;
+            cache::get_api_key(&mut conn, &key_hash).await?
+        }
+    };
+    let record = record.ok_or_else(|| WebAppError::unauth("invalid api key"))?;
+    if !scope.allowed_by(&record.scopes) {
+        return Err(WebAppError::forbidden(format!(
+            "api key {} has no {scope} scope",
+            record.name
+        )));
+    }
+
+    let meter = &context.usage_meter;
+    if let Some(limit) = record.quota.exceeded_by(&meter.today(&record.name)) {
+        return Err(WebAppError::too_many_requests(format!(
+            "api key {} exceeded its quota of {limit}",
+            record.name
+        )));
+    }
+    req.extensions_mut().insert(ApiClient {
+        name: record.name.clone(),
+        quota: record.quota,
+    });
+    let resp = next.run(req).await;
+    let usage = ApiUsage {
+        requests: 1,
+        ..ApiUsage::default()
+    };
+    meter.record(&record.name, usage);
+    Ok(resp.map(|body| Body::new(MeteredBody::new(body, meter.clone(), record.name))))
+}
+
+/// Response body counting its bytes to the api key as they're sent, a streamed body has no
+/// size up front
+struct MeteredBody {
+    inner: Body,
+    meter: Arc<UsageMeter>,
+    name: String,
+}
+
+impl MeteredBody {
+    fn new(inner: Body, meter: Arc<UsageMeter>, name: String) -> Self {
+        Self { inner, meter, name }
+    }
+}
+
+impl HttpBody for MeteredBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, axum::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll
+            && let Some(data) = frame.data_ref()
+        {
+            let usage = ApiUsage {
+                bytes: data.len() as u64,
+                ..ApiUsage::default()
+            };
+            self.meter.record(&self.name, usage);
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+pub async fn require_ingest(
+    State(context): State<WebAppContext>,
+    req: Request,
+    next: Next,
+) -> Result<Response, WebAppError> {
+    authorize(context, ApiScope::Ingest, req, next).await
+}
+
+pub async fn require_read(
+    State(context): State<WebAppContext>,
+    req: Request,
+    next: Next,
+) -> Result<Response, WebAppError> {
+    authorize(context, ApiScope::Read, req, next).await
+}
+
+pub async fn require_write(
+    State(context): State<WebAppContext>,
+    req: Request,
+    next: Next,
+) -> Result<Response, WebAppError> {
+    authorize(context, ApiScope::Write, req, next).await
+}
+
+pub async fn require_admin(
+    State(context): State<WebAppContext>,
+    req: Request,
+    next: Next,
+) -> Result<Response, WebAppError> {
+    authorize(context, ApiScope::Admin, req, next).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_metered_body() {
+        let meter = Arc::new(UsageMeter::default());
+        let body = MeteredBody::new(Body::from("hello"), meter.clone(), "k1".into());
+        assert_eq!(body.size_hint().exact(), Some(5));
+        axum::body::to_bytes(Body::new(body), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(meter.today("k1").bytes, 5);
+
+        // a stream has no exact size, its chunks are counted as they're read
+        let chunks = futures::stream::iter(["ab", "cde"].map(Ok::<_, Infallible>));
+        let body = MeteredBody::new(Body::from_stream(chunks), meter.clone(), "k2".into());
+        assert_eq!(body.size_hint().exact(), None);
+        axum::body::to_bytes(Body::new(body), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(meter.today("k2").bytes, 5);
+        assert_eq!(meter.today("k1").bytes, 5);
+    }
+
+    fn context(keys: serde_json::Value) -> WebAppContext {
+        let auth: AuthConfig = serde_json::from_value(serde_json::json!({ "keys": keys })).unwrap();
+        WebAppContext {
+            // the config keys are checked without redis
+            redis_client: Arc::new(redis::Client::open("redis://127.0.0.1:1").unwrap()),
+            sol_rpc_client: Arc::new(crate::rpc_provider::MockRpcProvider::default()),
+            ws_hub: Arc::new(crate::web::WsHub::default()),
+            redis_health: Arc::default(),
+            http_client: reqwest::Client::new(),
+            ipfs_gateway: "http://127.0.0.1:1".into(),
+            api_keys: Arc::new(ApiKeys::new(Some(&auth))),
+            usage_meter: Arc::default(),
+            disabled_dexes: Arc::new([]),
+            pool_state_tracked: false,
+            watchlists: Arc::default(),
+            avg_price_windows: Arc::new([]),
+            failed_swaps: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_authorize() {
+        use axum::{Router, middleware, routing::get};
+
+        let context = context(serde_json::json!([
+            {"name": "reader", "key": "read-key-0000000", "scopes": ["read"]},
+            {"name": "capped", "key": "capped-key-00000", "scopes": ["read"],
+                "quota": {"requests_per_day": 1}},
+        ]));
+        let app = Router::new()
+            .route("/read", get(|| async { "read" }))
+            .route_layer(middleware::from_fn_with_state(
+                context.clone(),
+                require_read,
+            ))
+            .merge(
+                Router::new()
+                    .route("/write", get(|| async { "write" }))
+                    .route_layer(middleware::from_fn_with_state(
+                        context.clone(),
+                        require_write,
+                    )),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = reqwest::Client::new();
+        let status = |path: &str, key: Option<&str>| {
+            let mut req = client.get(format!("http://{addr}{path}"));
+            if let Some(key) = key {
+                req = req.header(API_KEY_HEADER, key);
+            }
+            async move {
+                let resp = req.send().await.unwrap();
+                let status = resp.status().as_u16();
+                // the body is metered once it's sent
+                resp.bytes().await.unwrap();
+                status
+            }
+        };
+        assert_eq!(status("/read", None).await, 401);
+        assert_eq!(status("/read", Some("read-key-0000000")).await, 200);
+        assert_eq!(status("/write", Some("read-key-0000000")).await, 403);
+        assert_eq!(status("/read", Some("capped-key-00000")).await, 200);
+        assert_eq!(status("/read", Some("capped-key-00000")).await, 429);
+
+        let usage = context.usage_meter.today("reader");
+        assert_eq!((usage.requests, usage.bytes), (1, 4));
+    }
+
+    #[test]
+    fn test_api_key_scopes() {
+        assert!(ApiScope::Read.allowed_by(&[ApiScope::Read]));
+        assert!(!ApiScope::Ingest.allowed_by(&[ApiScope::Read]));
+        assert!(ApiScope::Ingest.allowed_by(&[ApiScope::Admin]));
+        assert!(!ApiScope::Write.allowed_by(&[ApiScope::Read]));
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            request_api_key(&headers, Some("a=1&api_key=k1")),
+            Some("k1".to_string())
+        );
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer k2"));
+        assert_eq!(request_api_key(&headers, None), Some("k2".to_string()));
+        headers.insert(API_KEY_HEADER, HeaderValue::from_static("k3"));
+        assert_eq!(request_api_key(&headers, None), Some("k3".to_string()));
+    }
+}
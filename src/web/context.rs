@@ -4,29 +4,82 @@ use anyhow::Result;
 use solana_rpc_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::commitment_config::CommitmentConfig;
 
-use crate::config::AppConfig;
+use crate::{
+    common::Dex,
+    config::AppConfig,
+    redis_monitor::RedisHealth,
+    rpc_provider::{MockRpcProvider, RpcProvider},
+    token_image::{self, DEFAULT_IPFS_GATEWAY},
+    watchlist::Watchlists,
+};
+
+use super::{ApiKeys, UsageMeter, WsHub};
 
 #[derive(Clone)]
 pub struct WebAppContext {
     pub redis_client: Arc<redis::Client>,
-    pub sol_rpc_client: Arc<RpcClient>,
+    pub sol_rpc_client: Arc<dyn RpcProvider>,
+    pub ws_hub: Arc<WsHub>,
+    pub redis_health: Arc<RedisHealth>,
+    /// for fetching token metadata and images
+    pub http_client: reqwest::Client,
+    pub ipfs_gateway: Arc<str>,
+    pub api_keys: Arc<ApiKeys>,
+    pub usage_meter: Arc<UsageMeter>,
+    pub disabled_dexes: Arc<[Dex]>,
+    /// the pool state tracker is configured and built in
+    pub pool_state_tracked: bool,
+    pub watchlists: Arc<Watchlists>,
+    /// windows of the averages in `/token_stats/{mint}`, empty when `avg_price` is off
+    pub avg_price_windows: Arc<[i64]>,
+    /// the failed raw helius transactions are queued for `FailedSwapAttempt`
+    pub failed_swaps: bool,
 }
 
 impl WebAppContext {
     pub async fn init(config: &AppConfig) -> Result<Self> {
-        let sol_rpc_client = RpcClient::new_with_timeout_and_commitment(
-            config.sol_rpc_url.clone(),
-            Duration::from_secs(5),
-            CommitmentConfig::processed(),
-        );
-        let sol_rpc_client = Arc::new(sol_rpc_client);
+        let sol_rpc_client: Arc<dyn RpcProvider> = if config.offline {
+            Arc::new(MockRpcProvider::default())
+        } else {
+            Arc::new(RpcClient::new_with_timeout_and_commitment(
+                config.sol_rpc_url.clone(),
+                Duration::from_secs(5),
+                CommitmentConfig::processed(),
+            ))
+        };
 
         let redis_client = redis::Client::open(config.redis_url.as_str())?;
         let redis_client = Arc::new(redis_client);
 
+        let ipfs_gateway: Arc<str> = config
+            .ipfs_gateway
+            .as_deref()
+            .unwrap_or(DEFAULT_IPFS_GATEWAY)
+            .into();
+        let http_client = token_image::http_client(&ipfs_gateway)?;
+
         Ok(Self {
             redis_client,
             sol_rpc_client,
+            ws_hub: Arc::new(WsHub::new(
+                config.naming.ws.clone(),
+                config.naming.ws_candle.clone(),
+            )),
+            redis_health: Arc::new(RedisHealth::default()),
+            http_client,
+            ipfs_gateway,
+            api_keys: Arc::new(ApiKeys::new(config.auth.as_ref())),
+            usage_meter: Arc::new(UsageMeter::default()),
+            disabled_dexes: config.disabled_dexes.as_slice().into(),
+            pool_state_tracked: cfg!(feature = "pubsub") && config.state_tracker.is_some(),
+            watchlists: Arc::new(Watchlists::default()),
+            avg_price_windows: config
+                .avg_price
+                .as_ref()
+                .map(|it| it.windows_secs.as_slice())
+                .unwrap_or_default()
+                .into(),
+            failed_swaps: config.failed_swaps,
         })
     }
 }
@@ -0,0 +1,238 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::extract::{Path, Query, State};
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache::{self, ApiKeyRecord, ApiUsage, ParseFailure},
+    ingest,
+    web::{
+        ApiQuota, ApiScope, WebAppContext, WebAppError,
+        extractor::{
+            json::Json,
+            page::{Page, Paged, SortOrder},
+        },
+        hash_api_key, new_api_key,
+    },
+};
+
+#[derive(Debug, Serialize)]
+pub struct ApiKeysResp {
+    /// keys of the config file, they can't be changed here
+    pub config_keys: Vec<ApiKeyRecord>,
+    pub keys: Vec<ApiKeyRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyReq {
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    #[serde(default)]
+    pub quota: ApiQuota,
+}
+
+/// The key is only shown once, only its hash is kept
+#[derive(Debug, Serialize)]
+pub struct ApiKeyResp {
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    pub quota: ApiQuota,
+    pub key: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeleteApiKeyResp {
+    pub deleted: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    /// UTC day, today by default
+    pub day: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UsageResp {
+    pub day: NaiveDate,
+    /// by api key name
+    pub keys: HashMap<String, ApiUsage>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FailuresResp {
+    /// failures kept, the latest 1000
+    pub total: u64,
+    pub failures: Vec<ParseFailure>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RequeueFailuresReq {
+    /// the failures of these txids, all of them if unset
+    pub txids: Option<HashSet<String>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RequeueFailuresResp {
+    pub requeued: usize,
+    /// none if nothing was queued
+    pub batch_id: Option<String>,
+}
+
+/// `dataset` and `stream_name` of the queued failures
+const REQUEUE_DATASET: &str = "requeue";
+
+pub async fn list_api_keys(
+    State(WebAppContext {
+        redis_client,
+        api_keys,
+        ..
+    }): State<WebAppContext>,
+) -> Result<Json<ApiKeysResp>, WebAppError> {
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let keys = cache::list_api_keys(&mut conn).await?;
+    Ok(Json(ApiKeysResp {
+        config_keys: api_keys.config_keys().cloned().collect(),
+        keys,
+    }))
+}
+
+pub async fn create_api_key(
+    State(WebAppContext {
+        redis_client,
+        api_keys,
+        ..
+    }): State<WebAppContext>,
+    Json(CreateApiKeyReq {
+        name,
+        scopes,
+        quota,
+    }): Json<CreateApiKeyReq>,
+) -> Result<Json<ApiKeyResp>, WebAppError> {
+    if name.is_empty() || scopes.is_empty() {
+        return Err(WebAppError::invalid_req("name and scopes are required"));
+    }
+    if api_keys.config_keys().any(|it| it.name == name) {
+        return Err(WebAppError::invalid_req(format!(
+            "api key {name} is in the config file"
+        )));
+    }
+
+    let key = new_api_key();
+    let record = ApiKeyRecord {
+        name,
+        scopes,
+        quota,
+    };
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    if !cache::create_api_key(&mut conn, &hash_api_key(&key), &record).await? {
+        return Err(WebAppError::invalid_req(format!(
+            "api key {} exists",
+            record.name
+        )));
+    }
+    Ok(Json(ApiKeyResp {
+        name: record.name,
+        scopes: record.scopes,
+        quota: record.quota,
+        key,
+    }))
+}
+
+/// Issue a new key with the same scopes, the old key stops working
+pub async fn rotate_api_key(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+    Path(name): Path<String>,
+) -> Result<Json<ApiKeyResp>, WebAppError> {
+    let key = new_api_key();
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let record = cache::rotate_api_key(&mut conn, &name, &hash_api_key(&key))
+        .await?
+        .ok_or_else(|| WebAppError::not_found(format!("no api key {name}")))?;
+    Ok(Json(ApiKeyResp {
+        name: record.name,
+        scopes: record.scopes,
+        quota: record.quota,
+        key,
+    }))
+}
+
+pub async fn delete_api_key(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+    Path(name): Path<String>,
+) -> Result<Json<DeleteApiKeyResp>, WebAppError> {
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let deleted = cache::delete_api_key(&mut conn, &name).await?;
+    Ok(Json(DeleteApiKeyResp { deleted }))
+}
+
+/// Usage of each api key in the day, flushed every few seconds so the latest requests may be missing
+pub async fn usage(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+    Query(UsageQuery { day }): Query<UsageQuery>,
+) -> Result<Json<UsageResp>, WebAppError> {
+    let day = day.unwrap_or_else(|| Utc::now().date_naive());
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let keys = cache::api_usage_on(&mut conn, day).await?;
+    Ok(Json(UsageResp { day, keys }))
+}
+
+/// Transactions which failed to parse by failure time, the earliest first unless `order=desc`
+pub async fn failures(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+    page: Page,
+) -> Result<Json<Paged<FailuresResp>>, WebAppError> {
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let mut failures = cache::list_parse_failures(&mut conn).await?;
+    let total = failures.len() as u64;
+    let (from, to) = page.score_range();
+    failures.retain(|it| {
+        let ts = it.failed_at.timestamp();
+        from.is_none_or(|from| ts >= from) && to.is_none_or(|to| ts <= to)
+    });
+    // kept the latest first
+    if page.order == SortOrder::Asc {
+        failures.reverse();
+        failures.sort_by_key(|it| it.failed_at);
+    } else {
+        failures.sort_by_key(|it| std::cmp::Reverse(it.failed_at));
+    }
+    let mut failures: Vec<_> = failures
+        .into_iter()
+        .skip(page.skip())
+        .take(page.fetch_len())
+        .collect();
+    let next_cursor = page.paginate(&mut failures, |it| it.failed_at.timestamp());
+    Ok(Json(Paged {
+        resp: FailuresResp { total, failures },
+        next_cursor,
+    }))
+}
+
+/// Queue the failed transactions to the processor again, e.g. after a decoder fix, they're
+/// removed from the failures once queued and added back if they fail again. Of a transaction
+/// with quarantined logs only these logs are parsed again.
+pub async fn requeue_failures(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+    Json(RequeueFailuresReq { txids }): Json<RequeueFailuresReq>,
+) -> Result<Json<RequeueFailuresResp>, WebAppError> {
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let (failures, records) = cache::find_parse_failures(&mut conn, txids.as_ref()).await?;
+    if failures.is_empty() {
+        return Ok(Json(RequeueFailuresResp {
+            requeued: 0,
+            batch_id: None,
+        }));
+    }
+    let txs: Vec<_> = failures
+        .into_iter()
+        .map(ParseFailure::into_requeued_tx)
+        .collect();
+    let requeued = txs.len();
+    let batch_id = ingest::push_txs(&mut conn, REQUEUE_DATASET, REQUEUE_DATASET, txs).await?;
+    cache::remove_parse_failures(&mut conn, &records).await?;
+    Ok(Json(RequeueFailuresResp {
+        requeued,
+        batch_id: Some(batch_id),
+    }))
+}
@@ -0,0 +1,23 @@
+use axum::extract::{Query, State};
+use serde::Deserialize;
+
+use crate::{
+    cache::{self, Congestion, MAX_CONGESTION_SLOTS},
+    web::{WebAppContext, WebAppError, extractor::json::Json},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct CongestionQuery {
+    /// 150 by default, about a minute, at most 1500
+    pub slots: Option<usize>,
+}
+
+/// Median compute unit price and limit requested by the dex transactions of the latest slots
+pub async fn congestion(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+    Query(CongestionQuery { slots }): Query<CongestionQuery>,
+) -> Result<Json<Congestion>, WebAppError> {
+    let slots = slots.unwrap_or(150).min(MAX_CONGESTION_SLOTS);
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    Ok(Json(cache::congestion(&mut conn, slots).await?))
+}
@@ -0,0 +1,76 @@
+use axum::extract::{Path, Query, State};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    cache::{self, CreatorToken},
+    creator_graph::{self, FundingLink},
+    web::{WebAppContext, WebAppError, extractor::json::Json},
+};
+
+const DEFAULT_FUNDER_DEPTH: usize = 3;
+const MAX_FUNDER_DEPTH: usize = 10;
+
+#[serde_as]
+#[derive(Debug, Serialize)]
+pub struct CreatorTokensResp {
+    #[serde_as(as = "DisplayFromStr")]
+    pub creator: Pubkey,
+    pub tokens: Vec<CreatorToken>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FundersQuery {
+    pub depth: Option<usize>,
+}
+
+#[serde_as]
+#[derive(Debug, Serialize)]
+pub struct CreatorFundersResp {
+    #[serde_as(as = "DisplayFromStr")]
+    pub creator: Pubkey,
+    /// the creator's first funder first, then the funder's first funder and so on
+    pub funders: Vec<FundingLink>,
+}
+
+fn parse_wallet(wallet: &str) -> Result<Pubkey, WebAppError> {
+    wallet
+        .parse()
+        .map_err(|_| WebAppError::invalid_req(format!("invalid wallet: {wallet}")))
+}
+
+/// Tokens created by the wallet, with their pools
+pub async fn creator_tokens(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+    Path(creator): Path<String>,
+) -> Result<Json<CreatorTokensResp>, WebAppError> {
+    let creator = parse_wallet(&creator)?;
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let tokens = cache::creator_tokens(&mut conn, &creator).await?;
+    Ok(Json(CreatorTokensResp { creator, tokens }))
+}
+
+/// Wallets which funded the creator, following first funders up to `depth`
+pub async fn creator_funders(
+    State(WebAppContext {
+        redis_client,
+        sol_rpc_client,
+        ..
+    }): State<WebAppContext>,
+    Path(creator): Path<String>,
+    Query(FundersQuery { depth }): Query<FundersQuery>,
+) -> Result<Json<CreatorFundersResp>, WebAppError> {
+    let creator = parse_wallet(&creator)?;
+    let depth = depth.unwrap_or(DEFAULT_FUNDER_DEPTH);
+    if depth > MAX_FUNDER_DEPTH {
+        return Err(WebAppError::invalid_req(format!(
+            "depth should be at most {MAX_FUNDER_DEPTH}"
+        )));
+    }
+
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let funders =
+        creator_graph::funder_chain(sol_rpc_client.as_ref(), &mut conn, &creator, depth).await?;
+    Ok(Json(CreatorFundersResp { creator, funders }))
+}
@@ -0,0 +1,81 @@
+use axum::extract::State;
+use serde::Serialize;
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+use strum::IntoEnumIterator;
+
+use crate::{
+    cache::DexEventKind,
+    common::Dex,
+    web::{WebAppContext, WebAppError, extractor::json::Json},
+};
+
+#[serde_as]
+#[derive(Debug, Serialize)]
+pub struct DexResp {
+    pub dex: Dex,
+    #[serde_as(as = "DisplayFromStr")]
+    pub program_id: Pubkey,
+    /// the pool state kinds only while the pool state tracker runs
+    pub event_kinds: Vec<DexEventKind>,
+    /// the events of a disabled dex are dropped after decoding
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DexesResp {
+    pub dexes: Vec<DexResp>,
+}
+
+/// The supported dex programs and the kinds of events they emit
+pub async fn dexes(
+    State(WebAppContext {
+        disabled_dexes,
+        pool_state_tracked,
+        ..
+    }): State<WebAppContext>,
+) -> Result<Json<DexesResp>, WebAppError> {
+    let dexes = dex_list(&disabled_dexes, pool_state_tracked);
+    Ok(Json(DexesResp { dexes }))
+}
+
+fn dex_list(disabled_dexes: &[Dex], pool_state_tracked: bool) -> Vec<DexResp> {
+    Dex::iter()
+        .map(|dex| {
+            let mut event_kinds = dex.event_kinds();
+            if pool_state_tracked {
+                event_kinds.extend(dex.state_event_kinds());
+            }
+            DexResp {
+                dex,
+                program_id: dex.program_id(),
+                event_kinds,
+                enabled: !disabled_dexes.contains(&dex),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dex_list() {
+        let dexes = dex_list(&[Dex::Moonshot], false);
+        assert_eq!(dexes.len(), Dex::iter().count());
+        let dlmm = dexes.iter().find(|it| it.dex == Dex::MeteoraDlmm).unwrap();
+        assert!(dlmm.enabled);
+        assert!(!dlmm.event_kinds.contains(&DexEventKind::PoolStateUpdated));
+        let moonshot = dexes.iter().find(|it| it.dex == Dex::Moonshot).unwrap();
+        assert!(!moonshot.enabled);
+        assert_eq!(moonshot.program_id, Dex::Moonshot.program_id());
+
+        // the pool state kinds only while the tracker runs
+        let dexes = dex_list(&[], true);
+        let dlmm = dexes.iter().find(|it| it.dex == Dex::MeteoraDlmm).unwrap();
+        assert!(dlmm.event_kinds.contains(&DexEventKind::PoolStateUpdated));
+        assert!(dlmm.event_kinds.contains(&DexEventKind::BinPriceUpdate));
+        assert!(dexes.iter().all(|it| it.enabled));
+    }
+}
@@ -0,0 +1,110 @@
+use axum::extract::{Path, State};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    cache::{self, TraderStats},
+    web::{
+        WebAppContext, WebAppError,
+        extractor::{
+            json::Json,
+            page::{Page, Paged},
+        },
+    },
+};
+
+/// wallets followed by one request
+const MAX_FOLLOW_LEN: usize = 1_000;
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub struct FollowReq {
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    pub wallets: Vec<Pubkey>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FollowResp {
+    /// wallets not followed before
+    pub added: u64,
+}
+
+#[serde_as]
+#[derive(Debug, Serialize)]
+pub struct FollowedWallet {
+    #[serde_as(as = "DisplayFromStr")]
+    pub wallet: Pubkey,
+    pub stats: TraderStats,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FollowsResp {
+    pub follows: Vec<FollowedWallet>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnfollowResp {
+    pub deleted: bool,
+}
+
+/// Follow the wallets, their trades are emitted as `FollowedWalletTrade` events
+pub async fn follow(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+    Json(FollowReq { wallets }): Json<FollowReq>,
+) -> Result<Json<FollowResp>, WebAppError> {
+    if wallets.is_empty() || wallets.len() > MAX_FOLLOW_LEN {
+        return Err(WebAppError::invalid_req(format!(
+            "wallets should have 1 to {MAX_FOLLOW_LEN} wallets"
+        )));
+    }
+
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let added = cache::follow_wallets(&mut conn, &wallets).await?;
+    Ok(Json(FollowResp { added }))
+}
+
+/// The followed wallets with their stats of the last 24 hours, a page of them by pubkey as the
+/// stats are read per wallet. `from`, `to` and `order` don't apply.
+pub async fn list_follows(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+    page: Page,
+) -> Result<Json<Paged<FollowsResp>>, WebAppError> {
+    if page.from.is_some() || page.to.is_some() {
+        return Err(WebAppError::invalid_req("the follows take no from or to"));
+    }
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let mut wallets: Vec<_> = cache::followed_wallets(&mut conn)
+        .await?
+        .into_iter()
+        .skip(page.skip())
+        .take(page.fetch_len())
+        .collect();
+    // one score for all, the cursor only counts the wallets listed
+    let next_cursor = page.paginate(&mut wallets, |_| 0);
+    let mut stats = cache::trader_stats(&mut conn, &wallets, Utc::now()).await?;
+    let follows = wallets
+        .into_iter()
+        .map(|wallet| FollowedWallet {
+            wallet,
+            stats: stats.remove(&wallet).unwrap_or_default(),
+        })
+        .collect();
+    Ok(Json(Paged {
+        resp: FollowsResp { follows },
+        next_cursor,
+    }))
+}
+
+pub async fn unfollow(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+    Path(wallet): Path<String>,
+) -> Result<Json<UnfollowResp>, WebAppError> {
+    let wallet: Pubkey = wallet
+        .parse()
+        .map_err(|_| WebAppError::invalid_req(format!("invalid wallet: {wallet}")))?;
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let deleted = cache::unfollow_wallet(&mut conn, &wallet).await?;
+    Ok(Json(UnfollowResp { deleted }))
+}
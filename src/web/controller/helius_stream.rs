@@ -0,0 +1,60 @@
+use std::time::Instant;
+
+use axum::extract::State;
+use tracing::{info, warn};
+
+use crate::{
+    cache,
+    helius_tx::HeliusWebhookTx,
+    qn_req_processor::{QnSolDexDatahubWebhookReq, QnStreamMetadata, new_batch_id},
+    web::{WebAppContext, WebAppError, extractor::json::Json},
+};
+
+use super::qn_stream::IngestAck;
+
+pub async fn helius_stream(
+    State(WebAppContext {
+        redis_client,
+        failed_swaps,
+        ..
+    }): State<WebAppContext>,
+    Json(helius_txs): Json<Vec<HeliusWebhookTx>>,
+) -> Result<Json<IngestAck>, WebAppError> {
+    let start = Instant::now();
+    let batch_id = new_batch_id();
+    let mut txs = vec![];
+    for helius_tx in helius_txs {
+        let signature = helius_tx.signature().to_string();
+        match helius_tx.into_tx(failed_swaps) {
+            Ok(Some(tx)) => txs.push(tx),
+            Ok(None) => continue,
+            Err(err) => warn!("convert helius tx {signature} error: {err}"),
+        }
+    }
+    let Some(metadata) = QnStreamMetadata::for_txs("helius_webhook", "helius", &txs) else {
+        return Ok(Json(IngestAck {
+            batch_id,
+            accepted_txs: 0,
+            duplicate: false,
+        }));
+    };
+
+    let accepted_txs = txs.len();
+    let req = QnSolDexDatahubWebhookReq {
+        batch_id: Some(batch_id.clone()),
+        txs,
+        metadata,
+    };
+    let req_body = serde_json::to_string(&req)?;
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    cache::rpush_qn_request(&mut conn, req_body).await?;
+
+    let elapsed = start.elapsed().as_millis();
+    info!("process helius request batch {batch_id} with {accepted_txs} txs take {elapsed} ms");
+
+    Ok(Json(IngestAck {
+        batch_id,
+        accepted_txs,
+        duplicate: false,
+    }))
+}
@@ -0,0 +1,50 @@
+use axum::extract::{Query, State};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache::{self, LeaderboardEntry, LeaderboardMetric, LeaderboardWindow},
+    web::{
+        WebAppContext, WebAppError,
+        extractor::{json::Json, page::Page},
+    },
+};
+
+const MAX_LEADERBOARD_LIMIT: usize = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct LeaderboardQuery {
+    /// `volume`, `trades` or `buy_sell_ratio`, `volume` by default
+    #[serde(default)]
+    pub metric: LeaderboardMetric,
+    /// `5m` or `1h`, `5m` by default
+    #[serde(default)]
+    pub window: LeaderboardWindow,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LeaderboardResp {
+    pub mints: Vec<LeaderboardEntry>,
+}
+
+/// The hottest mints of the window by SOL traded, trades or buy sell ratio, the highest first.
+/// Only `limit` of the page applies, the ranking has no next page.
+pub async fn leaderboard(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+    Query(LeaderboardQuery { metric, window }): Query<LeaderboardQuery>,
+    page: Page,
+) -> Result<Json<LeaderboardResp>, WebAppError> {
+    if page.cursor.is_some() || page.from.is_some() || page.to.is_some() {
+        return Err(WebAppError::invalid_req(
+            "the leaderboard takes no cursor, from or to",
+        ));
+    }
+    if page.limit > MAX_LEADERBOARD_LIMIT {
+        return Err(WebAppError::invalid_req(format!(
+            "limit should be between 1 and {MAX_LEADERBOARD_LIMIT}"
+        )));
+    }
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let mints = cache::leaderboard(&mut conn, metric, window, Utc::now(), page.limit).await?;
+    Ok(Json(LeaderboardResp { mints }))
+}
@@ -1,25 +1,66 @@
+use std::collections::{BTreeMap, HashMap};
+
 use axum::extract::State;
+use chrono::{NaiveTime, Utc};
 use redis::AsyncCommands;
 use serde::Serialize;
 
-use crate::web::{WebAppContext, WebAppError, extractor::json::Json};
+use crate::{
+    cache::{self, BigQueryCursor, ConsumerMetrics, DexShare},
+    common::{Dex, utils},
+    parse_error::{self, AccountViolationCount},
+    redis_monitor::RedisInfo,
+    shadow_parser,
+    web::{WebAppContext, WebAppError, WsMetrics, extractor::json::Json},
+};
 
 #[derive(Debug, Serialize)]
 pub struct MetricsResp {
     pub latest_sol_slot: u64,
     pub redis_test: String,
+    pub ws: WsMetrics,
+    /// none until the redis monitor checked once
+    pub redis: Option<RedisInfo>,
+    /// pumpfun bonding curves completed in the current UTC day
+    pub pumpfun_completes_today: u64,
+    /// SOL volume of the dexes in the current UTC day
+    pub dex_share_today: DexShare,
+    /// counters of the configured consumers
+    pub consumers: Vec<ConsumerMetrics>,
+    /// export cursors by bigquery table
+    pub bigquery: BTreeMap<String, BigQueryCursor>,
+    /// decoded amounts found inconsistent, e.g. a fee larger than the amount, since the start
+    pub amount_anomalies: u64,
+    /// dex logs which failed to parse since the start, by the kind of error and dex
+    pub parse_errors: HashMap<&'static str, HashMap<Dex, u64>>,
+    /// transactions skipped as a whole since the start, e.g. with a bad block timestamp
+    pub failed_txs: u64,
+    /// instruction accounts expected by an event which were missing since the start
+    pub account_violations: Vec<AccountViolationCount>,
+    /// events the candidate parser emitted differently from the production parser since the
+    /// start, 0 unless `shadow_parser` is on
+    pub shadow_parser_diffs: u64,
 }
 
 pub async fn check_health(
     State(WebAppContext {
         redis_client,
         sol_rpc_client,
+        ws_hub,
+        redis_health,
         ..
     }): State<WebAppContext>,
 ) -> Result<Json<MetricsResp>, WebAppError> {
     let mut redis_conn = redis_client.get_multiplexed_async_connection().await?;
     let _: () = redis_conn.set_ex("check_health", b"ok", 10).await?;
     let redis_result: String = redis_conn.get("check_health").await?;
+    let now = Utc::now();
+    let pumpfun_completes_today =
+        cache::pumpfun_completes_on(&mut redis_conn, now.date_naive()).await?;
+    let today = now.date_naive().and_time(NaiveTime::MIN).and_utc();
+    let dex_share_today = cache::dex_share_since(&mut redis_conn, today, now).await?;
+    let consumers = cache::consumer_metrics(&mut redis_conn).await?;
+    let bigquery = cache::bigquery_cursors(&mut redis_conn).await?;
     drop(redis_conn);
 
     let latest_sol_slot = sol_rpc_client.get_slot().await?;
@@ -27,5 +68,16 @@ pub async fn check_health(
     Ok(Json(MetricsResp {
         latest_sol_slot,
         redis_test: redis_result,
+        ws: ws_hub.metrics(),
+        redis: redis_health.latest(),
+        pumpfun_completes_today,
+        dex_share_today,
+        consumers,
+        bigquery,
+        amount_anomalies: utils::amount_anomalies(),
+        parse_errors: parse_error::parse_errors(),
+        failed_txs: parse_error::failed_txs(),
+        account_violations: parse_error::account_violation_counts(),
+        shadow_parser_diffs: shadow_parser::shadow_diffs(),
     }))
 }
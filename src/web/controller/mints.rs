@@ -0,0 +1,58 @@
+use axum::extract::{Query, State};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache::{self, MintRegistryRecord, MintSort},
+    web::{
+        WebAppContext, WebAppError,
+        extractor::{
+            json::Json,
+            page::{Page, Paged, SortOrder},
+        },
+    },
+};
+
+#[derive(Debug, Deserialize)]
+pub struct MintsQuery {
+    #[serde(default)]
+    pub sort: MintSort,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MintsResp {
+    pub mints: Vec<MintRegistryRecord>,
+}
+
+/// Registered mints by first seen time or by traded sol, the lowest first unless `order=desc`
+pub async fn mints(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+    Query(query): Query<MintsQuery>,
+    page: Page,
+) -> Result<Json<Paged<MintsResp>>, WebAppError> {
+    if query.sort == MintSort::Volume && (page.from.is_some() || page.to.is_some()) {
+        return Err(WebAppError::invalid_req(
+            "from and to only apply to sort=new",
+        ));
+    }
+
+    let (from, to) = page.score_range();
+    let rev = page.order == SortOrder::Desc;
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let mut mints = cache::list_mints(
+        &mut conn,
+        query.sort,
+        from,
+        to,
+        page.skip(),
+        page.fetch_len(),
+        rev,
+    )
+    .await?;
+    let next_cursor = page.paginate(&mut mints, |(_, score)| *score);
+    Ok(Json(Paged {
+        resp: MintsResp {
+            mints: mints.into_iter().map(|(mint, _)| mint).collect(),
+        },
+        next_cursor,
+    }))
+}
@@ -1,3 +1,23 @@
+pub mod admin;
+pub mod congestion;
+pub mod creators;
+pub mod dexes;
+pub mod follows;
+pub mod helius_stream;
 pub mod home;
+pub mod leaderboard;
 pub mod metrics;
+pub mod mints;
+pub mod ndjson_stream;
+pub mod pool;
+pub mod price;
+pub mod pumpamm;
+pub mod pumpfun;
 pub mod qn_stream;
+pub mod resolve;
+pub mod stats;
+pub mod token;
+pub mod token_stats;
+pub mod trade_stream;
+pub mod watchlists;
+pub mod ws;
@@ -0,0 +1,125 @@
+use std::mem;
+
+use axum::{body::Body, extract::State};
+use futures::StreamExt;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::{
+    ingest,
+    qn_req_processor::Tx,
+    web::{WebAppContext, WebAppError, extractor::json::Json},
+};
+
+/// Transactions queued as one request
+const NDJSON_BATCH_TXS: usize = 500;
+const MAX_NDJSON_LINE_LEN: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Default, Serialize)]
+pub struct NdjsonIngestAck {
+    pub batch_ids: Vec<String>,
+    pub accepted_txs: usize,
+    pub rejected_lines: usize,
+}
+
+/// Ingest newline delimited transactions in the quicknode stream tx format from a streamed body,
+/// queueing them in batches while reading instead of buffering the whole body.
+pub async fn ndjson_stream(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+    body: Body,
+) -> Result<Json<NdjsonIngestAck>, WebAppError> {
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let mut ack = NdjsonIngestAck::default();
+    let mut txs = vec![];
+    let mut buf = vec![];
+    let mut stream = body.into_data_stream();
+    loop {
+        let chunk =
+            stream.next().await.transpose().map_err(|err| {
+                WebAppError::invalid_req(format!("read ndjson stream error: {err}"))
+            })?;
+        let end = chunk.is_none();
+        match chunk {
+            Some(chunk) => buf.extend_from_slice(&chunk),
+            // last line without trailing newline
+            None => buf.push(b'\n'),
+        }
+
+        ack.rejected_lines += parse_lines(&mut buf, &mut txs);
+        if buf.len() > MAX_NDJSON_LINE_LEN {
+            return Err(WebAppError::invalid_req(format!(
+                "ndjson line longer than {MAX_NDJSON_LINE_LEN} bytes"
+            )));
+        }
+
+        if txs.len() >= NDJSON_BATCH_TXS || (end && !txs.is_empty()) {
+            let batch = mem::take(&mut txs);
+            ack.accepted_txs += batch.len();
+            ack.batch_ids
+                .push(ingest::push_txs(&mut conn, "ndjson_stream", "ndjson", batch).await?);
+        }
+        if end {
+            break;
+        }
+    }
+
+    info!(
+        "ndjson stream ended, {} txs in batches: {}, rejected lines: {}",
+        ack.accepted_txs,
+        ack.batch_ids.join(","),
+        ack.rejected_lines
+    );
+    Ok(Json(ack))
+}
+
+/// Parse the complete lines of `buf` into `txs` and remove them, the partial last line is kept
+/// for the next chunk. Returns the invalid lines, the blank ones are skipped.
+fn parse_lines(buf: &mut Vec<u8>, txs: &mut Vec<Tx>) -> usize {
+    let mut rejected = 0;
+    let mut consumed = 0;
+    while let Some(pos) = buf[consumed..].iter().position(|it| *it == b'\n') {
+        let line = &buf[consumed..consumed + pos];
+        consumed += pos + 1;
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        match serde_json::from_slice::<Tx>(line) {
+            Ok(tx) => txs.push(tx),
+            Err(err) => {
+                warn!("invalid ndjson tx line: {err}");
+                rejected += 1;
+            }
+        }
+    }
+    buf.drain(..consumed);
+    rejected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lines() {
+        let tx = |signature: &str| {
+            format!(r#"{{"blkTs":1,"slot":2,"signature":"{signature}","logs":[],"ixs":[]}}"#)
+        };
+        let mut txs = vec![];
+        // a line split over two chunks
+        let body = format!("{}\n\n  \nnot json\n{}", tx("a"), tx("b"));
+        let (first, second) = body.split_at(body.len() - 10);
+        let mut buf = first.as_bytes().to_vec();
+        assert_eq!(parse_lines(&mut buf, &mut txs), 1);
+        let partial = first.rsplit('\n').next().unwrap();
+        assert!(!partial.is_empty());
+        assert_eq!(buf, partial.as_bytes());
+
+        buf.extend_from_slice(second.as_bytes());
+        // the end of the body ends the last line
+        buf.push(b'\n');
+        assert_eq!(parse_lines(&mut buf, &mut txs), 0);
+        assert!(buf.is_empty());
+        let signatures: Vec<_> = txs.iter().map(|it| it.signature.as_str()).collect();
+        assert_eq!(signatures, ["a", "b"]);
+    }
+}
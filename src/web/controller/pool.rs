@@ -0,0 +1,78 @@
+use axum::extract::{Path, Query, State};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    cache::{self, PoolReserveSnapshot, PoolSafety, PriceOf, PriceQuote},
+    web::{WebAppContext, WebAppError, extractor::json::Json},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct ReservesQuery {
+    pub at_slot: Option<u64>,
+}
+
+#[serde_as]
+#[derive(Debug, Serialize)]
+pub struct PoolReservesResp {
+    #[serde_as(as = "DisplayFromStr")]
+    pub pool: Pubkey,
+    pub at_slot: Option<u64>,
+    #[serde(flatten)]
+    pub snapshot: PoolReserveSnapshot,
+}
+
+/// Reserves of the pool from the snapshot nearest to `at_slot`, the latest one without it
+pub async fn reserves(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+    Path(pool): Path<String>,
+    Query(ReservesQuery { at_slot }): Query<ReservesQuery>,
+) -> Result<Json<PoolReservesResp>, WebAppError> {
+    let pool: Pubkey = pool
+        .parse()
+        .map_err(|_| WebAppError::invalid_req(format!("invalid pool: {pool}")))?;
+
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let snapshot = cache::pool_reserves_at(&mut conn, &pool, at_slot)
+        .await?
+        .ok_or_else(|| WebAppError::not_found(format!("no reserve snapshot of pool {pool}")))?;
+    Ok(Json(PoolReservesResp {
+        pool,
+        at_slot,
+        snapshot,
+    }))
+}
+
+/// Price of the latest trade of the pool
+pub async fn price(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+    Path(pool): Path<String>,
+) -> Result<Json<PriceQuote>, WebAppError> {
+    let pool: Pubkey = pool
+        .parse()
+        .map_err(|_| WebAppError::invalid_req(format!("invalid pool: {pool}")))?;
+
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let quote = cache::price_quote(&mut conn, PriceOf::Pool, &pool, Utc::now())
+        .await?
+        .ok_or_else(|| WebAppError::not_found(format!("no trade of pool {pool}")))?;
+    Ok(Json(quote))
+}
+
+/// Lp lock state of the pool, watched for a while after its creation, see `lp_lock` of the config
+pub async fn safety(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+    Path(pool): Path<String>,
+) -> Result<Json<PoolSafety>, WebAppError> {
+    let pool: Pubkey = pool
+        .parse()
+        .map_err(|_| WebAppError::invalid_req(format!("invalid pool: {pool}")))?;
+
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let safety = cache::get_pool_safety(&mut conn, &pool)
+        .await?
+        .ok_or_else(|| WebAppError::not_found(format!("no lp lock state of pool {pool}")))?;
+    Ok(Json(safety))
+}
@@ -0,0 +1,24 @@
+use axum::extract::{Path, State};
+use chrono::Utc;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    cache::{self, PriceOf, PriceQuote},
+    web::{WebAppContext, WebAppError, extractor::json::Json},
+};
+
+/// Price of the latest trade of the mint, in any pool
+pub async fn price(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+    Path(mint): Path<String>,
+) -> Result<Json<PriceQuote>, WebAppError> {
+    let mint: Pubkey = mint
+        .parse()
+        .map_err(|_| WebAppError::invalid_req(format!("invalid mint: {mint}")))?;
+
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let quote = cache::price_quote(&mut conn, PriceOf::Mint, &mint, Utc::now())
+        .await?
+        .ok_or_else(|| WebAppError::not_found(format!("no trade of mint {mint}")))?;
+    Ok(Json(quote))
+}
@@ -0,0 +1,53 @@
+use axum::extract::State;
+use serde::Serialize;
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    pumpamm::accounts::PumpAmmGlobalConfig,
+    web::{WebAppContext, WebAppError, extractor::json::Json},
+};
+
+#[serde_as]
+#[derive(Debug, Serialize)]
+pub struct PumpAmmGlobalConfigResp {
+    #[serde_as(as = "DisplayFromStr")]
+    pub address: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub admin: Pubkey,
+    pub lp_fee_bps: u64,
+    pub protocol_fee_bps: u64,
+    /// none before creator fees were added to the config
+    pub coin_creator_fee_bps: Option<u64>,
+    pub total_fee_bps: u64,
+    pub disable_flags: u8,
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    pub protocol_fee_recipients: Vec<Pubkey>,
+}
+
+impl From<PumpAmmGlobalConfig> for PumpAmmGlobalConfigResp {
+    fn from(config: PumpAmmGlobalConfig) -> Self {
+        Self {
+            address: PumpAmmGlobalConfig::address(),
+            admin: config.admin,
+            lp_fee_bps: config.lp_fee_basis_points,
+            protocol_fee_bps: config.protocol_fee_basis_points,
+            coin_creator_fee_bps: config.coin_creator_fee_basis_points,
+            total_fee_bps: config.total_fee_bps(),
+            disable_flags: config.disable_flags,
+            protocol_fee_recipients: config
+                .protocol_fee_recipients
+                .into_iter()
+                .filter(|it| *it != Pubkey::default())
+                .collect(),
+        }
+    }
+}
+
+/// Current pumpamm protocol fees read from the global config account
+pub async fn global_config(
+    State(WebAppContext { sol_rpc_client, .. }): State<WebAppContext>,
+) -> Result<Json<PumpAmmGlobalConfigResp>, WebAppError> {
+    let config = PumpAmmGlobalConfig::from_rpc(sol_rpc_client.as_ref()).await?;
+    Ok(Json(config.into()))
+}
@@ -0,0 +1,36 @@
+use axum::extract::State;
+use serde::Serialize;
+
+use crate::{
+    cache::{self, PumpfunCompleteRecord},
+    web::{
+        WebAppContext, WebAppError,
+        extractor::{
+            json::Json,
+            page::{Page, Paged, SortOrder},
+        },
+    },
+};
+
+#[derive(Debug, Serialize)]
+pub struct CompletesResp {
+    pub completes: Vec<PumpfunCompleteRecord>,
+}
+
+/// Completed pumpfun bonding curves by block time, the earliest first unless `order=desc`
+pub async fn completes(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+    page: Page,
+) -> Result<Json<Paged<CompletesResp>>, WebAppError> {
+    let (from, to) = page.score_range();
+    let rev = page.order == SortOrder::Desc;
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let mut completes =
+        cache::list_pumpfun_completes(&mut conn, from, to, page.skip(), page.fetch_len(), rev)
+            .await?;
+    let next_cursor = page.paginate(&mut completes, |it| it.blk_ts.timestamp());
+    Ok(Json(Paged {
+        resp: CompletesResp { completes },
+        next_cursor,
+    }))
+}
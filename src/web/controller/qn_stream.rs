@@ -1,27 +1,66 @@
 use std::{cmp::min, time::Instant};
 
 use axum::extract::State;
+use serde::Serialize;
 use tracing::{debug, info};
 
 use crate::{
     cache,
-    web::{WebAppContext, WebAppError},
+    qn_req_processor::{QnSolDexDatahubWebhookReq, new_batch_id},
+    web::{WebAppContext, WebAppError, extractor::json::Json},
 };
 
+/// Receipt of an ingested request, `batch_id` shows up in the processor logs.
+#[derive(Debug, Serialize)]
+pub struct IngestAck {
+    pub batch_id: String,
+    pub accepted_txs: usize,
+    /// the stream batch was already ingested recently, the request is ignored
+    pub duplicate: bool,
+}
+
 pub async fn sol_dex_stream(
     State(WebAppContext { redis_client, .. }): State<WebAppContext>,
     req_body: String,
-) -> Result<(), WebAppError> {
+) -> Result<Json<IngestAck>, WebAppError> {
     let start = Instant::now();
+    let batch_id = new_batch_id();
     let body_start_len = min(50, req_body.len());
     let body_start = &req_body[0..body_start_len];
     debug!("request body is start with: {}", body_start);
+    let mut accepted_txs = 0;
+    let mut duplicate = false;
     if body_start.contains("metadata") {
+        let req = QnSolDexDatahubWebhookReq::from_ingested(&req_body, &batch_id)
+            .map_err(|err| WebAppError::invalid_req(format!("invalid qn request: {err}")))?;
+        // a retried stream batch has the same range
+        let stream_id = req.metadata.stream_id.clone();
+        let (batch_start_range, batch_end_range) =
+            (req.metadata.batch_start_range, req.metadata.batch_end_range);
+        let txs_len = req.txs.len();
+        let req_body = serde_json::to_string(&req)?;
         let mut conn = redis_client.get_multiplexed_async_connection().await?;
-        cache::rpush_qn_request(&mut conn, req_body).await?;
+        duplicate =
+            !cache::mark_qn_batch_seen(&mut conn, &stream_id, batch_start_range, batch_end_range)
+                .await?;
+        if duplicate {
+            info!(
+                "ignore duplicate qn request of stream {stream_id} range {batch_start_range}-{batch_end_range}"
+            );
+        } else if let Err(err) = cache::rpush_qn_request(&mut conn, req_body).await {
+            cache::forget_qn_batch(&mut conn, &stream_id, batch_start_range, batch_end_range)
+                .await?;
+            return Err(err.into());
+        } else {
+            accepted_txs = txs_len;
+        }
     }
     let elapsed = start.elapsed().as_millis();
-    info!("process qn request take {elapsed} ms");
+    info!("process qn request batch {batch_id} with {accepted_txs} txs take {elapsed} ms");
 
-    Ok(())
+    Ok(Json(IngestAck {
+        batch_id,
+        accepted_txs,
+        duplicate,
+    }))
 }
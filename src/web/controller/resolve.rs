@@ -0,0 +1,71 @@
+use axum::extract::{Path, State};
+use serde::Serialize;
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    cache::{self, DexPoolRecord, RedisCacheRecord},
+    common::Dex,
+    pool_resolver::{self, PoolAccountSummary},
+    web::{WebAppContext, WebAppError, extractor::json::Json},
+};
+
+#[serde_as]
+#[derive(Debug, Serialize)]
+pub struct ResolveResp {
+    #[serde_as(as = "DisplayFromStr")]
+    pub address: Pubkey,
+    pub dex: Dex,
+    /// the pool record kept from the transactions of the pool
+    pub cached: Option<DexPoolRecord>,
+    /// parsed from the onchain account when the pool is not cached
+    pub account: Option<PoolAccountSummary>,
+}
+
+/// The dex of a pool address, from the cached or archived pool records or else its onchain account
+pub async fn resolve(
+    State(WebAppContext {
+        redis_client,
+        sol_rpc_client,
+        ..
+    }): State<WebAppContext>,
+    Path(address): Path<String>,
+) -> Result<Json<ResolveResp>, WebAppError> {
+    let address: Pubkey = address
+        .parse()
+        .map_err(|_| WebAppError::invalid_req(format!("invalid pubkey: {address}")))?;
+
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let key = DexPoolRecord::new_key(address.to_string());
+    let mut record = DexPoolRecord::from_redis(&mut conn, &key).await?;
+    if record.is_none() {
+        record = cache::get_archived_pool(&mut conn, &address).await?;
+    }
+    if let Some(record) = record {
+        return Ok(Json(ResolveResp {
+            address,
+            dex: record.dex,
+            cached: Some(record),
+            account: None,
+        }));
+    }
+    drop(conn);
+
+    let account = sol_rpc_client
+        .get_multiple_accounts(&[address])
+        .await?
+        .pop()
+        .flatten()
+        .ok_or_else(|| WebAppError::not_found(format!("account {address} not found")))?;
+    let (dex, summary) = pool_resolver::parse_pool_account(&account.owner, &account.data)
+        .ok_or_else(|| {
+            WebAppError::not_found(format!("{address} is not a pool of a supported dex"))
+        })?;
+
+    Ok(Json(ResolveResp {
+        address,
+        dex,
+        cached: None,
+        account: Some(summary),
+    }))
+}
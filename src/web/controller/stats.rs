@@ -0,0 +1,67 @@
+use axum::extract::{Query, State};
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache::{self, DexShare, IngestionMinute, MAX_AUDIT_MINUTES},
+    web::{
+        WebAppContext, WebAppError,
+        extractor::{json::Json, page::deserialize_time},
+    },
+};
+
+#[derive(Debug, Serialize)]
+pub struct DexShareResp {
+    /// since the start of the current UTC hour
+    pub hour: DexShare,
+    /// since the start of the current UTC day
+    pub day: DexShare,
+}
+
+/// SOL volume and market share of the dexes, by block time
+pub async fn dex_share(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+) -> Result<Json<DexShareResp>, WebAppError> {
+    let now = Utc::now();
+    let today = now.date_naive().and_time(NaiveTime::MIN).and_utc();
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let hour = cache::dex_share_since(&mut conn, now, now).await?;
+    let day = cache::dex_share_since(&mut conn, today, now).await?;
+    Ok(Json(DexShareResp { hour, day }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IngestionQuery {
+    /// unix seconds or RFC 3339, an hour before `to` by default
+    #[serde(default, deserialize_with = "deserialize_time")]
+    pub from: Option<DateTime<Utc>>,
+    /// unix seconds or RFC 3339, now by default
+    #[serde(default, deserialize_with = "deserialize_time")]
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IngestionResp {
+    pub minutes: Vec<IngestionMinute>,
+}
+
+/// Events parsed per minute of block time by dex and kind, at most a day from `from`
+pub async fn ingestion(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+    Query(IngestionQuery { from, to }): Query<IngestionQuery>,
+) -> Result<Json<IngestionResp>, WebAppError> {
+    let to = to.unwrap_or_else(Utc::now);
+    let from = from.unwrap_or(to - Duration::hours(1));
+    if from > to {
+        return Err(WebAppError::invalid_req("from is after to"));
+    }
+    if to - from >= Duration::minutes(MAX_AUDIT_MINUTES) {
+        return Err(WebAppError::invalid_req(
+            "from and to are more than a day apart",
+        ));
+    }
+
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let minutes = cache::ingestion_audit(&mut conn, from, to).await?;
+    Ok(Json(IngestionResp { minutes }))
+}
@@ -0,0 +1,48 @@
+use axum::{
+    extract::{Path, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    token_image,
+    web::{WebAppContext, WebAppError},
+};
+
+/// Proxy of the token's metadata image, cached so clients don't hit ipfs gateways directly
+pub async fn image(
+    State(WebAppContext {
+        redis_client,
+        sol_rpc_client,
+        http_client,
+        ipfs_gateway,
+        ..
+    }): State<WebAppContext>,
+    Path(mint): Path<String>,
+) -> Result<Response, WebAppError> {
+    let mint: Pubkey = mint
+        .parse()
+        .map_err(|_| WebAppError::invalid_req(format!("invalid mint: {mint}")))?;
+
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let image = token_image::token_image(
+        sol_rpc_client.as_ref(),
+        &http_client,
+        &mut conn,
+        &mint,
+        &ipfs_gateway,
+    )
+    .await?
+    .ok_or_else(|| WebAppError::not_found(format!("no image of token {mint}")))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, image.content_type),
+            (header::CACHE_CONTROL, "public, max-age=86400".to_string()),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+        ],
+        image.data,
+    )
+        .into_response())
+}
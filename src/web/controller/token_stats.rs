@@ -0,0 +1,51 @@
+use axum::extract::{Path, State};
+use chrono::Utc;
+use serde::Serialize;
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    cache::{self, AvgPrice, NotionalHistogram},
+    web::{WebAppContext, WebAppError, extractor::json::Json},
+};
+
+#[serde_as]
+#[derive(Debug, Serialize)]
+pub struct TokenStatsResp {
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    pub notional_1h: NotionalHistogram,
+    pub notional_24h: NotionalHistogram,
+    /// rolling averages over the windows of `avg_price`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub avg_prices: Vec<AvgPrice>,
+}
+
+pub async fn token_stats(
+    State(WebAppContext {
+        redis_client,
+        avg_price_windows,
+        ..
+    }): State<WebAppContext>,
+    Path(mint): Path<String>,
+) -> Result<Json<TokenStatsResp>, WebAppError> {
+    let mint: Pubkey = mint
+        .parse()
+        .map_err(|_| WebAppError::invalid_req(format!("invalid mint: {mint}")))?;
+
+    let now = Utc::now();
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let notional_1h = cache::mint_notional_histogram(&mut conn, &mint, 3600, now).await?;
+    let notional_24h = cache::mint_notional_histogram(&mut conn, &mint, 24 * 3600, now).await?;
+    let avg_prices = cache::mint_avg_prices(&mut conn, &[mint], &avg_price_windows, now)
+        .await?
+        .remove(&mint)
+        .unwrap_or_default();
+
+    Ok(Json(TokenStatsResp {
+        mint,
+        notional_1h,
+        notional_24h,
+        avg_prices,
+    }))
+}
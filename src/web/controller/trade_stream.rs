@@ -0,0 +1,129 @@
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    time::Interval,
+};
+use tracing::warn;
+
+use crate::{
+    cache::{ApiUsage, DexEventKind},
+    web::{ApiClient, UsageMeter, WS_PING_INTERVAL, WebAppContext, WsFrame, WsHub},
+};
+
+/// The trades sent to the websocket event channel as an endless chunked response of newline
+/// delimited json, for the clients which can't open a websocket.
+pub async fn trades(
+    State(WebAppContext {
+        ws_hub,
+        usage_meter,
+        ..
+    }): State<WebAppContext>,
+    client: Option<ApiClient>,
+) -> Response {
+    let stream = TradeStream {
+        frames: ws_hub.subscribe(),
+        hub: ws_hub,
+        keep_alive: tokio::time::interval(WS_PING_INTERVAL),
+        metered: client.map(|client| (client, usage_meter)),
+    };
+    let body = Body::from_stream(futures::stream::unfold(stream, |mut stream| async move {
+        let line = stream.next_line().await?;
+        Some((Ok::<_, Infallible>(line), stream))
+    }));
+    ([(header::CONTENT_TYPE, "application/x-ndjson")], body).into_response()
+}
+
+struct TradeStream {
+    frames: broadcast::Receiver<Arc<WsFrame>>,
+    hub: Arc<WsHub>,
+    keep_alive: Interval,
+    metered: Option<(ApiClient, Arc<UsageMeter>)>,
+}
+
+impl TradeStream {
+    /// The next trade line, or an empty line on the ping interval so proxies keep the connection
+    /// open. None ends the response, when the hub closes or the api key exceeds its quota.
+    async fn next_line(&mut self) -> Option<String> {
+        loop {
+            tokio::select! {
+                frame = self.frames.recv() => match frame {
+                    Ok(frame) => {
+                        if frame.event_kind != Some(DexEventKind::Trade) {
+                            continue;
+                        }
+                        let Some(line) = event_line(&frame.text) else {
+                            continue;
+                        };
+                        if self.quota_exceeded() {
+                            return None;
+                        }
+                        if let Some((client, meter)) = &self.metered {
+                            let usage = ApiUsage {
+                                requests: 0,
+                                ws_messages: 1,
+                                bytes: line.len() as u64,
+                            };
+                            meter.record(&client.name, usage);
+                        }
+                        return Some(line);
+                    }
+                    Err(RecvError::Lagged(frames)) => {
+                        warn!("trade stream client lagged {frames} frames");
+                        self.hub.on_lagged(frames);
+                    }
+                    Err(RecvError::Closed) => return None,
+                },
+                _ = self.keep_alive.tick() => {
+                    if self.quota_exceeded() {
+                        return None;
+                    }
+                    return Some("\n".to_string());
+                }
+            }
+        }
+    }
+
+    /// Checked before each line, a busy stream would overrun the quota between keep-alives
+    fn quota_exceeded(&self) -> bool {
+        let Some((client, meter)) = &self.metered else {
+            return false;
+        };
+        let Some(limit) = client.quota.exceeded_by(&meter.today(&client.name)) else {
+            return false;
+        };
+        warn!(
+            "trade stream of api key {} exceeded {limit}, end",
+            client.name
+        );
+        true
+    }
+}
+
+/// The event of a frame as a json line
+fn event_line(frame: &str) -> Option<String> {
+    let frame: serde_json::Value = serde_json::from_str(frame).ok()?;
+    Some(format!("{}\n", frame.get("data")?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_line() {
+        let frame = r#"{"channel":"dex_events","data":{"kind":"Trade","txid":"tx"}}"#;
+        assert_eq!(
+            event_line(frame).as_deref(),
+            Some("{\"kind\":\"Trade\",\"txid\":\"tx\"}\n")
+        );
+        assert_eq!(event_line(r#"{"channel":"system"}"#), None);
+        assert_eq!(event_line("not json"), None);
+    }
+}
@@ -0,0 +1,109 @@
+use axum::extract::{Path, State};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    cache::{self, Watchlist},
+    web::{WebAppContext, WebAppError, extractor::json::Json},
+};
+
+/// mints and wallets of a watchlist together
+const MAX_WATCHLIST_LEN: usize = 10_000;
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub struct SaveWatchlistReq {
+    pub name: String,
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default)]
+    pub mints: Vec<Pubkey>,
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default)]
+    pub wallets: Vec<Pubkey>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WatchlistsResp {
+    pub watchlists: Vec<Watchlist>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeleteWatchlistResp {
+    pub deleted: bool,
+}
+
+/// Create or replace the watchlist, the websocket subscribers of its channel and the rules
+/// referencing it see the change without reconnecting
+pub async fn save_watchlist(
+    State(WebAppContext {
+        redis_client,
+        watchlists,
+        ..
+    }): State<WebAppContext>,
+    Json(SaveWatchlistReq {
+        name,
+        mints,
+        wallets,
+    }): Json<SaveWatchlistReq>,
+) -> Result<Json<Watchlist>, WebAppError> {
+    let valid_name = !name.is_empty()
+        && name
+            .chars()
+            .all(|it| it.is_ascii_alphanumeric() || it == '_' || it == '-');
+    if !valid_name {
+        return Err(WebAppError::invalid_req(
+            "name should be letters, digits, _ or -",
+        ));
+    }
+    if mints.len() + wallets.len() > MAX_WATCHLIST_LEN {
+        return Err(WebAppError::invalid_req(format!(
+            "a watchlist has at most {MAX_WATCHLIST_LEN} mints and wallets"
+        )));
+    }
+
+    let watchlist = Watchlist {
+        name,
+        mints,
+        wallets,
+        updated_at: Utc::now(),
+    };
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    cache::save_watchlist(&mut conn, &watchlist).await?;
+    watchlists.set(&watchlist);
+    Ok(Json(watchlist))
+}
+
+pub async fn list_watchlists(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+) -> Result<Json<WatchlistsResp>, WebAppError> {
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let watchlists = cache::list_watchlists(&mut conn).await?;
+    Ok(Json(WatchlistsResp { watchlists }))
+}
+
+pub async fn get_watchlist(
+    State(WebAppContext { redis_client, .. }): State<WebAppContext>,
+    Path(name): Path<String>,
+) -> Result<Json<Watchlist>, WebAppError> {
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let watchlist = cache::get_watchlist(&mut conn, &name)
+        .await?
+        .ok_or_else(|| WebAppError::not_found(format!("no watchlist {name}")))?;
+    Ok(Json(watchlist))
+}
+
+pub async fn delete_watchlist(
+    State(WebAppContext {
+        redis_client,
+        watchlists,
+        ..
+    }): State<WebAppContext>,
+    Path(name): Path<String>,
+) -> Result<Json<DeleteWatchlistResp>, WebAppError> {
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let deleted = cache::delete_watchlist(&mut conn, &name).await?;
+    watchlists.remove(&name);
+    Ok(Json(DeleteWatchlistResp { deleted }))
+}
@@ -0,0 +1,120 @@
+use std::{collections::HashSet, net::SocketAddr, sync::Arc, time::Instant};
+
+use axum::{
+    extract::{
+        ConnectInfo, State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    response::Response,
+};
+use futures::{SinkExt, StreamExt};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
+
+use crate::{
+    cache::ApiUsage,
+    web::{
+        ApiClient, UsageMeter, WS_IDLE_TIMEOUT, WS_PING_INTERVAL, WebAppContext, WsClientMsg, WsHub,
+    },
+};
+
+pub async fn ws(
+    ws: WebSocketUpgrade,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(WebAppContext {
+        ws_hub,
+        usage_meter,
+        ..
+    }): State<WebAppContext>,
+    client: Option<ApiClient>,
+) -> Response {
+    let metered = client.map(|client| (client, usage_meter));
+    ws.on_upgrade(move |socket| handle_socket(socket, addr, ws_hub, metered))
+}
+
+/// Messages sent to a client authorized by an api key are metered, the client is disconnected
+/// when the quota of the key is exceeded.
+async fn handle_socket(
+    socket: WebSocket,
+    addr: SocketAddr,
+    hub: Arc<WsHub>,
+    metered: Option<(ApiClient, Arc<UsageMeter>)>,
+) {
+    hub.on_connect();
+    info!("ws client {addr} connected");
+
+    let (mut sender, mut receiver) = socket.split();
+    let mut frames = hub.subscribe();
+    let mut channels: HashSet<String> = HashSet::new();
+    let mut ping_interval = tokio::time::interval(WS_PING_INTERVAL);
+    let mut last_seen = Instant::now();
+
+    let reaped = loop {
+        tokio::select! {
+            msg = receiver.next() => {
+                let Some(Ok(msg)) = msg else {
+                    break false;
+                };
+                last_seen = Instant::now();
+                match msg {
+                    Message::Text(text) => match serde_json::from_str::<WsClientMsg>(&text) {
+                        Ok(WsClientMsg::Subscribe { channels: subs }) => channels.extend(subs),
+                        Ok(WsClientMsg::Unsubscribe { channels: unsubs }) => {
+                            for channel in unsubs {
+                                channels.remove(&channel);
+                            }
+                        }
+                        Err(err) => debug!("invalid ws message from {addr}: {err}"),
+                    },
+                    Message::Close(_) => break false,
+                    _ => {}
+                }
+            }
+            frame = frames.recv() => {
+                match frame {
+                    Ok(frame) => {
+                        if !channels.contains(&frame.channel) {
+                            continue;
+                        }
+                        if sender.send(Message::text(frame.text.clone())).await.is_err() {
+                            break false;
+                        }
+                        if let Some((client, meter)) = &metered {
+                            let usage = ApiUsage {
+                                requests: 0,
+                                ws_messages: 1,
+                                bytes: frame.text.len() as u64,
+                            };
+                            meter.record(&client.name, usage);
+                        }
+                    }
+                    Err(RecvError::Lagged(frames)) => {
+                        warn!("ws client {addr} lagged {frames} frames");
+                        hub.on_lagged(frames);
+                    }
+                    Err(RecvError::Closed) => break false,
+                }
+            }
+            _ = ping_interval.tick() => {
+                if last_seen.elapsed() > WS_IDLE_TIMEOUT {
+                    warn!("ws client {addr} unresponsive for {:?}, disconnect", last_seen.elapsed());
+                    let _ = sender.send(Message::Close(None)).await;
+                    break true;
+                }
+                if let Some((client, meter)) = &metered
+                    && let Some(limit) = client.quota.exceeded_by(&meter.today(&client.name))
+                {
+                    warn!("ws client {addr} of api key {} exceeded {limit}, disconnect", client.name);
+                    let _ = sender.send(Message::Close(None)).await;
+                    break false;
+                }
+                if sender.send(Message::Ping(Default::default())).await.is_err() {
+                    break false;
+                }
+            }
+        }
+    };
+
+    hub.on_disconnect(reaped);
+    info!("ws client {addr} disconnected");
+}
@@ -9,6 +9,9 @@ pub enum WebAppError {
     UnAuthorized { err_msg: String },
     InvalidSignature,
     InvalidRequest { err_msg: String },
+    NotFound { err_msg: String },
+    Forbidden { err_msg: String },
+    TooManyRequests { err_msg: String },
     Other { err_msg: String },
 }
 
@@ -23,6 +26,21 @@ impl WebAppError {
         WebAppError::InvalidRequest { err_msg }
     }
 
+    pub fn not_found(err_msg: impl Into<String>) -> Self {
+        let err_msg = err_msg.into();
+        WebAppError::NotFound { err_msg }
+    }
+
+    pub fn forbidden(err_msg: impl Into<String>) -> Self {
+        let err_msg = err_msg.into();
+        WebAppError::Forbidden { err_msg }
+    }
+
+    pub fn too_many_requests(err_msg: impl Into<String>) -> Self {
+        let err_msg = err_msg.into();
+        WebAppError::TooManyRequests { err_msg }
+    }
+
     pub fn unauth(err_msg: impl Into<String>) -> Self {
         WebAppError::UnAuthorized {
             err_msg: err_msg.into(),
@@ -55,6 +73,21 @@ impl IntoResponse for WebAppError {
                 *resp.status_mut() = StatusCode::BAD_REQUEST;
                 resp
             }
+            Self::NotFound { err_msg } => {
+                let mut resp = Json(ErrorResp { error: err_msg }).into_response();
+                *resp.status_mut() = StatusCode::NOT_FOUND;
+                resp
+            }
+            Self::Forbidden { err_msg } => {
+                let mut resp = Json(ErrorResp { error: err_msg }).into_response();
+                *resp.status_mut() = StatusCode::FORBIDDEN;
+                resp
+            }
+            Self::TooManyRequests { err_msg } => {
+                let mut resp = Json(ErrorResp { error: err_msg }).into_response();
+                *resp.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+                resp
+            }
             Self::Other { err_msg } => {
                 let mut resp = Json(ErrorResp { error: err_msg }).into_response();
                 *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
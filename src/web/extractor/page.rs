@@ -0,0 +1,222 @@
+use axum::{
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+};
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize, de};
+
+use crate::web::WebAppError;
+
+pub const DEFAULT_PAGE_LIMIT: usize = 100;
+pub const MAX_PAGE_LIMIT: usize = 1_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// the earliest first
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Where the next page starts: the items with a score past `score`, and the ones at `score`
+/// but the first `skip` of them. Opaque to clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageCursor {
+    pub score: i64,
+    pub skip: usize,
+}
+
+impl PageCursor {
+    pub fn encode(&self) -> String {
+        BASE64_URL_SAFE_NO_PAD.encode(format!("{}:{}", self.score, self.skip))
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let bytes = BASE64_URL_SAFE_NO_PAD.decode(cursor).ok()?;
+        let (score, skip) = std::str::from_utf8(&bytes).ok()?.split_once(':')?;
+        Some(Self {
+            score: score.parse().ok()?,
+            skip: skip.parse().ok()?,
+        })
+    }
+}
+
+/// Unix seconds or RFC 3339, of the time query params
+pub fn deserialize_time<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<DateTime<Utc>>, D::Error> {
+    let Some(value) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    let time = match value.parse::<i64>() {
+        Ok(secs) => DateTime::from_timestamp(secs, 0),
+        Err(_) => DateTime::parse_from_rfc3339(&value)
+            .ok()
+            .map(|it| it.to_utc()),
+    };
+    time.map(Some)
+        .ok_or_else(|| de::Error::custom(format!("invalid time {value}")))
+}
+
+#[derive(Debug, Deserialize)]
+struct PageQuery {
+    limit: Option<usize>,
+    cursor: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_time")]
+    from: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "deserialize_time")]
+    to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    order: SortOrder,
+}
+
+/// `limit`, `cursor`, `order` and the inclusive time range `from`, `to` of a list query,
+/// shared by the list endpoints, whose responses are wrapped in `Paged`.
+#[derive(Debug, Clone)]
+pub struct Page {
+    pub limit: usize,
+    pub cursor: Option<PageCursor>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub order: SortOrder,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Page {
+    type Rejection = WebAppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(query) = Query::<PageQuery>::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| WebAppError::invalid_req(rejection.body_text()))?;
+
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        if limit == 0 || limit > MAX_PAGE_LIMIT {
+            return Err(WebAppError::invalid_req(format!(
+                "limit should be between 1 and {MAX_PAGE_LIMIT}"
+            )));
+        }
+        if let (Some(from), Some(to)) = (query.from, query.to)
+            && from > to
+        {
+            return Err(WebAppError::invalid_req("from should not be after to"));
+        }
+        let cursor = query
+            .cursor
+            .map(|it| PageCursor::decode(&it).ok_or(WebAppError::invalid_req("invalid cursor")))
+            .transpose()?;
+
+        Ok(Self {
+            limit,
+            cursor,
+            from: query.from,
+            to: query.to,
+            order: query.order,
+        })
+    }
+}
+
+impl Page {
+    /// Inclusive bounds of the scores in seconds to fetch, the time range narrowed by the cursor
+    pub fn score_range(&self) -> (Option<i64>, Option<i64>) {
+        let from = self.from.map(|it| it.timestamp());
+        let to = self.to.map(|it| it.timestamp());
+        match (self.cursor, self.order) {
+            (None, _) => (from, to),
+            (Some(cursor), SortOrder::Asc) => (Some(cursor.score), to),
+            (Some(cursor), SortOrder::Desc) => (from, Some(cursor.score)),
+        }
+    }
+
+    /// Items to skip at the start of the score range
+    pub fn skip(&self) -> usize {
+        self.cursor.map(|it| it.skip).unwrap_or_default()
+    }
+
+    /// Items to fetch, one more than the limit to know whether there is a next page
+    pub fn fetch_len(&self) -> usize {
+        self.limit + 1
+    }
+
+    /// Keep `limit` of the fetched items and return the cursor of the next page,
+    /// none if nothing is left.
+    pub fn paginate<T>(&self, items: &mut Vec<T>, score: impl Fn(&T) -> i64) -> Option<String> {
+        if items.len() <= self.limit {
+            return None;
+        }
+        items.truncate(self.limit);
+        let last = score(items.last()?);
+        let mut skip = items
+            .iter()
+            .rev()
+            .take_while(|it| score(it) == last)
+            .count();
+        if let Some(cursor) = self.cursor
+            && cursor.score == last
+        {
+            skip += cursor.skip;
+        }
+        Some(PageCursor { score: last, skip }.encode())
+    }
+}
+
+/// Response of a list endpoint, its fields with the cursor of the next page
+#[derive(Debug, Serialize)]
+pub struct Paged<T> {
+    #[serde(flatten)]
+    pub resp: T,
+    /// pass as `cursor` for the next page, none on the last page
+    pub next_cursor: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Request;
+
+    use super::*;
+
+    async fn page(query: &str) -> Result<Page, WebAppError> {
+        let req = Request::get(format!("/list?{query}")).body(()).unwrap();
+        let (mut parts, _) = req.into_parts();
+        Page::from_request_parts(&mut parts, &()).await
+    }
+
+    #[tokio::test]
+    async fn test_page() {
+        let first = page("limit=3&from=2023-11-14T22:13:20Z&to=1700000100")
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(
+            first.score_range(),
+            (Some(1_700_000_000), Some(1_700_000_100))
+        );
+
+        // scores of the items fetched by each page
+        let mut items = vec![10, 20, 20, 20];
+        let next = first.paginate(&mut items, |it| *it).unwrap();
+        assert_eq!(items, vec![10, 20, 20]);
+        assert_eq!(
+            PageCursor::decode(&next),
+            Some(PageCursor { score: 20, skip: 2 })
+        );
+
+        let second = page(&format!("limit=3&cursor={next}")).await.ok().unwrap();
+        assert_eq!(second.score_range(), (Some(20), None));
+        assert_eq!(second.skip(), 2);
+        let mut items = vec![20, 20, 20, 30];
+        let next = second.paginate(&mut items, |it| *it).unwrap();
+        assert_eq!(
+            PageCursor::decode(&next),
+            Some(PageCursor { score: 20, skip: 5 })
+        );
+
+        let mut items = vec![30];
+        assert_eq!(second.paginate(&mut items, |it| *it), None);
+
+        for query in ["limit=0", "cursor=bad", "from=20&to=10", "from=yesterday"] {
+            assert!(page(query).await.is_err(), "{query}");
+        }
+    }
+}
@@ -1,34 +1,119 @@
+mod auth;
 mod context;
 pub mod controller;
 mod error;
 pub mod extractor;
+mod usage;
+mod ws_candle;
+mod ws_hub;
+mod ws_status;
 
 use std::net::SocketAddr;
 
 use anyhow::Result;
+pub use auth::*;
 pub use context::*;
-use controller::{home, metrics, qn_stream};
+use controller::{
+    admin, congestion, creators, dexes, follows, helius_stream, home, leaderboard, metrics, mints,
+    ndjson_stream, pool, price, pumpamm, pumpfun, qn_stream, resolve, stats, token, token_stats,
+    trade_stream, watchlists, ws,
+};
 pub use error::*;
+pub use usage::*;
+pub use ws_candle::*;
+pub use ws_hub::*;
+pub use ws_status::*;
 
 use axum::{
     Router,
     extract::DefaultBodyLimit,
-    routing::{get, post},
+    middleware,
+    routing::{delete, get, post},
 };
 use tokio::net::TcpListener;
 use tower_http::decompression::RequestDecompressionLayer;
 use tower_http::trace::TraceLayer;
 use tracing::info;
 
-pub async fn start(context: WebAppContext, listen_on: &str) -> Result<()> {
-    let app = Router::new()
+/// Routes of the web server, also served by the integration tests
+pub fn router(context: WebAppContext) -> Router {
+    let ingest = Router::new()
+        .route("/sol_dex_stream", post(qn_stream::sol_dex_stream))
+        .route("/helius_stream", post(helius_stream::helius_stream))
+        .route("/ndjson_stream", post(ndjson_stream::ndjson_stream))
+        .route_layer(middleware::from_fn_with_state(
+            context.clone(),
+            require_ingest,
+        ));
+    let read = Router::new()
+        .route("/dexes", get(dexes::dexes))
+        .route("/ws", get(ws::ws))
+        .route("/stream/trades", get(trade_stream::trades))
+        .route("/pumpamm/global_config", get(pumpamm::global_config))
+        .route("/pumpfun/completes", get(pumpfun::completes))
+        .route("/token_stats/{mint}", get(token_stats::token_stats))
+        .route("/mints", get(mints::mints))
+        .route("/stats/dex_share", get(stats::dex_share))
+        .route("/stats/ingestion", get(stats::ingestion))
+        .route("/leaderboard", get(leaderboard::leaderboard))
+        .route("/congestion", get(congestion::congestion))
+        .route("/token/{mint}/image", get(token::image))
+        .route("/resolve/{pubkey}", get(resolve::resolve))
+        .route("/pool/{addr}/reserves", get(pool::reserves))
+        .route("/pool/{addr}/price", get(pool::price))
+        .route("/pool/{addr}/safety", get(pool::safety))
+        .route("/price/{mint}", get(price::price))
+        .route("/creators/{creator}/tokens", get(creators::creator_tokens))
+        .route(
+            "/creators/{creator}/funders",
+            get(creators::creator_funders),
+        )
+        .route("/watchlists", get(watchlists::list_watchlists))
+        .route("/watchlists/{name}", get(watchlists::get_watchlist))
+        .route("/follows", get(follows::list_follows))
+        .route_layer(middleware::from_fn_with_state(
+            context.clone(),
+            require_read,
+        ));
+    let write = Router::new()
+        .route("/watchlists", post(watchlists::save_watchlist))
+        .route("/watchlists/{name}", delete(watchlists::delete_watchlist))
+        .route("/follows", post(follows::follow))
+        .route("/follows/{wallet}", delete(follows::unfollow))
+        .route_layer(middleware::from_fn_with_state(
+            context.clone(),
+            require_write,
+        ));
+    let admin = Router::new()
+        .route(
+            "/admin/api_keys",
+            get(admin::list_api_keys).post(admin::create_api_key),
+        )
+        .route("/admin/api_keys/{name}", delete(admin::delete_api_key))
+        .route("/admin/api_keys/{name}/rotate", post(admin::rotate_api_key))
+        .route("/admin/usage", get(admin::usage))
+        .route("/admin/failures", get(admin::failures))
+        .route("/admin/failures/requeue", post(admin::requeue_failures))
+        .route_layer(middleware::from_fn_with_state(
+            context.clone(),
+            require_admin,
+        ));
+
+    Router::new()
         .route("/", get(home::index))
         .route("/metrics", get(metrics::check_health))
-        .route("/sol_dex_stream", post(qn_stream::sol_dex_stream))
+        .merge(ingest)
+        .merge(read)
+        .merge(write)
+        .merge(admin)
         .layer(DefaultBodyLimit::max(1024 * 1024 * 300))
         .layer(TraceLayer::new_for_http())
         .layer(RequestDecompressionLayer::new())
-        .with_state(context);
+        .with_state(context)
+}
+
+pub async fn start(context: WebAppContext, listen_on: &str) -> Result<()> {
+    let app = router(context);
     let listener = TcpListener::bind(listen_on).await?;
 
     info!("web server started, listen on: {}", listen_on);
@@ -0,0 +1,150 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Result;
+use chrono::{NaiveDate, Utc};
+use redis::aio::MultiplexedConnection;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::cache::{self, ApiUsage};
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Daily limits of an api key, unlimited if unset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiQuota {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_day: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes_per_day: Option<u64>,
+}
+
+impl ApiQuota {
+    /// The exceeded limit, if any
+    pub fn exceeded_by(&self, usage: &ApiUsage) -> Option<String> {
+        if let Some(max) = self.requests_per_day.filter(|max| usage.requests >= *max) {
+            return Some(format!("{max} requests per day"));
+        }
+        if let Some(max) = self.bytes_per_day.filter(|max| usage.bytes >= *max) {
+            return Some(format!("{max} bytes per day"));
+        }
+        None
+    }
+}
+
+#[derive(Debug, Default)]
+struct DailyUsage {
+    day: NaiveDate,
+    /// totals of the day in redis, counted by every instance, as of the last flush
+    flushed: HashMap<String, ApiUsage>,
+    pending: HashMap<String, ApiUsage>,
+}
+
+/// Usage counted per api key name in memory and flushed to redis periodically.
+#[derive(Debug, Default)]
+pub struct UsageMeter {
+    usage: Mutex<DailyUsage>,
+}
+
+impl UsageMeter {
+    pub fn record(&self, name: &str, usage: ApiUsage) {
+        if let Ok(mut daily) = self.usage.lock() {
+            daily
+                .pending
+                .entry(name.to_string())
+                .or_default()
+                .add(&usage);
+        }
+    }
+
+    /// Usage of the key in the current UTC day
+    pub fn today(&self, name: &str) -> ApiUsage {
+        let Ok(daily) = self.usage.lock() else {
+            return ApiUsage::default();
+        };
+        let mut usage = ApiUsage::default();
+        if daily.day == Utc::now().date_naive()
+            && let Some(flushed) = daily.flushed.get(name)
+        {
+            usage.add(flushed);
+        }
+        if let Some(pending) = daily.pending.get(name) {
+            usage.add(pending);
+        }
+        usage
+    }
+
+    async fn flush(&self, conn: &mut MultiplexedConnection) -> Result<()> {
+        let today = Utc::now().date_naive();
+        let pending: Vec<_> = match self.usage.lock() {
+            Ok(mut daily) => daily.pending.drain().collect(),
+            Err(_) => return Ok(()),
+        };
+        let totals = cache::incr_api_usage(conn, today, &pending).await?;
+
+        if let Ok(mut daily) = self.usage.lock() {
+            if daily.day != today {
+                daily.day = today;
+                daily.flushed.clear();
+            }
+            for ((name, _), total) in pending.into_iter().zip(totals) {
+                daily.flushed.insert(name, total);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Flush the metered usage to redis, the pending usage is lost if redis fails.
+pub async fn start_usage_flush(
+    redis_client: Arc<redis::Client>,
+    meter: Arc<UsageMeter>,
+) -> Result<()> {
+    info!("start api usage flush........");
+    loop {
+        tokio::time::sleep(FLUSH_INTERVAL).await;
+        let mut conn = redis_client.get_multiplexed_async_connection().await?;
+        meter.flush(&mut conn).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_quota() {
+        let meter = UsageMeter::default();
+        let quota = ApiQuota {
+            requests_per_day: Some(2),
+            bytes_per_day: None,
+        };
+        meter.record(
+            "bot",
+            ApiUsage {
+                requests: 1,
+                ws_messages: 0,
+                bytes: 100,
+            },
+        );
+        assert_eq!(quota.exceeded_by(&meter.today("bot")), None);
+
+        meter.record(
+            "bot",
+            ApiUsage {
+                requests: 1,
+                ws_messages: 3,
+                bytes: 100,
+            },
+        );
+        let usage = meter.today("bot");
+        assert_eq!(usage.ws_messages, 3);
+        assert_eq!(usage.bytes, 200);
+        assert!(quota.exceeded_by(&usage).is_some());
+        assert_eq!(meter.today("other"), ApiUsage::default());
+    }
+}
@@ -0,0 +1,284 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Utc, serde::ts_seconds};
+use serde::Serialize;
+use serde_with::{DisplayFromStr, serde_as};
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+
+use crate::cache::TradeRecord;
+
+/// Channel of the in-progress 1 minute candles of a mint, unless `naming.ws_candle` is set
+pub const WS_CANDLE_1M_CHANNEL: &str = "candle:1m:{mint}";
+const CANDLE_SECS: i64 = 60;
+
+/// The candle of a mint after the trades of a batch, with what the batch added
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CandleUpdate {
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde(with = "ts_seconds")]
+    pub start: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    /// the prices as exact decimal strings, as `price_sol_decimal` of the trades
+    pub open_decimal: String,
+    pub high_decimal: String,
+    pub low_decimal: String,
+    pub close_decimal: String,
+    pub volume_sol: f64,
+    pub trades: u64,
+    /// sol volume of the trades since the previous update of the candle
+    pub volume_sol_delta: f64,
+    pub trades_delta: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Candle {
+    start: i64,
+    open: CandlePrice,
+    high: CandlePrice,
+    low: CandlePrice,
+    close: CandlePrice,
+    volume: u64,
+    trades: u64,
+    /// (txid, idx, pool) of the trades folded in, the trades of a batch claimed again aren't
+    /// counted twice
+    folded: HashSet<(String, u64, Pubkey)>,
+}
+
+/// A price of a candle, with the decimal string of the trade it's taken from
+#[derive(Debug, Clone, Default)]
+struct CandlePrice {
+    sol: f64,
+    decimal: String,
+}
+
+impl CandlePrice {
+    fn of(trade: &TradeRecord) -> Self {
+        Self {
+            sol: trade.price_sol,
+            decimal: trade.price_sol_decimal.clone(),
+        }
+    }
+}
+
+impl Candle {
+    fn new(start: i64, trade: &TradeRecord) -> Self {
+        let price = CandlePrice::of(trade);
+        Self {
+            start,
+            open: price.clone(),
+            high: price.clone(),
+            low: price.clone(),
+            close: price,
+            volume: 0,
+            trades: 0,
+            folded: HashSet::new(),
+        }
+    }
+
+    fn add(&mut self, trade: &TradeRecord) {
+        let price = CandlePrice::of(trade);
+        if price.sol > self.high.sol {
+            self.high = price.clone();
+        }
+        if price.sol < self.low.sol {
+            self.low = price.clone();
+        }
+        self.close = price;
+        self.volume += trade.sol_amt;
+        self.trades += 1;
+    }
+}
+
+#[derive(Debug, Default)]
+struct CandleBook {
+    /// start of the latest candle, the candles before the previous minute are dropped
+    latest_start: i64,
+    by_mint: HashMap<Pubkey, Candle>,
+}
+
+/// In-progress 1 minute candles of the traded mints, by block time.
+#[derive(Debug, Default)]
+pub struct WsCandles {
+    book: Mutex<CandleBook>,
+}
+
+impl WsCandles {
+    /// Fold the trades into the candles of their minute, trades of a minute the mint's candle
+    /// already moved past, trades folded in already, price outliers and trades not quoted in SOL
+    /// are left out. Returns an update per touched candle.
+    pub fn update(&self, trades: &[&TradeRecord]) -> Vec<CandleUpdate> {
+        let mut book = self.book.lock().unwrap();
+        // (volume, trades) added per mint, in the order of the first trade
+        let mut deltas: Vec<(Pubkey, u64, u64)> = vec![];
+        for trade in trades
+            .iter()
+            .filter(|it| !it.price_outlier && it.is_sol_quoted())
+        {
+            let ts = trade.blk_ts.timestamp();
+            let start = ts - ts.rem_euclid(CANDLE_SECS);
+            let candle = book
+                .by_mint
+                .entry(trade.mint)
+                .or_insert_with(|| Candle::new(start, trade));
+            if start < candle.start {
+                continue;
+            }
+            if start > candle.start {
+                *candle = Candle::new(start, trade);
+                deltas.retain(|(mint, ..)| *mint != trade.mint);
+            }
+            if !candle
+                .folded
+                .insert((trade.txid.clone(), trade.idx, trade.pool))
+            {
+                continue;
+            }
+            candle.add(trade);
+
+            match deltas.iter_mut().find(|(mint, ..)| *mint == trade.mint) {
+                Some((_, volume, trades)) => {
+                    *volume += trade.sol_amt;
+                    *trades += 1;
+                }
+                None => deltas.push((trade.mint, trade.sol_amt, 1)),
+            }
+            if start > book.latest_start {
+                book.latest_start = start;
+            }
+        }
+
+        let oldest = book.latest_start - CANDLE_SECS;
+        book.by_mint.retain(|_, it| it.start >= oldest);
+        deltas
+            .into_iter()
+            .filter_map(|(mint, volume_delta, trades_delta)| {
+                let candle = book.by_mint.get(&mint)?;
+                Some(CandleUpdate {
+                    mint,
+                    start: DateTime::from_timestamp(candle.start, 0)?,
+                    open: candle.open.sol,
+                    high: candle.high.sol,
+                    low: candle.low.sol,
+                    close: candle.close.sol,
+                    open_decimal: candle.open.decimal.clone(),
+                    high_decimal: candle.high.decimal.clone(),
+                    low_decimal: candle.low.decimal.clone(),
+                    close_decimal: candle.close.decimal.clone(),
+                    volume_sol: candle.volume as f64 / LAMPORTS_PER_SOL as f64,
+                    trades: candle.trades,
+                    volume_sol_delta: volume_delta as f64 / LAMPORTS_PER_SOL as f64,
+                    trades_delta,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::{Dex, WSOL_MINT},
+        naming::NameTemplate,
+    };
+
+    fn trade(mint: Pubkey, ts: i64, price_sol: f64, sol_amt: u64) -> TradeRecord {
+        TradeRecord {
+            blk_ts: DateTime::from_timestamp(ts, 0).unwrap(),
+            slot: 1,
+            txid: "tx".to_string(),
+            idx: 0,
+            mint,
+            decimals: 6,
+            trader: Pubkey::new_unique(),
+            dex: Dex::Pumpfun,
+            pool: Pubkey::new_unique(),
+            pool_sol_amt: 0,
+            pool_token_amt: 0,
+            is_buy: true,
+            sol_amt,
+            token_amt: 1,
+            trader_sol_delta: 0,
+            trader_token_delta: 0,
+            price_sol,
+            price_sol_decimal: price_sol.to_string(),
+            quote_mint: WSOL_MINT,
+            price_quote: price_sol,
+            quote_amt: sol_amt,
+            pool_quote_amt: 0,
+            price_usd: None,
+            sol_price_usd_at_trade: None,
+            price_outlier: false,
+            source: None,
+            avg_prices: vec![],
+            cluster_id: None,
+            pool_drained: false,
+            pool_age_secs: None,
+            pool_trade_seq: 0,
+        }
+    }
+
+    #[test]
+    fn test_candle_updates() {
+        let candles = WsCandles::default();
+        let mint = Pubkey::new_unique();
+        let first = [
+            trade(mint, 120, 2.0, LAMPORTS_PER_SOL),
+            trade(mint, 130, 3.0, LAMPORTS_PER_SOL),
+            trade(mint, 150, 1.0, LAMPORTS_PER_SOL / 2),
+        ];
+        let updates = candles.update(&first.iter().collect::<Vec<_>>());
+        assert_eq!(updates.len(), 1);
+        let update = &updates[0];
+        assert_eq!(update.start.timestamp(), 120);
+        assert_eq!(
+            (update.open, update.high, update.low, update.close),
+            (2.0, 3.0, 1.0, 1.0)
+        );
+        assert_eq!(
+            (
+                update.open_decimal.as_str(),
+                update.high_decimal.as_str(),
+                update.low_decimal.as_str(),
+                update.close_decimal.as_str()
+            ),
+            ("2", "3", "1", "1")
+        );
+        assert_eq!(update.volume_sol, 2.5);
+        assert_eq!((update.trades, update.trades_delta), (3, 3));
+        // the batch claimed again
+        assert!(candles.update(&first.iter().collect::<Vec<_>>()).is_empty());
+
+        let mut outlier = trade(mint, 170, 100.0, LAMPORTS_PER_SOL);
+        outlier.price_outlier = true;
+        let second = [trade(mint, 175, 1.5, LAMPORTS_PER_SOL), outlier];
+        let update = &candles.update(&second.iter().collect::<Vec<_>>())[0];
+        assert_eq!((update.high, update.close), (3.0, 1.5));
+        assert_eq!(update.close_decimal, "1.5");
+        assert_eq!((update.volume_sol, update.volume_sol_delta), (3.5, 1.0));
+        assert_eq!((update.trades, update.trades_delta), (4, 1));
+
+        // a late trade of the previous minute is left out once the next candle started
+        let third = [
+            trade(mint, 185, 4.0, LAMPORTS_PER_SOL),
+            trade(mint, 170, 9.0, LAMPORTS_PER_SOL),
+        ];
+        let updates = candles.update(&third.iter().collect::<Vec<_>>());
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].start.timestamp(), 180);
+        assert_eq!((updates[0].open, updates[0].high), (4.0, 4.0));
+        assert_eq!((updates[0].trades, updates[0].trades_delta), (1, 1));
+        assert_eq!(
+            NameTemplate::new(WS_CANDLE_1M_CHANNEL).render_candle(&mint),
+            format!("candle:1m:{mint}")
+        );
+    }
+}
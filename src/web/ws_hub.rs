@@ -0,0 +1,229 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::{
+    cache::{DexEvent, DexEventKind, TradeRecord},
+    naming::NameTemplate,
+};
+
+use super::{WS_CANDLE_1M_CHANNEL, WsCandles};
+
+/// Server pings every client on this interval
+pub const WS_PING_INTERVAL: Duration = Duration::from_secs(15);
+/// Clients silent for longer than this, pongs included, are disconnected
+pub const WS_IDLE_TIMEOUT: Duration = Duration::from_secs(45);
+const WS_BROADCAST_CAPACITY: usize = 10_000;
+
+/// Channel of the dex events sent to webhook, unless `naming.ws` is set
+pub const WS_DEX_EVENTS_CHANNEL: &str = "dex_events";
+/// Channel of the trades of the followed wallets, instead of `dex_events`
+pub const WS_FOLLOWED_TRADES_CHANNEL: &str = "followed_trades";
+
+/// Text frame sent to the clients subscribed to `channel`
+#[derive(Debug, Clone)]
+pub struct WsFrame {
+    pub channel: String,
+    pub text: String,
+    /// kind of the event of a frame published by `publish_event`
+    pub event_kind: Option<DexEventKind>,
+}
+
+#[derive(Debug, Serialize)]
+struct WsFrameBody<'a, T: Serialize> {
+    channel: &'a str,
+    data: T,
+}
+
+/// Message sent by clients
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum WsClientMsg {
+    Subscribe { channels: Vec<String> },
+    Unsubscribe { channels: Vec<String> },
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct WsMetrics {
+    pub connected: u64,
+    pub total_connections: u64,
+    pub reaped: u64,
+    /// frames skipped because a client read too slowly
+    pub lagged_frames: u64,
+}
+
+/// Fan-out of frames to the websocket clients of this process.
+pub struct WsHub {
+    tx: broadcast::Sender<Arc<WsFrame>>,
+    connected: AtomicU64,
+    total_connections: AtomicU64,
+    reaped: AtomicU64,
+    lagged_frames: AtomicU64,
+    candles: WsCandles,
+    /// channel of the events sent to webhook
+    event_channel: NameTemplate,
+    /// channel of the 1 minute candles of a mint
+    candle_channel: NameTemplate,
+}
+
+impl Default for WsHub {
+    fn default() -> Self {
+        Self::new(
+            NameTemplate::new(WS_DEX_EVENTS_CHANNEL),
+            NameTemplate::new(WS_CANDLE_1M_CHANNEL),
+        )
+    }
+}
+
+impl WsHub {
+    pub fn new(event_channel: NameTemplate, candle_channel: NameTemplate) -> Self {
+        let (tx, _) = broadcast::channel(WS_BROADCAST_CAPACITY);
+        Self {
+            tx,
+            connected: AtomicU64::new(0),
+            total_connections: AtomicU64::new(0),
+            reaped: AtomicU64::new(0),
+            lagged_frames: AtomicU64::new(0),
+            candles: WsCandles::default(),
+            event_channel,
+            candle_channel,
+        }
+    }
+
+    pub fn publish(&self, channel: &str, data: impl Serialize) -> Result<()> {
+        self.send(channel, data, None)
+    }
+
+    /// Publish an event sent to webhook to its channel, the followed wallet trades to
+    /// `followed_trades`
+    pub fn publish_event(&self, evt: &DexEvent) -> Result<()> {
+        if self.tx.receiver_count() == 0 {
+            return Ok(());
+        }
+        let channel = match evt.kind() {
+            DexEventKind::FollowedWalletTrade => WS_FOLLOWED_TRADES_CHANNEL.to_string(),
+            _ => self.event_channel.render(evt),
+        };
+        self.send(&channel, evt, Some(evt.kind()))
+    }
+
+    fn send(
+        &self,
+        channel: &str,
+        data: impl Serialize,
+        event_kind: Option<DexEventKind>,
+    ) -> Result<()> {
+        if self.tx.receiver_count() == 0 {
+            return Ok(());
+        }
+
+        let text = serde_json::to_string(&WsFrameBody { channel, data })?;
+        // no receiver left is not an error
+        let _ = self.tx.send(Arc::new(WsFrame {
+            channel: channel.to_string(),
+            text,
+            event_kind,
+        }));
+        Ok(())
+    }
+
+    /// Fold the trades into the 1 minute candles and publish the updated ones to the candle
+    /// channels of their mints, the candles are kept without subscribers too
+    pub fn publish_candles(&self, trades: &[&TradeRecord]) -> Result<()> {
+        if trades.is_empty() {
+            return Ok(());
+        }
+        for update in self.candles.update(trades) {
+            self.publish(&self.candle_channel.render_candle(&update.mint), &update)?;
+        }
+        Ok(())
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<WsFrame>> {
+        self.tx.subscribe()
+    }
+
+    pub fn on_connect(&self) {
+        self.connected.fetch_add(1, Ordering::Relaxed);
+        self.total_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn on_disconnect(&self, reaped: bool) {
+        self.connected.fetch_sub(1, Ordering::Relaxed);
+        if reaped {
+            self.reaped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn on_lagged(&self, frames: u64) {
+        self.lagged_frames.fetch_add(frames, Ordering::Relaxed);
+    }
+
+    pub fn metrics(&self) -> WsMetrics {
+        WsMetrics {
+            connected: self.connected.load(Ordering::Relaxed),
+            total_connections: self.total_connections.load(Ordering::Relaxed),
+            reaped: self.reaped.load(Ordering::Relaxed),
+            lagged_frames: self.lagged_frames.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use serde_json::{Value, json};
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+    use crate::cache::MintInactiveRecord;
+
+    #[test]
+    fn test_ws_hub() {
+        let hub = WsHub::default();
+        // nobody listens, nothing is sent
+        hub.publish("a", 1).unwrap();
+        let mut frames = hub.subscribe();
+        hub.publish("a", json!({"x": 1})).unwrap();
+        let frame = frames.try_recv().unwrap();
+        assert_eq!((frame.channel.as_str(), frame.event_kind), ("a", None));
+        let body: Value = serde_json::from_str(&frame.text).unwrap();
+        assert_eq!(body, json!({"channel": "a", "data": {"x": 1}}));
+
+        let evt = DexEvent::MintInactive(MintInactiveRecord {
+            mint: Pubkey::new_unique(),
+            last_trade_ts: DateTime::from_timestamp(100, 0).unwrap(),
+        });
+        hub.publish_event(&evt).unwrap();
+        let frame = frames.try_recv().unwrap();
+        assert_eq!(frame.channel, WS_DEX_EVENTS_CHANNEL);
+        assert_eq!(frame.event_kind, Some(DexEventKind::MintInactive));
+
+        let msg: WsClientMsg =
+            serde_json::from_str(r#"{"op": "subscribe", "channels": ["a"]}"#).unwrap();
+        assert!(matches!(msg, WsClientMsg::Subscribe { channels } if channels == ["a"]));
+
+        hub.on_connect();
+        hub.on_connect();
+        hub.on_disconnect(true);
+        hub.on_lagged(3);
+        let metrics = hub.metrics();
+        assert_eq!(
+            (
+                metrics.connected,
+                metrics.total_connections,
+                metrics.reaped,
+                metrics.lagged_frames
+            ),
+            (1, 2, 1, 3)
+        );
+    }
+}
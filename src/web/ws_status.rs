@@ -0,0 +1,85 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{common::Dex, qn_req_processor::PipelineStatus};
+
+use super::WsHub;
+
+/// Channel of the pipeline status frames
+pub const WS_SYSTEM_CHANNEL: &str = "system";
+const STATUS_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SystemFrame {
+    Status {
+        processed_slot: u64,
+        /// seconds between now and the block time of the latest processed transaction
+        lag_secs: Option<i64>,
+        events_per_sec: f64,
+    },
+    /// redis used memory reached the alert ratio of maxmemory
+    RedisMemoryAlert { used_memory: u64, maxmemory: u64 },
+    /// an instruction account expected by an event went missing `count` times in a minute
+    AccountViolationAlert {
+        dex: Dex,
+        event: &'static str,
+        idx: usize,
+        count: u64,
+    },
+}
+
+impl SystemFrame {
+    /// Status of the pipeline at `now`, with the events processed since `last_events`
+    fn status(status: &PipelineStatus, last_events: u64, now: DateTime<Utc>) -> Self {
+        let events = status.processed_events();
+        let blk_ts = status.processed_blk_ts();
+        SystemFrame::Status {
+            processed_slot: status.processed_slot(),
+            lag_secs: (blk_ts > 0).then(|| now.timestamp() - blk_ts),
+            events_per_sec: (events - last_events) as f64 / STATUS_INTERVAL.as_secs_f64(),
+        }
+    }
+}
+
+/// Publish the pipeline status to the `system` channel periodically.
+pub async fn publish_status(hub: Arc<WsHub>, status: Arc<PipelineStatus>) -> Result<()> {
+    let mut interval = tokio::time::interval(STATUS_INTERVAL);
+    let mut last_events = status.processed_events();
+    loop {
+        interval.tick().await;
+        let frame = SystemFrame::status(&status, last_events, Utc::now());
+        last_events = status.processed_events();
+        hub.publish(WS_SYSTEM_CHANNEL, &frame)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_status_frame() {
+        let status = PipelineStatus::default();
+        let now = DateTime::from_timestamp(1_000, 0).unwrap();
+        // nothing processed yet, no lag to report
+        let frame = serde_json::to_value(SystemFrame::status(&status, 0, now)).unwrap();
+        assert_eq!(
+            frame,
+            json!({"type": "status", "processed_slot": 0, "lag_secs": null, "events_per_sec": 0.0})
+        );
+
+        status.record(300, 990, 60);
+        status.record(200, 980, 20);
+        let frame = serde_json::to_value(SystemFrame::status(&status, 30, now)).unwrap();
+        assert_eq!(
+            frame,
+            json!({"type": "status", "processed_slot": 300, "lag_secs": 10, "events_per_sec": 10.0})
+        );
+    }
+}
@@ -1,94 +1,421 @@
-use std::{sync::Arc, time::Duration};
+use std::{collections::HashMap, sync::Arc};
 
 use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc, serde::ts_milliseconds};
+use redis::aio::MultiplexedConnection;
 use reqwest::header;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tracing::{info, warn};
 
-use crate::cache::{self, DexPoolCreatedRecord, PumpfunCompleteRecord, TradeRecord};
+use crate::cache::{
+    self, BinPriceUpdateRecord, ConsumerCounter, DexEvent, DexEventKind, DexPoolCreatedRecord,
+    FailedSwapAttemptRecord, FollowedWalletTradeRecord, LiquidityChangeRecord,
+    LiquidityLockedRecord, MeteoraDbcCompleteRecord, MigrationCompletedRecord, MintInactiveRecord,
+    PoolDrainedRecord, PoolStateUpdatedRecord, PressureUpdateRecord, PumpfunCompleteRecord,
+    PumpfunSetParamsRecord, TradeRecord,
+};
+use crate::creator_graph;
+use crate::watchlist::{self, Watchlists};
+use crate::web::WsHub;
 
+use super::{EventPlugins, EventRule, FieldNames, RuleDeliveries, eval_rules, helius_swaps};
+
+/// Payload shape of the dex events sent to webhook.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookFormat {
+    /// An array per event kind
+    #[default]
+    Native,
+    /// Versioned `WebhookEnvelope` with the batch metadata
+    Envelope,
+    /// Array of Helius enhanced transactions, only trade events are sent.
+    Helius,
+}
+
+#[derive(Clone)]
 pub struct DexEvtWebhook {
     pub redis_client: Arc<redis::Client>,
     pub http_client: Arc<reqwest::Client>,
     pub endpoint: String,
+    pub format: WebhookFormat,
+    pub fields: FieldNames,
+    pub rules: Arc<Vec<EventRule>>,
+    pub plugins: Arc<EventPlugins>,
+    /// the webhook only sends events of this kind, one webhook runs per kind
+    pub kind: DexEventKind,
+    /// events sent to webhook are also published to the websocket clients when set
+    pub ws_hub: Option<Arc<WsHub>>,
+    /// referenced by the rules, and the events of their mints and wallets are published to
+    /// their websocket channels
+    pub watchlists: Arc<Watchlists>,
+    /// attach the cached first funders of pool creators before the plugins and rules
+    pub creator_funders: bool,
+    /// the events are read from the queue of this consumer, and counted for its metrics,
+    /// the shared queue of the kind if unset
+    pub consumer: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+/// Version of the envelope payload, bumped on breaking changes
+pub const WEBHOOK_ENVELOPE_VERSION: u32 = 1;
+
+/// Legacy payload, an array per event kind
+#[derive(Debug, Default, Serialize)]
 pub struct WebhookReq {
     pub pumpfun_complete_evts: Vec<PumpfunCompleteRecord>,
     pub pool_created_evts: Vec<DexPoolCreatedRecord>,
     pub trade_evts: Vec<TradeRecord>,
+    pub migration_completed_evts: Vec<MigrationCompletedRecord>,
+    pub mint_inactive_evts: Vec<MintInactiveRecord>,
+    pub pool_state_updated_evts: Vec<PoolStateUpdatedRecord>,
+    pub bin_price_update_evts: Vec<BinPriceUpdateRecord>,
+    pub pressure_update_evts: Vec<PressureUpdateRecord>,
+    pub followed_wallet_trade_evts: Vec<FollowedWalletTradeRecord>,
+    pub meteora_dbc_complete_evts: Vec<MeteoraDbcCompleteRecord>,
+    pub liquidity_change_evts: Vec<LiquidityChangeRecord>,
+    pub pumpfun_set_params_evts: Vec<PumpfunSetParamsRecord>,
+    pub liquidity_locked_evts: Vec<LiquidityLockedRecord>,
+    pub pool_drained_evts: Vec<PoolDrainedRecord>,
+    pub failed_swap_attempt_evts: Vec<FailedSwapAttemptRecord>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub event_tags: Vec<EventTags>,
+}
+
+impl WebhookReq {
+    fn new(events: Vec<DexEvent>, event_tags: Vec<EventTags>) -> Self {
+        let mut req = Self {
+            event_tags,
+            ..Default::default()
+        };
+        for evt in events {
+            match evt {
+                DexEvent::Trade(it) => req.trade_evts.push(it),
+                DexEvent::PoolCreated(it) => req.pool_created_evts.push(it),
+                DexEvent::PumpfunComplete(it) => req.pumpfun_complete_evts.push(it),
+                DexEvent::MigrationCompleted(it) => req.migration_completed_evts.push(it),
+                DexEvent::MintInactive(it) => req.mint_inactive_evts.push(it),
+                DexEvent::PoolStateUpdated(it) => req.pool_state_updated_evts.push(it),
+                DexEvent::BinPriceUpdate(it) => req.bin_price_update_evts.push(it),
+                DexEvent::PressureUpdate(it) => req.pressure_update_evts.push(it),
+                DexEvent::FollowedWalletTrade(it) => req.followed_wallet_trade_evts.push(it),
+                DexEvent::MeteoraDbcComplete(it) => req.meteora_dbc_complete_evts.push(it),
+                DexEvent::LiquidityChange(it) => req.liquidity_change_evts.push(it),
+                DexEvent::PumpfunSetParams(it) => req.pumpfun_set_params_evts.push(it),
+                DexEvent::LiquidityLocked(it) => req.liquidity_locked_evts.push(it),
+                DexEvent::PoolDrained(it) => req.pool_drained_evts.push(it),
+                DexEvent::FailedSwapAttempt(it) => req.failed_swap_attempt_evts.push(it),
+            }
+        }
+        req
+    }
+}
+
+/// A batch of events in one array, tagged by `kind`, with the metadata of the batch
+#[derive(Debug, Serialize)]
+pub struct WebhookEnvelope {
+    pub version: u32,
+    /// the same when a batch is sent again after a failed delivery
+    pub batch_id: String,
+    #[serde(with = "ts_milliseconds")]
+    pub emitted_at: DateTime<Utc>,
+    /// none when no event of the batch has a slot
+    pub min_slot: Option<u64>,
+    pub max_slot: Option<u64>,
+    pub counts: HashMap<DexEventKind, usize>,
+    pub events: Vec<DexEvent>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub event_tags: Vec<EventTags>,
+}
+
+impl WebhookEnvelope {
+    fn new(
+        batch_id: String,
+        emitted_at: DateTime<Utc>,
+        events: Vec<DexEvent>,
+        event_tags: Vec<EventTags>,
+    ) -> Self {
+        let slots = events.iter().filter_map(DexEvent::slot);
+        Self {
+            version: WEBHOOK_ENVELOPE_VERSION,
+            batch_id,
+            emitted_at,
+            min_slot: slots.clone().min(),
+            max_slot: slots.max(),
+            counts: count_kinds(&events),
+            events,
+            event_tags,
+        }
+    }
+}
+
+/// Order the events of a batch by `(slot, txid, idx)`, they're queued in parse order which
+/// interleaves the slots of the stream batches. Events without a slot, the ones of a mint, go
+/// first in their queued order.
+fn sort_events(events: &mut [DexEvent]) {
+    events.sort_by(|a, b| (a.slot(), a.txid(), a.idx()).cmp(&(b.slot(), b.txid(), b.idx())));
+}
+
+fn count_kinds(events: &[DexEvent]) -> HashMap<DexEventKind, usize> {
+    let mut counts = HashMap::new();
+    for evt in events {
+        *counts.entry(evt.kind()).or_default() += 1;
+    }
+    counts
+}
+
+/// Tags attached by rules to the event emitted by instruction `idx` of `txid`
+#[derive(Debug, Serialize)]
+pub struct EventTags {
+    pub txid: String,
+    pub idx: u64,
+    pub tags: Vec<String>,
 }
 
 impl DexEvtWebhook {
     pub async fn start(&self) -> Result<()> {
         loop {
             let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
-            let events = cache::lrange_dex_evts(&mut conn)
+            let consumer = self.consumer.as_deref();
+            let cache::DexEvtBatch {
+                id: batch_id,
+                mut events,
+            } = cache::claim_dex_evts(&mut conn, self.kind, consumer)
                 .await
-                .map_err(|err| anyhow!("lrange dex events error: {err}"))?;
+                .map_err(|err| anyhow!("claim dex events error: {err}"))?;
 
             let events_len = events.len();
             if events_len == 0 {
-                tokio::time::sleep(Duration::from_millis(200)).await;
+                tokio::time::sleep(self.kind.poll_interval()).await;
                 continue;
             }
+            sort_events(&mut events);
+
+            if self.creator_funders {
+                creator_graph::attach_creator_funders(&mut conn, &mut events).await?;
+            }
 
-            let mut pool_created_evts = vec![];
-            let mut trade_evts = vec![];
-            let mut pumpfun_complete_evts = vec![];
+            if let Some(ws_hub) = &self.ws_hub {
+                let trades: Vec<_> = events
+                    .iter()
+                    .filter_map(|evt| match evt {
+                        DexEvent::Trade(trade) => Some(trade),
+                        _ => None,
+                    })
+                    .collect();
+                ws_hub.publish_candles(&trades)?;
+            }
+
+            let mut sent_evts = vec![];
+            let mut event_tags = vec![];
+            let mut deliveries = RuleDeliveries::default();
 
             for evt in events {
-                match evt {
-                    cache::DexEvent::Trade(trade_record) => trade_evts.push(trade_record),
-                    cache::DexEvent::PoolCreated(dex_pool_record) => {
-                        pool_created_evts.push(dex_pool_record)
+                // a bad event or a failing plugin drops the event alone, it would block the
+                // queue of its kind otherwise
+                let (txid, idx) = (evt.txid().to_string(), evt.idx());
+                let evt = match self.plugins.transform(evt) {
+                    Ok(Some(evt)) => evt,
+                    Ok(None) => continue,
+                    Err(err) => {
+                        warn!(
+                            "wasm plugin error, drop {} event {txid}:{idx}: {err}",
+                            self.kind
+                        );
+                        continue;
+                    }
+                };
+                let outcome = match eval_rules(&self.rules, &evt, &self.watchlists)
+                    .and_then(|outcome| deliveries.add(&evt, &outcome).map(|_| outcome))
+                {
+                    Ok(outcome) => outcome,
+                    Err(err) => {
+                        warn!("rules error, drop {} event {txid}:{idx}: {err}", self.kind);
+                        continue;
+                    }
+                };
+                if outcome.drop {
+                    continue;
+                }
+                if let Some(ws_hub) = &self.ws_hub {
+                    ws_hub.publish_event(&evt)?;
+                    for name in self.watchlists.matching(&evt) {
+                        ws_hub.publish(&watchlist::watchlist_channel(&name), &evt)?;
                     }
-                    cache::DexEvent::PumpfunComplete(pump_complete_record) => {
+                }
+                if !outcome.tags.is_empty() {
+                    event_tags.push(EventTags {
+                        txid: evt.txid().to_string(),
+                        idx: evt.idx(),
+                        tags: outcome.tags,
+                    });
+                }
+                match &evt {
+                    DexEvent::PumpfunComplete(pump_complete_record) => {
                         info!("pumpfun complete, {:?}", pump_complete_record);
-                        pumpfun_complete_evts.push(pump_complete_record);
                     }
+                    DexEvent::PumpfunSetParams(set_params_record) => {
+                        info!("pumpfun params set, {:?}", set_params_record);
+                    }
+                    DexEvent::MeteoraDbcComplete(dbc_complete_record) => {
+                        info!("meteora dbc complete, {:?}", dbc_complete_record);
+                    }
+                    DexEvent::MigrationCompleted(migration_record) => {
+                        info!("pumpfun migration completed, {:?}", migration_record);
+                    }
+                    DexEvent::LiquidityLocked(locked_record) => {
+                        info!("liquidity locked, {:?}", locked_record);
+                    }
+                    DexEvent::PoolDrained(drained_record) => {
+                        info!("pool drained, {:?}", drained_record);
+                    }
+                    _ => {}
                 }
+                sent_evts.push(evt);
             }
 
-            let pump_complete_evts_len = pumpfun_complete_evts.len();
-            let pool_created_evts_len = pool_created_evts.len();
-            let trade_evts_len = trade_evts.len();
-            let req = WebhookReq {
-                pumpfun_complete_evts,
-                pool_created_evts,
-                trade_evts,
+            let counts = count_kinds(&sent_evts);
+            // counted as dropped with the events filtered out
+            let helius_txs = match self.format {
+                WebhookFormat::Helius => helius_swaps(&sent_evts),
+                WebhookFormat::Native | WebhookFormat::Envelope => vec![],
             };
+            let sent_len = match self.format {
+                WebhookFormat::Native | WebhookFormat::Envelope => sent_evts.len(),
+                WebhookFormat::Helius => helius_txs.len(),
+            };
+            if sent_len == 0 {
+                info!(
+                    "none of {events_len} {} events sent, dropped or not supported by webhook format",
+                    self.kind
+                );
+                cache::ack_dex_evts(&mut conn, self.kind, consumer).await?;
+                self.count(&mut conn, ConsumerCounter::Dropped, events_len)
+                    .await?;
+                deliveries.send(&self.http_client).await;
+                continue;
+            }
 
             info!(
-                "send total {} dex events to webhook: {}",
-                events_len, self.endpoint
+                "send total {} {} events of batch {} to webhook: {}",
+                events_len, self.kind, batch_id, self.endpoint
             );
-            info!(
-                "contain {} trade events, {} pool created events, {} pump complete events",
-                trade_evts_len, pool_created_evts_len, pump_complete_evts_len,
-            );
-            let msg = serde_json::to_string(&req)
-                .map_err(|err| anyhow!("failed serialize dex events from redis: {err}"))?;
+            info!("contain events by kind: {:?}", counts);
+            let msg = match self.format {
+                WebhookFormat::Native => self
+                    .fields
+                    .to_string(&WebhookReq::new(sent_evts, event_tags)),
+                WebhookFormat::Helius => self.fields.to_string(&helius_txs),
+                WebhookFormat::Envelope => self.fields.to_string(&WebhookEnvelope::new(
+                    batch_id,
+                    Utc::now(),
+                    sent_evts,
+                    event_tags,
+                )),
+            }
+            .map_err(|err| anyhow!("failed serialize dex events from redis: {err}"))?;
             let webhook_resp = self
                 .http_client
                 .post(&self.endpoint)
                 .header(header::CONTENT_TYPE, "application/json")
                 .body(msg)
                 .send()
-                .await
-                .map_err(|err| anyhow!("send dex events to webhhook failed: {err}"))?;
+                .await;
+            let webhook_resp = match webhook_resp {
+                Ok(resp) => resp,
+                Err(err) => {
+                    self.count(&mut conn, ConsumerCounter::Failed, 1).await?;
+                    return Err(anyhow!("send dex events to webhhook failed: {err}"));
+                }
+            };
 
             let webhook_resp_status = webhook_resp.status();
             if webhook_resp_status == reqwest::StatusCode::OK {
-                cache::ltrim_dex_evts(&mut conn, events_len).await?;
+                cache::ack_dex_evts(&mut conn, self.kind, consumer).await?;
+                self.count(&mut conn, ConsumerCounter::Sent, sent_len)
+                    .await?;
+                self.count(&mut conn, ConsumerCounter::Dropped, events_len - sent_len)
+                    .await?;
+                deliveries.send(&self.http_client).await;
             } else {
                 warn!(
                     "send dex events to webhook failed, status is not 200 is: {webhook_resp_status}"
                 );
+                self.count(&mut conn, ConsumerCounter::Failed, 1).await?;
             }
 
-            tokio::time::sleep(Duration::from_millis(500)).await;
+            tokio::time::sleep(self.kind.send_interval()).await;
         }
     }
+
+    /// Only the events of consumers are counted
+    async fn count(
+        &self,
+        conn: &mut MultiplexedConnection,
+        counter: ConsumerCounter,
+        n: usize,
+    ) -> Result<()> {
+        let Some(consumer) = &self.consumer else {
+            return Ok(());
+        };
+        cache::incr_consumer_counter(conn, consumer, counter, n as u64).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+
+    fn bin_price_update(slot: u64) -> DexEvent {
+        DexEvent::BinPriceUpdate(BinPriceUpdateRecord {
+            ts: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            slot,
+            lb_pair: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            active_id: 10,
+            price_sol: 0.001,
+        })
+    }
+
+    #[test]
+    fn test_webhook_envelope() {
+        let events = vec![
+            bin_price_update(300),
+            DexEvent::MintInactive(MintInactiveRecord {
+                mint: Pubkey::new_unique(),
+                last_trade_ts: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            }),
+            bin_price_update(200),
+        ];
+        let emitted_at = DateTime::from_timestamp_millis(1_700_000_000_123).unwrap();
+        let envelope = WebhookEnvelope::new("batch".to_string(), emitted_at, events, vec![]);
+        assert_eq!(envelope.min_slot, Some(200));
+        assert_eq!(envelope.max_slot, Some(300));
+        assert_eq!(envelope.counts[&DexEventKind::BinPriceUpdate], 2);
+        assert_eq!(envelope.counts[&DexEventKind::MintInactive], 1);
+
+        let json = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(json["version"], WEBHOOK_ENVELOPE_VERSION);
+        assert_eq!(json["emitted_at"], 1_700_000_000_123i64);
+        assert_eq!(json["counts"]["BinPriceUpdate"], 2);
+        assert_eq!(json["events"][1]["kind"], "MintInactive");
+        assert!(json.get("event_tags").is_none());
+
+        let empty = WebhookEnvelope::new("batch".to_string(), emitted_at, vec![], vec![]);
+        assert_eq!(empty.min_slot, None);
+        assert!(empty.counts.is_empty());
+    }
+
+    #[test]
+    fn test_sort_events() {
+        let mut events = vec![
+            bin_price_update(300),
+            bin_price_update(100),
+            bin_price_update(200),
+        ];
+        sort_events(&mut events);
+        let slots: Vec<_> = events.iter().filter_map(DexEvent::slot).collect();
+        assert_eq!(slots, vec![100, 200, 300]);
+    }
 }
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Field names of the payloads sent out, so the receivers don't follow the event structs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldNames {
+    /// The field names of the event structs
+    #[default]
+    SnakeCase,
+    /// The snake case names in camel case, `base_mint` as `baseMint`
+    CamelCase,
+    /// The names of the first payloads, a field renamed since keeps its old name
+    V1,
+}
+
+/// (field name, its v1 name) of the event fields renamed since the first payloads, add a pair
+/// when a field is renamed
+const V1_FIELD_NAMES: &[(&str, &str)] = &[];
+
+impl FieldNames {
+    pub fn to_string(self, payload: &impl Serialize) -> serde_json::Result<String> {
+        match self {
+            Self::SnakeCase => serde_json::to_string(payload),
+            _ => serde_json::to_string(&self.to_value(payload)?),
+        }
+    }
+
+    pub fn to_vec(self, payload: &impl Serialize) -> serde_json::Result<Vec<u8>> {
+        match self {
+            Self::SnakeCase => serde_json::to_vec(payload),
+            _ => serde_json::to_vec(&self.to_value(payload)?),
+        }
+    }
+
+    fn to_value(self, payload: &impl Serialize) -> serde_json::Result<Value> {
+        let mut value = serde_json::to_value(payload)?;
+        match self {
+            Self::SnakeCase => {}
+            Self::CamelCase => rename_keys(&mut value, &|key| Some(camel_case(key))),
+            Self::V1 => rename_keys(&mut value, &|key| {
+                V1_FIELD_NAMES
+                    .iter()
+                    .find(|(name, _)| *name == key)
+                    .map(|(_, v1_name)| v1_name.to_string())
+            }),
+        }
+        Ok(value)
+    }
+}
+
+/// Rename the keys of the objects at any depth, `rename` gives none to keep a key
+fn rename_keys(value: &mut Value, rename: &impl Fn(&str) -> Option<String>) {
+    match value {
+        Value::Object(map) => {
+            *map = std::mem::take(map)
+                .into_iter()
+                .map(|(key, mut value)| {
+                    rename_keys(&mut value, rename);
+                    (rename(&key).unwrap_or(key), value)
+                })
+                .collect();
+        }
+        Value::Array(values) => {
+            for value in values {
+                rename_keys(value, rename);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn camel_case(name: &str) -> String {
+    let mut camel = String::with_capacity(name.len());
+    let mut upper = false;
+    for ch in name.chars() {
+        if ch == '_' && !camel.is_empty() {
+            upper = true;
+        } else if upper {
+            camel.extend(ch.to_uppercase());
+            upper = false;
+        } else {
+            camel.push(ch);
+        }
+    }
+    camel
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_field_names() {
+        let payload = json!({
+            "trade_evts": [{"kind": "Trade", "base_mint": "m", "is_buy": true}],
+            "counts": {"PoolCreated": 1},
+            "_private": 1,
+        });
+        assert_eq!(
+            FieldNames::CamelCase.to_string(&payload).unwrap(),
+            json!({
+                "tradeEvts": [{"kind": "Trade", "baseMint": "m", "isBuy": true}],
+                "counts": {"PoolCreated": 1},
+                "_private": 1,
+            })
+            .to_string()
+        );
+        assert_eq!(
+            FieldNames::V1.to_string(&payload).unwrap(),
+            payload.to_string()
+        );
+
+        let mut value = json!({"evts": [{"sol_amt": 1}]});
+        rename_keys(&mut value, &|key| {
+            (key == "sol_amt").then(|| "sol".to_string())
+        });
+        assert_eq!(value, json!({"evts": [{"sol": 1}]}));
+    }
+}
@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    cache::{DexEvent, TradeRecord},
+    common::{Dex, utils},
+};
+
+/// Trade in the shape of a Helius "enhanced transaction" of type `SWAP`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeliusEnhancedTx {
+    pub description: String,
+    #[serde(rename = "type")]
+    pub tx_type: String,
+    pub source: String,
+    pub fee: u64,
+    pub fee_payer: String,
+    pub signature: String,
+    pub slot: u64,
+    pub timestamp: i64,
+    pub token_transfers: Vec<HeliusTokenTransfer>,
+    pub native_transfers: Vec<HeliusNativeTransfer>,
+    pub account_data: Vec<serde_json::Value>,
+    pub transaction_error: Option<serde_json::Value>,
+    pub instructions: Vec<serde_json::Value>,
+    pub events: HeliusEvents,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeliusTokenTransfer {
+    pub from_user_account: String,
+    pub to_user_account: String,
+    pub from_token_account: String,
+    pub to_token_account: String,
+    pub token_amount: f64,
+    pub mint: String,
+    pub token_standard: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeliusNativeTransfer {
+    pub from_user_account: String,
+    pub to_user_account: String,
+    pub amount: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HeliusEvents {
+    pub swap: Option<HeliusSwapEvent>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeliusSwapEvent {
+    pub native_input: Option<HeliusNativeAmount>,
+    pub native_output: Option<HeliusNativeAmount>,
+    pub token_inputs: Vec<HeliusTokenAmount>,
+    pub token_outputs: Vec<HeliusTokenAmount>,
+    pub token_fees: Vec<HeliusTokenAmount>,
+    pub native_fees: Vec<HeliusNativeAmount>,
+    pub inner_swaps: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HeliusNativeAmount {
+    pub account: String,
+    pub amount: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeliusTokenAmount {
+    pub user_account: String,
+    pub token_account: String,
+    pub mint: String,
+    pub raw_token_amount: HeliusRawTokenAmount,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeliusRawTokenAmount {
+    pub token_amount: String,
+    pub decimals: u8,
+}
+
+pub fn helius_source(dex: Dex) -> &'static str {
+    match dex {
+        Dex::RaydiumAmm => "RAYDIUM",
+        Dex::Pumpfun => "PUMP_FUN",
+        Dex::PumpAmm => "PUMP_AMM",
+        Dex::MeteoraDlmm | Dex::MeteoraDamm | Dex::MeteoraDbc => "METEORA",
+        Dex::RaydiumClmm | Dex::RaydiumCpmm => "RAYDIUM",
+        Dex::OrcaWhirlpool => "ORCA",
+        Dex::Moonshot => "MOONSHOT",
+    }
+}
+
+/// The SOL trades of the events as helius swaps. The other events, and the trades in other quote
+/// mints, have no helius shape, they're logged by kind and left out.
+pub fn helius_swaps(events: &[DexEvent]) -> Vec<HeliusEnhancedTx> {
+    let mut skipped: HashMap<String, usize> = HashMap::new();
+    let mut txs = vec![];
+    for evt in events {
+        match evt {
+            DexEvent::Trade(trade) if trade.is_sol_quoted() => txs.push(trade.into()),
+            DexEvent::Trade(_) => *skipped.entry("Trade not in SOL".to_string()).or_default() += 1,
+            _ => *skipped.entry(evt.kind().to_string()).or_default() += 1,
+        }
+    }
+    if !skipped.is_empty() {
+        info!("events without a helius swap shape skipped: {skipped:?}");
+    }
+    txs
+}
+
+impl From<&TradeRecord> for HeliusEnhancedTx {
+    fn from(trade: &TradeRecord) -> Self {
+        let trader = trade.trader.to_string();
+        let pool = trade.pool.to_string();
+        let mint = trade.mint.to_string();
+        let sol_ui_amt = trade.sol_amt as f64 / 1_000_000_000.0f64;
+        let token_ui_amt = utils::ui_amount(trade.token_amt, trade.decimals);
+
+        let native_amount = HeliusNativeAmount {
+            account: trader.clone(),
+            amount: trade.sol_amt.to_string(),
+        };
+        let token_amount = HeliusTokenAmount {
+            user_account: trader.clone(),
+            token_account: String::new(),
+            mint: mint.clone(),
+            raw_token_amount: HeliusRawTokenAmount {
+                token_amount: trade.token_amt.to_string(),
+                decimals: trade.decimals,
+            },
+        };
+
+        let (description, token_transfer, native_transfer, swap) = if trade.is_buy {
+            (
+                format!("{trader} swapped {sol_ui_amt} SOL for {token_ui_amt} {mint}"),
+                HeliusTokenTransfer {
+                    from_user_account: pool.clone(),
+                    to_user_account: trader.clone(),
+                    from_token_account: String::new(),
+                    to_token_account: String::new(),
+                    token_amount: token_ui_amt,
+                    mint: mint.clone(),
+                    token_standard: "Fungible".to_string(),
+                },
+                HeliusNativeTransfer {
+                    from_user_account: trader.clone(),
+                    to_user_account: pool.clone(),
+                    amount: trade.sol_amt,
+                },
+                HeliusSwapEvent {
+                    native_input: Some(native_amount),
+                    native_output: None,
+                    token_inputs: vec![],
+                    token_outputs: vec![token_amount],
+                    token_fees: vec![],
+                    native_fees: vec![],
+                    inner_swaps: vec![],
+                },
+            )
+        } else {
+            (
+                format!("{trader} swapped {token_ui_amt} {mint} for {sol_ui_amt} SOL"),
+                HeliusTokenTransfer {
+                    from_user_account: trader.clone(),
+                    to_user_account: pool.clone(),
+                    from_token_account: String::new(),
+                    to_token_account: String::new(),
+                    token_amount: token_ui_amt,
+                    mint: mint.clone(),
+                    token_standard: "Fungible".to_string(),
+                },
+                HeliusNativeTransfer {
+                    from_user_account: pool.clone(),
+                    to_user_account: trader.clone(),
+                    amount: trade.sol_amt,
+                },
+                HeliusSwapEvent {
+                    native_input: None,
+                    native_output: Some(native_amount),
+                    token_inputs: vec![token_amount],
+                    token_outputs: vec![],
+                    token_fees: vec![],
+                    native_fees: vec![],
+                    inner_swaps: vec![],
+                },
+            )
+        };
+
+        Self {
+            description,
+            tx_type: "SWAP".to_string(),
+            source: helius_source(trade.dex).to_string(),
+            fee: 0,
+            fee_payer: trader,
+            signature: trade.txid.clone(),
+            slot: trade.slot,
+            timestamp: trade.blk_ts.timestamp(),
+            token_transfers: vec![token_transfer],
+            native_transfers: vec![native_transfer],
+            account_data: vec![],
+            transaction_error: None,
+            instructions: vec![],
+            events: HeliusEvents { swap: Some(swap) },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use serde_json::json;
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+    use crate::{
+        cache::MintInactiveRecord,
+        common::{USDC_MINT, WSOL_MINT},
+    };
+
+    fn trade(is_buy: bool) -> TradeRecord {
+        TradeRecord {
+            blk_ts: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            slot: 250_000_000,
+            txid: "sig".to_string(),
+            idx: 0,
+            mint: Pubkey::new_from_array([1; 32]),
+            decimals: 6,
+            trader: Pubkey::new_from_array([2; 32]),
+            dex: Dex::Pumpfun,
+            pool: Pubkey::new_from_array([3; 32]),
+            pool_sol_amt: 0,
+            pool_token_amt: 0,
+            is_buy,
+            sol_amt: 1_500_000_000,
+            token_amt: 2_000_000,
+            trader_sol_delta: 0,
+            trader_token_delta: 0,
+            price_sol: 0.00075,
+            price_sol_decimal: "0.00075".to_string(),
+            quote_mint: WSOL_MINT,
+            price_quote: 0.00075,
+            quote_amt: 1_500_000_000,
+            pool_quote_amt: 0,
+            price_usd: None,
+            sol_price_usd_at_trade: None,
+            price_outlier: false,
+            source: None,
+            avg_prices: vec![],
+            cluster_id: None,
+            pool_drained: false,
+            pool_age_secs: None,
+            pool_trade_seq: 0,
+        }
+    }
+
+    #[test]
+    fn test_helius_tx_from_trade() {
+        let trade = trade(true);
+        let (mint, trader, pool) = (
+            trade.mint.to_string(),
+            trade.trader.to_string(),
+            trade.pool.to_string(),
+        );
+        let tx = serde_json::to_value(HeliusEnhancedTx::from(&trade)).unwrap();
+        assert_eq!(
+            tx,
+            json!({
+                "description": format!("{trader} swapped 1.5 SOL for 2 {mint}"),
+                "type": "SWAP",
+                "source": "PUMP_FUN",
+                "fee": 0,
+                "feePayer": trader,
+                "signature": "sig",
+                "slot": 250_000_000,
+                "timestamp": 1_700_000_000,
+                "tokenTransfers": [{
+                    "fromUserAccount": pool,
+                    "toUserAccount": trader,
+                    "fromTokenAccount": "",
+                    "toTokenAccount": "",
+                    "tokenAmount": 2.0,
+                    "mint": mint,
+                    "tokenStandard": "Fungible",
+                }],
+                "nativeTransfers": [{
+                    "fromUserAccount": trader,
+                    "toUserAccount": pool,
+                    "amount": 1_500_000_000u64,
+                }],
+                "accountData": [],
+                "transactionError": null,
+                "instructions": [],
+                "events": {"swap": {
+                    "nativeInput": {"account": trader, "amount": "1500000000"},
+                    "nativeOutput": null,
+                    "tokenInputs": [],
+                    "tokenOutputs": [{
+                        "userAccount": trader,
+                        "tokenAccount": "",
+                        "mint": mint,
+                        "rawTokenAmount": {"tokenAmount": "2000000", "decimals": 6},
+                    }],
+                    "tokenFees": [],
+                    "nativeFees": [],
+                    "innerSwaps": [],
+                }},
+            })
+        );
+
+        // a sell swaps the sides
+        let tx = HeliusEnhancedTx::from(&self::trade(false));
+        assert_eq!(
+            tx.description,
+            format!("{trader} swapped 2 {mint} for 1.5 SOL")
+        );
+        let swap = tx.events.swap.unwrap();
+        assert!(swap.native_input.is_none());
+        assert_eq!(swap.native_output.unwrap().amount, "1500000000");
+        assert_eq!(
+            swap.token_inputs[0].raw_token_amount.token_amount,
+            "2000000"
+        );
+        assert!(swap.token_outputs.is_empty());
+        assert_eq!(tx.token_transfers[0].from_user_account, trader);
+        assert_eq!(tx.native_transfers[0].to_user_account, trader);
+    }
+
+    #[test]
+    fn test_helius_swaps() {
+        let usdc_trade = TradeRecord {
+            quote_mint: USDC_MINT,
+            ..trade(true)
+        };
+        let events = vec![
+            DexEvent::Trade(trade(true)),
+            DexEvent::Trade(usdc_trade),
+            DexEvent::MintInactive(MintInactiveRecord {
+                mint: Pubkey::new_unique(),
+                last_trade_ts: DateTime::from_timestamp(1, 0).unwrap(),
+            }),
+            DexEvent::Trade(trade(false)),
+        ];
+        let txs = helius_swaps(&events);
+        assert_eq!(txs.len(), 2);
+        assert!(txs.iter().all(|it| it.tx_type == "SWAP"));
+    }
+}
@@ -1,3 +1,13 @@
 mod dex_evts;
+mod fields;
+mod helius;
+mod plugin;
+mod router;
+mod rules;
 
 pub use dex_evts::*;
+pub use fields::*;
+pub use helius::*;
+pub use plugin::*;
+pub use router::*;
+pub use rules::*;
@@ -0,0 +1,178 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use crate::cache::DexEvent;
+
+/// User provided WASM modules transforming dex events before the rules run.
+///
+/// A plugin module exports `memory`, `alloc(len: i32) -> i32` and
+/// `transform(ptr: i32, len: i32) -> i64`, which takes the event json and returns
+/// `(ptr << 32) | len` of the transformed event json, or 0 to drop the event.
+/// The transformed json must still be a valid dex event, unknown fields are ignored.
+/// Each call runs on a fuel budget, a plugin looping past it traps instead of hanging the sender.
+#[derive(Default)]
+pub struct EventPlugins {
+    #[cfg(feature = "wasm-plugin")]
+    plugins: Vec<std::sync::Mutex<wasm::WasmPlugin>>,
+}
+
+impl EventPlugins {
+    #[cfg(feature = "wasm-plugin")]
+    pub fn load(paths: &[PathBuf]) -> Result<Self> {
+        let engine = wasm::engine()?;
+        let mut plugins = vec![];
+        for path in paths {
+            let module = wasmtime::Module::from_file(&engine, path).map_err(|err| {
+                anyhow::anyhow!("load wasm plugin {} error: {err}", path.display())
+            })?;
+            plugins.push(std::sync::Mutex::new(wasm::WasmPlugin::new(
+                &engine, &module,
+            )?));
+        }
+        Ok(Self { plugins })
+    }
+
+    #[cfg(not(feature = "wasm-plugin"))]
+    pub fn load(paths: &[PathBuf]) -> Result<Self> {
+        if !paths.is_empty() {
+            anyhow::bail!("wasm plugins configured but built without the `wasm-plugin` feature");
+        }
+        Ok(Self::default())
+    }
+
+    /// `None` if a plugin dropped the event
+    #[cfg(feature = "wasm-plugin")]
+    pub fn transform(&self, evt: DexEvent) -> Result<Option<DexEvent>> {
+        if self.plugins.is_empty() {
+            return Ok(Some(evt));
+        }
+
+        let mut json = serde_json::to_vec(&evt)?;
+        for plugin in &self.plugins {
+            let mut plugin = plugin
+                .lock()
+                .map_err(|_| anyhow::anyhow!("wasm plugin lock poisoned"))?;
+            match plugin.transform(&json)? {
+                Some(transformed) => json = transformed,
+                None => return Ok(None),
+            }
+        }
+        let evt = serde_json::from_slice(&json)?;
+        Ok(Some(evt))
+    }
+
+    #[cfg(not(feature = "wasm-plugin"))]
+    pub fn transform(&self, evt: DexEvent) -> Result<Option<DexEvent>> {
+        Ok(Some(evt))
+    }
+}
+
+#[cfg(feature = "wasm-plugin")]
+mod wasm {
+    use anyhow::{Result, anyhow};
+    use wasmtime::{Config, Engine, Instance, Memory, Module, Store, TypedFunc};
+
+    /// wasm instructions a plugin may run per event, roughly
+    const FUEL_PER_CALL: u64 = 100_000_000;
+
+    pub fn engine() -> Result<Engine> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        Engine::new(&config)
+    }
+
+    pub struct WasmPlugin {
+        store: Store<()>,
+        memory: Memory,
+        alloc: TypedFunc<i32, i32>,
+        transform: TypedFunc<(i32, i32), i64>,
+    }
+
+    impl WasmPlugin {
+        pub fn new(engine: &Engine, module: &Module) -> Result<Self> {
+            let mut store = Store::new(engine, ());
+            let instance = Instance::new(&mut store, module, &[])?;
+            let memory = instance
+                .get_memory(&mut store, "memory")
+                .ok_or_else(|| anyhow!("wasm plugin should export memory"))?;
+            let alloc = instance.get_typed_func(&mut store, "alloc")?;
+            let transform = instance.get_typed_func(&mut store, "transform")?;
+            Ok(Self {
+                store,
+                memory,
+                alloc,
+                transform,
+            })
+        }
+
+        pub fn transform(&mut self, json: &[u8]) -> Result<Option<Vec<u8>>> {
+            let len = i32::try_from(json.len())?;
+            // for the alloc and the transform, refilled for every event
+            self.store.set_fuel(FUEL_PER_CALL)?;
+            let ptr = self.alloc.call(&mut self.store, len)?;
+            self.memory.write(&mut self.store, ptr as usize, json)?;
+
+            let packed = self.transform.call(&mut self.store, (ptr, len))?;
+            if packed == 0 {
+                return Ok(None);
+            }
+            let (out_ptr, out_len) = ((packed >> 32) as u32 as usize, packed as u32 as usize);
+            let mut out = vec![0; out_len];
+            self.memory.read(&self.store, out_ptr, &mut out)?;
+            Ok(Some(out))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_wasm_plugin_transform() {
+            // returns the input when it's longer than 4 bytes, drops it otherwise
+            let wat = r#"
+                (module
+                  (memory (export "memory") 1)
+                  (func (export "alloc") (param i32) (result i32) i32.const 1024)
+                  (func (export "transform") (param i32 i32) (result i64)
+                    local.get 1
+                    i32.const 4
+                    i32.le_u
+                    if
+                      i64.const 0
+                      return
+                    end
+                    local.get 0
+                    i64.extend_i32_u
+                    i64.const 32
+                    i64.shl
+                    local.get 1
+                    i64.extend_i32_u
+                    i64.or))
+            "#;
+            let engine = engine().unwrap();
+            let module = Module::new(&engine, wat).unwrap();
+            let mut plugin = WasmPlugin::new(&engine, &module).unwrap();
+            assert_eq!(
+                plugin.transform(br#"{"a":1}"#).unwrap(),
+                Some(br#"{"a":1}"#.to_vec())
+            );
+            assert_eq!(plugin.transform(b"{}").unwrap(), None);
+
+            // out of fuel, the next events still run
+            let looping = r#"
+                (module
+                  (memory (export "memory") 1)
+                  (func (export "alloc") (param i32) (result i32) i32.const 1024)
+                  (func (export "transform") (param i32 i32) (result i64)
+                    (loop br 0)
+                    i64.const 0))
+            "#;
+            let module = Module::new(&engine, looping).unwrap();
+            let mut plugin = WasmPlugin::new(&engine, &module).unwrap();
+            assert!(plugin.transform(b"{}").is_err());
+            assert!(plugin.transform(b"{}").is_err());
+        }
+    }
+}
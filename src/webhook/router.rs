@@ -0,0 +1,29 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use tracing::info;
+
+use crate::cache::{self, ConsumerCounter, DexEventKind};
+
+/// Copy the events of the kind from its shared queue to the queues of the consumers.
+pub async fn start_router(
+    redis_client: Arc<redis::Client>,
+    kind: DexEventKind,
+    consumers: Vec<String>,
+) -> Result<()> {
+    info!("start {kind} event router to consumers {consumers:?}........");
+    loop {
+        let mut conn = redis_client.get_multiplexed_async_connection().await?;
+        let batch = cache::route_dex_evts(&mut conn, kind, &consumers).await?;
+        if batch.events == 0 {
+            drop(conn);
+            tokio::time::sleep(kind.poll_interval()).await;
+            continue;
+        }
+        for consumer in &batch.overflowed {
+            let events = batch.events as u64;
+            cache::incr_consumer_counter(&mut conn, consumer, ConsumerCounter::Overflowed, events)
+                .await?;
+        }
+    }
+}
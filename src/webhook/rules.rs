@@ -0,0 +1,415 @@
+use std::collections::HashMap;
+
+use anyhow::{Result, anyhow};
+use reqwest::header;
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::warn;
+
+use crate::{
+    cache::DexEvent,
+    watchlist::{WATCHLIST_PREFIX, Watchlists},
+};
+
+/// Operator defined rule evaluated on every dex event before it's sent to webhook.
+///
+/// Rules run in order and every matching rule applies its action,
+/// a matching `drop` rule stops the evaluation of the rules after it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EventRule {
+    pub name: String,
+    /// all conditions must match, a rule without conditions matches every event
+    #[serde(default)]
+    pub conditions: Vec<RuleCondition>,
+    pub action: RuleAction,
+}
+
+/// Compare a top level field of the event json, e.g. `kind`, `dex`, `sol_amt`. `sol_amt` of a
+/// trade is zero when its pool isn't quoted in WSOL, `quote_amt` is the amount of its `quote_mint`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RuleCondition {
+    pub field: String,
+    pub op: RuleOp,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleOp {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    /// `value` is an array containing the field value, or `watchlist:{name}` of a watchlist
+    /// containing the field value
+    In,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleAction {
+    /// attach the tag to the event in the webhook request
+    Tag { tag: String },
+    /// don't send the event to webhook
+    Drop,
+    /// also send the event to another webhook endpoint
+    Route { endpoint: String },
+    /// send a telegram message about the event
+    Telegram { bot_token: String, chat_id: String },
+}
+
+/// Result of evaluating the rules on one event.
+#[derive(Debug, Default, PartialEq)]
+pub struct RuleOutcome {
+    pub drop: bool,
+    pub tags: Vec<String>,
+    pub routes: Vec<String>,
+    /// (rule name, telegram action) pairs
+    pub notifications: Vec<(String, RuleAction)>,
+}
+
+impl RuleCondition {
+    fn matches(&self, evt: &Value, watchlists: &Watchlists) -> bool {
+        let Some(field) = evt.get(&self.field) else {
+            return false;
+        };
+        if self.op == RuleOp::In
+            && let Some(name) = self
+                .value
+                .as_str()
+                .and_then(|it| it.strip_prefix(WATCHLIST_PREFIX))
+        {
+            return field
+                .as_str()
+                .and_then(|it| it.parse().ok())
+                .is_some_and(|it| watchlists.contains(name, &it));
+        }
+
+        match self.op {
+            RuleOp::Eq => json_eq(field, &self.value),
+            RuleOp::Ne => !json_eq(field, &self.value),
+            RuleOp::Gt => json_cmp(field, &self.value).is_some_and(|it| it.is_gt()),
+            RuleOp::Gte => json_cmp(field, &self.value).is_some_and(|it| it.is_ge()),
+            RuleOp::Lt => json_cmp(field, &self.value).is_some_and(|it| it.is_lt()),
+            RuleOp::Lte => json_cmp(field, &self.value).is_some_and(|it| it.is_le()),
+            RuleOp::In => self
+                .value
+                .as_array()
+                .is_some_and(|it| it.iter().any(|it| json_eq(field, it))),
+        }
+    }
+}
+
+fn json_eq(a: &Value, b: &Value) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn json_cmp(a: &Value, b: &Value) -> Option<std::cmp::Ordering> {
+    a.as_f64()?.partial_cmp(&b.as_f64()?)
+}
+
+pub fn eval_rules(
+    rules: &[EventRule],
+    evt: &DexEvent,
+    watchlists: &Watchlists,
+) -> Result<RuleOutcome> {
+    let mut outcome = RuleOutcome::default();
+    if rules.is_empty() {
+        return Ok(outcome);
+    }
+
+    let evt = serde_json::to_value(evt)?;
+    for rule in rules {
+        if !rule
+            .conditions
+            .iter()
+            .all(|it| it.matches(&evt, watchlists))
+        {
+            continue;
+        }
+        match &rule.action {
+            RuleAction::Tag { tag } => outcome.tags.push(tag.clone()),
+            RuleAction::Drop => {
+                outcome.drop = true;
+                break;
+            }
+            RuleAction::Route { endpoint } => outcome.routes.push(endpoint.clone()),
+            action @ RuleAction::Telegram { .. } => {
+                outcome
+                    .notifications
+                    .push((rule.name.clone(), action.clone()));
+            }
+        }
+    }
+
+    Ok(outcome)
+}
+
+/// Events routed to other endpoints and telegram messages of one webhook batch,
+/// delivered best effort, failures are only logged.
+#[derive(Debug, Default)]
+pub struct RuleDeliveries {
+    pub routes: HashMap<String, Vec<Value>>,
+    /// (bot token, chat id) -> message lines
+    pub telegram: HashMap<(String, String), Vec<String>>,
+}
+
+impl RuleDeliveries {
+    pub fn add(&mut self, evt: &DexEvent, outcome: &RuleOutcome) -> Result<()> {
+        if outcome.routes.is_empty() && outcome.notifications.is_empty() {
+            return Ok(());
+        }
+
+        let evt_json = serde_json::to_value(evt)?;
+        for endpoint in &outcome.routes {
+            self.routes
+                .entry(endpoint.clone())
+                .or_default()
+                .push(evt_json.clone());
+        }
+        for (rule_name, action) in &outcome.notifications {
+            if let RuleAction::Telegram { bot_token, chat_id } = action {
+                self.telegram
+                    .entry((bot_token.clone(), chat_id.clone()))
+                    .or_default()
+                    .push(format!("[{rule_name}] {}", evt_summary(evt)));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn send(self, http_client: &reqwest::Client) {
+        for (endpoint, evts) in self.routes {
+            if let Err(err) = post_json(http_client, &endpoint, &evts).await {
+                warn!("send routed dex events to {endpoint} failed: {err}");
+            }
+        }
+        for ((bot_token, chat_id), lines) in self.telegram {
+            let url = format!("https://api.telegram.org/bot{bot_token}/sendMessage");
+            let msg = serde_json::json!({"chat_id": chat_id, "text": lines.join("\n")});
+            if let Err(err) = post_json(http_client, &url, &msg).await {
+                warn!("send telegram notification to chat {chat_id} failed: {err}");
+            }
+        }
+    }
+}
+
+async fn post_json(
+    http_client: &reqwest::Client,
+    url: &str,
+    body: &impl serde::Serialize,
+) -> Result<()> {
+    let resp = http_client
+        .post(url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(serde_json::to_string(body)?)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        return Err(anyhow!("response status: {}", resp.status()));
+    }
+    Ok(())
+}
+
+fn evt_summary(evt: &DexEvent) -> String {
+    match evt {
+        DexEvent::Trade(it) => format!(
+            "{} {} {} of {} on {}, tx: {}",
+            it.trader,
+            if it.is_buy { "bought" } else { "sold" },
+            it.quote_ui_amt(),
+            it.mint,
+            it.dex,
+            it.txid
+        ),
+        DexEvent::PoolCreated(it) => format!(
+            "pool {} of {} / {} created on {}, tx: {}",
+            it.addr, it.mint_a, it.mint_b, it.dex, it.txid
+        ),
+        DexEvent::PumpfunComplete(it) => {
+            format!("pumpfun curve of {} completed, tx: {}", it.mint, it.txid)
+        }
+        DexEvent::PumpfunSetParams(it) => format!(
+            "pumpfun params set, initial virtual reserves {} sol {} tokens, fee {} bps, tx: {}",
+            it.initial_virtual_sol_reserves as f64 / 1_000_000_000.0,
+            it.initial_virtual_token_reserves,
+            it.fee_basis_points,
+            it.txid
+        ),
+        DexEvent::MeteoraDbcComplete(it) => format!(
+            "meteora dbc curve of {} completed, quote reserve {}, tx: {}",
+            it.mint, it.quote_reserve, it.txid
+        ),
+        DexEvent::MigrationCompleted(it) => format!(
+            "{} migrated to pumpamm pool {} in {} seconds, tx: {}",
+            it.mint, it.pool, it.secs_to_migrate, it.txid
+        ),
+        DexEvent::LiquidityChange(it) => format!(
+            "{} {} {} sol and {} tokens of {} {} {} pool {}, tx: {}",
+            it.provider,
+            if it.is_add { "added" } else { "removed" },
+            it.sol_amt as f64 / 1_000_000_000.0,
+            it.token_amt,
+            it.mint,
+            if it.is_add { "to" } else { "from" },
+            it.dex,
+            it.pool,
+            it.txid
+        ),
+        DexEvent::LiquidityLocked(it) => format!(
+            "{}% of the lp tokens of {} pool {} of {} locked, {}% burned",
+            it.pct_locked, it.dex, it.pool, it.mint, it.pct_burned
+        ),
+        DexEvent::PoolDrained(it) => format!(
+            "{} pool {} of {} drained to {} SOL from {} SOL, tx: {}",
+            it.dex,
+            it.pool,
+            it.mint,
+            it.pool_sol_amt as f64 / 1_000_000_000.0,
+            it.peak_sol_amt as f64 / 1_000_000_000.0,
+            it.txid
+        ),
+        DexEvent::FailedSwapAttempt(it) => format!(
+            "{} failed to {} {} of {} on {}: {}, tx: {}",
+            it.trader,
+            if it.is_buy { "buy" } else { "sell" },
+            it.token_amt,
+            it.mint,
+            it.dex,
+            it.err,
+            it.txid
+        ),
+        DexEvent::MintInactive(it) => {
+            format!("{} has no trade since {}", it.mint, it.last_trade_ts)
+        }
+        DexEvent::PoolStateUpdated(it) => format!(
+            "{} pool {} of {} updated at slot {}: {:?}",
+            it.dex, it.pool, it.mint, it.slot, it.state
+        ),
+        DexEvent::BinPriceUpdate(it) => format!(
+            "dlmm pool {} of {} moved to bin {}, price {} sol",
+            it.lb_pair, it.mint, it.active_id, it.price_sol
+        ),
+        DexEvent::PressureUpdate(it) => format!(
+            "{} bought {} sol, sold {} sol in {} seconds, net {} sol",
+            it.mint, it.buy_sol, it.sell_sol, it.window, it.net
+        ),
+        DexEvent::FollowedWalletTrade(it) => format!(
+            "followed {} {} {} of {} on {}, {} trades net {} sol in 24h, tx: {}",
+            it.trade.trader,
+            if it.trade.is_buy { "bought" } else { "sold" },
+            it.trade.quote_ui_amt(),
+            it.trade.mint,
+            it.trade.dex,
+            it.stats.trades,
+            it.stats.net_sol,
+            it.trade.txid
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use serde_json::json;
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+    use crate::{
+        cache::{TradeRecord, Watchlist},
+        common::{Dex, WSOL_MINT},
+    };
+
+    #[test]
+    fn test_eval_rules() {
+        let rules: Vec<EventRule> = serde_json::from_value(json!([
+            {
+                "name": "whale",
+                "conditions": [
+                    {"field": "kind", "op": "eq", "value": "Trade"},
+                    {"field": "sol_amt", "op": "gte", "value": 100_000_000_000u64}
+                ],
+                "action": {"type": "tag", "tag": "whale"}
+            },
+            {
+                "name": "no raydium",
+                "conditions": [{"field": "dex", "op": "in", "value": ["RaydiumAmm"]}],
+                "action": {"type": "drop"}
+            },
+            {
+                "name": "copy",
+                "action": {"type": "route", "endpoint": "http://127.0.0.1:9999"}
+            },
+            {
+                "name": "followed",
+                "conditions": [{"field": "trader", "op": "in", "value": "watchlist:follow"}],
+                "action": {"type": "tag", "tag": "followed"}
+            }
+        ]))
+        .unwrap();
+
+        let mut trade = TradeRecord {
+            blk_ts: DateTime::from_timestamp(1, 0).unwrap(),
+            slot: 1,
+            txid: "tx".to_string(),
+            idx: 0,
+            mint: Pubkey::new_unique(),
+            decimals: 6,
+            trader: Pubkey::new_unique(),
+            dex: Dex::Pumpfun,
+            pool: Pubkey::new_unique(),
+            pool_sol_amt: 0,
+            pool_token_amt: 0,
+            is_buy: true,
+            sol_amt: 200_000_000_000,
+            token_amt: 1,
+            trader_sol_delta: -1,
+            trader_token_delta: 1,
+            price_sol: 1.0,
+            price_sol_decimal: "1".to_string(),
+            quote_mint: WSOL_MINT,
+            price_quote: 1.0,
+            quote_amt: 200_000_000_000,
+            pool_quote_amt: 0,
+            price_usd: None,
+            sol_price_usd_at_trade: None,
+            price_outlier: false,
+            source: None,
+            avg_prices: vec![],
+            cluster_id: None,
+            pool_drained: false,
+            pool_age_secs: None,
+            pool_trade_seq: 0,
+        };
+        let watchlists = Watchlists::default();
+        let outcome = eval_rules(&rules, &DexEvent::Trade(trade.clone()), &watchlists).unwrap();
+        assert!(!outcome.drop);
+        assert_eq!(outcome.tags, vec!["whale".to_string()]);
+
+        watchlists.set(&Watchlist {
+            name: "follow".to_string(),
+            mints: vec![],
+            wallets: vec![trade.trader],
+            updated_at: DateTime::from_timestamp(1, 0).unwrap(),
+        });
+        let outcome = eval_rules(&rules, &DexEvent::Trade(trade.clone()), &watchlists).unwrap();
+        assert_eq!(
+            outcome.tags,
+            vec!["whale".to_string(), "followed".to_string()]
+        );
+        assert_eq!(outcome.routes, vec!["http://127.0.0.1:9999".to_string()]);
+
+        trade.dex = Dex::RaydiumAmm;
+        trade.sol_amt = 1;
+        let outcome = eval_rules(&rules, &DexEvent::Trade(trade), &watchlists).unwrap();
+        assert!(outcome.drop);
+        assert!(outcome.tags.is_empty());
+        assert!(outcome.routes.is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+//! End to end test of the ingest endpoint, the processor and the webhook worker.
+//!
+//! Needs a redis whose database is flushed by the test, e.g.
+//! `docker run --rm -p 6379:6379 redis` and `TEST_REDIS_URL=redis://127.0.0.1:6379/15`,
+//! the test is ignored unless run with `--ignored`.
+
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use axum::{Router, extract::State, routing::post};
+use serde_json::Value;
+use sol_dex_data_hub::{
+    cache::DexEventKind,
+    qn_req_processor::{self, PipelineStatus, ProcessorOptions},
+    redis_monitor::RedisHealth,
+    rpc_provider::MockRpcProvider,
+    watchlist::Watchlists,
+    web::{self, ApiKeys, UsageMeter, WebAppContext, WsHub},
+    webhook::{DexEvtWebhook, EventPlugins, FieldNames, WebhookFormat},
+};
+use tokio::{net::TcpListener, sync::mpsc};
+
+const QN_PUMPFUN_CREATE: &str = include_str!("fixtures/qn_pumpfun_create.json");
+
+async fn serve(app: Router) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+    });
+    addr
+}
+
+async fn receive(State(sender): State<mpsc::UnboundedSender<Value>>, body: String) {
+    sender.send(serde_json::from_str(&body).unwrap()).unwrap();
+}
+
+#[tokio::test]
+#[ignore = "needs redis, set TEST_REDIS_URL"]
+async fn test_qn_request_to_webhook() {
+    let redis_url = std::env::var("TEST_REDIS_URL").expect("TEST_REDIS_URL of a redis to flush");
+    let redis_client = Arc::new(redis::Client::open(redis_url).unwrap());
+    let mut conn = redis_client
+        .get_multiplexed_async_connection()
+        .await
+        .unwrap();
+    let _: () = redis::cmd("flushdb").query_async(&mut conn).await.unwrap();
+
+    let context = WebAppContext {
+        redis_client: redis_client.clone(),
+        sol_rpc_client: Arc::new(MockRpcProvider::default()),
+        ws_hub: Arc::new(WsHub::default()),
+        redis_health: Arc::new(RedisHealth::default()),
+        http_client: reqwest::Client::new(),
+        ipfs_gateway: "http://127.0.0.1:1".into(),
+        api_keys: Arc::new(ApiKeys::new(None)),
+        usage_meter: Arc::new(UsageMeter::default()),
+        disabled_dexes: Arc::new([]),
+        pool_state_tracked: false,
+        watchlists: Arc::new(Watchlists::default()),
+        avg_price_windows: Arc::new([]),
+        failed_swaps: false,
+    };
+    let app_addr = serve(web::router(context)).await;
+
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let receiver_app = Router::new()
+        .route("/events", post(receive))
+        .with_state(sender);
+    let receiver_addr = serve(receiver_app).await;
+
+    let processor_redis = redis_client.clone();
+    tokio::spawn(async move {
+        let options = ProcessorOptions::default();
+        qn_req_processor::start(
+            processor_redis,
+            &options,
+            Arc::new(PipelineStatus::default()),
+        )
+        .await
+    });
+    let webhook = DexEvtWebhook {
+        redis_client: redis_client.clone(),
+        http_client: Arc::new(reqwest::Client::new()),
+        endpoint: format!("http://{receiver_addr}/events"),
+        format: WebhookFormat::Envelope,
+        fields: FieldNames::default(),
+        rules: Arc::new(vec![]),
+        plugins: Arc::new(EventPlugins::default()),
+        kind: DexEventKind::PoolCreated,
+        ws_hub: None,
+        watchlists: Arc::new(Watchlists::default()),
+        creator_funders: false,
+        consumer: None,
+    };
+    tokio::spawn(async move { webhook.start().await });
+
+    let http_client = reqwest::Client::new();
+    let ingest_url = format!("http://{app_addr}/sol_dex_stream");
+    for duplicate in [false, true] {
+        let ack: Value = http_client
+            .post(&ingest_url)
+            .body(QN_PUMPFUN_CREATE)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(ack["duplicate"], duplicate);
+        assert_eq!(ack["accepted_txs"], if duplicate { 0 } else { 1 });
+    }
+
+    let envelope = tokio::time::timeout(Duration::from_secs(10), receiver.recv())
+        .await
+        .expect("no webhook request")
+        .unwrap();
+    assert_eq!(envelope["version"], 1);
+    assert_eq!(envelope["min_slot"], 250_000_000);
+    assert_eq!(envelope["max_slot"], 250_000_000);
+    assert_eq!(envelope["counts"], serde_json::json!({"PoolCreated": 1}));
+
+    let events = envelope["events"].as_array().unwrap();
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event["kind"], "PoolCreated");
+    assert_eq!(event["txid"], "fixture-pumpfun-create");
+    assert_eq!(event["idx"], 2);
+    assert_eq!(event["dex"], "Pumpfun");
+    assert_eq!(
+        event["mint_a"],
+        "4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi"
+    );
+    assert_eq!(event["addr"], "8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR");
+    assert_eq!(
+        event["creator"],
+        "CktRuQ2mttgRGkXJtyksdKHjUdc2C4TgDzyB98oEzy8"
+    );
+    assert_eq!(event["source"]["stream_id"], "fixture-stream");
+
+    // the created pool is archived
+    let archived: bool = redis::cmd("EXISTS")
+        .arg("str:pool_archive:8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR")
+        .query_async(&mut conn)
+        .await
+        .unwrap();
+    assert!(archived);
+
+    // the duplicate request wasn't queued
+    let next = tokio::time::timeout(Duration::from_secs(2), receiver.recv()).await;
+    assert!(next.is_err(), "unexpected webhook request: {next:?}");
+}